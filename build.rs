@@ -46,6 +46,7 @@ fn compile_shader(entry: DirEntry) {
     {
         "vert" => Ok(shaderc::ShaderKind::Vertex),
         "frag" => Ok(shaderc::ShaderKind::Fragment),
+        "comp" => Ok(shaderc::ShaderKind::Compute),
         _ => Err("Invalid extension"),
     }
     .expect("Failed to parse shader type");
@@ -96,7 +97,20 @@ fn compile_shaders_in_dir(parent_dir: &Path) {
 }
 
 fn main() {
-    let shader_dirs = ["src/egui_integration/shaders"];
+    let shader_dirs = [
+        "src/auto_exposure/shaders",
+        "src/components/skybox/shaders",
+        "src/cubemap/shaders",
+        "src/debug_draw/shaders",
+        "src/egui_integration/shaders",
+        "src/gpu_culling/shaders",
+        "src/ibl/shaders",
+        "src/picking/shaders",
+        "src/point_cloud/shaders",
+        "src/sdf_text/shaders",
+        "src/shadows/shaders",
+        "src/tone_mapping/shaders",
+    ];
 
     for dir in shader_dirs {
         println!("cargo:rerun-if-changed={}/src", dir);
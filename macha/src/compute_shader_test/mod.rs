@@ -11,6 +11,7 @@ use morrigu::{
     },
     compute_shader::ComputeShader,
     descriptor_resources::DescriptorResources,
+    frame_stats::FrameStats,
     math_types::{EulerRot, Quat, Vec2, Vec3},
     pipeline_barrier::PipelineBarrier,
     shader::Shader,
@@ -44,7 +45,7 @@ impl BuildableApplicationState<()> for CSTState {
             morrigu::components::camera::Projection::Perspective(PerspectiveData {
                 horizontal_fov: f32::to_radians(50.0),
                 near_plane: 0.001,
-                far_plane: 1000.0,
+                far_plane: Some(1000.0),
             }),
             &Vec2::new(1280.0, 720.0),
         );
@@ -86,12 +87,11 @@ impl BuildableApplicationState<()> for CSTState {
         let input_mesh_rendering_ref = MeshRendering::new(
             &mesh_ref,
             &material_ref,
-            DescriptorResources {
-                uniform_buffers: [mesh_rendering::default_ubo_bindings(context.renderer).unwrap()]
-                    .into(),
-                sampled_images: [(1, input_texture.clone())].into(),
-                ..Default::default()
-            },
+            mesh_rendering::default_descriptor_resources_with_textures(
+                [(1, input_texture.clone())],
+                context.renderer,
+            )
+            .expect("Failed to create mesh rendering resources"),
             context.renderer,
         )
         .expect("Failed to create mesh rendering");
@@ -99,12 +99,11 @@ impl BuildableApplicationState<()> for CSTState {
         let output_mesh_rendering_ref = MeshRendering::new(
             &mesh_ref,
             &material_ref,
-            DescriptorResources {
-                uniform_buffers: [mesh_rendering::default_ubo_bindings(context.renderer).unwrap()]
-                    .into(),
-                sampled_images: [(1, output_texture.clone())].into(),
-                ..Default::default()
-            },
+            mesh_rendering::default_descriptor_resources_with_textures(
+                [(1, output_texture.clone())],
+                context.renderer,
+            )
+            .expect("Failed to create mesh rendering resources"),
             context.renderer,
         )
         .expect("Failed to create mesh rendering");
@@ -170,42 +169,18 @@ impl ApplicationState for CSTState {
 
         compute_shader
             .lock()
-            .run(
-                (width / 16, height / 16, 1),
+            .dispatch_for_extent(
+                width,
+                height,
+                1,
+                // The transitions to SHADER_READ_ONLY_OPTIMAL are batched by the mesh renderer
                 PipelineBarrier {
                     src_stage_mask: vk::PipelineStageFlags::COMPUTE_SHADER,
                     dst_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
-                    dependency_flags: vk::DependencyFlags::empty(),
-                    memory_barriers: vec![],
-                    buffer_memory_barriers: vec![],
-                    image_memory_barriers: vec![
-                        vk::ImageMemoryBarrier::default()
-                            .old_layout(vk::ImageLayout::GENERAL)
-                            .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-                            .image(self.input_texture.lock().image_ref.lock().handle)
-                            .subresource_range(vk::ImageSubresourceRange {
-                                aspect_mask: vk::ImageAspectFlags::COLOR,
-                                base_mip_level: 0,
-                                level_count: 1,
-                                base_array_layer: 0,
-                                layer_count: 1,
-                            })
-                            .src_access_mask(vk::AccessFlags::SHADER_WRITE)
-                            .dst_access_mask(vk::AccessFlags::SHADER_READ),
-                        vk::ImageMemoryBarrier::default()
-                            .old_layout(vk::ImageLayout::GENERAL)
-                            .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-                            .image(self.output_texture.lock().image_ref.lock().handle)
-                            .subresource_range(vk::ImageSubresourceRange {
-                                aspect_mask: vk::ImageAspectFlags::COLOR,
-                                base_mip_level: 0,
-                                level_count: 1,
-                                base_array_layer: 0,
-                                layer_count: 1,
-                            })
-                            .src_access_mask(vk::AccessFlags::SHADER_WRITE)
-                            .dst_access_mask(vk::AccessFlags::SHADER_READ),
-                    ],
+                    memory_barriers: vec![vk::MemoryBarrier::default()
+                        .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+                        .dst_access_mask(vk::AccessFlags::SHADER_READ)],
+                    ..Default::default()
                 },
                 context.renderer,
             )
@@ -250,8 +225,12 @@ impl ApplicationState for CSTState {
         self.input_texture.lock().destroy(context.renderer);
     }
 
-    fn on_update_egui(&mut self, dt: std::time::Duration, context: &mut EguiUpdateContext) {
-        draw_debug_utils(context.egui_context, dt, &mut self.desired_state);
+    fn on_update_egui(&mut self, _dt: std::time::Duration, context: &mut EguiUpdateContext) {
+        draw_debug_utils(
+            context.egui_context,
+            context.ecs_manager.world.resource::<FrameStats>(),
+            &mut self.desired_state,
+        );
     }
 
     fn flow<'flow>(
@@ -263,7 +242,7 @@ impl ApplicationState for CSTState {
                 crate::editor::MachaState::build(context, ()),
             )),
             SwitchableStates::GLTFLoader => morrigu::application::StateFlow::SwitchState(Box::new(
-                crate::gltf_loader::GLTFViewerState::build(context, ()),
+                crate::gltf_loader::GLTFLoadingState::build(context, ()),
             )),
             SwitchableStates::PBRTest => morrigu::application::StateFlow::SwitchState(Box::new(
                 crate::pbr_test::PBRState::build(context, ()),
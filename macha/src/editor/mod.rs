@@ -4,7 +4,6 @@ mod systems;
 
 use crate::utils::{startup_state::SwitchableStates, ui::draw_debug_utils};
 
-use super::utils::camera::MachaCamera;
 use bevy_ecs::prelude::Entity;
 use components::{
     macha_options::{MachaEntityOptions, MachaGlobalOptions},
@@ -17,19 +16,21 @@ use morrigu::{
         event::WindowEvent, ApplicationState, BuildableApplicationState, EguiUpdateContext,
         StateContext,
     },
+    ash::vk,
     bevy_ecs,
     components::{
-        camera::{Camera, PerspectiveData},
+        camera::{Camera, ControllerModifier, OrbitController, PerspectiveData},
         mesh_rendering,
         resource_wrapper::ResourceWrapper,
         transform::Transform,
     },
     descriptor_resources::DescriptorResources,
     egui,
+    frame_stats::FrameStats,
     math_types::Vec2,
     shader::Shader,
     systems::mesh_renderer,
-    texture::{Texture, TextureFormat},
+    texture::{Texture, TextureColorSpace, TextureFormat},
     utils::ThreadSafeRef,
     winit,
 };
@@ -47,7 +48,7 @@ type Mesh = morrigu::mesh::Mesh<Vertex>;
 type MeshRendering = mesh_rendering::MeshRendering<Vertex>;
 
 pub struct MachaState {
-    camera: MachaCamera,
+    camera: OrbitController,
 
     shader_ref: ThreadSafeRef<Shader>,
     material_ref: ThreadSafeRef<Material>,
@@ -72,13 +73,15 @@ impl BuildableApplicationState<()> for MachaState {
             morrigu::components::camera::Projection::Perspective(PerspectiveData {
                 horizontal_fov: f32::to_radians(50.0),
                 near_plane: 0.001,
-                far_plane: 1000.0,
+                far_plane: Some(1000.0),
             }),
             &Vec2::new(1280.0, 720.0),
         );
-        let mut camera = MachaCamera::new(camera);
+        let mut camera = OrbitController::new(camera);
         camera.move_speed = 4.0;
         camera.set_distance(7.0);
+        // https://github.com/urholaukkarinen/egui-gizmo/issues/29
+        camera.suspend_modifier = Some(ControllerModifier::Alt);
 
         let shader_ref = Shader::from_spirv_u8(
             include_bytes!("shaders/gen/test/test.vert"),
@@ -104,14 +107,17 @@ impl BuildableApplicationState<()> for MachaState {
                 context.renderer,
             )
             .expect("Failed to load texture");
+        // Sampled near their edges, which must not bleed into each other
         let flowmap_ref = Texture::builder()
-            .with_format(TextureFormat::RGBA8_UNORM)
+            .with_color_space(TextureColorSpace::Linear)
+            .with_address_mode(vk::SamplerAddressMode::CLAMP_TO_EDGE)
             .build_from_path(
                 Path::new("assets/textures/jupiter_flowmap.png"),
                 context.renderer,
             )
             .expect("Failed to load flowmap texture");
         let gradient_ref = Texture::builder()
+            .with_address_mode(vk::SamplerAddressMode::CLAMP_TO_EDGE)
             .build_from_path(
                 Path::new("assets/textures/jupiter_gradient.png"),
                 context.renderer,
@@ -123,18 +129,15 @@ impl BuildableApplicationState<()> for MachaState {
             &mesh_ref,
             &material_ref,
             DescriptorResources {
-                uniform_buffers: [
-                    mesh_rendering::default_ubo_bindings(context.renderer).unwrap(),
-                    (
-                        4,
-                        ThreadSafeRef::new(
-                            AllocatedBuffer::builder(shader_options_size)
-                                .with_name("Shader options")
-                                .build_with_pod(shader_options, context.renderer)
-                                .unwrap(),
-                        ),
+                uniform_buffers: [(
+                    4,
+                    ThreadSafeRef::new(
+                        AllocatedBuffer::builder(shader_options_size)
+                            .with_name("Shader options")
+                            .build_with_pod(shader_options, context.renderer)
+                            .unwrap(),
                     ),
-                ]
+                )]
                 .into(),
                 sampled_images: [
                     (1, texture_ref.clone()),
@@ -265,23 +268,24 @@ impl ApplicationState for MachaState {
     }
 
     fn on_update(&mut self, dt: std::time::Duration, context: &mut StateContext) {
-        // https://github.com/urholaukkarinen/egui-gizmo/issues/29
-        if !context.window_input_state.held_alt() {
-            self.camera.on_update(dt, context.window_input_state);
-        }
+        self.camera.on_update(dt, context.window_input_state);
 
         context
             .ecs_manager
             .world
-            .insert_resource(self.camera.mrg_camera);
+            .insert_resource(self.camera.camera);
         context
             .ecs_manager
             .world
             .insert_resource(ResourceWrapper::new(context.window_input_state.clone()));
     }
 
-    fn on_update_egui(&mut self, dt: std::time::Duration, context: &mut EguiUpdateContext) {
-        draw_debug_utils(context.egui_context, dt, &mut self.desired_state);
+    fn on_update_egui(&mut self, _dt: std::time::Duration, context: &mut EguiUpdateContext) {
+        draw_debug_utils(
+            context.egui_context,
+            context.ecs_manager.world.resource::<FrameStats>(),
+            &mut self.desired_state,
+        );
 
         egui::Window::new("Shader uniforms").show(context.egui_context, |ui| {
             let image = egui::ImageSource::Texture(
@@ -362,7 +366,7 @@ impl ApplicationState for MachaState {
     ) -> morrigu::application::StateFlow<'flow> {
         match self.desired_state {
             SwitchableStates::GLTFLoader => morrigu::application::StateFlow::SwitchState(Box::new(
-                crate::gltf_loader::GLTFViewerState::build(context, ()),
+                crate::gltf_loader::GLTFLoadingState::build(context, ()),
             )),
             SwitchableStates::CSTest => morrigu::application::StateFlow::SwitchState(Box::new(
                 crate::compute_shader_test::CSTState::build(context, ()),
@@ -1,9 +1,9 @@
 use morrigu::bevy_ecs::prelude::{Query, Res};
 use morrigu::bevy_ecs::system::ResMut;
-use morrigu::winit_input_helper::WinitInputHelper;
 use morrigu::{
     components::{camera::Camera, resource_wrapper::ResourceWrapper, transform::Transform},
     egui,
+    input::InputState,
     math_types::Mat4,
 };
 
@@ -22,7 +22,7 @@ pub fn draw_gizmo(
     camera: Res<Camera>,
     mut macha_options: ResMut<MachaGlobalOptions>,
     egui_context: Res<ResourceWrapper<egui::Context>>,
-    window_input: Res<ResourceWrapper<WinitInputHelper>>,
+    window_input: Res<ResourceWrapper<InputState>>,
 ) {
     let window_input = &window_input.data;
 
@@ -2,6 +2,7 @@ use anyhow::Context;
 use gltf::buffer::Data;
 use morrigu::{
     allocated_types::AllocatedBuffer,
+    async_load::LoadProgress,
     components::{mesh_rendering::default_descriptor_resources, transform::Transform},
     descriptor_resources::DescriptorResources,
     math_types::{Mat4, Quat, Vec3, Vec4},
@@ -58,6 +59,11 @@ pub struct MapPresenceInfo {
 unsafe impl bytemuck::Zeroable for MapPresenceInfo {}
 unsafe impl bytemuck::Pod for MapPresenceInfo {}
 
+// Rough share of the total load time taken by each step, used to report the load's progress
+const IMPORT_PROGRESS: f32 = 0.3;
+const TEXTURES_PROGRESS: f32 = 0.7;
+const MATERIALS_PROGRESS: f32 = 0.8;
+
 #[derive(Debug, Default)]
 pub struct LoadData {
     pub meshes: Vec<ThreadSafeRef<Mesh>>,
@@ -88,7 +94,7 @@ pub fn load_node(
     materials: &[ThreadSafeRef<Material>],
     buffers: &[Data],
     default_material: &ThreadSafeRef<Material>,
-    renderer: &mut Renderer,
+    renderer_ref: &ThreadSafeRef<Renderer>,
 ) -> anyhow::Result<LoadData> {
     let mut load_data = LoadData::default();
 
@@ -118,13 +124,14 @@ pub fn load_node(
                 })
                 .collect::<Vec<_>>();
 
-            let vertex_buffer = upload_vertex_buffer(&vertices, renderer)?;
+            let mut renderer = renderer_ref.lock();
+            let vertex_buffer = upload_vertex_buffer(&vertices, &mut renderer)?;
 
             let (index_buffer, indices) = match reader.read_indices() {
                 Some(indices) => {
                     let indices = indices.into_u32().collect::<Vec<_>>();
                     (
-                        Some(upload_index_buffer(&indices, renderer)?),
+                        Some(upload_index_buffer(&indices, &mut renderer)?),
                         Some(indices),
                     )
                 }
@@ -146,8 +153,8 @@ pub fn load_node(
             load_data.mesh_renderings.push(MeshRendering::new(
                 &new_mesh_ref,
                 &material_ref,
-                default_descriptor_resources(renderer)?,
-                renderer,
+                default_descriptor_resources(&mut renderer)?,
+                &mut renderer,
             )?);
 
             load_data.transforms.push(current_transform.clone());
@@ -161,7 +168,7 @@ pub fn load_node(
             materials,
             buffers,
             default_material,
-            renderer,
+            renderer_ref,
         )?;
         load_data.meshes.append(&mut child_data.meshes);
         load_data
@@ -173,6 +180,8 @@ pub fn load_node(
     Ok(load_data)
 }
 
+/// Loads the scene at `path`, only locking the renderer while uploading each resource so that
+/// this can run on a worker thread while the application keeps rendering.
 #[profiling::function]
 pub fn load_gltf(
     path: &Path,
@@ -180,20 +189,35 @@ pub fn load_gltf(
     pbr_shader: ThreadSafeRef<Shader>,
     default_texture: ThreadSafeRef<Texture>,
     default_material: ThreadSafeRef<Material>,
-    renderer: &mut Renderer,
+    renderer_ref: &ThreadSafeRef<Renderer>,
+    progress: &LoadProgress,
 ) -> anyhow::Result<Scene> {
     let (document, buffers, images) = gltf::import(path)?;
+    progress.set(IMPORT_PROGRESS);
 
+    let image_count = images.len().max(1) as f32;
     let images = images
         .into_iter()
-        .map(|image| {
+        .enumerate()
+        .map(|(index, image)| {
             let image = image
                 .convert_format(gltf::image::Format::R8G8B8A8)
                 .context("Failed to convert GLTF image to RGBA8")?;
-            Texture::builder()
+            let texture = Texture::builder()
                 .with_format(morrigu::texture::TextureFormat::RGBA8_UNORM)
-                .build_from_data(&image.pixels, image.width, image.height, renderer)
-                .context("Failed to create texture form GTLF data")
+                .build_from_data(
+                    &image.pixels,
+                    image.width,
+                    image.height,
+                    &mut renderer_ref.lock(),
+                )
+                .context("Failed to create texture form GTLF data")?;
+            progress.set(
+                IMPORT_PROGRESS
+                    + (TEXTURES_PROGRESS - IMPORT_PROGRESS) * (index + 1) as f32 / image_count,
+            );
+
+            Ok(texture)
         })
         .collect::<anyhow::Result<Vec<_>, _>>()
         .context("Failed to build textures")?;
@@ -223,6 +247,8 @@ pub fn load_gltf(
 
             log::trace!("Material texture indices:");
 
+            let mut renderer = renderer_ref.lock();
+            let renderer = &mut *renderer;
             Material::builder()
                 .build::<Vertex>(
                     &pbr_shader,
@@ -307,14 +333,16 @@ pub fn load_gltf(
         })
         .collect::<anyhow::Result<Vec<_>, _>>()
         .context("Failed to build materials")?;
+    progress.set(MATERIALS_PROGRESS);
 
     let scene = match document.default_scene() {
         Some(default_scene) => default_scene,
         None => document.scenes().next().context("No scene in gltf file")?,
     };
 
+    let root_node_count = scene.nodes().count().max(1) as f32;
     let mut load_data = LoadData::default();
-    for root_node in scene.nodes() {
+    for (index, root_node) in scene.nodes().enumerate() {
         let initial_transform = transform.clone() * convert_transform(root_node.transform());
         let mut current_load_data = load_node(
             &root_node,
@@ -322,8 +350,11 @@ pub fn load_gltf(
             &materials,
             &buffers,
             &default_material,
-            renderer,
+            renderer_ref,
         )?;
+        progress.set(
+            MATERIALS_PROGRESS + (1.0 - MATERIALS_PROGRESS) * (index + 1) as f32 / root_node_count,
+        );
 
         load_data.meshes.append(&mut current_load_data.meshes);
         load_data
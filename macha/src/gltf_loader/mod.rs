@@ -1,63 +1,44 @@
 mod loader;
 mod scene;
 
-use std::{iter::zip, path::Path};
+use std::{iter::zip, path::Path, task::Poll};
 
 use morrigu::{
     application::{
         event::WindowEvent, ApplicationState, BuildableApplicationState, EguiUpdateContext,
+        StateFlow,
     },
-    ash::vk,
+    async_load::AsyncLoad,
     bevy_ecs,
     components::{
-        camera::{Camera, PerspectiveData},
-        mesh_rendering::default_descriptor_resources,
+        camera::{Camera, OrbitController, PerspectiveData},
+        skybox::Skybox,
         transform::Transform,
     },
     cubemap::Cubemap,
     descriptor_resources::DescriptorResources,
+    egui,
+    frame_stats::FrameStats,
     math_types::{Quat, Vec2, Vec3, Vec4},
     shader::Shader,
-    systems::mesh_renderer,
-    utils::ThreadSafeRef,
+    systems::{mesh_renderer, skybox},
 };
 
-use crate::utils::{camera::MachaCamera, startup_state::SwitchableStates, ui::draw_debug_utils};
+use crate::utils::{startup_state::SwitchableStates, ui::draw_debug_utils};
 
 use self::{
     loader::LightData,
     scene::{Material, Scene, Vertex},
 };
 
-pub struct GLTFViewerState {
-    light_data: LightData,
-    camera: MachaCamera,
-    scene: Scene,
-    skybox_entity_ref: bevy_ecs::entity::Entity,
-    skybox: ThreadSafeRef<SkyboxMeshRendering>,
-
-    desired_state: SwitchableStates,
+/// Loads the scene on a worker thread while displaying its progress, then switches to the viewer.
+pub struct GLTFLoadingState {
+    scene_load: AsyncLoad<anyhow::Result<Scene>>,
 }
 
-type SkyboxVertex = morrigu::vertices::simple::SimpleVertex;
-type SkyboxMaterial = morrigu::material::Material<SkyboxVertex>;
-type SkyboxMeshRendering = morrigu::components::mesh_rendering::MeshRendering<SkyboxVertex>;
-
 #[profiling::all_functions]
-impl BuildableApplicationState<()> for GLTFViewerState {
+impl BuildableApplicationState<()> for GLTFLoadingState {
     fn build(context: &mut morrigu::application::StateContext, _: ()) -> Self {
-        let camera = Camera::builder().build(
-            morrigu::components::camera::Projection::Perspective(PerspectiveData {
-                horizontal_fov: f32::to_radians(50.0),
-                near_plane: 0.001,
-                far_plane: 1000.0,
-            }),
-            &Vec2::new(1280.0, 720.0),
-        );
-
-        let mut camera = MachaCamera::new(camera);
-        camera.set_distance(0.0);
-
         let pbr_shader = Shader::from_spirv_u8(
             include_bytes!("shaders/gen/pbr/pbr.vert"),
             include_bytes!("shaders/gen/pbr/pbr.frag"),
@@ -79,6 +60,83 @@ impl BuildableApplicationState<()> for GLTFViewerState {
             )
             .expect("Failed to create default material");
 
+        let default_texture = context.renderer.default_texture();
+        let renderer_ref = context.ecs_manager.renderer_ref();
+        let scene_load = AsyncLoad::spawn(move |progress| {
+            loader::load_gltf(
+                Path::new("assets/scenes/sponza/Sponza.gltf"),
+                // Transform::default(),
+                Transform::from_trs(
+                    &Vec3::default(),
+                    &Quat::default(),
+                    &Vec3::new(10.0, 10.0, 10.0),
+                ),
+                pbr_shader,
+                default_texture,
+                default_material,
+                &renderer_ref,
+                progress,
+            )
+        });
+
+        Self { scene_load }
+    }
+}
+
+#[profiling::all_functions]
+impl ApplicationState for GLTFLoadingState {
+    fn on_update_egui(&mut self, _dt: std::time::Duration, context: &mut EguiUpdateContext) {
+        egui::CentralPanel::default().show(context.egui_context, |ui| {
+            ui.vertical_centered(|ui| {
+                ui.heading("Loading GLTF scene...");
+                ui.add(egui::ProgressBar::new(self.scene_load.progress()).show_percentage());
+            });
+        });
+    }
+
+    fn flow<'flow>(
+        &mut self,
+        context: &mut morrigu::application::StateContext,
+    ) -> StateFlow<'flow> {
+        match self.scene_load.poll() {
+            Poll::Pending => StateFlow::Continue,
+            Poll::Ready(scene) => {
+                let scene = scene
+                    .map_err(anyhow::Error::from)
+                    .and_then(|scene| scene)
+                    .expect("Failed to load GLTF scene");
+
+                StateFlow::SwitchState(Box::new(GLTFViewerState::new(context, scene)))
+            }
+        }
+    }
+}
+
+pub struct GLTFViewerState {
+    light_data: LightData,
+    camera: OrbitController,
+    scene: Scene,
+    skybox: Option<Skybox>,
+    skybox_entity_ref: bevy_ecs::entity::Entity,
+
+    desired_state: SwitchableStates,
+}
+
+#[profiling::all_functions]
+impl GLTFViewerState {
+    fn new(context: &mut morrigu::application::StateContext, scene: Scene) -> Self {
+        let camera = Camera::builder().build(
+            morrigu::components::camera::Projection::Perspective(PerspectiveData {
+                horizontal_fov: f32::to_radians(50.0),
+                near_plane: 0.001,
+                far_plane: Some(1000.0),
+            }),
+            &Vec2::new(1280.0, 720.0),
+        );
+
+        let mut camera = OrbitController::new(camera);
+        camera.set_distance(0.0);
+
         let skybox_cubemap = Cubemap::build_from_folder(
             "assets/textures/skybox",
             "jpg",
@@ -86,59 +144,15 @@ impl BuildableApplicationState<()> for GLTFViewerState {
             context.renderer,
         )
         .expect("Failed to build skybox cubemap texture");
-        let skybox_shader = Shader::from_spirv_u8(
-            include_bytes!("shaders/gen/cubemap/cubemap.vert"),
-            include_bytes!("shaders/gen/cubemap/cubemap.frag"),
-            &context.renderer.device,
-        )
-        .expect("Failed to create skybox shader");
-        let skybox_material: ThreadSafeRef<SkyboxMaterial> = Material::builder()
-            .z_write(false)
-            .cull_mode(vk::CullModeFlags::FRONT)
-            .build(
-                &skybox_shader,
-                DescriptorResources {
-                    cubemap_images: [(0, skybox_cubemap)].into(),
-                    ..Default::default()
-                },
-                context.renderer,
-            )
-            .expect("Failed to create skybox material");
-        let skybox_mesh = SkyboxVertex::load_model_from_path_obj(
-            Path::new("assets/meshes/cube.obj"),
-            context.renderer,
-        )
-        .expect("Failed to load cube obj");
-        let skybox = SkyboxMeshRendering::new(
-            &skybox_mesh,
-            &skybox_material,
-            default_descriptor_resources(context.renderer)
-                .expect("Failed to create default descriptor resources"),
-            context.renderer,
-        )
-        .expect("Failed to create skybox mesh rendering");
-
-        let scene = loader::load_gltf(
-            Path::new("assets/scenes/sponza/Sponza.gltf"),
-            // Transform::default(),
-            Transform::from_trs(
-                &Vec3::default(),
-                &Quat::default(),
-                &Vec3::new(10.0, 10.0, 10.0),
-            ),
-            pbr_shader,
-            context.renderer.default_texture(),
-            default_material,
-            context.renderer,
-        )
-        .expect("Failed to load GLTF scene");
+        let skybox =
+            Skybox::new(skybox_cubemap, context.renderer).expect("Failed to create skybox");
 
         let light_data = LightData {
             light_direction: Vec4::new(-1.0, -1.0, 0.0, 0.0).normalize(),
             light_color: Vec4::new(0.68, 0.68, 0.68, 1.0),
             ambient_light_color: Vec3::new(0.3, 0.3, 0.3),
             ambient_light_intensity: 0.2,
-            camera_position: *camera.mrg_camera.position(),
+            camera_position: *camera.camera.position(),
             __padding: 0.0,
         };
 
@@ -153,8 +167,8 @@ impl BuildableApplicationState<()> for GLTFViewerState {
             light_data,
             camera,
             scene,
+            skybox: Some(skybox),
             skybox_entity_ref: bevy_ecs::entity::Entity::PLACEHOLDER,
-            skybox,
 
             desired_state: SwitchableStates::GLTFLoader,
         }
@@ -166,7 +180,7 @@ impl ApplicationState for GLTFViewerState {
     fn on_attach(&mut self, context: &mut morrigu::application::StateContext) {
         context.ecs_manager.redefine_systems_schedule(|schedule| {
             schedule.add_systems(mesh_renderer::render_meshes::<Vertex>);
-            schedule.add_systems(mesh_renderer::render_meshes::<SkyboxVertex>);
+            schedule.add_systems(skybox::render_skybox);
         });
 
         for (transform, mesh_rendering_ref) in
@@ -180,44 +194,19 @@ impl ApplicationState for GLTFViewerState {
 
         let res = context.renderer.window_resolution();
         self.camera.on_resize(res.0, res.1);
-        self.skybox_entity_ref = context
-            .ecs_manager
-            .world
-            .spawn((
-                Transform::from_trs(
-                    self.camera.mrg_camera.position(),
-                    &Quat::default(),
-                    &Vec3::new(1.0, 1.0, 1.0),
-                ),
-                self.skybox.clone(),
-            ))
-            .id();
+        if let Some(skybox) = self.skybox.take() {
+            self.skybox_entity_ref = context.ecs_manager.world.spawn(skybox).id();
+        }
     }
 
     fn on_drop(&mut self, context: &mut morrigu::application::StateContext) {
-        let mut skybox = self.skybox.lock();
-        skybox.destroy(context.renderer);
-        skybox
-            .descriptor_resources
-            .uniform_buffers
-            .values()
-            .for_each(|buffer| {
-                buffer
-                    .lock()
-                    .destroy(&context.renderer.device, &mut context.renderer.allocator())
-            });
-        let mut skybox_material = skybox.material_ref.lock();
-        skybox_material.destroy(context.renderer);
-        skybox_material
-            .descriptor_resources
-            .cubemap_images
-            .values()
-            .for_each(|image| image.lock().destroy(context.renderer));
-        skybox_material
-            .shader_ref
-            .lock()
-            .destroy(&context.renderer.device);
-        skybox.mesh_ref.lock().destroy(context.renderer);
+        if let Some(mut skybox) = context
+            .ecs_manager
+            .world
+            .get_mut::<Skybox>(self.skybox_entity_ref)
+        {
+            skybox.destroy(context.renderer);
+        }
 
         self.scene.destroy(context.renderer);
     }
@@ -229,18 +218,9 @@ impl ApplicationState for GLTFViewerState {
     ) {
         self.camera.on_update(dt, context.window_input_state);
 
-        let cam_pos = self.camera.mrg_camera.position();
+        let cam_pos = self.camera.camera.position();
         self.light_data.camera_position = *cam_pos;
 
-        let mut entity_ref = context
-            .ecs_manager
-            .world
-            .get_entity_mut(self.skybox_entity_ref)
-            .expect("Failed to retrieve skybox entity");
-        if let Some(mut transform) = entity_ref.get_mut::<Transform>() {
-            transform.set_translation(cam_pos);
-        }
-
         for material in &self.scene.materials {
             material
                 .lock()
@@ -251,11 +231,15 @@ impl ApplicationState for GLTFViewerState {
         context
             .ecs_manager
             .world
-            .insert_resource(self.camera.mrg_camera);
+            .insert_resource(self.camera.camera);
     }
 
-    fn on_update_egui(&mut self, dt: std::time::Duration, context: &mut EguiUpdateContext) {
-        draw_debug_utils(context.egui_context, dt, &mut self.desired_state);
+    fn on_update_egui(&mut self, _dt: std::time::Duration, context: &mut EguiUpdateContext) {
+        draw_debug_utils(
+            context.egui_context,
+            context.ecs_manager.world.resource::<FrameStats>(),
+            &mut self.desired_state,
+        );
     }
 
     fn on_window_event(&mut self, event: WindowEvent, _context: &mut morrigu::application::StateContext) {
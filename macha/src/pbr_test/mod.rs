@@ -4,17 +4,18 @@ use morrigu::{
     allocated_types::AllocatedBuffer,
     application::{ApplicationState, BuildableApplicationState},
     bevy_ecs::entity::Entity,
-    components::transform::Transform,
+    components::{camera::OrbitController, transform::Transform},
     descriptor_resources::DescriptorResources,
     egui,
+    frame_stats::FrameStats,
     glam::vec3,
     math_types::{Vec2, Vec3, Vec4},
     shader::Shader,
-    texture::Texture,
+    texture::{Texture, TextureColorSpace},
     utils::ThreadSafeRef,
 };
 
-use crate::utils::{camera::MachaCamera, startup_state::SwitchableStates, ui::draw_debug_utils};
+use crate::utils::{startup_state::SwitchableStates, ui::draw_debug_utils};
 
 type Vertex = morrigu::vertices::textured::TexturedVertex;
 type Material = morrigu::material::Material<Vertex>;
@@ -32,7 +33,7 @@ unsafe impl bytemuck::Zeroable for LightData {}
 unsafe impl bytemuck::Pod for LightData {}
 
 pub struct PBRState {
-    camera: MachaCamera,
+    camera: OrbitController,
     camera_focus: Option<usize>,
 
     point_light_angle: f32,
@@ -91,14 +92,8 @@ impl BuildableApplicationState<()> for PBRState {
         let point_light_debug = MeshRendering::new(
             &mesh_ref,
             &flat_material_ref,
-            DescriptorResources {
-                uniform_buffers: [morrigu::components::mesh_rendering::default_ubo_bindings(
-                    context.renderer,
-                )
-                .unwrap()]
-                .into(),
-                ..Default::default()
-            },
+            morrigu::components::mesh_rendering::default_descriptor_resources(context.renderer)
+                .expect("Failed to create mesh rendering resources"),
             context.renderer,
         )
         .expect("Failed to create mesh rendering");
@@ -131,28 +126,28 @@ impl BuildableApplicationState<()> for PBRState {
             )
             .expect("Failed to load albedo texture");
         let normal = Texture::builder()
-            .with_format(morrigu::texture::TextureFormat::RGBA8_UNORM)
+            .with_color_space(TextureColorSpace::Linear)
             .build_from_path(
                 Path::new("assets/textures/rust_pbr/normal.png"),
                 context.renderer,
             )
             .expect("Failed to load normal texture");
         let metallic = Texture::builder()
-            .with_format(morrigu::texture::TextureFormat::RGBA8_UNORM)
+            .with_color_space(TextureColorSpace::Linear)
             .build_from_path(
                 Path::new("assets/textures/rust_pbr/metallic.png"),
                 context.renderer,
             )
             .expect("Failed to load metallic texture");
         let roughness = Texture::builder()
-            .with_format(morrigu::texture::TextureFormat::RGBA8_UNORM)
+            .with_color_space(TextureColorSpace::Linear)
             .build_from_path(
                 Path::new("assets/textures/rust_pbr/roughness.png"),
                 context.renderer,
             )
             .expect("Failed to load roughness texture");
         let ao = Texture::builder()
-            .with_format(morrigu::texture::TextureFormat::RGBA8_UNORM)
+            .with_color_space(TextureColorSpace::Linear)
             .build_from_data(
                 &[u8::MAX, u8::MAX, u8::MAX, u8::MAX],
                 1,
@@ -167,24 +162,17 @@ impl BuildableApplicationState<()> for PBRState {
                 let mesh_rendering_ref = MeshRendering::new(
                     &mesh_ref,
                     &pbr_material_ref,
-                    DescriptorResources {
-                        uniform_buffers: [
-                            morrigu::components::mesh_rendering::default_ubo_bindings(
-                                context.renderer,
-                            )
-                            .unwrap(),
-                        ]
-                        .into(),
-                        sampled_images: [
+                    morrigu::components::mesh_rendering::default_descriptor_resources_with_textures(
+                        [
                             (1, albedo.clone()),
                             (2, normal.clone()),
                             (3, metallic.clone()),
                             (4, roughness.clone()),
                             (5, ao.clone()),
-                        ]
-                        .into(),
-                        ..Default::default()
-                    },
+                        ],
+                        context.renderer,
+                    )
+                    .expect("Failed to create mesh rendering resources"),
                     context.renderer,
                 )
                 .expect("Failed to create mesh rendering");
@@ -199,14 +187,14 @@ impl BuildableApplicationState<()> for PBRState {
                 morrigu::components::camera::PerspectiveData {
                     horizontal_fov: (60.0_f32).to_radians(),
                     near_plane: 0.001,
-                    far_plane: 1000.0,
+                    far_plane: Some(1000.0),
                 },
             ),
             &Vec2::new(1280.0, 720.0),
         );
 
         Self {
-            camera: MachaCamera::new(camera),
+            camera: OrbitController::new(camera),
             camera_focus: None,
 
             point_light_angle: 0.0,
@@ -333,7 +321,7 @@ impl ApplicationState for PBRState {
         context
             .ecs_manager
             .world
-            .insert_resource(self.camera.mrg_camera);
+            .insert_resource(self.camera.camera);
 
         let light_pos = 10.0
             * Vec3::new(
@@ -342,7 +330,7 @@ impl ApplicationState for PBRState {
                 self.point_light_angle.to_radians().sin(),
             );
         let light_data = LightData {
-            camera_pos: (*self.camera.mrg_camera.position(), 0.0).into(),
+            camera_pos: (*self.camera.camera.position(), 0.0).into(),
             light_pos: (light_pos, 0.0).into(),
             light_color_intensity: (self.point_light_color, self.point_light_intensity).into(),
         };
@@ -364,10 +352,14 @@ impl ApplicationState for PBRState {
 
     fn on_update_egui(
         &mut self,
-        dt: std::time::Duration,
+        _dt: std::time::Duration,
         context: &mut morrigu::application::EguiUpdateContext,
     ) {
-        draw_debug_utils(context.egui_context, dt, &mut self.desired_state);
+        draw_debug_utils(
+            context.egui_context,
+            context.ecs_manager.world.resource::<FrameStats>(),
+            &mut self.desired_state,
+        );
 
         egui::Window::new("Light controls").show(context.egui_context, |ui| {
             ui.with_layout(egui::Layout::left_to_right(egui::Align::LEFT), |ui| {
@@ -453,7 +445,7 @@ impl ApplicationState for PBRState {
                 crate::editor::MachaState::build(context, ()),
             )),
             SwitchableStates::GLTFLoader => morrigu::application::StateFlow::SwitchState(Box::new(
-                crate::gltf_loader::GLTFViewerState::build(context, ()),
+                crate::gltf_loader::GLTFLoadingState::build(context, ()),
             )),
             SwitchableStates::CSTest => morrigu::application::StateFlow::SwitchState(Box::new(
                 crate::compute_shader_test::CSTState::build(context, ()),
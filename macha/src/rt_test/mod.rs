@@ -1,6 +1,10 @@
 use morrigu::{
     application::{ApplicationState, BuildableApplicationState, EguiUpdateContext},
-    components::ray_tracing::{mesh_rendering::MeshRendering, tlas::TLAS},
+    components::{
+        ray_tracing::{mesh_rendering::MeshRendering, tlas::TLAS},
+        transform::Transform,
+    },
+    frame_stats::FrameStats,
     utils::ThreadSafeRef,
     vertices::simple::SimpleVertex,
 };
@@ -35,8 +39,8 @@ impl BuildableApplicationState<()> for RayTracerState {
 
         let tlas = TLAS::new(
             &[
-                *monkey_mesh.lock().tlas_instance(),
-                *rock_mesh.lock().tlas_instance(),
+                monkey_mesh.lock().tlas_instance(&Transform::default()),
+                rock_mesh.lock().tlas_instance(&Transform::default()),
             ],
             context.renderer,
         )
@@ -71,8 +75,12 @@ impl ApplicationState for RayTracerState {
             .destroy(context.renderer);
     }
 
-    fn on_update_egui(&mut self, dt: std::time::Duration, context: &mut EguiUpdateContext) {
-        draw_debug_utils(context.egui_context, dt, &mut self.desired_state);
+    fn on_update_egui(&mut self, _dt: std::time::Duration, context: &mut EguiUpdateContext) {
+        draw_debug_utils(
+            context.egui_context,
+            context.ecs_manager.world.resource::<FrameStats>(),
+            &mut self.desired_state,
+        );
     }
 
     fn flow<'flow>(
@@ -84,7 +92,7 @@ impl ApplicationState for RayTracerState {
                 crate::editor::MachaState::build(context, ()),
             )),
             SwitchableStates::GLTFLoader => morrigu::application::StateFlow::SwitchState(Box::new(
-                crate::gltf_loader::GLTFViewerState::build(context, ()),
+                crate::gltf_loader::GLTFLoadingState::build(context, ()),
             )),
             SwitchableStates::CSTest => morrigu::application::StateFlow::SwitchState(Box::new(
                 crate::compute_shader_test::CSTState::build(context, ()),
@@ -1,3 +1,2 @@
-pub mod camera;
 pub mod startup_state;
 pub mod ui;
@@ -52,7 +52,7 @@ impl ApplicationState for StartupState {
                 crate::editor::MachaState::build(context, ()),
             )),
             SwitchableStates::GLTFLoader => morrigu::application::StateFlow::SwitchState(Box::new(
-                crate::gltf_loader::GLTFViewerState::build(context, ()),
+                crate::gltf_loader::GLTFLoadingState::build(context, ()),
             )),
             SwitchableStates::CSTest => morrigu::application::StateFlow::SwitchState(Box::new(
                 crate::compute_shader_test::CSTState::build(context, ()),
@@ -1,17 +1,21 @@
-use morrigu::egui;
+use morrigu::{egui, frame_stats::FrameStats};
 
 use super::startup_state::SwitchableStates;
 
-pub fn draw_debug_utils(ctx: &egui::Context, dt: std::time::Duration, current_state: &mut SwitchableStates) {
+pub fn draw_debug_utils(ctx: &egui::Context, frame_stats: &FrameStats, current_state: &mut SwitchableStates) {
     egui::Window::new("Debug tools").show(ctx, |ui| {
-        let color = match dt.as_millis() {
+        let color = match frame_stats.frame_time.as_millis() {
             0..=25 => [51, 204, 51],
             26..=50 => [255, 153, 0],
             _ => [204, 51, 51],
         };
         ui.colored_label(
             egui::Color32::from_rgb(color[0], color[1], color[2]),
-            format!("FPS: {} ({}ms)", 1.0 / dt.as_secs_f32(), dt.as_millis()),
+            format!(
+                "FPS: {:.0} ({}ms)",
+                frame_stats.average_fps(60),
+                frame_stats.frame_time.as_millis()
+            ),
         );
 
         egui::ComboBox::from_label("Select desired state:")
@@ -3,18 +3,51 @@ use bytemuck::bytes_of;
 use gpu_allocator::vulkan::{Allocation, AllocationCreateDesc, AllocationScheme, Allocator};
 use thiserror::Error;
 
+use std::ptr::NonNull;
+
 use crate::{
     renderer::Renderer,
+    resource_registry::{self, ResourceKind},
+    staging_pool::StagingPool,
     utils::{CommandUploader, ImmediateCommandError},
 };
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct AllocatedBuffer {
     pub handle: vk::Buffer,
     pub(crate) allocation: Option<Allocation>,
     size: u64,
+    /// Set for buffers built with [`AllocatedBufferBuilder::persistently_mapped`], and cleared
+    /// when the buffer is destroyed.
+    mapped_pointer: Option<MappedPointer>,
+
+    /// Build parameters, for [`AllocatedBuffer::resize`] to create the new buffer with.
+    usage: vk::BufferUsageFlags,
+    memory_location: gpu_allocator::MemoryLocation,
+    name: String,
+}
+
+impl Default for AllocatedBuffer {
+    fn default() -> Self {
+        Self {
+            handle: vk::Buffer::null(),
+            allocation: None,
+            size: 0,
+            mapped_pointer: None,
+            usage: vk::BufferUsageFlags::empty(),
+            memory_location: gpu_allocator::MemoryLocation::Unknown,
+            name: String::default(),
+        }
+    }
 }
 
+/// Start of a buffer's mapped memory. The memory stays mapped (and the pointer valid) for as long
+/// as the allocation lives, and the pointer is only used to write plain data.
+#[derive(Debug, Clone, Copy)]
+struct MappedPointer(NonNull<u8>);
+unsafe impl Send for MappedPointer {}
+unsafe impl Sync for MappedPointer {}
+
 #[derive(Error, Debug)]
 pub enum BufferDataUploadError {
     #[error("Conversion of data size from usize to u64 failed (check that {0} <= u64::MAX).")]
@@ -30,6 +63,62 @@ pub enum BufferDataUploadError {
 
     #[error("Failed to map the memory of this buffer.")]
     MemoryMappingFailed,
+
+    #[error("This buffer was not built as persistently mapped (see AllocatedBufferBuilder::persistently_mapped).")]
+    NotPersistentlyMapped,
+
+    #[error(
+        "Writing {data_size} bytes at offset {offset} overflows the buffer ({buffer_size} bytes)."
+    )]
+    OutOfBounds {
+        offset: u64,
+        data_size: usize,
+        buffer_size: u64,
+    },
+}
+
+#[derive(Error, Debug)]
+pub enum BufferResizeError {
+    #[error(
+        "Unable to find this buffer's allocation. This is most likely due to a use after free."
+    )]
+    UseAfterFree,
+
+    #[error("Building the new buffer failed with: {0}.")]
+    BuildFailed(#[from] BufferBuildError),
+
+    #[error("The contents of a buffer that isn't host visible can only be copied if its usage includes TRANSFER_SRC.")]
+    MissingTransferSourceUsage,
+
+    #[error("Copying the contents to the new buffer failed with the error: {0}.")]
+    CopyFailed(#[from] ImmediateCommandError),
+}
+
+#[derive(Error, Debug)]
+pub enum BufferReadBackError {
+    #[error(
+        "Unable to find this buffer's allocation. This is most likely due to a use after free."
+    )]
+    UseAfterFree,
+
+    #[error("Reading {count} elements of {element_size} bytes overflows the buffer ({buffer_size} bytes).")]
+    OutOfBounds {
+        count: usize,
+        element_size: usize,
+        buffer_size: u64,
+    },
+
+    #[error("The contents of a buffer can only be read back if its usage includes TRANSFER_SRC.")]
+    MissingTransferSourceUsage,
+
+    #[error("Building the readback buffer failed with: {0}.")]
+    ReadbackBufferBuildFailed(#[from] BufferBuildError),
+
+    #[error("Copying the contents to the readback buffer failed with the error: {0}.")]
+    CopyFailed(#[from] ImmediateCommandError),
+
+    #[error("Failed to map the memory of the readback buffer.")]
+    MemoryMappingFailed,
 }
 
 impl AllocatedBuffer {
@@ -77,12 +166,268 @@ impl AllocatedBuffer {
         Ok(())
     }
 
+    /// Copies `data` at `offset` bytes in the buffer, e.g. to update a single member of a
+    /// uniform block.
+    pub fn upload_data_at(
+        &mut self,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<(), BufferDataUploadError> {
+        let out_of_bounds = BufferDataUploadError::OutOfBounds {
+            offset,
+            data_size: data.len(),
+            buffer_size: self.size,
+        };
+        let allocation = self
+            .allocation
+            .as_mut()
+            .ok_or(BufferDataUploadError::UseAfterFree)?;
+        let end = u64::try_from(data.len())
+            .ok()
+            .and_then(|data_size| offset.checked_add(data_size));
+        if end.is_none_or(|end| end > self.size) {
+            return Err(out_of_bounds);
+        }
+        let offset = usize::try_from(offset).map_err(|_| out_of_bounds)?;
+
+        allocation
+            .mapped_slice_mut()
+            .ok_or(BufferDataUploadError::MemoryMappingFailed)?[offset..offset + data.len()]
+            .copy_from_slice(data);
+
+        Ok(())
+    }
+
+    /// Copies `data` at `offset` bytes in the buffer, through the pointer kept by persistently
+    /// mapped buffers (see [`AllocatedBufferBuilder::persistently_mapped`]), without looking up
+    /// the allocation's mapping.
+    ///
+    /// Like every host write, this doesn't synchronize with the GPU: the written range must not be
+    /// read by a frame still in flight.
+    pub fn write_at<T: bytemuck::Pod>(
+        &self,
+        offset: u64,
+        data: &T,
+    ) -> Result<(), BufferDataUploadError> {
+        if self.allocation.is_none() {
+            return Err(BufferDataUploadError::UseAfterFree);
+        }
+        let mapped_pointer = self
+            .mapped_pointer
+            .ok_or(BufferDataUploadError::NotPersistentlyMapped)?;
+
+        let raw_data = bytes_of(data);
+        let out_of_bounds = BufferDataUploadError::OutOfBounds {
+            offset,
+            data_size: raw_data.len(),
+            buffer_size: self.size,
+        };
+        let end = u64::try_from(raw_data.len())
+            .ok()
+            .and_then(|data_size| offset.checked_add(data_size));
+        if end.is_none_or(|end| end > self.size) {
+            return Err(out_of_bounds);
+        }
+        let offset = usize::try_from(offset).map_err(|_| out_of_bounds)?;
+
+        // SAFETY: the range was checked to be in the buffer, whose memory is mapped until the
+        // allocation is freed (which clears the pointer)
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                raw_data.as_ptr(),
+                mapped_pointer.0.as_ptr().add(offset),
+                raw_data.len(),
+            )
+        };
+
+        Ok(())
+    }
+
+    /// Grows the buffer to `new_size` bytes, replacing its handle and allocation by new ones built
+    /// with the same parameters. This does nothing if the buffer is already large enough.
+    ///
+    /// If `keep_contents` is set, the current contents are copied at the start of the new buffer,
+    /// through the mapped memory for host visible buffers, and with an immediate command
+    /// otherwise (which requires the buffer's usage to include `TRANSFER_SRC`).
+    ///
+    /// The old buffer is only destroyed once the frames in flight that may use it are done
+    /// executing. As descriptor sets reference the buffer's handle, they must be written again
+    /// (e.g. by binding the buffer again) after this call, before the buffer is used in the current
+    /// frame.
+    pub fn resize(
+        &mut self,
+        new_size: u64,
+        keep_contents: bool,
+        renderer: &mut Renderer,
+    ) -> Result<(), BufferResizeError> {
+        let allocation = self
+            .allocation
+            .as_ref()
+            .ok_or(BufferResizeError::UseAfterFree)?;
+        if new_size <= self.size {
+            return Ok(());
+        }
+
+        let host_copy = allocation.mapped_ptr().is_some();
+        let mut usage = self.usage;
+        if keep_contents && !host_copy {
+            if !usage.contains(vk::BufferUsageFlags::TRANSFER_SRC) {
+                return Err(BufferResizeError::MissingTransferSourceUsage);
+            }
+            usage |= vk::BufferUsageFlags::TRANSFER_DST;
+        }
+
+        let mut builder = AllocatedBuffer::builder(new_size)
+            .with_usage(usage)
+            .with_memory_location(self.memory_location)
+            .with_name(&self.name);
+        builder.persistently_mapped = self.mapped_pointer.is_some();
+        let mut new_buffer = builder.build(renderer)?;
+
+        if keep_contents {
+            let copy_result = if host_copy {
+                self.copy_mapped_contents(&mut new_buffer);
+                Ok(())
+            } else {
+                let copy_region = vk::BufferCopy::default().size(self.size);
+                renderer.immediate_command(|cmd_buffer| unsafe {
+                    renderer.device.cmd_copy_buffer(
+                        *cmd_buffer,
+                        self.handle,
+                        new_buffer.handle,
+                        std::slice::from_ref(&copy_region),
+                    )
+                })
+            };
+            if let Err(error) = copy_result {
+                new_buffer.destroy(&renderer.device, &mut renderer.allocator());
+                return Err(error.into());
+            }
+        }
+
+        // Frames in flight may still use the old buffer
+        let old_buffer = std::mem::replace(self, new_buffer);
+        renderer.destroy_buffer_deferred(old_buffer);
+
+        Ok(())
+    }
+
+    /// Reads the first `count` elements of the buffer (e.g. the results of a compute shader), by
+    /// copying them to a host visible buffer with an immediate command, which requires the
+    /// buffer's usage to include `TRANSFER_SRC`. All the writes submitted before are waited for.
+    pub fn read_back<T: bytemuck::Pod>(
+        &self,
+        count: usize,
+        renderer: &mut Renderer,
+    ) -> Result<Vec<T>, BufferReadBackError> {
+        if self.allocation.is_none() {
+            return Err(BufferReadBackError::UseAfterFree);
+        }
+        let element_size = std::mem::size_of::<T>();
+        let out_of_bounds = BufferReadBackError::OutOfBounds {
+            count,
+            element_size,
+            buffer_size: self.size,
+        };
+        let read_size = count
+            .checked_mul(element_size)
+            .and_then(|read_size| u64::try_from(read_size).ok())
+            .filter(|read_size| *read_size <= self.size)
+            .ok_or(out_of_bounds)?;
+        if read_size == 0 {
+            return Ok(vec![]);
+        }
+        if !self.usage.contains(vk::BufferUsageFlags::TRANSFER_SRC) {
+            return Err(BufferReadBackError::MissingTransferSourceUsage);
+        }
+
+        let mut readback_buffer = AllocatedBuffer::builder(read_size)
+            .with_usage(vk::BufferUsageFlags::TRANSFER_DST)
+            .with_memory_location(gpu_allocator::MemoryLocation::GpuToCpu)
+            .with_name("Readback buffer")
+            .build(renderer)?;
+
+        let copy_result = renderer.immediate_command(|cmd_buffer| unsafe {
+            let device = &renderer.device;
+            let write_barrier = vk::MemoryBarrier::default()
+                .src_access_mask(vk::AccessFlags::MEMORY_WRITE)
+                .dst_access_mask(vk::AccessFlags::TRANSFER_READ);
+            device.cmd_pipeline_barrier(
+                *cmd_buffer,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                std::slice::from_ref(&write_barrier),
+                &[],
+                &[],
+            );
+
+            let copy_region = vk::BufferCopy::default().size(read_size);
+            device.cmd_copy_buffer(
+                *cmd_buffer,
+                self.handle,
+                readback_buffer.handle,
+                std::slice::from_ref(&copy_region),
+            );
+
+            let readback_barrier = vk::MemoryBarrier::default()
+                .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                .dst_access_mask(vk::AccessFlags::HOST_READ);
+            device.cmd_pipeline_barrier(
+                *cmd_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(),
+                std::slice::from_ref(&readback_barrier),
+                &[],
+                &[],
+            );
+        });
+
+        let data = copy_result
+            .map_err(BufferReadBackError::from)
+            .and_then(|()| {
+                readback_buffer
+                    .allocation
+                    .as_ref()
+                    .and_then(|allocation| allocation.mapped_slice())
+                    .map(|mapped_slice| {
+                        mapped_slice[..read_size as usize]
+                            .chunks_exact(element_size)
+                            .map(bytemuck::pod_read_unaligned)
+                            .collect()
+                    })
+                    .ok_or(BufferReadBackError::MemoryMappingFailed)
+            });
+        readback_buffer.destroy(&renderer.device, &mut renderer.allocator());
+
+        data
+    }
+
+    /// Copies the contents of this buffer at the start of `destination`, both being host visible.
+    fn copy_mapped_contents(&self, destination: &mut AllocatedBuffer) {
+        let source = self
+            .allocation
+            .as_ref()
+            .and_then(|allocation| allocation.mapped_slice());
+        let destination = destination
+            .allocation
+            .as_mut()
+            .and_then(|allocation| allocation.mapped_slice_mut());
+        if let (Some(source), Some(destination)) = (source, destination) {
+            let size = self.size as usize;
+            destination[..size].copy_from_slice(&source[..size]);
+        }
+    }
+
     pub fn destroy(&mut self, device: &ash::Device, allocator: &mut Allocator) {
+        self.mapped_pointer = None;
         if let Some(allocation) = self.allocation.take() {
             allocator
                 .free(allocation)
                 .expect("Failed to free buffer memory");
             unsafe { device.destroy_buffer(self.handle, None) };
+            resource_registry::unregister(ResourceKind::Buffer, 1);
         }
     }
 }
@@ -97,6 +442,9 @@ pub enum BufferBuildError {
 
     #[error("Vulkan binding of the buffer's allocation failed with the result: {0}.")]
     VulkanAllocationBindingFailed(vk::Result),
+
+    #[error("The buffer was requested to be persistently mapped, but its memory location ({0:?}) is not host visible.")]
+    NotHostVisible(gpu_allocator::MemoryLocation),
 }
 
 #[derive(Error, Debug)]
@@ -112,8 +460,13 @@ pub struct AllocatedBufferBuilder {
     pub size: u64,
     pub usage: vk::BufferUsageFlags,
     pub memory_location: gpu_allocator::MemoryLocation,
+    pub persistently_mapped: bool,
 
     pub name: String,
+    /// Queue families sharing the buffer when there are several of them, set by
+    /// [`AllocatedBufferBuilder::build`] to the graphics and dedicated compute families (see
+    /// [`Renderer::has_dedicated_compute_queue`]).
+    pub queue_family_indices: Vec<u32>,
 }
 
 /// @TODO(Ithyx): create new type with MemoryLocation::GpuOnly
@@ -128,15 +481,35 @@ impl AllocatedBufferBuilder {
             size,
             usage: vk::BufferUsageFlags::UNIFORM_BUFFER,
             memory_location: gpu_allocator::MemoryLocation::CpuToGpu,
+            persistently_mapped: false,
+            queue_family_indices: vec![],
             name: String::from("unnamed buffer"),
         }
     }
 
+    /// Buffer read and written by shaders (e.g. the data of a compute simulation), which can also
+    /// be copied from and to. It is host visible so that its initial data can be uploaded, use
+    /// `MemoryLocation::GpuOnly` for data only ever accessed by the GPU.
+    pub fn storage_buffer_default(size: u64) -> Self {
+        Self {
+            size,
+            usage: vk::BufferUsageFlags::STORAGE_BUFFER
+                | vk::BufferUsageFlags::TRANSFER_SRC
+                | vk::BufferUsageFlags::TRANSFER_DST,
+            memory_location: gpu_allocator::MemoryLocation::CpuToGpu,
+            persistently_mapped: false,
+            queue_family_indices: vec![],
+            name: String::from("unnamed storage buffer"),
+        }
+    }
+
     pub fn staging_buffer_default(size: u64) -> Self {
         Self {
             size,
             usage: vk::BufferUsageFlags::TRANSFER_SRC,
             memory_location: gpu_allocator::MemoryLocation::CpuToGpu,
+            persistently_mapped: false,
+            queue_family_indices: vec![],
             name: String::from("unnamed staging buffer"),
         }
     }
@@ -156,8 +529,22 @@ impl AllocatedBufferBuilder {
         self
     }
 
-    pub fn build(self, renderer: &mut Renderer) -> Result<AllocatedBuffer, BufferBuildError> {
-        self.build_internal(&renderer.device, &mut renderer.allocator())
+    /// Keeps a pointer to the buffer's mapped memory, for [`AllocatedBuffer::write_at`] to write
+    /// through (e.g. for data updated every frame). The memory location must be host visible,
+    /// which the default `CpuToGpu` is.
+    pub fn persistently_mapped(mut self) -> Self {
+        self.persistently_mapped = true;
+        self
+    }
+
+    /// The buffer is named after [`AllocatedBufferBuilder::name`] if automatic naming is enabled
+    /// (see [`crate::renderer::RendererBuilder::with_auto_naming`]).
+    pub fn build(mut self, renderer: &mut Renderer) -> Result<AllocatedBuffer, BufferBuildError> {
+        self.queue_family_indices = renderer.resource_queue_families();
+        let buffer = self.build_internal(&renderer.device, &mut renderer.allocator())?;
+        renderer.auto_name(buffer.handle, &buffer.name);
+
+        Ok(buffer)
     }
 
     pub fn build_with_pod<T: bytemuck::Pod>(
@@ -189,12 +576,17 @@ impl AllocatedBufferBuilder {
         device: &ash::Device,
         allocator: &mut Allocator,
     ) -> Result<AllocatedBuffer, BufferBuildError> {
-        let buffer_info = vk::BufferCreateInfo {
+        let mut buffer_info = vk::BufferCreateInfo {
             size: self.size,
             usage: self.usage,
             sharing_mode: vk::SharingMode::EXCLUSIVE,
             ..Default::default()
         };
+        if self.queue_family_indices.len() > 1 {
+            buffer_info = buffer_info
+                .sharing_mode(vk::SharingMode::CONCURRENT)
+                .queue_family_indices(&self.queue_family_indices);
+        }
 
         let handle = unsafe { device.create_buffer(&buffer_info, None) }
             .map_err(BufferBuildError::VulkanCreationFailed)?;
@@ -210,12 +602,30 @@ impl AllocatedBufferBuilder {
 
         unsafe { device.bind_buffer_memory(handle, allocation.memory(), allocation.offset()) }
             .map_err(BufferBuildError::VulkanAllocationBindingFailed)?;
+        resource_registry::register(ResourceKind::Buffer, 1);
 
-        Ok(AllocatedBuffer {
+        // Host visible allocations are mapped for their whole lifetime by the allocator
+        let mapped_pointer = allocation
+            .mapped_ptr()
+            .map(|pointer| MappedPointer(pointer.cast()));
+        let mut buffer = AllocatedBuffer {
             handle,
             allocation: Some(allocation),
             size: self.size,
-        })
+            mapped_pointer: None,
+            usage: self.usage,
+            memory_location: self.memory_location,
+            name: self.name,
+        };
+        if self.persistently_mapped {
+            if mapped_pointer.is_none() {
+                buffer.destroy(device, allocator);
+                return Err(BufferBuildError::NotHostVisible(self.memory_location));
+            }
+            buffer.mapped_pointer = mapped_pointer;
+        }
+
+        Ok(buffer)
     }
 }
 
@@ -229,6 +639,8 @@ pub struct AllocatedImage {
     pub format: vk::Format,
     pub extent: vk::Extent3D,
     pub layer_count: u32,
+    /// Type of `view`, which views replacing it (e.g. covering other mip levels) must keep.
+    pub view_type: vk::ImageViewType,
 }
 
 #[derive(Error, Debug)]
@@ -261,15 +673,41 @@ impl AllocatedImage {
         allocator: &mut Allocator,
         command_uploader: &CommandUploader,
     ) -> Result<(), ImageDataUploadError> {
-        let mut staging_buffer = AllocatedBufferBuilder::staging_buffer_default(
+        self.upload_mip_data(
+            0,
+            data,
+            new_layout,
+            device,
+            graphics_queue,
+            allocator,
+            command_uploader,
+        )
+    }
+
+    /// Uploads `data` to the given mip level of the image, whose extent is the image's one halved
+    /// `mip_level` times. The level is written as a whole, so it is transitioned from `UNDEFINED`
+    /// whatever its current layout is (e.g. for the levels of streamed textures that were never
+    /// uploaded, which don't match `self.layout`), discarding its previous content.
+    #[allow(clippy::too_many_arguments)]
+    pub fn upload_mip_data(
+        &mut self,
+        mip_level: u32,
+        data: &[u8],
+        new_layout: Option<vk::ImageLayout>,
+        device: &ash::Device,
+        graphics_queue: vk::Queue,
+        allocator: &mut Allocator,
+        command_uploader: &CommandUploader,
+    ) -> Result<(), ImageDataUploadError> {
+        let mut staging_buffer = StagingPool::lease(
+            &command_uploader.staging_pool,
             u64::try_from(std::mem::size_of_val(data)).map_err(|_| {
                 ImageDataUploadError::SizeConversionFailed(std::mem::size_of_val(data))
             })?,
+            device,
+            allocator,
         )
-        .build_internal(device, allocator)
-        .map_err(|buffer_build_error| {
-            ImageDataUploadError::StagingBufferCreationFailed(buffer_build_error)
-        })?;
+        .map_err(ImageDataUploadError::StagingBufferCreationFailed)?;
 
         let slice = staging_buffer
             .allocation
@@ -287,39 +725,41 @@ impl AllocatedImage {
             |cmd_buffer: &vk::CommandBuffer| {
                 let range = vk::ImageSubresourceRange::default()
                     .aspect_mask(vk::ImageAspectFlags::COLOR)
-                    .base_mip_level(0)
+                    .base_mip_level(mip_level)
                     .level_count(1)
                     .base_array_layer(0)
                     .layer_count(self.layer_count);
-                if self.layout != vk::ImageLayout::TRANSFER_DST_OPTIMAL {
-                    let transfer_dst_barrier = vk::ImageMemoryBarrier::default()
-                        .src_access_mask(vk::AccessFlags::NONE)
-                        .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
-                        .old_layout(self.layout)
-                        .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
-                        .image(self.handle)
-                        .subresource_range(range);
-                    unsafe {
-                        device.cmd_pipeline_barrier(
-                            *cmd_buffer,
-                            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
-                            vk::PipelineStageFlags::TRANSFER,
-                            vk::DependencyFlags::empty(),
-                            &[],
-                            &[],
-                            std::slice::from_ref(&transfer_dst_barrier),
-                        )
-                    };
-                }
+                let transfer_dst_barrier = vk::ImageMemoryBarrier::default()
+                    .src_access_mask(vk::AccessFlags::NONE)
+                    .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .old_layout(vk::ImageLayout::UNDEFINED)
+                    .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                    .image(self.handle)
+                    .subresource_range(range);
+                unsafe {
+                    device.cmd_pipeline_barrier(
+                        *cmd_buffer,
+                        vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                        vk::PipelineStageFlags::TRANSFER,
+                        vk::DependencyFlags::empty(),
+                        &[],
+                        &[],
+                        std::slice::from_ref(&transfer_dst_barrier),
+                    )
+                };
 
                 let copy_region = vk::BufferImageCopy::default()
                     .image_subresource(vk::ImageSubresourceLayers {
                         aspect_mask: vk::ImageAspectFlags::COLOR,
-                        mip_level: 0,
+                        mip_level,
                         base_array_layer: 0,
                         layer_count: self.layer_count,
                     })
-                    .image_extent(self.extent);
+                    .image_extent(vk::Extent3D {
+                        width: (self.extent.width >> mip_level).max(1),
+                        height: (self.extent.height >> mip_level).max(1),
+                        depth: (self.extent.depth >> mip_level).max(1),
+                    });
                 unsafe {
                     device.cmd_copy_buffer_to_image(
                         *cmd_buffer,
@@ -355,8 +795,6 @@ impl AllocatedImage {
             self.layout = new_layout;
         }
 
-        staging_buffer.destroy(device, allocator);
-
         Ok(())
     }
 
@@ -371,6 +809,7 @@ impl AllocatedImage {
                 .free(allocation)
                 .expect("Failed to free image memory");
             unsafe { device.destroy_image(self.handle, None) };
+            resource_registry::unregister(ResourceKind::Image, 1);
         }
     }
 }
@@ -383,6 +822,10 @@ pub struct AllocatedImageBuilder<'a> {
     pub usage: vk::ImageUsageFlags,
 
     pub data: Option<Vec<u8>>,
+    /// Queue families sharing the image when there are several of them, set by
+    /// [`AllocatedImageBuilder::build`] to the graphics and dedicated compute families (see
+    /// [`Renderer::has_dedicated_compute_queue`]).
+    pub queue_family_indices: Vec<u32>,
 }
 
 #[derive(Error, Debug)]
@@ -414,6 +857,7 @@ impl AllocatedImageBuilder<'_> {
             layout: vk::ImageLayout::GENERAL,
             usage: vk::ImageUsageFlags::empty(),
             data: None,
+            queue_family_indices: vec![],
         }
     }
 
@@ -521,7 +965,8 @@ impl AllocatedImageBuilder<'_> {
         self
     }
 
-    pub fn build(self, renderer: &mut Renderer) -> Result<AllocatedImage, ImageBuildError> {
+    pub fn build(mut self, renderer: &mut Renderer) -> Result<AllocatedImage, ImageBuildError> {
+        self.queue_family_indices = renderer.resource_queue_families();
         self.build_internal(
             &renderer.device,
             renderer.graphics_queue.handle,
@@ -541,8 +986,14 @@ impl AllocatedImageBuilder<'_> {
             self.usage |= vk::ImageUsageFlags::TRANSFER_DST;
         }
         self.image_create_info.usage |= self.usage;
+        let mut image_create_info: vk::ImageCreateInfo = self.image_create_info;
+        if self.queue_family_indices.len() > 1 {
+            image_create_info = image_create_info
+                .sharing_mode(vk::SharingMode::CONCURRENT)
+                .queue_family_indices(&self.queue_family_indices);
+        }
 
-        let handle = unsafe { device.create_image(&self.image_create_info, None) }
+        let handle = unsafe { device.create_image(&image_create_info, None) }
             .map_err(ImageBuildError::VulkanCreationFailed)?;
 
         let memory_requirements = unsafe { device.get_image_memory_requirements(handle) };
@@ -568,7 +1019,9 @@ impl AllocatedImageBuilder<'_> {
             format: self.image_create_info.format,
             extent: self.image_create_info.extent,
             layer_count: self.image_create_info.array_layers,
+            view_type: self.image_view_create_info.view_type,
         };
+        resource_registry::register(ResourceKind::Image, 1);
 
         let data = match self.data {
             Some(data) => data,
@@ -619,6 +1072,7 @@ impl AllocatedImageBuilder<'_> {
         self.image_view_create_info = self.image_view_create_info.image(handle);
         let view = unsafe { device.create_image_view(&self.image_view_create_info, None) }
             .map_err(ImageBuildError::VulkanViewCreationFailed)?;
+        resource_registry::register(ResourceKind::Image, 1);
 
         Ok(AllocatedImage {
             view,
@@ -628,6 +1082,7 @@ impl AllocatedImageBuilder<'_> {
             format: self.image_create_info.format,
             extent: self.image_create_info.extent,
             layer_count: self.image_create_info.array_layers,
+            view_type: self.image_view_create_info.view_type,
         })
     }
 }
@@ -0,0 +1,132 @@
+use crate::{
+    components::skeleton::JointPose,
+    math_types::{Quat, Vec3},
+};
+
+/// How the values of a track are interpolated between two keyframes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Interpolation {
+    /// The value of the previous keyframe is kept until the next one.
+    Step,
+    /// Vectors are interpolated linearly, and rotations spherically.
+    #[default]
+    Linear,
+}
+
+/// What a track animates, see [`crate::components::animation_player::AnimationPlayer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationTarget {
+    /// The [`crate::components::transform::Transform`] of the entity playing the clip.
+    Root,
+    /// The pose of a joint of the entity's [`crate::components::skeleton::Skeleton`], by index.
+    Joint(usize),
+}
+
+/// Keyframe values of a track, one per keyframe time.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrackValues {
+    Translation(Vec<Vec3>),
+    Rotation(Vec<Quat>),
+    Scale(Vec<Vec3>),
+}
+
+impl TrackValues {
+    pub fn len(&self) -> usize {
+        match self {
+            TrackValues::Translation(values) | TrackValues::Scale(values) => values.len(),
+            TrackValues::Rotation(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Keyframes of one property of a target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationTrack {
+    pub target: AnimationTarget,
+    pub interpolation: Interpolation,
+    /// Time of each keyframe in seconds, in increasing order.
+    pub times: Vec<f32>,
+    pub values: TrackValues,
+}
+
+#[profiling::all_functions]
+impl AnimationTrack {
+    /// Writes the value of the track at `time` to the matching property of `pose`. Times before
+    /// the first keyframe or after the last one are clamped to them, and tracks without keyframes
+    /// leave the pose untouched.
+    pub fn apply(&self, time: f32, pose: &mut JointPose) {
+        let keyframe_count = self.times.len().min(self.values.len());
+        if keyframe_count == 0 {
+            return;
+        }
+
+        // Keyframes surrounding `time`, and how far it is between them
+        let next = self.times[..keyframe_count].partition_point(|keyframe| *keyframe <= time);
+        let (previous, next, factor) = if next == 0 {
+            (0, 0, 0.0)
+        } else if next == keyframe_count {
+            (keyframe_count - 1, keyframe_count - 1, 0.0)
+        } else {
+            let (start, end) = (self.times[next - 1], self.times[next]);
+            let factor = match self.interpolation {
+                Interpolation::Step => 0.0,
+                Interpolation::Linear if end > start => (time - start) / (end - start),
+                Interpolation::Linear => 0.0,
+            };
+            (next - 1, next, factor)
+        };
+
+        match &self.values {
+            TrackValues::Translation(values) => {
+                pose.translation = values[previous].lerp(values[next], factor);
+            }
+            TrackValues::Rotation(values) => {
+                pose.rotation = values[previous].slerp(values[next], factor);
+            }
+            TrackValues::Scale(values) => {
+                pose.scale = values[previous].lerp(values[next], factor);
+            }
+        }
+    }
+}
+
+/// Keyframed animation of the root and joints of an entity, played by an
+/// [`crate::components::animation_player::AnimationPlayer`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationClip {
+    pub name: String,
+    tracks: Vec<AnimationTrack>,
+    duration: f32,
+}
+
+#[profiling::all_functions]
+impl AnimationClip {
+    /// The clip lasts until the last keyframe of its tracks.
+    pub fn new(name: &str, tracks: Vec<AnimationTrack>) -> Self {
+        let duration = tracks
+            .iter()
+            .filter_map(|track| track.times.last())
+            .fold(0.0, |duration: f32, time| duration.max(*time));
+
+        Self {
+            name: name.to_owned(),
+            tracks,
+            duration,
+        }
+    }
+
+    /// Duration of the clip in seconds.
+    #[profiling::skip]
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    #[profiling::skip]
+    pub fn tracks(&self) -> &[AnimationTrack] {
+        &self.tracks
+    }
+}
@@ -1,9 +1,11 @@
 pub use winit::{event, window::Window};
-use winit_input_helper::WinitInputHelper;
 
 use crate::{
     components::camera::{Camera, PerspectiveData, Projection},
+    debug_draw::DebugDrawer,
     ecs_manager::ECSManager,
+    frame_stats::FrameStats,
+    input::InputState,
     math_types::Vec2,
     renderer::{Renderer, RendererBuilder},
     utils::ThreadSafeRef,
@@ -25,7 +27,12 @@ pub struct StateContext<'a> {
     pub renderer: &'a mut Renderer,
     pub ecs_manager: &'a mut ECSManager,
     pub window: &'a Window,
-    pub window_input_state: &'a WinitInputHelper,
+    pub window_input_state: &'a InputState,
+
+    /// Number of frames rendered since the application started.
+    pub frame_index: u64,
+    /// Time elapsed since the application started.
+    pub total_elapsed: Duration,
 }
 
 #[cfg(feature = "egui")]
@@ -35,7 +42,12 @@ pub struct EguiUpdateContext<'a> {
     pub renderer: &'a mut Renderer,
     pub ecs_manager: &'a mut ECSManager,
     pub window: &'a Window,
-    pub window_input_state: &'a WinitInputHelper,
+    pub window_input_state: &'a InputState,
+
+    /// Number of frames rendered since the application started.
+    pub frame_index: u64,
+    /// Time elapsed since the application started.
+    pub total_elapsed: Duration,
 }
 
 pub enum StateFlow<'state> {
@@ -57,6 +69,7 @@ pub trait ApplicationState {
     fn on_window_event(&mut self, _event: event::WindowEvent, _context: &mut StateContext) {}
     fn on_device_event(&mut self, _event: event::DeviceEvent, _context: &mut StateContext) {}
 
+    /// Evaluated after every frame and every event.
     fn flow<'flow>(&mut self, _context: &mut StateContext) -> StateFlow<'flow> {
         StateFlow::Continue
     }
@@ -76,6 +89,9 @@ pub struct ApplicationConfiguration {
     application_name: String,
     version: (u32, u32, u32),
     preferred_present_mode: vk::PresentModeKHR,
+    preferred_color_space: vk::ColorSpaceKHR,
+    prefer_software: bool,
+    gpu_profiling: bool,
 }
 
 impl ApplicationConfiguration {
@@ -87,6 +103,9 @@ impl ApplicationConfiguration {
             application_name: "Morrigu application".to_owned(),
             version: (0, 0, 0),
             preferred_present_mode: vk::PresentModeKHR::MAILBOX,
+            preferred_color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+            prefer_software: false,
+            gpu_profiling: false,
         }
     }
 
@@ -115,6 +134,21 @@ impl ApplicationConfiguration {
         self.preferred_present_mode = present_mode;
         self
     }
+
+    pub fn with_color_space(mut self, color_space: vk::ColorSpaceKHR) -> Self {
+        self.preferred_color_space = color_space;
+        self
+    }
+
+    pub fn prefer_software(mut self) -> Self {
+        self.prefer_software = true;
+        self
+    }
+
+    pub fn with_gpu_profiling(mut self) -> Self {
+        self.gpu_profiling = true;
+        self
+    }
 }
 
 impl Default for ApplicationConfiguration {
@@ -129,21 +163,30 @@ struct ApplicationData<'state> {
 
     ecs_manager: ECSManager,
     renderer_ref: ThreadSafeRef<Renderer>,
+    debug_drawer: DebugDrawer,
     window: Window,
     prev_time: std::time::Instant,
-    window_input_state: WinitInputHelper,
+    start_time: std::time::Instant,
+    frame_index: u64,
+    window_input_state: InputState,
 
     state: Box<dyn ApplicationState + 'state>,
 }
 
 impl ApplicationData<'_> {
-    fn update(&mut self) {
+    fn update(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
         let delta = self.prev_time.elapsed();
         self.prev_time = Instant::now();
+        let total_elapsed = self.start_time.elapsed();
 
-        let mut renderer = self.renderer_ref.lock();
-        if renderer.begin_frame() {
+        let frame_started = self.renderer_ref.lock().begin_frame(total_elapsed);
+        if frame_started {
             profiling::scope!("main loop");
+            self.ecs_manager
+                .world
+                .resource_mut::<FrameStats>()
+                .record(delta);
+            let mut renderer = self.renderer_ref.lock();
 
             #[cfg(feature = "egui")]
             self.egui.painter.cleanup_previous_frame(&mut renderer);
@@ -155,6 +198,8 @@ impl ApplicationData<'_> {
                 ecs_manager: &mut self.ecs_manager,
                 window: &self.window,
                 window_input_state: &self.window_input_state,
+                frame_index: self.frame_index,
+                total_elapsed,
             };
             {
                 profiling::scope!("on_update");
@@ -173,8 +218,15 @@ impl ApplicationData<'_> {
                     ecs_manager: &mut self.ecs_manager,
                     window: &self.window,
                     window_input_state: &self.window_input_state,
+                    frame_index: self.frame_index,
+                    total_elapsed,
                 };
                 self.state.after_systems(delta, &mut state_context);
+
+                self.debug_drawer.draw(
+                    self.ecs_manager.world.get_resource::<Camera>(),
+                    &mut renderer,
+                );
                 drop(renderer);
             }
 
@@ -189,6 +241,8 @@ impl ApplicationData<'_> {
                         ecs_manager: &mut self.ecs_manager,
                         window: &self.window,
                         window_input_state: &self.window_input_state,
+                        frame_index: self.frame_index,
+                        total_elapsed,
                     };
                     self.state.on_update_egui(delta, &mut egui_update_context);
                     egui_update_context
@@ -197,38 +251,25 @@ impl ApplicationData<'_> {
                     self.state.after_ui_systems(delta, &mut egui_update_context);
                 });
 
+                self.egui.update_viewports(event_loop, &mut renderer);
                 self.egui.paint(&mut renderer)
             }
 
             let mut renderer = self.renderer_ref.lock();
             renderer.end_frame();
+            self.frame_index += 1;
             profiling::finish_frame!();
         }
 
-        self.window_input_state.end_step();
-    }
-
-    fn handle_window_event(
-        &mut self,
-        event_loop: &winit::event_loop::ActiveEventLoop,
-        event: event::WindowEvent,
-    ) {
-        #[cfg(feature = "egui")]
-        if self.egui.handle_event(&self.window, &event) {
-            return;
-        }
-
-        self.window_input_state.process_window_event(&event);
-
-        if self.window_input_state.close_requested() || self.window_input_state.destroyed() {
-            event_loop.exit();
-        }
+        self.window_input_state.helper.end_step();
 
-        if let event::WindowEvent::Resized(PhysicalSize { width, height }) = event {
-            self.renderer_ref.lock().on_resize(width, height);
-            self.ecs_manager.on_resize(width, height);
-        };
+        // Evaluated every frame as well, so that states can switch without waiting for an event
+        // (e.g. once an `AsyncLoad` is ready)
+        self.evaluate_flow(event_loop);
+    }
 
+    /// Evaluates the current state's [`ApplicationState::flow`], and switches or exits if requested.
+    fn evaluate_flow(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
         let mut renderer = self.renderer_ref.lock();
         let mut state_context = StateContext {
             #[cfg(feature = "egui")]
@@ -237,8 +278,9 @@ impl ApplicationData<'_> {
             ecs_manager: &mut self.ecs_manager,
             window: &self.window,
             window_input_state: &self.window_input_state,
+            frame_index: self.frame_index,
+            total_elapsed: self.start_time.elapsed(),
         };
-        self.state.on_window_event(event, &mut state_context);
 
         match self.state.flow(&mut state_context) {
             StateFlow::Continue => (),
@@ -257,7 +299,7 @@ impl ApplicationData<'_> {
                     Projection::Perspective(PerspectiveData {
                         horizontal_fov: f32::to_radians(90.0),
                         near_plane: 0.001,
-                        far_plane: 1000.0,
+                        far_plane: Some(1000.0),
                     }),
                     &Vec2::new(res.0 as f32, res.1 as f32),
                 );
@@ -270,17 +312,27 @@ impl ApplicationData<'_> {
         }
     }
 
-    fn handle_device_event(
+    fn handle_window_event(
         &mut self,
         event_loop: &winit::event_loop::ActiveEventLoop,
-        event: event::DeviceEvent,
+        event: event::WindowEvent,
     ) {
-        self.window_input_state.process_device_event(&event);
+        #[cfg(feature = "egui")]
+        if self.egui.handle_event(&self.window, &event) {
+            return;
+        }
+
+        self.window_input_state.helper.process_window_event(&event);
 
         if self.window_input_state.close_requested() || self.window_input_state.destroyed() {
             event_loop.exit();
         }
 
+        if let event::WindowEvent::Resized(PhysicalSize { width, height }) = event {
+            self.renderer_ref.lock().on_resize(width, height);
+            self.ecs_manager.on_resize(width, height);
+        };
+
         let mut renderer = self.renderer_ref.lock();
         let mut state_context = StateContext {
             #[cfg(feature = "egui")]
@@ -289,37 +341,43 @@ impl ApplicationData<'_> {
             ecs_manager: &mut self.ecs_manager,
             window: &self.window,
             window_input_state: &self.window_input_state,
+            frame_index: self.frame_index,
+            total_elapsed: self.start_time.elapsed(),
         };
-        self.state.on_device_event(event, &mut state_context);
-
-        match self.state.flow(&mut state_context) {
-            StateFlow::Continue => (),
-            StateFlow::Exit => event_loop.exit(),
-            StateFlow::SwitchState(new_state) => {
-                log::debug!("Switching states !");
+        self.state.on_window_event(event, &mut state_context);
 
-                self.state.on_drop(&mut state_context);
+        drop(renderer);
 
-                let res = (
-                    self.window.inner_size().width,
-                    self.window.inner_size().height,
-                );
+        self.evaluate_flow(event_loop);
+    }
 
-                let camera = Camera::builder().build(
-                    Projection::Perspective(PerspectiveData {
-                        horizontal_fov: f32::to_radians(90.0),
-                        near_plane: 0.001,
-                        far_plane: 1000.0,
-                    }),
-                    &Vec2::new(res.0 as f32, res.1 as f32),
-                );
-                *state_context.ecs_manager = ECSManager::new(&self.renderer_ref, camera);
-                state_context.ecs_manager.on_resize(res.0, res.1);
+    fn handle_device_event(
+        &mut self,
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        event: event::DeviceEvent,
+    ) {
+        self.window_input_state.helper.process_device_event(&event);
 
-                self.state = new_state;
-                self.state.on_attach(&mut state_context);
-            }
+        if self.window_input_state.close_requested() || self.window_input_state.destroyed() {
+            event_loop.exit();
         }
+
+        let mut renderer = self.renderer_ref.lock();
+        let mut state_context = StateContext {
+            #[cfg(feature = "egui")]
+            egui: &mut self.egui,
+            renderer: &mut renderer,
+            ecs_manager: &mut self.ecs_manager,
+            window: &self.window,
+            window_input_state: &self.window_input_state,
+            frame_index: self.frame_index,
+            total_elapsed: self.start_time.elapsed(),
+        };
+        self.state.on_device_event(event, &mut state_context);
+
+        drop(renderer);
+
+        self.evaluate_flow(event_loop);
     }
 
     fn on_exit(&mut self) {
@@ -337,11 +395,14 @@ impl ApplicationData<'_> {
             ecs_manager: &mut self.ecs_manager,
             window: &self.window,
             window_input_state: &self.window_input_state,
+            frame_index: self.frame_index,
+            total_elapsed: self.start_time.elapsed(),
         };
         self.state.on_drop(&mut state_context);
 
+        self.debug_drawer.destroy(&mut renderer);
         #[cfg(feature = "egui")]
-        self.egui.painter.destroy(&mut renderer);
+        self.egui.destroy(&mut renderer);
     }
 }
 
@@ -374,7 +435,7 @@ where
                     log::warn!("Attempting to update before initialization")
                 }
                 ApplicationStatus::Running(application_data) => {
-                    application_data.window_input_state.step()
+                    application_data.window_input_state.helper.step()
                 }
             },
             event::StartCause::Init => {}
@@ -382,9 +443,9 @@ where
         }
     }
 
-    fn about_to_wait(&mut self, _: &winit::event_loop::ActiveEventLoop) {
+    fn about_to_wait(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
         if let ApplicationStatus::Running(application_data) = &mut self.status {
-            application_data.update();
+            application_data.update(event_loop);
         }
     }
 
@@ -409,7 +470,7 @@ where
     fn window_event(
         &mut self,
         event_loop: &winit::event_loop::ActiveEventLoop,
-        _: winit::window::WindowId,
+        window_id: winit::window::WindowId,
         event: event::WindowEvent,
     ) {
         match &mut self.status {
@@ -417,6 +478,15 @@ where
                 log::warn!("Window even received before initialization")
             }
             ApplicationStatus::Running(application_data) => {
+                // Other windows are the ones of egui's viewports
+                if window_id != application_data.window.id() {
+                    #[cfg(feature = "egui")]
+                    application_data
+                        .egui
+                        .handle_viewport_event(window_id, &event);
+                    return;
+                }
+
                 application_data.handle_window_event(event_loop, event)
             }
         }
@@ -453,25 +523,32 @@ where
                     .create_window(window_attributes)
                     .expect("Failed to create window");
 
-                let window_input_state = WinitInputHelper::new();
+                let window_input_state = InputState::new();
 
-                let renderer_ref = RendererBuilder::new(&window)
+                let mut renderer_builder = RendererBuilder::new(&window)
                     .with_dimensions(self.app_config.width, self.app_config.height)
                     .with_preferred_present_mode(self.app_config.preferred_present_mode)
+                    .with_color_space(self.app_config.preferred_color_space)
                     .with_name(&self.app_config.application_name)
                     .with_version(
                         self.app_config.version.0,
                         self.app_config.version.1,
                         self.app_config.version.2,
-                    )
-                    .build();
+                    );
+                if self.app_config.prefer_software {
+                    renderer_builder = renderer_builder.prefer_software();
+                }
+                if self.app_config.gpu_profiling {
+                    renderer_builder = renderer_builder.with_gpu_profiling();
+                }
+                let renderer_ref = renderer_builder.build();
                 let mut ecs_manager = ECSManager::new(
                     &renderer_ref,
                     Camera::builder().build(
                         Projection::Perspective(PerspectiveData {
                             horizontal_fov: f32::to_radians(90.0),
                             near_plane: 0.001,
-                            far_plane: 1000.0,
+                            far_plane: Some(1000.0),
                         }),
                         &Vec2::new(self.app_config.width as f32, self.app_config.height as f32),
                     ),
@@ -482,6 +559,8 @@ where
                 let mut egui =
                     crate::egui_integration::EguiIntegration::new(&window, &mut renderer)
                         .expect("Failed to create Egui integration");
+                let debug_drawer =
+                    DebugDrawer::new(&mut renderer).expect("Failed to create debug drawer");
 
                 let mut state = StartupStateType::build(
                     &mut StateContext {
@@ -492,6 +571,8 @@ where
                         ecs_manager: &mut ecs_manager,
                         window: &window,
                         window_input_state: &window_input_state,
+                        frame_index: 0,
+                        total_elapsed: Duration::ZERO,
                     },
                     data.clone(),
                 );
@@ -504,6 +585,8 @@ where
                     ecs_manager: &mut ecs_manager,
                     window: &window,
                     window_input_state: &window_input_state,
+                    frame_index: 0,
+                    total_elapsed: Duration::ZERO,
                 };
                 state.on_attach(&mut state_context);
                 let engine_init_time = instant.elapsed();
@@ -522,8 +605,11 @@ where
 
                     ecs_manager,
                     renderer_ref,
+                    debug_drawer,
                     window,
                     prev_time: Instant::now(),
+                    start_time: Instant::now(),
+                    frame_index: 0,
                     window_input_state,
 
                     state,
@@ -560,3 +646,97 @@ where
             .expect("Encountered error in main loop");
     }
 }
+
+pub struct ComputeContext<'a> {
+    pub renderer: &'a mut Renderer,
+
+    /// Number of updates run since the application started.
+    pub iteration: u64,
+    /// Time elapsed since the application started.
+    pub total_elapsed: Duration,
+}
+
+pub enum ComputeFlow {
+    Continue,
+    Exit,
+}
+
+pub trait ComputeApplicationState {
+    fn on_attach(&mut self, _context: &mut ComputeContext) {}
+    fn on_drop(&mut self, _context: &mut ComputeContext) {}
+
+    /// Called in a loop, as fast as possible, until it returns [`ComputeFlow::Exit`].
+    fn on_update(&mut self, dt: Duration, context: &mut ComputeContext) -> ComputeFlow;
+}
+
+/// Runs a [`ComputeApplicationState`] without any window, event loop or swapchain (see
+/// [`RendererBuilder::compute_only`]), e.g. for GPU simulations or batch processing on machines
+/// without a display.
+///
+/// The state dispatches its work from `on_update` (e.g. with
+/// [`crate::compute_shader::ComputeShader::run`], which waits for the dispatch to complete) and
+/// reads the results back from host visible buffers. As it is borrowed, the state still holds
+/// these results once the application exits.
+///
+/// The application name, version and `prefer_software` settings of the
+/// [`ApplicationConfiguration`] are used, the window and presentation ones are ignored.
+pub struct ComputeApplication;
+
+impl ComputeApplication {
+    pub fn run<State>(app_config: ApplicationConfiguration, state: &mut State)
+    where
+        State: ComputeApplicationState,
+    {
+        let mut renderer_builder = RendererBuilder::compute_only()
+            .with_name(&app_config.application_name)
+            .with_version(
+                app_config.version.0,
+                app_config.version.1,
+                app_config.version.2,
+            );
+        if app_config.prefer_software {
+            renderer_builder = renderer_builder.prefer_software();
+        }
+        let renderer_ref = renderer_builder.build();
+        let mut renderer = renderer_ref.lock();
+
+        let start_time = Instant::now();
+        let mut prev_time = start_time;
+        let mut iteration = 0;
+
+        state.on_attach(&mut ComputeContext {
+            renderer: &mut renderer,
+            iteration,
+            total_elapsed: Duration::ZERO,
+        });
+
+        loop {
+            profiling::scope!("compute loop");
+            let delta = prev_time.elapsed();
+            prev_time = Instant::now();
+
+            let flow = state.on_update(
+                delta,
+                &mut ComputeContext {
+                    renderer: &mut renderer,
+                    iteration,
+                    total_elapsed: start_time.elapsed(),
+                },
+            );
+            iteration += 1;
+            profiling::finish_frame!();
+
+            if let ComputeFlow::Exit = flow {
+                break;
+            }
+        }
+
+        unsafe { renderer.device.device_wait_idle() }.expect("Failed to wait for device");
+        state.on_drop(&mut ComputeContext {
+            renderer: &mut renderer,
+            iteration,
+            total_elapsed: start_time.elapsed(),
+        });
+        log::debug!("Compute application shut down");
+    }
+}
@@ -0,0 +1,116 @@
+use thiserror::Error;
+
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    task::Poll,
+    thread::JoinHandle,
+};
+
+/// Progress of an [`AsyncLoad`], shared between the loading thread and the application.
+#[derive(Debug, Clone, Default)]
+pub struct LoadProgress(Arc<AtomicU32>);
+
+impl LoadProgress {
+    /// Sets the completion of the load, clamped between 0 and 1.
+    pub fn set(&self, progress: f32) {
+        self.0
+            .store(progress.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum AsyncLoadError {
+    #[error("The loading thread panicked with message: \"{0}\".")]
+    LoaderPanicked(String),
+
+    #[error("The result of this load was already retrieved.")]
+    AlreadyRetrieved,
+}
+
+/// Handle to a value being loaded on a worker thread, polled from the main thread.
+///
+/// This is meant to keep the application responsive while loading heavy content: a loading state
+/// spawns the load in `on_attach`, draws its progress (e.g. in `on_update_egui`), and polls the
+/// handle in `flow`, which is evaluated every frame. Once the load is ready, `flow` returns
+/// [`crate::application::StateFlow::SwitchState`] with the state built from the loaded value.
+///
+/// Loads that create GPU resources need the renderer from the worker thread, which can be done by
+/// locking the reference returned by [`crate::ecs_manager::ECSManager::renderer_ref`]. The main
+/// thread only releases the renderer between the steps of a frame, so the load should lock it for
+/// each upload rather than for its whole duration.
+pub struct AsyncLoad<T> {
+    handle: Option<JoinHandle<T>>,
+    progress: LoadProgress,
+}
+
+impl<T> AsyncLoad<T>
+where
+    T: Send + 'static,
+{
+    /// Runs `loader` on a new thread. The loader can report its completion through the given
+    /// [`LoadProgress`].
+    pub fn spawn<F>(loader: F) -> Self
+    where
+        F: FnOnce(&LoadProgress) -> T + Send + 'static,
+    {
+        let progress = LoadProgress::default();
+        let loader_progress = progress.clone();
+        let handle = std::thread::spawn(move || {
+            let value = loader(&loader_progress);
+            loader_progress.set(1.0);
+
+            value
+        });
+
+        Self {
+            handle: Some(handle),
+            progress,
+        }
+    }
+
+    /// Completion of the load reported by the loader, between 0 and 1.
+    pub fn progress(&self) -> f32 {
+        self.progress.get()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.as_ref().is_none_or(JoinHandle::is_finished)
+    }
+
+    /// Returns the loaded value once the loader is done, without blocking. The value can only be
+    /// retrieved once, later polls return [`AsyncLoadError::AlreadyRetrieved`].
+    pub fn poll(&mut self) -> Poll<Result<T, AsyncLoadError>> {
+        if !self.is_finished() {
+            return Poll::Pending;
+        }
+
+        Poll::Ready(self.join())
+    }
+
+    /// Blocks until the loader is done, and returns the loaded value. This must not be called while
+    /// holding a lock the loader needs (e.g. from a state callback if the loader uses the renderer).
+    pub fn wait(mut self) -> Result<T, AsyncLoadError> {
+        self.join()
+    }
+
+    fn join(&mut self) -> Result<T, AsyncLoadError> {
+        let handle = self.handle.take().ok_or(AsyncLoadError::AlreadyRetrieved)?;
+
+        handle.join().map_err(|payload| {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic payload".to_owned());
+
+            AsyncLoadError::LoaderPanicked(message)
+        })
+    }
+}
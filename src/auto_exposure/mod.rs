@@ -0,0 +1,296 @@
+use ash::vk;
+use bytemuck::{bytes_of, cast_slice, Pod, Zeroable};
+use thiserror::Error;
+
+use std::time::Duration;
+
+use crate::{
+    allocated_types::{AllocatedBuffer, BufferBuildError},
+    components::camera::Camera,
+    compute_shader::{ComputeShader, ComputeShaderBuildError},
+    descriptor_resources::{DescriptorResources, ResourceBindingError},
+    renderer::Renderer,
+    texture::Texture,
+    utils::{ImmediateCommandError, ThreadSafeRef},
+};
+
+/// Number of bins of the luminance histogram, which is also the workgroup size of the metering
+/// shader (16x16 invocations).
+pub const HISTOGRAM_BIN_COUNT: usize = 256;
+const WORKGROUP_SIZE: u32 = 16;
+const HISTOGRAM_SIZE: usize = HISTOGRAM_BIN_COUNT * std::mem::size_of::<u32>();
+
+/// `log2(100 / 12.5)`, converts the log2 of an average luminance to the EV100 that exposes it as
+/// middle grey (12.5 being the usual reflected-light meter calibration constant).
+const MIDDLE_GREY_EV_OFFSET: f32 = 3.0;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct MeteringData {
+    min_log_luminance: f32,
+    inverse_log_luminance_range: f32,
+}
+unsafe impl Zeroable for MeteringData {}
+unsafe impl Pod for MeteringData {}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AutoExposureSettings {
+    /// Range of the histogram, in log2 of the luminance. Pixels outside of it are clamped to the
+    /// first or last bin, and pixels darker than `0.0001` are ignored.
+    pub min_log_luminance: f32,
+    pub max_log_luminance: f32,
+    /// Speed at which the exposure converges towards the metered one, per second. The remaining
+    /// difference is multiplied by `exp(-adaptation_speed * dt)` every update, so with the default
+    /// of 1.5 about 78% of a sudden lighting change is compensated after a second.
+    pub adaptation_speed: f32,
+    /// Bounds of the computed exposure, in EV100.
+    pub min_exposure: f32,
+    pub max_exposure: f32,
+    /// Added to the metered exposure, in EV: positive values darken the image.
+    pub exposure_compensation: f32,
+}
+
+impl Default for AutoExposureSettings {
+    fn default() -> Self {
+        Self {
+            min_log_luminance: -10.0,
+            max_log_luminance: 6.0,
+            adaptation_speed: 1.5,
+            min_exposure: -6.0,
+            max_exposure: 18.0,
+            exposure_compensation: 0.0,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum AutoExposureBuildError {
+    #[error("Creation of the luminance histogram buffer failed with error: {0}.")]
+    BufferCreationFailed(#[from] BufferBuildError),
+
+    #[error("Creation of the luminance metering compute shader failed with error: {0}.")]
+    ComputeShaderCreationFailed(#[from] ComputeShaderBuildError),
+}
+
+#[derive(Error, Debug)]
+pub enum AutoExposureUpdateError {
+    #[error("Submission of the luminance metering pass failed with error: {0}.")]
+    MeteringFailed(#[from] ImmediateCommandError),
+
+    #[error("Failed to map the memory of the luminance histogram buffer.")]
+    MemoryMappingFailed,
+}
+
+/// Adapts a camera's exposure (see [`Camera::set_exposure`]) to the luminance of an HDR image.
+///
+/// Every [`AutoExposure::update`] runs a compute pass building a histogram of the log luminance of
+/// the image ([`HISTOGRAM_BIN_COUNT`] bins, read back to the CPU), from which the average
+/// luminance and the matching exposure are computed. The camera's exposure is then moved towards
+/// that target according to [`AutoExposureSettings::adaptation_speed`], mimicking the eye's
+/// adaptation. The tone mapping shader is expected to scale its input by
+/// [`Camera::exposure_multiplier`] (see [`crate::tone_mapping::ToneMappingSettings::exposure`]).
+pub struct AutoExposure {
+    pub settings: AutoExposureSettings,
+
+    image_dimensions: [u32; 2],
+    histogram: [u32; HISTOGRAM_BIN_COUNT],
+    histogram_buffer_ref: ThreadSafeRef<AllocatedBuffer>,
+    compute_shader_ref: ThreadSafeRef<ComputeShader>,
+}
+
+#[profiling::all_functions]
+impl AutoExposure {
+    /// `hdr_texture` is usually the texture of an HDR [`crate::render_target::RenderTarget`], it
+    /// must be in `SHADER_READ_ONLY_OPTIMAL` layout when the metering pass runs.
+    pub fn new(
+        hdr_texture: &ThreadSafeRef<Texture>,
+        settings: AutoExposureSettings,
+        renderer: &mut Renderer,
+    ) -> Result<Self, AutoExposureBuildError> {
+        let histogram_buffer = AllocatedBuffer::builder(HISTOGRAM_SIZE as u64)
+            .with_name("Luminance histogram")
+            .with_usage(vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST)
+            .with_memory_location(gpu_allocator::MemoryLocation::GpuToCpu)
+            .build(renderer)?;
+        let histogram_buffer_ref = ThreadSafeRef::new(histogram_buffer);
+
+        let compute_shader_ref = ComputeShader::builder().build_from_spirv_u8(
+            include_bytes!("shaders/gen/luminance_histogram.comp"),
+            DescriptorResources {
+                sampled_images: [(0, hdr_texture.clone())].into(),
+                storage_buffers: [(1, histogram_buffer_ref.clone())].into(),
+                ..Default::default()
+            },
+            renderer,
+        )?;
+
+        Ok(Self {
+            settings,
+            image_dimensions: hdr_texture.lock().dimensions,
+            histogram: [0; HISTOGRAM_BIN_COUNT],
+            histogram_buffer_ref,
+            compute_shader_ref,
+        })
+    }
+
+    /// Histogram computed by the last metering pass. Bin 0 counts the black pixels, the other bins
+    /// evenly split the `[min_log_luminance, max_log_luminance]` range.
+    #[profiling::skip]
+    pub fn histogram(&self) -> &[u32; HISTOGRAM_BIN_COUNT] {
+        &self.histogram
+    }
+
+    /// Binds another image to meter (e.g. after the HDR target was recreated on resize), and
+    /// returns the previous one.
+    pub fn set_hdr_texture(
+        &mut self,
+        hdr_texture: ThreadSafeRef<Texture>,
+        renderer: &mut Renderer,
+    ) -> Result<ThreadSafeRef<Texture>, ResourceBindingError> {
+        self.image_dimensions = hdr_texture.lock().dimensions;
+        self.compute_shader_ref
+            .lock()
+            .bind_texture(0, hdr_texture, renderer)
+    }
+
+    /// Runs the metering pass and returns the average luminance of the image, black pixels
+    /// excluded. This must be called outside of the render pass (e.g. in `on_update`).
+    pub fn meter(&mut self, renderer: &mut Renderer) -> Result<f32, AutoExposureUpdateError> {
+        let settings = &self.settings;
+        let log_luminance_range =
+            (settings.max_log_luminance - settings.min_log_luminance).max(f32::EPSILON);
+        let metering_data = MeteringData {
+            min_log_luminance: settings.min_log_luminance,
+            inverse_log_luminance_range: log_luminance_range.recip(),
+        };
+        let group_count = self
+            .image_dimensions
+            .map(|size| size.div_ceil(WORKGROUP_SIZE));
+
+        let compute_shader = self.compute_shader_ref.lock();
+        let histogram_handle = self.histogram_buffer_ref.lock().handle;
+
+        renderer.immediate_command(|cmd_buffer| unsafe {
+            let device = &renderer.device;
+
+            device.cmd_fill_buffer(*cmd_buffer, histogram_handle, 0, vk::WHOLE_SIZE, 0);
+            let reset_barrier = vk::BufferMemoryBarrier::default()
+                .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                .dst_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .buffer(histogram_handle)
+                .size(vk::WHOLE_SIZE);
+            device.cmd_pipeline_barrier(
+                *cmd_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                std::slice::from_ref(&reset_barrier),
+                &[],
+            );
+
+            device.cmd_bind_pipeline(
+                *cmd_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                compute_shader.pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                *cmd_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                compute_shader.layout,
+                0,
+                &[compute_shader.descriptor_set],
+                &[],
+            );
+            device.cmd_push_constants(
+                *cmd_buffer,
+                compute_shader.layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                bytes_of(&metering_data),
+            );
+            device.cmd_dispatch(*cmd_buffer, group_count[0], group_count[1], 1);
+
+            let readback_barrier = vk::BufferMemoryBarrier::default()
+                .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+                .dst_access_mask(vk::AccessFlags::HOST_READ)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .buffer(histogram_handle)
+                .size(vk::WHOLE_SIZE);
+            device.cmd_pipeline_barrier(
+                *cmd_buffer,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(),
+                &[],
+                std::slice::from_ref(&readback_barrier),
+                &[],
+            );
+        })?;
+        drop(compute_shader);
+
+        let histogram_buffer = self.histogram_buffer_ref.lock();
+        let mapped_slice = histogram_buffer
+            .allocation
+            .as_ref()
+            .and_then(|allocation| allocation.mapped_slice())
+            .ok_or(AutoExposureUpdateError::MemoryMappingFailed)?;
+        self.histogram
+            .copy_from_slice(cast_slice(&mapped_slice[..HISTOGRAM_SIZE]));
+
+        // Weighted average of the bins' log luminance, black pixels excluded
+        let (weighted_sum, pixel_count) = self.histogram[1..].iter().enumerate().fold(
+            (0.0, 0),
+            |(weighted_sum, pixel_count), (index, &count)| {
+                (
+                    weighted_sum + index as f32 * count as f32,
+                    pixel_count + count,
+                )
+            },
+        );
+        if pixel_count == 0 {
+            return Ok(0.0);
+        }
+        let average_bin = weighted_sum / pixel_count as f32;
+        let average_log_luminance = average_bin / (HISTOGRAM_BIN_COUNT - 2) as f32
+            * log_luminance_range
+            + settings.min_log_luminance;
+
+        Ok(average_log_luminance.exp2())
+    }
+
+    /// Meters the image and moves the camera's exposure towards the matching one, `dt` being the
+    /// time elapsed since the last update.
+    pub fn update(
+        &mut self,
+        camera: &mut Camera,
+        dt: Duration,
+        renderer: &mut Renderer,
+    ) -> Result<(), AutoExposureUpdateError> {
+        let average_luminance = self.meter(renderer)?;
+        let settings = &self.settings;
+        // A fully black image has no meaningful exposure, keep the current one
+        if average_luminance <= 0.0 {
+            return Ok(());
+        }
+
+        let target_exposure =
+            (average_luminance.log2() + MIDDLE_GREY_EV_OFFSET + settings.exposure_compensation)
+                .clamp(settings.min_exposure, settings.max_exposure);
+        let blend = 1.0 - (-settings.adaptation_speed * dt.as_secs_f32()).exp();
+        let exposure = camera.exposure() + (target_exposure - camera.exposure()) * blend;
+        camera.set_exposure(exposure);
+
+        Ok(())
+    }
+
+    pub fn destroy(&mut self, renderer: &mut Renderer) {
+        self.compute_shader_ref.lock().destroy(renderer);
+        self.histogram_buffer_ref
+            .lock()
+            .destroy(&renderer.device, &mut renderer.allocator());
+    }
+}
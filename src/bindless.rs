@@ -0,0 +1,215 @@
+use ash::{vk, Instance};
+use thiserror::Error;
+
+use crate::{
+    resource_registry::{self, ResourceKind},
+    texture::Texture,
+};
+
+/// Descriptor set of the bindless texture array, bound for every material when the device
+/// supports it (see [`crate::renderer::Renderer::register_bindless_texture`]). Shaders declare it
+/// as:
+/// ```glsl
+/// #extension GL_EXT_nonuniform_qualifier : require
+/// layout(set = 4, binding = 0) uniform sampler2D textures[];
+/// ...
+/// texture(textures[nonuniformEXT(index)], uv)
+/// ```
+pub const BINDLESS_SET: u32 = 4;
+/// Binding of the texture array in [`BINDLESS_SET`].
+pub const BINDLESS_TEXTURES_BINDING: u32 = 0;
+
+/// Size of the texture array, if the device allows that many update after bind samplers.
+const MAX_BINDLESS_TEXTURES: u32 = 16384;
+
+#[derive(Error, Debug)]
+pub enum BindlessTextureError {
+    #[error("The device does not support descriptor indexing, which bindless textures require.")]
+    Unsupported,
+
+    #[error("All the {0} slots of the bindless texture array are used.")]
+    ArrayFull(u32),
+}
+
+#[derive(Error, Debug)]
+pub enum BindlessCreationError {
+    #[error("Vulkan creation of the bindless descriptor set layout failed with result: {0}.")]
+    VulkanDSLCreationFailed(vk::Result),
+
+    #[error("Vulkan creation of the bindless descriptor pool failed with result: {0}.")]
+    VulkanDescriptorPoolCreationFailed(vk::Result),
+
+    #[error("Vulkan allocation of the bindless descriptor set failed with result: {0}.")]
+    VulkanDescriptorSetAllocationFailed(vk::Result),
+}
+
+/// Whether the device supports the descriptor indexing features the texture array requires, and
+/// enough bound descriptor sets for the array's set to come after the per-object one.
+pub(crate) fn is_supported(instance: &Instance, physical_device: vk::PhysicalDevice) -> bool {
+    let mut vk12features = vk::PhysicalDeviceVulkan12Features::default();
+    let mut features = vk::PhysicalDeviceFeatures2::default().push_next(&mut vk12features);
+    unsafe { instance.get_physical_device_features2(physical_device, &mut features) };
+    let limits = unsafe { instance.get_physical_device_properties(physical_device) }.limits;
+
+    limits.max_bound_descriptor_sets > BINDLESS_SET
+        && vk12features.descriptor_indexing == vk::TRUE
+        && vk12features.shader_sampled_image_array_non_uniform_indexing == vk::TRUE
+        && vk12features.descriptor_binding_sampled_image_update_after_bind == vk::TRUE
+        && vk12features.descriptor_binding_update_unused_while_pending == vk::TRUE
+        && vk12features.descriptor_binding_partially_bound == vk::TRUE
+        && vk12features.runtime_descriptor_array == vk::TRUE
+}
+
+/// Enables the features checked by [`is_supported`].
+pub(crate) fn enable_features(vk12features: &mut vk::PhysicalDeviceVulkan12Features) {
+    vk12features.descriptor_indexing = vk::TRUE;
+    vk12features.shader_sampled_image_array_non_uniform_indexing = vk::TRUE;
+    vk12features.descriptor_binding_sampled_image_update_after_bind = vk::TRUE;
+    vk12features.descriptor_binding_update_unused_while_pending = vk::TRUE;
+    vk12features.descriptor_binding_partially_bound = vk::TRUE;
+    vk12features.runtime_descriptor_array = vk::TRUE;
+}
+
+/// [`MAX_BINDLESS_TEXTURES`], lowered to the device's update after bind limits.
+pub(crate) fn texture_capacity(instance: &Instance, physical_device: vk::PhysicalDevice) -> u32 {
+    let mut vk12properties = vk::PhysicalDeviceVulkan12Properties::default();
+    let mut properties = vk::PhysicalDeviceProperties2::default().push_next(&mut vk12properties);
+    unsafe { instance.get_physical_device_properties2(physical_device, &mut properties) };
+
+    [
+        MAX_BINDLESS_TEXTURES,
+        vk12properties.max_descriptor_set_update_after_bind_sampled_images,
+        vk12properties.max_descriptor_set_update_after_bind_samplers,
+        vk12properties.max_per_stage_descriptor_update_after_bind_sampled_images,
+        vk12properties.max_per_stage_descriptor_update_after_bind_samplers,
+    ]
+    .into_iter()
+    .min()
+    .unwrap_or(0)
+}
+
+/// Single array of combined image samplers shared by all the materials, which textures are
+/// written to once instead of being bound to every material and mesh rendering using them.
+///
+/// The array is partially bound, only the slots of registered textures may be sampled.
+#[derive(Debug)]
+pub(crate) struct BindlessTextures {
+    pub(crate) layout: vk::DescriptorSetLayout,
+    pool: vk::DescriptorPool,
+    pub(crate) set: vk::DescriptorSet,
+    capacity: u32,
+    /// First index never handed out, every index after it is free.
+    next_index: u32,
+    /// Indices released by [`BindlessTextures::unregister`], reused first.
+    free_indices: Vec<u32>,
+}
+
+#[profiling::all_functions]
+impl BindlessTextures {
+    pub(crate) fn new(capacity: u32, device: &ash::Device) -> Result<Self, BindlessCreationError> {
+        let binding = vk::DescriptorSetLayoutBinding::default()
+            .binding(BINDLESS_TEXTURES_BINDING)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(capacity)
+            .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT);
+        let binding_flags = [vk::DescriptorBindingFlags::UPDATE_AFTER_BIND
+            | vk::DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING
+            | vk::DescriptorBindingFlags::PARTIALLY_BOUND];
+        let mut binding_flags_info =
+            vk::DescriptorSetLayoutBindingFlagsCreateInfo::default().binding_flags(&binding_flags);
+        let layout_info = vk::DescriptorSetLayoutCreateInfo::default()
+            .flags(vk::DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL)
+            .bindings(std::slice::from_ref(&binding))
+            .push_next(&mut binding_flags_info);
+        let layout = unsafe { device.create_descriptor_set_layout(&layout_info, None) }
+            .map_err(BindlessCreationError::VulkanDSLCreationFailed)?;
+
+        let pool_size = vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: capacity,
+        };
+        let pool_info = vk::DescriptorPoolCreateInfo::default()
+            .flags(vk::DescriptorPoolCreateFlags::UPDATE_AFTER_BIND)
+            .max_sets(1)
+            .pool_sizes(std::slice::from_ref(&pool_size));
+        let pool = match unsafe { device.create_descriptor_pool(&pool_info, None) } {
+            Ok(pool) => pool,
+            Err(result) => {
+                unsafe { device.destroy_descriptor_set_layout(layout, None) };
+                return Err(BindlessCreationError::VulkanDescriptorPoolCreationFailed(
+                    result,
+                ));
+            }
+        };
+
+        let set_info = vk::DescriptorSetAllocateInfo::default()
+            .descriptor_pool(pool)
+            .set_layouts(std::slice::from_ref(&layout));
+        let set = match unsafe { device.allocate_descriptor_sets(&set_info) } {
+            Ok(sets) => sets[0],
+            Err(result) => {
+                unsafe {
+                    device.destroy_descriptor_pool(pool, None);
+                    device.destroy_descriptor_set_layout(layout, None);
+                }
+                return Err(BindlessCreationError::VulkanDescriptorSetAllocationFailed(
+                    result,
+                ));
+            }
+        };
+        resource_registry::register(ResourceKind::DescriptorSet, 1);
+
+        Ok(Self {
+            layout,
+            pool,
+            set,
+            capacity,
+            next_index: 0,
+            free_indices: vec![],
+        })
+    }
+
+    pub(crate) fn register(
+        &mut self,
+        texture: &Texture,
+        device: &ash::Device,
+    ) -> Result<u32, BindlessTextureError> {
+        let index = match self.free_indices.pop() {
+            Some(index) => index,
+            None if self.next_index < self.capacity => {
+                self.next_index += 1;
+                self.next_index - 1
+            }
+            None => return Err(BindlessTextureError::ArrayFull(self.capacity)),
+        };
+
+        let image_info = vk::DescriptorImageInfo::default()
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .image_view(texture.image_ref.lock().view)
+            .sampler(texture.sampler);
+        let write = vk::WriteDescriptorSet::default()
+            .dst_set(self.set)
+            .dst_binding(BINDLESS_TEXTURES_BINDING)
+            .dst_array_element(index)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(std::slice::from_ref(&image_info));
+        unsafe { device.update_descriptor_sets(std::slice::from_ref(&write), &[]) };
+
+        Ok(index)
+    }
+
+    /// The slot keeps referencing the texture until it is reused, it must not be sampled anymore.
+    pub(crate) fn unregister(&mut self, index: u32) {
+        if index < self.next_index && !self.free_indices.contains(&index) {
+            self.free_indices.push(index);
+        }
+    }
+
+    pub(crate) fn destroy(&mut self, device: &ash::Device) {
+        unsafe {
+            device.destroy_descriptor_pool(self.pool, None);
+            device.destroy_descriptor_set_layout(self.layout, None);
+        }
+        resource_registry::unregister(ResourceKind::DescriptorSet, 1);
+    }
+}
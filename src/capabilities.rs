@@ -0,0 +1,148 @@
+use ash::vk;
+
+/// Commonly needed limits of the selected device, and the optional features that were actually
+/// enabled on it. See [`crate::renderer::Renderer::capabilities`].
+///
+/// For anything not summarized here, the raw limits are available in
+/// [`crate::renderer::Renderer::device_properties`].
+#[derive(Debug, Clone, Copy)]
+pub struct Capabilities {
+    pub limits: CapabilitiesLimits,
+    pub features: CapabilitiesFeatures,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CapabilitiesLimits {
+    pub max_image_dimension_2d: u32,
+    pub max_image_dimension_cube: u32,
+    pub max_image_array_layers: u32,
+
+    pub max_push_constants_size: u32,
+    pub max_bound_descriptor_sets: u32,
+    pub max_per_stage_descriptor_samplers: u32,
+    pub max_per_stage_descriptor_sampled_images: u32,
+    pub max_per_stage_descriptor_uniform_buffers: u32,
+    pub max_per_stage_descriptor_storage_buffers: u32,
+    pub max_uniform_buffer_range: u32,
+    pub max_storage_buffer_range: u32,
+    pub min_uniform_buffer_offset_alignment: u64,
+
+    pub max_vertex_input_attributes: u32,
+
+    pub max_compute_work_group_count: [u32; 3],
+    pub max_compute_work_group_size: [u32; 3],
+    pub max_compute_work_group_invocations: u32,
+
+    pub max_sampler_anisotropy: f32,
+    pub framebuffer_color_sample_counts: vk::SampleCountFlags,
+    pub framebuffer_depth_sample_counts: vk::SampleCountFlags,
+
+    /// Minimum and maximum supported line widths, only relevant if `wide_lines` is enabled.
+    pub line_width_range: [f32; 2],
+    pub line_width_granularity: f32,
+
+    /// Nanoseconds per timestamp query tick, 0.0 if timestamps are not supported.
+    pub timestamp_period: f32,
+}
+
+/// Optional features enabled on the device. A feature being supported by the hardware doesn't mean
+/// it is listed here, only the ones the renderer requested are.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CapabilitiesFeatures {
+    /// Requires the `ray_tracing` cargo feature.
+    pub ray_tracing: bool,
+    pub sampler_anisotropy: bool,
+    pub geometry_shader: bool,
+    pub variable_rate_shading: bool,
+    pub large_points: bool,
+    /// Allows the `LINE` and `POINT` polygon modes (see
+    /// [`crate::material::MaterialBuilder::polygon_mode`]).
+    pub fill_mode_non_solid: bool,
+    /// Allows lines wider than a single pixel (see [`crate::material::MaterialBuilder::line_width`]).
+    pub wide_lines: bool,
+    /// `VK_EXT_line_rasterization` modes, see [`crate::material::LineMode`].
+    pub rectangular_lines: bool,
+    pub smooth_lines: bool,
+    pub draw_indirect_count: bool,
+    /// Allows indirect draw calls of more than one draw (see
+    /// [`crate::indirect_batch::IndirectBatch`]).
+    pub multi_draw_indirect: bool,
+    /// Allows indirect draws with a non-zero `first_instance`.
+    pub draw_indirect_first_instance: bool,
+    /// Allows BC compressed textures (see
+    /// [`crate::texture::TextureBuilder::build_from_ktx2`]).
+    pub texture_compression_bc: bool,
+    pub timestamp_queries: bool,
+    /// Allows [`crate::renderer::RendererBuilder::with_pipeline_statistics`].
+    pub pipeline_statistics_query: bool,
+    /// Requires the `bindless` cargo feature, see
+    /// `crate::renderer::Renderer::register_bindless_texture`.
+    pub bindless_textures: bool,
+}
+
+impl Capabilities {
+    pub(crate) fn new(
+        device_properties: &vk::PhysicalDeviceProperties,
+        enabled_features: &vk::PhysicalDeviceFeatures,
+        line_rasterization_features: &vk::PhysicalDeviceLineRasterizationFeaturesEXT,
+        draw_indirect_count: bool,
+    ) -> Self {
+        let limits = &device_properties.limits;
+        let timestamp_queries = limits.timestamp_compute_and_graphics == vk::TRUE;
+
+        Self {
+            limits: CapabilitiesLimits {
+                max_image_dimension_2d: limits.max_image_dimension2_d,
+                max_image_dimension_cube: limits.max_image_dimension_cube,
+                max_image_array_layers: limits.max_image_array_layers,
+                max_push_constants_size: limits.max_push_constants_size,
+                max_bound_descriptor_sets: limits.max_bound_descriptor_sets,
+                max_per_stage_descriptor_samplers: limits.max_per_stage_descriptor_samplers,
+                max_per_stage_descriptor_sampled_images: limits
+                    .max_per_stage_descriptor_sampled_images,
+                max_per_stage_descriptor_uniform_buffers: limits
+                    .max_per_stage_descriptor_uniform_buffers,
+                max_per_stage_descriptor_storage_buffers: limits
+                    .max_per_stage_descriptor_storage_buffers,
+                max_uniform_buffer_range: limits.max_uniform_buffer_range,
+                max_storage_buffer_range: limits.max_storage_buffer_range,
+                min_uniform_buffer_offset_alignment: limits.min_uniform_buffer_offset_alignment,
+                max_vertex_input_attributes: limits.max_vertex_input_attributes,
+                max_compute_work_group_count: limits.max_compute_work_group_count,
+                max_compute_work_group_size: limits.max_compute_work_group_size,
+                max_compute_work_group_invocations: limits.max_compute_work_group_invocations,
+                max_sampler_anisotropy: limits.max_sampler_anisotropy,
+                framebuffer_color_sample_counts: limits.framebuffer_color_sample_counts,
+                framebuffer_depth_sample_counts: limits.framebuffer_depth_sample_counts,
+                line_width_range: limits.line_width_range,
+                line_width_granularity: limits.line_width_granularity,
+                timestamp_period: if timestamp_queries {
+                    limits.timestamp_period
+                } else {
+                    0.0
+                },
+            },
+            features: CapabilitiesFeatures {
+                ray_tracing: cfg!(feature = "ray_tracing"),
+                sampler_anisotropy: enabled_features.sampler_anisotropy == vk::TRUE,
+                geometry_shader: enabled_features.geometry_shader == vk::TRUE,
+                // No fragment shading rate extension is requested by the renderer yet
+                variable_rate_shading: false,
+                large_points: enabled_features.large_points == vk::TRUE,
+                fill_mode_non_solid: enabled_features.fill_mode_non_solid == vk::TRUE,
+                wide_lines: enabled_features.wide_lines == vk::TRUE,
+                rectangular_lines: line_rasterization_features.rectangular_lines == vk::TRUE,
+                smooth_lines: line_rasterization_features.smooth_lines == vk::TRUE,
+                draw_indirect_count,
+                multi_draw_indirect: enabled_features.multi_draw_indirect == vk::TRUE,
+                draw_indirect_first_instance: enabled_features.draw_indirect_first_instance
+                    == vk::TRUE,
+                texture_compression_bc: enabled_features.texture_compression_bc == vk::TRUE,
+                timestamp_queries,
+                pipeline_statistics_query: enabled_features.pipeline_statistics_query == vk::TRUE,
+                // Set by the renderer once the bindless texture array is created
+                bindless_textures: false,
+            },
+        }
+    }
+}
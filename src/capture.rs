@@ -0,0 +1,92 @@
+use ash::vk;
+use thiserror::Error;
+
+use crate::allocated_types::BufferBuildError;
+
+use std::path::Path;
+
+/// Pixels read back from the GPU, see [`crate::renderer::Renderer::capture_frame`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageData {
+    pub width: u32,
+    pub height: u32,
+    /// RGBA8 pixels, tightly packed rows starting with the top one.
+    pub pixels: Vec<u8>,
+}
+
+#[derive(Error, Debug)]
+pub enum CaptureError {
+    #[error("No capture was recorded since the last one was read, see Renderer::request_capture.")]
+    NoCaptureRecorded,
+
+    #[error("The swapchain images don't support being copied from (TRANSFER_SRC usage).")]
+    UnsupportedUsage,
+
+    #[error("The format {0:?} of the captured image can't be converted to RGBA8.")]
+    UnsupportedFormat(vk::Format),
+
+    #[error("Waiting for the render fence failed with result: {0}.")]
+    FenceWaitFailed(vk::Result),
+
+    #[error("Creation of the readback buffer failed with error: {0}.")]
+    BufferCreationFailed(#[from] BufferBuildError),
+
+    #[error("Failed to map the memory of the readback buffer.")]
+    MemoryMappingFailed,
+
+    #[error("Saving the capture failed with error: {0}.")]
+    ImageSavingFailed(#[from] image::ImageError),
+}
+
+impl ImageData {
+    /// Whether images of `format` can be captured, i.e. it is a 4 bytes per pixel format that can
+    /// be converted to RGBA8.
+    pub(crate) fn is_format_supported(format: vk::Format) -> bool {
+        matches!(
+            format,
+            vk::Format::R8G8B8A8_UNORM
+                | vk::Format::R8G8B8A8_SRGB
+                | vk::Format::B8G8R8A8_UNORM
+                | vk::Format::B8G8R8A8_SRGB
+        )
+    }
+
+    /// Converts tightly packed pixels of a 4 bytes per pixel format to RGBA8.
+    pub(crate) fn from_raw(
+        width: u32,
+        height: u32,
+        format: vk::Format,
+        mut pixels: Vec<u8>,
+    ) -> Result<Self, CaptureError> {
+        if !Self::is_format_supported(format) {
+            return Err(CaptureError::UnsupportedFormat(format));
+        }
+        if matches!(
+            format,
+            vk::Format::B8G8R8A8_UNORM | vk::Format::B8G8R8A8_SRGB
+        ) {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Saves the pixels to `path`, the format being deduced from its extension (e.g. `.png`).
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CaptureError> {
+        image::save_buffer(
+            path,
+            &self.pixels,
+            self.width,
+            self.height,
+            image::ExtendedColorType::Rgba8,
+        )?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,106 @@
+use bevy_ecs::component::Component;
+
+use crate::{animation::AnimationClip, utils::ThreadSafeRef};
+
+/// Plays an [`AnimationClip`] on the entity's [`crate::components::transform::Transform`] and
+/// [`crate::components::skeleton::Skeleton`], advanced every frame by
+/// [`crate::systems::animation::advance_players`].
+#[derive(Debug, Component)]
+pub struct AnimationPlayer {
+    clip_ref: Option<ThreadSafeRef<AnimationClip>>,
+    /// Playback position in the clip, in seconds.
+    time: f32,
+    paused: bool,
+    /// Multiplier of the frame time, negative values playing the clip backwards.
+    pub speed: f32,
+    /// Whether the clip restarts once it reaches its end, it stops on its last frame otherwise.
+    pub looping: bool,
+}
+
+impl Default for AnimationPlayer {
+    fn default() -> Self {
+        Self {
+            clip_ref: None,
+            time: 0.0,
+            paused: false,
+            speed: 1.0,
+            looping: true,
+        }
+    }
+}
+
+#[profiling::all_functions]
+impl AnimationPlayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts playing `clip_ref` from its beginning.
+    pub fn play(&mut self, clip_ref: ThreadSafeRef<AnimationClip>) {
+        self.clip_ref = Some(clip_ref);
+        self.time = 0.0;
+        self.paused = false;
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// Moves the playback position to `time` seconds, wrapped in the clip if it loops and
+    /// clamped to it otherwise. The pose is updated even if the player is paused.
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time;
+        self.wrap_time();
+    }
+
+    #[profiling::skip]
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    #[profiling::skip]
+    pub fn clip(&self) -> Option<&ThreadSafeRef<AnimationClip>> {
+        self.clip_ref.as_ref()
+    }
+
+    /// Whether a clip is being played, which is not the case when paused or once a clip that
+    /// doesn't loop reached its end.
+    pub fn is_playing(&self) -> bool {
+        !self.paused && !self.is_finished()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        let Some(clip_ref) = &self.clip_ref else {
+            return true;
+        };
+        let duration = clip_ref.lock().duration();
+
+        !self.looping
+            && ((self.speed >= 0.0 && self.time >= duration)
+                || (self.speed < 0.0 && self.time <= 0.0))
+    }
+
+    pub(crate) fn advance(&mut self, delta_time: f32) {
+        self.time += delta_time * self.speed;
+        self.wrap_time();
+    }
+
+    fn wrap_time(&mut self) {
+        let Some(clip_ref) = &self.clip_ref else {
+            return;
+        };
+        let duration = clip_ref.lock().duration();
+
+        self.time = if duration <= 0.0 {
+            0.0
+        } else if self.looping {
+            self.time.rem_euclid(duration)
+        } else {
+            self.time.clamp(0.0, duration)
+        };
+    }
+}
@@ -1,38 +1,80 @@
 use bevy_ecs::system::Resource;
+use winit::{event::MouseButton, event::WindowEvent, keyboard::KeyCode};
 
-use std::default::Default;
+use std::{default::Default, time::Duration};
 
 use crate::{
+    input::InputState,
     math_types::Quat,
-    math_types::{Mat4, Vec2, Vec3},
+    math_types::{Mat4, Vec2, Vec3, Vec4},
 };
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PerspectiveData {
     pub horizontal_fov: f32,
     pub near_plane: f32,
-    pub far_plane: f32,
+    /// `None` places the far plane at infinity, so that nothing is ever clipped for being too far
+    /// away (e.g. skyboxes or very large scenes). Depth precision stays close to the finite case.
+    pub far_plane: Option<f32>,
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OrthographicData {
+    /// Height of the visible area, in world units. Its width follows the aspect ratio, so that
+    /// resizing the camera keeps the vertical extent and widens or narrows the view (see
+    /// [`Camera::orthographic_bounds`]).
     pub scale: f32,
     pub near_plane: f32,
     pub far_plane: f32,
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Projection {
     Perspective(PerspectiveData),
     Orthographic(OrthographicData),
 }
 
+impl Projection {
+    pub fn near_plane(&self) -> f32 {
+        match self {
+            Projection::Perspective(data) => data.near_plane,
+            Projection::Orthographic(data) => data.near_plane,
+        }
+    }
+
+    /// Returns `None` if the far plane is at infinity.
+    pub fn far_plane(&self) -> Option<f32> {
+        match self {
+            Projection::Perspective(data) => data.far_plane,
+            Projection::Orthographic(data) => Some(data.far_plane),
+        }
+    }
+
+    /// Returns the same projection, with its far plane moved to `far_plane`.
+    pub fn with_far_plane(self, far_plane: f32) -> Self {
+        match self {
+            Projection::Perspective(data) => Projection::Perspective(PerspectiveData {
+                far_plane: Some(far_plane),
+                ..data
+            }),
+            Projection::Orthographic(data) => {
+                Projection::Orthographic(OrthographicData { far_plane, ..data })
+            }
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone, Copy)]
 pub struct CameraBuilder {
     pub position: Vec3,
     pub pitch: f32,
     pub yaw: f32,
     pub roll: f32,
+    /// See [`Camera::set_exposure`].
+    pub exposure: f32,
 }
 
 impl CameraBuilder {
@@ -64,6 +106,7 @@ impl CameraBuilder {
             view_projection,
 
             size: *size,
+            exposure: self.exposure,
         }
     }
 }
@@ -84,6 +127,7 @@ pub struct Camera {
     view_projection: Mat4,
 
     size: Vec2,
+    exposure: f32,
 }
 
 impl Default for Camera {
@@ -92,7 +136,7 @@ impl Default for Camera {
             Projection::Perspective(PerspectiveData {
                 horizontal_fov: f32::to_radians(90.0),
                 near_plane: 0.0001,
-                far_plane: 1000.0,
+                far_plane: Some(1000.0),
             }),
             &Vec2::new(1280.0, 720.0),
         )
@@ -106,30 +150,56 @@ impl Camera {
         CameraBuilder::new()
     }
 
+    /// Orthographic camera at the origin looking down -Z, with one world unit per pixel of `size`
+    /// (which suits 2D scenes and editor overlays). Like every projection, the resulting matrix
+    /// has a `[0, 1]` depth range, and relies on the renderer's flipped viewport (see
+    /// [`crate::renderer::RendererBuilder::with_flipped_viewport`]) for Y to point up.
+    pub fn orthographic(size: &Vec2, near_plane: f32, far_plane: f32) -> Self {
+        Self::builder().build(
+            Projection::Orthographic(OrthographicData {
+                scale: size.y,
+                near_plane,
+                far_plane,
+            }),
+            size,
+        )
+    }
+
     fn compute_orientation(pitch: f32, yaw: f32, roll: f32) -> Quat {
         Quat::from_euler(glam::EulerRot::YZX, pitch, yaw, roll)
     }
 
-    fn compute_projection(projection_type: &Projection, aspect_ratio: f32) -> Mat4 {
+    pub(crate) fn compute_projection(projection_type: &Projection, aspect_ratio: f32) -> Mat4 {
         match projection_type {
-            Projection::Perspective(data) => Mat4::perspective_rh(
-                data.horizontal_fov,
-                aspect_ratio,
-                data.near_plane,
-                data.far_plane,
-            ),
+            Projection::Perspective(data) => match data.far_plane {
+                Some(far_plane) => Mat4::perspective_rh(
+                    data.horizontal_fov,
+                    aspect_ratio,
+                    data.near_plane,
+                    far_plane,
+                ),
+                None => Mat4::perspective_infinite_rh(
+                    data.horizontal_fov,
+                    aspect_ratio,
+                    data.near_plane,
+                ),
+            },
             Projection::Orthographic(data) => {
-                let right = data.scale * aspect_ratio * 0.5;
-                let left = -right;
-
-                let top = data.scale * 0.5;
-                let bottom = -top;
+                let [left, right, bottom, top] =
+                    Self::compute_orthographic_bounds(data, aspect_ratio);
 
                 Mat4::orthographic_rh(left, right, bottom, top, data.near_plane, data.far_plane)
             }
         }
     }
 
+    fn compute_orthographic_bounds(data: &OrthographicData, aspect_ratio: f32) -> [f32; 4] {
+        let right = data.scale * aspect_ratio * 0.5;
+        let top = data.scale * 0.5;
+
+        [-right, right, -top, top]
+    }
+
     fn compute_view(position: &Vec3, orientation: &Quat) -> Mat4 {
         let view_inverse = Mat4::from_rotation_translation(*orientation, *position);
         view_inverse.inverse()
@@ -154,6 +224,70 @@ impl Camera {
         &self.view_projection
     }
 
+    /// Returns the left, right, bottom, top, near and far planes of the camera's frustum, in world
+    /// space. Each plane is stored as `(normal, distance)` with the normal pointing inwards, so a
+    /// point `p` is inside the frustum if `plane.xyz().dot(p) + plane.w >= 0` for every plane.
+    ///
+    /// With an infinite far plane, the far plane is returned as `(0, 0, 0, 1)`, which contains
+    /// every point.
+    pub fn frustum_planes(&self) -> [Vec4; 6] {
+        let rows = self.view_projection.transpose();
+
+        [
+            rows.w_axis + rows.x_axis,
+            rows.w_axis - rows.x_axis,
+            rows.w_axis + rows.y_axis,
+            rows.w_axis - rows.y_axis,
+            // Vulkan's depth range is [0, 1], so the near plane is the third row alone
+            rows.z_axis,
+            rows.w_axis - rows.z_axis,
+        ]
+        .map(|plane| {
+            let normal_length = plane.truncate().length();
+            if normal_length > f32::EPSILON {
+                plane / normal_length
+            } else {
+                Vec4::W
+            }
+        })
+    }
+
+    /// Returns the origin and direction, in world space, of the ray going from the camera through
+    /// `screen_position`, in pixels from the top left corner of the camera's area (see
+    /// [`Camera::size`]). The origin is on the near plane.
+    ///
+    /// The Y axis of the NDC is assumed to point up, as with the renderer's default flipped
+    /// viewport (see [`crate::renderer::RendererBuilder::with_flipped_viewport`]).
+    pub fn screen_ray(&self, screen_position: Vec2) -> (Vec3, Vec3) {
+        let ndc = Vec2::new(
+            2.0 * screen_position.x / self.size.x - 1.0,
+            1.0 - 2.0 * screen_position.y / self.size.y,
+        );
+        let inverse_view_projection = self.view_projection.inverse();
+        // The depth only reaches 1 at infinity with an infinite far plane
+        let near = inverse_view_projection.project_point3(ndc.extend(0.0));
+        let further = inverse_view_projection.project_point3(ndc.extend(0.5));
+
+        (near, (further - near).normalize())
+    }
+
+    /// Left, right, bottom and top bounds of the visible area in view space, or `None` for a
+    /// perspective camera. They are recomputed from the aspect ratio whenever the camera is
+    /// resized.
+    pub fn orthographic_bounds(&self) -> Option<[f32; 4]> {
+        match &self.projection_type {
+            Projection::Orthographic(data) => {
+                Some(Self::compute_orthographic_bounds(data, self.aspect_ratio))
+            }
+            Projection::Perspective(_) => None,
+        }
+    }
+
+    #[profiling::skip]
+    pub fn projection_type(&self) -> &Projection {
+        &self.projection_type
+    }
+
     #[profiling::skip]
     pub fn position(&self) -> &Vec3 {
         &self.position
@@ -184,6 +318,27 @@ impl Camera {
         &self.size
     }
 
+    #[profiling::skip]
+    pub fn exposure(&self) -> f32 {
+        self.exposure
+    }
+
+    /// Factor the HDR color should be multiplied by before tone mapping, derived from the
+    /// exposure as `1 / (1.2 * 2^ev)` (the maximum luminance that doesn't saturate a sensor at
+    /// ISO 100 is `1.2 * 2^ev`).
+    #[profiling::skip]
+    pub fn exposure_multiplier(&self) -> f32 {
+        (1.2 * self.exposure.exp2()).recip()
+    }
+
+    /// Sets the exposure in EV100: higher values darken the image, each step halving the amount
+    /// of light. Around 15 suits a sunny outdoor scene and 6 a dim interior. The exposure isn't
+    /// used by the renderer directly, see [`Camera::exposure_multiplier`] and
+    /// [`crate::auto_exposure::AutoExposure`].
+    pub fn set_exposure(&mut self, ev: f32) {
+        self.exposure = ev;
+    }
+
     pub fn set_projection_type(&mut self, projection_type: Projection) {
         self.projection_type = projection_type;
         self.projection = Self::compute_projection(&self.projection_type, self.aspect_ratio);
@@ -242,3 +397,266 @@ impl Camera {
         self.set_size(&Vec2::new(width as f32, height as f32));
     }
 }
+
+/// Modifier key suspending the mouse and keyboard controls of a camera controller while held, so
+/// that they can be used for something else (e.g. manipulating a gizmo).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControllerModifier {
+    Alt,
+    Control,
+    Shift,
+}
+
+impl ControllerModifier {
+    fn is_held(self, input: &InputState) -> bool {
+        match self {
+            Self::Alt => input.held_alt(),
+            Self::Control => input.held_control(),
+            Self::Shift => input.held_shift(),
+        }
+    }
+}
+
+/// Moves `camera` along its own axes with WASD (forward and sideways) and QE (up and down),
+/// `distance` units at most.
+fn keyboard_translation(camera: &Camera, input: &InputState, distance: f32) -> Vec3 {
+    let mut translation = Vec3::ZERO;
+    for (key, direction) in [
+        (KeyCode::KeyW, camera.forward_vector()),
+        (KeyCode::KeyS, -camera.forward_vector()),
+        (KeyCode::KeyA, camera.right_vector()),
+        (KeyCode::KeyD, -camera.right_vector()),
+        (KeyCode::KeyQ, camera.up_vector()),
+        (KeyCode::KeyE, -camera.up_vector()),
+    ] {
+        if input.key_held(key) {
+            translation += direction * distance;
+        }
+    }
+
+    translation
+}
+
+/// Orbits a camera around a focal point, in the style of 3D editors:
+/// - left mouse button: rotate around the focal point
+/// - right mouse button or scroll wheel: zoom (move towards the focal point)
+/// - middle mouse button: pan (move the focal point in the view plane)
+/// - WASD and QE: move the focal point along the camera's axes
+#[derive(Debug, Clone, Copy)]
+pub struct OrbitController {
+    pub camera: Camera,
+    /// Speed of the keyboard movements, in units per second.
+    pub move_speed: f32,
+    /// Scale applied to mouse movements (in pixels) before they are turned into zoom and pan.
+    pub mouse_sensitivity: f32,
+    /// Radians the camera rotates by per scaled unit of mouse movement (see
+    /// [`OrbitController::mouse_sensitivity`]).
+    pub rotate_sensitivity: f32,
+    /// Smallest and largest distances to the focal point, [`OrbitController::set_distance`] and
+    /// zooming are clamped to them.
+    pub zoom_range: (f32, f32),
+    /// See [`ControllerModifier`], nothing suspends the controls by default.
+    pub suspend_modifier: Option<ControllerModifier>,
+
+    distance: f32,
+    focal_point: Vec3,
+}
+
+#[profiling::all_functions]
+impl OrbitController {
+    /// Takes control of `camera`, which orbits the origin at a distance of 1.
+    pub fn new(camera: Camera) -> Self {
+        let mut controller = Self {
+            camera,
+            move_speed: 1.0,
+            mouse_sensitivity: 0.003,
+            rotate_sensitivity: 0.8,
+            zoom_range: (0.1, 100.0),
+            suspend_modifier: None,
+            distance: 1.0,
+            focal_point: Vec3::ZERO,
+        };
+        controller.update_position();
+
+        controller
+    }
+
+    #[profiling::skip]
+    pub fn distance(&self) -> f32 {
+        self.distance
+    }
+
+    pub fn set_distance(&mut self, distance: f32) {
+        self.distance = distance.clamp(self.zoom_range.0, self.zoom_range.1);
+        self.update_position();
+    }
+
+    #[profiling::skip]
+    pub fn focal_point(&self) -> &Vec3 {
+        &self.focal_point
+    }
+
+    pub fn set_focal_point(&mut self, focal_point: &Vec3) {
+        self.focal_point = *focal_point;
+        self.update_position();
+    }
+
+    pub fn on_update(&mut self, dt: Duration, input: &InputState) {
+        if self
+            .suspend_modifier
+            .is_some_and(|modifier| modifier.is_held(input))
+        {
+            return;
+        }
+
+        let mouse_delta = input.mouse_delta() * Vec2::new(1.0, -1.0) * self.mouse_sensitivity;
+        if input.mouse_held(MouseButton::Left) {
+            self.rotate(mouse_delta * self.rotate_sensitivity);
+        }
+        if input.mouse_held(MouseButton::Right) {
+            self.zoom(mouse_delta.y * 5.0);
+        }
+        if input.mouse_held(MouseButton::Middle) {
+            self.pan(mouse_delta);
+        }
+
+        let scroll = input.scroll_delta();
+        if scroll != 0.0 {
+            self.zoom(scroll * 0.4);
+        }
+
+        let translation =
+            keyboard_translation(&self.camera, input, self.move_speed * dt.as_secs_f32());
+        if translation != Vec3::ZERO {
+            self.set_focal_point(&(self.focal_point + translation));
+        }
+    }
+
+    /// Keeps the camera's size in sync with the window.
+    pub fn on_event(&mut self, event: &WindowEvent) {
+        if let WindowEvent::Resized(size) = event {
+            self.camera.on_resize(size.width, size.height);
+        }
+    }
+
+    fn update_position(&mut self) {
+        let position = self.focal_point - self.camera.forward_vector() * self.distance;
+        self.camera.set_position(&position);
+    }
+
+    fn rotate(&mut self, delta: Vec2) {
+        self.camera.set_pitch(self.camera.pitch() - delta.x);
+        self.camera.set_roll(self.camera.roll() + delta.y);
+        self.update_position();
+    }
+
+    /// Zooms faster when far from the focal point.
+    fn zoom(&mut self, delta: f32) {
+        let distance_unit = self.distance * 0.2;
+        let speed = f32::min(distance_unit * distance_unit, 100.0);
+        self.set_distance(self.distance - delta * speed);
+    }
+
+    /// Pans by about the same amount of pixels as the mouse moved, whatever the window's size.
+    fn pan(&mut self, delta: Vec2) {
+        let pan_speed = |size: f32| {
+            let unit = f32::min(size / 1000.0, 2.4);
+            0.0366 * (unit * unit) - 0.1778 * unit + 0.3021
+        };
+        let size = *self.camera.size();
+
+        let mut focal_point = self.focal_point;
+        focal_point += self.camera.right_vector() * delta.x * pan_speed(size.x) * self.distance;
+        focal_point += self.camera.up_vector() * delta.y * pan_speed(size.y) * self.distance;
+        self.set_focal_point(&focal_point);
+    }
+}
+
+/// Moves a camera freely, in the style of first person games:
+/// - right mouse button: look around
+/// - WASD and QE: move along the camera's axes, faster while shift is held
+#[derive(Debug, Clone, Copy)]
+pub struct FlyController {
+    pub camera: Camera,
+    /// Speed of the keyboard movements, in units per second.
+    pub move_speed: f32,
+    /// Multiplier of [`FlyController::move_speed`] while shift is held.
+    pub fast_multiplier: f32,
+    /// Radians the camera rotates by per pixel of mouse movement.
+    pub rotate_sensitivity: f32,
+    /// See [`ControllerModifier`], nothing suspends the controls by default.
+    pub suspend_modifier: Option<ControllerModifier>,
+}
+
+#[profiling::all_functions]
+impl FlyController {
+    /// Takes control of `camera`, which keeps its position and orientation.
+    pub fn new(camera: Camera) -> Self {
+        Self {
+            camera,
+            move_speed: 4.0,
+            fast_multiplier: 4.0,
+            rotate_sensitivity: 0.0024,
+            suspend_modifier: None,
+        }
+    }
+
+    pub fn on_update(&mut self, dt: Duration, input: &InputState) {
+        if self
+            .suspend_modifier
+            .is_some_and(|modifier| modifier.is_held(input))
+        {
+            return;
+        }
+
+        if input.mouse_held(MouseButton::Right) {
+            let delta = input.mouse_delta() * Vec2::new(1.0, -1.0) * self.rotate_sensitivity;
+            self.camera.set_pitch(self.camera.pitch() - delta.x);
+            self.camera.set_roll(self.camera.roll() + delta.y);
+        }
+
+        let mut speed = self.move_speed;
+        if input.held_shift() {
+            speed *= self.fast_multiplier;
+        }
+        let translation = keyboard_translation(&self.camera, input, speed * dt.as_secs_f32());
+        if translation != Vec3::ZERO {
+            self.camera
+                .set_position(&(*self.camera.position() + translation));
+        }
+    }
+
+    /// Keeps the camera's size in sync with the window.
+    pub fn on_event(&mut self, event: &WindowEvent) {
+        if let WindowEvent::Resized(size) = event {
+            self.camera.on_resize(size.width, size.height);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn projected_depth(projection: &Mat4, view_distance: f32) -> f32 {
+        let clip_position = *projection * Vec4::new(0.0, 0.0, -view_distance, 1.0);
+        clip_position.z / clip_position.w
+    }
+
+    #[test]
+    fn infinite_far_plane_depth() {
+        let projection = Camera::compute_projection(
+            &Projection::Perspective(PerspectiveData {
+                horizontal_fov: 70.0_f32.to_radians(),
+                near_plane: 0.1,
+                far_plane: None,
+            }),
+            16.0 / 9.0,
+        );
+
+        assert!(projected_depth(&projection, 0.1).abs() < 1e-6);
+        let far_depth = projected_depth(&projection, 1e6);
+        assert!(far_depth <= 1.0 && 1.0 - far_depth < 1e-4);
+        assert!(projected_depth(&projection, 10.0) < projected_depth(&projection, 1000.0));
+    }
+}
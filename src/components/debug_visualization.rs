@@ -0,0 +1,36 @@
+use bevy_ecs::component::Component;
+
+use crate::math_types::Vec4;
+
+/// Draws the bounding box of the entity's mesh every frame, transformed by the entity's
+/// transform (see [`crate::systems::debug_visualization::draw_debug_visualizations`]).
+#[derive(Debug, Clone, Copy, Component)]
+pub struct ShowBounds {
+    pub color: Vec4,
+}
+
+impl Default for ShowBounds {
+    fn default() -> Self {
+        Self {
+            color: Vec4::new(1.0, 1.0, 0.0, 1.0),
+        }
+    }
+}
+
+/// Draws the normals of the entity's mesh every frame, as lines starting at each vertex. Vertex
+/// types without normals (see [`crate::material::Vertex::normal`]) draw nothing.
+#[derive(Debug, Clone, Copy, Component)]
+pub struct ShowNormals {
+    /// Length of the drawn normals, in world units.
+    pub length: f32,
+    pub color: Vec4,
+}
+
+impl Default for ShowNormals {
+    fn default() -> Self {
+        Self {
+            length: 0.1,
+            color: Vec4::new(0.0, 1.0, 1.0, 1.0),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use bevy_ecs::component::Component;
+
+use crate::math_types::{Mat4, Vec3, Vec4};
+
+/// Descriptor set of the lights storage buffer, bound for every material.
+pub const LIGHTS_SET: u32 = 1;
+/// Binding of the lights storage buffer in [`LIGHTS_SET`].
+pub const LIGHTS_BINDING: u32 = 0;
+/// Number of lights the storage buffer holds, the others are ignored (see
+/// [`crate::systems::light::gather_lights`]).
+pub const MAX_LIGHTS: usize = 256;
+
+/// Light shining in the entity's forward direction (-Z in its local space) from infinitely far
+/// away, e.g. the sun.
+#[derive(Debug, Clone, Copy, Component)]
+pub struct DirectionalLight {
+    pub color: Vec3,
+    pub intensity: f32,
+}
+
+impl Default for DirectionalLight {
+    fn default() -> Self {
+        Self {
+            color: Vec3::ONE,
+            intensity: 1.0,
+        }
+    }
+}
+
+/// Light shining in every direction from the entity's position.
+#[derive(Debug, Clone, Copy, Component)]
+pub struct PointLight {
+    pub color: Vec3,
+    pub intensity: f32,
+    /// Distance beyond which the light has no effect, in world units.
+    pub range: f32,
+}
+
+impl Default for PointLight {
+    fn default() -> Self {
+        Self {
+            color: Vec3::ONE,
+            intensity: 1.0,
+            range: 10.0,
+        }
+    }
+}
+
+/// Light shining in a cone around the entity's forward direction (-Z in its local space), from
+/// its position.
+#[derive(Debug, Clone, Copy, Component)]
+pub struct SpotLight {
+    pub color: Vec3,
+    pub intensity: f32,
+    /// Distance beyond which the light has no effect, in world units.
+    pub range: f32,
+    /// Angle from the cone's axis, in radians, within which the light has its full intensity.
+    pub inner_angle: f32,
+    /// Angle from the cone's axis, in radians, beyond which the light has no effect. The intensity
+    /// fades between the inner and outer angles.
+    pub outer_angle: f32,
+}
+
+impl Default for SpotLight {
+    fn default() -> Self {
+        Self {
+            color: Vec3::ONE,
+            intensity: 1.0,
+            range: 10.0,
+            inner_angle: 0.0,
+            outer_angle: std::f32::consts::FRAC_PI_4,
+        }
+    }
+}
+
+/// Kind of a [`GpuLight`], stored in the `w` component of its position.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightKind {
+    Directional = 0,
+    Point = 1,
+    Spot = 2,
+}
+
+/// A light as stored in the lights storage buffer, which shaders declare as:
+/// ```glsl
+/// struct Light {
+///     vec4 position;  // xyz: world position, w: kind (0: directional, 1: point, 2: spot)
+///     vec4 direction; // xyz: normalized world direction the light shines in, w: range
+///     vec4 color;     // rgb: color, a: intensity
+///     vec4 cone;      // x: cosine of the inner angle, y: cosine of the outer angle
+/// };
+///
+/// layout(set = 1, binding = 0) readonly buffer Lights {
+///     uint light_count;
+///     Light lights[];
+/// } u_Lights;
+/// ```
+/// The count is followed by 12 bytes of padding, so that the array starts at offset 16 as the
+/// `std430` layout requires. Only the first `light_count` lights (at most [`MAX_LIGHTS`]) are
+/// valid.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GpuLight {
+    pub position: Vec4,
+    pub direction: Vec4,
+    pub color: Vec4,
+    pub cone: Vec4,
+}
+
+unsafe impl bytemuck::Zeroable for GpuLight {}
+unsafe impl bytemuck::Pod for GpuLight {}
+
+impl GpuLight {
+    pub fn directional(light: &DirectionalLight, world_matrix: Mat4) -> Self {
+        Self {
+            position: Vec4::ZERO.with_w(LightKind::Directional as u32 as f32),
+            direction: forward(world_matrix).extend(0.0),
+            color: light.color.extend(light.intensity),
+            cone: Vec4::ZERO,
+        }
+    }
+
+    pub fn point(light: &PointLight, world_matrix: Mat4) -> Self {
+        Self {
+            position: world_matrix
+                .w_axis
+                .truncate()
+                .extend(LightKind::Point as u32 as f32),
+            direction: Vec4::ZERO.with_w(light.range),
+            color: light.color.extend(light.intensity),
+            cone: Vec4::ZERO,
+        }
+    }
+
+    pub fn spot(light: &SpotLight, world_matrix: Mat4) -> Self {
+        Self {
+            position: world_matrix
+                .w_axis
+                .truncate()
+                .extend(LightKind::Spot as u32 as f32),
+            direction: forward(world_matrix).extend(light.range),
+            color: light.color.extend(light.intensity),
+            cone: Vec4::new(light.inner_angle.cos(), light.outer_angle.cos(), 0.0, 0.0),
+        }
+    }
+}
+
+fn forward(world_matrix: Mat4) -> Vec3 {
+    world_matrix
+        .transform_vector3(Vec3::NEG_Z)
+        .normalize_or(Vec3::NEG_Z)
+}
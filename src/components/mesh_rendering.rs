@@ -1,42 +1,138 @@
 use ash::vk;
 use bevy_ecs::prelude::Component;
+use spirv_reflect::types::ReflectDescriptorType;
 use thiserror::Error;
 
+use std::{collections::BTreeMap, ops::Range};
+
 use crate::{
     allocated_types::{AllocatedBuffer, AllocatedImage, BufferBuildError},
     descriptor_resources::{
-        DescriptorResources, DescriptorSetUpdateError, ResourceBindingError, UniformUpdateError,
+        DescriptorResources, DescriptorSetAllocationError, DescriptorSetUpdateError,
+        ResourceBindingError, UniformUpdateError, MODEL_MATRIX_BINDING, OBJECT_SET,
     },
+    descriptor_set_cache::DescriptorSetKey,
+    dynamic_uniforms::DynamicUniformError,
     material::{Material, Vertex},
     math_types::Mat4,
     mesh::Mesh,
     renderer::Renderer,
+    resource_registry::{self, ResourceKind},
+    shader::Shader,
     texture::Texture,
     utils::ThreadSafeRef,
 };
 
+/// Draws a mesh with a material. On top of the material's resources (set 2), each mesh rendering
+/// owns a descriptor set filled with its own [`DescriptorResources`] (set 3, see
+/// [`OBJECT_SET`]), which holds at least the model matrix and can hold any per-object resource.
 #[derive(Debug, Component)]
 pub struct MeshRendering<VertexType>
 where
     VertexType: Vertex,
 {
     pub visible: bool,
+    /// Set by [`crate::systems::mesh_renderer::cull_meshes`] when the mesh is outside of every
+    /// camera's frustum, the mesh is then skipped like an invisible one. Kept apart from
+    /// `visible` so that culling never overrides the user's choice.
+    pub(crate) culled: bool,
+    /// Index into the material's texture array, pushed to the fragment stage right after the
+    /// camera data (`layout(offset = 80) uint`). Ignored if the shader does not declare it.
+    pub texture_index: u32,
 
     descriptor_pool: vk::DescriptorPool,
     pub descriptor_resources: DescriptorResources,
 
     pub mesh_ref: ThreadSafeRef<Mesh<VertexType>>,
     pub material_ref: ThreadSafeRef<Material<VertexType>>,
+    submeshes: Vec<Submesh<VertexType>>,
+
+    pub(crate) descriptor_set: vk::DescriptorSet, // level 3 (OBJECT_SET)
+    /// Set when the descriptor set (and its pool) is owned by the renderer's cache.
+    cache_key: Option<DescriptorSetKey>,
+
+    /// Data of the shader's dynamic uniforms, sorted by slot like the offsets Vulkan expects.
+    dynamic_uniforms: BTreeMap<u32, Vec<u8>>,
+    /// Offsets of the dynamic uniforms written for the current frame, in slot order.
+    pub(crate) dynamic_offsets: Vec<u32>,
+}
+
+/// Range of a mesh's indices drawn with its own material, see [`MeshRendering::set_submeshes`].
+#[derive(Debug)]
+pub struct Submesh<VertexType>
+where
+    VertexType: Vertex,
+{
+    pub index_range: Range<u32>,
+    pub material_ref: ThreadSafeRef<Material<VertexType>>,
+}
 
-    pub(crate) descriptor_set: vk::DescriptorSet, // level 3
+#[derive(Error, Debug)]
+pub enum SubmeshError {
+    #[error("Submeshes can only be used with indexed meshes.")]
+    MeshNotIndexed,
+
+    #[error("Submesh index range {start}..{end} is out of the mesh's {index_count} indices.")]
+    InvalidIndexRange {
+        start: u32,
+        end: u32,
+        index_count: usize,
+    },
+
+    #[error("The material of submesh {0} doesn't declare the same set 3 bindings as the mesh rendering's material.")]
+    IncompatibleMaterial(usize),
 }
 
+/// Set 3 bindings of a shader, which must match for a descriptor set to be shared between materials.
+fn level_3_bindings(
+    shader: &Shader,
+) -> Vec<(vk::ShaderStageFlags, u32, ReflectDescriptorType, u32, bool)> {
+    let mut bindings = shader
+        .vertex_bindings
+        .iter()
+        .map(|binding| (vk::ShaderStageFlags::VERTEX, binding))
+        .chain(
+            shader
+                .fragment_bindings
+                .iter()
+                .map(|binding| (vk::ShaderStageFlags::FRAGMENT, binding)),
+        )
+        .filter(|(_, binding)| binding.set == OBJECT_SET)
+        .map(|(stage, binding)| {
+            (
+                stage,
+                binding.slot,
+                binding.descriptor_type,
+                binding.count,
+                binding.dynamic,
+            )
+        })
+        .collect::<Vec<_>>();
+    bindings.sort_by_key(|(stage, slot, _, _, _)| (stage.as_raw(), *slot));
+
+    bindings
+}
+
+/// Zeroed data for each dynamic uniform of a shader (see [`Shader::set_dynamic_uniforms`]).
+fn dynamic_uniforms(shader: &Shader) -> BTreeMap<u32, Vec<u8>> {
+    shader
+        .vertex_bindings
+        .iter()
+        .chain(&shader.fragment_bindings)
+        .filter(|binding| binding.set == OBJECT_SET && binding.dynamic)
+        .map(|binding| (binding.slot, vec![0; binding.size as usize]))
+        .collect()
+}
+
+/// Uniform buffer of a model matrix at [`MODEL_MATRIX_BINDING`]. Shaders read the model matrix as
+/// a dynamic uniform written by the render systems, so this buffer is only useful to copy the
+/// matrix to by hand (e.g. for compute work), [`default_descriptor_resources`] doesn't need it.
 pub fn default_ubo_bindings(
     renderer: &mut Renderer,
 ) -> Result<(u32, ThreadSafeRef<AllocatedBuffer>), BufferBuildError> {
     let size: u64 = std::mem::size_of::<Mat4>().try_into().unwrap();
     Ok((
-        0,
+        MODEL_MATRIX_BINDING,
         ThreadSafeRef::new(
             AllocatedBuffer::builder(size)
                 .with_name("Default UBO")
@@ -44,12 +140,23 @@ pub fn default_ubo_bindings(
         ),
     ))
 }
+/// Resources of a mesh rendering whose shader only declares the model matrix in set 3, which the
+/// render systems write to the renderer's dynamic uniform buffer every frame.
 pub fn default_descriptor_resources(
+    _renderer: &mut Renderer,
+) -> Result<DescriptorResources, BufferBuildError> {
+    Ok(DescriptorResources::default())
+}
+
+/// Same as [`default_descriptor_resources`], with per-object textures bound at the given slots.
+/// The shader must declare them in set 3 (e.g. `layout(set = 3, binding = 1) uniform sampler2D`).
+pub fn default_descriptor_resources_with_textures(
+    textures: impl IntoIterator<Item = (u32, ThreadSafeRef<Texture>)>,
     renderer: &mut Renderer,
 ) -> Result<DescriptorResources, BufferBuildError> {
     Ok(DescriptorResources {
-        uniform_buffers: [default_ubo_bindings(renderer)?].into(),
-        ..Default::default()
+        sampled_images: textures.into_iter().collect(),
+        ..default_descriptor_resources(renderer)?
     })
 }
 
@@ -65,6 +172,22 @@ pub enum MeshRenderingBuildError {
     DescriptorSetUpdateFailed(#[from] DescriptorSetUpdateError),
 }
 
+impl From<DescriptorSetAllocationError> for MeshRenderingBuildError {
+    fn from(value: DescriptorSetAllocationError) -> Self {
+        match value {
+            DescriptorSetAllocationError::VulkanDescriptorPoolCreationFailed(result) => {
+                Self::VulkanDescriptorPoolCreationFailed(result)
+            }
+            DescriptorSetAllocationError::VulkanDescriptorSetAllocationFailed(result) => {
+                Self::VulkanDescriptorSetAllocationFailed(result)
+            }
+            DescriptorSetAllocationError::DescriptorSetUpdateFailed(error) => {
+                Self::DescriptorSetUpdateFailed(error)
+            }
+        }
+    }
+}
+
 impl<VertexType> MeshRendering<VertexType>
 where
     VertexType: Vertex,
@@ -82,62 +205,15 @@ where
         let material = material_ref.lock();
 
         let material_shader = material.shader_ref.lock();
-        let ubo_count: u32 = descriptor_resources
-            .uniform_buffers
-            .len()
-            .try_into()
-            .unwrap();
-        let storage_image_count: u32 = descriptor_resources
-            .storage_images
-            .len()
-            .try_into()
-            .unwrap();
-        let sampled_image_count: u32 = descriptor_resources
-            .sampled_images
-            .len()
-            .try_into()
-            .unwrap();
-
-        let pool_sizes = [
-            vk::DescriptorPoolSize {
-                ty: vk::DescriptorType::UNIFORM_BUFFER,
-                descriptor_count: std::cmp::max(ubo_count, 1),
-            },
-            vk::DescriptorPoolSize {
-                ty: vk::DescriptorType::STORAGE_IMAGE,
-                descriptor_count: std::cmp::max(storage_image_count, 1),
-            },
-            vk::DescriptorPoolSize {
-                ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-                descriptor_count: std::cmp::max(sampled_image_count, 1),
-            },
-        ];
-        let pool_info = vk::DescriptorPoolCreateInfo::default()
-            .max_sets(1)
-            .pool_sizes(&pool_sizes);
-        let descriptor_pool = unsafe { renderer.device.create_descriptor_pool(&pool_info, None) }
-            .map_err(|result| {
-            MeshRenderingBuildError::VulkanDescriptorPoolCreationFailed(result)
-        })?;
-
-        let descriptor_set_alloc_info = vk::DescriptorSetAllocateInfo::default()
-            .descriptor_pool(descriptor_pool)
-            .set_layouts(std::slice::from_ref(&material_shader.level_3_dsl));
-        let descriptor_set = unsafe {
-            renderer
-                .device
-                .allocate_descriptor_sets(&descriptor_set_alloc_info)
-        }
-        .map_err(MeshRenderingBuildError::VulkanDescriptorSetAllocationFailed)?[0];
-
         let mut merged_bindings = material_shader.vertex_bindings.clone();
         merged_bindings.extend(&material_shader.fragment_bindings);
-        descriptor_resources.update_descriptors_set_from_bindings(
+        let (descriptor_pool, descriptor_set) = descriptor_resources.allocate_descriptor_set(
+            material_shader.level_3_dsl,
             &merged_bindings,
-            &descriptor_set,
-            Some(&[3]),
+            OBJECT_SET,
             renderer,
         )?;
+        let dynamic_uniforms = dynamic_uniforms(&material_shader);
 
         drop(material_shader);
         drop(material);
@@ -145,20 +221,198 @@ where
 
         Ok(ThreadSafeRef::new(Self {
             visible: true,
+            culled: false,
+            texture_index: 0,
             descriptor_pool,
             descriptor_resources,
             mesh_ref,
             material_ref,
+            submeshes: vec![],
             descriptor_set,
+            cache_key: None,
+            dynamic_uniforms,
+            dynamic_offsets: vec![],
         }))
     }
 
+    /// Same as [`MeshRendering::new`], but shares its set 3 descriptor set with the other cached
+    /// mesh renderings bound to the exact same resources (same buffers, textures and samplers,
+    /// compared by handle) for the same shader, instead of allocating its own.
+    ///
+    /// Dynamic uniforms, such as the model matrix written by the render systems, are not part of
+    /// the shared resources: each mesh rendering draws with its own data. Sharers read the same
+    /// regular buffers, data uploaded to one of them (e.g. with `update_uniform_pod`) is seen by
+    /// all of them. This is meant for renderings whose other set 3 resources are all shared
+    /// anyway. Binding a different resource (with
+    /// `bind_uniform`, `bind_texture`, ...) detaches the mesh rendering from the shared set first,
+    /// giving it a private copy, so the other sharers are never affected by it.
+    pub fn new_cached(
+        mesh_ref: &ThreadSafeRef<Mesh<VertexType>>,
+        material_ref: &ThreadSafeRef<Material<VertexType>>,
+        descriptor_resources: DescriptorResources,
+        renderer: &mut Renderer,
+    ) -> Result<ThreadSafeRef<Self>, MeshRenderingBuildError> {
+        let material = material_ref.lock();
+        let material_shader = material.shader_ref.lock();
+
+        let mut merged_bindings = material_shader.vertex_bindings.clone();
+        merged_bindings.extend(&material_shader.fragment_bindings);
+        let cache_key = descriptor_resources.descriptor_set_key(
+            material_shader.level_3_dsl,
+            &merged_bindings,
+            OBJECT_SET,
+        );
+        let (descriptor_pool, descriptor_set) =
+            match renderer.descriptor_set_cache.acquire(&cache_key) {
+                Some(cached_set) => cached_set,
+                None => {
+                    let (descriptor_pool, descriptor_set) = descriptor_resources
+                        .allocate_descriptor_set(
+                            material_shader.level_3_dsl,
+                            &merged_bindings,
+                            OBJECT_SET,
+                            renderer,
+                        )?;
+                    renderer.descriptor_set_cache.insert(
+                        cache_key.clone(),
+                        descriptor_pool,
+                        descriptor_set,
+                    );
+
+                    (descriptor_pool, descriptor_set)
+                }
+            };
+        let dynamic_uniforms = dynamic_uniforms(&material_shader);
+
+        drop(material_shader);
+        drop(material);
+
+        Ok(ThreadSafeRef::new(Self {
+            visible: true,
+            culled: false,
+            texture_index: 0,
+            descriptor_pool,
+            descriptor_resources,
+            mesh_ref: ThreadSafeRef::clone(mesh_ref),
+            material_ref: ThreadSafeRef::clone(material_ref),
+            submeshes: vec![],
+            descriptor_set,
+            cache_key: Some(cache_key),
+            dynamic_uniforms,
+            dynamic_offsets: vec![],
+        }))
+    }
+
+    /// Whether the mesh was found outside of the cameras' frustums by the last
+    /// [`crate::systems::mesh_renderer::cull_meshes`].
+    pub fn is_culled(&self) -> bool {
+        self.culled
+    }
+
+    /// Whether the descriptor set is shared through the cache (see [`MeshRendering::new_cached`]).
+    pub fn is_descriptor_set_shared(&self) -> bool {
+        self.cache_key.is_some()
+    }
+
+    /// Gives the mesh rendering its own copy of a cached descriptor set, before it is modified.
+    fn detach_shared_descriptor_set(
+        &mut self,
+        renderer: &mut Renderer,
+    ) -> Result<(), ResourceBindingError> {
+        let Some(cache_key) = self.cache_key.take() else {
+            return Ok(());
+        };
+
+        let material_ref = self.material_ref.clone();
+        let material = material_ref.lock();
+        let material_shader = material.shader_ref.lock();
+        let mut merged_bindings = material_shader.vertex_bindings.clone();
+        merged_bindings.extend(&material_shader.fragment_bindings);
+        let allocation_result = self.descriptor_resources.allocate_descriptor_set(
+            material_shader.level_3_dsl,
+            &merged_bindings,
+            OBJECT_SET,
+            renderer,
+        );
+        let (descriptor_pool, descriptor_set) = match allocation_result {
+            Ok(allocation) => allocation,
+            Err(error) => {
+                self.cache_key = Some(cache_key);
+                return Err(error.into());
+            }
+        };
+
+        renderer
+            .descriptor_set_cache
+            .release(&cache_key, &renderer.device);
+        self.descriptor_pool = descriptor_pool;
+        self.descriptor_set = descriptor_set;
+
+        Ok(())
+    }
+
+    pub fn submeshes(&self) -> &[Submesh<VertexType>] {
+        &self.submeshes
+    }
+
+    /// Splits the mesh into index ranges drawn with their own material (one `cmd_draw_indexed` per
+    /// submesh), e.g. for models using several materials. When the list is empty (the default),
+    /// the whole mesh is drawn with [`MeshRendering::material_ref`].
+    ///
+    /// All submeshes share this mesh rendering's set 3 descriptor set (and its bound resources),
+    /// so their materials must declare the same set 3 bindings as [`MeshRendering::material_ref`],
+    /// which is typically the case when they use the same shader. Submeshes are drawn directly, and
+    /// ignore GPU culling if the entity has a [`crate::gpu_culling::GpuCulling`] component.
+    pub fn set_submeshes(
+        &mut self,
+        submeshes: Vec<Submesh<VertexType>>,
+    ) -> Result<(), SubmeshError> {
+        if !submeshes.is_empty() {
+            let mesh = self.mesh_ref.lock();
+            let index_count = match (mesh.index_buffer.as_ref(), mesh.indices.as_ref()) {
+                (Some(_), Some(indices)) => indices.len(),
+                _ => return Err(SubmeshError::MeshNotIndexed),
+            };
+
+            let expected_bindings = level_3_bindings(&self.material_ref.lock().shader_ref.lock());
+            for (submesh_index, submesh) in submeshes.iter().enumerate() {
+                let range = &submesh.index_range;
+                if range.start > range.end
+                    || usize::try_from(range.end).map_or(true, |end| end > index_count)
+                {
+                    return Err(SubmeshError::InvalidIndexRange {
+                        start: range.start,
+                        end: range.end,
+                        index_count,
+                    });
+                }
+
+                let bindings = level_3_bindings(&submesh.material_ref.lock().shader_ref.lock());
+                if bindings != expected_bindings {
+                    return Err(SubmeshError::IncompatibleMaterial(submesh_index));
+                }
+            }
+        }
+
+        self.submeshes = submeshes;
+        Ok(())
+    }
+
     pub fn bind_uniform(
         &mut self,
         binding_slot: u32,
         buffer_ref: ThreadSafeRef<AllocatedBuffer>,
         renderer: &mut Renderer,
     ) -> Result<ThreadSafeRef<AllocatedBuffer>, ResourceBindingError> {
+        // Dynamic uniforms always read the renderer's buffer
+        if self.is_uniform_dynamic(binding_slot) {
+            return Err(ResourceBindingError::InvalidBindingSlot {
+                slot: binding_slot,
+                set: OBJECT_SET,
+            });
+        }
+        self.detach_shared_descriptor_set(renderer)?;
+
         let Some(old_buffer) = self
             .descriptor_resources
             .uniform_buffers
@@ -166,7 +420,7 @@ where
         else {
             return Err(ResourceBindingError::InvalidBindingSlot {
                 slot: binding_slot,
-                set: 3,
+                set: OBJECT_SET,
             });
         };
 
@@ -202,19 +456,79 @@ where
             .get(&binding_slot)
             .ok_or(UniformUpdateError::InvalidBindingSlot {
                 slot: binding_slot,
-                set: 3,
+                set: OBJECT_SET,
             })?
             .lock()
             .upload_pod(pod)
             .map_err(|err| err.into())
     }
 
+    /// Sets the data of a dynamic uniform (see [`Shader::set_dynamic_uniforms`]). The data is kept
+    /// until it is set again, and written to the renderer's dynamic uniform buffer every frame the
+    /// mesh rendering is drawn. Unlike [`MeshRendering::update_uniform_pod`], this doesn't touch
+    /// any buffer, and the data of `T` must match the uniform's size exactly.
+    pub fn set_dynamic_uniform_pod<T: bytemuck::Pod>(
+        &mut self,
+        binding_slot: u32,
+        pod: T,
+    ) -> Result<(), UniformUpdateError> {
+        let data = self.dynamic_uniforms.get_mut(&binding_slot).ok_or(
+            UniformUpdateError::InvalidBindingSlot {
+                slot: binding_slot,
+                set: OBJECT_SET,
+            },
+        )?;
+        let bytes = bytemuck::bytes_of(&pod);
+        if bytes.len() != data.len() {
+            return Err(UniformUpdateError::DynamicUniformSizeMismatch {
+                slot: binding_slot,
+                data_size: bytes.len(),
+                uniform_size: data.len(),
+            });
+        }
+
+        data.copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Whether the uniform at `binding_slot` is dynamic (see [`Shader::set_dynamic_uniforms`]).
+    pub fn is_uniform_dynamic(&self, binding_slot: u32) -> bool {
+        self.dynamic_uniforms.contains_key(&binding_slot)
+    }
+
+    /// Writes the dynamic uniforms to the renderer's buffer for the current frame, and keeps their
+    /// offsets for the draws of this frame.
+    pub(crate) fn write_dynamic_uniforms(
+        &mut self,
+        renderer: &mut Renderer,
+    ) -> Result<(), DynamicUniformError> {
+        self.dynamic_offsets.clear();
+        for data in self.dynamic_uniforms.values() {
+            match renderer.push_dynamic_uniform(data) {
+                Ok(offset) => self.dynamic_offsets.push(offset),
+                Err(error) => {
+                    self.dynamic_offsets.clear();
+                    return Err(error);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether every dynamic uniform has an offset to be drawn with this frame.
+    pub(crate) fn has_dynamic_offsets(&self) -> bool {
+        self.dynamic_offsets.len() == self.dynamic_uniforms.len()
+    }
+
     pub fn bind_storage_image<T: bytemuck::Pod>(
         &mut self,
         binding_slot: u32,
         image_ref: ThreadSafeRef<AllocatedImage>,
         renderer: &mut Renderer,
     ) -> Result<ThreadSafeRef<AllocatedImage>, ResourceBindingError> {
+        self.detach_shared_descriptor_set(renderer)?;
+
         let Some(old_image) = self
             .descriptor_resources
             .storage_images
@@ -222,7 +536,7 @@ where
         else {
             return Err(ResourceBindingError::InvalidBindingSlot {
                 slot: binding_slot,
-                set: 3,
+                set: OBJECT_SET,
             });
         };
 
@@ -253,6 +567,8 @@ where
         texture_ref: ThreadSafeRef<Texture>,
         renderer: &mut Renderer,
     ) -> Result<ThreadSafeRef<Texture>, ResourceBindingError> {
+        self.detach_shared_descriptor_set(renderer)?;
+
         let Some(old_texture) = self
             .descriptor_resources
             .sampled_images
@@ -260,7 +576,7 @@ where
         else {
             return Err(ResourceBindingError::InvalidBindingSlot {
                 slot: binding_slot,
-                set: 3,
+                set: OBJECT_SET,
             });
         };
 
@@ -286,11 +602,78 @@ where
         Ok(old_texture)
     }
 
-    pub fn destroy(&mut self, renderer: &mut Renderer) {
+    pub fn bind_texture_array(
+        &mut self,
+        binding_slot: u32,
+        texture_refs: Vec<ThreadSafeRef<Texture>>,
+        renderer: &mut Renderer,
+    ) -> Result<Vec<ThreadSafeRef<Texture>>, ResourceBindingError> {
+        {
+            let material = self.material_ref.lock();
+            let shader = material.shader_ref.lock();
+            let mut bindings = shader.vertex_bindings.clone();
+            bindings.extend(&shader.fragment_bindings);
+            self.descriptor_resources.prepare_texture_array(
+                &bindings,
+                OBJECT_SET,
+                binding_slot,
+                &texture_refs,
+                renderer,
+            )?;
+        }
+        self.detach_shared_descriptor_set(renderer)?;
+
+        let Some(old_textures) = self
+            .descriptor_resources
+            .sampled_image_arrays
+            .insert(binding_slot, texture_refs.clone())
+        else {
+            return Err(ResourceBindingError::InvalidBindingSlot {
+                slot: binding_slot,
+                set: OBJECT_SET,
+            });
+        };
+
+        let descriptor_image_infos = texture_refs
+            .iter()
+            .map(|texture_ref| {
+                let texture = texture_ref.lock();
+                let view = texture.image_ref.lock().view;
+                vk::DescriptorImageInfo::default()
+                    .sampler(texture.sampler)
+                    .image_view(view)
+                    .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            })
+            .collect::<Vec<_>>();
+
+        let set_write = vk::WriteDescriptorSet::default()
+            .dst_set(self.descriptor_set)
+            .dst_binding(binding_slot)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&descriptor_image_infos);
+
         unsafe {
             renderer
                 .device
-                .destroy_descriptor_pool(self.descriptor_pool, None);
+                .update_descriptor_sets(std::slice::from_ref(&set_write), &[])
+        };
+
+        Ok(old_textures)
+    }
+
+    pub fn destroy(&mut self, renderer: &mut Renderer) {
+        match self.cache_key.take() {
+            Some(cache_key) => renderer
+                .descriptor_set_cache
+                .release(&cache_key, &renderer.device),
+            None => {
+                unsafe {
+                    renderer
+                        .device
+                        .destroy_descriptor_pool(self.descriptor_pool, None)
+                };
+                resource_registry::unregister(ResourceKind::DescriptorSet, 1);
+            }
         }
     }
 }
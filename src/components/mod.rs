@@ -1,7 +1,13 @@
+pub mod animation_player;
 pub mod camera;
+pub mod debug_visualization;
+pub mod light;
 pub mod mesh_rendering;
 pub mod resource_wrapper;
+pub mod skeleton;
+pub mod skybox;
 pub mod transform;
+pub mod viewport;
 
 #[cfg(feature = "ray_tracing")]
 pub mod ray_tracing;
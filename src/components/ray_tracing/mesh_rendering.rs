@@ -6,6 +6,7 @@ use thiserror::Error;
 
 use crate::{
     allocated_types::{AllocatedBuffer, BufferBuildError},
+    components::transform::Transform,
     material::Vertex,
     mesh::Mesh,
     renderer::Renderer,
@@ -60,8 +61,18 @@ impl<VertexType: Vertex> MeshRendering<VertexType> {
         &self.blas
     }
 
-    pub fn tlas_instance(&self) -> &vk::AccelerationStructureInstanceKHR {
-        &self.tlas_instance
+    /// Instance of the mesh's BLAS placed by `transform`, to build or update a [`super::tlas::TLAS`].
+    pub fn tlas_instance(&self, transform: &Transform) -> vk::AccelerationStructureInstanceKHR {
+        // The instance's 3x4 matrix is row-major, the first three rows of the transposed (column
+        // major) matrix
+        let rows = transform.matrix().transpose().to_cols_array();
+        let mut matrix = [0.0; 12];
+        matrix.copy_from_slice(&rows[..12]);
+
+        vk::AccelerationStructureInstanceKHR {
+            transform: vk::TransformMatrixKHR { matrix },
+            ..self.tlas_instance
+        }
     }
 
     pub fn new(
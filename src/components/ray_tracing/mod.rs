@@ -1,3 +1,3 @@
 pub mod mesh_rendering;
+pub mod pipeline;
 pub mod tlas;
-
@@ -0,0 +1,550 @@
+use std::ffi::CString;
+
+use ash::vk;
+use thiserror::Error;
+
+use crate::{
+    allocated_types::{AllocatedBuffer, AllocatedImage, BufferBuildError, BufferDataUploadError},
+    material::PushConstantError,
+    pipeline_builder::PipelineBuildError,
+    renderer::Renderer,
+    resource_registry::{self, ResourceKind},
+    shader::create_shader_module,
+    utils::{ImmediateCommandError, ThreadSafeRef},
+};
+
+use super::tlas::TLAS;
+
+/// Binding of the TLAS traced against in the pipeline's descriptor set (set 0), declared in GLSL
+/// as `layout(set = 0, binding = 0) uniform accelerationStructureEXT`.
+pub const TLAS_BINDING: u32 = 0;
+/// Binding of the storage image written by the raygen shader in the pipeline's descriptor set
+/// (set 0), declared in GLSL as `layout(set = 0, binding = 1, rgba8) uniform image2D` (or any
+/// format matching the target image).
+pub const TARGET_IMAGE_BINDING: u32 = 1;
+
+/// Raygen, miss and hit group, in this order in the pipeline and the shader binding table.
+const GROUP_COUNT: u32 = 3;
+
+pub struct RayTracingPipelineBuilder {
+    pub entry_point: String,
+    pub max_recursion_depth: u32,
+}
+
+struct ShaderBindingTable {
+    buffer: AllocatedBuffer,
+    raygen_region: vk::StridedDeviceAddressRegionKHR,
+    miss_region: vk::StridedDeviceAddressRegionKHR,
+    hit_region: vk::StridedDeviceAddressRegionKHR,
+}
+
+/// Pipeline tracing rays against a [`TLAS`] into a storage image, made of a raygen, a miss and a
+/// closest hit shader (see [`RayTracingPipeline::trace`]).
+///
+/// The descriptor set layout is fixed: the TLAS is bound at [`TLAS_BINDING`] and the target image
+/// at [`TARGET_IMAGE_BINDING`] of set 0. Data such as the camera matrices is passed as push
+/// constants (see [`RayTracingPipeline::set_push_constants`]). Every TLAS instance uses the single
+/// hit group, as set by [`super::mesh_rendering::MeshRendering::tlas_instance`].
+pub struct RayTracingPipeline {
+    dsl: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+
+    shader_binding_table: ShaderBindingTable,
+    push_constant_size: u32,
+    push_constant_stages: vk::ShaderStageFlags,
+    push_constant_data: Vec<u8>,
+
+    loader: ash::khr::ray_tracing_pipeline::Device,
+}
+
+#[derive(Error, Debug)]
+pub enum RayTracingPipelineBuildError {
+    #[error("SPIRV decoding failed with error: {0}.")]
+    SPIRVDecodingFailed(std::io::Error),
+
+    #[error("Vulkan creation of shader module failed with result: {0}.")]
+    ShaderModuleCreationFailed(vk::Result),
+
+    #[error("SPIRV reflection creation failed with error message: {0}.")]
+    ReflectionLoadingFailed(&'static str),
+
+    #[error("Vulkan creation of the descriptor set layout failed with result: {0}.")]
+    VulkanDSLCreationFailed(vk::Result),
+
+    #[error("Vulkan descriptor pool creation failed with status: {0}.")]
+    VulkanDescriptorPoolCreationFailed(vk::Result),
+
+    #[error("Vulkan descriptor set allocation failed with status: {0}.")]
+    VulkanDescriptorSetAllocationFailed(vk::Result),
+
+    #[error("Vulkan pipeline layout creation failed with status: {0}.")]
+    VulkanPipelineLayoutCreationFailed(vk::Result),
+
+    #[error("Pipeline creation failed with error: {0}.")]
+    PipelineCreationFailed(#[from] PipelineBuildError),
+
+    #[error("Query of the shader group handles failed with result: {0}.")]
+    ShaderGroupHandlesQueryFailed(vk::Result),
+
+    #[error("Creation of the shader binding table buffer failed with error: {0}.")]
+    SBTCreationFailed(#[from] BufferBuildError),
+
+    #[error("Upload of the shader binding table failed with error: {0}.")]
+    SBTUploadFailed(#[from] BufferDataUploadError),
+}
+
+fn align_up(value: u64, alignment: u64) -> u64 {
+    value.div_ceil(alignment) * alignment
+}
+
+fn ray_tracing_properties(
+    renderer: &Renderer,
+) -> vk::PhysicalDeviceRayTracingPipelinePropertiesKHR<'static> {
+    let mut rt_properties = vk::PhysicalDeviceRayTracingPipelinePropertiesKHR::default();
+    let mut properties = vk::PhysicalDeviceProperties2::default().push_next(&mut rt_properties);
+    unsafe {
+        renderer
+            .instance
+            .get_physical_device_properties2(renderer.physical_device, &mut properties)
+    };
+
+    rt_properties
+}
+
+/// Size and stages of the push constant block, the biggest of the ones declared by the stages.
+fn reflect_push_constants(
+    stages: &[(&[u32], vk::ShaderStageFlags)],
+) -> Result<(u32, vk::ShaderStageFlags), RayTracingPipelineBuildError> {
+    let mut size = 0;
+    let mut push_constant_stages = vk::ShaderStageFlags::empty();
+    for (source_spirv, stage) in stages {
+        let reflection_module = spirv_reflect::ShaderModule::load_u32_data(source_spirv)
+            .map_err(RayTracingPipelineBuildError::ReflectionLoadingFailed)?;
+        let push_constants = reflection_module
+            .enumerate_push_constant_blocks(None)
+            .map_err(RayTracingPipelineBuildError::ReflectionLoadingFailed)?;
+        if let Some(block) = push_constants.first() {
+            size = size.max(block.size);
+            push_constant_stages |= *stage;
+        }
+    }
+
+    Ok((size, push_constant_stages))
+}
+
+#[profiling::all_functions]
+impl RayTracingPipelineBuilder {
+    pub fn new() -> Self {
+        Self {
+            entry_point: String::from("main"),
+            max_recursion_depth: 1,
+        }
+    }
+
+    /// Maximum depth of `traceRayEXT` calls, clamped to what the device supports.
+    pub fn with_max_recursion_depth(mut self, max_recursion_depth: u32) -> Self {
+        self.max_recursion_depth = max_recursion_depth;
+        self
+    }
+
+    pub fn build_from_spirv_u8(
+        self,
+        raygen_spirv: &[u8],
+        miss_spirv: &[u8],
+        closest_hit_spirv: &[u8],
+        renderer: &mut Renderer,
+    ) -> Result<ThreadSafeRef<RayTracingPipeline>, RayTracingPipelineBuildError> {
+        let decode = |source_spirv: &[u8]| {
+            ash::util::read_spv(&mut std::io::Cursor::new(source_spirv))
+                .map_err(RayTracingPipelineBuildError::SPIRVDecodingFailed)
+        };
+
+        self.build_from_spirv_u32(
+            &decode(raygen_spirv)?,
+            &decode(miss_spirv)?,
+            &decode(closest_hit_spirv)?,
+            renderer,
+        )
+    }
+
+    pub fn build_from_spirv_u32(
+        self,
+        raygen_spirv: &[u32],
+        miss_spirv: &[u32],
+        closest_hit_spirv: &[u32],
+        renderer: &mut Renderer,
+    ) -> Result<ThreadSafeRef<RayTracingPipeline>, RayTracingPipelineBuildError> {
+        let stages = [
+            (raygen_spirv, vk::ShaderStageFlags::RAYGEN_KHR),
+            (miss_spirv, vk::ShaderStageFlags::MISS_KHR),
+            (closest_hit_spirv, vk::ShaderStageFlags::CLOSEST_HIT_KHR),
+        ];
+        let (push_constant_size, push_constant_stages) = reflect_push_constants(&stages)?;
+
+        let dsl_bindings = [
+            vk::DescriptorSetLayoutBinding::default()
+                .binding(TLAS_BINDING)
+                .descriptor_type(vk::DescriptorType::ACCELERATION_STRUCTURE_KHR)
+                .descriptor_count(1)
+                .stage_flags(
+                    vk::ShaderStageFlags::RAYGEN_KHR | vk::ShaderStageFlags::CLOSEST_HIT_KHR,
+                ),
+            vk::DescriptorSetLayoutBinding::default()
+                .binding(TARGET_IMAGE_BINDING)
+                .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::RAYGEN_KHR),
+        ];
+        let dsl_info = vk::DescriptorSetLayoutCreateInfo::default().bindings(&dsl_bindings);
+        let dsl = unsafe {
+            renderer
+                .device
+                .create_descriptor_set_layout(&dsl_info, None)
+        }
+        .map_err(RayTracingPipelineBuildError::VulkanDSLCreationFailed)?;
+
+        let pool_sizes = [
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::ACCELERATION_STRUCTURE_KHR,
+                descriptor_count: 1,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::STORAGE_IMAGE,
+                descriptor_count: 1,
+            },
+        ];
+        let pool_info = vk::DescriptorPoolCreateInfo::default()
+            .max_sets(1)
+            .pool_sizes(&pool_sizes);
+        let descriptor_pool =
+            unsafe { renderer.device.create_descriptor_pool(&pool_info, None) }
+                .map_err(RayTracingPipelineBuildError::VulkanDescriptorPoolCreationFailed)?;
+
+        let descriptor_set_alloc_info = vk::DescriptorSetAllocateInfo::default()
+            .descriptor_pool(descriptor_pool)
+            .set_layouts(std::slice::from_ref(&dsl));
+        let descriptor_set = unsafe {
+            renderer
+                .device
+                .allocate_descriptor_sets(&descriptor_set_alloc_info)
+        }
+        .map_err(RayTracingPipelineBuildError::VulkanDescriptorSetAllocationFailed)?[0];
+        resource_registry::register(ResourceKind::DescriptorSet, 1);
+
+        let pc_ranges = if push_constant_size == 0 {
+            vec![]
+        } else {
+            vec![vk::PushConstantRange::default()
+                .stage_flags(push_constant_stages)
+                .offset(0)
+                .size(push_constant_size)]
+        };
+        let layout_info = vk::PipelineLayoutCreateInfo::default()
+            .set_layouts(std::slice::from_ref(&dsl))
+            .push_constant_ranges(&pc_ranges);
+        let layout = unsafe { renderer.device.create_pipeline_layout(&layout_info, None) }
+            .map_err(RayTracingPipelineBuildError::VulkanPipelineLayoutCreationFailed)?;
+
+        let shader_modules = stages
+            .iter()
+            .map(|(source_spirv, _)| {
+                create_shader_module(&renderer.device, source_spirv)
+                    .map_err(RayTracingPipelineBuildError::ShaderModuleCreationFailed)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let entry_point = CString::new(self.entry_point).unwrap();
+        let shader_stages = shader_modules
+            .iter()
+            .zip(stages.iter())
+            .map(|(module, (_, stage))| {
+                vk::PipelineShaderStageCreateInfo::default()
+                    .stage(*stage)
+                    .module(*module)
+                    .name(&entry_point)
+            })
+            .collect::<Vec<_>>();
+        let shader_groups = [
+            vk::RayTracingShaderGroupCreateInfoKHR::default()
+                .ty(vk::RayTracingShaderGroupTypeKHR::GENERAL)
+                .general_shader(0)
+                .closest_hit_shader(vk::SHADER_UNUSED_KHR)
+                .any_hit_shader(vk::SHADER_UNUSED_KHR)
+                .intersection_shader(vk::SHADER_UNUSED_KHR),
+            vk::RayTracingShaderGroupCreateInfoKHR::default()
+                .ty(vk::RayTracingShaderGroupTypeKHR::GENERAL)
+                .general_shader(1)
+                .closest_hit_shader(vk::SHADER_UNUSED_KHR)
+                .any_hit_shader(vk::SHADER_UNUSED_KHR)
+                .intersection_shader(vk::SHADER_UNUSED_KHR),
+            vk::RayTracingShaderGroupCreateInfoKHR::default()
+                .ty(vk::RayTracingShaderGroupTypeKHR::TRIANGLES_HIT_GROUP)
+                .general_shader(vk::SHADER_UNUSED_KHR)
+                .closest_hit_shader(2)
+                .any_hit_shader(vk::SHADER_UNUSED_KHR)
+                .intersection_shader(vk::SHADER_UNUSED_KHR),
+        ];
+
+        let properties = ray_tracing_properties(renderer);
+        let pipeline_info = vk::RayTracingPipelineCreateInfoKHR::default()
+            .stages(&shader_stages)
+            .groups(&shader_groups)
+            .max_pipeline_ray_recursion_depth(
+                self.max_recursion_depth
+                    .clamp(1, properties.max_ray_recursion_depth.max(1)),
+            )
+            .layout(layout);
+
+        let loader =
+            ash::khr::ray_tracing_pipeline::Device::new(&renderer.instance, &renderer.device);
+        let result = unsafe {
+            loader.create_ray_tracing_pipelines(
+                vk::DeferredOperationKHR::null(),
+                vk::PipelineCache::null(),
+                std::slice::from_ref(&pipeline_info),
+                None,
+            )
+        };
+        for shader_module in shader_modules {
+            unsafe { renderer.device.destroy_shader_module(shader_module, None) };
+        }
+        let pipeline = match result {
+            Ok(pipelines) => {
+                resource_registry::register(ResourceKind::Pipeline, 1);
+                pipelines[0]
+            }
+            Err((_, error)) => return Err(PipelineBuildError::from(error).into()),
+        };
+
+        let shader_binding_table =
+            build_shader_binding_table(pipeline, &loader, &properties, renderer)?;
+
+        Ok(ThreadSafeRef::new(RayTracingPipeline {
+            dsl,
+            descriptor_pool,
+            descriptor_set,
+            layout,
+            pipeline,
+            shader_binding_table,
+            push_constant_size,
+            push_constant_stages,
+            push_constant_data: vec![],
+            loader,
+        }))
+    }
+}
+
+impl Default for RayTracingPipelineBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Lays the handles of the raygen, miss and hit groups out in a buffer, each in its own region
+/// starting at the device's shader group base alignment.
+fn build_shader_binding_table(
+    pipeline: vk::Pipeline,
+    loader: &ash::khr::ray_tracing_pipeline::Device,
+    properties: &vk::PhysicalDeviceRayTracingPipelinePropertiesKHR,
+    renderer: &mut Renderer,
+) -> Result<ShaderBindingTable, RayTracingPipelineBuildError> {
+    let handle_size = u64::from(properties.shader_group_handle_size);
+    let handle_stride = align_up(
+        handle_size,
+        u64::from(properties.shader_group_handle_alignment),
+    );
+    let base_alignment = u64::from(properties.shader_group_base_alignment);
+    let region_size = align_up(handle_stride, base_alignment);
+
+    let handles = unsafe {
+        loader.get_ray_tracing_shader_group_handles(
+            pipeline,
+            0,
+            GROUP_COUNT,
+            (handle_size * u64::from(GROUP_COUNT)) as usize,
+        )
+    }
+    .map_err(RayTracingPipelineBuildError::ShaderGroupHandlesQueryFailed)?;
+
+    // The buffer's address is only guaranteed to be aligned to its memory requirements, so there
+    // is room left to align the first region
+    let mut buffer =
+        AllocatedBuffer::builder(region_size * u64::from(GROUP_COUNT) + base_alignment)
+            .with_name("Shader binding table")
+            .with_usage(
+                vk::BufferUsageFlags::SHADER_BINDING_TABLE_KHR
+                    | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            )
+            .build(renderer)?;
+    let buffer_info = vk::BufferDeviceAddressInfo::default().buffer(buffer.handle);
+    let buffer_address = unsafe { renderer.device.get_buffer_device_address(&buffer_info) };
+    let first_region_address = align_up(buffer_address, base_alignment);
+
+    let mut data = vec![0; buffer.size() as usize];
+    for (group, handle) in handles.chunks_exact(handle_size as usize).enumerate() {
+        let offset = (first_region_address - buffer_address + group as u64 * region_size) as usize;
+        data[offset..offset + handle.len()].copy_from_slice(handle);
+    }
+    if let Err(error) = buffer.upload_data(&data) {
+        buffer.destroy(&renderer.device, &mut renderer.allocator());
+        return Err(error.into());
+    }
+
+    let region = |group: u64, stride: u64| vk::StridedDeviceAddressRegionKHR {
+        device_address: first_region_address + group * region_size,
+        stride,
+        size: region_size,
+    };
+    Ok(ShaderBindingTable {
+        buffer,
+        // The raygen region's stride must be equal to its size
+        raygen_region: region(0, region_size),
+        miss_region: region(1, handle_stride),
+        hit_region: region(2, handle_stride),
+    })
+}
+
+#[profiling::all_functions]
+impl RayTracingPipeline {
+    pub fn builder() -> RayTracingPipelineBuilder {
+        RayTracingPipelineBuilder::new()
+    }
+
+    /// Sets the data pushed to the shaders' push constant block by the following traces. `T` must
+    /// have the exact size of the biggest block declared by the shaders.
+    pub fn set_push_constants<T: bytemuck::Pod>(
+        &mut self,
+        data: &T,
+    ) -> Result<(), PushConstantError> {
+        let data = bytemuck::bytes_of(data);
+        if data.len() != self.push_constant_size as usize {
+            return Err(PushConstantError::SizeMismatch {
+                expected: self.push_constant_size,
+                provided: data.len(),
+            });
+        }
+
+        self.push_constant_data.clear();
+        self.push_constant_data.extend_from_slice(data);
+
+        Ok(())
+    }
+
+    /// Traces `extent` rays (one per pixel of `target_image` usually) against `tlas`, and waits
+    /// for them to complete. The target image must have been created with the `STORAGE` usage, it
+    /// is left in the `GENERAL` layout (see [`Renderer::request_image_layout`] to sample it
+    /// afterwards).
+    pub fn trace(
+        &self,
+        target_image: &mut AllocatedImage,
+        tlas: &TLAS,
+        extent: (u32, u32),
+        renderer: &mut Renderer,
+    ) -> Result<(), ImmediateCommandError> {
+        let tlas_handle = tlas.handle();
+        let mut tlas_write_info = vk::WriteDescriptorSetAccelerationStructureKHR::default()
+            .acceleration_structures(std::slice::from_ref(&tlas_handle));
+        let image_info = vk::DescriptorImageInfo::default()
+            .image_view(target_image.view)
+            .image_layout(vk::ImageLayout::GENERAL);
+        let set_writes = [
+            vk::WriteDescriptorSet::default()
+                .dst_set(self.descriptor_set)
+                .dst_binding(TLAS_BINDING)
+                .descriptor_type(vk::DescriptorType::ACCELERATION_STRUCTURE_KHR)
+                .descriptor_count(1)
+                .push_next(&mut tlas_write_info),
+            vk::WriteDescriptorSet::default()
+                .dst_set(self.descriptor_set)
+                .dst_binding(TARGET_IMAGE_BINDING)
+                .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                .image_info(std::slice::from_ref(&image_info)),
+        ];
+        unsafe { renderer.device.update_descriptor_sets(&set_writes, &[]) };
+
+        let old_layout = target_image.layout;
+        let subresource_range = vk::ImageSubresourceRange::default()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .level_count(1)
+            .layer_count(1);
+        renderer.immediate_command(|cmd_buffer| unsafe {
+            if old_layout != vk::ImageLayout::GENERAL {
+                let to_general_barrier = vk::ImageMemoryBarrier::default()
+                    .src_access_mask(vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE)
+                    .dst_access_mask(vk::AccessFlags::SHADER_WRITE)
+                    .old_layout(old_layout)
+                    .new_layout(vk::ImageLayout::GENERAL)
+                    .image(target_image.handle)
+                    .subresource_range(subresource_range);
+                renderer.device.cmd_pipeline_barrier(
+                    *cmd_buffer,
+                    vk::PipelineStageFlags::ALL_COMMANDS,
+                    vk::PipelineStageFlags::RAY_TRACING_SHADER_KHR,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[],
+                    std::slice::from_ref(&to_general_barrier),
+                );
+            }
+
+            renderer.device.cmd_bind_pipeline(
+                *cmd_buffer,
+                vk::PipelineBindPoint::RAY_TRACING_KHR,
+                self.pipeline,
+            );
+            renderer.device.cmd_bind_descriptor_sets(
+                *cmd_buffer,
+                vk::PipelineBindPoint::RAY_TRACING_KHR,
+                self.layout,
+                0,
+                &[self.descriptor_set],
+                &[],
+            );
+            if !self.push_constant_data.is_empty() {
+                renderer.device.cmd_push_constants(
+                    *cmd_buffer,
+                    self.layout,
+                    self.push_constant_stages,
+                    0,
+                    &self.push_constant_data,
+                );
+            }
+
+            let sbt = &self.shader_binding_table;
+            self.loader.cmd_trace_rays(
+                *cmd_buffer,
+                &sbt.raygen_region,
+                &sbt.miss_region,
+                &sbt.hit_region,
+                &vk::StridedDeviceAddressRegionKHR::default(),
+                extent.0,
+                extent.1,
+                1,
+            );
+        })?;
+        target_image.layout = vk::ImageLayout::GENERAL;
+
+        Ok(())
+    }
+
+    pub fn destroy(&mut self, renderer: &mut Renderer) {
+        self.shader_binding_table
+            .buffer
+            .destroy(&renderer.device, &mut renderer.allocator());
+
+        unsafe {
+            renderer.device.destroy_pipeline(self.pipeline, None);
+            resource_registry::unregister(ResourceKind::Pipeline, 1);
+            renderer.device.destroy_pipeline_layout(self.layout, None);
+            renderer
+                .device
+                .destroy_descriptor_pool(self.descriptor_pool, None);
+            resource_registry::unregister(ResourceKind::DescriptorSet, 1);
+            renderer
+                .device
+                .destroy_descriptor_set_layout(self.dsl, None);
+        }
+    }
+}
@@ -4,7 +4,9 @@ use bytemuck::try_cast_slice;
 use thiserror::Error;
 
 use crate::{
-    allocated_types::{AllocatedBuffer, BufferBuildError, BufferBuildWithDataError},
+    allocated_types::{
+        AllocatedBuffer, BufferBuildError, BufferBuildWithDataError, BufferDataUploadError,
+    },
     renderer::Renderer,
     utils::{ImmediateCommandError, PodWrapper, ThreadSafeRef},
 };
@@ -14,12 +16,15 @@ pub enum TLASBuildError {
     #[error("Failed to cast the blas_list to raw bytes. This is an internal error and should never happen, sorry :( (raw error: {0})")]
     ByteExtractionFailed(bytemuck::PodCastError),
 
-    #[error("The BLAS list results in a size that cannot be converted from usize to u64 (probably too big)")]
+    #[error("The BLAS list is empty, or results in a size that cannot be converted from usize to u64 (probably too big)")]
     InvalidBLASList,
 
     #[error("Failed to build the instances buffer with error: {0}")]
     InstancesBufferBuildError(#[from] BufferBuildWithDataError),
 
+    #[error("Failed to upload the updated instances with error: {0}")]
+    InstancesUploadError(#[from] BufferDataUploadError),
+
     #[error("Error while running command buffer: {0}")]
     CommandBufferError(#[from] ImmediateCommandError),
 
@@ -33,29 +38,71 @@ pub enum TLASBuildError {
     TLASCreationFailed(vk::Result),
 }
 
+/// How [`TLAS::update_instances`] applied the new instances.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TLASUpdate {
+    /// The TLAS was updated in place, descriptor sets binding it stay valid.
+    Updated,
+    /// The TLAS was rebuilt with a new acceleration structure, the old one being destroyed:
+    /// descriptor sets binding it must be written again.
+    Rebuilt,
+}
+
 // Not tested with multiple TLAS yet, so it stays as a Resource instead of a Component for now
 #[derive(Resource)]
 pub struct TLAS {
     data_buffer: AllocatedBuffer,
     instances_buffer: AllocatedBuffer,
+    /// Kept for the updates, big enough for both builds and updates.
+    scratch_buffer: AllocatedBuffer,
+    /// Number of instances the TLAS was built with, which its updates must keep.
+    instance_count: u32,
     tlas: vk::AccelerationStructureKHR,
 }
 
+/// Instances as they are laid out in the instances buffer.
+fn instances_data(
+    instances: &[vk::AccelerationStructureInstanceKHR],
+) -> Result<Vec<u8>, TLASBuildError> {
+    let data_slice = instances
+        .iter()
+        .map(|instance| PodWrapper(*instance))
+        .collect::<Vec<_>>();
+
+    try_cast_slice(&data_slice)
+        .map(<[u8]>::to_vec)
+        .map_err(TLASBuildError::ByteExtractionFailed)
+}
+
+fn buffer_address(buffer: &AllocatedBuffer, renderer: &Renderer) -> vk::DeviceAddress {
+    let buffer_address_info = vk::BufferDeviceAddressInfo::default().buffer(buffer.handle);
+    unsafe {
+        renderer
+            .device
+            .get_buffer_device_address(&buffer_address_info)
+    }
+}
+
 impl TLAS {
     pub fn new(
         blas_list: &[vk::AccelerationStructureInstanceKHR],
         renderer: &mut Renderer,
     ) -> Result<ThreadSafeRef<Self>, TLASBuildError> {
-        let data_slice = blas_list
-            .iter()
-            .map(|blas| PodWrapper(*blas))
-            .collect::<Vec<_>>();
+        Self::build(blas_list, renderer).map(ThreadSafeRef::new)
+    }
 
-        let data: &[u8] =
-            try_cast_slice(&data_slice).map_err(TLASBuildError::ByteExtractionFailed)?;
+    pub fn handle(&self) -> vk::AccelerationStructureKHR {
+        self.tlas
+    }
+
+    fn build(
+        blas_list: &[vk::AccelerationStructureInstanceKHR],
+        renderer: &mut Renderer,
+    ) -> Result<Self, TLASBuildError> {
+        let data = instances_data(blas_list)?;
 
         let instances_buffer = AllocatedBuffer::builder(
-            std::mem::size_of_val(data)
+            data.len()
                 .try_into()
                 .map_err(|_| TLASBuildError::InvalidBLASList)?,
         )
@@ -64,19 +111,11 @@ impl TLAS {
             vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS
                 | vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR,
         )
-        .build_with_data(data, renderer)?;
-
-        let buffer_address_info =
-            vk::BufferDeviceAddressInfo::default().buffer(instances_buffer.handle);
-        let instances_buffer_address = unsafe {
-            renderer
-                .device
-                .get_buffer_device_address(&buffer_address_info)
-        };
+        .build_with_data(&data, renderer)?;
 
         let instances_data_info = vk::AccelerationStructureGeometryInstancesDataKHR::default()
             .data(vk::DeviceOrHostAddressConstKHR {
-                device_address: instances_buffer_address,
+                device_address: buffer_address(&instances_buffer, renderer),
             });
 
         let tlas_geometry = vk::AccelerationStructureGeometryKHR::default()
@@ -86,7 +125,10 @@ impl TLAS {
             });
 
         let build_info = vk::AccelerationStructureBuildGeometryInfoKHR::default()
-            .flags(vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE)
+            .flags(
+                vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE
+                    | vk::BuildAccelerationStructureFlagsKHR::ALLOW_UPDATE,
+            )
             .geometries(std::slice::from_ref(&tlas_geometry))
             .mode(vk::BuildAccelerationStructureModeKHR::BUILD)
             .ty(vk::AccelerationStructureTypeKHR::TOP_LEVEL);
@@ -123,35 +165,73 @@ impl TLAS {
         }
         .map_err(TLASBuildError::TLASCreationFailed)?;
 
-        let mut scratch_buffer = AllocatedBuffer::builder(build_sizes.build_scratch_size)
-            .with_name("TLAS scratch")
-            .with_usage(
-                vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
-            )
-            .build(renderer)
-            .map_err(TLASBuildError::ScratchBufferBuildError)?;
-        let buffer_info = vk::BufferDeviceAddressInfo::default().buffer(scratch_buffer.handle);
-        let scratch_address = unsafe { renderer.device.get_buffer_device_address(&buffer_info) };
+        let scratch_buffer = AllocatedBuffer::builder(std::cmp::max(
+            build_sizes.build_scratch_size,
+            build_sizes.update_scratch_size,
+        ))
+        .with_name("TLAS scratch")
+        .with_usage(
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+        )
+        .build(renderer)
+        .map_err(TLASBuildError::ScratchBufferBuildError)?;
+
+        let tlas = Self {
+            data_buffer,
+            instances_buffer,
+            scratch_buffer,
+            instance_count: blas_count,
+            tlas,
+        };
+        tlas.record_build(
+            build_info,
+            tlas_geometry,
+            vk::BuildAccelerationStructureModeKHR::BUILD,
+            renderer,
+        )?;
+
+        Ok(tlas)
+    }
 
-        let build_info =
-            build_info
-                .dst_acceleration_structure(tlas)
-                .scratch_data(vk::DeviceOrHostAddressKHR {
-                    device_address: scratch_address,
-                });
+    fn record_build(
+        &self,
+        build_info: vk::AccelerationStructureBuildGeometryInfoKHR,
+        tlas_geometry: vk::AccelerationStructureGeometryKHR,
+        mode: vk::BuildAccelerationStructureModeKHR,
+        renderer: &mut Renderer,
+    ) -> Result<(), TLASBuildError> {
+        let mut build_info = build_info
+            .geometries(std::slice::from_ref(&tlas_geometry))
+            .mode(mode)
+            .dst_acceleration_structure(self.tlas)
+            .scratch_data(vk::DeviceOrHostAddressKHR {
+                device_address: buffer_address(&self.scratch_buffer, renderer),
+            });
+        if mode == vk::BuildAccelerationStructureModeKHR::UPDATE {
+            build_info = build_info.src_acceleration_structure(self.tlas);
+        }
 
-        let offset_range =
-            vk::AccelerationStructureBuildRangeInfoKHR::default().primitive_count(blas_count);
+        let offset_range = vk::AccelerationStructureBuildRangeInfoKHR::default()
+            .primitive_count(self.instance_count);
 
+        let acceleration_structure_loader =
+            ash::khr::acceleration_structure::Device::new(&renderer.instance, &renderer.device);
         renderer.immediate_command(|cmd_buffer| {
             let barrier = vk::MemoryBarrier::default()
-                .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                .src_access_mask(
+                    vk::AccessFlags::TRANSFER_WRITE
+                        | vk::AccessFlags::ACCELERATION_STRUCTURE_READ_KHR,
+                )
                 .dst_access_mask(vk::AccessFlags::ACCELERATION_STRUCTURE_WRITE_KHR);
+            let build_barrier = vk::MemoryBarrier::default()
+                .src_access_mask(vk::AccessFlags::ACCELERATION_STRUCTURE_WRITE_KHR)
+                .dst_access_mask(vk::AccessFlags::ACCELERATION_STRUCTURE_READ_KHR);
 
             unsafe {
                 renderer.device.cmd_pipeline_barrier(
                     *cmd_buffer,
-                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::TRANSFER
+                        | vk::PipelineStageFlags::RAY_TRACING_SHADER_KHR,
                     vk::PipelineStageFlags::ACCELERATION_STRUCTURE_BUILD_KHR,
                     vk::DependencyFlags::empty(),
                     std::slice::from_ref(&barrier),
@@ -167,19 +247,90 @@ impl TLAS {
                     &[std::slice::from_ref(&offset_range)],
                 )
             };
-        })?;
 
-        scratch_buffer.destroy(&renderer.device, &mut renderer.allocator());
+            // Traces (see `RayTracingPipeline::trace`) read the TLAS after its build
+            unsafe {
+                renderer.device.cmd_pipeline_barrier(
+                    *cmd_buffer,
+                    vk::PipelineStageFlags::ACCELERATION_STRUCTURE_BUILD_KHR,
+                    vk::PipelineStageFlags::RAY_TRACING_SHADER_KHR,
+                    vk::DependencyFlags::empty(),
+                    std::slice::from_ref(&build_barrier),
+                    &[],
+                    &[],
+                )
+            };
+        })?;
 
-        Ok(ThreadSafeRef::new(Self {
-            data_buffer,
-            instances_buffer,
-            tlas,
-        }))
+        Ok(())
     }
 
-    pub fn update(&mut self) {
-        todo!()
+    /// Replaces the instances of the TLAS (e.g. with updated transforms, see
+    /// [`super::mesh_rendering::MeshRendering::tlas_instance`]), which must not be in use by the
+    /// GPU.
+    ///
+    /// The TLAS is updated in place, reusing its buffers, as long as there aren't more instances
+    /// than it was built with: missing instances are replaced by masked out copies of the first
+    /// one, which no ray can hit. It is only rebuilt when the instances outgrow it, which replaces
+    /// its acceleration structure: [`TLASUpdate::Rebuilt`] is then returned, and the descriptor
+    /// sets binding the TLAS (e.g. the ones of ray tracing pipelines) must be bound to it again
+    /// before tracing rays. An empty list of instances returns [`TLASBuildError::InvalidBLASList`],
+    /// and leaves the TLAS unchanged.
+    pub fn update_instances(
+        &mut self,
+        instances: &[vk::AccelerationStructureInstanceKHR],
+        renderer: &mut Renderer,
+    ) -> Result<TLASUpdate, TLASBuildError> {
+        if instances.is_empty() {
+            return Err(TLASBuildError::InvalidBLASList);
+        }
+        let instance_count: u32 = instances
+            .len()
+            .try_into()
+            .map_err(|_| TLASBuildError::InvalidBLASList)?;
+        if instance_count > self.instance_count {
+            let rebuilt_tlas = Self::build(instances, renderer)?;
+            self.destroy(renderer);
+            *self = rebuilt_tlas;
+
+            return Ok(TLASUpdate::Rebuilt);
+        }
+
+        let mut padded_instances = instances.to_vec();
+        padded_instances.resize(
+            self.instance_count as usize,
+            vk::AccelerationStructureInstanceKHR {
+                instance_custom_index_and_mask: vk::Packed24_8::new(0, 0),
+                ..instances[0]
+            },
+        );
+        self.instances_buffer
+            .upload_data(&instances_data(&padded_instances)?)?;
+
+        let tlas_geometry = vk::AccelerationStructureGeometryKHR::default()
+            .geometry_type(vk::GeometryTypeKHR::INSTANCES)
+            .geometry(vk::AccelerationStructureGeometryDataKHR {
+                instances: vk::AccelerationStructureGeometryInstancesDataKHR::default().data(
+                    vk::DeviceOrHostAddressConstKHR {
+                        device_address: buffer_address(&self.instances_buffer, renderer),
+                    },
+                ),
+            });
+        let build_info = vk::AccelerationStructureBuildGeometryInfoKHR::default()
+            .flags(
+                vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE
+                    | vk::BuildAccelerationStructureFlagsKHR::ALLOW_UPDATE,
+            )
+            .ty(vk::AccelerationStructureTypeKHR::TOP_LEVEL);
+
+        self.record_build(
+            build_info,
+            tlas_geometry,
+            vk::BuildAccelerationStructureModeKHR::UPDATE,
+            renderer,
+        )?;
+
+        Ok(TLASUpdate::Updated)
     }
 
     pub fn rebuild(self) -> Self {
@@ -198,5 +349,8 @@ impl TLAS {
 
         self.instances_buffer
             .destroy(&renderer.device, &mut renderer.allocator());
+
+        self.scratch_buffer
+            .destroy(&renderer.device, &mut renderer.allocator());
     }
 }
@@ -0,0 +1,204 @@
+use bevy_ecs::component::Component;
+use thiserror::Error;
+
+use crate::{
+    components::mesh_rendering::MeshRendering,
+    descriptor_resources::UniformUpdateError,
+    material::Vertex,
+    math_types::{Mat4, Quat, Vec3},
+};
+
+/// Maximum number of joints of a [`Skeleton`], which is the size of the joint matrix array
+/// shaders declare (`mat4 joints[256]`, filling the 16KiB all devices allow for uniform buffers).
+pub const MAX_JOINTS: usize = 256;
+/// Binding of [`crate::descriptor_resources::OBJECT_SET`] shaders declare the joint matrices at,
+/// see [`Skeleton::write_joint_matrices`].
+pub const JOINT_MATRICES_BINDING: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum SkeletonBuildError {
+    #[error("The skeleton has {0} joints, but at most {MAX_JOINTS} are supported.")]
+    TooManyJoints(usize),
+
+    #[error("The skeleton has {joint_count} joints, but {matrix_count} inverse bind matrices.")]
+    InverseBindMatrixCountMismatch {
+        joint_count: usize,
+        matrix_count: usize,
+    },
+
+    #[error("Joint {joint} has parent {parent}, which is not a joint of the skeleton.")]
+    InvalidParent { joint: usize, parent: usize },
+
+    #[error("The hierarchy of joint {0} contains a cycle.")]
+    HierarchyCycle(usize),
+}
+
+/// Local transform of a joint, relative to its parent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JointPose {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for JointPose {
+    fn default() -> Self {
+        Self {
+            translation: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            scale: Vec3::ONE,
+        }
+    }
+}
+
+impl JointPose {
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Joint {
+    pub name: String,
+    /// Index of the parent joint in the skeleton, `None` for the roots.
+    pub parent: Option<usize>,
+    /// Transform of the nodes between the joint and its parent joint (or the root of the scene)
+    /// that aren't joints, such as the armature node of Blender exports. The inverse bind
+    /// matrices account for them, so they must be applied for the bind pose to be correct.
+    pub parent_transform: Mat4,
+    /// Current pose of the joint, which animations update.
+    pub pose: JointPose,
+}
+
+/// Joint hierarchy deforming the [`crate::vertices::skinned::SkinnedVertex`] meshes of the
+/// entity. The joint matrices of the current pose are computed on the CPU and written to the mesh
+/// rendering of the entity by [`crate::systems::skinning::upload_joint_matrices`] whenever the
+/// skeleton is modified, and the vertex shader blends them (see
+/// [`Skeleton::write_joint_matrices`]).
+///
+/// Joint matrices are in the mesh's model space: the transform of the entity is applied on top of
+/// them as for any other mesh.
+#[derive(Debug, Component)]
+pub struct Skeleton {
+    pub joints: Vec<Joint>,
+    /// Inverse of each joint's model space transform in the bind pose, which the mesh was modeled
+    /// in.
+    inverse_bind_matrices: Vec<Mat4>,
+}
+
+#[profiling::all_functions]
+impl Skeleton {
+    /// Joints can be in any order, as long as their parents are joints of the skeleton. The
+    /// vertices reference the joints by their index in `joints`.
+    pub fn new(
+        joints: Vec<Joint>,
+        inverse_bind_matrices: Vec<Mat4>,
+    ) -> Result<Self, SkeletonBuildError> {
+        if joints.len() > MAX_JOINTS {
+            return Err(SkeletonBuildError::TooManyJoints(joints.len()));
+        }
+        if joints.len() != inverse_bind_matrices.len() {
+            return Err(SkeletonBuildError::InverseBindMatrixCountMismatch {
+                joint_count: joints.len(),
+                matrix_count: inverse_bind_matrices.len(),
+            });
+        }
+        for (joint_index, joint) in joints.iter().enumerate() {
+            // A chain longer than the joint count goes through a joint twice
+            let mut current = joint;
+            for _ in 0..=joints.len() {
+                let Some(parent) = current.parent else {
+                    break;
+                };
+                current = joints
+                    .get(parent)
+                    .ok_or(SkeletonBuildError::InvalidParent {
+                        joint: joint_index,
+                        parent,
+                    })?;
+            }
+            if current.parent.is_some() {
+                return Err(SkeletonBuildError::HierarchyCycle(joint_index));
+            }
+        }
+
+        Ok(Self {
+            joints,
+            inverse_bind_matrices,
+        })
+    }
+
+    #[profiling::skip]
+    pub fn inverse_bind_matrices(&self) -> &[Mat4] {
+        &self.inverse_bind_matrices
+    }
+
+    /// Model space transform of each joint in its current pose. Joints whose parent was made
+    /// invalid after the skeleton was built are treated as roots.
+    pub fn global_transforms(&self) -> Vec<Mat4> {
+        // `None` until computed, the parents being computed first as they may come after
+        let mut global_transforms = vec![None; self.joints.len()];
+        for joint_index in 0..self.joints.len() {
+            let mut chain = vec![];
+            let mut current = Some(joint_index);
+            let mut parent_transform = Mat4::IDENTITY;
+            // The chain is bounded in case a cycle was introduced
+            while let Some(index) = current.filter(|_| chain.len() <= self.joints.len()) {
+                if let Some(global_transform) = global_transforms[index] {
+                    parent_transform = global_transform;
+                    break;
+                }
+                chain.push(index);
+                current = self.joints[index]
+                    .parent
+                    .filter(|parent| *parent < self.joints.len());
+            }
+
+            for index in chain.into_iter().rev() {
+                let joint = &self.joints[index];
+                parent_transform *= joint.parent_transform * joint.pose.matrix();
+                global_transforms[index] = Some(parent_transform);
+            }
+        }
+
+        global_transforms.into_iter().flatten().collect()
+    }
+
+    /// Matrices moving the vertices from the bind pose to the current pose, as written to the
+    /// mesh renderings.
+    pub fn joint_matrices(&self) -> Vec<Mat4> {
+        self.global_transforms()
+            .into_iter()
+            .zip(&self.inverse_bind_matrices)
+            .map(|(global_transform, inverse_bind_matrix)| global_transform * *inverse_bind_matrix)
+            .collect()
+    }
+
+    /// Writes the joint matrices of the current pose to the mesh rendering of a skinned mesh,
+    /// which [`crate::systems::skinning::upload_joint_matrices`] does for the skeletons modified
+    /// each frame. The shader must declare them as a dynamic uniform (see
+    /// [`crate::shader::Shader::set_dynamic_uniforms`]) of [`MAX_JOINTS`] matrices:
+    /// ```glsl
+    /// layout(set = 3, binding = 1) uniform JointMatrices {
+    ///     mat4 joints[256];
+    /// };
+    /// ```
+    /// The matrices past the skeleton's joints are the identity. Like the model matrix, each frame
+    /// in flight reads its own copy of them from the renderer's dynamic uniform buffer, of which
+    /// every skinned mesh rendering uses 16KiB per frame (see
+    /// [`crate::renderer::RendererBuilder::with_dynamic_uniform_capacity`]).
+    pub fn write_joint_matrices<VertexType>(
+        &self,
+        mesh_rendering: &mut MeshRendering<VertexType>,
+    ) -> Result<(), UniformUpdateError>
+    where
+        VertexType: Vertex,
+    {
+        let mut joint_matrices = [Mat4::IDENTITY; MAX_JOINTS];
+        for (slot, matrix) in joint_matrices.iter_mut().zip(self.joint_matrices()) {
+            *slot = matrix;
+        }
+
+        mesh_rendering.set_dynamic_uniform_pod(JOINT_MATRICES_BINDING, joint_matrices)
+    }
+}
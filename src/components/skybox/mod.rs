@@ -0,0 +1,134 @@
+use ash::vk;
+use bevy_ecs::component::Component;
+use thiserror::Error;
+
+use crate::{
+    cubemap::Cubemap,
+    descriptor_resources::DescriptorResources,
+    material::{Material, MaterialBuildError, MaterialBuilder},
+    math_types::Vec3,
+    mesh::{Mesh, UploadError},
+    renderer::Renderer,
+    shader::{Shader, ShaderBuildError},
+    utils::ThreadSafeRef,
+    vertices::simple::SimpleVertex,
+};
+
+#[derive(Error, Debug)]
+pub enum SkyboxBuildError {
+    #[error("Creation of skybox shader failed with error: {0}.")]
+    ShaderCreationFailed(#[from] ShaderBuildError),
+
+    #[error("Creation of skybox material failed with error: {0}.")]
+    MaterialCreationFailed(#[from] MaterialBuildError),
+
+    #[error("Upload of the skybox cube failed with error: {0}.")]
+    MeshUploadFailed(#[from] UploadError),
+}
+
+/// Corners of the unit cube, the coordinate along each axis being given by a bit of the index.
+const CUBE_CORNERS: [Vec3; 8] = [
+    Vec3::new(-1.0, -1.0, -1.0),
+    Vec3::new(1.0, -1.0, -1.0),
+    Vec3::new(-1.0, 1.0, -1.0),
+    Vec3::new(1.0, 1.0, -1.0),
+    Vec3::new(-1.0, -1.0, 1.0),
+    Vec3::new(1.0, -1.0, 1.0),
+    Vec3::new(-1.0, 1.0, 1.0),
+    Vec3::new(1.0, 1.0, 1.0),
+];
+/// Triangles of the cube's faces, counter clockwise when seen from the outside.
+const CUBE_INDICES: [usize; 36] = [
+    4, 5, 7, 4, 7, 6, // +Z
+    0, 2, 3, 0, 3, 1, // -Z
+    5, 1, 3, 5, 3, 7, // +X
+    0, 4, 6, 0, 6, 2, // -X
+    6, 7, 3, 6, 3, 2, // +Y
+    0, 1, 5, 0, 5, 4, // -Y
+];
+
+/// Cubemap drawn behind everything else, around the camera, by
+/// [`crate::systems::skybox::render_skybox`].
+///
+/// The skybox is a cube centered on the camera and drawn at the far plane, without writing the
+/// depth, so the system can run before or after the mesh renderers.
+#[derive(Debug, Component)]
+pub struct Skybox {
+    pub material_ref: ThreadSafeRef<Material<SimpleVertex>>,
+    pub mesh_ref: ThreadSafeRef<Mesh<SimpleVertex>>,
+    cubemap_ref: ThreadSafeRef<Cubemap>,
+}
+
+#[profiling::all_functions]
+impl Skybox {
+    /// The skybox takes ownership of the cubemap, which is destroyed along with it.
+    pub fn new(cubemap: Cubemap, renderer: &mut Renderer) -> Result<Self, SkyboxBuildError> {
+        let cubemap_ref = ThreadSafeRef::new(cubemap);
+        let shader_ref = match Shader::from_spirv_u8(
+            include_bytes!("shaders/gen/skybox.vert"),
+            include_bytes!("shaders/gen/skybox.frag"),
+            &renderer.device,
+        ) {
+            Ok(shader_ref) => shader_ref,
+            Err(error) => {
+                cubemap_ref.lock().destroy(renderer);
+                return Err(error.into());
+            }
+        };
+
+        let material_ref = match MaterialBuilder::new()
+            .z_write(false)
+            .cull_mode(vk::CullModeFlags::FRONT)
+            .build(
+                &shader_ref,
+                DescriptorResources {
+                    cubemap_images: [(0, cubemap_ref.clone())].into(),
+                    ..Default::default()
+                },
+                renderer,
+            ) {
+            Ok(material_ref) => material_ref,
+            Err(error) => {
+                shader_ref.lock().destroy(&renderer.device);
+                cubemap_ref.lock().destroy(renderer);
+                return Err(error.into());
+            }
+        };
+
+        let vertices = CUBE_INDICES
+            .iter()
+            .map(|index| SimpleVertex {
+                position: CUBE_CORNERS[*index],
+            })
+            .collect();
+        let mesh_ref = match Mesh::from_vertices(vertices, renderer) {
+            Ok(mesh_ref) => mesh_ref,
+            Err(error) => {
+                material_ref.lock().destroy(renderer);
+                shader_ref.lock().destroy(&renderer.device);
+                cubemap_ref.lock().destroy(renderer);
+                return Err(error.into());
+            }
+        };
+
+        Ok(Self {
+            material_ref,
+            mesh_ref,
+            cubemap_ref,
+        })
+    }
+
+    #[profiling::skip]
+    pub fn cubemap(&self) -> &ThreadSafeRef<Cubemap> {
+        &self.cubemap_ref
+    }
+
+    /// Destroys the cube, the material and its shader, and the cubemap.
+    pub fn destroy(&mut self, renderer: &mut Renderer) {
+        self.mesh_ref.lock().destroy(renderer);
+        let mut material = self.material_ref.lock();
+        material.destroy(renderer);
+        material.shader_ref.lock().destroy(&renderer.device);
+        self.cubemap_ref.lock().destroy(renderer);
+    }
+}
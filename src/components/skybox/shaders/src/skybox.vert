@@ -8,16 +8,12 @@ layout(push_constant) uniform CameraData {
 }
 pc_CameraData;
 
-layout(set = 3, binding = 0) uniform ModelData { mat4 modelMatrix; }
-u_ModelData;
-
 layout(location = 0) out vec3 fs_TexCoords;
 
 void main() {
     fs_TexCoords = v_Position;
 
-    mat4 transform = pc_CameraData.viewProjection * u_ModelData.modelMatrix;
-    vec4 pos = transform * vec4(v_Position, 1);
+    // The cube follows the camera, and its depth is forced to the far plane
+    vec4 pos = pc_CameraData.viewProjection * vec4(v_Position + pc_CameraData.worldPos.xyz, 1);
     gl_Position = pos.xyww;
 }
-
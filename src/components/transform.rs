@@ -1,5 +1,7 @@
 use std::ops::Mul;
 
+use bevy_ecs::entity::Entity;
+
 use crate::{
     math_types::{Mat4, Quat, Vec3},
     utils::ThreadSafeRef,
@@ -11,6 +13,36 @@ struct CacheData {
     matrix: Mat4,
 }
 
+/// Makes the entity's [`Transform`] relative to the one of another entity, see
+/// [`crate::systems::transform::propagate_transforms`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, bevy_ecs::component::Component)]
+pub struct Parent(pub Entity);
+
+/// World space transform of an entity, which is its [`Transform`] combined with the ones of its
+/// [`Parent`] chain. It is computed (and added to the entities with a [`Transform`]) by
+/// [`crate::systems::transform::propagate_transforms`], and is what the rendering systems use
+/// when it is present.
+#[derive(Debug, Clone, Copy, PartialEq, bevy_ecs::component::Component)]
+pub struct GlobalTransform(pub(crate) Mat4);
+
+impl Default for GlobalTransform {
+    fn default() -> Self {
+        Self(Mat4::IDENTITY)
+    }
+}
+
+impl GlobalTransform {
+    pub fn matrix(&self) -> Mat4 {
+        self.0
+    }
+
+    /// Model matrix of an entity: its global transform if it has one, its local transform
+    /// otherwise (e.g. before the first propagation).
+    pub(crate) fn resolve(transform: &Transform, global_transform: Option<&Self>) -> Mat4 {
+        global_transform.map_or_else(|| transform.matrix(), Self::matrix)
+    }
+}
+
 #[derive(Debug, bevy_ecs::component::Component)]
 pub struct Transform {
     translation: Vec3,
@@ -145,3 +177,39 @@ impl Mul<Transform> for Transform {
         mat.into()
     }
 }
+
+/// Serialized form of a [`Transform`], without its cached matrix.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename = "Transform")]
+struct TransformData {
+    translation: Vec3,
+    rotation: Quat,
+    scale: Vec3,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Transform {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        TransformData {
+            translation: self.translation,
+            rotation: self.rotation,
+            scale: self.scale,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// The rotation is normalized, as hand edited or rounded files rarely hold unit quaternions.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Transform {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = TransformData::deserialize(deserializer)?;
+        let rotation = glam::Vec4::from(data.rotation)
+            .try_normalize()
+            .map(Quat::from_vec4)
+            .ok_or_else(|| serde::de::Error::custom("the rotation quaternion has a zero length"))?;
+
+        Ok(Self::from_trs(&data.translation, &rotation, &data.scale))
+    }
+}
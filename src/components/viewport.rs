@@ -0,0 +1,94 @@
+use ash::vk;
+use bevy_ecs::system::Resource;
+
+use super::camera::Camera;
+
+/// Region of the framebuffer a camera renders to. All values are fractions of the framebuffer's
+/// size (so that viewports follow window resizes), with the origin in the top left corner.
+#[derive(Debug, Clone, Copy)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+
+    pub min_depth: f32,
+    pub max_depth: f32,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::new(0.0, 0.0, 1.0, 1.0)
+    }
+}
+
+impl Viewport {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        }
+    }
+
+    pub fn with_depth_range(mut self, min_depth: f32, max_depth: f32) -> Self {
+        self.min_depth = min_depth;
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Size of the viewport in pixels, for the given framebuffer size.
+    pub fn pixel_size(&self, framebuffer_width: u32, framebuffer_height: u32) -> (u32, u32) {
+        (
+            (self.width * framebuffer_width as f32).round() as u32,
+            (self.height * framebuffer_height as f32).round() as u32,
+        )
+    }
+
+    /// Returns the vulkan viewport and the matching scissor, inside of `region` (see
+    /// [`crate::renderer::Renderer::render_region`]). When `flipped` is set, the viewport has a
+    /// negative height so that the Y axis of the NDC points up (see
+    /// [`crate::renderer::RendererBuilder::with_flipped_viewport`]).
+    pub(crate) fn to_vk(self, region: vk::Rect2D, flipped: bool) -> (vk::Viewport, vk::Rect2D) {
+        let x = region.offset.x as f32 + (self.x * region.extent.width as f32).round();
+        let y = region.offset.y as f32 + (self.y * region.extent.height as f32).round();
+        let (width, height) = self.pixel_size(region.extent.width, region.extent.height);
+
+        // This one small trick allows us to keep vertex data sane
+        // (Actual engineers hate him)
+        // This is also why we had to bump to requesting 1.1.0 lmao
+        // https://www.saschawillems.de/blog/2019/03/29/flipping-the-vulkan-viewport/
+        let viewport = vk::Viewport::default()
+            .x(x)
+            .width(width as f32)
+            .min_depth(self.min_depth)
+            .max_depth(self.max_depth);
+        let viewport = if flipped {
+            viewport.y(y + height as f32).height(-(height as f32))
+        } else {
+            viewport.y(y).height(height as f32)
+        };
+        let scissor = vk::Rect2D::default()
+            .offset(vk::Offset2D {
+                x: x as i32,
+                y: y as i32,
+            })
+            .extent(vk::Extent2D { width, height });
+
+        (viewport, scissor)
+    }
+}
+
+/// Additional cameras, each rendering the scene into its own viewport (split-screen,
+/// picture-in-picture, ...).
+///
+/// When this resource is inserted in the world and isn't empty, `render_meshes` draws every mesh
+/// once per view, in order, instead of using the main [`Camera`] resource. Cameras are resized to
+/// their viewport's pixel size when the window is resized.
+#[derive(Debug, Default, Resource)]
+pub struct CameraViews {
+    pub views: Vec<(Camera, Viewport)>,
+}
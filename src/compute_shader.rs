@@ -6,11 +6,13 @@ use crate::descriptor_resources::{
     create_dsl, DSLCreationError, DescriptorResources, DescriptorSetUpdateError,
     ResourceBindingError,
 };
+use crate::material::PushConstantError;
 use crate::pipeline_barrier::PipelineBarrier;
 use crate::pipeline_builder::{ComputePipelineBuilder, PipelineBuildError};
 use crate::renderer::Renderer;
+use crate::resource_registry::{self, ResourceKind};
 use crate::shader::create_shader_module;
-use crate::utils::ImmediateCommandError;
+use crate::utils::{AsyncCommandError, ImmediateCommandError};
 use crate::{shader::BindingData, texture::Texture, utils::ThreadSafeRef};
 
 use ash::vk;
@@ -35,7 +37,13 @@ pub struct ComputeShader {
 
     pub(crate) descriptor_set: vk::DescriptorSet,
     pub(crate) layout: vk::PipelineLayout,
-    pipeline: vk::Pipeline,
+    pub(crate) pipeline: vk::Pipeline,
+
+    /// Pushed before every dispatch, see [`ComputeShader::set_push_constants`].
+    push_constant_data: Vec<u8>,
+
+    /// `local_size` declared by the shader, see [`ComputeShader::workgroup_size`].
+    workgroup_size: [u32; 3],
 }
 
 #[derive(Error, Debug)]
@@ -55,6 +63,9 @@ pub enum ComputeShaderBuildError {
     #[error("SPIRV reflection creation failed with error message: {0}.")]
     ReflectionLoadingFailed(&'static str),
 
+    #[error("The provided SPIRV does not contain any entry point.")]
+    NoEntryPoint,
+
     #[error("Descriptor set layout creation failed with error: {0}.")]
     DSLCreationFailed(#[from] DSLCreationError),
 
@@ -79,6 +90,15 @@ pub enum ComputeShaderBuildError {
     PipelineCreationFailed(#[from] PipelineBuildError),
 }
 
+#[derive(Error, Debug)]
+pub enum ComputeDispatchError {
+    #[error("Update of the push constants failed with error: {0}.")]
+    PushConstantUpdateFailed(#[from] PushConstantError),
+
+    #[error("Execution of the dispatch failed with error: {0}.")]
+    DispatchFailed(#[from] ImmediateCommandError),
+}
+
 #[profiling::all_functions]
 impl ComputeShaderBuilder {
     pub fn new() -> Self {
@@ -95,10 +115,7 @@ impl ComputeShaderBuilder {
     ) -> Result<ThreadSafeRef<ComputeShader>, ComputeShaderBuildError> {
         let source_spirv =
             fs::read(source_path).map_err(|error| ComputeShaderBuildError::InvalidPath {
-                provided_path: source_path
-                    .to_str()
-                    .expect("Failed to parse provided path.")
-                    .to_owned(),
+                provided_path: source_path.display().to_string(),
                 error,
             })?;
 
@@ -130,45 +147,46 @@ impl ComputeShaderBuilder {
             .map_err(ComputeShaderBuildError::ReflectionLoadingFailed)?;
         let entry_point = reflection_module
             .enumerate_entry_points()
-            .map_err(ComputeShaderBuildError::ReflectionLoadingFailed)?[0]
-            .clone();
+            .map_err(ComputeShaderBuildError::ReflectionLoadingFailed)?
+            .into_iter()
+            .next()
+            .ok_or(ComputeShaderBuildError::NoEntryPoint)?;
         let bindings_reflection = reflection_module
             .enumerate_descriptor_bindings(Some(entry_point.name.as_str()))
             .map_err(ComputeShaderBuildError::ReflectionLoadingFailed)?;
         let push_constants = reflection_module
             .enumerate_push_constant_blocks(Some(entry_point.name.as_str()))
             .map_err(ComputeShaderBuildError::ReflectionLoadingFailed)?;
+        let workgroup_size = parse_workgroup_size(source_spirv, entry_point.id);
+
+        let bindings = bindings_reflection
+            .iter()
+            .map(BindingData::from)
+            .collect::<Vec<_>>();
 
         let dsl = create_dsl(
             &renderer.device,
             0,
-            &[(bindings_reflection.clone(), vk::ShaderStageFlags::COMPUTE)],
+            &[(bindings.as_slice(), vk::ShaderStageFlags::COMPUTE)],
         )?;
 
-        let bindings = bindings_reflection
-            .iter()
-            .map(|binding| BindingData {
-                set: binding.set,
-                slot: binding.binding,
-                descriptor_type: binding.descriptor_type,
-                size: binding.block.size,
-                dim: binding.image.dim,
-            })
-            .collect::<Vec<_>>();
-
         let ubo_count: u32 = descriptor_resources
             .uniform_buffers
             .len()
             .try_into()
             .unwrap();
+        let ssbo_count: u32 = descriptor_resources
+            .storage_buffers
+            .len()
+            .try_into()
+            .unwrap();
         let storage_image_count: u32 = descriptor_resources
             .storage_images
             .len()
             .try_into()
             .unwrap();
         let sampled_image_count: u32 = descriptor_resources
-            .sampled_images
-            .len()
+            .sampled_image_descriptor_count()
             .try_into()
             .unwrap();
 
@@ -177,6 +195,10 @@ impl ComputeShaderBuilder {
                 ty: vk::DescriptorType::UNIFORM_BUFFER,
                 descriptor_count: std::cmp::max(ubo_count, 1),
             },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::STORAGE_BUFFER,
+                descriptor_count: std::cmp::max(ssbo_count, 1),
+            },
             vk::DescriptorPoolSize {
                 ty: vk::DescriptorType::STORAGE_IMAGE,
                 descriptor_count: std::cmp::max(storage_image_count, 1),
@@ -203,6 +225,7 @@ impl ComputeShaderBuilder {
                 .allocate_descriptor_sets(&descriptor_set_alloc_info)
         }
         .map_err(ComputeShaderBuildError::VulkanDescriptorSetAllocationFailed)?[0];
+        resource_registry::register(ResourceKind::DescriptorSet, 1);
 
         descriptor_resources.update_descriptors_set_from_bindings(
             &bindings,
@@ -252,10 +275,73 @@ impl ComputeShaderBuilder {
             descriptor_resources,
             layout,
             pipeline,
+            push_constant_data: vec![],
+            workgroup_size,
         }))
     }
 }
 
+/// Reads the `local_size` of the entry point `entry_point_id` from its execution modes, which
+/// reflection doesn't expose. Shaders declaring none are run with a single invocation per group.
+fn parse_workgroup_size(spirv: &[u32], entry_point_id: u32) -> [u32; 3] {
+    const OP_EXECUTION_MODE: u32 = 16;
+    const OP_CONSTANT: u32 = 43;
+    const OP_SPEC_CONSTANT: u32 = 50;
+    const OP_EXECUTION_MODE_ID: u32 = 331;
+    const EXECUTION_MODE_LOCAL_SIZE: u32 = 17;
+    const EXECUTION_MODE_LOCAL_SIZE_ID: u32 = 38;
+    // Words of the module's header, before the first instruction
+    const HEADER_LENGTH: usize = 5;
+
+    let mut instructions = vec![];
+    let mut words = spirv.get(HEADER_LENGTH..).unwrap_or_default();
+    while let Some(first_word) = words.first() {
+        let word_count = (*first_word >> 16) as usize;
+        if word_count == 0 || word_count > words.len() {
+            break;
+        }
+        instructions.push((*first_word & 0xffff, &words[1..word_count]));
+        words = &words[word_count..];
+    }
+
+    // Specialization constants are read with their default value, as none are specialized
+    let constant_value = |id: u32| {
+        instructions
+            .iter()
+            .find_map(|(opcode, operands)| match operands {
+                [_, result_id, value, ..]
+                    if (*opcode == OP_CONSTANT || *opcode == OP_SPEC_CONSTANT)
+                        && *result_id == id =>
+                {
+                    Some(*value)
+                }
+                _ => None,
+            })
+    };
+
+    instructions
+        .iter()
+        .find_map(|(opcode, operands)| match (*opcode, operands) {
+            (OP_EXECUTION_MODE, [entry_point, EXECUTION_MODE_LOCAL_SIZE, x, y, z, ..])
+                if *entry_point == entry_point_id =>
+            {
+                Some([*x, *y, *z])
+            }
+            // LocalSizeId is declared with OpExecutionModeId, its operands being constant ids
+            (OP_EXECUTION_MODE_ID, [entry_point, EXECUTION_MODE_LOCAL_SIZE_ID, x, y, z, ..])
+                if *entry_point == entry_point_id =>
+            {
+                Some([
+                    constant_value(*x)?,
+                    constant_value(*y)?,
+                    constant_value(*z)?,
+                ])
+            }
+            _ => None,
+        })
+        .unwrap_or([1, 1, 1])
+}
+
 impl Default for ComputeShaderBuilder {
     fn default() -> Self {
         Self::new()
@@ -268,21 +354,138 @@ impl ComputeShader {
         ComputeShaderBuilder::new()
     }
 
+    /// Dispatches the shader and waits for it to complete. The pipeline and descriptor set are
+    /// created once by the builder, so the shader can be run repeatedly, with its resources
+    /// rebound (e.g. [`ComputeShader::rebind_storage_image`]) and its push constants updated
+    /// (see [`ComputeShader::set_push_constants`]) between runs.
     pub fn run(
         &self,
         group_shape: (u32, u32, u32),
         pipeline_barrier: PipelineBarrier,
         renderer: &mut Renderer,
     ) -> Result<(), ImmediateCommandError> {
-        renderer.immediate_command(|cmd_buffer| unsafe {
-            renderer.device.cmd_bind_pipeline(
+        renderer.immediate_command(|cmd_buffer| {
+            self.record_dispatch(
+                group_shape,
+                &pipeline_barrier,
+                &renderer.device,
                 *cmd_buffer,
-                vk::PipelineBindPoint::COMPUTE,
-                self.pipeline,
-            );
+            )
+        })
+    }
 
-            renderer.device.cmd_bind_descriptor_sets(
-                *cmd_buffer,
+    /// Number of invocations in each workgroup, as declared by the shader's `local_size_x`,
+    /// `local_size_y` and `local_size_z` (specialization constants keep their default value).
+    #[profiling::skip]
+    pub fn workgroup_size(&self) -> [u32; 3] {
+        self.workgroup_size
+    }
+
+    /// Number of workgroups covering `width`x`height`x`depth` invocations, rounded up so that
+    /// every invocation is run when the extent isn't a multiple of the workgroup size. The
+    /// invocations beyond the extent are run too, the shader must discard them.
+    pub fn group_count_for_extent(&self, width: u32, height: u32, depth: u32) -> (u32, u32, u32) {
+        let [size_x, size_y, size_z] = self.workgroup_size;
+        (
+            width.div_ceil(size_x),
+            height.div_ceil(size_y),
+            depth.div_ceil(size_z),
+        )
+    }
+
+    /// Same as [`ComputeShader::run`], with enough workgroups for one invocation per element of
+    /// a `width`x`height`x`depth` extent (see [`ComputeShader::group_count_for_extent`]).
+    pub fn dispatch_for_extent(
+        &self,
+        width: u32,
+        height: u32,
+        depth: u32,
+        pipeline_barrier: PipelineBarrier,
+        renderer: &mut Renderer,
+    ) -> Result<(), ImmediateCommandError> {
+        self.run(
+            self.group_count_for_extent(width, height, depth),
+            pipeline_barrier,
+            renderer,
+        )
+    }
+
+    /// Same as [`ComputeShader::run`], after replacing the push constants with `push_constants`
+    /// (see [`ComputeShader::set_push_constant_bytes`]).
+    pub fn run_with_push_constants(
+        &mut self,
+        group_shape: (u32, u32, u32),
+        push_constants: &[u8],
+        pipeline_barrier: PipelineBarrier,
+        renderer: &mut Renderer,
+    ) -> Result<(), ComputeDispatchError> {
+        self.set_push_constant_bytes(push_constants)?;
+        self.run(group_shape, pipeline_barrier, renderer)?;
+
+        Ok(())
+    }
+
+    /// Same as [`ComputeShader::run`], but submits the dispatch to [`Renderer::compute_queue`]
+    /// without waiting for it to complete, so that it can overlap with rendering on devices with a
+    /// dedicated compute queue family (it is submitted to the graphics queue otherwise). The
+    /// returned semaphore is signaled once the dispatch completes: pass it to
+    /// [`Renderer::wait_semaphore`] to make the next frame wait for it, or wait for it in your own
+    /// submission and destroy it afterwards.
+    ///
+    /// The shader and its bound resources must not be modified nor destroyed until the dispatch
+    /// completed. Buffers and images built with the renderer are shared by the graphics and
+    /// compute queue families, so the results can be used by the frame waiting for the semaphore
+    /// without ownership transfers. Resources created by hand with `SharingMode::EXCLUSIVE` must
+    /// be transferred to [`Renderer::compute_queue`]'s family and back instead.
+    pub fn run_async(
+        &self,
+        group_shape: (u32, u32, u32),
+        pipeline_barrier: PipelineBarrier,
+        renderer: &mut Renderer,
+    ) -> Result<vk::Semaphore, AsyncCommandError> {
+        let device = renderer.device.clone();
+        renderer.submit_async_compute(|cmd_buffer| {
+            self.record_dispatch(group_shape, &pipeline_barrier, &device, *cmd_buffer)
+        })
+    }
+
+    /// Sets the data pushed to the shader's push constant block by the following runs. `T` must
+    /// have the exact size of the block declared by the shader.
+    pub fn set_push_constants<T: bytemuck::Pod>(
+        &mut self,
+        data: &T,
+    ) -> Result<(), PushConstantError> {
+        self.set_push_constant_bytes(bytemuck::bytes_of(data))
+    }
+
+    /// Same as [`ComputeShader::set_push_constants`], with the raw bytes of the block.
+    pub fn set_push_constant_bytes(&mut self, data: &[u8]) -> Result<(), PushConstantError> {
+        let expected = self.push_constants.first().map_or(0, |block| block.size);
+        if data.len() != expected as usize {
+            return Err(PushConstantError::SizeMismatch {
+                expected,
+                provided: data.len(),
+            });
+        }
+
+        self.push_constant_data.clear();
+        self.push_constant_data.extend_from_slice(data);
+
+        Ok(())
+    }
+
+    fn record_dispatch(
+        &self,
+        group_shape: (u32, u32, u32),
+        pipeline_barrier: &PipelineBarrier,
+        device: &ash::Device,
+        cmd_buffer: vk::CommandBuffer,
+    ) {
+        unsafe {
+            device.cmd_bind_pipeline(cmd_buffer, vk::PipelineBindPoint::COMPUTE, self.pipeline);
+
+            device.cmd_bind_descriptor_sets(
+                cmd_buffer,
                 vk::PipelineBindPoint::COMPUTE,
                 self.layout,
                 0,
@@ -290,20 +493,20 @@ impl ComputeShader {
                 &[],
             );
 
-            renderer
-                .device
-                .cmd_dispatch(*cmd_buffer, group_shape.0, group_shape.1, group_shape.2);
+            if !self.push_constant_data.is_empty() {
+                device.cmd_push_constants(
+                    cmd_buffer,
+                    self.layout,
+                    vk::ShaderStageFlags::COMPUTE,
+                    0,
+                    &self.push_constant_data,
+                );
+            }
+
+            device.cmd_dispatch(cmd_buffer, group_shape.0, group_shape.1, group_shape.2);
+        }
 
-            renderer.device.cmd_pipeline_barrier(
-                *cmd_buffer,
-                pipeline_barrier.src_stage_mask,
-                pipeline_barrier.dst_stage_mask,
-                pipeline_barrier.dependency_flags,
-                &pipeline_barrier.memory_barriers,
-                &pipeline_barrier.buffer_memory_barriers,
-                &pipeline_barrier.image_memory_barriers,
-            )
-        })
+        pipeline_barrier.record(device, cmd_buffer);
     }
 
     pub fn bind_uniform(
@@ -345,11 +548,66 @@ impl ComputeShader {
         Ok(old_buffer)
     }
 
+    /// Replaces the storage buffer bound to `binding_slot`, and returns the previous one. As for
+    /// [`ComputeShader::rebind_storage_image`], this must not be called while a dispatch of the
+    /// shader is executing.
+    pub fn bind_storage_buffer(
+        &mut self,
+        binding_slot: u32,
+        buffer_ref: ThreadSafeRef<AllocatedBuffer>,
+        renderer: &mut Renderer,
+    ) -> Result<ThreadSafeRef<AllocatedBuffer>, ResourceBindingError> {
+        let Some(old_buffer) = self
+            .descriptor_resources
+            .storage_buffers
+            .insert(binding_slot, buffer_ref.clone())
+        else {
+            return Err(ResourceBindingError::InvalidBindingSlot {
+                slot: binding_slot,
+                set: 0,
+            });
+        };
+
+        let buffer = buffer_ref.lock();
+
+        let descriptor_buffer_info = vk::DescriptorBufferInfo::default()
+            .buffer(buffer.handle)
+            .offset(0)
+            .range(buffer.size());
+
+        let set_write = vk::WriteDescriptorSet::default()
+            .dst_set(self.descriptor_set)
+            .dst_binding(binding_slot)
+            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+            .buffer_info(std::slice::from_ref(&descriptor_buffer_info));
+
+        unsafe {
+            renderer
+                .device
+                .update_descriptor_sets(std::slice::from_ref(&set_write), &[])
+        };
+
+        Ok(old_buffer)
+    }
+
     pub fn bind_storage_image<T: bytemuck::Pod>(
         &mut self,
         binding_slot: u32,
         image_ref: ThreadSafeRef<AllocatedImage>,
         renderer: &mut Renderer,
+    ) -> Result<ThreadSafeRef<AllocatedImage>, ResourceBindingError> {
+        self.rebind_storage_image(binding_slot, image_ref, renderer)
+    }
+
+    /// Replaces the storage image bound to `binding_slot`, and returns the previous one. The
+    /// descriptor set is updated in place, so this must not be called while a dispatch of the
+    /// shader is executing (e.g. submitted by [`ComputeShader::run_async`]). Swapping two images
+    /// between runs allows ping-pong effects, the images must be in the `GENERAL` layout.
+    pub fn rebind_storage_image(
+        &mut self,
+        binding_slot: u32,
+        image_ref: ThreadSafeRef<AllocatedImage>,
+        renderer: &mut Renderer,
     ) -> Result<ThreadSafeRef<AllocatedImage>, ResourceBindingError> {
         let Some(old_image) = self
             .descriptor_resources
@@ -425,10 +683,12 @@ impl ComputeShader {
     pub fn destroy(&mut self, renderer: &mut Renderer) {
         unsafe {
             renderer.device.destroy_pipeline(self.pipeline, None);
+            resource_registry::unregister(ResourceKind::Pipeline, 1);
             renderer.device.destroy_pipeline_layout(self.layout, None);
             renderer
                 .device
                 .destroy_descriptor_pool(self.descriptor_pool, None);
+            resource_registry::unregister(ResourceKind::DescriptorSet, 1);
 
             renderer
                 .device
@@ -0,0 +1,341 @@
+use crate::{
+    allocated_types::{AllocatedImage, ImageBuildError},
+    compute_shader::{ComputeShader, ComputeShaderBuildError},
+    descriptor_resources::DescriptorResources,
+    renderer::Renderer,
+    resource_registry::{self, ResourceKind},
+    texture::{
+        load_hdr_image_file, load_image_file, ImageFileLoadingError, Texture, TextureFormat,
+    },
+    utils::{ImmediateCommandError, ThreadSafeRef},
+};
+
+use ash::vk;
+use image::EncodableLayout;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CubemapBuildError {
+    #[error("Base image loading failed with error: {0}.")]
+    ImageLoadError(#[from] ImageFileLoadingError),
+
+    #[error("Face \"{face}\" is {found:?} pixels, but all faces must match the front face's {expected:?}.")]
+    MismatchedFaceDimensions {
+        face: &'static str,
+        expected: (u32, u32),
+        found: (u32, u32),
+    },
+
+    #[error("Creation of texture's underlying image failed with error: {0}.")]
+    ImageCreationFailed(#[from] ImageBuildError),
+
+    #[error("Vulkan creation of texture sampler failed with result: {0}.")]
+    VulkanSamplerCreationFailed(vk::Result),
+
+    #[error("Creation of the equirectangular projection compute shader failed with error: {0}.")]
+    ComputeShaderCreationFailed(#[from] ComputeShaderBuildError),
+
+    #[error(
+        "Projection of the equirectangular image on the cubemap faces failed with error: {0}."
+    )]
+    ProjectionFailed(#[from] ImmediateCommandError),
+}
+
+/// Format of the cubemaps created by [`Cubemap::from_equirectangular`].
+pub const EQUIRECTANGULAR_CUBEMAP_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+const WORKGROUP_SIZE: u32 = 16;
+
+#[derive(Debug)]
+pub struct Cubemap {
+    pub image_ref: ThreadSafeRef<AllocatedImage>,
+    pub sampler: vk::Sampler,
+
+    pub path: Option<String>,
+}
+
+#[profiling::all_functions]
+impl Cubemap {
+    pub fn build_from_folder(
+        folder_path: &str,
+        extension: &str,
+        format: TextureFormat,
+        renderer: &mut Renderer,
+    ) -> Result<ThreadSafeRef<Cubemap>, CubemapBuildError> {
+        let front_path: std::path::PathBuf = [folder_path, format!("front.{extension}").as_str()]
+            .iter()
+            .collect();
+        let back_path: std::path::PathBuf = [folder_path, format!("back.{extension}").as_str()]
+            .iter()
+            .collect();
+        let top_path: std::path::PathBuf = [folder_path, format!("top.{extension}").as_str()]
+            .iter()
+            .collect();
+        let bottom_path: std::path::PathBuf = [folder_path, format!("bottom.{extension}").as_str()]
+            .iter()
+            .collect();
+        let right_path: std::path::PathBuf = [folder_path, format!("right.{extension}").as_str()]
+            .iter()
+            .collect();
+        let left_path: std::path::PathBuf = [folder_path, format!("left.{extension}").as_str()]
+            .iter()
+            .collect();
+
+        let front_image = load_image_file(&front_path)?;
+        let back_image = load_image_file(&back_path)?;
+        let top_image = load_image_file(&top_path)?;
+        let bottom_image = load_image_file(&bottom_path)?;
+        let right_image = load_image_file(&right_path)?;
+        let left_image = load_image_file(&left_path)?;
+
+        let initial_layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+        let format: vk::Format = format.into();
+        let (width, height) = front_image.dimensions();
+        for (face, image) in [
+            ("back", &back_image),
+            ("top", &top_image),
+            ("bottom", &bottom_image),
+            ("right", &right_image),
+            ("left", &left_image),
+        ] {
+            if image.dimensions() != (width, height) {
+                return Err(CubemapBuildError::MismatchedFaceDimensions {
+                    face,
+                    expected: (width, height),
+                    found: image.dimensions(),
+                });
+            }
+        }
+        let data = [
+            front_image.as_bytes(),
+            back_image.as_bytes(),
+            top_image.as_bytes(),
+            bottom_image.as_bytes(),
+            right_image.as_bytes(),
+            left_image.as_bytes(),
+        ]
+        .concat();
+
+        let final_image = AllocatedImage::builder(vk::Extent3D {
+            width,
+            height,
+            depth: 1,
+        })
+        .cubemap_default(format)
+        .with_layout(initial_layout)
+        .with_data(data)
+        .build(renderer)?;
+
+        let sampler_info = vk::SamplerCreateInfo::default()
+            .mag_filter(vk::Filter::NEAREST)
+            .min_filter(vk::Filter::NEAREST)
+            .address_mode_u(vk::SamplerAddressMode::REPEAT)
+            .address_mode_v(vk::SamplerAddressMode::REPEAT)
+            .address_mode_w(vk::SamplerAddressMode::REPEAT);
+        let sampler = unsafe { renderer.device.create_sampler(&sampler_info, None) }
+            .map_err(CubemapBuildError::VulkanSamplerCreationFailed)?;
+        resource_registry::register(ResourceKind::Sampler, 1);
+
+        let folder_path = folder_path.to_owned();
+
+        renderer.auto_name(final_image.handle, &folder_path);
+        renderer.auto_name(final_image.view, &format!("{folder_path} (view)"));
+        renderer.auto_name(sampler, &format!("{folder_path} (sampler)"));
+
+        Ok(ThreadSafeRef::new(Cubemap {
+            image_ref: ThreadSafeRef::new(final_image),
+            sampler,
+            path: Some(folder_path),
+        }))
+    }
+
+    /// Loads an equirectangular panorama (usually an HDR `.hdr` file, any format supported by the
+    /// `image` crate works) and projects it on the faces of a [`EQUIRECTANGULAR_CUBEMAP_FORMAT`]
+    /// cubemap of `face_size` pixels, with a compute shader.
+    ///
+    /// The panorama is expected to cover 360 degrees horizontally and 180 vertically, which makes
+    /// it twice as wide as it is high: other aspect ratios are stretched, and a warning is logged.
+    /// Its top row looks up (+Y), and its center towards +X.
+    pub fn from_equirectangular(
+        path: &str,
+        face_size: u32,
+        renderer: &mut Renderer,
+    ) -> Result<ThreadSafeRef<Cubemap>, CubemapBuildError> {
+        let panorama = load_hdr_image_file(std::path::Path::new(path))?;
+        let (width, height) = panorama.dimensions();
+        if width != 2 * height {
+            log::warn!(
+                "Equirectangular image \"{path}\" is {width}x{height} pixels, but should be twice as wide as it is high"
+            );
+        }
+
+        let panorama_image = AllocatedImage::builder(vk::Extent3D {
+            width,
+            height,
+            depth: 1,
+        })
+        .texture_default(vk::Format::R32G32B32A32_SFLOAT)
+        .with_data(bytemuck::cast_slice(panorama.as_raw()).to_vec())
+        .build(renderer)?;
+        let panorama_texture = match Texture::from_image(panorama_image, renderer) {
+            Ok(texture) => texture,
+            Err(result) => return Err(CubemapBuildError::VulkanSamplerCreationFailed(result)),
+        };
+        let panorama_texture_ref = ThreadSafeRef::new(panorama_texture);
+
+        let cubemap_image = AllocatedImage::builder(vk::Extent3D {
+            width: face_size,
+            height: face_size,
+            depth: 1,
+        })
+        // The usage must be set first, as uninitialized images only use the create info's one
+        .with_usage(vk::ImageUsageFlags::STORAGE)
+        .cubemap_default(EQUIRECTANGULAR_CUBEMAP_FORMAT)
+        .build_uninitialized(&renderer.device, &mut renderer.allocator());
+        let cubemap_image_ref = match cubemap_image {
+            Ok(cubemap_image) => ThreadSafeRef::new(cubemap_image),
+            Err(error) => {
+                panorama_texture_ref.lock().destroy(renderer);
+                return Err(error.into());
+            }
+        };
+
+        let projection_result = Self::project_equirectangular(
+            &panorama_texture_ref,
+            &cubemap_image_ref,
+            face_size,
+            renderer,
+        );
+        panorama_texture_ref.lock().destroy(renderer);
+        if let Err(error) = projection_result {
+            cubemap_image_ref.lock().destroy(renderer);
+            return Err(error);
+        }
+
+        let sampler_info = vk::SamplerCreateInfo::default()
+            .mag_filter(vk::Filter::LINEAR)
+            .min_filter(vk::Filter::LINEAR)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE);
+        let sampler = match unsafe { renderer.device.create_sampler(&sampler_info, None) } {
+            Ok(sampler) => sampler,
+            Err(result) => {
+                cubemap_image_ref.lock().destroy(renderer);
+                return Err(CubemapBuildError::VulkanSamplerCreationFailed(result));
+            }
+        };
+        resource_registry::register(ResourceKind::Sampler, 1);
+
+        {
+            let cubemap_image = cubemap_image_ref.lock();
+            renderer.auto_name(cubemap_image.handle, path);
+            renderer.auto_name(cubemap_image.view, &format!("{path} (view)"));
+        }
+        renderer.auto_name(sampler, &format!("{path} (sampler)"));
+
+        Ok(ThreadSafeRef::new(Cubemap {
+            image_ref: cubemap_image_ref,
+            sampler,
+            path: Some(path.to_owned()),
+        }))
+    }
+
+    /// Writes every face of `cubemap_image_ref` from the panorama, and leaves it in
+    /// `SHADER_READ_ONLY_OPTIMAL` layout.
+    fn project_equirectangular(
+        panorama_texture_ref: &ThreadSafeRef<Texture>,
+        cubemap_image_ref: &ThreadSafeRef<AllocatedImage>,
+        face_size: u32,
+        renderer: &mut Renderer,
+    ) -> Result<(), CubemapBuildError> {
+        let compute_shader_ref = ComputeShader::builder().build_from_spirv_u8(
+            include_bytes!("shaders/gen/equirectangular_to_cubemap.comp"),
+            DescriptorResources {
+                sampled_images: [(0, panorama_texture_ref.clone())].into(),
+                storage_images: [(1, cubemap_image_ref.clone())].into(),
+                ..Default::default()
+            },
+            renderer,
+        )?;
+
+        let compute_shader = compute_shader_ref.lock();
+        let mut cubemap_image = cubemap_image_ref.lock();
+        let subresource_range = vk::ImageSubresourceRange::default()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .level_count(1)
+            .layer_count(6);
+        let group_count = face_size.div_ceil(WORKGROUP_SIZE);
+
+        let dispatch_result = renderer.immediate_command(|cmd_buffer| unsafe {
+            let device = &renderer.device;
+
+            let to_general_barrier = vk::ImageMemoryBarrier::default()
+                .src_access_mask(vk::AccessFlags::NONE)
+                .dst_access_mask(vk::AccessFlags::SHADER_WRITE)
+                .old_layout(vk::ImageLayout::UNDEFINED)
+                .new_layout(vk::ImageLayout::GENERAL)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(cubemap_image.handle)
+                .subresource_range(subresource_range);
+            device.cmd_pipeline_barrier(
+                *cmd_buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                std::slice::from_ref(&to_general_barrier),
+            );
+
+            device.cmd_bind_pipeline(
+                *cmd_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                compute_shader.pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                *cmd_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                compute_shader.layout,
+                0,
+                &[compute_shader.descriptor_set],
+                &[],
+            );
+            device.cmd_dispatch(*cmd_buffer, group_count, group_count, 6);
+
+            let to_shader_read_barrier = vk::ImageMemoryBarrier::default()
+                .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+                .dst_access_mask(vk::AccessFlags::SHADER_READ)
+                .old_layout(vk::ImageLayout::GENERAL)
+                .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(cubemap_image.handle)
+                .subresource_range(subresource_range);
+            device.cmd_pipeline_barrier(
+                *cmd_buffer,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                std::slice::from_ref(&to_shader_read_barrier),
+            );
+        });
+        if dispatch_result.is_ok() {
+            cubemap_image.layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+        }
+        drop(cubemap_image);
+        drop(compute_shader);
+        compute_shader_ref.lock().destroy(renderer);
+
+        Ok(dispatch_result?)
+    }
+
+    pub fn destroy(&mut self, renderer: &mut Renderer) {
+        unsafe { renderer.device.destroy_sampler(self.sampler, None) };
+        resource_registry::unregister(ResourceKind::Sampler, 1);
+
+        self.image_ref.lock().destroy(renderer);
+    }
+}
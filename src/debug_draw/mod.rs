@@ -0,0 +1,308 @@
+//! Immediate mode 3D debug drawing.
+//!
+//! The functions of this module can be called from anywhere during a frame (usually
+//! `on_update` or a system), the primitives they add are drawn as depth tested lines once the ECS
+//! schedule has run, using the ECS world's [`Camera`] resource, and are then discarded. Nothing is
+//! kept between frames, so primitives must be added again every frame they should be visible.
+
+use ash::vk;
+use bytemuck::bytes_of;
+use thiserror::Error;
+
+use std::sync::{Mutex, MutexGuard};
+
+use crate::{
+    allocated_types::{AllocatedBuffer, BufferResizeError},
+    components::{camera::Camera, viewport::Viewport},
+    descriptor_resources::DescriptorResources,
+    material::{Material, MaterialBuildError, MaterialBuilder, PrimitiveTopology},
+    math_types::{Mat4, Vec3, Vec4},
+    renderer::Renderer,
+    shader::{Shader, ShaderBuildError},
+    systems::mesh_renderer::CameraData,
+    utils::ThreadSafeRef,
+};
+
+pub use crate::vertices::line::LineVertex;
+
+/// Segments used to approximate circles (and the great circles of spheres).
+const CIRCLE_SEGMENTS: u32 = 32;
+
+static PENDING_LINES: Mutex<Vec<LineVertex>> = Mutex::new(Vec::new());
+
+fn pending_lines() -> MutexGuard<'static, Vec<LineVertex>> {
+    PENDING_LINES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+pub fn line(start: Vec3, end: Vec3, color: Vec4) {
+    pending_lines().extend([
+        LineVertex {
+            position: start,
+            color,
+        },
+        LineVertex {
+            position: end,
+            color,
+        },
+    ]);
+}
+
+/// Draws a line from `start` to `end`, with a head at `end` a fifth of the arrow's length.
+pub fn arrow(start: Vec3, end: Vec3, color: Vec4) {
+    line(start, end, color);
+
+    let direction = end - start;
+    let length = direction.length();
+    if length <= f32::EPSILON {
+        return;
+    }
+    let (side, up) = (direction / length).any_orthonormal_pair();
+    let head_base = end - direction * 0.2;
+    let head_radius = length * 0.05;
+    for offset in [side, -side, up, -up] {
+        line(end, head_base + offset * head_radius, color);
+    }
+}
+
+/// Draws the edges of the axis aligned box going from `min` to `max`.
+pub fn aabb(min: Vec3, max: Vec3, color: Vec4) {
+    transformed_aabb(&Mat4::IDENTITY, min, max, color);
+}
+
+/// Draws the edges of the box going from `min` to `max`, transformed by `transform` (e.g. the
+/// bounds of a mesh placed in the world by its entity's transform).
+pub fn transformed_aabb(transform: &Mat4, min: Vec3, max: Vec3, color: Vec4) {
+    box_edges(|corner| transform.transform_point3(corner), min, max, color);
+}
+
+/// Draws the edges of the volume seen through `view_projection` (e.g. the frustum of another
+/// camera, see [`Camera::view_projection`]), which must have a `[0, 1]` depth range like the
+/// renderer's projections. Projections without a far plane can't be drawn, as their far corners
+/// are infinitely far away.
+pub fn frustum(view_projection: &Mat4, color: Vec4) {
+    let inverse = view_projection.inverse();
+    box_edges(
+        |corner| inverse.project_point3(corner),
+        Vec3::new(-1.0, -1.0, 0.0),
+        Vec3::ONE,
+        color,
+    );
+}
+
+/// Draws the edges of the box going from `min` to `max`, each corner being placed by `place`.
+fn box_edges(place: impl Fn(Vec3) -> Vec3, min: Vec3, max: Vec3, color: Vec4) {
+    let corner = |index: u32| {
+        place(Vec3::new(
+            if index & 1 == 0 { min.x } else { max.x },
+            if index & 2 == 0 { min.y } else { max.y },
+            if index & 4 == 0 { min.z } else { max.z },
+        ))
+    };
+
+    // Every corner is linked to the corners differing from it on a single axis
+    for index in 0..8 {
+        for axis_bit in [1, 2, 4] {
+            if index & axis_bit == 0 {
+                line(corner(index), corner(index | axis_bit), color);
+            }
+        }
+    }
+}
+
+/// Draws a square grid of `cell_count`x`cell_count` cells `cell_size` units wide, centered on
+/// `center` in the plane orthogonal to `normal` (e.g. a floor grid with [`Vec3::Y`]).
+pub fn grid(center: Vec3, normal: Vec3, cell_size: f32, cell_count: u32, color: Vec4) {
+    let (tangent, bitangent) = normal.normalize_or(Vec3::Y).any_orthonormal_pair();
+    let half_extent = cell_size * cell_count as f32 / 2.0;
+
+    for line_index in 0..=cell_count {
+        let offset = line_index as f32 * cell_size - half_extent;
+        line(
+            center + tangent * offset - bitangent * half_extent,
+            center + tangent * offset + bitangent * half_extent,
+            color,
+        );
+        line(
+            center + bitangent * offset - tangent * half_extent,
+            center + bitangent * offset + tangent * half_extent,
+            color,
+        );
+    }
+}
+
+pub fn circle(center: Vec3, normal: Vec3, radius: f32, color: Vec4) {
+    let (tangent, bitangent) = normal.normalize_or(Vec3::Y).any_orthonormal_pair();
+    let point = |segment: u32| {
+        let angle = segment as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::TAU;
+        center + (tangent * angle.cos() + bitangent * angle.sin()) * radius
+    };
+
+    for segment in 0..CIRCLE_SEGMENTS {
+        line(point(segment), point(segment + 1), color);
+    }
+}
+
+/// Draws a sphere as its three great circles aligned with the world axes.
+pub fn sphere(center: Vec3, radius: f32, color: Vec4) {
+    for normal in [Vec3::X, Vec3::Y, Vec3::Z] {
+        circle(center, normal, radius, color);
+    }
+}
+
+/// Draws the axes of `transform` (e.g. [`crate::components::transform::Transform::matrix`]), `size`
+/// units long and colored red, green and blue.
+pub fn axes(transform: &Mat4, size: f32) {
+    let origin = transform.transform_point3(Vec3::ZERO);
+    for (axis, color) in [
+        (Vec3::X, Vec4::new(1.0, 0.0, 0.0, 1.0)),
+        (Vec3::Y, Vec4::new(0.0, 1.0, 0.0, 1.0)),
+        (Vec3::Z, Vec4::new(0.0, 0.0, 1.0, 1.0)),
+    ] {
+        let direction = transform.transform_vector3(axis).normalize_or_zero();
+        arrow(origin, origin + direction * size, color);
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum DebugDrawerCreationError {
+    #[error("Creation of debug draw shader failed with error: {0}.")]
+    ShaderCreationFailed(#[from] ShaderBuildError),
+
+    #[error("Creation of debug draw material failed with error: {0}.")]
+    MaterialCreationFailed(#[from] MaterialBuildError),
+}
+
+/// Draws the primitives added through this module's functions, owned by the application.
+pub(crate) struct DebugDrawer {
+    shader_ref: ThreadSafeRef<Shader>,
+    material_ref: ThreadSafeRef<Material<LineVertex>>,
+    /// One per frame in flight, see [`Renderer::current_frame_index`].
+    vertex_buffers: Vec<Option<AllocatedBuffer>>,
+}
+
+#[profiling::all_functions]
+impl DebugDrawer {
+    pub(crate) fn new(renderer: &mut Renderer) -> Result<Self, DebugDrawerCreationError> {
+        let shader_ref = Shader::from_spirv_u8(
+            include_bytes!("shaders/gen/debug_draw.vert"),
+            include_bytes!("shaders/gen/debug_draw.frag"),
+            &renderer.device,
+        )?;
+        let material_ref = MaterialBuilder::new()
+            .topology(PrimitiveTopology::LINE_LIST)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .z_write(false)
+            .build(&shader_ref, DescriptorResources::empty(), renderer)?;
+
+        Ok(Self {
+            shader_ref,
+            material_ref,
+            vertex_buffers: (0..renderer.frames_in_flight()).map(|_| None).collect(),
+        })
+    }
+
+    /// Records the frame's primitives in the primary render pass, and clears them.
+    pub(crate) fn draw(&mut self, camera: Option<&Camera>, renderer: &mut Renderer) {
+        let vertices = std::mem::take(&mut *pending_lines());
+        let Some(camera) = camera else {
+            return;
+        };
+        if vertices.is_empty() {
+            return;
+        }
+
+        let data_size = std::mem::size_of_val(vertices.as_slice());
+        let frame_index = renderer.current_frame_index();
+        if let Err(error) = self.reserve(frame_index, data_size.try_into().unwrap(), renderer) {
+            log::warn!("Failed to grow the debug draw vertex buffer: {error}");
+            return;
+        }
+        let vertex_buffer = self.vertex_buffers[frame_index].as_ref().unwrap();
+        let Some(mapped_ptr) = vertex_buffer
+            .allocation
+            .as_ref()
+            .and_then(|allocation| allocation.mapped_ptr())
+        else {
+            log::warn!("Failed to map the debug draw vertex buffer");
+            return;
+        };
+        // Vertices can't be cast to bytes because of their padding, see `upload_vertex_buffer`
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                vertices.as_ptr(),
+                mapped_ptr.cast::<LineVertex>().as_ptr(),
+                vertices.len(),
+            )
+        };
+
+        let material = self.material_ref.lock();
+        let (viewport, scissor) =
+            Viewport::default().to_vk(renderer.render_region(), renderer.is_viewport_flipped());
+        let camera_data = CameraData {
+            view_projection: *camera.view_projection(),
+            world_position: (*camera.position(), 1.0).into(),
+        };
+        let device = &renderer.device;
+        let cmd_buffer = renderer.primary_command_buffer;
+        unsafe {
+            device.cmd_bind_pipeline(
+                cmd_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                material.pipeline,
+            );
+            device.cmd_set_viewport(cmd_buffer, 0, std::slice::from_ref(&viewport));
+            device.cmd_set_scissor(cmd_buffer, 0, std::slice::from_ref(&scissor));
+            device.cmd_push_constants(
+                cmd_buffer,
+                material.layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                bytes_of(&camera_data),
+            );
+            device.cmd_bind_vertex_buffers(
+                cmd_buffer,
+                0,
+                std::slice::from_ref(&vertex_buffer.handle),
+                &[0],
+            );
+            device.cmd_draw(cmd_buffer, vertices.len().try_into().unwrap(), 1, 0, 0);
+        }
+    }
+
+    /// Makes sure the vertex buffer of the frame holds at least `size` bytes. The frame that last
+    /// used that buffer is done executing when the current frame is recorded, so it can be
+    /// replaced right away.
+    fn reserve(
+        &mut self,
+        frame_index: usize,
+        size: u64,
+        renderer: &mut Renderer,
+    ) -> Result<(), BufferResizeError> {
+        match &mut self.vertex_buffers[frame_index] {
+            Some(vertex_buffer) if vertex_buffer.size() >= size => (),
+            Some(vertex_buffer) => {
+                vertex_buffer.resize(size.next_power_of_two(), false, renderer)?
+            }
+            vertex_buffer_slot @ None => {
+                *vertex_buffer_slot = Some(
+                    AllocatedBuffer::builder(size.next_power_of_two())
+                        .with_usage(vk::BufferUsageFlags::VERTEX_BUFFER)
+                        .with_name("Debug draw vertices")
+                        .build(renderer)?,
+                )
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn destroy(&mut self, renderer: &mut Renderer) {
+        for mut vertex_buffer in self.vertex_buffers.drain(..).flatten() {
+            vertex_buffer.destroy(&renderer.device, &mut renderer.allocator());
+        }
+        self.material_ref.lock().destroy(renderer);
+        self.shader_ref.lock().destroy(&renderer.device);
+    }
+}
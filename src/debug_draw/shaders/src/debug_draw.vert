@@ -0,0 +1,18 @@
+#version 450
+
+layout(location = 0) in vec3 v_Position;
+layout(location = 1) in vec4 v_Color;
+
+layout(push_constant) uniform CameraData {
+    mat4 viewProjection;
+    vec4 worldPos;
+}
+pc_CameraData;
+
+layout(location = 0) out vec4 fs_Color;
+
+void main() {
+    fs_Color = v_Color;
+
+    gl_Position = pc_CameraData.viewProjection * vec4(v_Position, 1);
+}
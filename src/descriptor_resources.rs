@@ -1,16 +1,21 @@
 use crate::{
     allocated_types::{AllocatedBuffer, AllocatedImage, BufferDataUploadError},
     cubemap::Cubemap,
+    descriptor_set_cache::DescriptorSetKey,
     renderer::Renderer,
+    resource_registry::{self, ResourceKind},
     shader::BindingData,
     texture::Texture,
     utils::{ImmediateCommandError, ThreadSafeRef},
 };
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use ash::{vk, Device};
-use spirv_reflect::types::{ReflectDescriptorBinding, ReflectDescriptorType};
+use ash::{
+    vk::{self, Handle},
+    Device,
+};
+use spirv_reflect::types::ReflectDescriptorType;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -22,6 +27,7 @@ pub(crate) fn binding_type_cast(
 ) -> Result<vk::DescriptorType, UnsupportedDescriptorTypeError> {
     match descriptor_type {
         ReflectDescriptorType::UniformBuffer => Ok(vk::DescriptorType::UNIFORM_BUFFER),
+        ReflectDescriptorType::StorageBuffer => Ok(vk::DescriptorType::STORAGE_BUFFER),
         ReflectDescriptorType::StorageImage => Ok(vk::DescriptorType::STORAGE_IMAGE),
         ReflectDescriptorType::CombinedImageSampler => {
             Ok(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
@@ -42,46 +48,51 @@ pub enum DSLCreationError {
 pub(crate) fn create_dsl(
     device: &Device,
     set_level: u32,
-    stage_bindings: &[(Vec<ReflectDescriptorBinding>, vk::ShaderStageFlags)],
+    stage_bindings: &[(&[BindingData], vk::ShaderStageFlags)],
 ) -> Result<vk::DescriptorSetLayout, DSLCreationError> {
     let mut bindings_infos = vec![];
 
     let mut ubo_map = HashMap::new();
+    let mut ssbo_map = HashMap::new();
     let mut images_map = HashMap::new();
     let mut sampler_map = HashMap::new();
 
     for (bindings, stage) in stage_bindings {
-        for binding_reflection in bindings {
-            if binding_reflection.set != set_level {
+        for binding in *bindings {
+            if binding.set != set_level {
                 continue;
             }
 
-            let binding_type = binding_type_cast(binding_reflection.descriptor_type)?;
+            let mut binding_type = binding_type_cast(binding.descriptor_type)?;
+            if binding.dynamic {
+                binding_type = vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC;
+            }
             let map = match binding_type {
-                vk::DescriptorType::UNIFORM_BUFFER => Ok(&mut ubo_map),
+                vk::DescriptorType::UNIFORM_BUFFER | vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC => {
+                    Ok(&mut ubo_map)
+                }
+                vk::DescriptorType::STORAGE_BUFFER => Ok(&mut ssbo_map),
                 vk::DescriptorType::STORAGE_IMAGE => Ok(&mut images_map),
                 vk::DescriptorType::COMBINED_IMAGE_SAMPLER => Ok(&mut sampler_map),
-                _ => Err(UnsupportedDescriptorTypeError(
-                    binding_reflection.descriptor_type,
-                )),
+                _ => Err(UnsupportedDescriptorTypeError(binding.descriptor_type)),
             }?;
 
-            match map.get(&binding_reflection.binding) {
+            match map.get(&binding.slot) {
                 None => {
                     let set_binding = vk::DescriptorSetLayoutBinding {
-                        binding: binding_reflection.binding,
+                        binding: binding.slot,
                         descriptor_type: binding_type,
-                        descriptor_count: 1,
+                        descriptor_count: std::cmp::max(binding.count, 1),
                         stage_flags: *stage,
                         ..Default::default()
                     };
 
-                    map.insert(binding_reflection.binding, set_binding);
+                    map.insert(binding.slot, set_binding);
                 }
                 Some(&old_binding) => {
                     let mut new_binding = old_binding;
                     new_binding.stage_flags |= *stage;
-                    map.insert(binding_reflection.binding, new_binding);
+                    map.insert(binding.slot, new_binding);
                 }
             }
         }
@@ -90,6 +101,9 @@ pub(crate) fn create_dsl(
     for (_, binding_info) in ubo_map {
         bindings_infos.push(binding_info);
     }
+    for (_, binding_info) in ssbo_map {
+        bindings_infos.push(binding_info);
+    }
     for (_, binding_info) in images_map {
         bindings_infos.push(binding_info);
     }
@@ -110,15 +124,63 @@ pub enum DescriptorSetUpdateError {
     #[error("Required shader resource at binding {set} and location {slot} was not provided.")]
     ResourceNotProvided { set: u32, slot: u32 },
 
+    #[error("Texture array at binding {set} and location {slot} expects {expected} textures, but {provided} were provided.")]
+    ArraySizeMismatch {
+        set: u32,
+        slot: u32,
+        expected: u32,
+        provided: usize,
+    },
+
     #[error("Failed to transition image layout with error: {0}.")]
     ImageLayoutTransitionFailed(#[from] ImmediateCommandError),
 }
 
+#[derive(Error, Debug)]
+pub enum DescriptorSetAllocationError {
+    #[error("Vulkan descriptor pool creation failed with status: {0}.")]
+    VulkanDescriptorPoolCreationFailed(vk::Result),
+
+    #[error("Vulkan descriptor set allocation failed with status: {0}.")]
+    VulkanDescriptorSetAllocationFailed(vk::Result),
+
+    #[error("Descriptor set update failed with status: {0}.")]
+    DescriptorSetUpdateFailed(#[from] DescriptorSetUpdateError),
+}
+
+/// Descriptor set holding the resources of a [`crate::material::Material`], shared by every mesh
+/// rendering using it.
+pub const MATERIAL_SET: u32 = 2;
+/// Descriptor set holding the resources of a single
+/// [`crate::components::mesh_rendering::MeshRendering`] (per-object resources).
+pub const OBJECT_SET: u32 = 3;
+/// Uniform binding of [`OBJECT_SET`] the render systems upload the model matrix to. It is always a
+/// dynamic uniform (see [`crate::shader::Shader::set_dynamic_uniforms`]), so that each frame in
+/// flight reads its own copy.
+pub const MODEL_MATRIX_BINDING: u32 = 0;
+
+/// Resources bound to the descriptors of a shader, indexed by binding slot.
+///
+/// Which descriptor set resources end up in only depends on their owner: the ones given to a
+/// material fill the shader's bindings declared in [`MATERIAL_SET`] (`layout(set = 2, ...)`), and
+/// the ones given to a mesh rendering fill the ones declared in [`OBJECT_SET`]
+/// (`layout(set = 3, ...)`). Resources that should differ between objects drawn with the same
+/// material (e.g. a per-entity texture) must therefore be declared in set 3 by the shader and
+/// given to the mesh rendering (see
+/// [`crate::components::mesh_rendering::default_descriptor_resources_with_textures`]). Sets 0 and
+/// 1 are owned by the renderer.
+///
+/// Every binding the shader declares in the target set must be provided, and resources at slots
+/// the shader doesn't declare in that set are ignored (with a warning).
 #[derive(Debug, Default)]
 pub struct DescriptorResources {
     pub uniform_buffers: HashMap<u32, ThreadSafeRef<AllocatedBuffer>>,
+    pub storage_buffers: HashMap<u32, ThreadSafeRef<AllocatedBuffer>>,
     pub storage_images: HashMap<u32, ThreadSafeRef<AllocatedImage>>,
     pub sampled_images: HashMap<u32, ThreadSafeRef<Texture>>,
+    /// Textures bound to array bindings (e.g. `uniform sampler2D u_Textures[16]`). The length of
+    /// each array must match the size declared in the shader.
+    pub sampled_image_arrays: HashMap<u32, Vec<ThreadSafeRef<Texture>>>,
     pub cubemap_images: HashMap<u32, ThreadSafeRef<Cubemap>>,
 }
 
@@ -130,6 +192,167 @@ impl DescriptorResources {
         Self::default()
     }
 
+    /// Total number of combined image sampler descriptors needed, array elements included.
+    pub(crate) fn sampled_image_descriptor_count(&self) -> usize {
+        self.sampled_images.len()
+            + self.cubemap_images.len()
+            + self
+                .sampled_image_arrays
+                .values()
+                .map(|textures| textures.len())
+                .sum::<usize>()
+    }
+
+    /// Creates a pool holding a single descriptor set of layout `dsl`, and fills the set with the
+    /// resources matching the `set_level` bindings of `bindings`.
+    pub(crate) fn allocate_descriptor_set(
+        &self,
+        dsl: vk::DescriptorSetLayout,
+        bindings: &[BindingData],
+        set_level: u32,
+        renderer: &mut Renderer,
+    ) -> Result<(vk::DescriptorPool, vk::DescriptorSet), DescriptorSetAllocationError> {
+        let ubo_count: u32 = self.uniform_buffers.len().try_into().unwrap();
+        let ssbo_count: u32 = self.storage_buffers.len().try_into().unwrap();
+        let storage_image_count: u32 = self.storage_images.len().try_into().unwrap();
+        let sampled_image_count: u32 = self.sampled_image_descriptor_count().try_into().unwrap();
+        // Dynamic uniforms aren't part of the resources, they all use the renderer's buffer
+        let dynamic_ubo_count: u32 = bindings
+            .iter()
+            .filter(|binding| binding.set == set_level && binding.dynamic)
+            .count()
+            .try_into()
+            .unwrap();
+
+        let pool_sizes = [
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::UNIFORM_BUFFER,
+                descriptor_count: std::cmp::max(ubo_count, 1),
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+                descriptor_count: std::cmp::max(dynamic_ubo_count, 1),
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::STORAGE_BUFFER,
+                descriptor_count: std::cmp::max(ssbo_count, 1),
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::STORAGE_IMAGE,
+                descriptor_count: std::cmp::max(storage_image_count, 1),
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: std::cmp::max(sampled_image_count, 1),
+            },
+        ];
+        let pool_info = vk::DescriptorPoolCreateInfo::default()
+            .max_sets(1)
+            .pool_sizes(&pool_sizes);
+        let descriptor_pool =
+            unsafe { renderer.device.create_descriptor_pool(&pool_info, None) }
+                .map_err(DescriptorSetAllocationError::VulkanDescriptorPoolCreationFailed)?;
+
+        let descriptor_set_alloc_info = vk::DescriptorSetAllocateInfo::default()
+            .descriptor_pool(descriptor_pool)
+            .set_layouts(std::slice::from_ref(&dsl));
+        let descriptor_set = match unsafe {
+            renderer
+                .device
+                .allocate_descriptor_sets(&descriptor_set_alloc_info)
+        } {
+            Ok(descriptor_sets) => descriptor_sets[0],
+            Err(result) => {
+                unsafe {
+                    renderer
+                        .device
+                        .destroy_descriptor_pool(descriptor_pool, None)
+                };
+                return Err(
+                    DescriptorSetAllocationError::VulkanDescriptorSetAllocationFailed(result),
+                );
+            }
+        };
+
+        if let Err(error) = self.update_descriptors_set_from_bindings(
+            bindings,
+            &descriptor_set,
+            Some(&[set_level]),
+            renderer,
+        ) {
+            unsafe {
+                renderer
+                    .device
+                    .destroy_descriptor_pool(descriptor_pool, None)
+            };
+            return Err(error.into());
+        }
+        resource_registry::register(ResourceKind::DescriptorSet, 1);
+
+        Ok((descriptor_pool, descriptor_set))
+    }
+
+    /// Key identifying a set of layout `dsl` holding these resources, used to share identical
+    /// descriptor sets. Uniforms at the dynamic `set_level` slots of `bindings` (such as the model
+    /// matrix) are left out: they read the renderer's dynamic uniform buffer at an offset given
+    /// by each draw, so sharers never read each other's data.
+    pub(crate) fn descriptor_set_key(
+        &self,
+        dsl: vk::DescriptorSetLayout,
+        bindings: &[BindingData],
+        set_level: u32,
+    ) -> DescriptorSetKey {
+        let is_dynamic = |slot: u32| {
+            bindings
+                .iter()
+                .any(|binding| binding.set == set_level && binding.slot == slot && binding.dynamic)
+        };
+        let buffers = self
+            .uniform_buffers
+            .iter()
+            .filter(|(slot, _)| !is_dynamic(**slot))
+            .chain(&self.storage_buffers)
+            .map(|(slot, buffer_ref)| (*slot, vec![buffer_ref.lock().handle.as_raw()]));
+        let storage_images = self
+            .storage_images
+            .iter()
+            .map(|(slot, image_ref)| (*slot, vec![image_ref.lock().view.as_raw()]));
+        let sampled_images = self.sampled_images.iter().map(|(slot, texture_ref)| {
+            let texture = texture_ref.lock();
+            let view = texture.image_ref.lock().view;
+            (*slot, vec![view.as_raw(), texture.sampler.as_raw()])
+        });
+        let sampled_image_arrays = self.sampled_image_arrays.iter().map(|(slot, textures)| {
+            let handles = textures
+                .iter()
+                .flat_map(|texture_ref| {
+                    let texture = texture_ref.lock();
+                    let view = texture.image_ref.lock().view;
+                    [view.as_raw(), texture.sampler.as_raw()]
+                })
+                .collect();
+            (*slot, handles)
+        });
+        let cubemap_images = self.cubemap_images.iter().map(|(slot, cubemap_ref)| {
+            let cubemap = cubemap_ref.lock();
+            let view = cubemap.image_ref.lock().view;
+            (*slot, vec![view.as_raw(), cubemap.sampler.as_raw()])
+        });
+
+        let mut resources = buffers
+            .chain(storage_images)
+            .chain(sampled_images)
+            .chain(sampled_image_arrays)
+            .chain(cubemap_images)
+            .collect::<Vec<_>>();
+        resources.sort_by_key(|(slot, _)| *slot);
+
+        DescriptorSetKey {
+            layout: dsl,
+            resources,
+        }
+    }
+
     pub(crate) fn update_descriptors_set_from_bindings(
         &self,
         bindings: &[BindingData],
@@ -145,6 +368,21 @@ impl DescriptorResources {
             }
 
             match binding_type_cast(binding.descriptor_type)? {
+                vk::DescriptorType::UNIFORM_BUFFER if binding.dynamic => {
+                    // Each draw reads `size` bytes at the dynamic offset it is bound with
+                    let descriptor_buffer_info = vk::DescriptorBufferInfo::default()
+                        .buffer(renderer.dynamic_uniform_buffer())
+                        .offset(0)
+                        .range(binding.size.into());
+
+                    let set_write = vk::WriteDescriptorSet::default()
+                        .dst_set(*descriptor_set)
+                        .dst_binding(binding.slot)
+                        .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+                        .buffer_info(std::slice::from_ref(&descriptor_buffer_info));
+
+                    unsafe { renderer.device.update_descriptor_sets(&[set_write], &[]) };
+                }
                 vk::DescriptorType::UNIFORM_BUFFER => {
                     let buffer_ref = self.uniform_buffers.get(&binding.slot).ok_or(
                         DescriptorSetUpdateError::ResourceNotProvided {
@@ -167,6 +405,28 @@ impl DescriptorResources {
 
                     unsafe { renderer.device.update_descriptor_sets(&[set_write], &[]) };
                 }
+                vk::DescriptorType::STORAGE_BUFFER => {
+                    let buffer_ref = self.storage_buffers.get(&binding.slot).ok_or(
+                        DescriptorSetUpdateError::ResourceNotProvided {
+                            set: binding.set,
+                            slot: binding.slot,
+                        },
+                    )?;
+                    let buffer = buffer_ref.lock();
+
+                    let descriptor_buffer_info = vk::DescriptorBufferInfo::default()
+                        .buffer(buffer.handle)
+                        .offset(0)
+                        .range(buffer.size());
+
+                    let set_write = vk::WriteDescriptorSet::default()
+                        .dst_set(*descriptor_set)
+                        .dst_binding(binding.slot)
+                        .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                        .buffer_info(std::slice::from_ref(&descriptor_buffer_info));
+
+                    unsafe { renderer.device.update_descriptor_sets(&[set_write], &[]) };
+                }
                 vk::DescriptorType::STORAGE_IMAGE => {
                     let image_ref = self.storage_images.get(&binding.slot).ok_or(
                         DescriptorSetUpdateError::ResourceNotProvided {
@@ -202,6 +462,62 @@ impl DescriptorResources {
                         renderer,
                     )?;
                 }
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER if binding.count > 1 => {
+                    let textures = self.sampled_image_arrays.get(&binding.slot).ok_or(
+                        DescriptorSetUpdateError::ResourceNotProvided {
+                            set: binding.set,
+                            slot: binding.slot,
+                        },
+                    )?;
+                    if textures.len() != binding.count as usize {
+                        return Err(DescriptorSetUpdateError::ArraySizeMismatch {
+                            set: binding.set,
+                            slot: binding.slot,
+                            expected: binding.count,
+                            provided: textures.len(),
+                        });
+                    }
+
+                    let mut descriptor_image_infos = Vec::with_capacity(textures.len());
+                    for texture_ref in textures {
+                        let texture = texture_ref.lock();
+                        let image = texture.image_ref.lock();
+
+                        self.update_layout(
+                            image.handle,
+                            image.layout,
+                            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                            renderer,
+                        )?;
+
+                        descriptor_image_infos.push(
+                            vk::DescriptorImageInfo::default()
+                                .sampler(texture.sampler)
+                                .image_view(image.view)
+                                .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL),
+                        );
+                    }
+
+                    let set_write = vk::WriteDescriptorSet::default()
+                        .dst_set(*descriptor_set)
+                        .dst_binding(binding.slot)
+                        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                        .image_info(&descriptor_image_infos);
+
+                    unsafe { renderer.device.update_descriptor_sets(&[set_write], &[]) };
+
+                    for texture_ref in textures {
+                        let texture = texture_ref.lock();
+                        let image = texture.image_ref.lock();
+
+                        self.update_layout(
+                            image.handle,
+                            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                            image.layout,
+                            renderer,
+                        )?;
+                    }
+                }
                 vk::DescriptorType::COMBINED_IMAGE_SAMPLER => {
                     let (image, sampler) = match binding.dim {
                         spirv_reflect::types::ReflectDimension::Type2d => {
@@ -260,9 +576,41 @@ impl DescriptorResources {
             };
         }
 
+        if let Some(set_constraints) = set_constraints {
+            self.warn_unused_resources(bindings, set_constraints);
+        }
+
         Ok(())
     }
 
+    /// Resources not matching any binding are most likely meant for the other set (e.g. a texture
+    /// given to a mesh rendering while the shader declares it in the material's set).
+    fn warn_unused_resources(&self, bindings: &[BindingData], sets: &[u32]) {
+        let declared_slots = bindings
+            .iter()
+            .filter(|binding| sets.contains(&binding.set) && !binding.dynamic)
+            .map(|binding| binding.slot)
+            .collect::<HashSet<_>>();
+
+        let provided_slots = self
+            .uniform_buffers
+            .keys()
+            // The model matrix is provided by convention, even to shaders that don't use it
+            .filter(|slot| !(sets == [OBJECT_SET] && **slot == MODEL_MATRIX_BINDING))
+            .chain(self.storage_buffers.keys())
+            .chain(self.storage_images.keys())
+            .chain(self.sampled_images.keys())
+            .chain(self.sampled_image_arrays.keys())
+            .chain(self.cubemap_images.keys());
+        for slot in provided_slots {
+            if !declared_slots.contains(slot) {
+                log::warn!(
+                    "A resource was provided at slot {slot}, but the shader doesn't declare it in sets {sets:?}, so it is ignored"
+                );
+            }
+        }
+    }
+
     fn update_layout(
         &self,
         image: vk::Image,
@@ -301,62 +649,69 @@ impl DescriptorResources {
             Ok(())
         }
     }
-    pub(crate) fn prepare_image_layouts_for_render(
+
+    /// Checks that `texture_refs` fill the array of sampled images declared at `slot` of `set` in
+    /// `bindings`, and transitions them to the `SHADER_READ_ONLY_OPTIMAL` layout their descriptors
+    /// are written with, as when the descriptor set is first written.
+    pub(crate) fn prepare_texture_array(
         &self,
+        bindings: &[BindingData],
+        set: u32,
+        slot: u32,
+        texture_refs: &[ThreadSafeRef<Texture>],
         renderer: &mut Renderer,
-    ) -> Result<(), ImmediateCommandError> {
-        for image in self.storage_images.values() {
-            let image = image.lock();
-
-            self.update_layout(
-                image.handle,
-                image.layout,
-                vk::ImageLayout::GENERAL,
-                renderer,
-            )?;
+    ) -> Result<(), ResourceBindingError> {
+        let binding = bindings
+            .iter()
+            .find(|binding| binding.set == set && binding.slot == slot)
+            .ok_or(ResourceBindingError::InvalidBindingSlot { slot, set })?;
+        if texture_refs.len() != binding.count as usize {
+            return Err(ResourceBindingError::ArraySizeMismatch {
+                set,
+                slot,
+                expected: binding.count,
+                provided: texture_refs.len(),
+            });
         }
-        for texture in self.sampled_images.values() {
-            let texture = texture.lock();
-            let image = texture.image_ref.lock();
 
+        for texture_ref in texture_refs {
+            let texture = texture_ref.lock();
+            let mut image = texture.image_ref.lock();
             self.update_layout(
                 image.handle,
                 image.layout,
                 vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
                 renderer,
             )?;
+            image.layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
         }
 
         Ok(())
     }
 
-    pub(crate) fn restore_image_layouts(
-        &self,
-        renderer: &mut Renderer,
-    ) -> Result<(), ImmediateCommandError> {
+    /// Requests every image of these resources to be in the layout its descriptor expects during
+    /// the current frame (see [`Renderer::request_image_layout`]): `GENERAL` for storage images, and
+    /// `SHADER_READ_ONLY_OPTIMAL` for sampled textures and cubemaps.
+    pub(crate) fn request_layouts_for_render(&self, renderer: &mut Renderer) {
         for image in self.storage_images.values() {
-            let image = image.lock();
-
-            self.update_layout(
-                image.handle,
-                vk::ImageLayout::GENERAL,
-                image.layout,
-                renderer,
-            )?;
+            renderer.request_image_layout(&mut image.lock(), vk::ImageLayout::GENERAL);
         }
-        for texture in self.sampled_images.values() {
-            let texture = texture.lock();
-            let image = texture.image_ref.lock();
-
-            self.update_layout(
-                image.handle,
+        for texture in self
+            .sampled_images
+            .values()
+            .chain(self.sampled_image_arrays.values().flatten())
+        {
+            renderer.request_image_layout(
+                &mut texture.lock().image_ref.lock(),
                 vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-                image.layout,
-                renderer,
-            )?;
+            );
+        }
+        for cubemap in self.cubemap_images.values() {
+            renderer.request_image_layout(
+                &mut cubemap.lock().image_ref.lock(),
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            );
         }
-
-        Ok(())
     }
 }
 
@@ -364,6 +719,20 @@ impl DescriptorResources {
 pub enum ResourceBindingError {
     #[error("The binding of slot {slot} does not exist in descriptor set {set}. Please make sure all slots were filled when initializing descriptor resources.")]
     InvalidBindingSlot { slot: u32, set: u32 },
+
+    #[error("Allocation of a descriptor set no longer shared with other mesh renderings failed with error: {0}.")]
+    DescriptorSetDetachFailed(#[from] DescriptorSetAllocationError),
+
+    #[error("Texture array at binding {set} and location {slot} expects {expected} textures, but {provided} were provided.")]
+    ArraySizeMismatch {
+        set: u32,
+        slot: u32,
+        expected: u32,
+        provided: usize,
+    },
+
+    #[error("Failed to transition image layout with error: {0}.")]
+    ImageLayoutTransitionFailed(#[from] ImmediateCommandError),
 }
 
 #[derive(Error, Debug)]
@@ -373,4 +742,21 @@ pub enum UniformUpdateError {
 
     #[error("Update of the uniform failed with this error: {0}.")]
     UniformUploadFailed(#[from] BufferDataUploadError),
+
+    #[error("The data's size ({data_size}) does not match the size of the dynamic uniform at slot {slot} ({uniform_size}).")]
+    DynamicUniformSizeMismatch {
+        slot: u32,
+        data_size: usize,
+        uniform_size: usize,
+    },
+
+    #[error("The shader does not declare a member \"{member}\" in uniform \"{uniform}\".")]
+    UnknownMember { uniform: String, member: String },
+
+    #[error("The data's size ({data_size}) does not match the size of member \"{member}\" ({member_size}).")]
+    MemberSizeMismatch {
+        member: String,
+        data_size: usize,
+        member_size: u32,
+    },
 }
@@ -0,0 +1,88 @@
+use ash::vk;
+
+use crate::resource_registry::{self, ResourceKind};
+
+use std::collections::HashMap;
+
+/// Identifies the content of a descriptor set: its layout, and the handles of the resources bound
+/// to each of its slots (buffers, image views and samplers).
+///
+/// Only handles are compared, so two sets binding the same buffer are considered identical
+/// regardless of the data the buffer holds.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct DescriptorSetKey {
+    pub(crate) layout: vk::DescriptorSetLayout,
+    /// Raw resource handles per binding slot, sorted by slot.
+    pub(crate) resources: Vec<(u32, Vec<u64>)>,
+}
+
+#[derive(Debug)]
+struct CachedDescriptorSet {
+    pool: vk::DescriptorPool,
+    set: vk::DescriptorSet,
+    user_count: usize,
+}
+
+/// Reference counted descriptor sets shared by mesh renderings with identical set 3 resources (see
+/// [`crate::components::mesh_rendering::MeshRendering::new_cached`]).
+#[derive(Debug, Default)]
+pub(crate) struct DescriptorSetCache {
+    entries: HashMap<DescriptorSetKey, CachedDescriptorSet>,
+}
+
+impl DescriptorSetCache {
+    /// Returns the set matching `key` if there is one, and registers a new user for it.
+    pub(crate) fn acquire(
+        &mut self,
+        key: &DescriptorSetKey,
+    ) -> Option<(vk::DescriptorPool, vk::DescriptorSet)> {
+        self.entries.get_mut(key).map(|entry| {
+            entry.user_count += 1;
+            (entry.pool, entry.set)
+        })
+    }
+
+    /// Adds a newly allocated set to the cache, with a single user. The cache takes ownership of
+    /// the pool.
+    pub(crate) fn insert(
+        &mut self,
+        key: DescriptorSetKey,
+        pool: vk::DescriptorPool,
+        set: vk::DescriptorSet,
+    ) {
+        self.entries.insert(
+            key,
+            CachedDescriptorSet {
+                pool,
+                set,
+                user_count: 1,
+            },
+        );
+    }
+
+    /// Unregisters a user of the set matching `key`, destroying it once it has no user left.
+    pub(crate) fn release(&mut self, key: &DescriptorSetKey, device: &ash::Device) {
+        let Some(entry) = self.entries.get_mut(key) else {
+            return;
+        };
+
+        entry.user_count -= 1;
+        if entry.user_count == 0 {
+            if let Some(entry) = self.entries.remove(key) {
+                unsafe { device.destroy_descriptor_pool(entry.pool, None) };
+                resource_registry::unregister(ResourceKind::DescriptorSet, 1);
+            }
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(crate) fn destroy(&mut self, device: &ash::Device) {
+        for (_, entry) in self.entries.drain() {
+            unsafe { device.destroy_descriptor_pool(entry.pool, None) };
+            resource_registry::unregister(ResourceKind::DescriptorSet, 1);
+        }
+    }
+}
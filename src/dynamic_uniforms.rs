@@ -0,0 +1,121 @@
+use ash::vk;
+use gpu_allocator::vulkan::Allocator;
+use thiserror::Error;
+
+use crate::allocated_types::{AllocatedBuffer, AllocatedBufferBuilder, BufferBuildError};
+
+/// Per frame capacity of the dynamic uniform buffer if none is requested with
+/// [`crate::renderer::RendererBuilder::with_dynamic_uniform_capacity`], enough for 16384 model
+/// matrices per frame.
+pub const DEFAULT_DYNAMIC_UNIFORM_CAPACITY: u64 = 1024 * 1024;
+
+#[derive(Error, Debug)]
+pub enum DynamicUniformError {
+    #[error("The dynamic uniform buffer is full ({capacity} bytes), its capacity can be increased with RendererBuilder::with_dynamic_uniform_capacity.")]
+    BufferFull { capacity: u64 },
+
+    #[error("Failed to map the memory of the dynamic uniform buffer.")]
+    MemoryMappingFailed,
+}
+
+/// Ring buffer holding the dynamic uniforms of the frames in flight (see
+/// [`crate::shader::Shader::set_dynamic_uniforms`]).
+///
+/// The buffer is split in one segment per frame in flight (see
+/// [`crate::renderer::RendererBuilder::with_frames_in_flight`]). Every object's data is written
+/// at the next offset of the current frame's segment aligned to the device's
+/// `min_uniform_buffer_offset_alignment`, and the offset is given to `cmd_bind_descriptor_sets`
+/// when the object is drawn. A segment is free again once the frame that last used it is done,
+/// so it is simply rewound when its frame starts. The capacity is fixed: a frame writing more
+/// than its segment holds fails to draw the extra objects.
+pub(crate) struct DynamicUniformBuffer {
+    buffer: AllocatedBuffer,
+    alignment: u64,
+    segment_size: u64,
+    /// Offset of the current frame's segment in the buffer.
+    segment_start: u64,
+    /// Offset of the first free byte in the buffer.
+    cursor: u64,
+}
+
+#[profiling::all_functions]
+impl DynamicUniformBuffer {
+    /// `capacity` is the size of each frame's segment.
+    pub(crate) fn new(
+        capacity: u64,
+        frame_count: usize,
+        device_properties: &vk::PhysicalDeviceProperties,
+        device: &ash::Device,
+        allocator: &mut Allocator,
+    ) -> Result<Self, BufferBuildError> {
+        let alignment = device_properties
+            .limits
+            .min_uniform_buffer_offset_alignment
+            .max(1);
+        // Segments start on aligned offsets, as they start with an object's data
+        let segment_size = capacity.next_multiple_of(alignment);
+        let buffer = AllocatedBufferBuilder::uniform_buffer_default(
+            segment_size * frame_count.max(1) as u64,
+        )
+        .with_name("Dynamic uniforms")
+        .build_internal(device, allocator)?;
+
+        Ok(Self {
+            buffer,
+            alignment,
+            segment_size,
+            segment_start: 0,
+            cursor: 0,
+        })
+    }
+
+    #[profiling::skip]
+    pub(crate) fn handle(&self) -> vk::Buffer {
+        self.buffer.handle
+    }
+
+    /// Size of a frame's segment.
+    #[profiling::skip]
+    pub(crate) fn capacity(&self) -> u64 {
+        self.segment_size
+    }
+
+    /// Bytes written during the current frame, alignment padding included.
+    #[profiling::skip]
+    pub(crate) fn used(&self) -> u64 {
+        self.cursor - self.segment_start
+    }
+
+    /// Makes the segment of `frame_index` available again, the frame that last used it must be
+    /// done executing.
+    pub(crate) fn reset(&mut self, frame_index: usize) {
+        self.segment_start = self.segment_size * frame_index as u64;
+        self.cursor = self.segment_start;
+    }
+
+    /// Copies `data` to the next aligned offset of the buffer, and returns that offset.
+    pub(crate) fn push(&mut self, data: &[u8]) -> Result<u32, DynamicUniformError> {
+        let offset = self.cursor.next_multiple_of(self.alignment);
+        let end = offset + data.len() as u64;
+        let capacity = self.capacity();
+        if end > self.segment_start + capacity {
+            return Err(DynamicUniformError::BufferFull { capacity });
+        }
+
+        let mapped_slice = self
+            .buffer
+            .allocation
+            .as_mut()
+            .and_then(|allocation| allocation.mapped_slice_mut())
+            .ok_or(DynamicUniformError::MemoryMappingFailed)?;
+        mapped_slice[offset as usize..end as usize].copy_from_slice(data);
+        self.cursor = end;
+
+        // The capacity is a u64, but dynamic offsets are u32
+        u32::try_from(offset).map_err(|_| DynamicUniformError::BufferFull { capacity })
+    }
+
+    pub(crate) fn destroy(&mut self, device: &ash::Device, allocator: &mut Allocator) {
+        self.buffer.destroy(device, allocator);
+    }
+}
@@ -3,7 +3,8 @@ use std::time::Instant;
 use bevy_ecs::{prelude::World, schedule::Schedule};
 
 use crate::{
-    components::{camera::Camera, resource_wrapper::ResourceWrapper},
+    components::{camera::Camera, resource_wrapper::ResourceWrapper, viewport::CameraViews},
+    frame_stats::FrameStats,
     renderer::Renderer,
     utils::ThreadSafeRef,
 };
@@ -28,6 +29,7 @@ impl ECSManager {
 
         world.insert_resource(camera);
         world.insert_resource(ResourceWrapper::new(Instant::now()));
+        world.insert_resource(FrameStats::default());
         world.insert_resource(renderer_ref);
 
         #[cfg(feature = "egui")]
@@ -50,6 +52,16 @@ impl ECSManager {
         }
     }
 
+    /// Shared reference to the renderer, which can be moved to other threads (e.g. to upload the
+    /// resources of an [`crate::async_load::AsyncLoad`]).
+    pub fn renderer_ref(&self) -> ThreadSafeRef<Renderer> {
+        ThreadSafeRef::clone(
+            self.world
+                .get_resource::<ThreadSafeRef<Renderer>>()
+                .expect("No renderer bound to world"),
+        )
+    }
+
     pub(crate) fn on_resize(&mut self, width: u32, height: u32) {
         let mut camera = self
             .world
@@ -57,6 +69,13 @@ impl ECSManager {
             .expect("No camera bound to world");
         camera.on_resize(width, height);
 
+        if let Some(mut camera_views) = self.world.get_resource_mut::<CameraViews>() {
+            for (camera, viewport) in camera_views.views.iter_mut() {
+                let (viewport_width, viewport_height) = viewport.pixel_size(width, height);
+                camera.on_resize(viewport_width, viewport_height);
+            }
+        }
+
         if let Some(callback) = self.resize_callback.as_ref() {
             callback(width, height);
         }
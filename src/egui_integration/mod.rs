@@ -1,25 +1,64 @@
 mod painter;
-pub use painter::Painter;
+mod render_target;
+mod viewport;
+pub use painter::{EguiCallbackFn, EguiCallbackInfo, Painter};
+pub use render_target::{EguiRenderTarget, EguiRenderTargetCreationError};
 
-use crate::renderer::Renderer;
+use crate::{components::viewport::Viewport, renderer::Renderer, utils::ThreadSafeRef};
 
-use self::painter::PainterCreationError;
+use ash::vk;
+
+use self::{
+    painter::{PaintTarget, PainterCreationError},
+    viewport::ViewportWindow,
+};
+
+/// Controls where and how the egui pass is rendered. By default, egui is painted over the scene,
+/// covering the whole swapchain.
+#[derive(Default)]
+pub struct EguiPassOptions {
+    /// Color the UI area is cleared to before painting. When rendering to the swapchain, `None`
+    /// keeps the scene visible under the UI, while render targets are always cleared (to
+    /// transparent black if `None`).
+    pub clear_color: Option<[f32; 4]>,
+    /// Area of the swapchain (or of the render target) the UI is laid out and painted in. Pointer
+    /// positions are offset accordingly when painting to the swapchain. When painting to the
+    /// swapchain, this is a fraction of the renderer's render region (see
+    /// [`Renderer::set_render_region`]).
+    pub viewport: Viewport,
+    /// Renders the UI into this target, in its own render pass, instead of over the scene.
+    pub render_target: Option<ThreadSafeRef<EguiRenderTarget>>,
+}
 
 pub struct EguiIntegration {
     pub egui_platform_state: egui_winit::State,
     pub painter: Painter,
+    pub pass_options: EguiPassOptions,
 
     shapes: Vec<egui::epaint::ClippedShape>,
     textures_delta: egui::TexturesDelta,
+    /// Viewports output by the last run of the root viewport, see
+    /// [`EguiIntegration::update_viewports`].
+    viewport_output: egui::ViewportIdMap<egui::ViewportOutput>,
+    /// Native windows of the deferred viewports.
+    viewports: egui::ViewportIdMap<ViewportWindow>,
+    /// Render region of the renderer at the last paint, as the UI is laid out before painting,
+    /// without access to the renderer. `None` until the first paint, where the whole window is
+    /// used.
+    last_render_region: Option<vk::Rect2D>,
 }
 
 impl EguiIntegration {
+    /// Deferred viewports (see [`egui::Context::show_viewport_deferred`]) are shown in their own
+    /// native windows, see [`EguiIntegration::update_viewports`]. Immediate viewports are embedded
+    /// in their parent instead, as the integration doesn't render them.
     pub fn new(
         window: &winit::window::Window,
         renderer: &mut Renderer,
     ) -> Result<Self, PainterCreationError> {
         let painter = Painter::new(renderer)?;
         let context = egui::Context::default();
+        context.set_embed_viewports(false);
         let egui_platform_state = egui_winit::State::new(
             context.clone(),
             egui::ViewportId::ROOT,
@@ -32,8 +71,12 @@ impl EguiIntegration {
         Ok(Self {
             egui_platform_state,
             painter,
+            pass_options: Default::default(),
             shapes: vec![],
             textures_delta: Default::default(),
+            viewport_output: Default::default(),
+            viewports: Default::default(),
+            last_render_region: None,
         })
     }
 
@@ -47,12 +90,30 @@ impl EguiIntegration {
             .consumed
     }
 
+    /// Forwards an event of one of the deferred viewports' windows, events of other windows are
+    /// ignored.
+    pub fn handle_viewport_event(
+        &mut self,
+        window_id: winit::window::WindowId,
+        event: &winit::event::WindowEvent,
+    ) {
+        if let Some(viewport) = self
+            .viewports
+            .values_mut()
+            .find(|viewport| viewport.window().id() == window_id)
+        {
+            viewport.handle_event(event);
+        }
+    }
+
     pub fn run(&mut self, window: &winit::window::Window, ui_callback: impl FnMut(&egui::Context)) {
-        let raw_input = self.egui_platform_state.take_egui_input(window);
+        let mut raw_input = self.egui_platform_state.take_egui_input(window);
+        self.apply_pass_area(window, &mut raw_input);
         let egui::FullOutput {
             platform_output,
             textures_delta,
             shapes,
+            viewport_output,
             ..
         } = self
             .egui_platform_state
@@ -63,6 +124,109 @@ impl EguiIntegration {
             .handle_platform_output(window, platform_output);
         self.shapes = shapes;
         self.textures_delta.append(textures_delta);
+        self.viewport_output = viewport_output;
+    }
+
+    /// Opens a window for each deferred viewport shown by the last [`EguiIntegration::run`], and
+    /// closes the windows of the viewports that are not shown anymore. The UI of every viewport is
+    /// then run, to be painted by [`EguiIntegration::paint`] along with the root viewport.
+    ///
+    /// Commands sent to the root viewport (e.g. to resize the main window) are not applied.
+    pub fn update_viewports(
+        &mut self,
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        renderer: &mut Renderer,
+    ) {
+        let egui_ctx = self.egui_platform_state.egui_ctx().clone();
+        let mut viewport_output = std::mem::take(&mut self.viewport_output);
+        viewport_output.remove(&egui::ViewportId::ROOT);
+
+        let closed_viewports = self
+            .viewports
+            .keys()
+            .filter(|viewport_id| !viewport_output.contains_key(viewport_id))
+            .copied()
+            .collect::<Vec<_>>();
+        for viewport_id in closed_viewports {
+            if let Some(viewport) = self.viewports.remove(&viewport_id) {
+                viewport.destroy(renderer);
+            }
+        }
+
+        for (viewport_id, output) in viewport_output {
+            if let Some(viewport) = self.viewports.get_mut(&viewport_id) {
+                viewport.update(output, &egui_ctx);
+                continue;
+            }
+            let Some(ui_callback) = output.viewport_ui_cb else {
+                continue;
+            };
+
+            match ViewportWindow::new(
+                viewport_id,
+                output.builder,
+                ui_callback,
+                event_loop,
+                &egui_ctx,
+                renderer,
+            ) {
+                Ok(mut viewport) => {
+                    viewport.apply_commands(output.commands, &egui_ctx);
+                    self.viewports.insert(viewport_id, viewport);
+                }
+                Err(error) => log::error!("Failed to open egui viewport {viewport_id:?}: {error}"),
+            }
+        }
+
+        // Commands sent while running a viewport are applied once they all ran
+        let mut commands = vec![];
+        for viewport in self.viewports.values_mut() {
+            let full_output = viewport.run(&egui_ctx);
+            self.textures_delta.append(full_output.textures_delta);
+            commands.extend(
+                full_output
+                    .viewport_output
+                    .into_iter()
+                    .map(|(viewport_id, output)| (viewport_id, output.commands)),
+            );
+        }
+        for (viewport_id, viewport_commands) in commands {
+            if let Some(viewport) = self.viewports.get_mut(&viewport_id) {
+                viewport.apply_commands(viewport_commands, &egui_ctx);
+            }
+        }
+    }
+
+    /// Restricts egui's screen to the configured viewport (of the window or of the render target).
+    fn apply_pass_area(&self, window: &winit::window::Window, raw_input: &mut egui::RawInput) {
+        let pixels_per_point = self.egui_platform_state.egui_ctx().pixels_per_point();
+        let region = match &self.pass_options.render_target {
+            Some(render_target_ref) => {
+                let [width, height] = render_target_ref.lock().dimensions();
+                full_rect(width, height)
+            }
+            None => self.last_render_region.unwrap_or_else(|| {
+                let (width, height) = window.inner_size().into();
+                full_rect(width, height)
+            }),
+        };
+        let area = painter::paint_area(&self.pass_options.viewport, region);
+        raw_input.screen_rect = Some(egui::Rect::from_min_size(
+            egui::Pos2::ZERO,
+            area.size() / pixels_per_point,
+        ));
+
+        if self.pass_options.render_target.is_none() {
+            let offset = area.min.to_vec2() / pixels_per_point;
+            for event in &mut raw_input.events {
+                match event {
+                    egui::Event::PointerMoved(pos) | egui::Event::PointerButton { pos, .. } => {
+                        *pos -= offset
+                    }
+                    _ => (),
+                }
+            }
+        }
     }
 
     pub fn paint(&mut self, renderer: &mut Renderer) {
@@ -71,13 +235,74 @@ impl EguiIntegration {
             shapes,
             self.egui_platform_state.egui_ctx().pixels_per_point(),
         );
-        let textures_delta = std::mem::take(&mut self.textures_delta);
+        // Freed textures may still be used by the viewports, which are painted after the root
+        let egui::TexturesDelta { set, free } = std::mem::take(&mut self.textures_delta);
+        let textures_delta = egui::TexturesDelta { set, free: vec![] };
+        let pixels_per_point = self.egui_platform_state.egui_ctx().pixels_per_point();
 
-        self.painter.paint_and_update_textures(
-            self.egui_platform_state.egui_ctx().pixels_per_point(),
-            &clipped_primitives,
-            textures_delta,
-            renderer,
-        );
+        match self.pass_options.render_target.clone() {
+            Some(render_target_ref) => {
+                let render_target = render_target_ref.lock();
+                let [width, height] = render_target.dimensions();
+                let cmd_buffer = render_target
+                    .begin(self.pass_options.clear_color.unwrap_or_default(), renderer);
+                let target = PaintTarget::new(
+                    cmd_buffer,
+                    &self.pass_options.viewport,
+                    full_rect(width, height),
+                );
+
+                self.painter.paint_and_update_textures_into(
+                    pixels_per_point,
+                    &clipped_primitives,
+                    textures_delta,
+                    &target,
+                    renderer,
+                );
+                render_target.end_and_submit(renderer);
+            }
+            None => {
+                let render_region = renderer.render_region();
+                self.last_render_region = Some(render_region);
+                let target = PaintTarget::new(
+                    renderer.primary_command_buffer,
+                    &self.pass_options.viewport,
+                    render_region,
+                );
+                if let Some(clear_color) = self.pass_options.clear_color {
+                    target.clear(clear_color, &renderer.device);
+                }
+
+                self.painter.paint_and_update_textures_into(
+                    pixels_per_point,
+                    &clipped_primitives,
+                    textures_delta,
+                    &target,
+                    renderer,
+                );
+            }
+        }
+
+        for viewport in self.viewports.values_mut() {
+            viewport.paint(&mut self.painter, renderer);
+        }
+        for id in free {
+            self.painter.free_texture(id, renderer);
+        }
+    }
+
+    /// Destroys the painter and closes the windows of the deferred viewports.
+    pub(crate) fn destroy(&mut self, renderer: &mut Renderer) {
+        for (_, viewport) in self.viewports.drain() {
+            viewport.destroy(renderer);
+        }
+        self.painter.destroy(renderer);
+    }
+}
+
+fn full_rect(width: u32, height: u32) -> vk::Rect2D {
+    vk::Rect2D {
+        extent: vk::Extent2D { width, height },
+        ..Default::default()
     }
 }
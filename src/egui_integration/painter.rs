@@ -1,11 +1,11 @@
 use std::mem::offset_of;
 
 use crate::{
-    components::mesh_rendering::MeshRendering,
+    components::{mesh_rendering::MeshRendering, viewport::Viewport},
     descriptor_resources::DescriptorResources,
     material::{Material, MaterialBuildError, MaterialBuilder, Vertex, VertexInputDescription},
     math_types::{Vec2, Vec4},
-    mesh::{upload_mesh_data, Mesh, UploadData},
+    mesh::{upload_mesh_data, Aabb, Mesh, UploadData},
     renderer::Renderer,
     shader::{Shader, ShaderBuildError},
     texture::{Texture, TextureFormat},
@@ -75,6 +75,152 @@ impl Vertex for EguiVertex {
     }
 }
 
+/// Area covered by `viewport`, a fraction of `region` (e.g. the renderer's render region, or the
+/// whole render target), in pixels.
+pub(crate) fn paint_area(viewport: &Viewport, region: vk::Rect2D) -> Rect {
+    let (width, height) = viewport.pixel_size(region.extent.width, region.extent.height);
+    let min = egui::pos2(
+        region.offset.x as f32 + (viewport.x * region.extent.width as f32).round(),
+        region.offset.y as f32 + (viewport.y * region.extent.height as f32).round(),
+    );
+
+    Rect::from_min_size(min, egui::vec2(width as f32, height as f32))
+}
+
+/// Area egui is painted in, and the command buffer recording it.
+pub(crate) struct PaintTarget {
+    pub(crate) cmd_buffer: vk::CommandBuffer,
+    /// Offset and size of the painted area, in pixels.
+    pub(crate) x: f32,
+    pub(crate) y: f32,
+    pub(crate) width: f32,
+    pub(crate) height: f32,
+}
+
+impl PaintTarget {
+    /// Paints in `viewport`, see [`paint_area`].
+    pub(crate) fn new(
+        cmd_buffer: vk::CommandBuffer,
+        viewport: &Viewport,
+        region: vk::Rect2D,
+    ) -> Self {
+        let area = paint_area(viewport, region);
+
+        Self {
+            cmd_buffer,
+            x: area.min.x,
+            y: area.min.y,
+            width: area.width(),
+            height: area.height(),
+        }
+    }
+
+    /// Clears the painted area of the color attachment, inside of the current render pass.
+    pub(crate) fn clear(&self, clear_color: [f32; 4], device: &ash::Device) {
+        let clear_attachment = vk::ClearAttachment {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            color_attachment: 0,
+            clear_value: vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: clear_color,
+                },
+            },
+        };
+        let clear_rect = vk::ClearRect {
+            rect: vk::Rect2D {
+                offset: vk::Offset2D {
+                    x: self.x as i32,
+                    y: self.y as i32,
+                },
+                extent: vk::Extent2D {
+                    width: self.width as u32,
+                    height: self.height as u32,
+                },
+            },
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+
+        unsafe {
+            device.cmd_clear_attachments(
+                self.cmd_buffer,
+                std::slice::from_ref(&clear_attachment),
+                std::slice::from_ref(&clear_rect),
+            )
+        };
+    }
+}
+
+/// Where an [`EguiCallbackFn`] paints, with its viewport and scissor already set in the command
+/// buffer.
+pub struct EguiCallbackInfo {
+    pub cmd_buffer: vk::CommandBuffer,
+    /// Covers the callback's rect (`egui::PaintCallback::rect`), in pixels. Its height is negative
+    /// when the renderer's viewport is flipped (see [`Renderer::is_viewport_flipped`]).
+    pub viewport: vk::Viewport,
+    /// Clip rect of the callback, in pixels, set as the scissor.
+    pub clip_rect: vk::Rect2D,
+    pub pixels_per_point: f32,
+}
+
+type EguiCallback = dyn Fn(&EguiCallbackInfo, &mut Renderer) + Send + Sync;
+
+/// Custom Vulkan rendering inside of an egui region, e.g. a 3D viewport drawn in a panel.
+///
+/// Stored in an `egui::PaintCallback` (see [`EguiCallbackFn::paint_callback`]), it is called while
+/// the UI is painted, inside of the UI's render pass: it only has to bind its own pipeline and
+/// resources, and record its draws. The pipeline must be compatible with that render pass (the
+/// primary render pass, or the one of an [`super::EguiRenderTarget`]).
+pub struct EguiCallbackFn {
+    callback: Box<EguiCallback>,
+}
+
+impl EguiCallbackFn {
+    pub fn new(
+        callback: impl Fn(&EguiCallbackInfo, &mut Renderer) + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            callback: Box::new(callback),
+        }
+    }
+
+    /// Wraps the callback in a paint callback covering `rect`, to add to an `egui::Painter`.
+    pub fn paint_callback(self, rect: Rect) -> egui::PaintCallback {
+        egui::PaintCallback {
+            rect,
+            callback: std::sync::Arc::new(self),
+        }
+    }
+}
+
+/// Scissor covering `clip_rect` (in points), clamped to the painted area.
+fn clip_rect_scissor(clip_rect: &Rect, pixels_per_point: f32, target: &PaintTarget) -> vk::Rect2D {
+    let min_x = pixels_per_point * clip_rect.min.x;
+    let min_y = pixels_per_point * clip_rect.min.y;
+    let max_x = pixels_per_point * clip_rect.max.x;
+    let max_y = pixels_per_point * clip_rect.max.y;
+
+    let min_x = min_x.clamp(0.0, target.width);
+    let min_y = min_y.clamp(0.0, target.height);
+    let max_x = max_x.clamp(min_x, target.width);
+    let max_y = max_y.clamp(min_y, target.height);
+
+    let min_x = min_x.round() as u32;
+    let min_y = min_y.round() as u32;
+    let max_x = max_x.round() as u32;
+    let max_y = max_y.round() as u32;
+
+    vk::Rect2D::default()
+        .offset(vk::Offset2D {
+            x: target.x as i32 + min_x as i32,
+            y: target.y as i32 + min_y as i32,
+        })
+        .extent(vk::Extent2D {
+            width: max_x - min_x,
+            height: max_y - min_y,
+        })
+}
+
 struct TextureInfo {
     handle: ThreadSafeRef<Texture>,
     is_user: bool,
@@ -86,10 +232,18 @@ pub struct Painter {
     material: ThreadSafeRef<Material<EguiVertex>>,
 
     textures: std::collections::HashMap<egui::TextureId, TextureInfo>,
-    frame_meshes: Vec<ThreadSafeRef<MeshRendering<EguiVertex>>>,
+    /// One per frame in flight, see [`Renderer::current_frame_index`].
+    frame_resources: Vec<FrameResources>,
     user_texture_id: u64,
 }
 
+/// Resources used by a frame, destroyed once it is done executing.
+#[derive(Default)]
+struct FrameResources {
+    meshes: Vec<ThreadSafeRef<MeshRendering<EguiVertex>>>,
+    freed_textures: Vec<ThreadSafeRef<Texture>>,
+}
+
 #[allow(clippy::enum_variant_names)]
 #[derive(Error, Debug)]
 pub enum PainterCreationError {
@@ -105,16 +259,10 @@ pub enum PainterCreationError {
 
 impl Painter {
     pub fn new(renderer: &mut Renderer) -> Result<Self, PainterCreationError> {
-        let max_texture_size = renderer
-            .device_properties
-            .limits
-            .max_image_dimension2_d
+        let max_image_dimension_2d = renderer.capabilities().limits.max_image_dimension_2d;
+        let max_texture_size = max_image_dimension_2d
             .try_into()
-            .map_err(|_| {
-                PainterCreationError::SizeConversionFailed(
-                    renderer.device_properties.limits.max_image_dimension2_d,
-                )
-            })?;
+            .map_err(|_| PainterCreationError::SizeConversionFailed(max_image_dimension_2d))?;
         let shader = Shader::from_spirv_u8(
             include_bytes!("shaders/gen/egui.vert"),
             include_bytes!("shaders/gen/egui.frag"),
@@ -128,7 +276,9 @@ impl Painter {
             max_texture_size,
             material,
             textures: Default::default(),
-            frame_meshes: Default::default(),
+            frame_resources: (0..renderer.frames_in_flight())
+                .map(|_| FrameResources::default())
+                .collect(),
             user_texture_id: 0,
         })
     }
@@ -139,22 +289,45 @@ impl Painter {
         clipped_primitives: &[egui::ClippedPrimitive],
         textures_delta: egui::TexturesDelta,
         renderer: &mut Renderer,
+    ) {
+        let target = PaintTarget::new(
+            renderer.primary_command_buffer,
+            &Viewport::default(),
+            renderer.render_region(),
+        );
+        self.paint_and_update_textures_into(
+            pixels_per_point,
+            clipped_primitives,
+            textures_delta,
+            &target,
+            renderer,
+        );
+    }
+
+    pub(crate) fn paint_and_update_textures_into(
+        &mut self,
+        pixels_per_point: f32,
+        clipped_primitives: &[egui::ClippedPrimitive],
+        textures_delta: egui::TexturesDelta,
+        target: &PaintTarget,
+        renderer: &mut Renderer,
     ) {
         for (id, image_delta) in textures_delta.set {
             self.set_texture(id, &image_delta, renderer);
         }
 
-        self.paint_primitives(pixels_per_point, clipped_primitives, renderer);
+        self.paint_primitives(pixels_per_point, clipped_primitives, target, renderer);
 
         for id in textures_delta.free {
             self.free_texture(id, renderer);
         }
     }
 
-    fn paint_primitives(
+    pub(crate) fn paint_primitives(
         &mut self,
         pixels_per_point: f32,
         clipped_primitives: &[egui::ClippedPrimitive],
+        target: &PaintTarget,
         renderer: &mut Renderer,
     ) {
         for egui::ClippedPrimitive {
@@ -164,10 +337,10 @@ impl Painter {
         {
             match primitive {
                 egui::epaint::Primitive::Mesh(mesh) => {
-                    self.paint_mesh(pixels_per_point, clip_rect, mesh, renderer)
+                    self.paint_mesh(pixels_per_point, clip_rect, mesh, target, renderer)
                 }
-                egui::epaint::Primitive::Callback(_) => {
-                    todo!("Custom rendering callback not implemented yet")
+                egui::epaint::Primitive::Callback(callback) => {
+                    self.paint_callback(pixels_per_point, clip_rect, callback, target, renderer)
                 }
             }
         }
@@ -178,6 +351,7 @@ impl Painter {
         pixels_per_point: f32,
         clip_rect: &Rect,
         mesh: &egui::Mesh,
+        target: &PaintTarget,
         renderer: &mut Renderer,
     ) {
         assert!(mesh.is_valid());
@@ -185,16 +359,22 @@ impl Painter {
             return;
         }
 
-        let width = renderer.framebuffer_width as f32;
-        let height = renderer.framebuffer_height as f32;
+        let width = target.width;
+        let height = target.height;
         let width_in_points = width / pixels_per_point;
         let height_in_points = height / pixels_per_point;
+        let flipped_viewport = renderer.is_viewport_flipped();
 
         let vertices: &[EguiVertex] = &mesh
             .vertices
             .iter()
             .map(|vertex| EguiVertex {
-                position: Vec2::new(vertex.pos.x, height_in_points - vertex.pos.y),
+                // Egui's Y axis points down, see `RendererBuilder::with_flipped_viewport`
+                position: if flipped_viewport {
+                    Vec2::new(vertex.pos.x, height_in_points - vertex.pos.y)
+                } else {
+                    Vec2::new(vertex.pos.x, vertex.pos.y)
+                },
                 texture_coords: Vec2::new(vertex.uv.x, vertex.uv.y),
                 color: Vec4::new(
                     vertex.color.r() as f32 / u8::MAX as f32,
@@ -210,6 +390,7 @@ impl Painter {
         } = upload_mesh_data(vertices, &mesh.indices, renderer)
             .expect("Failed to upload egui mesh data");
         let mesh_ref = ThreadSafeRef::new(Mesh {
+            aabb: Aabb::from_vertices(vertices),
             vertices: vertices.to_vec(),
             indices: Some(mesh.indices.clone()),
             vertex_buffer,
@@ -239,7 +420,7 @@ impl Painter {
             .expect("Texture binding for Egui should succeed");
 
         let device = &renderer.device;
-        let cmd_buffer = &renderer.primary_command_buffer;
+        let cmd_buffer = &target.cmd_buffer;
         let material = self.material.lock();
         unsafe {
             device.cmd_bind_descriptor_sets(
@@ -247,46 +428,23 @@ impl Painter {
                 vk::PipelineBindPoint::GRAPHICS,
                 material.layout,
                 0,
-                &[
-                    renderer.descriptors[0].handle,
-                    renderer.descriptors[1].handle,
-                ],
+                &renderer.frame_descriptor_sets(),
                 &[],
             )
         };
 
         let viewport = vk::Viewport::default()
-            .x(0.0)
-            .y(height)
+            .x(target.x)
             .width(width)
-            .height(-height)
             .min_depth(0.0)
             .max_depth(1.0);
+        let viewport = if flipped_viewport {
+            viewport.y(target.y + height).height(-height)
+        } else {
+            viewport.y(target.y).height(height)
+        };
 
-        let min_x = pixels_per_point * clip_rect.min.x;
-        let min_y = pixels_per_point * clip_rect.min.y;
-        let max_x = pixels_per_point * clip_rect.max.x;
-        let max_y = pixels_per_point * clip_rect.max.y;
-
-        let min_x = min_x.clamp(0.0, width);
-        let min_y = min_y.clamp(0.0, height);
-        let max_x = max_x.clamp(min_x, width);
-        let max_y = max_y.clamp(min_y, height);
-
-        let min_x = min_x.round() as u32;
-        let min_y = min_y.round() as u32;
-        let max_x = max_x.round() as u32;
-        let max_y = max_y.round() as u32;
-
-        let scissor = vk::Rect2D::default()
-            .offset(vk::Offset2D {
-                x: min_x as i32,
-                y: min_y as i32,
-            })
-            .extent(vk::Extent2D {
-                width: max_x - min_x,
-                height: max_y - min_y,
-            });
+        let scissor = clip_rect_scissor(clip_rect, pixels_per_point, target);
         unsafe {
             device.cmd_bind_pipeline(
                 *cmd_buffer,
@@ -320,46 +478,86 @@ impl Painter {
                 &[],
             );
 
-            let mesh = mesh_ref.lock();
-            device.cmd_bind_vertex_buffers(
-                *cmd_buffer,
-                0,
-                std::slice::from_ref(&mesh.vertex_buffer.handle),
-                &[0],
-            );
-            device.cmd_bind_index_buffer(
-                *cmd_buffer,
-                mesh.index_buffer.as_ref().unwrap().handle,
-                0,
-                vk::IndexType::UINT32,
-            );
-            device.cmd_draw_indexed(
-                *cmd_buffer,
-                mesh.indices
-                    .as_ref()
-                    .unwrap()
-                    .len()
-                    .try_into()
-                    .expect("Unsupported architecture"),
-                1,
-                0,
-                0,
-                0,
-            );
+            mesh_ref.lock().record_draw(device, *cmd_buffer);
         };
 
         drop(mesh_rendering);
-        self.frame_meshes.push(mesh_rendering_ref);
+        self.frame_resources[renderer.current_frame_index()]
+            .meshes
+            .push(mesh_rendering_ref);
+    }
+
+    fn paint_callback(
+        &self,
+        pixels_per_point: f32,
+        clip_rect: &Rect,
+        callback: &egui::PaintCallback,
+        target: &PaintTarget,
+        renderer: &mut Renderer,
+    ) {
+        let Some(callback_fn) = callback.callback.downcast_ref::<EguiCallbackFn>() else {
+            log::warn!("Unsupported egui paint callback, it should be an EguiCallbackFn");
+            return;
+        };
+
+        let scissor = clip_rect_scissor(clip_rect, pixels_per_point, target);
+        if scissor.extent.width == 0 || scissor.extent.height == 0 {
+            return;
+        }
+
+        let rect = callback.rect;
+        let viewport = vk::Viewport::default()
+            .x(target.x + rect.min.x * pixels_per_point)
+            .width(rect.width() * pixels_per_point)
+            .min_depth(0.0)
+            .max_depth(1.0);
+        let viewport = if renderer.is_viewport_flipped() {
+            viewport
+                .y(target.y + rect.max.y * pixels_per_point)
+                .height(-rect.height() * pixels_per_point)
+        } else {
+            viewport
+                .y(target.y + rect.min.y * pixels_per_point)
+                .height(rect.height() * pixels_per_point)
+        };
+
+        unsafe {
+            renderer
+                .device
+                .cmd_set_viewport(target.cmd_buffer, 0, std::slice::from_ref(&viewport));
+            renderer
+                .device
+                .cmd_set_scissor(target.cmd_buffer, 0, std::slice::from_ref(&scissor));
+        };
+
+        (callback_fn.callback)(
+            &EguiCallbackInfo {
+                cmd_buffer: target.cmd_buffer,
+                viewport,
+                clip_rect: scissor,
+                pixels_per_point,
+            },
+            renderer,
+        );
     }
 
+    /// Destroys the meshes and textures of the frame that last used the current frame's slot,
+    /// which is done executing once the current frame has begun.
     pub fn cleanup_previous_frame(&mut self, renderer: &mut Renderer) {
-        for mesh_rendering_ref in &self.frame_meshes {
+        let frame_index = renderer.current_frame_index();
+        self.cleanup_frame(frame_index, renderer);
+    }
+
+    fn cleanup_frame(&mut self, frame_index: usize, renderer: &mut Renderer) {
+        let frame_resources = std::mem::take(&mut self.frame_resources[frame_index]);
+        for mesh_rendering_ref in frame_resources.meshes {
             let mut mesh_rendering = mesh_rendering_ref.lock();
             mesh_rendering.mesh_ref.lock().destroy(renderer);
             mesh_rendering.destroy(renderer);
         }
-
-        self.frame_meshes.clear();
+        for texture_ref in frame_resources.freed_textures {
+            texture_ref.lock().destroy(renderer);
+        }
     }
 
     fn set_texture(
@@ -519,9 +717,13 @@ impl Painter {
         }
     }
 
+    /// The texture may still be drawn by the frames in flight, it is only destroyed once they are
+    /// done executing.
     pub(crate) fn free_texture(&mut self, tex_id: egui::TextureId, renderer: &mut Renderer) {
         if let Some(TextureInfo { handle, .. }) = self.textures.remove(&tex_id) {
-            handle.lock().destroy(renderer);
+            self.frame_resources[renderer.current_frame_index()]
+                .freed_textures
+                .push(handle);
         }
     }
 
@@ -564,7 +766,9 @@ impl Painter {
     }
 
     pub(crate) fn destroy(&mut self, renderer: &mut Renderer) {
-        self.cleanup_previous_frame(renderer);
+        for frame_index in 0..self.frame_resources.len() {
+            self.cleanup_frame(frame_index, renderer);
+        }
 
         for (_, TextureInfo { handle, is_user }) in self.textures.drain() {
             if !is_user {
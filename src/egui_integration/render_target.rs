@@ -0,0 +1,315 @@
+use crate::{
+    allocated_types::{AllocatedImage, ImageBuildError},
+    renderer::{image_aspect_mask, Renderer},
+    texture::{Texture, TextureBuildError, TextureBuilder},
+    utils::ThreadSafeRef,
+};
+
+use ash::vk;
+use thiserror::Error;
+
+/// Offscreen target egui can be rendered into instead of the swapchain (see
+/// [`super::EguiPassOptions::render_target`]).
+///
+/// The UI is rendered in its own render pass and submission, before the main frame is submitted,
+/// so [`EguiRenderTarget::texture_ref`] can be sampled by materials or composed by the application
+/// during the same frame. Its format matches the swapchain's, as egui's pipeline is shared between
+/// both passes. The target is owned by the application, and must be destroyed by it.
+pub struct EguiRenderTarget {
+    pub texture_ref: ThreadSafeRef<Texture>,
+    depth_image: AllocatedImage,
+
+    render_pass: vk::RenderPass,
+    framebuffer: vk::Framebuffer,
+    command_pool: vk::CommandPool,
+    /// Command buffer of each frame in flight, and the fence signaled once its submission
+    /// completed, which is waited before the command buffer is recorded again.
+    frames: Vec<(vk::CommandBuffer, vk::Fence)>,
+}
+
+#[derive(Error, Debug)]
+pub enum EguiRenderTargetCreationError {
+    #[error("Creation of the target's color texture failed with error: {0}.")]
+    TextureCreationFailed(#[from] TextureBuildError),
+
+    #[error("Creation of the target's depth image failed with error: {0}.")]
+    DepthImageCreationFailed(#[from] ImageBuildError),
+
+    #[error("Vulkan creation of the target's render pass failed with result: {0}.")]
+    VulkanRenderPassCreationFailed(vk::Result),
+
+    #[error("Vulkan creation of the target's framebuffer failed with result: {0}.")]
+    VulkanFramebufferCreationFailed(vk::Result),
+
+    #[error("Vulkan creation of the target's command pool failed with result: {0}.")]
+    VulkanCommandPoolCreationFailed(vk::Result),
+
+    #[error("Vulkan allocation of the target's command buffers failed with result: {0}.")]
+    VulkanCommandBufferAllocationFailed(vk::Result),
+
+    #[error("Vulkan creation of the target's fences failed with result: {0}.")]
+    VulkanFenceCreationFailed(vk::Result),
+}
+
+impl EguiRenderTarget {
+    pub fn new(
+        width: u32,
+        height: u32,
+        renderer: &mut Renderer,
+    ) -> Result<ThreadSafeRef<Self>, EguiRenderTargetCreationError> {
+        let color_format = renderer.surface_format().format;
+        let depth_format = renderer.depth_format();
+
+        let texture_ref = TextureBuilder {
+            format: color_format,
+            ..Texture::builder().with_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
+        }
+        .build([width, height], renderer)?;
+
+        let mut depth_image_builder = AllocatedImage::builder(vk::Extent3D {
+            width,
+            height,
+            depth: 1,
+        });
+        depth_image_builder.image_create_info = depth_image_builder
+            .image_create_info
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(depth_format)
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+        depth_image_builder.image_view_create_info = depth_image_builder
+            .image_view_create_info
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(depth_format)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: image_aspect_mask(depth_format),
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            });
+        let depth_image =
+            depth_image_builder.build_uninitialized(&renderer.device, &mut renderer.allocator())?;
+
+        // Must stay compatible with the primary render pass, egui's pipeline is built against it
+        let attachments = [
+            vk::AttachmentDescription {
+                format: color_format,
+                samples: vk::SampleCountFlags::TYPE_1,
+                load_op: vk::AttachmentLoadOp::CLEAR,
+                store_op: vk::AttachmentStoreOp::STORE,
+                initial_layout: vk::ImageLayout::UNDEFINED,
+                final_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                ..Default::default()
+            },
+            vk::AttachmentDescription {
+                format: depth_format,
+                samples: vk::SampleCountFlags::TYPE_1,
+                load_op: vk::AttachmentLoadOp::CLEAR,
+                store_op: vk::AttachmentStoreOp::DONT_CARE,
+                stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+                stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+                initial_layout: vk::ImageLayout::UNDEFINED,
+                final_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+                ..Default::default()
+            },
+        ];
+        let color_attachment_ref = vk::AttachmentReference {
+            attachment: 0,
+            layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        };
+        let depth_attachment_ref = vk::AttachmentReference {
+            attachment: 1,
+            layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        };
+        let subpass_description = vk::SubpassDescription::default()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(std::slice::from_ref(&color_attachment_ref))
+            .depth_stencil_attachment(&depth_attachment_ref);
+        let dependencies = [
+            // Previous frame's sampling of the texture must be done before it's overwritten
+            vk::SubpassDependency::default()
+                .src_subpass(vk::SUBPASS_EXTERNAL)
+                .dst_subpass(0)
+                .src_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+                .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .src_access_mask(vk::AccessFlags::SHADER_READ)
+                .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE),
+            vk::SubpassDependency::default()
+                .src_subpass(0)
+                .dst_subpass(vk::SUBPASS_EXTERNAL)
+                .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+                .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .dst_access_mask(vk::AccessFlags::SHADER_READ),
+        ];
+        let render_pass_info = vk::RenderPassCreateInfo::default()
+            .attachments(&attachments)
+            .subpasses(std::slice::from_ref(&subpass_description))
+            .dependencies(&dependencies);
+        let render_pass = unsafe { renderer.device.create_render_pass(&render_pass_info, None) }
+            .map_err(EguiRenderTargetCreationError::VulkanRenderPassCreationFailed)?;
+
+        let framebuffer_attachments = [texture_ref.lock().image_ref.lock().view, depth_image.view];
+        let framebuffer_info = vk::FramebufferCreateInfo::default()
+            .render_pass(render_pass)
+            .attachments(&framebuffer_attachments)
+            .width(width)
+            .height(height)
+            .layers(1);
+        let framebuffer = unsafe { renderer.device.create_framebuffer(&framebuffer_info, None) }
+            .map_err(EguiRenderTargetCreationError::VulkanFramebufferCreationFailed)?;
+
+        let command_pool_info = vk::CommandPoolCreateInfo::default()
+            .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+            .queue_family_index(renderer.graphics_queue.family_index);
+        let command_pool = unsafe {
+            renderer
+                .device
+                .create_command_pool(&command_pool_info, None)
+        }
+        .map_err(EguiRenderTargetCreationError::VulkanCommandPoolCreationFailed)?;
+        let command_buffer_info = vk::CommandBufferAllocateInfo::default()
+            .command_pool(command_pool)
+            .command_buffer_count(renderer.frames_in_flight().try_into().unwrap())
+            .level(vk::CommandBufferLevel::PRIMARY);
+        let command_buffers = unsafe {
+            renderer
+                .device
+                .allocate_command_buffers(&command_buffer_info)
+        }
+        .map_err(EguiRenderTargetCreationError::VulkanCommandBufferAllocationFailed)?;
+        let mut frames = Vec::with_capacity(command_buffers.len());
+        for command_buffer in command_buffers {
+            let fence_info = vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED);
+            match unsafe { renderer.device.create_fence(&fence_info, None) } {
+                Ok(fence) => frames.push((command_buffer, fence)),
+                Err(result) => {
+                    unsafe {
+                        for (_, fence) in frames {
+                            renderer.device.destroy_fence(fence, None);
+                        }
+                        renderer.device.destroy_command_pool(command_pool, None);
+                    }
+                    return Err(EguiRenderTargetCreationError::VulkanFenceCreationFailed(
+                        result,
+                    ));
+                }
+            }
+        }
+
+        Ok(ThreadSafeRef::new(Self {
+            texture_ref,
+            depth_image,
+            render_pass,
+            framebuffer,
+            command_pool,
+            frames,
+        }))
+    }
+
+    pub fn dimensions(&self) -> [u32; 2] {
+        self.texture_ref.lock().dimensions
+    }
+
+    /// Starts recording the target's render pass, and returns the command buffer to paint with.
+    /// The command buffer of the current frame in flight is reused once its previous submission
+    /// completed.
+    pub(crate) fn begin(&self, clear_color: [f32; 4], renderer: &Renderer) -> vk::CommandBuffer {
+        let [width, height] = self.dimensions();
+        let (command_buffer, fence) = self.frames[renderer.current_frame_index()];
+
+        unsafe {
+            renderer
+                .device
+                .wait_for_fences(&[fence], true, u64::MAX)
+                .expect("Failed to wait for the egui target fence");
+            renderer
+                .device
+                .reset_fences(&[fence])
+                .expect("Failed to reset the egui target fence");
+            renderer.device.begin_command_buffer(
+                command_buffer,
+                &vk::CommandBufferBeginInfo::default()
+                    .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+            )
+        }
+        .expect("Failed to start egui target command buffer");
+
+        let clear_values = [
+            vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: clear_color,
+                },
+            },
+            vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: 1.0,
+                    stencil: 0,
+                },
+            },
+        ];
+        let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+            .render_pass(self.render_pass)
+            .framebuffer(self.framebuffer)
+            .render_area(vk::Rect2D {
+                extent: vk::Extent2D { width, height },
+                ..Default::default()
+            })
+            .clear_values(&clear_values);
+        unsafe {
+            renderer.device.cmd_begin_render_pass(
+                command_buffer,
+                &render_pass_begin_info,
+                vk::SubpassContents::INLINE,
+            )
+        };
+
+        command_buffer
+    }
+
+    /// Ends the render pass started by [`EguiRenderTarget::begin`] and submits it to the graphics
+    /// queue, before the main frame.
+    pub(crate) fn end_and_submit(&self, renderer: &Renderer) {
+        let (command_buffer, fence) = self.frames[renderer.current_frame_index()];
+        unsafe {
+            renderer.device.cmd_end_render_pass(command_buffer);
+            renderer.device.end_command_buffer(command_buffer)
+        }
+        .expect("Failed to record egui target command buffer");
+
+        let submit_info =
+            vk::SubmitInfo::default().command_buffers(std::slice::from_ref(&command_buffer));
+        unsafe {
+            renderer
+                .device
+                .queue_submit(renderer.graphics_queue.handle, &[submit_info], fence)
+        }
+        .expect("Failed to submit egui target command buffer");
+    }
+
+    pub fn destroy(&mut self, renderer: &mut Renderer) {
+        unsafe {
+            renderer
+                .device
+                .device_wait_idle()
+                .expect("Failed to wait for device");
+
+            for (_, fence) in self.frames.drain(..) {
+                renderer.device.destroy_fence(fence, None);
+            }
+            renderer
+                .device
+                .destroy_command_pool(self.command_pool, None);
+            renderer.device.destroy_framebuffer(self.framebuffer, None);
+            renderer.device.destroy_render_pass(self.render_pass, None);
+        }
+
+        self.depth_image.destroy(renderer);
+        self.texture_ref.lock().destroy(renderer);
+    }
+}
@@ -0,0 +1,349 @@
+use crate::{
+    components::viewport::Viewport,
+    renderer::{Renderer, SurfaceInfo, SwapchainInfo},
+};
+
+use super::painter::{PaintTarget, Painter};
+
+use std::sync::Arc;
+
+use ash::vk;
+use thiserror::Error;
+use winit::{event::WindowEvent, event_loop::ActiveEventLoop, window::Window};
+
+#[derive(Error, Debug)]
+#[allow(clippy::enum_variant_names)]
+pub(crate) enum ViewportWindowCreationError {
+    #[error("Creation of the viewport's window failed with error: {0}.")]
+    WindowCreationFailed(#[from] winit::error::OsError),
+
+    #[error("Vulkan creation of the viewport's surface failed with result: {0}.")]
+    VulkanSurfaceCreationFailed(vk::Result),
+
+    #[error("Vulkan creation of the viewport's command pool failed with result: {0}.")]
+    VulkanCommandPoolCreationFailed(vk::Result),
+
+    #[error("Vulkan allocation of the viewport's command buffer failed with result: {0}.")]
+    VulkanCommandBufferAllocationFailed(vk::Result),
+
+    #[error("Vulkan creation of the viewport's sync objects failed with result: {0}.")]
+    VulkanSyncObjectsCreationFailed(vk::Result),
+}
+
+/// Native window of a deferred egui viewport (see [`egui::Context::show_viewport_deferred`]), with
+/// its own swapchain.
+///
+/// The viewport is painted with egui's pipeline in the renderer's primary render pass, and
+/// submitted to the graphics queue before the main frame. The painter's resources of the frame are
+/// thus released once the main frame is done executing, like the root viewport's.
+pub(crate) struct ViewportWindow {
+    window: Window,
+    egui_winit_state: egui_winit::State,
+    info: egui::ViewportInfo,
+    builder: egui::ViewportBuilder,
+    ui_callback: Arc<egui::DeferredViewportUiCallback>,
+
+    /// Output of the last [`ViewportWindow::run`], painted by [`ViewportWindow::paint`].
+    clipped_primitives: Vec<egui::ClippedPrimitive>,
+    pixels_per_point: f32,
+
+    surface: SurfaceInfo,
+    /// Created on the first paint, and recreated when the window is resized. `None` while the
+    /// window is minimized.
+    swapchain: Option<(SwapchainInfo, Vec<vk::Framebuffer>)>,
+    needs_resize: bool,
+
+    command_pool: vk::CommandPool,
+    command_buffer: vk::CommandBuffer,
+    render_fence: vk::Fence,
+    present_semaphore: vk::Semaphore,
+    render_semaphore: vk::Semaphore,
+}
+
+impl ViewportWindow {
+    pub(crate) fn new(
+        viewport_id: egui::ViewportId,
+        builder: egui::ViewportBuilder,
+        ui_callback: Arc<egui::DeferredViewportUiCallback>,
+        event_loop: &ActiveEventLoop,
+        egui_ctx: &egui::Context,
+        renderer: &mut Renderer,
+    ) -> Result<Self, ViewportWindowCreationError> {
+        let window = egui_winit::create_window(egui_ctx, event_loop, &builder)?;
+        let egui_winit_state =
+            egui_winit::State::new(egui_ctx.clone(), viewport_id, &window, None, None, None);
+        let mut info = egui::ViewportInfo::default();
+        egui_winit::update_viewport_info(&mut info, egui_ctx, &window, true);
+
+        let surface = renderer
+            .create_window_surface(&window)
+            .map_err(ViewportWindowCreationError::VulkanSurfaceCreationFailed)?;
+
+        let command_pool_info = vk::CommandPoolCreateInfo::default()
+            .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+            .queue_family_index(renderer.graphics_queue.family_index);
+        let command_pool = unsafe {
+            renderer
+                .device
+                .create_command_pool(&command_pool_info, None)
+        }
+        .map_err(ViewportWindowCreationError::VulkanCommandPoolCreationFailed)?;
+        let command_buffer_info = vk::CommandBufferAllocateInfo::default()
+            .command_pool(command_pool)
+            .command_buffer_count(1)
+            .level(vk::CommandBufferLevel::PRIMARY);
+        let command_buffer = unsafe {
+            renderer
+                .device
+                .allocate_command_buffers(&command_buffer_info)
+        }
+        .map_err(ViewportWindowCreationError::VulkanCommandBufferAllocationFailed)?[0];
+
+        let fence_info = vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED);
+        let render_fence = unsafe { renderer.device.create_fence(&fence_info, None) }
+            .map_err(ViewportWindowCreationError::VulkanSyncObjectsCreationFailed)?;
+        let semaphore_info = vk::SemaphoreCreateInfo::default();
+        let present_semaphore = unsafe { renderer.device.create_semaphore(&semaphore_info, None) }
+            .map_err(ViewportWindowCreationError::VulkanSyncObjectsCreationFailed)?;
+        let render_semaphore =
+            unsafe { renderer.device.create_semaphore(&semaphore_info, None) }
+                .map_err(ViewportWindowCreationError::VulkanSyncObjectsCreationFailed)?;
+
+        Ok(Self {
+            window,
+            egui_winit_state,
+            info,
+            builder,
+            ui_callback,
+            clipped_primitives: vec![],
+            pixels_per_point: 1.0,
+            surface,
+            swapchain: None,
+            needs_resize: false,
+            command_pool,
+            command_buffer,
+            render_fence,
+            present_semaphore,
+            render_semaphore,
+        })
+    }
+
+    pub(crate) fn window(&self) -> &Window {
+        &self.window
+    }
+
+    pub(crate) fn handle_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::Resized(_) => self.needs_resize = true,
+            // Forwarded to the UI, which stops showing the viewport if it wants it closed
+            WindowEvent::CloseRequested => self.info.events.push(egui::ViewportEvent::Close),
+            _ => (),
+        }
+
+        let _ = self.egui_winit_state.on_window_event(&self.window, event);
+    }
+
+    /// Applies the changes of the viewport's builder and the commands output by egui. Actions
+    /// requiring the backend's help (e.g. screenshots) are not supported, and ignored.
+    pub(crate) fn update(&mut self, output: egui::ViewportOutput, egui_ctx: &egui::Context) {
+        if let Some(ui_callback) = output.viewport_ui_cb {
+            self.ui_callback = ui_callback;
+        }
+
+        let (mut commands, _) = self.builder.patch(output.builder);
+        commands.extend(output.commands);
+        self.apply_commands(commands, egui_ctx);
+    }
+
+    pub(crate) fn apply_commands(
+        &mut self,
+        commands: Vec<egui::ViewportCommand>,
+        egui_ctx: &egui::Context,
+    ) {
+        let mut actions_requested = Default::default();
+        egui_winit::process_viewport_commands(
+            egui_ctx,
+            &mut self.info,
+            commands,
+            &self.window,
+            &mut actions_requested,
+        );
+    }
+
+    /// Runs the viewport's UI, and tessellates it for the next [`ViewportWindow::paint`]. The
+    /// textures and viewports of the output are left to the integration.
+    pub(crate) fn run(&mut self, egui_ctx: &egui::Context) -> egui::FullOutput {
+        egui_winit::update_viewport_info(&mut self.info, egui_ctx, &self.window, false);
+        let mut raw_input = self.egui_winit_state.take_egui_input(&self.window);
+        raw_input
+            .viewports
+            .insert(raw_input.viewport_id, self.info.clone());
+        self.info.events.clear();
+
+        let ui_callback = self.ui_callback.clone();
+        let mut full_output = egui_ctx.run(raw_input, |ctx| ui_callback(ctx));
+
+        self.egui_winit_state.handle_platform_output(
+            &self.window,
+            std::mem::take(&mut full_output.platform_output),
+        );
+        self.pixels_per_point = full_output.pixels_per_point;
+        self.clipped_primitives = egui_ctx.tessellate(
+            std::mem::take(&mut full_output.shapes),
+            full_output.pixels_per_point,
+        );
+
+        full_output
+    }
+
+    /// Paints the UI of the last [`ViewportWindow::run`] to the window and presents it. The
+    /// textures it uses must already be set in the painter.
+    pub(crate) fn paint(&mut self, painter: &mut Painter, renderer: &mut Renderer) {
+        let (width, height): (u32, u32) = self.window.inner_size().into();
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        unsafe {
+            renderer
+                .device
+                .wait_for_fences(&[self.render_fence], true, u64::MAX)
+        }
+        .expect("Failed to wait for egui viewport fence");
+
+        if self.needs_resize {
+            self.needs_resize = false;
+            if let Some((swapchain, framebuffers)) = self.swapchain.take() {
+                // The presentation of the previous frame may still be using the swapchain
+                unsafe { renderer.device.device_wait_idle() }.expect("Failed to wait for device");
+                renderer.destroy_window_swapchain(swapchain, framebuffers);
+            }
+        }
+        let (swapchain, framebuffers) = self
+            .swapchain
+            .get_or_insert_with(|| renderer.create_window_swapchain(width, height, &self.surface));
+
+        let image_index = match unsafe {
+            swapchain.loader.acquire_next_image(
+                swapchain.handle,
+                u64::MAX,
+                self.present_semaphore,
+                vk::Fence::null(),
+            )
+        } {
+            Ok((image_index, _)) => image_index,
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                self.needs_resize = true;
+                return;
+            }
+            Err(err) => panic!("Failed to acquire next egui viewport image: {:?}", err),
+        };
+
+        unsafe { renderer.device.reset_fences(&[self.render_fence]) }
+            .expect("Failed to reset egui viewport fence");
+        unsafe {
+            renderer.device.begin_command_buffer(
+                self.command_buffer,
+                &vk::CommandBufferBeginInfo::default()
+                    .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+            )
+        }
+        .expect("Failed to start egui viewport command buffer");
+
+        let render_area = vk::Rect2D {
+            extent: swapchain.extent,
+            ..Default::default()
+        };
+        let clear_values = [
+            vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: renderer.clear_config.color.unwrap_or_default(),
+                },
+            },
+            vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: 1.0,
+                    stencil: 0,
+                },
+            },
+        ];
+        let framebuffer_index: usize = image_index.try_into().expect("Unsupported architecture");
+        let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+            .render_pass(renderer.primary_render_pass)
+            .framebuffer(framebuffers[framebuffer_index])
+            .render_area(render_area)
+            .clear_values(&clear_values);
+        unsafe {
+            renderer.device.cmd_begin_render_pass(
+                self.command_buffer,
+                &render_pass_begin_info,
+                vk::SubpassContents::INLINE,
+            )
+        };
+
+        let target = PaintTarget::new(self.command_buffer, &Viewport::default(), render_area);
+        painter.paint_primitives(
+            self.pixels_per_point,
+            &self.clipped_primitives,
+            &target,
+            renderer,
+        );
+
+        unsafe {
+            renderer.device.cmd_end_render_pass(self.command_buffer);
+            renderer.device.end_command_buffer(self.command_buffer)
+        }
+        .expect("Failed to record egui viewport command buffer");
+
+        let submit_info = vk::SubmitInfo::default()
+            .wait_semaphores(std::slice::from_ref(&self.present_semaphore))
+            .wait_dst_stage_mask(&[vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT])
+            .command_buffers(std::slice::from_ref(&self.command_buffer))
+            .signal_semaphores(std::slice::from_ref(&self.render_semaphore));
+        unsafe {
+            renderer.device.queue_submit(
+                renderer.graphics_queue.handle,
+                &[submit_info],
+                self.render_fence,
+            )
+        }
+        .expect("Failed to submit egui viewport command buffer");
+
+        let present_info = vk::PresentInfoKHR::default()
+            .swapchains(std::slice::from_ref(&swapchain.handle))
+            .wait_semaphores(std::slice::from_ref(&self.render_semaphore))
+            .image_indices(std::slice::from_ref(&image_index));
+        match unsafe {
+            swapchain
+                .loader
+                .queue_present(renderer.graphics_queue.handle, &present_info)
+        } {
+            Ok(false) => (),
+            Ok(true) | Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => self.needs_resize = true,
+            Err(err) => panic!("Failed to present egui viewport image: {:?}", err),
+        }
+    }
+
+    pub(crate) fn destroy(mut self, renderer: &mut Renderer) {
+        unsafe { renderer.device.device_wait_idle() }.expect("Failed to wait for device");
+
+        if let Some((swapchain, framebuffers)) = self.swapchain.take() {
+            renderer.destroy_window_swapchain(swapchain, framebuffers);
+        }
+        // The window is dropped after its surface
+        renderer.destroy_window_surface(self.surface);
+
+        unsafe {
+            renderer
+                .device
+                .destroy_semaphore(self.render_semaphore, None);
+            renderer
+                .device
+                .destroy_semaphore(self.present_semaphore, None);
+            renderer.device.destroy_fence(self.render_fence, None);
+            renderer
+                .device
+                .destroy_command_pool(self.command_pool, None);
+        }
+    }
+}
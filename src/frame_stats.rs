@@ -0,0 +1,72 @@
+use bevy_ecs::system::Resource;
+
+use std::{collections::VecDeque, time::Duration};
+
+/// Number of frame times kept by [`FrameStats`].
+pub const FRAME_HISTORY_LENGTH: usize = 240;
+
+/// Timings of the recent frames, recorded by the application before updating each frame. It is a
+/// resource of the ECS world, e.g. to display the frame rate or plot the frame times.
+#[derive(Debug, Clone, Resource)]
+pub struct FrameStats {
+    /// Time elapsed since the previous frame.
+    pub frame_time: Duration,
+    /// Frame rate matching `frame_time`.
+    pub fps: f32,
+    /// Times of the last [`FRAME_HISTORY_LENGTH`] frames, from the oldest to the latest.
+    history: VecDeque<Duration>,
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self {
+            frame_time: Duration::ZERO,
+            fps: 0.0,
+            history: VecDeque::with_capacity(FRAME_HISTORY_LENGTH),
+        }
+    }
+}
+
+#[profiling::all_functions]
+impl FrameStats {
+    pub(crate) fn record(&mut self, frame_time: Duration) {
+        self.frame_time = frame_time;
+        self.fps = fps(frame_time);
+
+        if self.history.len() == FRAME_HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+        self.history.push_back(frame_time);
+    }
+
+    /// Times of the recent frames, from the oldest to the latest.
+    #[profiling::skip]
+    pub fn frame_times(&self) -> impl ExactSizeIterator<Item = Duration> + '_ {
+        self.history.iter().copied()
+    }
+
+    /// Average frame rate over the last `window` frames, or all the recorded ones if there are
+    /// fewer. This is steadier than [`FrameStats::fps`] for display.
+    pub fn average_fps(&self, window: usize) -> f32 {
+        let frame_count = window.min(self.history.len());
+        let total_time = self
+            .history
+            .iter()
+            .rev()
+            .take(frame_count)
+            .sum::<Duration>();
+        if total_time.is_zero() {
+            return 0.0;
+        }
+
+        frame_count as f32 / total_time.as_secs_f32()
+    }
+}
+
+fn fps(frame_time: Duration) -> f32 {
+    if frame_time.is_zero() {
+        0.0
+    } else {
+        1.0 / frame_time.as_secs_f32()
+    }
+}
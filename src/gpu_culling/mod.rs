@@ -0,0 +1,319 @@
+use ash::vk;
+use bytemuck::{bytes_of, cast_slice, Pod, Zeroable};
+use thiserror::Error;
+
+use crate::{
+    allocated_types::{AllocatedBuffer, BufferBuildError, BufferDataUploadError},
+    components::camera::Camera,
+    compute_shader::{ComputeShader, ComputeShaderBuildError},
+    descriptor_resources::DescriptorResources,
+    material::Vertex,
+    math_types::Vec4,
+    mesh::Mesh,
+    renderer::{supports_draw_indirect_count, Renderer},
+    utils::ThreadSafeRef,
+};
+
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Per-instance input of the culling pass (binding 0 of the culling shader, std430 layout).
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct CullingInstance {
+    /// World space bounding sphere of the instance: `xyz` is the center, `w` the radius.
+    pub bounding_sphere: Vec4,
+    pub index_count: u32,
+    pub first_index: u32,
+    pub vertex_offset: i32,
+    /// Written as the draw's `first_instance`, so the vertex shader can fetch per-instance data
+    /// (e.g. a model matrix from a storage buffer) using `gl_InstanceIndex`.
+    pub instance_data_index: u32,
+}
+unsafe impl Zeroable for CullingInstance {}
+unsafe impl Pod for CullingInstance {}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct CullingData {
+    frustum_planes: [Vec4; 6],
+    instance_count: u32,
+    _padding: [u32; 3],
+}
+unsafe impl Zeroable for CullingData {}
+unsafe impl Pod for CullingData {}
+
+/// GPU frustum culling producing a compacted draw list.
+///
+/// A compute pass tests every [`CullingInstance`] against the camera frustum, and appends a
+/// `vk::DrawIndexedIndirectCommand` for each visible one to the draw command buffer, incrementing
+/// the draw count buffer. Both buffers are then consumed by `cmd_draw_indexed_indirect_count`,
+/// see `systems::mesh_renderer::render_culled_meshes`.
+pub struct GpuCulling {
+    max_instance_count: u32,
+    instance_count: u32,
+
+    pub instance_buffer: ThreadSafeRef<AllocatedBuffer>,
+    pub draw_command_buffer: ThreadSafeRef<AllocatedBuffer>,
+    pub draw_count_buffer: ThreadSafeRef<AllocatedBuffer>,
+
+    compute_shader_ref: ThreadSafeRef<ComputeShader>,
+}
+
+#[derive(Error, Debug)]
+pub enum GpuCullingBuildError {
+    #[error("The selected device does not support the drawIndirectCount feature.")]
+    DrawIndirectCountUnsupported,
+
+    #[error("Creation of a culling buffer failed with error: {0}.")]
+    BufferCreationFailed(#[from] BufferBuildError),
+
+    #[error("Creation of the culling compute shader failed with error: {0}.")]
+    ComputeShaderCreationFailed(#[from] ComputeShaderBuildError),
+}
+
+#[derive(Error, Debug)]
+pub enum CullingInstancesUploadError {
+    #[error(
+        "Too many instances provided ({provided}), the culling buffers were created for {max}."
+    )]
+    TooManyInstances { provided: usize, max: u32 },
+
+    #[error("Upload of the instance data failed with error: {0}.")]
+    DataUploadFailed(#[from] BufferDataUploadError),
+}
+
+#[profiling::all_functions]
+impl GpuCulling {
+    pub fn new(
+        max_instance_count: u32,
+        renderer: &mut Renderer,
+    ) -> Result<Self, GpuCullingBuildError> {
+        if !supports_draw_indirect_count(&renderer.instance, renderer.physical_device) {
+            return Err(GpuCullingBuildError::DrawIndirectCountUnsupported);
+        }
+
+        let max_instances = u64::from(std::cmp::max(max_instance_count, 1));
+
+        let instance_buffer =
+            AllocatedBuffer::builder(max_instances * std::mem::size_of::<CullingInstance>() as u64)
+                .with_name("Culling instances")
+                .with_usage(vk::BufferUsageFlags::STORAGE_BUFFER)
+                .with_memory_location(gpu_allocator::MemoryLocation::CpuToGpu)
+                .build(renderer)?;
+        let draw_command_buffer = AllocatedBuffer::builder(
+            max_instances * std::mem::size_of::<vk::DrawIndexedIndirectCommand>() as u64,
+        )
+        .with_name("Culling draw commands")
+        .with_usage(vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::INDIRECT_BUFFER)
+        .with_memory_location(gpu_allocator::MemoryLocation::GpuOnly)
+        .build(renderer)?;
+        let draw_count_buffer = AllocatedBuffer::builder(std::mem::size_of::<u32>() as u64)
+            .with_name("Culling draw count")
+            .with_usage(
+                vk::BufferUsageFlags::STORAGE_BUFFER
+                    | vk::BufferUsageFlags::INDIRECT_BUFFER
+                    | vk::BufferUsageFlags::TRANSFER_DST,
+            )
+            .with_memory_location(gpu_allocator::MemoryLocation::GpuOnly)
+            .build(renderer)?;
+
+        let instance_buffer = ThreadSafeRef::new(instance_buffer);
+        let draw_command_buffer = ThreadSafeRef::new(draw_command_buffer);
+        let draw_count_buffer = ThreadSafeRef::new(draw_count_buffer);
+
+        let compute_shader_ref = ComputeShader::builder().build_from_spirv_u8(
+            include_bytes!("shaders/gen/culling.comp"),
+            DescriptorResources {
+                storage_buffers: [
+                    (0, instance_buffer.clone()),
+                    (1, draw_command_buffer.clone()),
+                    (2, draw_count_buffer.clone()),
+                ]
+                .into(),
+                ..Default::default()
+            },
+            renderer,
+        )?;
+
+        Ok(Self {
+            max_instance_count,
+            instance_count: 0,
+            instance_buffer,
+            draw_command_buffer,
+            draw_count_buffer,
+            compute_shader_ref,
+        })
+    }
+
+    #[profiling::skip]
+    pub fn max_instance_count(&self) -> u32 {
+        self.max_instance_count
+    }
+
+    #[profiling::skip]
+    pub fn instance_count(&self) -> u32 {
+        self.instance_count
+    }
+
+    pub fn upload_instances(
+        &mut self,
+        instances: &[CullingInstance],
+    ) -> Result<(), CullingInstancesUploadError> {
+        let instance_count = u32::try_from(instances.len())
+            .ok()
+            .filter(|&count| count <= self.max_instance_count)
+            .ok_or(CullingInstancesUploadError::TooManyInstances {
+                provided: instances.len(),
+                max: self.max_instance_count,
+            })?;
+
+        self.instance_buffer
+            .lock()
+            .upload_data(cast_slice(instances))?;
+        self.instance_count = instance_count;
+
+        Ok(())
+    }
+
+    /// Records the culling pass for the given camera in the current frame. This must be called
+    /// during a frame (e.g. in `on_update`), before the culled meshes are drawn: the current render
+    /// pass is interrupted while the pass is recorded, and resumed afterwards.
+    pub fn cull(&self, camera: &Camera, renderer: &mut Renderer) {
+        let culling_data = CullingData {
+            frustum_planes: camera.frustum_planes(),
+            instance_count: self.instance_count,
+            _padding: Default::default(),
+        };
+        let group_count = self.instance_count.div_ceil(WORKGROUP_SIZE);
+
+        let compute_shader = self.compute_shader_ref.lock();
+        let draw_count_handle = self.draw_count_buffer.lock().handle;
+        let draw_command_handle = self.draw_command_buffer.lock().handle;
+
+        renderer.record_outside_render_pass(|device, cmd_buffer| unsafe {
+            // The previous frame's draws may still be reading the buffers that are rewritten
+            let previous_draws_barriers = [
+                (draw_count_handle, vk::AccessFlags::TRANSFER_WRITE),
+                (draw_command_handle, vk::AccessFlags::SHADER_WRITE),
+            ]
+            .map(|(buffer, dst_access_mask)| {
+                vk::BufferMemoryBarrier::default()
+                    .src_access_mask(vk::AccessFlags::INDIRECT_COMMAND_READ)
+                    .dst_access_mask(dst_access_mask)
+                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .buffer(buffer)
+                    .size(vk::WHOLE_SIZE)
+            });
+            device.cmd_pipeline_barrier(
+                cmd_buffer,
+                vk::PipelineStageFlags::DRAW_INDIRECT,
+                vk::PipelineStageFlags::TRANSFER | vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &previous_draws_barriers,
+                &[],
+            );
+
+            device.cmd_fill_buffer(cmd_buffer, draw_count_handle, 0, vk::WHOLE_SIZE, 0);
+            let reset_barrier = vk::BufferMemoryBarrier::default()
+                .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                .dst_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .buffer(draw_count_handle)
+                .size(vk::WHOLE_SIZE);
+            device.cmd_pipeline_barrier(
+                cmd_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                std::slice::from_ref(&reset_barrier),
+                &[],
+            );
+
+            device.cmd_bind_pipeline(
+                cmd_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                compute_shader.pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                cmd_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                compute_shader.layout,
+                0,
+                &[compute_shader.descriptor_set],
+                &[],
+            );
+            device.cmd_push_constants(
+                cmd_buffer,
+                compute_shader.layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                bytes_of(&culling_data),
+            );
+            device.cmd_dispatch(cmd_buffer, group_count, 1, 1);
+
+            let output_barriers = [draw_command_handle, draw_count_handle].map(|buffer| {
+                vk::BufferMemoryBarrier::default()
+                    .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::INDIRECT_COMMAND_READ)
+                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .buffer(buffer)
+                    .size(vk::WHOLE_SIZE)
+            });
+            device.cmd_pipeline_barrier(
+                cmd_buffer,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::DRAW_INDIRECT,
+                vk::DependencyFlags::empty(),
+                &[],
+                &output_barriers,
+                &[],
+            );
+        });
+    }
+
+    /// Records the draw of every instance that survived the last culling pass. The pipeline and
+    /// descriptor sets must already be bound.
+    pub(crate) fn record_draw<VertexType>(
+        &self,
+        mesh: &Mesh<VertexType>,
+        device: &ash::Device,
+        cmd_buffer: vk::CommandBuffer,
+    ) where
+        VertexType: Vertex,
+    {
+        mesh.bind_buffers(device, cmd_buffer);
+
+        unsafe {
+            device.cmd_draw_indexed_indirect_count(
+                cmd_buffer,
+                self.draw_command_buffer.lock().handle,
+                0,
+                self.draw_count_buffer.lock().handle,
+                0,
+                self.max_instance_count,
+                std::mem::size_of::<vk::DrawIndexedIndirectCommand>()
+                    .try_into()
+                    .expect("Unsupported architecture"),
+            );
+        }
+    }
+
+    pub fn destroy(&mut self, renderer: &mut Renderer) {
+        self.compute_shader_ref.lock().destroy(renderer);
+
+        for buffer in [
+            &self.instance_buffer,
+            &self.draw_command_buffer,
+            &self.draw_count_buffer,
+        ] {
+            buffer
+                .lock()
+                .destroy(&renderer.device, &mut renderer.allocator());
+        }
+    }
+}
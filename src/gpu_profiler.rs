@@ -0,0 +1,345 @@
+use std::{
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+    time::Duration,
+};
+
+use ash::vk;
+use thiserror::Error;
+
+use crate::{
+    renderer::Renderer,
+    utils::{CommandUploader, ImmediateCommandError},
+};
+
+/// Maximum number of zones that can be recorded in a single frame.
+pub const MAX_GPU_ZONES_PER_FRAME: u32 = 256;
+
+/// Timing of a GPU zone, relative to the start of the frame's command buffer.
+#[derive(Debug, Clone, Copy)]
+pub struct GpuZone {
+    pub name: &'static str,
+    /// Nesting level of the zone, 0 being the whole frame.
+    pub depth: u32,
+    pub start: Duration,
+    pub end: Duration,
+}
+
+impl GpuZone {
+    pub fn duration(&self) -> Duration {
+        self.end.saturating_sub(self.start)
+    }
+}
+
+/// Zone opened by [`Renderer::gpu_scope`], closed when the guard is dropped. The guard
+/// dereferences to the renderer, so that the zone's commands can be recorded through it.
+pub struct GpuScope<'a> {
+    renderer: &'a mut Renderer,
+}
+
+impl<'a> GpuScope<'a> {
+    pub(crate) fn new(name: &'static str, renderer: &'a mut Renderer) -> Self {
+        renderer.begin_gpu_zone(name);
+        Self { renderer }
+    }
+}
+
+impl Deref for GpuScope<'_> {
+    type Target = Renderer;
+
+    fn deref(&self) -> &Self::Target {
+        self.renderer
+    }
+}
+
+impl DerefMut for GpuScope<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.renderer
+    }
+}
+
+impl Drop for GpuScope<'_> {
+    fn drop(&mut self) {
+        self.renderer.end_gpu_zone();
+    }
+}
+
+struct RecordedZone {
+    name: &'static str,
+    depth: u32,
+    start_query: u32,
+    end_query: Option<u32>,
+
+    #[cfg(feature = "profile-with-tracy")]
+    tracy_span: Option<profiling::tracy_client::GpuSpan>,
+}
+
+#[derive(Error, Debug)]
+pub enum GpuProfilerCreationError {
+    #[error("The selected device does not support timestamp queries on its graphics queue.")]
+    TimestampsUnsupported,
+
+    #[error("Vulkan creation of the timestamp query pool failed with result: {0}.")]
+    VulkanQueryPoolCreationFailed(vk::Result),
+
+    #[error("Calibration of the GPU clock failed with error: {0}.")]
+    CalibrationFailed(#[from] ImmediateCommandError),
+
+    #[error("Vulkan query results retrieval failed with result: {0}.")]
+    VulkanQueryResultsRetrievalFailed(vk::Result),
+}
+
+/// Measures the GPU time spent in named zones of the frame using timestamp queries.
+///
+/// Zones are opened and closed with [`crate::renderer::Renderer::begin_gpu_zone`] and
+/// [`crate::renderer::Renderer::end_gpu_zone`], and a "GPU frame" zone wraps every frame. Results
+/// are read back once the GPU is done with the frame (at the start of the next one), and are
+/// available through [`crate::renderer::Renderer::gpu_zones`].
+///
+/// When the `profile-with-tracy` feature is enabled and a Tracy client is running, every zone is
+/// also sent to a Tracy GPU context, so that GPU zones show up next to the CPU ones on the trace.
+pub struct GpuProfiler {
+    query_pool: vk::QueryPool,
+    /// Nanoseconds per timestamp tick.
+    timestamp_period: f32,
+    timestamp_mask: u64,
+
+    recorded_zones: Vec<RecordedZone>,
+    open_zones: Vec<usize>,
+    next_query: u32,
+
+    last_frame_zones: Vec<GpuZone>,
+
+    #[cfg(feature = "profile-with-tracy")]
+    tracy_context: Option<profiling::tracy_client::GpuContext>,
+}
+
+#[profiling::all_functions]
+impl GpuProfiler {
+    pub(crate) fn new(
+        device: &ash::Device,
+        queue: vk::Queue,
+        device_properties: &vk::PhysicalDeviceProperties,
+        timestamp_valid_bits: u32,
+        command_uploader: &mut CommandUploader,
+    ) -> Result<Self, GpuProfilerCreationError> {
+        if device_properties.limits.timestamp_compute_and_graphics != vk::TRUE
+            || timestamp_valid_bits == 0
+        {
+            return Err(GpuProfilerCreationError::TimestampsUnsupported);
+        }
+
+        let query_pool_info = vk::QueryPoolCreateInfo::default()
+            .query_type(vk::QueryType::TIMESTAMP)
+            .query_count(2 * MAX_GPU_ZONES_PER_FRAME);
+        let query_pool = unsafe { device.create_query_pool(&query_pool_info, None) }
+            .map_err(GpuProfilerCreationError::VulkanQueryPoolCreationFailed)?;
+
+        let timestamp_mask = if timestamp_valid_bits >= 64 {
+            u64::MAX
+        } else {
+            (1 << timestamp_valid_bits) - 1
+        };
+
+        // Read a first timestamp, used to align the GPU clock with the CPU one
+        command_uploader.immediate_command(device, queue, |cmd_buffer| unsafe {
+            device.cmd_reset_query_pool(*cmd_buffer, query_pool, 0, 1);
+            device.cmd_write_timestamp(
+                *cmd_buffer,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                query_pool,
+                0,
+            );
+        })?;
+        let mut calibration_timestamp = [0_u64];
+        unsafe {
+            device.get_query_pool_results(
+                query_pool,
+                0,
+                &mut calibration_timestamp,
+                vk::QueryResultFlags::TYPE_64 | vk::QueryResultFlags::WAIT,
+            )
+        }
+        .map_err(GpuProfilerCreationError::VulkanQueryResultsRetrievalFailed)?;
+
+        #[cfg(feature = "profile-with-tracy")]
+        let tracy_context = profiling::tracy_client::Client::running().and_then(|client| {
+            client
+                .new_gpu_context(
+                    Some("Morrigu graphics queue"),
+                    profiling::tracy_client::GpuContextType::Vulkan,
+                    (calibration_timestamp[0] & timestamp_mask) as i64,
+                    device_properties.limits.timestamp_period,
+                )
+                .map_err(|error| log::warn!("Failed to create the Tracy GPU context: {error}"))
+                .ok()
+        });
+        #[cfg(not(feature = "profile-with-tracy"))]
+        let _ = calibration_timestamp;
+
+        Ok(Self {
+            query_pool,
+            timestamp_period: device_properties.limits.timestamp_period,
+            timestamp_mask,
+            recorded_zones: vec![],
+            open_zones: vec![],
+            next_query: 0,
+            last_frame_zones: vec![],
+            #[cfg(feature = "profile-with-tracy")]
+            tracy_context,
+        })
+    }
+
+    #[profiling::skip]
+    pub fn last_frame_zones(&self) -> &[GpuZone] {
+        &self.last_frame_zones
+    }
+
+    /// Milliseconds spent in every zone of the last frame, zones sharing a name being summed.
+    pub fn last_frame_timings(&self) -> HashMap<String, f64> {
+        let mut timings = HashMap::new();
+        for zone in &self.last_frame_zones {
+            *timings.entry(zone.name.to_owned()).or_default() +=
+                zone.duration().as_secs_f64() * 1000.0;
+        }
+
+        timings
+    }
+
+    /// Reads back the zones of the previous frame and resets the queries. The previous frame must
+    /// be done executing, and the command buffer must be outside of any render pass.
+    pub(crate) fn begin_frame(&mut self, device: &ash::Device, cmd_buffer: vk::CommandBuffer) {
+        self.collect_results(device);
+
+        unsafe {
+            device.cmd_reset_query_pool(cmd_buffer, self.query_pool, 0, 2 * MAX_GPU_ZONES_PER_FRAME)
+        };
+        self.recorded_zones.clear();
+        self.open_zones.clear();
+        self.next_query = 0;
+    }
+
+    pub(crate) fn begin_zone(
+        &mut self,
+        name: &'static str,
+        device: &ash::Device,
+        cmd_buffer: vk::CommandBuffer,
+    ) {
+        if self.recorded_zones.len() >= MAX_GPU_ZONES_PER_FRAME as usize {
+            log::warn!("Too many GPU zones recorded this frame, ignoring zone \"{name}\"");
+            // Still keep track of the zone so that the matching end call is ignored as well
+            self.open_zones.push(usize::MAX);
+            return;
+        }
+
+        let start_query = self.next_query;
+        self.next_query += 1;
+        unsafe {
+            device.cmd_write_timestamp(
+                cmd_buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                self.query_pool,
+                start_query,
+            )
+        };
+
+        self.open_zones.push(self.recorded_zones.len());
+        self.recorded_zones.push(RecordedZone {
+            name,
+            depth: (self.open_zones.len() - 1).try_into().unwrap(),
+            start_query,
+            end_query: None,
+            #[cfg(feature = "profile-with-tracy")]
+            tracy_span: self.tracy_context.as_ref().and_then(|context| {
+                context
+                    .span_alloc(name, "", file!(), line!())
+                    .map_err(|error| log::warn!("Failed to create Tracy GPU span: {error}"))
+                    .ok()
+            }),
+        });
+    }
+
+    pub(crate) fn end_zone(&mut self, device: &ash::Device, cmd_buffer: vk::CommandBuffer) {
+        let Some(zone_index) = self.open_zones.pop() else {
+            log::warn!("Attempted to end a GPU zone, but none is open");
+            return;
+        };
+        let Some(zone) = self.recorded_zones.get_mut(zone_index) else {
+            return;
+        };
+
+        let end_query = self.next_query;
+        self.next_query += 1;
+        unsafe {
+            device.cmd_write_timestamp(
+                cmd_buffer,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                self.query_pool,
+                end_query,
+            )
+        };
+        zone.end_query = Some(end_query);
+
+        #[cfg(feature = "profile-with-tracy")]
+        if let Some(tracy_span) = zone.tracy_span.as_mut() {
+            tracy_span.end_zone();
+        }
+    }
+
+    fn collect_results(&mut self, device: &ash::Device) {
+        if self.next_query == 0 {
+            return;
+        }
+
+        let mut timestamps = vec![0_u64; self.next_query.try_into().unwrap()];
+        if let Err(error) = unsafe {
+            device.get_query_pool_results(
+                self.query_pool,
+                0,
+                &mut timestamps,
+                vk::QueryResultFlags::TYPE_64 | vk::QueryResultFlags::WAIT,
+            )
+        } {
+            log::warn!("Failed to retrieve GPU timestamps: {error}");
+            return;
+        }
+        for timestamp in &mut timestamps {
+            *timestamp &= self.timestamp_mask;
+        }
+
+        let frame_start = timestamps[0];
+        let to_duration = |timestamp: u64| {
+            Duration::from_nanos(
+                (timestamp.saturating_sub(frame_start) as f64 * f64::from(self.timestamp_period))
+                    as u64,
+            )
+        };
+
+        self.last_frame_zones.clear();
+        for zone in self.recorded_zones.drain(..) {
+            let Some(end_query) = zone.end_query else {
+                log::warn!("GPU zone \"{}\" was never ended", zone.name);
+                continue;
+            };
+            let start_timestamp = timestamps[usize::try_from(zone.start_query).unwrap()];
+            let end_timestamp = timestamps[usize::try_from(end_query).unwrap()];
+
+            #[cfg(feature = "profile-with-tracy")]
+            if let Some(tracy_span) = zone.tracy_span {
+                tracy_span.upload_timestamp_start(start_timestamp as i64);
+                tracy_span.upload_timestamp_end(end_timestamp as i64);
+            }
+
+            self.last_frame_zones.push(GpuZone {
+                name: zone.name,
+                depth: zone.depth,
+                start: to_duration(start_timestamp),
+                end: to_duration(end_timestamp),
+            });
+        }
+    }
+
+    pub(crate) fn destroy(&mut self, device: &ash::Device) {
+        unsafe { device.destroy_query_pool(self.query_pool, None) };
+    }
+}
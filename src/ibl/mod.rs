@@ -0,0 +1,627 @@
+use ash::vk;
+use bytemuck::bytes_of;
+use thiserror::Error;
+
+use crate::{
+    allocated_types::{AllocatedImage, ImageBuildError},
+    compute_shader::{ComputeShader, ComputeShaderBuildError},
+    cubemap::Cubemap,
+    descriptor_resources::{DescriptorResources, ResourceBindingError},
+    renderer::Renderer,
+    resource_registry::{self, ResourceKind},
+    texture::{SamplerSettings, Texture},
+    utils::{ImmediateCommandError, ThreadSafeRef},
+};
+
+/// Face size of the cubemaps created by [`Cubemap::compute_irradiance`]. Irradiance varies
+/// slowly with the normal, so a small cubemap sampled with linear filtering is enough.
+pub const IRRADIANCE_FACE_SIZE: u32 = 32;
+/// Format of the cubemaps and textures generated by this module.
+pub const IBL_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+const WORKGROUP_SIZE: u32 = 16;
+
+#[derive(Error, Debug)]
+pub enum IblGenerationError {
+    #[error("Creation of the generated image failed with error: {0}.")]
+    ImageCreationFailed(#[from] ImageBuildError),
+
+    #[error("Creation of the generation compute shader failed with error: {0}.")]
+    ComputeShaderCreationFailed(#[from] ComputeShaderBuildError),
+
+    #[error("Binding of the next mip level's image failed with error: {0}.")]
+    ResourceBindingFailed(#[from] ResourceBindingError),
+
+    #[error("Submission of the generation pass failed with error: {0}.")]
+    ComputeFailed(#[from] ImmediateCommandError),
+
+    #[error("Vulkan creation of the generated image's sampler failed with result: {0}.")]
+    VulkanSamplerCreationFailed(vk::Result),
+}
+
+#[profiling::all_functions]
+impl Cubemap {
+    /// Convolves the cubemap with a cosine lobe into a [`IRRADIANCE_FACE_SIZE`] cubemap, whose
+    /// texel in a direction `n` is the irradiance received by a surface of normal `n`. It is
+    /// usually sampled with the normal for the diffuse part of image based lighting, the result
+    /// still has to be multiplied by the albedo and divided by PI.
+    ///
+    /// This cubemap must be in `SHADER_READ_ONLY_OPTIMAL` layout, and is only sampled through its
+    /// first mip level.
+    pub fn compute_irradiance(
+        &self,
+        renderer: &mut Renderer,
+    ) -> Result<ThreadSafeRef<Cubemap>, IblGenerationError> {
+        let irradiance_image = AllocatedImage::builder(vk::Extent3D {
+            width: IRRADIANCE_FACE_SIZE,
+            height: IRRADIANCE_FACE_SIZE,
+            depth: 1,
+        })
+        // The usage must be set first, as uninitialized images only use the create info's one
+        .with_usage(vk::ImageUsageFlags::STORAGE)
+        .cubemap_default(IBL_FORMAT)
+        .build_uninitialized(&renderer.device, &mut renderer.allocator())?;
+        let irradiance_image_ref = ThreadSafeRef::new(irradiance_image);
+
+        let result = self
+            .convolve_irradiance(&irradiance_image_ref, renderer)
+            .and_then(|()| create_ibl_sampler(1, renderer));
+        let sampler = match result {
+            Ok(sampler) => sampler,
+            Err(error) => {
+                irradiance_image_ref.lock().destroy(renderer);
+                return Err(error);
+            }
+        };
+
+        let name = format!("{} (irradiance)", self.debug_name());
+        {
+            let irradiance_image = irradiance_image_ref.lock();
+            renderer.auto_name(irradiance_image.handle, &name);
+            renderer.auto_name(irradiance_image.view, &format!("{name} (view)"));
+        }
+        renderer.auto_name(sampler, &format!("{name} (sampler)"));
+
+        Ok(ThreadSafeRef::new(Cubemap {
+            image_ref: irradiance_image_ref,
+            sampler,
+            path: None,
+        }))
+    }
+
+    /// Prefilters the cubemap for the specular part of image based lighting, following the split
+    /// sum approximation: every mip level of the returned cubemap is the environment convolved
+    /// with the GGX distribution of a roughness going linearly from 0 at the first level to 1 at
+    /// the last one. It is usually sampled with the reflected view direction, at the LOD
+    /// `roughness * (mip_levels - 1)`, and combined with [`Texture::brdf_lut`].
+    ///
+    /// The first level has the size of this cubemap, and every following one half of the
+    /// previous one. `mip_levels` is clamped between 1 and the number of levels down to 1x1
+    /// faces. This cubemap must be in `SHADER_READ_ONLY_OPTIMAL` layout, and is only sampled
+    /// through its first mip level: very bright and small light sources can show up as sparkles
+    /// on the rough levels.
+    pub fn compute_prefiltered(
+        &self,
+        mip_levels: u32,
+        renderer: &mut Renderer,
+    ) -> Result<ThreadSafeRef<Cubemap>, IblGenerationError> {
+        let face_size = self.image_ref.lock().extent.width;
+        let mip_levels = mip_levels.clamp(1, face_size.ilog2() + 1);
+
+        let mut builder = AllocatedImage::builder(vk::Extent3D {
+            width: face_size,
+            height: face_size,
+            depth: 1,
+        })
+        // The usage must be set first, as uninitialized images only use the create info's one
+        .with_usage(vk::ImageUsageFlags::TRANSFER_DST)
+        .cubemap_default(IBL_FORMAT);
+        builder.image_create_info = builder.image_create_info.mip_levels(mip_levels);
+        builder.image_view_create_info.subresource_range.level_count = mip_levels;
+        let prefiltered_image =
+            builder.build_uninitialized(&renderer.device, &mut renderer.allocator())?;
+        let prefiltered_image_ref = ThreadSafeRef::new(prefiltered_image);
+
+        let result = self
+            .prefilter(&prefiltered_image_ref, mip_levels, renderer)
+            .and_then(|()| create_ibl_sampler(mip_levels, renderer));
+        let sampler = match result {
+            Ok(sampler) => sampler,
+            Err(error) => {
+                prefiltered_image_ref.lock().destroy(renderer);
+                return Err(error);
+            }
+        };
+
+        let name = format!("{} (prefiltered)", self.debug_name());
+        {
+            let prefiltered_image = prefiltered_image_ref.lock();
+            renderer.auto_name(prefiltered_image.handle, &name);
+            renderer.auto_name(prefiltered_image.view, &format!("{name} (view)"));
+        }
+        renderer.auto_name(sampler, &format!("{name} (sampler)"));
+
+        Ok(ThreadSafeRef::new(Cubemap {
+            image_ref: prefiltered_image_ref,
+            sampler,
+            path: None,
+        }))
+    }
+
+    /// Writes every face of `irradiance_image_ref`, and leaves it in `SHADER_READ_ONLY_OPTIMAL`
+    /// layout.
+    fn convolve_irradiance(
+        &self,
+        irradiance_image_ref: &ThreadSafeRef<AllocatedImage>,
+        renderer: &mut Renderer,
+    ) -> Result<(), IblGenerationError> {
+        let compute_shader_ref = ComputeShader::builder().build_from_spirv_u8(
+            include_bytes!("shaders/gen/irradiance.comp"),
+            DescriptorResources {
+                cubemap_images: [(0, self.shared_ref())].into(),
+                storage_images: [(1, irradiance_image_ref.clone())].into(),
+                ..Default::default()
+            },
+            renderer,
+        )?;
+
+        let compute_shader = compute_shader_ref.lock();
+        let mut irradiance_image = irradiance_image_ref.lock();
+        let group_count = IRRADIANCE_FACE_SIZE.div_ceil(WORKGROUP_SIZE);
+
+        let dispatch_result = renderer.immediate_command(|cmd_buffer| unsafe {
+            let device = &renderer.device;
+
+            record_dispatch(
+                device,
+                *cmd_buffer,
+                &compute_shader,
+                &irradiance_image,
+                (group_count, group_count, 6),
+                &[],
+            );
+            transition_from_general(
+                device,
+                *cmd_buffer,
+                &irradiance_image,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                vk::AccessFlags::SHADER_READ,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+            );
+        });
+        if dispatch_result.is_ok() {
+            irradiance_image.layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+        }
+        drop(irradiance_image);
+        drop(compute_shader);
+        compute_shader_ref.lock().destroy(renderer);
+
+        Ok(dispatch_result?)
+    }
+
+    /// Writes every mip level of `prefiltered_image_ref`, and leaves it in
+    /// `SHADER_READ_ONLY_OPTIMAL` layout.
+    ///
+    /// Storage views can only cover a single mip level, so every level is computed in a
+    /// temporary cubemap of its size which is then copied into the final one.
+    fn prefilter(
+        &self,
+        prefiltered_image_ref: &ThreadSafeRef<AllocatedImage>,
+        mip_levels: u32,
+        renderer: &mut Renderer,
+    ) -> Result<(), IblGenerationError> {
+        let face_size = prefiltered_image_ref.lock().extent.width;
+        let mut compute_shader_ref: Option<ThreadSafeRef<ComputeShader>> = None;
+
+        let mut result = Ok(());
+        for mip_level in 0..mip_levels {
+            let mip_size = (face_size >> mip_level).max(1);
+            // Same roughness distribution as the one expected when sampling the result
+            let roughness = if mip_levels > 1 {
+                mip_level as f32 / (mip_levels - 1) as f32
+            } else {
+                0.0
+            };
+
+            result = self.prefilter_level(
+                &mut compute_shader_ref,
+                prefiltered_image_ref,
+                mip_level,
+                mip_size,
+                roughness,
+                renderer,
+            );
+            if result.is_err() {
+                break;
+            }
+        }
+
+        if let Some(compute_shader_ref) = compute_shader_ref {
+            compute_shader_ref.lock().destroy(renderer);
+        }
+        if result.is_ok() {
+            prefiltered_image_ref.lock().layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+        }
+
+        result
+    }
+
+    /// Computes a single mip level of [`Self::prefilter`], building the compute shader on the
+    /// first call.
+    fn prefilter_level(
+        &self,
+        compute_shader_ref: &mut Option<ThreadSafeRef<ComputeShader>>,
+        prefiltered_image_ref: &ThreadSafeRef<AllocatedImage>,
+        mip_level: u32,
+        mip_size: u32,
+        roughness: f32,
+        renderer: &mut Renderer,
+    ) -> Result<(), IblGenerationError> {
+        let level_image = AllocatedImage::builder(vk::Extent3D {
+            width: mip_size,
+            height: mip_size,
+            depth: 1,
+        })
+        .with_usage(vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_SRC)
+        .cubemap_default(IBL_FORMAT)
+        .build_uninitialized(&renderer.device, &mut renderer.allocator())?;
+        let level_image_ref = ThreadSafeRef::new(level_image);
+
+        let binding_result = match compute_shader_ref {
+            Some(compute_shader_ref) => compute_shader_ref
+                .lock()
+                .bind_storage_image::<u8>(1, level_image_ref.clone(), renderer)
+                .map(|_| compute_shader_ref.clone())
+                .map_err(IblGenerationError::from),
+            None => ComputeShader::builder()
+                .build_from_spirv_u8(
+                    include_bytes!("shaders/gen/prefilter.comp"),
+                    DescriptorResources {
+                        cubemap_images: [(0, self.shared_ref())].into(),
+                        storage_images: [(1, level_image_ref.clone())].into(),
+                        ..Default::default()
+                    },
+                    renderer,
+                )
+                .map_err(IblGenerationError::from),
+        };
+        let shader_ref = match binding_result {
+            Ok(shader_ref) => compute_shader_ref.insert(shader_ref),
+            Err(error) => {
+                level_image_ref.lock().destroy(renderer);
+                return Err(error);
+            }
+        };
+
+        let compute_shader = shader_ref.lock();
+        let level_image = level_image_ref.lock();
+        let prefiltered_image = prefiltered_image_ref.lock();
+        let group_count = mip_size.div_ceil(WORKGROUP_SIZE);
+
+        let dispatch_result = renderer.immediate_command(|cmd_buffer| unsafe {
+            let device = &renderer.device;
+
+            record_dispatch(
+                device,
+                *cmd_buffer,
+                &compute_shader,
+                &level_image,
+                (group_count, group_count, 6),
+                bytes_of(&roughness),
+            );
+            transition_from_general(
+                device,
+                *cmd_buffer,
+                &level_image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                vk::AccessFlags::TRANSFER_READ,
+                vk::PipelineStageFlags::TRANSFER,
+            );
+
+            let mip_range = vk::ImageSubresourceRange::default()
+                .aspect_mask(vk::ImageAspectFlags::COLOR)
+                .base_mip_level(mip_level)
+                .level_count(1)
+                .layer_count(6);
+            let transfer_dst_barrier = vk::ImageMemoryBarrier::default()
+                .src_access_mask(vk::AccessFlags::NONE)
+                .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                .old_layout(vk::ImageLayout::UNDEFINED)
+                .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(prefiltered_image.handle)
+                .subresource_range(mip_range);
+            device.cmd_pipeline_barrier(
+                *cmd_buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                std::slice::from_ref(&transfer_dst_barrier),
+            );
+
+            let copy_region = vk::ImageCopy::default()
+                .src_subresource(vk::ImageSubresourceLayers {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    mip_level: 0,
+                    base_array_layer: 0,
+                    layer_count: 6,
+                })
+                .dst_subresource(vk::ImageSubresourceLayers {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    mip_level,
+                    base_array_layer: 0,
+                    layer_count: 6,
+                })
+                .extent(vk::Extent3D {
+                    width: mip_size,
+                    height: mip_size,
+                    depth: 1,
+                });
+            device.cmd_copy_image(
+                *cmd_buffer,
+                level_image.handle,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                prefiltered_image.handle,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                std::slice::from_ref(&copy_region),
+            );
+
+            let shader_read_barrier = vk::ImageMemoryBarrier::default()
+                .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                .dst_access_mask(vk::AccessFlags::SHADER_READ)
+                .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(prefiltered_image.handle)
+                .subresource_range(mip_range);
+            device.cmd_pipeline_barrier(
+                *cmd_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                std::slice::from_ref(&shader_read_barrier),
+            );
+        });
+        drop(prefiltered_image);
+        drop(level_image);
+        drop(compute_shader);
+        // The submission is done, and the next level binds its own image before dispatching
+        level_image_ref.lock().destroy(renderer);
+
+        Ok(dispatch_result?)
+    }
+
+    /// Shares this cubemap's image and sampler to bind them to a compute shader, the returned
+    /// cubemap must not be destroyed.
+    fn shared_ref(&self) -> ThreadSafeRef<Cubemap> {
+        ThreadSafeRef::new(Cubemap {
+            image_ref: self.image_ref.clone(),
+            sampler: self.sampler,
+            path: None,
+        })
+    }
+
+    #[profiling::skip]
+    fn debug_name(&self) -> &str {
+        self.path.as_deref().unwrap_or("Cubemap")
+    }
+}
+
+#[profiling::all_functions]
+impl Texture {
+    /// Generates the `size`x`size` lookup table of the split sum approximation's BRDF, to use
+    /// with [`Cubemap::compute_prefiltered`]. It is sampled with `(dot(N, V), roughness)`, and
+    /// its red and green channels are the scale and bias to apply to the specular color F0:
+    /// `specular = prefiltered * (F0 * lut.r + lut.g)`.
+    ///
+    /// The table only depends on the BRDF, so it can be generated once and shared by every
+    /// environment. 512 is a common size. Its format is [`IBL_FORMAT`], as two channels formats
+    /// aren't guaranteed to support storage.
+    pub fn brdf_lut(
+        size: u32,
+        renderer: &mut Renderer,
+    ) -> Result<ThreadSafeRef<Texture>, IblGenerationError> {
+        let lut_image = AllocatedImage::builder(vk::Extent3D {
+            width: size,
+            height: size,
+            depth: 1,
+        })
+        // The usage must be set first, as uninitialized images only use the create info's one
+        .with_usage(vk::ImageUsageFlags::SAMPLED)
+        .storage_image_default(IBL_FORMAT)
+        .build_uninitialized(&renderer.device, &mut renderer.allocator())?;
+        let lut_image_ref = ThreadSafeRef::new(lut_image);
+
+        let sampler_settings = SamplerSettings {
+            filter: vk::Filter::LINEAR,
+            mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+            address_mode: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            anisotropy: None,
+        };
+        let result = integrate_brdf(&lut_image_ref, size, renderer).and_then(|()| {
+            Texture::from_image_ref(lut_image_ref.clone(), sampler_settings, renderer)
+                .map_err(IblGenerationError::VulkanSamplerCreationFailed)
+        });
+        let texture = match result {
+            Ok(texture) => texture,
+            Err(error) => {
+                lut_image_ref.lock().destroy(renderer);
+                return Err(error);
+            }
+        };
+
+        {
+            let lut_image = lut_image_ref.lock();
+            renderer.auto_name(lut_image.handle, "BRDF LUT");
+            renderer.auto_name(lut_image.view, "BRDF LUT (view)");
+        }
+
+        Ok(ThreadSafeRef::new(texture))
+    }
+}
+
+/// Writes the whole BRDF lookup table, and leaves it in `SHADER_READ_ONLY_OPTIMAL` layout.
+fn integrate_brdf(
+    lut_image_ref: &ThreadSafeRef<AllocatedImage>,
+    size: u32,
+    renderer: &mut Renderer,
+) -> Result<(), IblGenerationError> {
+    let compute_shader_ref = ComputeShader::builder().build_from_spirv_u8(
+        include_bytes!("shaders/gen/brdf_lut.comp"),
+        DescriptorResources {
+            storage_images: [(0, lut_image_ref.clone())].into(),
+            ..Default::default()
+        },
+        renderer,
+    )?;
+
+    let compute_shader = compute_shader_ref.lock();
+    let mut lut_image = lut_image_ref.lock();
+    let group_count = size.div_ceil(WORKGROUP_SIZE);
+
+    let dispatch_result = renderer.immediate_command(|cmd_buffer| unsafe {
+        let device = &renderer.device;
+
+        record_dispatch(
+            device,
+            *cmd_buffer,
+            &compute_shader,
+            &lut_image,
+            (group_count, group_count, 1),
+            &[],
+        );
+        transition_from_general(
+            device,
+            *cmd_buffer,
+            &lut_image,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::AccessFlags::SHADER_READ,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+        );
+    });
+    if dispatch_result.is_ok() {
+        lut_image.layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+    }
+    drop(lut_image);
+    drop(compute_shader);
+    compute_shader_ref.lock().destroy(renderer);
+
+    Ok(dispatch_result?)
+}
+
+/// Records the transition of every layer of `image` (first mip level only) from `UNDEFINED` to
+/// `GENERAL`, then the dispatch of `compute_shader`.
+unsafe fn record_dispatch(
+    device: &ash::Device,
+    cmd_buffer: vk::CommandBuffer,
+    compute_shader: &ComputeShader,
+    image: &AllocatedImage,
+    group_count: (u32, u32, u32),
+    push_constants: &[u8],
+) {
+    let to_general_barrier = vk::ImageMemoryBarrier::default()
+        .src_access_mask(vk::AccessFlags::NONE)
+        .dst_access_mask(vk::AccessFlags::SHADER_WRITE)
+        .old_layout(vk::ImageLayout::UNDEFINED)
+        .new_layout(vk::ImageLayout::GENERAL)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(image.handle)
+        .subresource_range(first_level_range(image));
+    device.cmd_pipeline_barrier(
+        cmd_buffer,
+        vk::PipelineStageFlags::TOP_OF_PIPE,
+        vk::PipelineStageFlags::COMPUTE_SHADER,
+        vk::DependencyFlags::empty(),
+        &[],
+        &[],
+        std::slice::from_ref(&to_general_barrier),
+    );
+
+    device.cmd_bind_pipeline(
+        cmd_buffer,
+        vk::PipelineBindPoint::COMPUTE,
+        compute_shader.pipeline,
+    );
+    device.cmd_bind_descriptor_sets(
+        cmd_buffer,
+        vk::PipelineBindPoint::COMPUTE,
+        compute_shader.layout,
+        0,
+        &[compute_shader.descriptor_set],
+        &[],
+    );
+    if !push_constants.is_empty() {
+        device.cmd_push_constants(
+            cmd_buffer,
+            compute_shader.layout,
+            vk::ShaderStageFlags::COMPUTE,
+            0,
+            push_constants,
+        );
+    }
+    device.cmd_dispatch(cmd_buffer, group_count.0, group_count.1, group_count.2);
+}
+
+/// Records the transition of the image written by [`record_dispatch`] to `new_layout`, for
+/// accesses of `dst_access_mask` in `dst_stage`.
+unsafe fn transition_from_general(
+    device: &ash::Device,
+    cmd_buffer: vk::CommandBuffer,
+    image: &AllocatedImage,
+    new_layout: vk::ImageLayout,
+    dst_access_mask: vk::AccessFlags,
+    dst_stage: vk::PipelineStageFlags,
+) {
+    let barrier = vk::ImageMemoryBarrier::default()
+        .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+        .dst_access_mask(dst_access_mask)
+        .old_layout(vk::ImageLayout::GENERAL)
+        .new_layout(new_layout)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(image.handle)
+        .subresource_range(first_level_range(image));
+    device.cmd_pipeline_barrier(
+        cmd_buffer,
+        vk::PipelineStageFlags::COMPUTE_SHADER,
+        dst_stage,
+        vk::DependencyFlags::empty(),
+        &[],
+        &[],
+        std::slice::from_ref(&barrier),
+    );
+}
+
+fn first_level_range(image: &AllocatedImage) -> vk::ImageSubresourceRange {
+    vk::ImageSubresourceRange::default()
+        .aspect_mask(vk::ImageAspectFlags::COLOR)
+        .level_count(1)
+        .layer_count(image.layer_count)
+}
+
+/// Trilinear sampler clamping to the edges, covering `mip_levels` levels.
+fn create_ibl_sampler(
+    mip_levels: u32,
+    renderer: &mut Renderer,
+) -> Result<vk::Sampler, IblGenerationError> {
+    let sampler_info = vk::SamplerCreateInfo::default()
+        .mag_filter(vk::Filter::LINEAR)
+        .min_filter(vk::Filter::LINEAR)
+        .mipmap_mode(vk::SamplerMipmapMode::LINEAR)
+        .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+        .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+        .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+        .min_lod(0.0)
+        .max_lod((mip_levels - 1) as f32);
+    let sampler = unsafe { renderer.device.create_sampler(&sampler_info, None) }
+        .map_err(IblGenerationError::VulkanSamplerCreationFailed)?;
+    resource_registry::register(ResourceKind::Sampler, 1);
+
+    Ok(sampler)
+}
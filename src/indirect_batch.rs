@@ -0,0 +1,246 @@
+use ash::vk;
+use thiserror::Error;
+
+use crate::{
+    allocated_types::{AllocatedBuffer, BufferBuildError, BufferDataUploadError},
+    material::Vertex,
+    mesh::Mesh,
+    renderer::Renderer,
+};
+
+const COMMAND_STRIDE: u32 = std::mem::size_of::<vk::DrawIndexedIndirectCommand>() as u32;
+
+#[derive(Error, Debug)]
+pub enum IndirectBatchUploadError {
+    #[error("Creation of the indirect command buffer failed with error: {0}.")]
+    BufferCreationFailed(#[from] BufferBuildError),
+
+    #[error("Upload of the indirect commands failed with error: {0}.")]
+    DataUploadFailed(#[from] BufferDataUploadError),
+}
+
+struct CommandBuffer {
+    buffer: AllocatedBuffer,
+    /// [`IndirectBatch::generation`] of the commands the buffer holds.
+    generation: u64,
+}
+
+/// Draws of a single mesh recorded as one indirect multi-draw (`cmd_draw_indexed_indirect`),
+/// see `systems::mesh_renderer::render_indirect`.
+///
+/// The batch is added to an entity next to a [`crate::components::mesh_rendering::MeshRendering`],
+/// whose mesh, material and descriptor sets are bound once for all the draws. Every draw is a
+/// `vk::DrawIndexedIndirectCommand`, whose `first_instance` is usually used by the vertex shader
+/// to fetch per-instance data (e.g. a model matrix from a storage buffer bound to the mesh
+/// rendering) with `gl_InstanceIndex`, which requires the `draw_indirect_first_instance`
+/// capability.
+///
+/// The commands are copied to a buffer of the frame only when they changed since that buffer was
+/// last written, so batches whose visible instances are stable don't cost any upload.
+pub struct IndirectBatch {
+    commands: Vec<vk::DrawIndexedIndirectCommand>,
+    /// Instances the commands were built from by [`IndirectBatch::set_visible_instances`].
+    visible_instances: Vec<u32>,
+    /// Incremented every time the commands change.
+    generation: u64,
+    /// One buffer per frame in flight, created on the first frame that draws the batch.
+    command_buffers: Vec<Option<CommandBuffer>>,
+    current_frame: usize,
+}
+
+#[profiling::all_functions]
+impl IndirectBatch {
+    pub fn new() -> Self {
+        Self {
+            commands: vec![],
+            visible_instances: vec![],
+            generation: 0,
+            command_buffers: vec![],
+            current_frame: 0,
+        }
+    }
+
+    #[profiling::skip]
+    pub fn commands(&self) -> &[vk::DrawIndexedIndirectCommand] {
+        &self.commands
+    }
+
+    /// Replaces the draws of the batch, the indices they reference are the ones of the mesh
+    /// rendering's mesh.
+    pub fn set_commands(&mut self, commands: Vec<vk::DrawIndexedIndirectCommand>) {
+        self.commands = commands;
+        self.visible_instances.clear();
+        self.generation += 1;
+    }
+
+    /// Rebuilds the draws so that the whole `mesh` is drawn once for each of `instances` (e.g. the
+    /// indices of the visible entities in an instance data buffer), consecutive instances sharing
+    /// a single draw. Nothing is rebuilt if the instances are the same as the last call's, and
+    /// whether they changed is returned.
+    ///
+    /// The mesh must be indexed, non-indexed meshes are not drawn.
+    pub fn set_visible_instances<VertexType>(
+        &mut self,
+        instances: &[u32],
+        mesh: &Mesh<VertexType>,
+    ) -> bool
+    where
+        VertexType: Vertex,
+    {
+        if self.generation != 0 && self.visible_instances == instances {
+            return false;
+        }
+
+        let index_count = mesh.indices.as_ref().map_or(0, |indices| {
+            indices.len().try_into().expect("Unsupported architecture")
+        });
+        let mut commands = Vec::<vk::DrawIndexedIndirectCommand>::new();
+        for &instance in instances {
+            match commands.last_mut() {
+                Some(command)
+                    if command.first_instance.checked_add(command.instance_count)
+                        == Some(instance) =>
+                {
+                    command.instance_count += 1;
+                }
+                _ => commands.push(vk::DrawIndexedIndirectCommand {
+                    index_count,
+                    instance_count: 1,
+                    first_index: 0,
+                    vertex_offset: 0,
+                    first_instance: instance,
+                }),
+            }
+        }
+        if index_count == 0 {
+            commands.clear();
+        }
+
+        self.commands = commands;
+        self.visible_instances = instances.to_vec();
+        self.generation += 1;
+
+        true
+    }
+
+    /// Makes sure the buffer of the current frame holds the latest commands. The frame that last
+    /// used that buffer is done executing when the current frame is recorded, so it can be
+    /// written right away.
+    pub(crate) fn prepare(
+        &mut self,
+        renderer: &mut Renderer,
+    ) -> Result<(), IndirectBatchUploadError> {
+        self.command_buffers
+            .resize_with(renderer.frames_in_flight(), || None);
+        self.current_frame = renderer.current_frame_index();
+        if self.commands.is_empty() {
+            return Ok(());
+        }
+
+        let slot = &mut self.command_buffers[self.current_frame];
+        if slot
+            .as_ref()
+            .is_some_and(|command_buffer| command_buffer.generation == self.generation)
+        {
+            return Ok(());
+        }
+
+        // SAFETY: `vk::DrawIndexedIndirectCommand` is a `repr(C)` struct of 4 bytes integers,
+        // without any padding
+        let data = unsafe {
+            std::slice::from_raw_parts(
+                self.commands.as_ptr().cast::<u8>(),
+                std::mem::size_of_val(self.commands.as_slice()),
+            )
+        };
+        let size = data.len() as u64;
+        if slot
+            .as_ref()
+            .is_none_or(|command_buffer| command_buffer.buffer.size() < size)
+        {
+            if let Some(mut command_buffer) = slot.take() {
+                command_buffer
+                    .buffer
+                    .destroy(&renderer.device, &mut renderer.allocator());
+            }
+            let buffer = AllocatedBuffer::builder(size.next_power_of_two())
+                .with_usage(vk::BufferUsageFlags::INDIRECT_BUFFER)
+                .with_name("Indirect batch commands")
+                .build(renderer)?;
+            *slot = Some(CommandBuffer {
+                buffer,
+                generation: 0,
+            });
+        }
+
+        let command_buffer = slot.as_mut().expect("The buffer was just created");
+        command_buffer.buffer.upload_data(data)?;
+        command_buffer.generation = self.generation;
+
+        Ok(())
+    }
+
+    /// Records the draws uploaded by the last [`IndirectBatch::prepare`]. The pipeline and
+    /// descriptor sets must already be bound. Without the `multi_draw_indirect` capability, every
+    /// draw is recorded as its own indirect draw call.
+    pub(crate) fn record_draw<VertexType>(
+        &self,
+        mesh: &Mesh<VertexType>,
+        multi_draw_indirect: bool,
+        device: &ash::Device,
+        cmd_buffer: vk::CommandBuffer,
+    ) where
+        VertexType: Vertex,
+    {
+        let Some(command_buffer) = self
+            .command_buffers
+            .get(self.current_frame)
+            .and_then(Option::as_ref)
+            .filter(|_| !self.commands.is_empty())
+        else {
+            return;
+        };
+        mesh.bind_buffers(device, cmd_buffer);
+
+        let draw_count: u32 = self
+            .commands
+            .len()
+            .try_into()
+            .expect("Unsupported architecture");
+        unsafe {
+            if multi_draw_indirect {
+                device.cmd_draw_indexed_indirect(
+                    cmd_buffer,
+                    command_buffer.buffer.handle,
+                    0,
+                    draw_count,
+                    COMMAND_STRIDE,
+                );
+            } else {
+                for draw in 0..draw_count {
+                    device.cmd_draw_indexed_indirect(
+                        cmd_buffer,
+                        command_buffer.buffer.handle,
+                        u64::from(draw * COMMAND_STRIDE),
+                        1,
+                        COMMAND_STRIDE,
+                    );
+                }
+            }
+        }
+    }
+
+    pub fn destroy(&mut self, renderer: &mut Renderer) {
+        for mut command_buffer in self.command_buffers.drain(..).flatten() {
+            command_buffer
+                .buffer
+                .destroy(&renderer.device, &mut renderer.allocator());
+        }
+    }
+}
+
+impl Default for IndirectBatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
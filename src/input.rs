@@ -0,0 +1,70 @@
+use std::ops::Deref;
+
+use winit::keyboard::KeyCode;
+use winit_input_helper::WinitInputHelper;
+
+use crate::{
+    components::camera::Camera,
+    math_types::{Vec2, Vec3},
+};
+
+/// Input of the main window, updated by the application between frames (see
+/// [`crate::application::StateContext::window_input_state`]).
+///
+/// Wraps [`WinitInputHelper`] with queries commonly needed by camera controllers and editors, the
+/// rest of the helper being available through `Deref`.
+#[derive(Clone)]
+pub struct InputState {
+    pub(crate) helper: WinitInputHelper,
+}
+
+#[profiling::all_functions]
+impl InputState {
+    pub(crate) fn new() -> Self {
+        Self {
+            helper: WinitInputHelper::new(),
+        }
+    }
+
+    /// Whether `key` was pressed since the last frame.
+    #[profiling::skip]
+    pub fn key_pressed(&self, key: KeyCode) -> bool {
+        self.helper.key_pressed(key)
+    }
+
+    /// Whether `key` was released since the last frame.
+    #[profiling::skip]
+    pub fn key_released(&self, key: KeyCode) -> bool {
+        self.helper.key_released(key)
+    }
+
+    /// Movement of the mouse since the last frame, in pixels.
+    pub fn mouse_delta(&self) -> Vec2 {
+        self.helper.mouse_diff().into()
+    }
+
+    /// Vertical scrolling since the last frame, positive when scrolling up.
+    pub fn scroll_delta(&self) -> f32 {
+        self.helper.scroll_diff().1
+    }
+
+    /// Origin and direction, in world space, of the ray going from `camera` through the cursor (see
+    /// [`Camera::screen_ray`]), e.g. to pick the entity under the cursor. `None` when the cursor is
+    /// outside of the window.
+    ///
+    /// The camera is expected to cover the whole window, which is the case for the application's
+    /// camera as it is resized with the window.
+    pub fn mouse_world_ray(&self, camera: &Camera) -> Option<(Vec3, Vec3)> {
+        self.helper
+            .cursor()
+            .map(|cursor| camera.screen_ray(cursor.into()))
+    }
+}
+
+impl Deref for InputState {
+    type Target = WinitInputHelper;
+
+    fn deref(&self) -> &Self::Target {
+        &self.helper
+    }
+}
@@ -0,0 +1,135 @@
+//! Minimal reader of KTX2 containers (see [`crate::texture::TextureBuilder::build_from_ktx2`]),
+//! supporting single 2D images with their mip levels, without supercompression.
+
+use ash::vk;
+use thiserror::Error;
+
+const IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+/// Identifier, header and index, which the level index directly follows.
+const HEADER_SIZE: usize = 80;
+const LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+
+#[derive(Error, Debug)]
+pub enum Ktx2LoadingError {
+    #[error("Reading of KTX2 file \"{path}\" failed with error: {error}.")]
+    FileReadingFailed { path: String, error: std::io::Error },
+
+    #[error("The data is not a KTX2 container.")]
+    InvalidIdentifier,
+
+    #[error("The KTX2 container is truncated, {0} is out of bounds.")]
+    TruncatedData(&'static str),
+
+    #[error("Supercompression scheme {0} is not supported, only uncompressed containers are.")]
+    UnsupportedSupercompression(u32),
+
+    #[error("Only 2D images are supported, the container has {layer_count} layers, {face_count} faces and a depth of {depth}.")]
+    UnsupportedLayout {
+        layer_count: u32,
+        face_count: u32,
+        depth: u32,
+    },
+}
+
+/// Image stored in a KTX2 container, borrowing its data.
+pub(crate) struct Ktx2Image<'a> {
+    pub(crate) format: vk::Format,
+    pub(crate) dimensions: [u32; 2],
+    /// Data of every mip level, from the largest one.
+    pub(crate) levels: Vec<&'a [u8]>,
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+/// Size in bytes of the 4x4 texel blocks of a BC `format`, `None` for other formats.
+fn bc_block_size(format: vk::Format) -> Option<u64> {
+    match format {
+        vk::Format::BC1_RGB_UNORM_BLOCK
+        | vk::Format::BC1_RGB_SRGB_BLOCK
+        | vk::Format::BC1_RGBA_UNORM_BLOCK
+        | vk::Format::BC1_RGBA_SRGB_BLOCK => Some(8),
+        vk::Format::BC3_UNORM_BLOCK
+        | vk::Format::BC3_SRGB_BLOCK
+        | vk::Format::BC5_UNORM_BLOCK
+        | vk::Format::BC5_SNORM_BLOCK
+        | vk::Format::BC7_UNORM_BLOCK
+        | vk::Format::BC7_SRGB_BLOCK => Some(16),
+        _ => None,
+    }
+}
+
+pub(crate) fn parse(bytes: &[u8]) -> Result<Ktx2Image<'_>, Ktx2LoadingError> {
+    if !bytes.starts_with(&IDENTIFIER) {
+        return Err(Ktx2LoadingError::InvalidIdentifier);
+    }
+    if bytes.len() < HEADER_SIZE {
+        return Err(Ktx2LoadingError::TruncatedData("the header"));
+    }
+
+    let format = vk::Format::from_raw(read_u32(bytes, 12) as i32);
+    let width = read_u32(bytes, 20);
+    let height = read_u32(bytes, 24);
+    let depth = read_u32(bytes, 28);
+    let layer_count = read_u32(bytes, 32);
+    let face_count = read_u32(bytes, 36);
+    // 0 requests the mip levels to be generated, only the base level is stored then
+    let level_count = read_u32(bytes, 40).max(1);
+    let supercompression_scheme = read_u32(bytes, 44);
+
+    if supercompression_scheme != 0 {
+        return Err(Ktx2LoadingError::UnsupportedSupercompression(
+            supercompression_scheme,
+        ));
+    }
+    if depth > 1 || layer_count > 1 || face_count != 1 {
+        return Err(Ktx2LoadingError::UnsupportedLayout {
+            layer_count,
+            face_count,
+            depth,
+        });
+    }
+
+    let level_index_size = level_count as usize * LEVEL_INDEX_ENTRY_SIZE;
+    if bytes.len() < HEADER_SIZE + level_index_size {
+        return Err(Ktx2LoadingError::TruncatedData("the level index"));
+    }
+    let height = height.max(1);
+    let levels = (0..level_count as usize)
+        .map(|level| {
+            let entry_offset = HEADER_SIZE + level * LEVEL_INDEX_ENTRY_SIZE;
+            let byte_offset = read_u64(bytes, entry_offset);
+            let byte_length = read_u64(bytes, entry_offset + 8);
+
+            // The upload copies whole levels, so they must hold every block of their extent
+            if let Some(block_size) = bc_block_size(format) {
+                let block_count = |dimension: u32| {
+                    let level_dimension = dimension.checked_shr(level as u32).unwrap_or(0);
+                    u64::from(level_dimension.max(1).div_ceil(4))
+                };
+                if byte_length < block_count(width) * block_count(height) * block_size {
+                    return Err(Ktx2LoadingError::TruncatedData("a mip level"));
+                }
+            }
+
+            usize::try_from(byte_offset)
+                .ok()
+                .zip(usize::try_from(byte_length).ok())
+                .and_then(|(start, length)| bytes.get(start..start.checked_add(length)?))
+                .ok_or(Ktx2LoadingError::TruncatedData("a mip level"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Ktx2Image {
+        format,
+        dimensions: [width, height],
+        levels,
+    })
+}
@@ -1,15 +1,40 @@
 pub mod allocated_types;
+pub mod animation;
 pub mod application;
+pub mod async_load;
+pub mod auto_exposure;
+#[cfg(feature = "bindless")]
+pub mod bindless;
+pub mod capabilities;
+pub mod capture;
 pub mod compute_shader;
 pub mod cubemap;
+pub mod debug_draw;
 pub mod descriptor_resources;
+pub mod dynamic_uniforms;
+pub mod frame_stats;
+pub mod gpu_culling;
+pub mod gpu_profiler;
+pub mod ibl;
+pub mod indirect_batch;
+pub mod input;
+pub mod ktx2;
 pub mod material;
 pub mod math_types;
 pub mod mesh;
+pub mod picking;
 pub mod pipeline_barrier;
+pub mod pipeline_statistics;
+pub mod point_cloud;
+pub mod render_target;
 pub mod renderer;
+pub mod resource_registry;
 pub mod shader;
+pub mod shadows;
+pub mod staging_pool;
+pub mod text;
 pub mod texture;
+pub mod tone_mapping;
 pub mod utils;
 pub mod vertices;
 
@@ -19,8 +44,14 @@ pub mod systems;
 
 #[cfg(feature = "egui")]
 pub mod egui_integration;
+#[cfg(feature = "sdf_text")]
+pub mod sdf_text;
+#[cfg(feature = "texture_streaming")]
+pub mod texture_streaming;
 
+mod descriptor_set_cache;
 mod pipeline_builder;
+mod sampler_cache;
 
 // Core re-exports
 pub use ash;
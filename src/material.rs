@@ -3,17 +3,24 @@ use thiserror::Error;
 
 use crate::{
     allocated_types::{AllocatedBuffer, AllocatedImage},
+    capabilities::CapabilitiesFeatures,
     descriptor_resources::{
         DescriptorResources, DescriptorSetUpdateError, ResourceBindingError, UniformUpdateError,
+        MATERIAL_SET,
     },
-    math_types::{Mat4, Vec4},
+    math_types::{Mat4, Vec3, Vec4},
     pipeline_builder::{PipelineBuildError, PipelineBuilder},
+    render_target::RenderTarget,
     renderer::Renderer,
-    shader::Shader,
+    resource_registry::{self, ResourceKind},
+    shader::{Shader, ShaderReflection, ShaderResourceKind, ShaderScalarType},
     texture::Texture,
     utils::ThreadSafeRef,
 };
 
+#[cfg(feature = "gltf")]
+use crate::vertices::{GltfVertexAttributes, VertexModelLoadingError};
+
 pub struct VertexInputDescription {
     pub bindings: Vec<vk::VertexInputBindingDescription>,
     pub attributes: Vec<vk::VertexInputAttributeDescription>,
@@ -27,6 +34,46 @@ pub trait Vertex: Sync + Send + 'static + std::fmt::Debug {
     fn position_offset() -> u32 {
         0
     }
+
+    /// Position of the vertex, read as three `f32` at [`Vertex::position_offset`] (like the ray
+    /// tracing acceleration structures do). Used by CPU side helpers such as
+    /// [`crate::mesh::Mesh::bounds`].
+    fn position(&self) -> Vec3
+    where
+        Self: Sized,
+    {
+        let offset = Self::position_offset() as usize;
+        assert!(offset + std::mem::size_of::<Vec3>() <= std::mem::size_of::<Self>());
+
+        // SAFETY: the offset was checked to be in the vertex, and any bit pattern is a valid f32
+        unsafe {
+            std::ptr::from_ref(self)
+                .cast::<u8>()
+                .add(offset)
+                .cast::<Vec3>()
+                .read_unaligned()
+        }
+    }
+
+    /// Normal of the vertex, if the vertex type has one.
+    fn normal(&self) -> Option<Vec3> {
+        None
+    }
+
+    /// Builds a vertex from the attributes of a glTF primitive (see
+    /// [`crate::mesh::Mesh::load_gltf_from_path`]). Vertex types opt in to glTF loading by
+    /// implementing this, the default implementation refuses every model.
+    #[cfg(feature = "gltf")]
+    fn from_gltf_attributes(
+        _attributes: &GltfVertexAttributes,
+    ) -> Result<Self, VertexModelLoadingError>
+    where
+        Self: Sized,
+    {
+        Err(VertexModelLoadingError::UnsupportedGltfVertexType(
+            std::any::type_name::<Self>(),
+        ))
+    }
 }
 
 #[allow(dead_code)] // We never "read" value from this struct, it's directly uploaded to the GPU without any field access
@@ -48,6 +95,12 @@ where
     pub(crate) descriptor_set: vk::DescriptorSet,
     pub(crate) layout: vk::PipelineLayout,
     pub(crate) pipeline: vk::Pipeline,
+    /// Vertex only pipeline writing the depth, see [`MaterialBuilder::depth_prepass`].
+    pub(crate) depth_pipeline: Option<vk::Pipeline>,
+    pub(crate) push_constant_stages: vk::ShaderStageFlags,
+    pub(crate) push_constant_size: u32,
+    /// Description the pipeline was built from, see [`Material::rebuild_pipeline`].
+    pipeline_description: MaterialBuilder,
 
     vertex_type_safety: std::marker::PhantomData<VertexType>,
 }
@@ -61,12 +114,115 @@ where
     }
 }
 
-pub use vk::CullModeFlags;
+pub use vk::{CullModeFlags, PolygonMode, PrimitiveTopology};
 
+/// How line primitives (and polygon edges drawn as lines) are rasterized.
+///
+/// The non default modes come from `VK_EXT_line_rasterization`, which isn't available on every
+/// device (see `rectangular_lines` and `smooth_lines` in
+/// [`crate::capabilities::CapabilitiesFeatures`]). When the requested mode isn't supported, the
+/// material falls back to the next simplest one: `Smooth` to `Rectangular`, then to `Default`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineMode {
+    /// Implementation defined rasterization, usually parallelograms or Bresenham lines.
+    #[default]
+    Default,
+    /// Lines are rasterized as rectangles of the line's width, centered on the line.
+    Rectangular,
+    /// Anti-aliased rectangular lines. The coverage of each fragment is multiplied into its alpha
+    /// value, which is then blended with the framebuffer like any other material output.
+    Smooth,
+}
+
+impl LineMode {
+    fn supported_fallback(self, features: &CapabilitiesFeatures) -> Self {
+        match self {
+            LineMode::Smooth if features.smooth_lines => LineMode::Smooth,
+            LineMode::Smooth | LineMode::Rectangular if features.rectangular_lines => {
+                LineMode::Rectangular
+            }
+            _ => LineMode::Default,
+        }
+    }
+}
+
+impl From<LineMode> for vk::LineRasterizationModeEXT {
+    fn from(value: LineMode) -> Self {
+        match value {
+            LineMode::Default => vk::LineRasterizationModeEXT::DEFAULT,
+            LineMode::Rectangular => vk::LineRasterizationModeEXT::RECTANGULAR,
+            LineMode::Smooth => vk::LineRasterizationModeEXT::RECTANGULAR_SMOOTH,
+        }
+    }
+}
+
+/// How the color output of a material is combined with the framebuffer's content.
+///
+/// In every blending mode, the alpha written to the framebuffer is the material's output alpha.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    /// Blending is disabled, the output replaces the framebuffer's content.
+    Opaque,
+    /// `src * src_alpha + dst * (1 - src_alpha)`.
+    #[default]
+    AlphaBlend,
+    /// `src * src_alpha + dst`, for light-like effects (particles, glows, ...).
+    Additive,
+    /// `src * dst`, which darkens the framebuffer (decals, tinted glass, ...).
+    Multiply,
+}
+
+impl BlendMode {
+    /// Whether the output is combined with what was drawn before, which makes the result depend
+    /// on the draw order (see [`crate::systems::mesh_renderer::render_meshes_sorted`]).
+    pub fn is_transparent(self) -> bool {
+        self != BlendMode::Opaque
+    }
+
+    fn attachment_state(self) -> vk::PipelineColorBlendAttachmentState {
+        let (src_color_blend_factor, dst_color_blend_factor) = match self {
+            BlendMode::Opaque => {
+                return vk::PipelineColorBlendAttachmentState::default()
+                    .blend_enable(false)
+                    .color_write_mask(vk::ColorComponentFlags::RGBA);
+            }
+            BlendMode::AlphaBlend => (
+                vk::BlendFactor::SRC_ALPHA,
+                vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            ),
+            BlendMode::Additive => (vk::BlendFactor::SRC_ALPHA, vk::BlendFactor::ONE),
+            BlendMode::Multiply => (vk::BlendFactor::DST_COLOR, vk::BlendFactor::ZERO),
+        };
+
+        vk::PipelineColorBlendAttachmentState::default()
+            .blend_enable(true)
+            .src_color_blend_factor(src_color_blend_factor)
+            .dst_color_blend_factor(dst_color_blend_factor)
+            .color_blend_op(vk::BlendOp::ADD)
+            .src_alpha_blend_factor(vk::BlendFactor::ONE)
+            .dst_alpha_blend_factor(vk::BlendFactor::ZERO)
+            .alpha_blend_op(vk::BlendOp::ADD)
+            .color_write_mask(vk::ColorComponentFlags::RGBA)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
 pub struct MaterialBuilder {
     pub z_test: bool,
     pub z_write: bool,
     pub cull_mode: CullModeFlags,
+    pub polygon_mode: PolygonMode,
+    pub blend_mode: BlendMode,
+    pub topology: PrimitiveTopology,
+    pub line_mode: LineMode,
+    pub line_width: f32,
+    /// Render pass the material is drawn in, the renderer's primary render pass if `None`.
+    pub render_pass: Option<vk::RenderPass>,
+    pub subpass: u32,
+    pub color_attachment_count: u32,
+    pub samples: vk::SampleCountFlags,
+    /// See [`MaterialBuilder::depth_prepass`].
+    pub depth_prepass: bool,
 }
 
 #[derive(Error, Debug)]
@@ -85,6 +241,24 @@ pub enum MaterialBuildError {
     )]
     InvalidPushConstantSize,
 
+    #[error(
+        "Topology {0:?} requires the geometry shader feature, which is not enabled on this device."
+    )]
+    UnsupportedTopology(PrimitiveTopology),
+
+    #[error(
+        "Polygon mode {0:?} requires the fill mode non solid feature, which is not enabled on this device."
+    )]
+    UnsupportedPolygonMode(PolygonMode),
+
+    #[error("The shader reads a vertex attribute of format {expected:?} at location {location}, but the vertex type provides {found:?}.")]
+    VertexLayoutMismatch {
+        location: u32,
+        expected: vk::Format,
+        /// `UNDEFINED` if the vertex type has no attribute at this location.
+        found: vk::Format,
+    },
+
     #[error("Material's vulkan pipeline layout creation failed with status: {0}.")]
     VulkanPipelineLayoutCreationFailed(vk::Result),
 
@@ -92,12 +266,34 @@ pub enum MaterialBuildError {
     PipelineCreationFailed(#[from] PipelineBuildError),
 }
 
+#[derive(Error, Debug)]
+pub enum PushConstantError {
+    #[error("The push constant data is {provided} bytes long, but the shader declares a {expected} bytes block.")]
+    SizeMismatch { expected: u32, provided: usize },
+
+    #[error("Push constants were pushed to stages {requested:?}, but the shader declares them for stages {declared:?}.")]
+    StageMismatch {
+        declared: vk::ShaderStageFlags,
+        requested: vk::ShaderStageFlags,
+    },
+}
+
 impl MaterialBuilder {
     pub fn new() -> Self {
         Self {
             z_test: true,
             z_write: true,
             cull_mode: CullModeFlags::BACK,
+            polygon_mode: PolygonMode::FILL,
+            blend_mode: BlendMode::AlphaBlend,
+            topology: PrimitiveTopology::TRIANGLE_LIST,
+            line_mode: LineMode::Default,
+            line_width: 1.0,
+            render_pass: None,
+            subpass: 0,
+            color_attachment_count: 1,
+            samples: vk::SampleCountFlags::TYPE_1,
+            depth_prepass: false,
         }
     }
 
@@ -116,6 +312,121 @@ impl MaterialBuilder {
         self
     }
 
+    /// Sets how polygons are rasterized: filled (the default), as their edges (`LINE`, e.g. for
+    /// wireframe overlays) or as their vertices (`POINT`).
+    ///
+    /// Modes other than `FILL` require the `fill_mode_non_solid` feature (see
+    /// [`crate::capabilities::CapabilitiesFeatures`]). Edges use the material's
+    /// [`MaterialBuilder::line_width`] and [`MaterialBuilder::line_mode`].
+    pub fn polygon_mode(mut self, polygon_mode: PolygonMode) -> Self {
+        self.polygon_mode = polygon_mode;
+        self
+    }
+
+    /// Sets how the material's output is blended with the framebuffer ([`BlendMode::AlphaBlend`]
+    /// by default), for every color attachment.
+    ///
+    /// Blending doesn't change depth writes: a blended material still hides what is drawn behind
+    /// it afterwards, so transparent materials usually disable [`MaterialBuilder::z_write`]
+    /// (keeping [`MaterialBuilder::z_test`] so that opaque geometry still occludes them), and are
+    /// drawn after the opaque ones.
+    pub fn blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+
+    /// Sets how vertices are assembled into primitives (triangle lists by default).
+    ///
+    /// Adjacency topologies (`LINE_LIST_WITH_ADJACENCY`, `TRIANGLE_LIST_WITH_ADJACENCY`, ...) are
+    /// meant to be consumed by geometry shaders (e.g. for silhouette extraction), and require the
+    /// geometry shader feature (see [`crate::capabilities::CapabilitiesFeatures`]). Their index
+    /// buffer layout is:
+    /// - `LINE_LIST_WITH_ADJACENCY`: 4 indices per line, `[previous, start, end, next]`.
+    /// - `TRIANGLE_LIST_WITH_ADJACENCY`: 6 indices per triangle, `[v0, adjacent to v0v1, v1,
+    ///   adjacent to v1v2, v2, adjacent to v2v0]`. Such a buffer can be generated from a regular
+    ///   triangle list with [`crate::mesh::triangle_list_with_adjacency`].
+    pub fn topology(mut self, topology: PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    /// Sets the rasterization mode of lines, see [`LineMode`] for the fallback used when the
+    /// device doesn't support it. Only relevant for line topologies.
+    pub fn line_mode(mut self, line_mode: LineMode) -> Self {
+        self.line_mode = line_mode;
+        self
+    }
+
+    /// Sets the width of lines, in pixels (1.0 by default).
+    ///
+    /// The width is clamped to the device's `line_width_range` (see
+    /// [`crate::capabilities::CapabilitiesLimits`]), and is always 1.0 if the `wide_lines` feature
+    /// isn't available.
+    pub fn line_width(mut self, line_width: f32) -> Self {
+        self.line_width = line_width;
+        self
+    }
+
+    /// Builds the material's pipeline for `render_pass` instead of the renderer's primary render
+    /// pass (e.g. for offscreen, shadow or deferred passes).
+    ///
+    /// The pipeline is only compatible with the pass if the rest of its description matches the
+    /// subpass it is used in: its index ([`MaterialBuilder::subpass`]), its number of color
+    /// attachments ([`MaterialBuilder::color_attachment_count`], which all use the material's
+    /// blending) and their sample count ([`MaterialBuilder::samples`]). All of them default to the
+    /// primary render pass' values: subpass 0, a single color attachment and a single sample.
+    /// [`MaterialBuilder::for_render_target`] sets them all from a [`RenderTarget`].
+    pub fn for_render_pass(mut self, render_pass: vk::RenderPass) -> Self {
+        self.render_pass = Some(render_pass);
+        self
+    }
+
+    /// Index of the subpass the material is drawn in, see [`MaterialBuilder::for_render_pass`].
+    pub fn subpass(mut self, subpass: u32) -> Self {
+        self.subpass = subpass;
+        self
+    }
+
+    /// Number of color attachments of the subpass the material is drawn in (0 for depth only
+    /// passes), see [`MaterialBuilder::for_render_pass`].
+    pub fn color_attachment_count(mut self, color_attachment_count: u32) -> Self {
+        self.color_attachment_count = color_attachment_count;
+        self
+    }
+
+    /// Sample count of the attachments of the subpass the material is drawn in, see
+    /// [`MaterialBuilder::for_render_pass`].
+    pub fn samples(mut self, samples: vk::SampleCountFlags) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    /// Also builds a depth only pipeline from the shader's vertex stage, which
+    /// [`crate::systems::mesh_renderer::depth_prepass`] draws the material with before the color
+    /// pass. The color pipeline then only shades the fragments left visible by the prepass (its
+    /// depth test becomes `EQUAL`, without depth writes), which removes the overdraw of expensive
+    /// fragment shaders.
+    ///
+    /// This is only meant for opaque materials of the primary render pass: such a material is
+    /// only visible if the state runs `depth_prepass` before `render_meshes` (`render_indirect`
+    /// and `render_culled_meshes` draw the prepass of their meshes themselves). Its vertex shader
+    /// should declare `invariant gl_Position`, so that both pipelines compute the same depth.
+    pub fn depth_prepass(mut self, depth_prepass: bool) -> Self {
+        self.depth_prepass = depth_prepass;
+        self
+    }
+
+    /// Builds the material for the render pass of `render_target`, with a matching attachment
+    /// description (no color attachment for depth only targets).
+    pub fn for_render_target(self, render_target: &RenderTarget) -> Self {
+        let color_attachment_count = if render_target.is_depth_only() { 0 } else { 1 };
+
+        self.for_render_pass(render_target.render_pass())
+            .subpass(0)
+            .color_attachment_count(color_attachment_count)
+            .samples(render_target.samples())
+    }
+
     #[profiling::function]
     pub fn build<VertexType>(
         self,
@@ -126,22 +437,45 @@ impl MaterialBuilder {
     where
         VertexType: Vertex,
     {
+        let requires_geometry_shader = [
+            PrimitiveTopology::LINE_LIST_WITH_ADJACENCY,
+            PrimitiveTopology::LINE_STRIP_WITH_ADJACENCY,
+            PrimitiveTopology::TRIANGLE_LIST_WITH_ADJACENCY,
+            PrimitiveTopology::TRIANGLE_STRIP_WITH_ADJACENCY,
+        ]
+        .contains(&self.topology);
+        if requires_geometry_shader && !renderer.capabilities().features.geometry_shader {
+            return Err(MaterialBuildError::UnsupportedTopology(self.topology));
+        }
+        if self.polygon_mode != PolygonMode::FILL
+            && !renderer.capabilities().features.fill_mode_non_solid
+        {
+            return Err(MaterialBuildError::UnsupportedPolygonMode(
+                self.polygon_mode,
+            ));
+        }
+
         let shader_ref = ThreadSafeRef::clone(shader_ref);
         let shader = shader_ref.lock();
+        validate_vertex_layout(&VertexType::vertex_input_description(), shader.reflection())?;
 
         let ubo_count: u32 = descriptor_resources
             .uniform_buffers
             .len()
             .try_into()
             .unwrap();
+        let ssbo_count: u32 = descriptor_resources
+            .storage_buffers
+            .len()
+            .try_into()
+            .unwrap();
         let storage_image_count: u32 = descriptor_resources
             .storage_images
             .len()
             .try_into()
             .unwrap();
         let sampled_image_count: u32 = descriptor_resources
-            .sampled_images
-            .len()
+            .sampled_image_descriptor_count()
             .try_into()
             .unwrap();
 
@@ -150,6 +484,10 @@ impl MaterialBuilder {
                 ty: vk::DescriptorType::UNIFORM_BUFFER,
                 descriptor_count: std::cmp::max(ubo_count, 1),
             },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::STORAGE_BUFFER,
+                descriptor_count: std::cmp::max(ssbo_count, 1),
+            },
             vk::DescriptorPoolSize {
                 ty: vk::DescriptorType::STORAGE_IMAGE,
                 descriptor_count: std::cmp::max(storage_image_count, 1),
@@ -174,13 +512,14 @@ impl MaterialBuilder {
                 .allocate_descriptor_sets(&descriptor_set_alloc_info)
         }
         .map_err(MaterialBuildError::VulkanDescriptorSetAllocationFailed)?[0];
+        resource_registry::register(ResourceKind::DescriptorSet, 1);
 
         let mut merged_bindings = shader.vertex_bindings.clone();
         merged_bindings.extend(&shader.fragment_bindings);
         descriptor_resources.update_descriptors_set_from_bindings(
             &merged_bindings,
             &descriptor_set,
-            Some(&[2]),
+            Some(&[MATERIAL_SET]),
             renderer,
         )?;
 
@@ -192,7 +531,12 @@ impl MaterialBuilder {
         }
         if !shader.fragment_push_constants.is_empty() {
             pc_shader_stages |= vk::ShaderStageFlags::FRAGMENT;
-            size = Some(shader.fragment_push_constants[0].size);
+            // The fragment block may extend past the vertex one (e.g. a per-draw texture index
+            // stored after the camera data), so the range must cover both
+            size = Some(std::cmp::max(
+                size.unwrap_or(0),
+                shader.fragment_push_constants[0].size,
+            ));
         }
 
         let mut pc_ranges = vec![];
@@ -208,12 +552,67 @@ impl MaterialBuilder {
             shader.level_2_dsl,
             shader.level_3_dsl,
         ];
+        // At `BINDLESS_SET`, right after the per-object set
+        #[cfg(feature = "bindless")]
+        let layouts = layouts
+            .into_iter()
+            .chain(
+                renderer
+                    .bindless_textures()
+                    .map(|bindless_textures| bindless_textures.layout),
+            )
+            .collect::<Vec<_>>();
         let layout_info = vk::PipelineLayoutCreateInfo::default()
             .set_layouts(&layouts)
             .push_constant_ranges(&pc_ranges);
         let layout = unsafe { renderer.device.create_pipeline_layout(&layout_info, None) }
             .map_err(MaterialBuildError::VulkanPipelineLayoutCreationFailed)?;
 
+        let pipeline = self.build_pipeline::<VertexType>(&shader, layout, false, renderer)?;
+        let depth_pipeline = self
+            .depth_prepass
+            .then(|| self.build_pipeline::<VertexType>(&shader, layout, true, renderer))
+            .transpose()?;
+
+        if let Some(name) = &shader.name {
+            renderer.auto_name(pipeline, &format!("{name} (pipeline)"));
+            if let Some(depth_pipeline) = depth_pipeline {
+                renderer.auto_name(depth_pipeline, &format!("{name} (depth pipeline)"));
+            }
+            renderer.auto_name(layout, &format!("{name} (pipeline layout)"));
+            renderer.auto_name(descriptor_set, &format!("{name} (material set)"));
+            renderer.auto_name(shader.vertex_module, &format!("{name} (vertex module)"));
+            renderer.auto_name(shader.fragment_module, &format!("{name} (fragment module)"));
+        }
+        drop(shader);
+
+        Ok(ThreadSafeRef::new(Material {
+            descriptor_pool,
+            descriptor_resources,
+            shader_ref,
+            descriptor_set,
+            layout,
+            pipeline,
+            depth_pipeline,
+            push_constant_stages: pc_shader_stages,
+            push_constant_size: size.unwrap_or(0),
+            pipeline_description: self,
+            vertex_type_safety: std::marker::PhantomData,
+        }))
+    }
+
+    /// Builds the color pipeline, or the depth only one of [`MaterialBuilder::depth_prepass`] if
+    /// `depth_only` is set.
+    fn build_pipeline<VertexType>(
+        &self,
+        shader: &Shader,
+        layout: vk::PipelineLayout,
+        depth_only: bool,
+        renderer: &Renderer,
+    ) -> Result<vk::Pipeline, MaterialBuildError>
+    where
+        VertexType: Vertex,
+    {
         let vertex_info = VertexType::vertex_input_description();
         let vertex_input_state_info = vk::PipelineVertexInputStateCreateInfo::default()
             .vertex_binding_descriptions(&vertex_info.bindings)
@@ -229,57 +628,120 @@ impl MaterialBuilder {
             .module(shader.fragment_module)
             .name(&shader_module_entry_point);
 
-        let input_assembly_state_info = vk::PipelineInputAssemblyStateCreateInfo::default()
-            .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
-        let rasterizer_state_info = vk::PipelineRasterizationStateCreateInfo::default()
-            .polygon_mode(vk::PolygonMode::FILL)
+        let capabilities = renderer.capabilities();
+        let line_mode = self.line_mode.supported_fallback(&capabilities.features);
+        if line_mode != self.line_mode {
+            log::warn!(
+                "Line mode {:?} is not supported by the device, falling back to {line_mode:?}",
+                self.line_mode
+            );
+        }
+        let [min_line_width, max_line_width] = capabilities.limits.line_width_range;
+        let line_width = if capabilities.features.wide_lines {
+            self.line_width.clamp(min_line_width, max_line_width)
+        } else {
+            1.0
+        };
+
+        // Flipping the viewport mirrors the winding order on screen
+        let front_face = if renderer.is_viewport_flipped() {
+            vk::FrontFace::COUNTER_CLOCKWISE
+        } else {
+            vk::FrontFace::CLOCKWISE
+        };
+        let input_assembly_state_info =
+            vk::PipelineInputAssemblyStateCreateInfo::default().topology(self.topology);
+        let mut line_state_info = vk::PipelineRasterizationLineStateCreateInfoEXT::default()
+            .line_rasterization_mode(line_mode.into());
+        let mut rasterizer_state_info = vk::PipelineRasterizationStateCreateInfo::default()
+            .polygon_mode(self.polygon_mode)
             .cull_mode(self.cull_mode)
-            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
-            .line_width(1.0);
+            .front_face(front_face)
+            .line_width(line_width);
+        if line_mode != LineMode::Default {
+            rasterizer_state_info = rasterizer_state_info.push_next(&mut line_state_info);
+        }
         let multisampling_state_info = vk::PipelineMultisampleStateCreateInfo::default()
-            .rasterization_samples(vk::SampleCountFlags::TYPE_1)
+            .rasterization_samples(self.samples)
             .min_sample_shading(1.0);
+        // The prepass already wrote the depth of the visible fragments, which the color pass tests
+        // against
+        let (depth_write, depth_compare_op) = match (self.depth_prepass, depth_only) {
+            (true, false) => (false, vk::CompareOp::EQUAL),
+            (true, true) => (true, vk::CompareOp::LESS_OR_EQUAL),
+            (false, _) => (self.z_write, vk::CompareOp::LESS_OR_EQUAL),
+        };
         let depth_stencil_state_info = vk::PipelineDepthStencilStateCreateInfo::default()
             .depth_test_enable(self.z_test)
-            .depth_write_enable(self.z_write)
-            .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL)
+            .depth_write_enable(depth_write)
+            .depth_compare_op(depth_compare_op)
             .min_depth_bounds(0.0)
             .max_depth_bounds(1.0);
-        let color_blend_attachment_state = vk::PipelineColorBlendAttachmentState::default()
-            .blend_enable(true)
-            .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
-            .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
-            .color_blend_op(vk::BlendOp::ADD)
-            .src_alpha_blend_factor(vk::BlendFactor::ONE)
-            .dst_alpha_blend_factor(vk::BlendFactor::ZERO)
-            .alpha_blend_op(vk::BlendOp::ADD)
-            .color_write_mask(vk::ColorComponentFlags::RGBA);
+        let (shader_stages, color_blend_attachment_state) = if depth_only {
+            (
+                vec![vertex_shader_stage],
+                // Leaves the color attachments untouched
+                vk::PipelineColorBlendAttachmentState::default(),
+            )
+        } else {
+            (
+                vec![vertex_shader_stage, fragment_shader_stage],
+                self.blend_mode.attachment_state(),
+            )
+        };
 
         let pipeline = PipelineBuilder {
-            shader_stages: vec![vertex_shader_stage, fragment_shader_stage],
+            shader_stages,
             vertex_input_state_info,
             input_assembly_state_info,
             rasterizer_state_info,
             multisampling_state_info,
             depth_stencil_state_info,
             color_blend_attachment_state,
+            color_attachment_count: self.color_attachment_count,
+            subpass: self.subpass,
             layout,
             cache: None, // @TODO(Ithyx): use pipeline cache plz
         }
-        .build(&renderer.device, renderer.primary_render_pass)?;
+        .build(
+            &renderer.device,
+            self.render_pass.unwrap_or(renderer.primary_render_pass),
+        )?;
 
-        drop(shader);
+        Ok(pipeline)
+    }
+}
 
-        Ok(ThreadSafeRef::new(Material {
-            descriptor_pool,
-            descriptor_resources,
-            shader_ref,
-            descriptor_set,
-            layout,
-            pipeline,
-            vertex_type_safety: std::marker::PhantomData,
-        }))
+/// Checks that every input of the vertex stage is fed by an attribute with the same numeric type.
+/// The component counts may differ: missing components are filled in, and extra ones are ignored.
+fn validate_vertex_layout(
+    vertex_info: &VertexInputDescription,
+    reflection: &ShaderReflection,
+) -> Result<(), MaterialBuildError> {
+    for input in &reflection.vertex_inputs {
+        if input.scalar_type == ShaderScalarType::Unknown {
+            continue;
+        }
+
+        let found = vertex_info
+            .attributes
+            .iter()
+            .find(|attribute| attribute.location == input.location)
+            .map_or(vk::Format::UNDEFINED, |attribute| attribute.format);
+        let found_scalar_type = ShaderScalarType::from_vertex_format(found);
+        if found == vk::Format::UNDEFINED
+            || (found_scalar_type != ShaderScalarType::Unknown
+                && found_scalar_type != input.scalar_type)
+        {
+            return Err(MaterialBuildError::VertexLayoutMismatch {
+                location: input.location,
+                expected: input.vk_format(),
+                found,
+            });
+        }
     }
+
+    Ok(())
 }
 
 impl Default for MaterialBuilder {
@@ -298,6 +760,108 @@ where
         MaterialBuilder::new()
     }
 
+    /// See [`BlendMode::is_transparent`].
+    #[profiling::skip]
+    pub fn is_transparent(&self) -> bool {
+        self.pipeline_description.blend_mode.is_transparent()
+    }
+
+    /// Recreates the material's pipeline from the current modules of its shader, e.g. after
+    /// `Shader::poll_reload` (`hot_reload` feature) picked up an edit. The pipeline layout, descriptor set and bound
+    /// resources are kept, which is valid as long as the shader's interface didn't change (a hot
+    /// reload refuses such changes).
+    ///
+    /// The device is waited on before the old pipeline is destroyed, as it might still be used by
+    /// the frame in flight.
+    pub fn rebuild_pipeline(&mut self, renderer: &mut Renderer) -> Result<(), MaterialBuildError> {
+        let shader = self.shader_ref.lock();
+        let pipeline = self.pipeline_description.build_pipeline::<VertexType>(
+            &shader,
+            self.layout,
+            false,
+            renderer,
+        )?;
+        let depth_pipeline = self
+            .pipeline_description
+            .depth_prepass
+            .then(|| {
+                self.pipeline_description.build_pipeline::<VertexType>(
+                    &shader,
+                    self.layout,
+                    true,
+                    renderer,
+                )
+            })
+            .transpose()?;
+        if let Some(name) = &shader.name {
+            renderer.auto_name(pipeline, &format!("{name} (pipeline)"));
+            if let Some(depth_pipeline) = depth_pipeline {
+                renderer.auto_name(depth_pipeline, &format!("{name} (depth pipeline)"));
+            }
+        }
+        drop(shader);
+
+        unsafe {
+            renderer
+                .device
+                .device_wait_idle()
+                .expect("Failed to wait for device");
+            renderer.device.destroy_pipeline(self.pipeline, None);
+            resource_registry::unregister(ResourceKind::Pipeline, 1);
+            if let Some(old_depth_pipeline) = self.depth_pipeline {
+                renderer.device.destroy_pipeline(old_depth_pipeline, None);
+                resource_registry::unregister(ResourceKind::Pipeline, 1);
+            }
+        }
+        self.pipeline = pipeline;
+        self.depth_pipeline = depth_pipeline;
+
+        Ok(())
+    }
+
+    /// Records the push of `data` to the material's push constant block in the current frame's
+    /// command buffer, so it must be called while a frame is being recorded. `T` must have the
+    /// exact size of the block declared by the shader, and `stages` must be the stages declaring
+    /// it (the union of the vertex and fragment stages if both do).
+    ///
+    /// Pushed values stay set for the following draws using the material's layout. Note that the
+    /// mesh render systems push their own data (the camera data, then the texture index, see
+    /// [`crate::components::mesh_rendering::MeshRendering::texture_index`]) before every draw,
+    /// overwriting the beginning of the block, so this is meant for draws recorded by the
+    /// application itself.
+    pub fn set_push_constants<T: bytemuck::Pod>(
+        &self,
+        data: &T,
+        stages: vk::ShaderStageFlags,
+        renderer: &Renderer,
+    ) -> Result<(), PushConstantError> {
+        let provided = std::mem::size_of::<T>();
+        if provided != self.push_constant_size as usize {
+            return Err(PushConstantError::SizeMismatch {
+                expected: self.push_constant_size,
+                provided,
+            });
+        }
+        if stages != self.push_constant_stages {
+            return Err(PushConstantError::StageMismatch {
+                declared: self.push_constant_stages,
+                requested: stages,
+            });
+        }
+
+        unsafe {
+            renderer.device.cmd_push_constants(
+                renderer.primary_command_buffer,
+                self.layout,
+                stages,
+                0,
+                bytemuck::bytes_of(data),
+            )
+        };
+
+        Ok(())
+    }
+
     pub fn bind_uniform<T: bytemuck::Pod>(
         &mut self,
         binding_slot: u32,
@@ -311,7 +875,7 @@ where
         else {
             return Err(ResourceBindingError::InvalidBindingSlot {
                 slot: binding_slot,
-                set: 2,
+                set: MATERIAL_SET,
             });
         };
 
@@ -347,13 +911,57 @@ where
             .get(&binding_slot)
             .ok_or(UniformUpdateError::InvalidBindingSlot {
                 slot: binding_slot,
-                set: 2,
+                set: MATERIAL_SET,
             })?
             .lock()
             .upload_pod(data)
             .map_err(|err| err.into())
     }
 
+    /// Writes `value` to a single member of a uniform of the material, both found by name in the
+    /// shader's reflection (see [`Shader::reflection`]) rather than by binding slot and offset.
+    /// `T` must have the exact size of the member.
+    pub fn update_uniform_member<T: bytemuck::Pod>(
+        &mut self,
+        uniform_name: &str,
+        member_name: &str,
+        value: T,
+    ) -> Result<(), UniformUpdateError> {
+        let shader = self.shader_ref.lock();
+        let member_location = shader
+            .reflection()
+            .binding_by_name(MATERIAL_SET, uniform_name)
+            .filter(|binding| binding.kind == ShaderResourceKind::UniformBuffer)
+            .and_then(|binding| Some((binding.slot, binding.member(member_name)?)));
+        let Some((binding_slot, member)) = member_location else {
+            return Err(UniformUpdateError::UnknownMember {
+                uniform: uniform_name.to_owned(),
+                member: member_name.to_owned(),
+            });
+        };
+        let data = bytemuck::bytes_of(&value);
+        if data.len() != member.size as usize {
+            return Err(UniformUpdateError::MemberSizeMismatch {
+                member: member_name.to_owned(),
+                data_size: data.len(),
+                member_size: member.size,
+            });
+        }
+        let offset = member.offset;
+        drop(shader);
+
+        self.descriptor_resources
+            .uniform_buffers
+            .get(&binding_slot)
+            .ok_or(UniformUpdateError::InvalidBindingSlot {
+                slot: binding_slot,
+                set: MATERIAL_SET,
+            })?
+            .lock()
+            .upload_data_at(offset.into(), data)
+            .map_err(|err| err.into())
+    }
+
     pub fn bind_storage_image<T: bytemuck::Pod>(
         &mut self,
         binding_slot: u32,
@@ -367,7 +975,7 @@ where
         else {
             return Err(ResourceBindingError::InvalidBindingSlot {
                 slot: binding_slot,
-                set: 2,
+                set: MATERIAL_SET,
             });
         };
 
@@ -405,7 +1013,7 @@ where
         else {
             return Err(ResourceBindingError::InvalidBindingSlot {
                 slot: binding_slot,
-                set: 2,
+                set: MATERIAL_SET,
             });
         };
 
@@ -431,13 +1039,76 @@ where
         Ok(old_texture)
     }
 
+    pub fn bind_texture_array(
+        &mut self,
+        binding_slot: u32,
+        texture_refs: Vec<ThreadSafeRef<Texture>>,
+        renderer: &mut Renderer,
+    ) -> Result<Vec<ThreadSafeRef<Texture>>, ResourceBindingError> {
+        {
+            let shader = self.shader_ref.lock();
+            let mut bindings = shader.vertex_bindings.clone();
+            bindings.extend(&shader.fragment_bindings);
+            self.descriptor_resources.prepare_texture_array(
+                &bindings,
+                MATERIAL_SET,
+                binding_slot,
+                &texture_refs,
+                renderer,
+            )?;
+        }
+
+        let Some(old_textures) = self
+            .descriptor_resources
+            .sampled_image_arrays
+            .insert(binding_slot, texture_refs.clone())
+        else {
+            return Err(ResourceBindingError::InvalidBindingSlot {
+                slot: binding_slot,
+                set: MATERIAL_SET,
+            });
+        };
+
+        let descriptor_image_infos = texture_refs
+            .iter()
+            .map(|texture_ref| {
+                let texture = texture_ref.lock();
+                let view = texture.image_ref.lock().view;
+                vk::DescriptorImageInfo::default()
+                    .sampler(texture.sampler)
+                    .image_view(view)
+                    .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            })
+            .collect::<Vec<_>>();
+
+        let set_write = vk::WriteDescriptorSet::default()
+            .dst_set(self.descriptor_set)
+            .dst_binding(binding_slot)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&descriptor_image_infos);
+
+        unsafe {
+            renderer
+                .device
+                .update_descriptor_sets(std::slice::from_ref(&set_write), &[])
+        };
+
+        Ok(old_textures)
+    }
+
     pub fn destroy(&mut self, renderer: &mut Renderer) {
         unsafe {
             renderer.device.destroy_pipeline(self.pipeline, None);
+            resource_registry::unregister(ResourceKind::Pipeline, 1);
+            if let Some(depth_pipeline) = self.depth_pipeline {
+                renderer.device.destroy_pipeline(depth_pipeline, None);
+                resource_registry::unregister(ResourceKind::Pipeline, 1);
+            }
             renderer.device.destroy_pipeline_layout(self.layout, None);
             renderer
                 .device
                 .destroy_descriptor_pool(self.descriptor_pool, None);
+            resource_registry::unregister(ResourceKind::DescriptorSet, 1);
         }
     }
 }
@@ -2,13 +2,399 @@ use ash::vk;
 use bytemuck::cast_slice;
 use thiserror::Error;
 
+use std::{collections::HashMap, ops::Range};
+
 use crate::{
     allocated_types::{AllocatedBuffer, BufferBuildError},
     material::Vertex,
+    math_types::{Mat4, Vec3, Vec4},
     renderer::Renderer,
-    utils::ImmediateCommandError,
+    staging_pool::StagingPool,
+    utils::{ImmediateCommandError, ThreadSafeRef},
+    vertices::MtlMaterial,
+};
+
+#[cfg(feature = "gltf")]
+use crate::{
+    animation::{AnimationClip, AnimationTarget, AnimationTrack, Interpolation, TrackValues},
+    components::skeleton::{Joint, JointPose, Skeleton},
+    math_types::{Quat, Vec2},
+    vertices::{
+        validate_gltf_accessors, validate_indices, GltfVertexAttributes, VertexModelLoadingError,
+    },
 };
 
+/// Stride of the vertices and offset of their position, for the passes that only read the
+/// position (e.g. entity picking and shadow maps), so that vertex types sharing them share a
+/// pipeline.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct PositionLayout {
+    pub(crate) stride: u32,
+    pub(crate) position_offset: u32,
+}
+
+#[profiling::all_functions]
+impl PositionLayout {
+    /// Layout of the vertices of `VertexType`, or `None` if their position (see
+    /// [`Vertex::position_index`]) is not a `vec3` read from the first vertex buffer.
+    pub(crate) fn of<VertexType>() -> Option<Self>
+    where
+        VertexType: Vertex,
+    {
+        let vertex_info = VertexType::vertex_input_description();
+        vertex_info
+            .attributes
+            .get(VertexType::position_index())
+            .filter(|attribute| {
+                attribute.binding == 0 && attribute.format == vk::Format::R32G32B32_SFLOAT
+            })
+            .zip(
+                vertex_info
+                    .bindings
+                    .iter()
+                    .find(|binding| binding.binding == 0),
+            )
+            .map(|(attribute, binding)| Self {
+                stride: binding.stride,
+                position_offset: attribute.offset,
+            })
+    }
+
+    pub(crate) fn binding_description(&self) -> vk::VertexInputBindingDescription {
+        vk::VertexInputBindingDescription::default()
+            .binding(0)
+            .stride(self.stride)
+            .input_rate(vk::VertexInputRate::VERTEX)
+    }
+
+    /// The position, read at location 0.
+    pub(crate) fn attribute_description(&self) -> vk::VertexInputAttributeDescription {
+        vk::VertexInputAttributeDescription::default()
+            .location(0)
+            .binding(0)
+            .format(vk::Format::R32G32B32_SFLOAT)
+            .offset(self.position_offset)
+    }
+}
+
+/// Draw of a mesh queued for a pass that only reads the position of its vertices, and recorded
+/// later in the frame.
+pub(crate) struct PositionOnlyDraw {
+    pub(crate) layout: PositionLayout,
+    vertex_buffer: vk::Buffer,
+    index_buffer: Option<vk::Buffer>,
+    /// Indices drawn, or vertices for meshes without indices.
+    elements: Range<u32>,
+}
+
+#[profiling::all_functions]
+impl PositionOnlyDraw {
+    pub(crate) fn record(&self, device: &ash::Device, cmd_buffer: vk::CommandBuffer) {
+        let element_count = self.elements.end - self.elements.start;
+        unsafe {
+            device.cmd_bind_vertex_buffers(
+                cmd_buffer,
+                0,
+                std::slice::from_ref(&self.vertex_buffer),
+                &[0],
+            );
+            match self.index_buffer {
+                Some(index_buffer) => {
+                    device.cmd_bind_index_buffer(
+                        cmd_buffer,
+                        index_buffer,
+                        0,
+                        vk::IndexType::UINT32,
+                    );
+                    device.cmd_draw_indexed(
+                        cmd_buffer,
+                        element_count,
+                        1,
+                        self.elements.start,
+                        0,
+                        0,
+                    );
+                }
+                None => device.cmd_draw(cmd_buffer, element_count, 1, self.elements.start, 0),
+            }
+        }
+    }
+}
+
+/// Axis aligned bounding box, stored as its min and max corners.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+#[profiling::all_functions]
+impl Aabb {
+    /// Bounds of the vertices' positions (see [`Vertex::position`]), or `None` if there are no
+    /// vertices.
+    pub fn from_vertices<VertexType>(vertices: &[VertexType]) -> Option<Self>
+    where
+        VertexType: Vertex,
+    {
+        vertices
+            .iter()
+            .map(Vertex::position)
+            .fold(None, |bounds, position| {
+                Some(match bounds {
+                    Some(Self { min, max }) => Self {
+                        min: position.min(min),
+                        max: position.max(max),
+                    },
+                    None => Self {
+                        min: position,
+                        max: position,
+                    },
+                })
+            })
+    }
+
+    #[profiling::skip]
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    #[profiling::skip]
+    pub fn half_extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    /// Smallest box containing this one once transformed by `matrix` (e.g. a model matrix).
+    pub fn transformed(&self, matrix: &Mat4) -> Self {
+        let center = matrix.transform_point3(self.center());
+        let half_extents = self.half_extents();
+        // Each axis of the transformed box spans the absolute projections of the original axes
+        let world_half_extents = matrix.x_axis.truncate().abs() * half_extents.x
+            + matrix.y_axis.truncate().abs() * half_extents.y
+            + matrix.z_axis.truncate().abs() * half_extents.z;
+
+        Self {
+            min: center - world_half_extents,
+            max: center + world_half_extents,
+        }
+    }
+
+    /// Whether the box is at least partially inside the frustum described by `planes` (see
+    /// [`crate::components::camera::Camera::frustum_planes`]). This is conservative: boxes close
+    /// to the frustum's edges can be reported as intersecting while being outside of it.
+    pub fn intersects_frustum(&self, planes: &[Vec4; 6]) -> bool {
+        let center = self.center();
+        let half_extents = self.half_extents();
+
+        planes.iter().all(|plane| {
+            let normal = plane.truncate();
+            normal.dot(center) + plane.w + normal.abs().dot(half_extents) >= 0.0
+        })
+    }
+}
+
+/// Meshes of a glTF model, see [`Mesh::load_gltf_from_path`].
+#[cfg(feature = "gltf")]
+pub struct GltfModel<VertexType>
+where
+    VertexType: Vertex,
+{
+    /// One mesh per primitive, in the order of the file's meshes and of their primitives.
+    pub meshes: Vec<ThreadSafeRef<Mesh<VertexType>>>,
+    /// Index in the file's materials of the material used by each primitive, `None` for the
+    /// primitives using the default material.
+    pub material_indices: Vec<Option<usize>>,
+    /// One skeleton per skin of the file, in their bind pose.
+    pub skins: Vec<Skeleton>,
+    /// Index in [`GltfModel::skins`] of the skin deforming each primitive, which is the skin of
+    /// the first node using the primitive's mesh.
+    pub skin_indices: Vec<Option<usize>>,
+    /// One clip per animation of the file. Channels animating a joint target the joint of the
+    /// first skin using it, and the ones animating a node using a mesh target the root. The
+    /// others, and morph target weights, are skipped.
+    pub animations: Vec<AnimationClip>,
+}
+
+#[cfg(feature = "gltf")]
+impl<VertexType> GltfModel<VertexType>
+where
+    VertexType: Vertex,
+{
+    pub fn destroy(&mut self, renderer: &mut Renderer) {
+        for mesh in &self.meshes {
+            mesh.lock().destroy(renderer);
+        }
+    }
+}
+
+/// Joints and inverse bind matrices of each skin of a glTF document. A joint's parent is the
+/// closest ancestor node that is a joint of the same skin, the transforms of the nodes in between
+/// being its parent transform.
+#[cfg(feature = "gltf")]
+fn read_gltf_skins(
+    document: &gltf::Document,
+    buffers: &[gltf::buffer::Data],
+) -> Vec<(Vec<Joint>, Vec<Mat4>)> {
+    let node_parents = document
+        .nodes()
+        .flat_map(|node| {
+            node.children()
+                .map(move |child| (child.index(), node.index()))
+        })
+        .collect::<HashMap<_, _>>();
+    let node_matrices = document
+        .nodes()
+        .map(|node| Mat4::from_cols_array_2d(&node.transform().matrix()))
+        .collect::<Vec<_>>();
+
+    document
+        .skins()
+        .map(|skin| {
+            let joint_nodes = skin.joints().map(|node| node.index()).collect::<Vec<_>>();
+            let joints = skin
+                .joints()
+                .map(|node| {
+                    // Bounded by the node count, in case the hierarchy is invalid
+                    let mut parent = None;
+                    let mut parent_transform = Mat4::IDENTITY;
+                    for ancestor in std::iter::successors(node_parents.get(&node.index()), |node| {
+                        node_parents.get(node)
+                    })
+                    .take(node_parents.len())
+                    {
+                        parent = joint_nodes.iter().position(|joint| joint == ancestor);
+                        if parent.is_some() {
+                            break;
+                        }
+                        parent_transform = node_matrices[*ancestor] * parent_transform;
+                    }
+                    let (translation, rotation, scale) = node.transform().decomposed();
+
+                    Joint {
+                        name: node
+                            .name()
+                            .map_or_else(|| node.index().to_string(), str::to_owned),
+                        parent,
+                        parent_transform,
+                        pose: JointPose {
+                            translation: translation.into(),
+                            rotation: Quat::from_array(rotation),
+                            scale: scale.into(),
+                        },
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            // Missing matrices are the identity, as the specification says
+            let reader = skin.reader(|buffer| Some(&buffers[buffer.index()]));
+            let mut inverse_bind_matrices = reader
+                .read_inverse_bind_matrices()
+                .map(|matrices| {
+                    matrices
+                        .map(|matrix| Mat4::from_cols_array_2d(&matrix))
+                        .collect()
+                })
+                .unwrap_or_else(Vec::new);
+            inverse_bind_matrices.resize(joints.len(), Mat4::IDENTITY);
+
+            (joints, inverse_bind_matrices)
+        })
+        .collect()
+}
+
+/// Clips of each animation of a glTF document, see [`GltfModel::animations`]. Cubic spline
+/// channels are interpolated linearly between their keyframes' values.
+#[cfg(feature = "gltf")]
+fn read_gltf_animations(
+    document: &gltf::Document,
+    buffers: &[gltf::buffer::Data],
+) -> Vec<AnimationClip> {
+    use gltf::animation::{util::ReadOutputs, Interpolation as GltfInterpolation};
+
+    let mut node_targets = HashMap::new();
+    for node in document.nodes() {
+        if node.mesh().is_some() {
+            node_targets.insert(node.index(), AnimationTarget::Root);
+        }
+    }
+    // Skins are inserted in reverse for the first one to take precedence
+    for skin in document.skins().collect::<Vec<_>>().into_iter().rev() {
+        for (joint_index, node) in skin.joints().enumerate() {
+            node_targets.insert(node.index(), AnimationTarget::Joint(joint_index));
+        }
+    }
+
+    document
+        .animations()
+        .map(|animation| {
+            let tracks = animation
+                .channels()
+                .filter_map(|channel| {
+                    let target = *node_targets.get(&channel.target().node().index())?;
+                    let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
+                    let times = reader.read_inputs()?.collect::<Vec<_>>();
+
+                    // Cubic spline keyframes are stored as (in tangent, value, out tangent)
+                    let (interpolation, stride) = match channel.sampler().interpolation() {
+                        GltfInterpolation::Step => (Interpolation::Step, 1),
+                        GltfInterpolation::Linear => (Interpolation::Linear, 1),
+                        GltfInterpolation::CubicSpline => (Interpolation::Linear, 3),
+                    };
+                    let values = match reader.read_outputs()? {
+                        ReadOutputs::Translations(translations) => TrackValues::Translation(
+                            translations
+                                .map(Vec3::from)
+                                .skip(stride / 2)
+                                .step_by(stride)
+                                .collect(),
+                        ),
+                        ReadOutputs::Rotations(rotations) => TrackValues::Rotation(
+                            rotations
+                                .into_f32()
+                                .map(Quat::from_array)
+                                .skip(stride / 2)
+                                .step_by(stride)
+                                .collect(),
+                        ),
+                        ReadOutputs::Scales(scales) => TrackValues::Scale(
+                            scales
+                                .map(Vec3::from)
+                                .skip(stride / 2)
+                                .step_by(stride)
+                                .collect(),
+                        ),
+                        ReadOutputs::MorphTargetWeights(..) => return None,
+                    };
+
+                    Some(AnimationTrack {
+                        target,
+                        interpolation,
+                        times,
+                        values,
+                    })
+                })
+                .collect();
+
+            AnimationClip::new(
+                &animation
+                    .name()
+                    .map_or_else(|| animation.index().to_string(), str::to_owned),
+                tracks,
+            )
+        })
+        .collect()
+}
+
+/// Meshes of an OBJ model split by material, see e.g.
+/// [`crate::vertices::textured::TexturedVertex::load_model_from_path_obj_with_materials`].
+pub struct LoadedModel<VertexType>
+where
+    VertexType: Vertex,
+{
+    /// One mesh per material, in the order of their first use in the file, the faces without a
+    /// material being last.
+    pub submeshes: Vec<(ThreadSafeRef<Mesh<VertexType>>, MtlMaterial)>,
+}
+
 #[derive(Debug)]
 pub struct Mesh<VertexType>
 where
@@ -18,12 +404,316 @@ where
     pub indices: Option<Vec<u32>>,
     pub vertex_buffer: AllocatedBuffer,
     pub index_buffer: Option<AllocatedBuffer>,
+    /// Model space bounds of the vertices, computed when the mesh is created (`None` if it has no
+    /// vertices). Used for frustum culling, it must be updated with [`Mesh::update_aabb`] if the
+    /// vertices are modified.
+    pub aabb: Option<Aabb>,
 }
 
+#[profiling::all_functions]
 impl<VertexType> Mesh<VertexType>
 where
     VertexType: Vertex,
 {
+    /// Creates a mesh without an index buffer, vertices will be drawn in order (useful for point
+    /// clouds or procedurally generated geometry).
+    pub fn from_vertices(
+        vertices: Vec<VertexType>,
+        renderer: &mut Renderer,
+    ) -> Result<ThreadSafeRef<Self>, UploadError> {
+        let vertex_buffer = upload_vertex_buffer(&vertices, renderer)?;
+
+        Ok(ThreadSafeRef::new(Self {
+            aabb: Aabb::from_vertices(&vertices),
+            vertices,
+            indices: None,
+            vertex_buffer,
+            index_buffer: None,
+        }))
+    }
+
+    /// Model space axis aligned bounding box of the mesh's vertices, as its `(min, max)` corners,
+    /// or `None` if the mesh has no vertices. This goes through every vertex, so callers needing
+    /// it every frame should keep the result.
+    pub fn bounds(&self) -> Option<(Vec3, Vec3)> {
+        Aabb::from_vertices(&self.vertices).map(|aabb| (aabb.min, aabb.max))
+    }
+
+    /// Recomputes [`Mesh::aabb`] from the current vertices.
+    pub fn update_aabb(&mut self) {
+        self.aabb = Aabb::from_vertices(&self.vertices);
+    }
+
+    /// Loads every primitive of every mesh of a glTF (or GLB) model as its own mesh, along with the
+    /// index of the material each primitive uses. Only the geometry is loaded: node transforms
+    /// are not applied, and the materials and textures are left to the caller. Primitives are
+    /// expected to be triangle lists, indexed ones keep their index buffer.
+    ///
+    /// The skins are loaded as [`Skeleton`]s, for the meshes loaded as
+    /// [`crate::vertices::skinned::SkinnedVertex`] to be animated.
+    ///
+    /// The vertex type must implement [`Vertex::from_gltf_attributes`].
+    #[cfg(feature = "gltf")]
+    pub fn load_gltf_from_path(
+        path: &std::path::Path,
+        renderer: &mut Renderer,
+    ) -> Result<GltfModel<VertexType>, VertexModelLoadingError> {
+        if !path.exists() {
+            return Err(VertexModelLoadingError::FileNotFound(
+                path.display().to_string(),
+            ));
+        }
+
+        let gltf::Gltf { document, blob } = gltf::Gltf::open(path)?;
+        let buffers = gltf::import_buffers(&document, path.parent(), blob)?;
+
+        let mesh_skins = document
+            .nodes()
+            .filter_map(|node| Some((node.mesh()?.index(), node.skin()?.index())))
+            .rev()
+            .collect::<HashMap<_, _>>();
+
+        // All primitives are read before uploading anything, so that an invalid one doesn't leave
+        // the previous ones' buffers behind
+        let mut primitives = vec![];
+        for gltf_mesh in document.meshes() {
+            let mesh_name = gltf_mesh
+                .name()
+                .map_or_else(|| gltf_mesh.index().to_string(), str::to_owned);
+
+            for primitive in gltf_mesh.primitives() {
+                validate_gltf_accessors(&primitive)?;
+                let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+
+                let positions = reader
+                    .read_positions()
+                    .ok_or(VertexModelLoadingError::MissingAttribute("position"))?;
+                let mut normals = reader.read_normals();
+                let mut texture_coords = reader.read_tex_coords(0).map(|coords| coords.into_f32());
+                let mut colors = reader.read_colors(0).map(|colors| colors.into_rgba_f32());
+                let mut joints = reader.read_joints(0).map(|joints| joints.into_u16());
+                let mut weights = reader.read_weights(0).map(|weights| weights.into_f32());
+
+                let vertices = positions
+                    .map(|position| {
+                        VertexType::from_gltf_attributes(&GltfVertexAttributes {
+                            position: position.into(),
+                            normal: normals.as_mut().and_then(Iterator::next).map(Vec3::from),
+                            texture_coords: texture_coords
+                                .as_mut()
+                                .and_then(Iterator::next)
+                                .map(Vec2::from),
+                            color: colors.as_mut().and_then(Iterator::next).map(Vec4::from),
+                            joints: joints.as_mut().and_then(Iterator::next),
+                            weights: weights.as_mut().and_then(Iterator::next).map(Vec4::from),
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let indices = reader
+                    .read_indices()
+                    .map(|indices| indices.into_u32().collect::<Vec<_>>());
+                if let Some(indices) = &indices {
+                    validate_indices(indices, vertices.len())?;
+                }
+
+                primitives.push((
+                    format!(
+                        "{} ({mesh_name}, primitive {})",
+                        path.display(),
+                        primitive.index()
+                    ),
+                    vertices,
+                    indices,
+                    primitive.material().index(),
+                    mesh_skins.get(&gltf_mesh.index()).copied(),
+                ));
+            }
+        }
+        if primitives.is_empty() {
+            return Err(VertexModelLoadingError::EmptyModel);
+        }
+
+        let skins = read_gltf_skins(&document, &buffers);
+        let animations = read_gltf_animations(&document, &buffers);
+
+        let mut model = GltfModel {
+            meshes: Vec::with_capacity(primitives.len()),
+            material_indices: Vec::with_capacity(primitives.len()),
+            skins: Vec::with_capacity(skins.len()),
+            skin_indices: Vec::with_capacity(primitives.len()),
+            animations,
+        };
+        for (name, vertices, indices, material_index, skin_index) in primitives {
+            let mesh = match Self::upload_gltf_primitive(vertices, indices, renderer) {
+                Ok(mesh) => mesh,
+                Err(error) => {
+                    model.destroy(renderer);
+                    return Err(error);
+                }
+            };
+            mesh.auto_name(&name, renderer);
+
+            model.meshes.push(ThreadSafeRef::new(mesh));
+            model.material_indices.push(material_index);
+            model.skin_indices.push(skin_index);
+        }
+        for (joints, inverse_bind_matrices) in skins {
+            match Skeleton::new(joints, inverse_bind_matrices) {
+                Ok(skeleton) => model.skins.push(skeleton),
+                Err(error) => {
+                    model.destroy(renderer);
+                    return Err(error.into());
+                }
+            }
+        }
+
+        Ok(model)
+    }
+
+    #[cfg(feature = "gltf")]
+    fn upload_gltf_primitive(
+        vertices: Vec<VertexType>,
+        indices: Option<Vec<u32>>,
+        renderer: &mut Renderer,
+    ) -> Result<Self, VertexModelLoadingError> {
+        Ok(match indices {
+            Some(indices) => {
+                let upload_result = upload_mesh_data(&vertices, &indices, renderer)?;
+                Self {
+                    aabb: Aabb::from_vertices(&vertices),
+                    vertices,
+                    indices: Some(indices),
+                    vertex_buffer: upload_result.vertex_buffer,
+                    index_buffer: Some(upload_result.index_buffer),
+                }
+            }
+            None => {
+                let vertex_buffer = upload_vertex_buffer(&vertices, renderer)?;
+                Self {
+                    aabb: Aabb::from_vertices(&vertices),
+                    vertices,
+                    indices: None,
+                    vertex_buffer,
+                    index_buffer: None,
+                }
+            }
+        })
+    }
+
+    /// Names the mesh's buffers after `name` (see `RendererBuilder::with_auto_naming`).
+    pub(crate) fn auto_name(&self, name: &str, renderer: &Renderer) {
+        renderer.auto_name(self.vertex_buffer.handle, &format!("{name} (vertices)"));
+        if let Some(index_buffer) = &self.index_buffer {
+            renderer.auto_name(index_buffer.handle, &format!("{name} (indices)"));
+        }
+    }
+
+    pub(crate) fn bind_buffers(&self, device: &ash::Device, cmd_buffer: vk::CommandBuffer) {
+        unsafe {
+            device.cmd_bind_vertex_buffers(
+                cmd_buffer,
+                0,
+                std::slice::from_ref(&self.vertex_buffer.handle),
+                &[0],
+            );
+            if let Some(index_buffer) = self.index_buffer.as_ref() {
+                device.cmd_bind_index_buffer(
+                    cmd_buffer,
+                    index_buffer.handle,
+                    0,
+                    vk::IndexType::UINT32,
+                );
+            }
+        }
+    }
+
+    /// Binds the mesh's buffers and records its draw call, using `cmd_draw_indexed` if the mesh
+    /// has indices and `cmd_draw` otherwise.
+    pub(crate) fn record_draw(&self, device: &ash::Device, cmd_buffer: vk::CommandBuffer) {
+        self.bind_buffers(device, cmd_buffer);
+
+        unsafe {
+            match (self.index_buffer.as_ref(), self.indices.as_ref()) {
+                (Some(_), Some(indices)) => {
+                    device.cmd_draw_indexed(
+                        cmd_buffer,
+                        indices.len().try_into().expect("Unsupported architecture"),
+                        1,
+                        0,
+                        0,
+                        0,
+                    );
+                }
+                _ => {
+                    device.cmd_draw(
+                        cmd_buffer,
+                        self.vertices
+                            .len()
+                            .try_into()
+                            .expect("Unsupported architecture"),
+                        1,
+                        0,
+                        0,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Draw of `index_range` (or of the whole mesh if `None`) for a pass that only reads the
+    /// position, or `None` if the position can't be read on its own (see [`PositionLayout::of`]).
+    pub(crate) fn position_only_draw(
+        &self,
+        index_range: Option<Range<u32>>,
+    ) -> Option<PositionOnlyDraw> {
+        let layout = PositionLayout::of::<VertexType>()?;
+        let index_buffer = self
+            .index_buffer
+            .as_ref()
+            .filter(|_| self.indices.is_some())
+            .map(|index_buffer| index_buffer.handle);
+        let elements = match (index_range, &self.indices) {
+            (Some(index_range), _) => index_range,
+            (None, Some(indices)) => 0..indices.len().try_into().expect("Unsupported architecture"),
+            (None, None) => {
+                0..self
+                    .vertices
+                    .len()
+                    .try_into()
+                    .expect("Unsupported architecture")
+            }
+        };
+
+        Some(PositionOnlyDraw {
+            layout,
+            vertex_buffer: self.vertex_buffer.handle,
+            index_buffer,
+            elements,
+        })
+    }
+
+    /// Binds the mesh's buffers and draws `index_range` of its indices. The mesh must be indexed.
+    pub(crate) fn record_draw_range(
+        &self,
+        index_range: Range<u32>,
+        device: &ash::Device,
+        cmd_buffer: vk::CommandBuffer,
+    ) {
+        self.bind_buffers(device, cmd_buffer);
+
+        unsafe {
+            device.cmd_draw_indexed(
+                cmd_buffer,
+                index_range.end - index_range.start,
+                1,
+                index_range.start,
+                0,
+                0,
+            )
+        };
+    }
+
     pub fn destroy(&mut self, renderer: &mut Renderer) {
         if let Some(index_buffer) = self.index_buffer.as_mut() {
             index_buffer.destroy(&renderer.device, &mut renderer.allocator());
@@ -33,6 +723,48 @@ where
     }
 }
 
+/// Converts a triangle list's indices to the `TRIANGLE_LIST_WITH_ADJACENCY` layout: each triangle
+/// `[v0, v1, v2]` becomes `[v0, a01, v1, a12, v2, a20]`, where `aXY` is the vertex opposite to the
+/// edge `vXvY` in the neighbouring triangle. Boundary edges (without a neighbour) use the
+/// triangle's own opposite vertex.
+///
+/// Edges are matched by index, so meshes whose vertices are duplicated along seams (e.g. for
+/// different UVs) should be deduplicated by position first for the adjacency to be complete.
+pub fn triangle_list_with_adjacency(indices: &[u32]) -> Vec<u32> {
+    let mut opposite_vertices = HashMap::new();
+    for triangle in indices.chunks_exact(3) {
+        for edge_index in 0..3 {
+            let start = triangle[edge_index];
+            let end = triangle[(edge_index + 1) % 3];
+            let opposite = triangle[(edge_index + 2) % 3];
+            opposite_vertices.insert((start, end), opposite);
+        }
+    }
+
+    indices
+        .chunks_exact(3)
+        .flat_map(|triangle| {
+            let adjacent_vertex = |edge_index: usize| {
+                let start = triangle[edge_index];
+                let end = triangle[(edge_index + 1) % 3];
+                // The neighbouring triangle has the same winding, so it stores the edge reversed
+                *opposite_vertices
+                    .get(&(end, start))
+                    .unwrap_or(&triangle[(edge_index + 2) % 3])
+            };
+
+            [
+                triangle[0],
+                adjacent_vertex(0),
+                triangle[1],
+                adjacent_vertex(1),
+                triangle[2],
+                adjacent_vertex(2),
+            ]
+        })
+        .collect()
+}
+
 pub struct UploadData {
     pub vertex_buffer: AllocatedBuffer,
     pub index_buffer: AllocatedBuffer,
@@ -66,12 +798,29 @@ where
     VertexType: Vertex,
 {
     let vertex_data_size: u64 = std::mem::size_of_val(vertices).try_into().unwrap();
-    let mut vertex_staging_buffer = AllocatedBuffer::builder(vertex_data_size)
-        .with_name("Vertex staging")
-        .with_usage(vk::BufferUsageFlags::TRANSFER_SRC)
-        .with_memory_location(gpu_allocator::MemoryLocation::CpuToGpu)
+
+    let mut buffer_usage_flags =
+        vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::VERTEX_BUFFER;
+    if cfg!(feature = "ray_tracing") {
+        buffer_usage_flags |= vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS;
+        buffer_usage_flags |=
+            vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR;
+    }
+
+    let vertex_buffer = AllocatedBuffer::builder(vertex_data_size)
+        .with_name("Vertex data")
+        .with_usage(buffer_usage_flags)
+        .with_memory_location(gpu_allocator::MemoryLocation::GpuOnly)
         .build(renderer)
-        .map_err(UploadError::StagingBufferCreationFailed)?;
+        .map_err(UploadError::MainBufferCreationFailed)?;
+
+    let vertex_staging_buffer = StagingPool::lease(
+        &renderer.command_uploader.staging_pool,
+        vertex_data_size,
+        &renderer.device,
+        &mut renderer.allocator(),
+    )
+    .map_err(UploadError::StagingBufferCreationFailed)?;
 
     // We cannot cast this vertex slice using bytemuck because we don't want to enforce that a vertex types doesn't have padding.
     // Padding issues are not a problem because of the way input bindings are set up (using offsets into a struct).
@@ -91,21 +840,6 @@ where
         std::ptr::copy_nonoverlapping(vertices.as_ptr(), vertex_staging_ptr, vertices.len());
     };
 
-    let mut buffer_usage_flags =
-        vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::VERTEX_BUFFER;
-    if cfg!(feature = "ray_tracing") {
-        buffer_usage_flags |= vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS;
-        buffer_usage_flags |=
-            vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR;
-    }
-
-    let vertex_buffer = AllocatedBuffer::builder(vertex_data_size)
-        .with_name("Vertex data")
-        .with_usage(buffer_usage_flags)
-        .with_memory_location(gpu_allocator::MemoryLocation::GpuOnly)
-        .build(renderer)
-        .map_err(UploadError::MainBufferCreationFailed)?;
-
     renderer
         .immediate_command(|cmd_buffer| {
             let copy_info = vk::BufferCopy::default().size(vertex_data_size);
@@ -121,8 +855,6 @@ where
         })
         .map_err(UploadError::CopyCommandFailed)?;
 
-    vertex_staging_buffer.destroy(&renderer.device, &mut renderer.allocator());
-
     Ok(vertex_buffer)
 }
 
@@ -131,21 +863,6 @@ pub fn upload_index_buffer(
     renderer: &mut Renderer,
 ) -> Result<AllocatedBuffer, UploadError> {
     let index_data_size: u64 = std::mem::size_of_val(indices).try_into().unwrap();
-    let mut index_staging_buffer = AllocatedBuffer::builder(index_data_size)
-        .with_name("Index staging")
-        .with_usage(vk::BufferUsageFlags::TRANSFER_SRC)
-        .with_memory_location(gpu_allocator::MemoryLocation::CpuToGpu)
-        .build(renderer)
-        .map_err(UploadError::StagingBufferCreationFailed)?;
-
-    let raw_indices = cast_slice(indices);
-    index_staging_buffer
-        .allocation
-        .as_mut()
-        .ok_or(UploadError::UseAfterFree)?
-        .mapped_slice_mut()
-        .ok_or(UploadError::MemoryMappingFailed)?[..raw_indices.len()]
-        .copy_from_slice(raw_indices);
 
     let mut buffer_usage_flags =
         vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::INDEX_BUFFER;
@@ -162,6 +879,23 @@ pub fn upload_index_buffer(
         .build(renderer)
         .map_err(UploadError::MainBufferCreationFailed)?;
 
+    let mut index_staging_buffer = StagingPool::lease(
+        &renderer.command_uploader.staging_pool,
+        index_data_size,
+        &renderer.device,
+        &mut renderer.allocator(),
+    )
+    .map_err(UploadError::StagingBufferCreationFailed)?;
+
+    let raw_indices = cast_slice(indices);
+    index_staging_buffer
+        .allocation
+        .as_mut()
+        .ok_or(UploadError::UseAfterFree)?
+        .mapped_slice_mut()
+        .ok_or(UploadError::MemoryMappingFailed)?[..raw_indices.len()]
+        .copy_from_slice(raw_indices);
+
     renderer
         .immediate_command(|cmd_buffer| {
             let copy_info = vk::BufferCopy::default().size(index_data_size);
@@ -177,8 +911,6 @@ pub fn upload_index_buffer(
         })
         .map_err(UploadError::CopyCommandFailed)?;
 
-    index_staging_buffer.destroy(&renderer.device, &mut renderer.allocator());
-
     Ok(index_buffer)
 }
 
@@ -0,0 +1,564 @@
+//! Entity picking, by rendering the index of every entity to an integer attachment (see
+//! [`crate::renderer::RendererBuilder::with_entity_picking`] and
+//! [`crate::renderer::Renderer::pick_entity_at`]).
+
+use std::{collections::HashMap, ops::Range};
+
+use ash::vk;
+use bevy_ecs::entity::Entity;
+use bytemuck::{bytes_of, Pod, Zeroable};
+use thiserror::Error;
+
+use crate::{
+    allocated_types::{AllocatedBuffer, AllocatedImage, BufferBuildError, ImageBuildError},
+    material::Vertex,
+    math_types::Mat4,
+    mesh::{Mesh, PositionLayout, PositionOnlyDraw},
+    pipeline_builder::{PipelineBuildError, PipelineBuilder},
+    renderer::{image_aspect_mask, Renderer},
+    shader::{Shader, ShaderBuildError},
+    utils::{ImmediateCommandError, ThreadSafeRef},
+};
+
+const ENTITY_ID_FORMAT: vk::Format = vk::Format::R32_UINT;
+/// Value of the pixels no entity was drawn to.
+const NO_ENTITY: u32 = u32::MAX;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct EntityData {
+    model_view_projection: Mat4,
+    entity_index: u32,
+    _padding: [u32; 3],
+}
+unsafe impl Zeroable for EntityData {}
+unsafe impl Pod for EntityData {}
+
+#[derive(Error, Debug)]
+pub enum EntityPickingCreationError {
+    #[error("Creation of the entity ID shader failed with error: {0}.")]
+    ShaderCreationFailed(#[from] ShaderBuildError),
+
+    #[error("Creation of an entity ID attachment failed with error: {0}.")]
+    ImageCreationFailed(#[from] ImageBuildError),
+
+    #[error("Creation of the entity ID readback buffer failed with error: {0}.")]
+    BufferCreationFailed(#[from] BufferBuildError),
+
+    #[error("Vulkan creation of the entity ID render pass failed with result: {0}.")]
+    VulkanRenderPassCreationFailed(vk::Result),
+
+    #[error("Vulkan creation of the entity ID framebuffer failed with result: {0}.")]
+    VulkanFramebufferCreationFailed(vk::Result),
+
+    #[error("Vulkan creation of the entity ID pipeline layout failed with result: {0}.")]
+    VulkanPipelineLayoutCreationFailed(vk::Result),
+}
+
+#[derive(Error, Debug)]
+pub enum EntityPickingError {
+    #[error("Vulkan wait for the picked frame failed with result: {0}.")]
+    FenceWaitFailed(vk::Result),
+
+    #[error("Copy of the picked pixel failed with error: {0}.")]
+    CopyFailed(#[from] ImmediateCommandError),
+
+    #[error("Mapping of the readback buffer failed.")]
+    MemoryMappingFailed,
+}
+
+/// Draw of a mesh in the entity ID pass, queued by the render systems during the frame.
+struct EntityIdDraw {
+    entity: Entity,
+    model_view_projection: Mat4,
+    viewport: vk::Viewport,
+    scissor: vk::Rect2D,
+    draw: PositionOnlyDraw,
+}
+
+/// Offscreen pass drawing the index of the entity of every mesh rendering to an `R32_UINT`
+/// attachment the size of the framebuffer, with a depth test so that the closest entity wins.
+///
+/// Draws are queued by the render systems, and recorded at the end of the frame once the primary
+/// render pass is done. The attachment is then left in `TRANSFER_SRC_OPTIMAL` layout, so that a
+/// single pixel can be copied back to the CPU when picking.
+pub(crate) struct EntityIdPass {
+    id_image: AllocatedImage,
+    depth_image: AllocatedImage,
+    render_pass: vk::RenderPass,
+    framebuffer: vk::Framebuffer,
+
+    shader_ref: ThreadSafeRef<Shader>,
+    pipeline_layout: vk::PipelineLayout,
+    pipelines: HashMap<PositionLayout, vk::Pipeline>,
+
+    readback_buffer: AllocatedBuffer,
+    queued_draws: Vec<EntityIdDraw>,
+    /// Entities drawn by the last recorded pass by index, with the index of the frame that
+    /// recorded it (see [`Renderer::current_frame_index`]).
+    last_pass: Option<(HashMap<u32, Entity>, usize)>,
+}
+
+#[profiling::all_functions]
+impl EntityIdPass {
+    pub(crate) fn new(
+        width: u32,
+        height: u32,
+        renderer: &mut Renderer,
+    ) -> Result<Self, EntityPickingCreationError> {
+        let shader_ref = Shader::from_spirv_u8(
+            include_bytes!("shaders/gen/entity_id.vert"),
+            include_bytes!("shaders/gen/entity_id.frag"),
+            &renderer.device,
+        )?;
+
+        let attachments = [
+            vk::AttachmentDescription {
+                format: ENTITY_ID_FORMAT,
+                samples: vk::SampleCountFlags::TYPE_1,
+                load_op: vk::AttachmentLoadOp::CLEAR,
+                store_op: vk::AttachmentStoreOp::STORE,
+                initial_layout: vk::ImageLayout::UNDEFINED,
+                final_layout: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                ..Default::default()
+            },
+            vk::AttachmentDescription {
+                format: renderer.depth_format(),
+                samples: vk::SampleCountFlags::TYPE_1,
+                load_op: vk::AttachmentLoadOp::CLEAR,
+                store_op: vk::AttachmentStoreOp::DONT_CARE,
+                stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+                stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+                initial_layout: vk::ImageLayout::UNDEFINED,
+                final_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+                ..Default::default()
+            },
+        ];
+        let color_attachment_ref = vk::AttachmentReference {
+            attachment: 0,
+            layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        };
+        let depth_attachment_ref = vk::AttachmentReference {
+            attachment: 1,
+            layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        };
+        let subpass_description = vk::SubpassDescription::default()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(std::slice::from_ref(&color_attachment_ref))
+            .depth_stencil_attachment(&depth_attachment_ref);
+        let dependencies = [
+            // The attachments are shared by the frames in flight, the previous pass must be done
+            // writing them, and the last pick done reading them
+            vk::SubpassDependency::default()
+                .src_subpass(vk::SUBPASS_EXTERNAL)
+                .dst_subpass(0)
+                .src_stage_mask(
+                    vk::PipelineStageFlags::TRANSFER
+                        | vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                        | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                )
+                .dst_stage_mask(
+                    vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                        | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+                )
+                .src_access_mask(
+                    vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                )
+                .dst_access_mask(
+                    vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                ),
+            vk::SubpassDependency::default()
+                .src_subpass(0)
+                .dst_subpass(vk::SUBPASS_EXTERNAL)
+                .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .dst_stage_mask(vk::PipelineStageFlags::TRANSFER)
+                .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .dst_access_mask(vk::AccessFlags::TRANSFER_READ),
+        ];
+        let render_pass_info = vk::RenderPassCreateInfo::default()
+            .attachments(&attachments)
+            .subpasses(std::slice::from_ref(&subpass_description))
+            .dependencies(&dependencies);
+        let render_pass = unsafe { renderer.device.create_render_pass(&render_pass_info, None) }
+            .map_err(EntityPickingCreationError::VulkanRenderPassCreationFailed)?;
+
+        let (id_image, depth_image, framebuffer) =
+            Self::create_attachments(width, height, render_pass, renderer)?;
+
+        let push_constant_range = vk::PushConstantRange::default()
+            .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+            .offset(0)
+            .size(std::mem::size_of::<EntityData>().try_into().unwrap());
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::default()
+            .push_constant_ranges(std::slice::from_ref(&push_constant_range));
+        let pipeline_layout = unsafe {
+            renderer
+                .device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+        }
+        .map_err(EntityPickingCreationError::VulkanPipelineLayoutCreationFailed)?;
+
+        let readback_buffer = AllocatedBuffer::builder(std::mem::size_of::<u32>() as u64)
+            .with_usage(vk::BufferUsageFlags::TRANSFER_DST)
+            .with_memory_location(gpu_allocator::MemoryLocation::GpuToCpu)
+            .with_name("Entity picking readback")
+            .build(renderer)?;
+
+        Ok(Self {
+            id_image,
+            depth_image,
+            render_pass,
+            framebuffer,
+            shader_ref,
+            pipeline_layout,
+            pipelines: HashMap::new(),
+            readback_buffer,
+            queued_draws: vec![],
+            last_pass: None,
+        })
+    }
+
+    fn create_attachments(
+        width: u32,
+        height: u32,
+        render_pass: vk::RenderPass,
+        renderer: &mut Renderer,
+    ) -> Result<(AllocatedImage, AllocatedImage, vk::Framebuffer), EntityPickingCreationError> {
+        let attachment_image = |format, usage, aspect_mask, renderer: &mut Renderer| {
+            let mut builder = AllocatedImage::builder(vk::Extent3D {
+                width,
+                height,
+                depth: 1,
+            });
+            builder.image_create_info = builder
+                .image_create_info
+                .image_type(vk::ImageType::TYPE_2D)
+                .format(format)
+                .mip_levels(1)
+                .array_layers(1)
+                .samples(vk::SampleCountFlags::TYPE_1)
+                .tiling(vk::ImageTiling::OPTIMAL)
+                .usage(usage)
+                .sharing_mode(vk::SharingMode::EXCLUSIVE);
+            builder.image_view_create_info = builder
+                .image_view_create_info
+                .view_type(vk::ImageViewType::TYPE_2D)
+                .format(format)
+                .subresource_range(vk::ImageSubresourceRange {
+                    aspect_mask,
+                    base_mip_level: 0,
+                    level_count: 1,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                });
+
+            builder.build_uninitialized(&renderer.device, &mut renderer.allocator())
+        };
+
+        let id_image = attachment_image(
+            ENTITY_ID_FORMAT,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+            vk::ImageAspectFlags::COLOR,
+            renderer,
+        )?;
+        let depth_format = renderer.depth_format();
+        let depth_image = attachment_image(
+            depth_format,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            image_aspect_mask(depth_format),
+            renderer,
+        )?;
+
+        let framebuffer_attachments = [id_image.view, depth_image.view];
+        let framebuffer_info = vk::FramebufferCreateInfo::default()
+            .render_pass(render_pass)
+            .attachments(&framebuffer_attachments)
+            .width(width)
+            .height(height)
+            .layers(1);
+        let framebuffer = unsafe { renderer.device.create_framebuffer(&framebuffer_info, None) }
+            .map_err(EntityPickingCreationError::VulkanFramebufferCreationFailed)?;
+
+        Ok((id_image, depth_image, framebuffer))
+    }
+
+    /// Recreates the attachments at the new size of the framebuffer. The device must be idle.
+    pub(crate) fn resize(
+        &mut self,
+        width: u32,
+        height: u32,
+        renderer: &mut Renderer,
+    ) -> Result<(), EntityPickingCreationError> {
+        self.destroy_attachments(renderer);
+        (self.id_image, self.depth_image, self.framebuffer) =
+            Self::create_attachments(width, height, self.render_pass, renderer)?;
+        self.last_pass = None;
+
+        Ok(())
+    }
+
+    #[profiling::skip]
+    pub(crate) fn dimensions(&self) -> [u32; 2] {
+        [self.id_image.extent.width, self.id_image.extent.height]
+    }
+
+    /// Queues the draw of `index_range` (or of the whole mesh if `None`) for the next pass. Meshes
+    /// whose position (see [`Vertex::position_index`]) is not a `vec3` read from the vertex buffer
+    /// can't be drawn, and are ignored.
+    pub(crate) fn queue_draw<VertexType>(
+        &mut self,
+        entity: Entity,
+        model_view_projection: Mat4,
+        (viewport, scissor): (vk::Viewport, vk::Rect2D),
+        mesh: &Mesh<VertexType>,
+        index_range: Option<Range<u32>>,
+    ) where
+        VertexType: Vertex,
+    {
+        let Some(draw) = mesh.position_only_draw(index_range) else {
+            return;
+        };
+
+        self.queued_draws.push(EntityIdDraw {
+            entity,
+            model_view_projection,
+            viewport,
+            scissor,
+            draw,
+        });
+    }
+
+    /// Records the pass with the draws queued during the frame, outside of any render pass.
+    pub(crate) fn record(
+        &mut self,
+        frame_index: usize,
+        device: &ash::Device,
+        cmd_buffer: vk::CommandBuffer,
+    ) {
+        let [width, height] = self.dimensions();
+        let clear_values = [
+            vk::ClearValue {
+                color: vk::ClearColorValue {
+                    uint32: [NO_ENTITY; 4],
+                },
+            },
+            vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: 1.0,
+                    stencil: 0,
+                },
+            },
+        ];
+        let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+            .render_pass(self.render_pass)
+            .framebuffer(self.framebuffer)
+            .render_area(vk::Rect2D {
+                extent: vk::Extent2D { width, height },
+                ..Default::default()
+            })
+            .clear_values(&clear_values);
+        unsafe {
+            device.cmd_begin_render_pass(
+                cmd_buffer,
+                &render_pass_begin_info,
+                vk::SubpassContents::INLINE,
+            )
+        };
+
+        let mut entities = HashMap::new();
+        for draw in std::mem::take(&mut self.queued_draws) {
+            let pipeline = match self.pipeline(draw.draw.layout, device) {
+                Ok(pipeline) => pipeline,
+                Err(error) => {
+                    log::warn!("Failed to build an entity ID pipeline: {error}");
+                    continue;
+                }
+            };
+            let entity_data = EntityData {
+                model_view_projection: draw.model_view_projection,
+                entity_index: draw.entity.index(),
+                _padding: [0; 3],
+            };
+            entities.insert(draw.entity.index(), draw.entity);
+
+            unsafe {
+                device.cmd_bind_pipeline(cmd_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+                device.cmd_set_viewport(cmd_buffer, 0, std::slice::from_ref(&draw.viewport));
+                device.cmd_set_scissor(cmd_buffer, 0, std::slice::from_ref(&draw.scissor));
+                device.cmd_push_constants(
+                    cmd_buffer,
+                    self.pipeline_layout,
+                    vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                    0,
+                    bytes_of(&entity_data),
+                );
+            }
+            draw.draw.record(device, cmd_buffer);
+        }
+
+        unsafe { device.cmd_end_render_pass(cmd_buffer) };
+        self.id_image.layout = vk::ImageLayout::TRANSFER_SRC_OPTIMAL;
+        self.last_pass = Some((entities, frame_index));
+    }
+
+    fn pipeline(
+        &mut self,
+        vertex_layout: PositionLayout,
+        device: &ash::Device,
+    ) -> Result<vk::Pipeline, PipelineBuildError> {
+        if let Some(pipeline) = self.pipelines.get(&vertex_layout) {
+            return Ok(*pipeline);
+        }
+
+        let binding = vertex_layout.binding_description();
+        let attribute = vertex_layout.attribute_description();
+        let shader_module_entry_point = std::ffi::CString::new("main").unwrap();
+        let shader = self.shader_ref.lock();
+
+        let pipeline = PipelineBuilder {
+            shader_stages: vec![
+                vk::PipelineShaderStageCreateInfo::default()
+                    .stage(vk::ShaderStageFlags::VERTEX)
+                    .module(shader.vertex_module)
+                    .name(&shader_module_entry_point),
+                vk::PipelineShaderStageCreateInfo::default()
+                    .stage(vk::ShaderStageFlags::FRAGMENT)
+                    .module(shader.fragment_module)
+                    .name(&shader_module_entry_point),
+            ],
+            vertex_input_state_info: vk::PipelineVertexInputStateCreateInfo::default()
+                .vertex_binding_descriptions(std::slice::from_ref(&binding))
+                .vertex_attribute_descriptions(std::slice::from_ref(&attribute)),
+            input_assembly_state_info: vk::PipelineInputAssemblyStateCreateInfo::default()
+                .topology(vk::PrimitiveTopology::TRIANGLE_LIST),
+            // Back faces are kept, as the materials of the entities may not cull them either
+            rasterizer_state_info: vk::PipelineRasterizationStateCreateInfo::default()
+                .polygon_mode(vk::PolygonMode::FILL)
+                .cull_mode(vk::CullModeFlags::NONE)
+                .line_width(1.0),
+            multisampling_state_info: vk::PipelineMultisampleStateCreateInfo::default()
+                .rasterization_samples(vk::SampleCountFlags::TYPE_1)
+                .min_sample_shading(1.0),
+            depth_stencil_state_info: vk::PipelineDepthStencilStateCreateInfo::default()
+                .depth_test_enable(true)
+                .depth_write_enable(true)
+                .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL)
+                .min_depth_bounds(0.0)
+                .max_depth_bounds(1.0),
+            // Integer attachments can't be blended
+            color_blend_attachment_state: vk::PipelineColorBlendAttachmentState::default()
+                .blend_enable(false)
+                .color_write_mask(vk::ColorComponentFlags::R),
+            color_attachment_count: 1,
+            subpass: 0,
+            layout: self.pipeline_layout,
+            cache: None,
+        }
+        .build(device, self.render_pass)?;
+        self.pipelines.insert(vertex_layout, pipeline);
+
+        Ok(pipeline)
+    }
+
+    /// Index of the frame that recorded the last pass, which must be done executing before
+    /// reading entities.
+    #[profiling::skip]
+    pub(crate) fn last_frame_index(&self) -> Option<usize> {
+        self.last_pass.as_ref().map(|(_, frame_index)| *frame_index)
+    }
+
+    /// Reads the entity drawn at pixel (`x`, `y`) of the attachment by the last pass.
+    pub(crate) fn entity_at(
+        &self,
+        x: u32,
+        y: u32,
+        renderer: &Renderer,
+    ) -> Result<Option<Entity>, EntityPickingError> {
+        let Some((entities, _)) = &self.last_pass else {
+            return Ok(None);
+        };
+
+        renderer.immediate_command(|cmd_buffer| unsafe {
+            let copy_region = vk::BufferImageCopy::default()
+                .image_subresource(vk::ImageSubresourceLayers {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    mip_level: 0,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                })
+                .image_offset(vk::Offset3D {
+                    x: x.try_into().unwrap(),
+                    y: y.try_into().unwrap(),
+                    z: 0,
+                })
+                .image_extent(vk::Extent3D {
+                    width: 1,
+                    height: 1,
+                    depth: 1,
+                });
+            renderer.device.cmd_copy_image_to_buffer(
+                *cmd_buffer,
+                self.id_image.handle,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                self.readback_buffer.handle,
+                std::slice::from_ref(&copy_region),
+            );
+
+            let readback_barrier = vk::BufferMemoryBarrier::default()
+                .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                .dst_access_mask(vk::AccessFlags::HOST_READ)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .buffer(self.readback_buffer.handle)
+                .size(vk::WHOLE_SIZE);
+            renderer.device.cmd_pipeline_barrier(
+                *cmd_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(),
+                &[],
+                std::slice::from_ref(&readback_barrier),
+                &[],
+            );
+        })?;
+
+        let mapped_slice = self
+            .readback_buffer
+            .allocation
+            .as_ref()
+            .and_then(|allocation| allocation.mapped_slice())
+            .ok_or(EntityPickingError::MemoryMappingFailed)?;
+        let entity_index = u32::from_ne_bytes(
+            mapped_slice[..std::mem::size_of::<u32>()]
+                .try_into()
+                .unwrap(),
+        );
+
+        Ok(entities.get(&entity_index).copied())
+    }
+
+    fn destroy_attachments(&mut self, renderer: &mut Renderer) {
+        unsafe { renderer.device.destroy_framebuffer(self.framebuffer, None) };
+        self.depth_image.destroy(renderer);
+        self.id_image.destroy(renderer);
+    }
+
+    pub(crate) fn destroy(&mut self, renderer: &mut Renderer) {
+        self.destroy_attachments(renderer);
+        self.readback_buffer
+            .destroy(&renderer.device, &mut renderer.allocator());
+
+        unsafe {
+            for (_, pipeline) in self.pipelines.drain() {
+                renderer.device.destroy_pipeline(pipeline, None);
+            }
+            renderer
+                .device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+            renderer.device.destroy_render_pass(self.render_pass, None);
+        }
+        self.shader_ref.lock().destroy(&renderer.device);
+    }
+}
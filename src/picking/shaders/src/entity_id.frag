@@ -0,0 +1,13 @@
+#version 450
+
+layout(push_constant) uniform EntityData {
+    mat4 modelViewProjection;
+    uint entityIndex;
+}
+pc_EntityData;
+
+layout(location = 0) out uint f_EntityIndex;
+
+void main() {
+    f_EntityIndex = pc_EntityData.entityIndex;
+}
@@ -0,0 +1,13 @@
+#version 450
+
+layout(location = 0) in vec3 v_Position;
+
+layout(push_constant) uniform EntityData {
+    mat4 modelViewProjection;
+    uint entityIndex;
+}
+pc_EntityData;
+
+void main() {
+    gl_Position = pc_EntityData.modelViewProjection * vec4(v_Position, 1);
+}
@@ -8,3 +8,38 @@ pub struct PipelineBarrier<'a> {
     pub buffer_memory_barriers: Vec<vk::BufferMemoryBarrier<'a>>,
     pub image_memory_barriers: Vec<vk::ImageMemoryBarrier<'a>>,
 }
+
+impl Default for PipelineBarrier<'_> {
+    fn default() -> Self {
+        Self {
+            src_stage_mask: vk::PipelineStageFlags::empty(),
+            dst_stage_mask: vk::PipelineStageFlags::empty(),
+            dependency_flags: vk::DependencyFlags::empty(),
+            memory_barriers: vec![],
+            buffer_memory_barriers: vec![],
+            image_memory_barriers: vec![],
+        }
+    }
+}
+
+impl PipelineBarrier<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.memory_barriers.is_empty()
+            && self.buffer_memory_barriers.is_empty()
+            && self.image_memory_barriers.is_empty()
+    }
+
+    pub(crate) fn record(&self, device: &ash::Device, cmd_buffer: vk::CommandBuffer) {
+        unsafe {
+            device.cmd_pipeline_barrier(
+                cmd_buffer,
+                self.src_stage_mask,
+                self.dst_stage_mask,
+                self.dependency_flags,
+                &self.memory_barriers,
+                &self.buffer_memory_barriers,
+                &self.image_memory_barriers,
+            )
+        };
+    }
+}
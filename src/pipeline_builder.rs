@@ -1,6 +1,8 @@
 use ash::vk;
 use thiserror::Error;
 
+use crate::resource_registry::{self, ResourceKind};
+
 pub(crate) struct PipelineBuilder<'a> {
     pub(crate) shader_stages: Vec<vk::PipelineShaderStageCreateInfo<'a>>,
     pub(crate) vertex_input_state_info: vk::PipelineVertexInputStateCreateInfo<'a>,  
@@ -9,6 +11,9 @@ pub(crate) struct PipelineBuilder<'a> {
     pub(crate) multisampling_state_info: vk::PipelineMultisampleStateCreateInfo<'a>,
     pub(crate) depth_stencil_state_info: vk::PipelineDepthStencilStateCreateInfo<'a>,
     pub(crate) color_blend_attachment_state: vk::PipelineColorBlendAttachmentState,
+    /// The blend state is used for every color attachment of the subpass.
+    pub(crate) color_attachment_count: u32,
+    pub(crate) subpass: u32,
     pub(crate) layout: vk::PipelineLayout,
     pub(crate) cache: Option<vk::PipelineCache>,
 }
@@ -29,9 +34,11 @@ impl PipelineBuilder<'_> {
             .viewport_count(1)
             .scissor_count(1);
 
+        let color_blend_attachment_states =
+            vec![self.color_blend_attachment_state; self.color_attachment_count as usize];
         let color_blend_info = vk::PipelineColorBlendStateCreateInfo::default()
             .logic_op_enable(false)
-            .attachments(std::slice::from_ref(&self.color_blend_attachment_state));
+            .attachments(&color_blend_attachment_states);
 
         let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
         let dynamic_state_info =
@@ -49,7 +56,7 @@ impl PipelineBuilder<'_> {
             .dynamic_state(&dynamic_state_info)
             .layout(self.layout)
             .render_pass(render_pass)
-            .subpass(0);
+            .subpass(self.subpass);
 
         let result = unsafe {
             device.create_graphics_pipelines(
@@ -60,7 +67,10 @@ impl PipelineBuilder<'_> {
         };
 
         match result {
-            Ok(pipelines) => Ok(pipelines[0]),
+            Ok(pipelines) => {
+                resource_registry::register(ResourceKind::Pipeline, 1);
+                Ok(pipelines[0])
+            }
             Err((_, result)) => Err(result.into()),
         }
     }
@@ -87,7 +97,10 @@ impl ComputePipelineBuilder<'_> {
         };
 
         match result {
-            Ok(pipelines) => Ok(pipelines[0]),
+            Ok(pipelines) => {
+                resource_registry::register(ResourceKind::Pipeline, 1);
+                Ok(pipelines[0])
+            }
             Err((_, error)) => Err(error.into()),
         }
     }
@@ -0,0 +1,152 @@
+use ash::vk;
+use thiserror::Error;
+
+/// Statistics collected by [`PipelineStatistics`], in the order Vulkan writes their results.
+const COLLECTED_STATISTICS: [vk::QueryPipelineStatisticFlags; 7] = [
+    vk::QueryPipelineStatisticFlags::INPUT_ASSEMBLY_VERTICES,
+    vk::QueryPipelineStatisticFlags::INPUT_ASSEMBLY_PRIMITIVES,
+    vk::QueryPipelineStatisticFlags::VERTEX_SHADER_INVOCATIONS,
+    vk::QueryPipelineStatisticFlags::CLIPPING_INVOCATIONS,
+    vk::QueryPipelineStatisticFlags::CLIPPING_PRIMITIVES,
+    vk::QueryPipelineStatisticFlags::FRAGMENT_SHADER_INVOCATIONS,
+    vk::QueryPipelineStatisticFlags::COMPUTE_SHADER_INVOCATIONS,
+];
+
+/// Counters of the work done by the GPU during a frame. Implementations are allowed to report
+/// approximate values for some of them (e.g. fragment shader invocations with early depth tests).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PipelineStats {
+    /// Vertices read by the input assembler, including the ones shared by several primitives.
+    pub input_assembly_vertices: u64,
+    pub input_assembly_primitives: u64,
+    pub vertex_shader_invocations: u64,
+    /// Primitives that reached the clipping stage, after culling by earlier stages.
+    pub clipping_invocations: u64,
+    /// Primitives output by the clipping stage, which are the ones actually rasterized.
+    pub clipping_primitives: u64,
+    /// Comparing this with the framebuffer's pixel count gives an idea of the frame's overdraw.
+    pub fragment_shader_invocations: u64,
+    /// Only counts the dispatches recorded in the frame's command buffer.
+    pub compute_shader_invocations: u64,
+}
+
+impl PipelineStats {
+    fn from_results(results: &[u64; COLLECTED_STATISTICS.len()]) -> Self {
+        Self {
+            input_assembly_vertices: results[0],
+            input_assembly_primitives: results[1],
+            vertex_shader_invocations: results[2],
+            clipping_invocations: results[3],
+            clipping_primitives: results[4],
+            fragment_shader_invocations: results[5],
+            compute_shader_invocations: results[6],
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum PipelineStatisticsCreationError {
+    #[error("The selected device does not support the pipeline_statistics_query feature.")]
+    PipelineStatisticsUnsupported,
+
+    #[error("Vulkan creation of the pipeline statistics query pool failed with result: {0}.")]
+    VulkanQueryPoolCreationFailed(vk::Result),
+}
+
+/// Counts the work done by the GPU over whole frames using a pipeline statistics query.
+///
+/// The query is reset and begun at the start of every frame, before the primary render pass, and
+/// ended once the render pass is done. Its results are read back at the start of the next frame
+/// (once the GPU is done with the previous one), and are available through
+/// [`crate::renderer::Renderer::pipeline_stats`].
+pub struct PipelineStatistics {
+    query_pool: vk::QueryPool,
+    /// Whether the query was recorded in the previous frame, and therefore has results.
+    query_recorded: bool,
+
+    last_frame_stats: PipelineStats,
+}
+
+#[profiling::all_functions]
+impl PipelineStatistics {
+    pub(crate) fn new(
+        device: &ash::Device,
+        enabled_features: &vk::PhysicalDeviceFeatures,
+    ) -> Result<Self, PipelineStatisticsCreationError> {
+        if enabled_features.pipeline_statistics_query != vk::TRUE {
+            return Err(PipelineStatisticsCreationError::PipelineStatisticsUnsupported);
+        }
+
+        let statistics = COLLECTED_STATISTICS
+            .into_iter()
+            .fold(vk::QueryPipelineStatisticFlags::empty(), |flags, flag| {
+                flags | flag
+            });
+        let query_pool_info = vk::QueryPoolCreateInfo::default()
+            .query_type(vk::QueryType::PIPELINE_STATISTICS)
+            .query_count(1)
+            .pipeline_statistics(statistics);
+        let query_pool = unsafe { device.create_query_pool(&query_pool_info, None) }
+            .map_err(PipelineStatisticsCreationError::VulkanQueryPoolCreationFailed)?;
+
+        Ok(Self {
+            query_pool,
+            query_recorded: false,
+            last_frame_stats: PipelineStats::default(),
+        })
+    }
+
+    #[profiling::skip]
+    pub fn last_frame_stats(&self) -> PipelineStats {
+        self.last_frame_stats
+    }
+
+    /// Reads back the statistics of the previous frame, then resets and begins the query. The
+    /// previous frame must be done executing, and the command buffer must be outside of any render
+    /// pass.
+    pub(crate) fn begin_frame(&mut self, device: &ash::Device, cmd_buffer: vk::CommandBuffer) {
+        self.collect_results(device);
+
+        unsafe {
+            device.cmd_reset_query_pool(cmd_buffer, self.query_pool, 0, 1);
+            device.cmd_begin_query(
+                cmd_buffer,
+                self.query_pool,
+                0,
+                vk::QueryControlFlags::empty(),
+            );
+        }
+    }
+
+    /// Ends the frame's query, the command buffer must be outside of any render pass.
+    pub(crate) fn end_frame(&mut self, device: &ash::Device, cmd_buffer: vk::CommandBuffer) {
+        unsafe { device.cmd_end_query(cmd_buffer, self.query_pool, 0) };
+        self.query_recorded = true;
+    }
+
+    fn collect_results(&mut self, device: &ash::Device) {
+        if !self.query_recorded {
+            return;
+        }
+        self.query_recorded = false;
+
+        let mut results = [[0_u64; COLLECTED_STATISTICS.len()]];
+        if let Err(error) = unsafe {
+            device.get_query_pool_results(
+                self.query_pool,
+                0,
+                &mut results,
+                vk::QueryResultFlags::TYPE_64 | vk::QueryResultFlags::WAIT,
+            )
+        } {
+            log::warn!("Failed to retrieve pipeline statistics: {error}");
+            return;
+        }
+
+        self.last_frame_stats = PipelineStats::from_results(&results[0]);
+    }
+
+    pub(crate) fn destroy(&mut self, device: &ash::Device) {
+        unsafe { device.destroy_query_pool(self.query_pool, None) };
+    }
+}
@@ -0,0 +1,67 @@
+use thiserror::Error;
+
+use crate::{
+    descriptor_resources::DescriptorResources,
+    material::{Material, MaterialBuildError, MaterialBuilder, PrimitiveTopology},
+    math_types::Vec3,
+    mesh::{Mesh, UploadError},
+    renderer::Renderer,
+    shader::{Shader, ShaderBuildError},
+    utils::ThreadSafeRef,
+};
+
+pub use crate::vertices::point::PointVertex;
+
+/// A non-indexed mesh of points. Render it with a [`MeshRendering`](crate::components::mesh_rendering::MeshRendering)
+/// using [`default_point_cloud_material`] (or any material built with [`PrimitiveTopology::POINT_LIST`]),
+/// and register `render_meshes::<PointVertex>` in the ECS schedule.
+pub type PointCloud = Mesh<PointVertex>;
+
+#[derive(Error, Debug)]
+pub enum PointCloudMaterialBuildError {
+    #[error("Creation of point cloud shader failed with error: {0}.")]
+    ShaderCreationFailed(#[from] ShaderBuildError),
+
+    #[error("Creation of point cloud material failed with error: {0}.")]
+    MaterialCreationFailed(#[from] MaterialBuildError),
+}
+
+#[profiling::all_functions]
+impl Mesh<PointVertex> {
+    /// Creates a point cloud of white, single pixel points.
+    ///
+    /// The points are uploaded in a single staging copy, so this is suitable for large clouds.
+    pub fn from_positions(
+        positions: &[Vec3],
+        renderer: &mut Renderer,
+    ) -> Result<ThreadSafeRef<Self>, UploadError> {
+        let vertices = positions
+            .iter()
+            .map(|position| PointVertex {
+                position: *position,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+
+        Self::from_vertices(vertices, renderer)
+    }
+}
+
+/// Builds a material drawing each vertex as a point of the vertex's color and size.
+///
+/// Sizes above 1 pixel require the `large_points` device feature, which is enabled by the
+/// renderer when available. The caller owns the returned material and its shader.
+pub fn default_point_cloud_material(
+    renderer: &mut Renderer,
+) -> Result<ThreadSafeRef<Material<PointVertex>>, PointCloudMaterialBuildError> {
+    let shader = Shader::from_spirv_u8(
+        include_bytes!("shaders/gen/point_cloud.vert"),
+        include_bytes!("shaders/gen/point_cloud.frag"),
+        &renderer.device,
+    )?;
+
+    Ok(MaterialBuilder::new()
+        .topology(PrimitiveTopology::POINT_LIST)
+        .cull_mode(ash::vk::CullModeFlags::NONE)
+        .build(&shader, DescriptorResources::empty(), renderer)?)
+}
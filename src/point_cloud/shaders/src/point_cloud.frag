@@ -0,0 +1,9 @@
+#version 450
+
+layout(location = 0) in vec4 vs_Color;
+
+layout(location = 0) out vec4 f_Color;
+
+void main() {
+    f_Color = vs_Color;
+}
@@ -0,0 +1,524 @@
+use crate::{
+    allocated_types::{AllocatedImage, AllocatedImageBuilder, ImageBuildError},
+    renderer::{image_aspect_mask, Renderer},
+    texture::Texture,
+    utils::{ImmediateCommandError, ThreadSafeRef},
+};
+
+use ash::{prelude::VkResult, vk};
+use thiserror::Error;
+
+/// Offscreen color and depth attachments, with a matching render pass and framebuffer.
+///
+/// The result of a pass is always available as a single-sampled texture, [`RenderTarget::texture_ref`],
+/// which is left in `SHADER_READ_ONLY_OPTIMAL` layout at the end of the pass so it can be sampled
+/// (or copied from, its image also has the `TRANSFER_SRC` usage).
+/// When the target is multisampled, rendering happens in transient multisampled attachments that
+/// are resolved into that texture at the end of the pass.
+///
+/// Targets created with [`RenderTarget::depth_only`] have no color attachment at all, their
+/// texture is the depth attachment itself.
+pub struct RenderTarget {
+    pub texture_ref: ThreadSafeRef<Texture>,
+    multisampled_color_image: Option<AllocatedImage>,
+    /// None for depth-only targets, where the depth image is owned by the texture.
+    depth_image: Option<AllocatedImage>,
+
+    samples: vk::SampleCountFlags,
+    pub(crate) render_pass: vk::RenderPass,
+    pub(crate) framebuffer: vk::Framebuffer,
+}
+
+#[derive(Error, Debug)]
+pub enum RenderTargetCreationError {
+    #[error(
+        "Sample count {requested:?} is not supported for this format (supported: {supported:?})."
+    )]
+    UnsupportedSampleCount {
+        requested: vk::SampleCountFlags,
+        supported: vk::SampleCountFlags,
+    },
+
+    #[error("Format {0:?} cannot be used as a color attachment by the selected device.")]
+    UnsupportedFormat(vk::Format),
+
+    #[error("Creation of a render target image failed with error: {0}.")]
+    ImageCreationFailed(#[from] ImageBuildError),
+
+    #[error("Initial layout transition of the render target failed with error: {0}.")]
+    LayoutTransitionFailed(#[from] ImmediateCommandError),
+
+    #[error("Vulkan creation of the render target's sampler failed with result: {0}.")]
+    VulkanSamplerCreationFailed(vk::Result),
+
+    #[error("Vulkan creation of the render target's render pass failed with result: {0}.")]
+    VulkanRenderPassCreationFailed(vk::Result),
+
+    #[error("Vulkan creation of the render target's framebuffer failed with result: {0}.")]
+    VulkanFramebufferCreationFailed(vk::Result),
+}
+
+/// Format of the depth targets (see [`RenderTarget::depth_only`]), which are sampled and have their
+/// own pipelines. Color targets use [`Renderer::depth_format`] instead, so that materials built for
+/// the primary render pass can draw to them.
+pub(crate) const DEPTH_ONLY_FORMAT: vk::Format = vk::Format::D32_SFLOAT;
+
+fn attachment_image_builder<'a>(
+    width: u32,
+    height: u32,
+    format: vk::Format,
+    samples: vk::SampleCountFlags,
+    usage: vk::ImageUsageFlags,
+    aspect_mask: vk::ImageAspectFlags,
+) -> AllocatedImageBuilder<'a> {
+    let mut builder = AllocatedImage::builder(vk::Extent3D {
+        width,
+        height,
+        depth: 1,
+    });
+    builder.image_create_info = builder
+        .image_create_info
+        .image_type(vk::ImageType::TYPE_2D)
+        .format(format)
+        .mip_levels(1)
+        .array_layers(1)
+        .samples(samples)
+        .tiling(vk::ImageTiling::OPTIMAL)
+        .usage(usage)
+        .sharing_mode(vk::SharingMode::EXCLUSIVE);
+    builder.image_view_create_info = builder
+        .image_view_create_info
+        .view_type(vk::ImageViewType::TYPE_2D)
+        .format(format)
+        .subresource_range(vk::ImageSubresourceRange {
+            aspect_mask,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        });
+
+    builder
+}
+
+/// The texture might be bound before anything is rendered to it, so it starts in the layout it is
+/// left in at the end of every pass. All the layers of the image are transitioned.
+pub(crate) fn transition_to_shader_read(
+    image: &mut AllocatedImage,
+    aspect_mask: vk::ImageAspectFlags,
+    renderer: &mut Renderer,
+) -> Result<(), ImmediateCommandError> {
+    renderer.immediate_command(|cmd_buffer| {
+        let barrier = vk::ImageMemoryBarrier::default()
+            .src_access_mask(vk::AccessFlags::NONE)
+            .dst_access_mask(vk::AccessFlags::SHADER_READ)
+            .old_layout(vk::ImageLayout::UNDEFINED)
+            .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .image(image.handle)
+            .subresource_range(
+                vk::ImageSubresourceRange::default()
+                    .aspect_mask(aspect_mask)
+                    .level_count(1)
+                    .layer_count(vk::REMAINING_ARRAY_LAYERS),
+            );
+        unsafe {
+            renderer.device.cmd_pipeline_barrier(
+                *cmd_buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                std::slice::from_ref(&barrier),
+            )
+        };
+    })?;
+    image.layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+
+    Ok(())
+}
+
+/// Render pass of a single `DEPTH_ONLY_FORMAT` attachment, cleared and left in
+/// `SHADER_READ_ONLY_OPTIMAL` layout to be sampled afterwards.
+pub(crate) fn depth_only_render_pass(device: &ash::Device) -> VkResult<vk::RenderPass> {
+    let depth_attachment = vk::AttachmentDescription {
+        format: DEPTH_ONLY_FORMAT,
+        samples: vk::SampleCountFlags::TYPE_1,
+        load_op: vk::AttachmentLoadOp::CLEAR,
+        store_op: vk::AttachmentStoreOp::STORE,
+        stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+        stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+        initial_layout: vk::ImageLayout::UNDEFINED,
+        final_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        ..Default::default()
+    };
+    let depth_attachment_ref = vk::AttachmentReference {
+        attachment: 0,
+        layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+    };
+    let subpass_description = vk::SubpassDescription::default()
+        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+        .depth_stencil_attachment(&depth_attachment_ref);
+
+    let depth_test_stages =
+        vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS;
+    let dependencies = [
+        vk::SubpassDependency::default()
+            .src_subpass(vk::SUBPASS_EXTERNAL)
+            .dst_subpass(0)
+            .src_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+            .dst_stage_mask(depth_test_stages)
+            .src_access_mask(vk::AccessFlags::SHADER_READ)
+            .dst_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE),
+        vk::SubpassDependency::default()
+            .src_subpass(0)
+            .dst_subpass(vk::SUBPASS_EXTERNAL)
+            .src_stage_mask(depth_test_stages)
+            .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+            .src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+            .dst_access_mask(vk::AccessFlags::SHADER_READ),
+    ];
+
+    let render_pass_info = vk::RenderPassCreateInfo::default()
+        .attachments(std::slice::from_ref(&depth_attachment))
+        .subpasses(std::slice::from_ref(&subpass_description))
+        .dependencies(&dependencies);
+    unsafe { device.create_render_pass(&render_pass_info, None) }
+}
+
+#[profiling::all_functions]
+impl RenderTarget {
+    pub fn new(
+        width: u32,
+        height: u32,
+        format: vk::Format,
+        renderer: &mut Renderer,
+    ) -> Result<ThreadSafeRef<Self>, RenderTargetCreationError> {
+        Self::new_with_samples(
+            width,
+            height,
+            format,
+            vk::SampleCountFlags::TYPE_1,
+            renderer,
+        )
+    }
+
+    /// Creates a render target rendering with `samples` samples per pixel, independently of the
+    /// swapchain (e.g. 1 for a shadow map, 4 or 8 for a high quality offscreen scene).
+    ///
+    /// The sample count must be supported by the device for both the color format and the depth
+    /// attachment, see `framebuffer_color_sample_counts` in [`Renderer::capabilities`].
+    pub fn new_with_samples(
+        width: u32,
+        height: u32,
+        format: vk::Format,
+        samples: vk::SampleCountFlags,
+        renderer: &mut Renderer,
+    ) -> Result<ThreadSafeRef<Self>, RenderTargetCreationError> {
+        let is_multisampled = samples != vk::SampleCountFlags::TYPE_1;
+        let color_usage = vk::ImageUsageFlags::COLOR_ATTACHMENT;
+
+        let format_properties = unsafe {
+            renderer
+                .instance
+                .get_physical_device_image_format_properties(
+                    renderer.physical_device,
+                    format,
+                    vk::ImageType::TYPE_2D,
+                    vk::ImageTiling::OPTIMAL,
+                    color_usage | vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_SRC,
+                    vk::ImageCreateFlags::empty(),
+                )
+        }
+        .map_err(|_| RenderTargetCreationError::UnsupportedFormat(format))?;
+        let limits = renderer.capabilities().limits;
+        let supported_samples = format_properties.sample_counts
+            & limits.framebuffer_color_sample_counts
+            & limits.framebuffer_depth_sample_counts;
+        if samples.as_raw().count_ones() != 1 || !supported_samples.contains(samples) {
+            return Err(RenderTargetCreationError::UnsupportedSampleCount {
+                requested: samples,
+                supported: supported_samples,
+            });
+        }
+
+        let mut resolved_image = attachment_image_builder(
+            width,
+            height,
+            format,
+            vk::SampleCountFlags::TYPE_1,
+            color_usage | vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_SRC,
+            vk::ImageAspectFlags::COLOR,
+        )
+        .build_uninitialized(&renderer.device, &mut renderer.allocator())?;
+        let multisampled_color_image = is_multisampled
+            .then(|| {
+                attachment_image_builder(
+                    width,
+                    height,
+                    format,
+                    samples,
+                    color_usage | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+                    vk::ImageAspectFlags::COLOR,
+                )
+                .build_uninitialized(&renderer.device, &mut renderer.allocator())
+            })
+            .transpose()?;
+        let depth_format = renderer.depth_format();
+        let depth_image = attachment_image_builder(
+            width,
+            height,
+            depth_format,
+            samples,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            image_aspect_mask(depth_format),
+        )
+        .build_uninitialized(&renderer.device, &mut renderer.allocator())?;
+
+        transition_to_shader_read(&mut resolved_image, vk::ImageAspectFlags::COLOR, renderer)?;
+
+        let color_attachment = vk::AttachmentDescription {
+            format,
+            samples,
+            load_op: vk::AttachmentLoadOp::CLEAR,
+            store_op: if is_multisampled {
+                vk::AttachmentStoreOp::DONT_CARE
+            } else {
+                vk::AttachmentStoreOp::STORE
+            },
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            final_layout: if is_multisampled {
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+            } else {
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+            },
+            ..Default::default()
+        };
+        let depth_attachment = vk::AttachmentDescription {
+            format: depth_format,
+            samples,
+            load_op: vk::AttachmentLoadOp::CLEAR,
+            store_op: vk::AttachmentStoreOp::DONT_CARE,
+            stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+            stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            final_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            ..Default::default()
+        };
+        let resolve_attachment = vk::AttachmentDescription {
+            format,
+            samples: vk::SampleCountFlags::TYPE_1,
+            load_op: vk::AttachmentLoadOp::DONT_CARE,
+            store_op: vk::AttachmentStoreOp::STORE,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            final_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            ..Default::default()
+        };
+
+        let mut attachments = vec![color_attachment, depth_attachment];
+        if is_multisampled {
+            attachments.push(resolve_attachment);
+        }
+
+        let color_attachment_ref = vk::AttachmentReference {
+            attachment: 0,
+            layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        };
+        let depth_attachment_ref = vk::AttachmentReference {
+            attachment: 1,
+            layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        };
+        let resolve_attachment_ref = vk::AttachmentReference {
+            attachment: 2,
+            layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        };
+        let mut subpass_description = vk::SubpassDescription::default()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(std::slice::from_ref(&color_attachment_ref))
+            .depth_stencil_attachment(&depth_attachment_ref);
+        if is_multisampled {
+            subpass_description = subpass_description
+                .resolve_attachments(std::slice::from_ref(&resolve_attachment_ref));
+        }
+
+        let dependencies = [
+            // Previous sampling of the texture must be done before it's overwritten
+            vk::SubpassDependency::default()
+                .src_subpass(vk::SUBPASS_EXTERNAL)
+                .dst_subpass(0)
+                .src_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+                .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .src_access_mask(vk::AccessFlags::SHADER_READ)
+                .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE),
+            vk::SubpassDependency::default()
+                .src_subpass(0)
+                .dst_subpass(vk::SUBPASS_EXTERNAL)
+                .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+                .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .dst_access_mask(vk::AccessFlags::SHADER_READ),
+        ];
+
+        let render_pass_info = vk::RenderPassCreateInfo::default()
+            .attachments(&attachments)
+            .subpasses(std::slice::from_ref(&subpass_description))
+            .dependencies(&dependencies);
+        let render_pass = unsafe { renderer.device.create_render_pass(&render_pass_info, None) }
+            .map_err(RenderTargetCreationError::VulkanRenderPassCreationFailed)?;
+
+        let framebuffer_attachments = match &multisampled_color_image {
+            Some(multisampled_color_image) => vec![
+                multisampled_color_image.view,
+                depth_image.view,
+                resolved_image.view,
+            ],
+            None => vec![resolved_image.view, depth_image.view],
+        };
+        let framebuffer_info = vk::FramebufferCreateInfo::default()
+            .render_pass(render_pass)
+            .attachments(&framebuffer_attachments)
+            .width(width)
+            .height(height)
+            .layers(1);
+        let framebuffer = unsafe { renderer.device.create_framebuffer(&framebuffer_info, None) }
+            .map_err(RenderTargetCreationError::VulkanFramebufferCreationFailed)?;
+
+        let texture = Texture::from_image(resolved_image, renderer)
+            .map_err(RenderTargetCreationError::VulkanSamplerCreationFailed)?;
+
+        Ok(ThreadSafeRef::new(Self {
+            texture_ref: ThreadSafeRef::new(texture),
+            multisampled_color_image,
+            depth_image: Some(depth_image),
+            samples,
+            render_pass,
+            framebuffer,
+        }))
+    }
+
+    /// Creates a target with a single depth attachment and no color one, for passes that only
+    /// write depth such as shadow maps. [`RenderTarget::texture_ref`] samples the depth values.
+    ///
+    /// Materials drawing into it must not write any color output, and
+    /// [`RenderTarget::begin_render_pass`] ignores its clear color.
+    pub fn depth_only(
+        width: u32,
+        height: u32,
+        renderer: &mut Renderer,
+    ) -> Result<ThreadSafeRef<Self>, RenderTargetCreationError> {
+        let mut depth_image = attachment_image_builder(
+            width,
+            height,
+            DEPTH_ONLY_FORMAT,
+            vk::SampleCountFlags::TYPE_1,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            vk::ImageAspectFlags::DEPTH,
+        )
+        .build_uninitialized(&renderer.device, &mut renderer.allocator())?;
+        transition_to_shader_read(&mut depth_image, vk::ImageAspectFlags::DEPTH, renderer)?;
+
+        let render_pass = depth_only_render_pass(&renderer.device)
+            .map_err(RenderTargetCreationError::VulkanRenderPassCreationFailed)?;
+
+        let framebuffer_info = vk::FramebufferCreateInfo::default()
+            .render_pass(render_pass)
+            .attachments(std::slice::from_ref(&depth_image.view))
+            .width(width)
+            .height(height)
+            .layers(1);
+        let framebuffer = unsafe { renderer.device.create_framebuffer(&framebuffer_info, None) }
+            .map_err(RenderTargetCreationError::VulkanFramebufferCreationFailed)?;
+
+        let texture = Texture::from_image(depth_image, renderer)
+            .map_err(RenderTargetCreationError::VulkanSamplerCreationFailed)?;
+
+        Ok(ThreadSafeRef::new(Self {
+            texture_ref: ThreadSafeRef::new(texture),
+            multisampled_color_image: None,
+            depth_image: None,
+            samples: vk::SampleCountFlags::TYPE_1,
+            render_pass,
+            framebuffer,
+        }))
+    }
+
+    #[profiling::skip]
+    pub fn is_depth_only(&self) -> bool {
+        self.depth_image.is_none()
+    }
+
+    #[profiling::skip]
+    pub fn dimensions(&self) -> [u32; 2] {
+        self.texture_ref.lock().dimensions
+    }
+
+    #[profiling::skip]
+    pub fn samples(&self) -> vk::SampleCountFlags {
+        self.samples
+    }
+
+    /// Render pass of the target, materials drawing into it must be built against it (see
+    /// [`crate::material::MaterialBuilder::for_render_target`]).
+    #[profiling::skip]
+    pub fn render_pass(&self) -> vk::RenderPass {
+        self.render_pass
+    }
+
+    /// Records the beginning of the target's render pass, clearing it to `clear_color` (and the
+    /// depth to 1.0).
+    pub fn begin_render_pass(
+        &self,
+        cmd_buffer: vk::CommandBuffer,
+        clear_color: [f32; 4],
+        device: &ash::Device,
+    ) {
+        let [width, height] = self.dimensions();
+        let color_clear_value = vk::ClearValue {
+            color: vk::ClearColorValue {
+                float32: clear_color,
+            },
+        };
+        let depth_clear_value = vk::ClearValue {
+            depth_stencil: vk::ClearDepthStencilValue {
+                depth: 1.0,
+                stencil: 0,
+            },
+        };
+        let clear_values = if self.is_depth_only() {
+            vec![depth_clear_value]
+        } else {
+            vec![color_clear_value, depth_clear_value]
+        };
+        let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+            .render_pass(self.render_pass)
+            .framebuffer(self.framebuffer)
+            .render_area(vk::Rect2D {
+                extent: vk::Extent2D { width, height },
+                ..Default::default()
+            })
+            .clear_values(&clear_values);
+
+        unsafe {
+            device.cmd_begin_render_pass(
+                cmd_buffer,
+                &render_pass_begin_info,
+                vk::SubpassContents::INLINE,
+            )
+        };
+    }
+
+    pub fn destroy(&mut self, renderer: &mut Renderer) {
+        unsafe {
+            renderer.device.destroy_framebuffer(self.framebuffer, None);
+            renderer.device.destroy_render_pass(self.render_pass, None);
+        }
+
+        if let Some(mut multisampled_color_image) = self.multisampled_color_image.take() {
+            multisampled_color_image.destroy(renderer);
+        }
+        if let Some(mut depth_image) = self.depth_image.take() {
+            depth_image.destroy(renderer);
+        }
+        self.texture_ref.lock().destroy(renderer);
+    }
+}
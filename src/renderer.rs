@@ -1,15 +1,37 @@
 use crate::{
     allocated_types::{AllocatedBuffer, AllocatedBufferBuilder, AllocatedImage},
+    capabilities::Capabilities,
+    capture::{CaptureError, ImageData},
+    components::light::{GpuLight, LIGHTS_BINDING, MAX_LIGHTS},
+    descriptor_set_cache::DescriptorSetCache,
+    dynamic_uniforms::{
+        DynamicUniformBuffer, DynamicUniformError, DEFAULT_DYNAMIC_UNIFORM_CAPACITY,
+    },
+    gpu_profiler::{GpuProfiler, GpuScope, GpuZone},
     math_types::Vec4,
+    picking::{EntityIdPass, EntityPickingError},
+    pipeline_barrier::PipelineBarrier,
+    pipeline_statistics::{PipelineStatistics, PipelineStats},
+    render_target::RenderTarget,
+    resource_registry::{self, ResourceCounts, ResourceKind},
+    sampler_cache::SamplerCache,
+    staging_pool::StagingPoolStats,
     texture::Texture,
-    utils::{CommandUploader, ImmediateCommandError, ThreadSafeRef},
+    utils::{
+        AsyncCommandError, AsyncCommandSubmitter, CommandUploader, ImmediateCommandError,
+        ThreadSafeRef,
+    },
 };
 
+#[cfg(feature = "bindless")]
+use crate::bindless::{self, BindlessTextureError, BindlessTextures};
+
 use ash::{
     ext, khr,
     vk::{self, PhysicalDeviceType},
     Entry, Instance,
 };
+use bevy_ecs::entity::Entity;
 use gpu_allocator::{
     vulkan::{Allocator, AllocatorCreateDesc},
     AllocationSizes,
@@ -18,10 +40,12 @@ use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
 use winit::window::Window;
 
 use std::{
-    cmp::Ordering,
+    collections::HashMap,
     ffi::{CStr, CString},
     mem,
+    path::Path,
     sync::MutexGuard,
+    time::Duration,
 };
 
 #[cfg(debug_assertions)]
@@ -84,23 +108,47 @@ pub struct QueueInfo {
     pub family_index: u32,
 }
 
-struct SurfaceInfo {
+pub(crate) struct SurfaceInfo {
     handle: vk::SurfaceKHR,
     format: vk::SurfaceFormatKHR,
     loader: khr::surface::Instance,
 }
 
-struct SwapchainInfo {
-    handle: vk::SwapchainKHR,
-    #[allow(dead_code)] // Unused for now, but need to keep these alive
+pub(crate) struct SwapchainInfo {
+    pub(crate) handle: vk::SwapchainKHR,
     images: Vec<vk::Image>,
     image_views: Vec<vk::ImageView>,
     depth_image: AllocatedImage,
     preferred_present_mode: vk::PresentModeKHR,
-    loader: khr::swapchain::Device,
-    extent: vk::Extent2D,
+    fallback_present_mode: vk::PresentModeKHR,
+    /// Mode selected among the preferred and fallback ones, see [`Renderer::active_present_mode`].
+    present_mode: vk::PresentModeKHR,
+    /// Whether the images can be copied from, see [`Renderer::request_capture`].
+    capturable: bool,
+    /// Whether each image was already rendered to, images that weren't are still in `UNDEFINED`
+    /// layout, and can't be loaded (see [`ClearConfig`]).
+    rendered_images: Vec<bool>,
+    pub(crate) loader: khr::swapchain::Device,
+    pub(crate) extent: vk::Extent2D,
 }
 
+/// Candidate formats of the depth attachment of the primary render pass, by order of preference
+/// (see [`Renderer::depth_format`]). Vulkan requires one of the combined depth stencil formats to
+/// be supported as an attachment, so one of them is always picked.
+const DEPTH_FORMAT_PREFERENCES: [vk::Format; 3] = [
+    vk::Format::D32_SFLOAT,
+    vk::Format::D32_SFLOAT_S8_UINT,
+    vk::Format::D24_UNORM_S8_UINT,
+];
+
+/// Color format of the primary render pass of compute only and headless renderers, which have no
+/// surface to query it from. Materials can still be built against it, headless renderers draw them
+/// to their target (see [`RendererBuilder::headless`]).
+const COMPUTE_ONLY_SURFACE_FORMAT: vk::SurfaceFormatKHR = vk::SurfaceFormatKHR {
+    format: vk::Format::B8G8R8A8_SRGB,
+    color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+};
+
 pub(crate) struct DebugMessengerInfo {
     pub handle: vk::DebugUtilsMessengerEXT,
     pub instance_loader: ext::debug_utils::Instance,
@@ -112,14 +160,100 @@ struct SyncObjects {
     render_semaphore: vk::Semaphore,
 }
 
+/// Number of frames that can be recorded while the previous ones are still executing, if none is
+/// requested with [`RendererBuilder::with_frames_in_flight`].
+pub const DEFAULT_FRAMES_IN_FLIGHT: usize = 2;
+
+/// Objects owned by each frame in flight, used in turn by successive frames.
+struct FrameData {
+    sync_objects: SyncObjects,
+    primary_command_buffer: vk::CommandBuffer,
+    /// Submitted right before the primary command buffer, with the barriers batched during the
+    /// frame (see [`Renderer::request_image_layout`]).
+    barrier_command_buffer: vk::CommandBuffer,
+    /// Semaphores waited by the last submission of the frame, destroyed once it completed (see
+    /// [`Renderer::wait_semaphore`]).
+    waited_semaphores: Vec<vk::Semaphore>,
+    /// Image views released before the last submission of the frame, destroyed once it completed
+    /// (see [`Renderer::destroy_image_view_deferred`]).
+    released_image_views: Vec<vk::ImageView>,
+    /// Buffers released before the last submission of the frame, destroyed once it completed (see
+    /// [`Renderer::destroy_buffer_deferred`]).
+    released_buffers: Vec<AllocatedBuffer>,
+}
+
 pub(crate) struct DescriptorInfo {
-    pub(crate) handle: vk::DescriptorSet,
+    /// One set per frame in flight, see [`Renderer::frame_descriptor_sets`].
+    pub(crate) handles: Vec<vk::DescriptorSet>,
     pub(crate) layout: vk::DescriptorSetLayout,
-    pub(crate) buffer: Option<AllocatedBuffer>,
+    /// Buffers bound to the sets, one per frame in flight for the ones written every frame, so
+    /// that a frame never overwrites data the previous one may still be reading.
+    pub(crate) buffers: Vec<AllocatedBuffer>,
+}
+
+/// Where [`Renderer::begin_frame_to`] redirects the rest of the frame's rendering.
+#[derive(Clone, Copy)]
+pub enum FrameTarget<'a> {
+    /// The swapchain image of the frame, or the target of headless renderers (see
+    /// [`RendererBuilder::headless`]).
+    Swapchain,
+    RenderTarget(&'a RenderTarget),
+}
+
+impl<'a> From<&'a RenderTarget> for FrameTarget<'a> {
+    fn from(render_target: &'a RenderTarget) -> Self {
+        Self::RenderTarget(render_target)
+    }
+}
+
+/// How the attachments of the swapchain are initialized by [`Renderer::begin_frame`], see
+/// [`Renderer::clear_config`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClearConfig {
+    /// Color the swapchain image is cleared to, `None` keeps what the image held instead (e.g. to
+    /// draw over what a previous pass rendered to it).
+    pub color: Option<[f32; 4]>,
+    /// Depth the depth buffer is cleared to, `None` keeps the depth of the previous frame instead.
+    pub depth: Option<f32>,
+}
+
+impl Default for ClearConfig {
+    fn default() -> Self {
+        Self {
+            color: Some([0.0, 0.0, 0.0, 1.0]),
+            depth: Some(1.0),
+        }
+    }
+}
+
+/// Copy of the image rendered by a frame, see [`Renderer::request_capture`].
+struct PendingCapture {
+    readback_buffer: AllocatedBuffer,
+    extent: vk::Extent2D,
+    format: vk::Format,
+    /// Index in `frames` of the frame that recorded the copy.
+    frame_index: usize,
 }
 
 pub struct Renderer {
-    pub clear_color: [f32; 4],
+    /// Attachments cleared at the start of every frame rendered to the swapchain, and their clear
+    /// values. The swapchain's images are cycled through by the presentation engine: with
+    /// several images, a color that isn't cleared holds the frame presented with the same image,
+    /// usually two or three frames ago, unless the application drew over it itself (e.g. in a
+    /// compute pass or with [`Renderer::begin_frame_to`]). Images that were never rendered to are
+    /// cleared anyway, as their content is undefined.
+    ///
+    /// Render targets (see [`Renderer::begin_frame_to`]), and the target of headless renderers,
+    /// are always cleared, to [`ClearConfig::color`] if set and to transparent black otherwise.
+    pub clear_config: ClearConfig,
+    flipped_viewport: bool,
+    /// See [`Renderer::set_render_region`], `None` covers the whole framebuffer.
+    render_region: Option<vk::Rect2D>,
+    /// Extent of the render target being rendered to, see [`Renderer::begin_frame_to`].
+    active_render_target: Option<vk::Extent2D>,
+    /// Whether the swapchain's render pass was begun during the current frame, after which it is
+    /// only resumed.
+    swapchain_pass_begun: bool,
 
     needs_resize: bool,
     window_width: u32,
@@ -127,27 +261,80 @@ pub struct Renderer {
     pub framebuffer_width: u32,
     pub framebuffer_height: u32,
     next_image_index: u32,
+    /// Whether the frame being recorded, or the next one, must copy its image for a capture, see
+    /// [`Renderer::request_capture`].
+    capture_requested: bool,
+    pending_capture: Option<PendingCapture>,
 
     pub(crate) debug_messenger: Option<DebugMessengerInfo>,
+    /// Only loaded along with the debug messenger, see [`Renderer::set_debug_name`].
+    debug_utils_device: Option<ext::debug_utils::Device>,
+    /// See [`RendererBuilder::with_auto_naming`].
+    auto_naming: bool,
+    /// Written to the time uniform instead of the elapsed time, see [`Renderer::set_time`].
+    time_override: Option<Vec4>,
+    /// Lights given to [`Renderer::upload_lights`], and whether each frame in flight's lights
+    /// buffer misses them.
+    lights: Vec<GpuLight>,
+    stale_lights_buffers: Vec<bool>,
 
     pub(crate) default_texture_ref: ThreadSafeRef<Texture>,
 
     pub(crate) command_uploader: CommandUploader,
+    /// Submits to the compute queue, see [`Renderer::submit_async_compute`].
+    async_compute: AsyncCommandSubmitter,
+
+    gpu_profiler: Option<GpuProfiler>,
+    pipeline_statistics: Option<PipelineStatistics>,
+    /// See [`RendererBuilder::with_entity_picking`].
+    entity_ids: Option<EntityIdPass>,
 
     pub(crate) descriptors: [DescriptorInfo; 2],
+    dynamic_uniforms: DynamicUniformBuffer,
     descriptor_pool: vk::DescriptorPool,
-    sync_objects: SyncObjects,
+    pub(crate) descriptor_set_cache: DescriptorSetCache,
+    pub(crate) sampler_cache: SamplerCache,
+    #[cfg(feature = "bindless")]
+    bindless_textures: Option<BindlessTextures>,
+    frames: Vec<FrameData>,
+    /// Index in `frames` of the frame being recorded, see [`Renderer::current_frame_index`].
+    current_frame: usize,
+    /// Primary command buffer of the current frame.
     pub(crate) primary_command_buffer: vk::CommandBuffer,
+    frame_barrier: PipelineBarrier<'static>,
+    /// Waited by the next frame's submission, see [`Renderer::wait_semaphore`].
+    frame_wait_semaphores: Vec<(vk::Semaphore, vk::PipelineStageFlags)>,
+    /// Handed to the next frame's submission, see [`Renderer::destroy_image_view_deferred`].
+    released_image_views: Vec<vk::ImageView>,
+    /// Handed to the next frame's submission, see [`Renderer::destroy_buffer_deferred`].
+    released_buffers: Vec<AllocatedBuffer>,
     command_pool: vk::CommandPool,
     swapchain_framebuffers: Vec<vk::Framebuffer>,
     pub(crate) primary_render_pass: vk::RenderPass,
-    swapchain: SwapchainInfo,
+    /// See [`Renderer::depth_format`].
+    depth_format: vk::Format,
+    /// Compatible with the primary render pass, but loads the attachments instead of clearing
+    /// them, to resume rendering to the swapchain after [`Renderer::end_frame_to`].
+    resume_render_pass: vk::RenderPass,
+    /// Compatible with the primary render pass, but only clear one of the attachments, the other
+    /// one is loaded (see [`Renderer::clear_config`]).
+    load_color_render_pass: vk::RenderPass,
+    load_depth_render_pass: vk::RenderPass,
+    /// Replaces the swapchain of headless renderers, see [`RendererBuilder::headless`].
+    headless_target: Option<ThreadSafeRef<RenderTarget>>,
+    /// `None` for compute only and headless renderers (see [`RendererBuilder::compute_only`]), as
+    /// is the surface.
+    swapchain: Option<SwapchainInfo>,
     pub graphics_queue: QueueInfo,
+    /// Queue of a family dedicated to compute when the device has one, the graphics queue
+    /// otherwise (see [`Renderer::has_dedicated_compute_queue`]).
+    pub compute_queue: QueueInfo,
     pub allocator: Option<ThreadSafeRef<Allocator>>,
     pub device: ash::Device,
     pub device_properties: vk::PhysicalDeviceProperties,
-    physical_device: vk::PhysicalDevice,
-    surface: SurfaceInfo,
+    capabilities: Capabilities,
+    pub(crate) physical_device: vk::PhysicalDevice,
+    surface: Option<SurfaceInfo>,
     pub(crate) instance: Instance,
     #[allow(dead_code)]
     // This field is never read, but we need to keep it alive longer than the instance
@@ -155,12 +342,23 @@ pub struct Renderer {
 }
 
 pub struct RendererBuilder<'a> {
-    window_handle: &'a Window,
+    window_handle: Option<&'a Window>,
+    headless: bool,
     application_name: CString,
     application_version: u32,
     width: u32,
     height: u32,
     preferred_present_mode: vk::PresentModeKHR,
+    fallback_present_mode: vk::PresentModeKHR,
+    preferred_color_space: vk::ColorSpaceKHR,
+    prefer_software: bool,
+    gpu_profiling: bool,
+    pipeline_statistics: bool,
+    entity_picking: bool,
+    dynamic_uniform_capacity: u64,
+    frames_in_flight: usize,
+    flipped_viewport: bool,
+    auto_naming: bool,
     input_attachments: Vec<(vk::AttachmentDescription, vk::AttachmentReference)>,
 }
 
@@ -169,10 +367,12 @@ fn create_swapchain(
     mut width: u32,
     mut height: u32,
     preferred_present_mode: vk::PresentModeKHR,
+    fallback_present_mode: vk::PresentModeKHR,
     instance: &Instance,
     physical_device: vk::PhysicalDevice,
     device: &ash::Device,
     surface: &SurfaceInfo,
+    depth_format: vk::Format,
     allocator: &mut Allocator,
 ) -> SwapchainInfo {
     let capabilities = unsafe {
@@ -202,21 +402,31 @@ fn create_swapchain(
             .get_physical_device_surface_present_modes(physical_device, surface.handle)
     }
     .expect("Failed to query surface present modes");
-    let present_mode = present_modes
-        .iter()
-        .cloned()
-        .find(|&mode| mode == preferred_present_mode)
+    // FIFO is the only mode all surfaces are required to support
+    let present_mode = [preferred_present_mode, fallback_present_mode]
+        .into_iter()
+        .find(|mode| present_modes.contains(mode))
         .unwrap_or(vk::PresentModeKHR::FIFO);
 
     let swapchain_loader = khr::swapchain::Device::new(instance, device);
 
+    // Copying from the images is only needed for captures, which are unavailable otherwise
+    let capturable = capabilities
+        .supported_usage_flags
+        .contains(vk::ImageUsageFlags::TRANSFER_SRC);
+    let image_usage = if capturable {
+        vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC
+    } else {
+        vk::ImageUsageFlags::COLOR_ATTACHMENT
+    };
+
     let swapchain_create_info = vk::SwapchainCreateInfoKHR::default()
         .surface(surface.handle)
         .min_image_count(requested_image_count)
         .image_color_space(surface.format.color_space)
         .image_format(surface.format.format)
         .image_extent(surface_extent)
-        .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
+        .image_usage(image_usage)
         .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
         .pre_transform(capabilities.current_transform)
         .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
@@ -262,7 +472,7 @@ fn create_swapchain(
     let depth_image_create_info = vk::ImageCreateInfo::default()
         .extent(depth_extent)
         .image_type(vk::ImageType::TYPE_2D)
-        .format(vk::Format::D32_SFLOAT)
+        .format(depth_format)
         .mip_levels(1)
         .array_layers(1)
         .samples(vk::SampleCountFlags::TYPE_1)
@@ -292,12 +502,13 @@ fn create_swapchain(
         )
     }
     .expect("Failed to bind depth image memory");
+    resource_registry::register(ResourceKind::Image, 1);
 
     let depth_image_view_create_info = vk::ImageViewCreateInfo::default()
         .view_type(vk::ImageViewType::TYPE_2D)
-        .format(vk::Format::D32_SFLOAT)
+        .format(depth_format)
         .subresource_range(vk::ImageSubresourceRange {
-            aspect_mask: vk::ImageAspectFlags::DEPTH,
+            aspect_mask: image_aspect_mask(depth_format),
             base_mip_level: 0,
             level_count: 1,
             base_array_layer: 0,
@@ -307,6 +518,7 @@ fn create_swapchain(
     let depth_image_view = unsafe { device.create_image_view(&depth_image_view_create_info, None) }
         .expect("Failed to create depth image view");
 
+    let rendered_images = vec![false; swapchain_images.len()];
     SwapchainInfo {
         handle: swapchain,
         images: swapchain_images,
@@ -319,8 +531,13 @@ fn create_swapchain(
             format: depth_image_create_info.format,
             extent: depth_extent,
             layer_count: 1,
+            view_type: vk::ImageViewType::TYPE_2D,
         },
         preferred_present_mode,
+        fallback_present_mode,
+        present_mode,
+        capturable,
+        rendered_images,
         loader: swapchain_loader,
         extent: surface_extent,
     }
@@ -353,6 +570,99 @@ fn create_framebuffers(
     framebuffers
 }
 
+pub(crate) fn supports_draw_indirect_count(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+) -> bool {
+    let mut vk12features = vk::PhysicalDeviceVulkan12Features::default();
+    let mut features = vk::PhysicalDeviceFeatures2::default().push_next(&mut vk12features);
+    unsafe { instance.get_physical_device_features2(physical_device, &mut features) };
+
+    vk12features.draw_indirect_count == vk::TRUE
+}
+
+/// Content of the time uniform `total_elapsed` after startup, see `Renderer::update_time_buffer`.
+fn time_uniform_data(total_elapsed: Duration) -> Vec4 {
+    let current_time = total_elapsed.as_secs_f32();
+
+    Vec4::new(
+        current_time / 20.0,
+        current_time,
+        current_time * 2.0,
+        current_time * 3.0,
+    )
+}
+
+/// Queue family supporting compute but not graphics, whose queues can execute compute work
+/// concurrently with rendering (see [`Renderer::compute_queue`]).
+fn find_dedicated_compute_family(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+) -> Option<u32> {
+    unsafe { instance.get_physical_device_queue_family_properties(physical_device) }
+        .iter()
+        .position(|queue_family| {
+            queue_family.queue_flags.contains(vk::QueueFlags::COMPUTE)
+                && !queue_family.queue_flags.contains(vk::QueueFlags::GRAPHICS)
+        })
+        .map(|queue_family_index| queue_family_index as u32)
+}
+
+/// First format of [`DEPTH_FORMAT_PREFERENCES`] usable as a depth attachment with optimal tiling.
+fn select_depth_format(instance: &Instance, physical_device: vk::PhysicalDevice) -> vk::Format {
+    DEPTH_FORMAT_PREFERENCES
+        .into_iter()
+        .find(|&format| {
+            let format_properties =
+                unsafe { instance.get_physical_device_format_properties(physical_device, format) };
+            format_properties
+                .optimal_tiling_features
+                .contains(vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
+        })
+        .expect("No supported depth format")
+}
+
+/// Aspects of the images of `format`, both depth and stencil for combined formats.
+pub(crate) fn image_aspect_mask(format: vk::Format) -> vk::ImageAspectFlags {
+    match format {
+        vk::Format::D16_UNORM | vk::Format::D32_SFLOAT | vk::Format::X8_D24_UNORM_PACK32 => {
+            vk::ImageAspectFlags::DEPTH
+        }
+        vk::Format::S8_UINT => vk::ImageAspectFlags::STENCIL,
+        vk::Format::D16_UNORM_S8_UINT
+        | vk::Format::D24_UNORM_S8_UINT
+        | vk::Format::D32_SFLOAT_S8_UINT => {
+            vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+        }
+        _ => vk::ImageAspectFlags::COLOR,
+    }
+}
+
+/// Line rasterization modes the renderer can use, all disabled if the device doesn't support
+/// `VK_EXT_line_rasterization`. Stippled and Bresenham lines are never enabled.
+pub(crate) fn supported_line_rasterization_features(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+) -> vk::PhysicalDeviceLineRasterizationFeaturesEXT<'static> {
+    let extensions = unsafe { instance.enumerate_device_extension_properties(physical_device) }
+        .unwrap_or_default();
+    let is_extension_supported = extensions
+        .iter()
+        .any(|extension| extension.extension_name_as_c_str() == Ok(ext::line_rasterization::NAME));
+    if !is_extension_supported {
+        return vk::PhysicalDeviceLineRasterizationFeaturesEXT::default();
+    }
+
+    let mut line_rasterization_features = vk::PhysicalDeviceLineRasterizationFeaturesEXT::default();
+    let mut features =
+        vk::PhysicalDeviceFeatures2::default().push_next(&mut line_rasterization_features);
+    unsafe { instance.get_physical_device_features2(physical_device, &mut features) };
+
+    vk::PhysicalDeviceLineRasterizationFeaturesEXT::default()
+        .rectangular_lines(line_rasterization_features.rectangular_lines == vk::TRUE)
+        .smooth_lines(line_rasterization_features.smooth_lines == vk::TRUE)
+}
+
 impl RendererBuilder<'_> {
     fn create_instance(&self, entry: &Entry) -> Instance {
         let engine_name = CString::new("Morrigu").unwrap();
@@ -364,14 +674,17 @@ impl RendererBuilder<'_> {
             .api_version(vk::make_api_version(0, 1, 2, 0));
 
         #[allow(unused_mut)]
-        let mut required_extensions = ash_window::enumerate_required_extensions(
-            self.window_handle
-                .display_handle()
-                .expect("window has no display handle")
-                .as_raw(),
-        )
-        .expect("Failed to query extensions")
-        .to_vec();
+        let mut required_extensions = match self.window_handle {
+            Some(window_handle) => ash_window::enumerate_required_extensions(
+                window_handle
+                    .display_handle()
+                    .expect("window has no display handle")
+                    .as_raw(),
+            )
+            .expect("Failed to query extensions")
+            .to_vec(),
+            None => vec![],
+        };
 
         #[allow(unused_assignments)]
         #[allow(unused_mut)]
@@ -385,6 +698,28 @@ impl RendererBuilder<'_> {
             required_extensions.push(ext::debug_utils::NAME.as_ptr());
         }
 
+        // HDR color spaces (other than the default sRGB one) are exposed through this extension
+        if self.window_handle.is_some()
+            && self.preferred_color_space != vk::ColorSpaceKHR::SRGB_NONLINEAR
+        {
+            let colorspace_extension_available =
+                unsafe { entry.enumerate_instance_extension_properties(None) }
+                    .unwrap_or_default()
+                    .iter()
+                    .any(|extension| {
+                        extension.extension_name_as_c_str() == Ok(ext::swapchain_colorspace::NAME)
+                    });
+            if colorspace_extension_available {
+                required_extensions.push(ext::swapchain_colorspace::NAME.as_ptr());
+            } else {
+                log::warn!(
+                    "Color space {:?} was requested, but {:?} is not available",
+                    self.preferred_color_space,
+                    ext::swapchain_colorspace::NAME
+                );
+            }
+        }
+
         let instance_info = vk::InstanceCreateInfo::default()
             .application_info(&app_info)
             .enabled_layer_names(&raw_layer_names)
@@ -434,11 +769,12 @@ impl RendererBuilder<'_> {
         debug_messenger
     }
 
+    /// Compute only renderers don't have a surface, any queue family supporting both graphics and
+    /// compute is then accepted.
     fn select_physical_device(
         &self,
-        surface: vk::SurfaceKHR,
+        surface: Option<(vk::SurfaceKHR, &khr::surface::Instance)>,
         instance: &Instance,
-        surface_loader: &khr::surface::Instance,
         required_version: u32,
     ) -> (vk::PhysicalDevice, u32) {
         let mut physical_devices = unsafe { instance.enumerate_physical_devices() }
@@ -462,14 +798,17 @@ impl RendererBuilder<'_> {
                     let supports_compute = device_queue_info
                         .queue_flags
                         .contains(vk::QueueFlags::COMPUTE);
-                    let is_compatible_with_surface = unsafe {
-                        surface_loader.get_physical_device_surface_support(
-                            raw_physical_device,
-                            queue_index as u32,
-                            surface,
-                        )
-                    }
-                    .expect("Failed to query surface compatibility");
+                    let is_compatible_with_surface =
+                        surface.is_none_or(|(surface, surface_loader)| {
+                            unsafe {
+                                surface_loader.get_physical_device_surface_support(
+                                    raw_physical_device,
+                                    queue_index as u32,
+                                    surface,
+                                )
+                            }
+                            .expect("Failed to query surface compatibility")
+                        });
 
                     let mut meets_rt_requirements = true;
                     if cfg!(feature = "ray_tracing") {
@@ -510,23 +849,15 @@ impl RendererBuilder<'_> {
                     .find_map(device_discriminator)
             };
 
-        physical_devices.sort_unstable_by(|a, b| {
-            let device_a_info = unsafe { instance.get_physical_device_properties(*a) };
-            let device_b_info = unsafe { instance.get_physical_device_properties(*b) };
-
-            let mut ordering = Ordering::Equal;
-            if device_a_info.device_type == PhysicalDeviceType::DISCRETE_GPU
-                && device_b_info.device_type != PhysicalDeviceType::DISCRETE_GPU
-            {
-                ordering = Ordering::Less;
-            }
-            if device_b_info.device_type == PhysicalDeviceType::DISCRETE_GPU
-                && device_a_info.device_type != PhysicalDeviceType::DISCRETE_GPU
-            {
-                ordering = Ordering::Greater;
-            }
+        let preferred_device_type = if self.prefer_software {
+            PhysicalDeviceType::CPU
+        } else {
+            PhysicalDeviceType::DISCRETE_GPU
+        };
+        physical_devices.sort_by_key(|device| {
+            let device_info = unsafe { instance.get_physical_device_properties(*device) };
 
-            ordering
+            device_info.device_type != preferred_device_type
         });
         log::debug!("Physical device list (sorted):");
         for device in &physical_devices {
@@ -572,12 +903,49 @@ impl RendererBuilder<'_> {
         instance: &Instance,
         physical_device: vk::PhysicalDevice,
         queue_family_index: u32,
-    ) -> ash::Device {
-        let mut raw_extensions_names = vec![khr::swapchain::NAME.as_ptr()];
-        let features = vk::PhysicalDeviceFeatures::default();
+        compute_queue_family_index: Option<u32>,
+    ) -> (ash::Device, vk::PhysicalDeviceFeatures) {
+        let mut raw_extensions_names = vec![];
+        if self.window_handle.is_some() {
+            raw_extensions_names.push(khr::swapchain::NAME.as_ptr());
+        }
+        let supported_features = unsafe { instance.get_physical_device_features(physical_device) };
+        if supported_features.sampler_anisotropy != vk::TRUE {
+            log::warn!("The selected device does not support anisotropic filtering, textures will be sampled without it");
+        }
+        // Allows point primitives to be bigger than a single pixel (used by point clouds), lines
+        // wider than a pixel, wireframe polygons and adjacency topologies (see `MaterialBuilder`)
+        let features = vk::PhysicalDeviceFeatures::default()
+            .large_points(supported_features.large_points == vk::TRUE)
+            .fill_mode_non_solid(supported_features.fill_mode_non_solid == vk::TRUE)
+            .wide_lines(supported_features.wide_lines == vk::TRUE)
+            .geometry_shader(supported_features.geometry_shader == vk::TRUE)
+            // See `TextureBuilder::with_anisotropy`
+            .sampler_anisotropy(supported_features.sampler_anisotropy == vk::TRUE)
+            // See `PipelineStatistics`
+            .pipeline_statistics_query(supported_features.pipeline_statistics_query == vk::TRUE)
+            // See `IndirectBatch`, GPU culling also writes the instance index as `first_instance`
+            .multi_draw_indirect(supported_features.multi_draw_indirect == vk::TRUE)
+            .draw_indirect_first_instance(
+                supported_features.draw_indirect_first_instance == vk::TRUE,
+            )
+            // See `TextureBuilder::build_from_ktx2`
+            .texture_compression_bc(supported_features.texture_compression_bc == vk::TRUE);
         let mut vk12features = vk::PhysicalDeviceVulkan12Features::default();
         let priorities = [1.0];
 
+        // Used by GPU culling to consume its compacted draw list
+        vk12features.draw_indirect_count =
+            supports_draw_indirect_count(instance, physical_device).into();
+
+        // See `Renderer::register_bindless_texture`
+        #[cfg(feature = "bindless")]
+        if bindless::is_supported(instance, physical_device) {
+            bindless::enable_features(&mut vk12features);
+        } else {
+            log::warn!("The selected device does not support descriptor indexing, bindless textures are disabled");
+        }
+
         if cfg!(feature = "ray_tracing") {
             // For rt acceleration structures
             raw_extensions_names.push(khr::acceleration_structure::NAME.as_ptr());
@@ -589,16 +957,36 @@ impl RendererBuilder<'_> {
             vk12features.buffer_device_address = vk::TRUE;
         }
 
-        let queue_info = vk::DeviceQueueCreateInfo::default()
+        // Anti-aliased lines (see `MaterialBuilder::line_mode`)
+        let mut line_rasterization_features =
+            supported_line_rasterization_features(instance, physical_device);
+        let uses_line_rasterization = line_rasterization_features.rectangular_lines == vk::TRUE
+            || line_rasterization_features.smooth_lines == vk::TRUE;
+        if uses_line_rasterization {
+            raw_extensions_names.push(ext::line_rasterization::NAME.as_ptr());
+        }
+
+        let mut queue_infos = vec![vk::DeviceQueueCreateInfo::default()
             .queue_family_index(queue_family_index)
-            .queue_priorities(&priorities);
+            .queue_priorities(&priorities)];
+        if let Some(compute_queue_family_index) = compute_queue_family_index {
+            queue_infos.push(
+                vk::DeviceQueueCreateInfo::default()
+                    .queue_family_index(compute_queue_family_index)
+                    .queue_priorities(&priorities),
+            );
+        }
 
         let mut device_create_info = vk::DeviceCreateInfo::default()
             .enabled_features(&features)
             .enabled_extension_names(&raw_extensions_names)
-            .queue_create_infos(std::slice::from_ref(&queue_info))
+            .queue_create_infos(&queue_infos)
             .push_next(&mut vk12features);
 
+        if uses_line_rasterization {
+            device_create_info = device_create_info.push_next(&mut line_rasterization_features);
+        }
+
         let mut as_features = vk::PhysicalDeviceAccelerationStructureFeaturesKHR::default()
             .acceleration_structure(true);
         let mut rtp_features =
@@ -608,8 +996,10 @@ impl RendererBuilder<'_> {
             device_create_info = device_create_info.push_next(&mut rtp_features);
         }
 
-        unsafe { instance.create_device(physical_device, &device_create_info, None) }
-            .expect("Failed to create logical device")
+        let device = unsafe { instance.create_device(physical_device, &device_create_info, None) }
+            .expect("Failed to create logical device");
+
+        (device, features)
     }
 
     fn create_allocator(
@@ -633,6 +1023,37 @@ impl RendererBuilder<'_> {
         &self,
         surface_formats: Vec<vk::SurfaceFormatKHR>,
     ) -> vk::SurfaceFormatKHR {
+        if self.preferred_color_space != vk::ColorSpaceKHR::SRGB_NONLINEAR {
+            // HDR10 needs (at least) 10 bits per channel, and extended sRGB needs float values
+            let preferred_formats: &[vk::Format] = match self.preferred_color_space {
+                vk::ColorSpaceKHR::HDR10_ST2084_EXT => &[
+                    vk::Format::A2B10G10R10_UNORM_PACK32,
+                    vk::Format::A2R10G10B10_UNORM_PACK32,
+                ],
+                vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT => &[vk::Format::R16G16B16A16_SFLOAT],
+                _ => &[],
+            };
+
+            let matching_formats = surface_formats
+                .iter()
+                .cloned()
+                .filter(|surface_format| surface_format.color_space == self.preferred_color_space)
+                .collect::<Vec<_>>();
+            let hdr_format = matching_formats
+                .iter()
+                .cloned()
+                .find(|surface_format| preferred_formats.contains(&surface_format.format))
+                .or(matching_formats.first().cloned());
+
+            match hdr_format {
+                Some(hdr_format) => return hdr_format,
+                None => log::warn!(
+                    "Color space {:?} is not supported by the surface, falling back to SRGB_NONLINEAR",
+                    self.preferred_color_space
+                ),
+            }
+        }
+
         surface_formats
             .iter()
             .cloned()
@@ -643,29 +1064,44 @@ impl RendererBuilder<'_> {
             .unwrap_or(surface_formats[0])
     }
 
+    /// Loaded attachments are loaded in the layout the primary render pass leaves them in,
+    /// instead of being cleared. The input attachments are only loaded when both the color and
+    /// depth attachments are (e.g. to resume rendering).
     fn create_render_passes(
         &self,
-        surface: &SurfaceInfo,
-        depth_image: &AllocatedImage,
+        color_format: vk::Format,
+        depth_format: vk::Format,
+        (load_color, load_depth): (bool, bool),
         device: &ash::Device,
     ) -> vk::RenderPass {
+        let load_ops = |load| {
+            if load {
+                (vk::AttachmentLoadOp::LOAD, vk::ImageLayout::PRESENT_SRC_KHR)
+            } else {
+                (vk::AttachmentLoadOp::CLEAR, vk::ImageLayout::UNDEFINED)
+            }
+        };
+        let resume = load_color && load_depth;
+
+        let (load_op, initial_layout) = load_ops(load_color);
         let color_attachment = vk::AttachmentDescription {
-            format: surface.format.format,
+            format: color_format,
             samples: vk::SampleCountFlags::TYPE_1,
-            load_op: vk::AttachmentLoadOp::CLEAR,
+            load_op,
             store_op: vk::AttachmentStoreOp::STORE,
-            initial_layout: vk::ImageLayout::UNDEFINED,
+            initial_layout,
             final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
             ..Default::default()
         };
+        let (load_op, initial_layout) = load_ops(load_depth);
         let depth_attachment = vk::AttachmentDescription {
-            format: depth_image.format,
+            format: depth_format,
             samples: vk::SampleCountFlags::TYPE_1,
-            load_op: vk::AttachmentLoadOp::CLEAR,
+            load_op,
             store_op: vk::AttachmentStoreOp::STORE,
             stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
             stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
-            initial_layout: vk::ImageLayout::UNDEFINED,
+            initial_layout,
             final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
             ..Default::default()
         };
@@ -698,7 +1134,15 @@ impl RendererBuilder<'_> {
                 .input_attachments
                 .clone()
                 .iter()
-                .map(|pair| pair.0)
+                .map(|pair| {
+                    if resume {
+                        pair.0
+                            .load_op(vk::AttachmentLoadOp::LOAD)
+                            .initial_layout(pair.0.final_layout)
+                    } else {
+                        pair.0
+                    }
+                })
                 .collect::<Vec<vk::AttachmentDescription>>(),
         );
 
@@ -740,12 +1184,20 @@ impl RendererBuilder<'_> {
         device: &ash::Device,
         allocator: &mut Allocator,
     ) -> (vk::DescriptorPool, [DescriptorInfo; 2]) {
-        let descriptor_pool_info = vk::DescriptorPoolCreateInfo::default()
-            .max_sets(2)
-            .pool_sizes(&[vk::DescriptorPoolSize {
+        let frame_count = u32::try_from(self.frames_in_flight).unwrap();
+        let pool_sizes = [
+            vk::DescriptorPoolSize {
                 ty: vk::DescriptorType::UNIFORM_BUFFER,
-                descriptor_count: 2,
-            }]);
+                descriptor_count: frame_count,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::STORAGE_BUFFER,
+                descriptor_count: frame_count,
+            },
+        ];
+        let descriptor_pool_info = vk::DescriptorPoolCreateInfo::default()
+            .max_sets(2 * frame_count)
+            .pool_sizes(&pool_sizes);
         let descriptor_pool = unsafe { device.create_descriptor_pool(&descriptor_pool_info, None) }
             .expect("Failed to create descriptor pool");
 
@@ -761,52 +1213,108 @@ impl RendererBuilder<'_> {
         let level_0_layout =
             unsafe { device.create_descriptor_set_layout(&level_0_layout_info, None) }
                 .expect("Failed to create descriptor set 0 layout");
+        let level_0_layouts = vec![level_0_layout; self.frames_in_flight];
         let level_0_allocation_info = vk::DescriptorSetAllocateInfo::default()
             .descriptor_pool(descriptor_pool)
-            .set_layouts(std::slice::from_ref(&level_0_layout));
-        let level_0_handle = unsafe { device.allocate_descriptor_sets(&level_0_allocation_info) }
-            .expect("Failed to allocate level 0 descriptor")[0];
+            .set_layouts(&level_0_layouts);
+        let level_0_handles = unsafe { device.allocate_descriptor_sets(&level_0_allocation_info) }
+            .expect("Failed to allocate level 0 descriptors");
+        // The time is written every frame, each frame in flight has its own buffer
         let time_buffer_size: u64 = mem::size_of::<Vec4>().try_into().unwrap();
-        let time_buffer = AllocatedBufferBuilder::uniform_buffer_default(time_buffer_size)
-            .build_internal(device, allocator)
-            .expect("Failed to create time buffer");
-        let time_buffer_info = vk::DescriptorBufferInfo {
-            buffer: time_buffer.handle,
-            offset: 0,
-            range: time_buffer_size,
-        };
-        let time_set_write = vk::WriteDescriptorSet {
-            dst_set: level_0_handle,
-            dst_binding: 0,
+        let time_buffers = level_0_handles
+            .iter()
+            .map(|level_0_handle| {
+                let time_buffer = AllocatedBufferBuilder::uniform_buffer_default(time_buffer_size)
+                    .with_name("Time")
+                    .build_internal(device, allocator)
+                    .expect("Failed to create time buffer");
+                let time_buffer_info = vk::DescriptorBufferInfo {
+                    buffer: time_buffer.handle,
+                    offset: 0,
+                    range: time_buffer_size,
+                };
+                let time_set_write = vk::WriteDescriptorSet {
+                    dst_set: *level_0_handle,
+                    dst_binding: 0,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
+                    p_buffer_info: &time_buffer_info,
+                    ..Default::default()
+                };
+                unsafe { device.update_descriptor_sets(&[time_set_write], &[]) };
+
+                time_buffer
+            })
+            .collect();
+
+        let level_1_bindings = [vk::DescriptorSetLayoutBinding {
+            binding: LIGHTS_BINDING,
             descriptor_count: 1,
-            descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
-            p_buffer_info: &time_buffer_info,
+            descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+            stage_flags: vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
             ..Default::default()
-        };
-        unsafe { device.update_descriptor_sets(&[time_set_write], &[]) };
-
-        let level_1_layout_info = vk::DescriptorSetLayoutCreateInfo::default().bindings(&[]);
+        }];
+        let level_1_layout_info =
+            vk::DescriptorSetLayoutCreateInfo::default().bindings(&level_1_bindings);
         let level_1_layout =
             unsafe { device.create_descriptor_set_layout(&level_1_layout_info, None) }
-                .expect("Failed to create descriptor set 0 layout");
+                .expect("Failed to create descriptor set 1 layout");
+        let level_1_layouts = vec![level_1_layout; self.frames_in_flight];
         let level_1_allocation_info = vk::DescriptorSetAllocateInfo::default()
             .descriptor_pool(descriptor_pool)
-            .set_layouts(std::slice::from_ref(&level_1_layout));
-        let level_1_handle = unsafe { device.allocate_descriptor_sets(&level_1_allocation_info) }
-            .expect("Failed to allocate level 1 descriptor")[0];
+            .set_layouts(&level_1_layouts);
+        let level_1_handles = unsafe { device.allocate_descriptor_sets(&level_1_allocation_info) }
+            .expect("Failed to allocate level 1 descriptors");
+        // A light count, padded to 16 bytes, followed by the lights (see `GpuLight`)
+        let lights_buffer_size: u64 = (mem::size_of::<[u32; 4]>()
+            + MAX_LIGHTS * mem::size_of::<GpuLight>())
+        .try_into()
+        .unwrap();
+        // The lights can change every frame, each frame in flight has its own buffer
+        let lights_buffers = level_1_handles
+            .iter()
+            .map(|level_1_handle| {
+                let mut lights_buffer =
+                    AllocatedBufferBuilder::uniform_buffer_default(lights_buffer_size)
+                        .with_usage(vk::BufferUsageFlags::STORAGE_BUFFER)
+                        .with_name("Lights")
+                        .build_internal(device, allocator)
+                        .expect("Failed to create lights buffer");
+                lights_buffer
+                    .upload_data_at(0, bytemuck::bytes_of(&[0_u32; 4]))
+                    .expect("Failed to initialize lights buffer");
+                let lights_buffer_info = vk::DescriptorBufferInfo {
+                    buffer: lights_buffer.handle,
+                    offset: 0,
+                    range: lights_buffer_size,
+                };
+                let lights_set_write = vk::WriteDescriptorSet {
+                    dst_set: *level_1_handle,
+                    dst_binding: LIGHTS_BINDING,
+                    descriptor_count: 1,
+                    descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+                    p_buffer_info: &lights_buffer_info,
+                    ..Default::default()
+                };
+                unsafe { device.update_descriptor_sets(&[lights_set_write], &[]) };
+
+                lights_buffer
+            })
+            .collect();
+        resource_registry::register(ResourceKind::DescriptorSet, 2 * self.frames_in_flight);
 
         (
             descriptor_pool,
             [
                 DescriptorInfo {
-                    handle: level_0_handle,
+                    handles: level_0_handles,
                     layout: level_0_layout,
-                    buffer: Some(time_buffer),
+                    buffers: time_buffers,
                 },
                 DescriptorInfo {
-                    handle: level_1_handle,
+                    handles: level_1_handles,
                     layout: level_1_layout,
-                    buffer: None,
+                    buffers: lights_buffers,
                 },
             ],
         )
@@ -815,17 +1323,60 @@ impl RendererBuilder<'_> {
 
 impl<'a> RendererBuilder<'a> {
     pub fn new(window_handle: &'a Window) -> Self {
+        Self::from_window_handle(Some(window_handle))
+    }
+
+    fn from_window_handle(window_handle: Option<&'a Window>) -> Self {
         RendererBuilder {
             window_handle,
+            headless: false,
             application_name: CString::new("").unwrap(),
             application_version: 0,
             width: 1280,
             height: 720,
             preferred_present_mode: vk::PresentModeKHR::MAILBOX,
+            fallback_present_mode: vk::PresentModeKHR::FIFO,
+            preferred_color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+            prefer_software: false,
+            gpu_profiling: false,
+            pipeline_statistics: false,
+            entity_picking: false,
+            dynamic_uniform_capacity: DEFAULT_DYNAMIC_UNIFORM_CAPACITY,
+            frames_in_flight: DEFAULT_FRAMES_IN_FLIGHT,
+            flipped_viewport: true,
+            auto_naming: false,
             input_attachments: vec![],
         }
     }
 
+    /// Builds a renderer without any window, surface or swapchain, to only run compute work (see
+    /// [`crate::application::ComputeApplication`]). Such a renderer never starts frames, so
+    /// everything must be recorded with [`Renderer::immediate_command`] (e.g. through
+    /// [`crate::compute_shader::ComputeShader::run`]), and results read back from host visible
+    /// buffers. Presentation related settings (dimensions, present mode and color space) are
+    /// ignored.
+    pub fn compute_only() -> Self {
+        Self::from_window_handle(None)
+    }
+
+    /// Builds a renderer without any window, which renders its frames to an owned
+    /// [`RenderTarget`] of the requested dimensions (see [`RendererBuilder::with_dimensions`])
+    /// instead of a swapchain, e.g. for rendering tests on machines without a display.
+    ///
+    /// Frames are driven by hand with [`Renderer::begin_frame`] and [`Renderer::end_frame`],
+    /// presenting is a no-op and captures (see [`Renderer::request_capture`]) copy the target as
+    /// rendered by the frame. The target uses the format of [`Renderer::surface_format`], so
+    /// materials built for the primary render pass draw to it, as long as no input attachments
+    /// are requested. A single frame is in flight, so that every frame sees the previous one done. As
+    /// the target's render pass always clears it, resuming it after [`Renderer::end_frame_to`]
+    /// loses what was drawn before [`Renderer::begin_frame_to`].
+    pub fn headless() -> Self {
+        RendererBuilder {
+            headless: true,
+            ..Self::from_window_handle(None)
+        }
+    }
+
     pub fn with_dimensions(mut self, width: u32, height: u32) -> Self {
         self.width = width;
         self.height = height;
@@ -837,6 +1388,121 @@ impl<'a> RendererBuilder<'a> {
         self
     }
 
+    /// Present mode used when the preferred one isn't supported by the surface (e.g. MAILBOX on
+    /// some Wayland compositors), `FIFO` by default. `FIFO_RELAXED` keeps vsync while avoiding
+    /// stutters when frames are late, at the cost of tearing. If this mode isn't supported either,
+    /// `FIFO` is used, as it is always available. See [`Renderer::active_present_mode`].
+    pub fn with_fallback_present_mode(mut self, present_mode: vk::PresentModeKHR) -> Self {
+        self.fallback_present_mode = present_mode;
+        self
+    }
+
+    /// Requests a swapchain color space (e.g. `HDR10_ST2084_EXT` or `EXTENDED_SRGB_LINEAR_EXT` for
+    /// HDR displays). If the surface doesn't support it, the default sRGB format is used instead,
+    /// check [`Renderer::surface_format`] to know which one was selected.
+    pub fn with_color_space(mut self, color_space: vk::ColorSpaceKHR) -> Self {
+        self.preferred_color_space = color_space;
+        self
+    }
+
+    /// Picks a software (CPU type) implementation such as lavapipe or SwiftShader over any
+    /// hardware device, if one is available. This is mostly useful to run on CI machines without
+    /// a GPU.
+    pub fn prefer_software(mut self) -> Self {
+        self.prefer_software = true;
+        self
+    }
+
+    /// Measures the GPU time of every frame, and of the zones delimited by
+    /// [`Renderer::begin_gpu_zone`] and [`Renderer::end_gpu_zone`] (see [`GpuProfiler`]).
+    pub fn with_gpu_profiling(mut self) -> Self {
+        self.gpu_profiling = true;
+        self
+    }
+
+    /// Counts the work done by the GPU during every frame (see [`PipelineStatistics`] and
+    /// [`Renderer::pipeline_stats`]). Requires the `pipeline_statistics_query` device feature.
+    pub fn with_pipeline_statistics(mut self) -> Self {
+        self.pipeline_statistics = true;
+        self
+    }
+
+    /// Renders the index of the entity of every mesh drawn by the mesh renderer to an additional
+    /// `R32_UINT` attachment the size of the framebuffer, so that [`Renderer::pick_entity_at`] can
+    /// tell which entity is under the cursor. The meshes are drawn a second time with their
+    /// positions only, their materials' vertex shaders (e.g. displacement) are therefore ignored.
+    /// Batches drawn indirectly (GPU culled or with an [`crate::indirect_batch::IndirectBatch`])
+    /// and meshes drawn to render targets can't be picked.
+    pub fn with_entity_picking(mut self) -> Self {
+        self.entity_picking = true;
+        self
+    }
+
+    /// Size in bytes of the buffer holding the dynamic uniforms written during a frame (see
+    /// [`crate::shader::Shader::set_dynamic_uniforms`]). Each object uses its uniforms' size,
+    /// rounded up to the device's `min_uniform_buffer_offset_alignment` (usually 64 or 256 bytes).
+    pub fn with_dynamic_uniform_capacity(mut self, capacity: u64) -> Self {
+        self.dynamic_uniform_capacity = capacity;
+        self
+    }
+
+    /// Number of frames the CPU can record while the GPU is still executing the previous ones
+    /// ([`DEFAULT_FRAMES_IN_FLIGHT`] by default, at least 1). Each frame in flight has its own
+    /// command buffers, synchronization objects and segment of the dynamic uniform buffer, so
+    /// `begin_frame` only waits for the frame that last used them instead of the previous one.
+    ///
+    /// Resources used by a frame must stay alive until it is done executing: destroying a buffer
+    /// or texture drawn during the last frames requires waiting for the device to be idle first
+    /// (see [`Renderer::device`]). The uniforms the renderer writes every frame are duplicated per
+    /// frame in flight: the time uniform, the lights, and the model matrices, which are dynamic
+    /// uniforms. Buffers written by the application every frame are not, a frame may therefore
+    /// read values written for the next one: use dynamic uniforms (see
+    /// [`crate::shader::Shader::set_dynamic_uniforms`]) or index copies with
+    /// [`Renderer::current_frame_index`] when this matters. GPU profiling and
+    /// pipeline statistics read their results back at the start of the next frame, which waits
+    /// for the previous frame and cancels the benefit of more frames in flight.
+    pub fn with_frames_in_flight(mut self, count: usize) -> Self {
+        self.frames_in_flight = count.max(1);
+        self
+    }
+
+    /// Selects the Y axis convention of the scene, egui and debug draws (enabled by default).
+    ///
+    /// Vulkan's clip space has its Y axis pointing down: a vertex at NDC `y = -1` ends up at the
+    /// top of the framebuffer. With a flipped viewport (negative height, see
+    /// [`crate::components::viewport::Viewport`]), the Y axis points up instead, like in OpenGL,
+    /// so that the usual right-handed projection matrices (e.g. `glam::Mat4::perspective_rh`) and
+    /// meshes exported with Y up render the right way up. Without it, NDC are Vulkan's native ones,
+    /// which shaders ported from other Vulkan code or D3D may expect.
+    ///
+    /// Flipping the viewport also mirrors the winding order of triangles on screen, so materials
+    /// pick their front face accordingly: triangles wound counter-clockwise in Y-up world space
+    /// are front facing in both conventions. Egui is drawn with the same convention as the scene,
+    /// so both always agree on where the top of the screen is.
+    pub fn with_flipped_viewport(mut self, flipped_viewport: bool) -> Self {
+        self.flipped_viewport = flipped_viewport;
+        self
+    }
+
+    /// Names the Vulkan objects of the resources created by the engine's loaders, so that
+    /// validation messages and captures (e.g. RenderDoc) refer to them by name. This relies on the
+    /// `VK_EXT_debug_utils` extension, which is only enabled in debug builds: this setting has no
+    /// effect in release builds. The names follow these conventions:
+    /// - textures and cubemaps loaded from files: `<path>` for the image, `<path> (view)` and
+    ///   `<path> (sampler)`,
+    /// - meshes loaded from files: `<path> (vertices)` and `<path> (indices)` for their buffers,
+    /// - materials: `<shader name> (pipeline)`, `<shader name> (pipeline layout)`,
+    ///   `<shader name> (material set)`, `<shader name> (vertex module)` and
+    ///   `<shader name> (fragment module)`, using [`crate::shader::Shader::name`], which shaders
+    ///   loaded with `Shader::from_path` set to the file name of their vertex stage,
+    /// - buffers: the name given to [`crate::allocated_types::AllocatedBufferBuilder::with_name`].
+    ///
+    /// Other objects can be named with [`Renderer::set_debug_name`].
+    pub fn with_auto_naming(mut self, auto_naming: bool) -> Self {
+        self.auto_naming = auto_naming;
+        self
+    }
+
     pub fn with_name(mut self, name: &'a str) -> Self {
         self.application_name = CString::new(name).expect("Invalid application name");
         self
@@ -848,33 +1514,36 @@ impl<'a> RendererBuilder<'a> {
     }
 
     pub fn build(mut self) -> ThreadSafeRef<Renderer> {
+        if self.headless {
+            self.frames_in_flight = 1;
+        }
+
         let entry = Entry::linked();
         let instance = self.create_instance(&entry);
         let debug_messenger = self.create_debug_messenger(&entry, &instance);
 
-        let surface_handle = unsafe {
+        let surface_handle = self.window_handle.map(|window_handle| unsafe {
             ash_window::create_surface(
                 &entry,
                 &instance,
-                self.window_handle
+                window_handle
                     .display_handle()
                     .expect("window has no display_handle")
                     .as_raw(),
-                self.window_handle
+                window_handle
                     .window_handle()
                     .expect("window has no window handle")
                     .as_raw(),
                 None,
             )
             .expect("Failed to create rendering surface")
-        };
+        });
         let surface_loader = khr::surface::Instance::new(&entry, &instance);
 
         let required_api_version = (1, 2, 0);
         let (physical_device, queue_family_index) = self.select_physical_device(
-            surface_handle,
+            surface_handle.map(|surface_handle| (surface_handle, &surface_loader)),
             &instance,
-            &surface_loader,
             vk::make_api_version(
                 0,
                 required_api_version.0,
@@ -882,17 +1551,21 @@ impl<'a> RendererBuilder<'a> {
                 required_api_version.2,
             ),
         );
-        let surface_format = self.select_surface_format(
-            unsafe {
-                surface_loader.get_physical_device_surface_formats(physical_device, surface_handle)
+        let surface = surface_handle.map(|surface_handle| {
+            let surface_format = self.select_surface_format(
+                unsafe {
+                    surface_loader
+                        .get_physical_device_surface_formats(physical_device, surface_handle)
+                }
+                .expect("Failed to query physical device formats"),
+            );
+
+            SurfaceInfo {
+                handle: surface_handle,
+                format: surface_format,
+                loader: surface_loader.clone(),
             }
-            .expect("Failed to query physical device formats"),
-        );
-        let surface = SurfaceInfo {
-            handle: surface_handle,
-            format: surface_format,
-            loader: surface_loader,
-        };
+        });
 
         let device_properties = unsafe { instance.get_physical_device_properties(physical_device) };
         let device_name = unsafe { CStr::from_ptr(device_properties.device_name.as_ptr()) }
@@ -914,41 +1587,99 @@ impl<'a> RendererBuilder<'a> {
             required_api_version.2,
         );
 
-        let device = self.create_device(&instance, physical_device, queue_family_index);
+        let compute_queue_family_index = find_dedicated_compute_family(&instance, physical_device);
+        if compute_queue_family_index.is_some() {
+            log::debug!("\tDedicated compute queue family available");
+        }
+        let (device, enabled_features) = self.create_device(
+            &instance,
+            physical_device,
+            queue_family_index,
+            compute_queue_family_index,
+        );
+        let debug_utils_device = debug_messenger
+            .is_some()
+            .then(|| ext::debug_utils::Device::new(&instance, &device));
+        let capabilities = Capabilities::new(
+            &device_properties,
+            &enabled_features,
+            &supported_line_rasterization_features(&instance, physical_device),
+            supports_draw_indirect_count(&instance, physical_device),
+        );
         let graphics_queue = QueueInfo {
             handle: unsafe { device.get_device_queue(queue_family_index, 0) },
             family_index: queue_family_index,
         };
+        let compute_queue_family_index = compute_queue_family_index.unwrap_or(queue_family_index);
+        let compute_queue = QueueInfo {
+            handle: unsafe { device.get_device_queue(compute_queue_family_index, 0) },
+            family_index: compute_queue_family_index,
+        };
 
         let mut command_uploader = CommandUploader::new(&device, queue_family_index)
             .expect("Failed to create a command uploader");
+        let async_compute = AsyncCommandSubmitter::new(&device, compute_queue_family_index)
+            .expect("Failed to create the async compute command submitter");
 
         let mut gpu_allocator =
             self.create_allocator(instance.clone(), physical_device, device.clone());
 
-        let swapchain = create_swapchain(
-            self.width,
-            self.height,
-            self.preferred_present_mode,
-            &instance,
-            physical_device,
-            &device,
-            &surface,
-            &mut gpu_allocator,
-        );
-        self.width = swapchain.extent.width;
-        self.height = swapchain.extent.height;
+        let depth_format = select_depth_format(&instance, physical_device);
+        log::debug!("\tDepth format: {depth_format:?}");
+        let swapchain = surface.as_ref().map(|surface| {
+            create_swapchain(
+                self.width,
+                self.height,
+                self.preferred_present_mode,
+                self.fallback_present_mode,
+                &instance,
+                physical_device,
+                &device,
+                surface,
+                depth_format,
+                &mut gpu_allocator,
+            )
+        });
+        if let Some(swapchain) = &swapchain {
+            self.width = swapchain.extent.width;
+            self.height = swapchain.extent.height;
+
+            if swapchain.present_mode == self.preferred_present_mode {
+                log::info!("Using present mode {:?}", swapchain.present_mode);
+            } else {
+                log::warn!(
+                    "Preferred present mode {:?} is not supported by the surface, using {:?} instead",
+                    self.preferred_present_mode,
+                    swapchain.present_mode
+                );
+            }
+        }
 
+        let color_format = surface
+            .as_ref()
+            .map_or(COMPUTE_ONLY_SURFACE_FORMAT, |surface| surface.format)
+            .format;
         let primary_render_pass =
-            self.create_render_passes(&surface, &swapchain.depth_image, &device);
-
-        let swapchain_framebuffers = create_framebuffers(
-            self.width,
-            self.height,
-            primary_render_pass,
-            &swapchain,
-            &device,
-        );
+            self.create_render_passes(color_format, depth_format, (false, false), &device);
+        let resume_render_pass =
+            self.create_render_passes(color_format, depth_format, (true, true), &device);
+        let load_color_render_pass =
+            self.create_render_passes(color_format, depth_format, (true, false), &device);
+        let load_depth_render_pass =
+            self.create_render_passes(color_format, depth_format, (false, true), &device);
+
+        let swapchain_framebuffers = swapchain
+            .as_ref()
+            .map(|swapchain| {
+                create_framebuffers(
+                    self.width,
+                    self.height,
+                    primary_render_pass,
+                    swapchain,
+                    &device,
+                )
+            })
+            .unwrap_or_default();
 
         let command_pool_create_info = vk::CommandPoolCreateInfo::default()
             .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
@@ -957,27 +1688,79 @@ impl<'a> RendererBuilder<'a> {
             .expect("Failed to create renderer command pool");
         let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::default()
             .command_pool(command_pool)
-            .command_buffer_count(1)
+            .command_buffer_count(2 * u32::try_from(self.frames_in_flight).unwrap())
             .level(vk::CommandBufferLevel::PRIMARY);
-        let primary_command_buffer =
+        let command_buffers =
             unsafe { device.allocate_command_buffers(&command_buffer_allocate_info) }
-                .expect("Failed to allocate primary command buffer")[0];
-
-        let sync_objects = self.create_sync_objects(&device);
+                .expect("Failed to allocate primary command buffers");
+        let frames = command_buffers
+            .chunks_exact(2)
+            .map(|command_buffers| FrameData {
+                sync_objects: self.create_sync_objects(&device),
+                primary_command_buffer: command_buffers[0],
+                barrier_command_buffer: command_buffers[1],
+                waited_semaphores: vec![],
+                released_image_views: vec![],
+                released_buffers: vec![],
+            })
+            .collect::<Vec<_>>();
+        let primary_command_buffer = frames[0].primary_command_buffer;
 
         let (descriptor_pool, descriptors) = self.create_descriptors(&device, &mut gpu_allocator);
+        let dynamic_uniforms = DynamicUniformBuffer::new(
+            self.dynamic_uniform_capacity,
+            self.frames_in_flight,
+            &device_properties,
+            &device,
+            &mut gpu_allocator,
+        )
+        .expect("Failed to create the dynamic uniform buffer");
 
+        let mut sampler_cache = SamplerCache::default();
         let default_texture_ref = Texture::builder()
             .build_default_internal(
                 &device,
                 graphics_queue.handle,
                 &mut gpu_allocator,
                 &mut command_uploader,
+                &mut sampler_cache,
             )
             .expect("Default texture creation failed");
 
-        ThreadSafeRef::new(Renderer {
-            clear_color: [0.0_f32, 0.0_f32, 0.0_f32, 1.0_f32],
+        let gpu_profiler = if self.gpu_profiling {
+            let queue_families_properties =
+                unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
+            let timestamp_valid_bits = queue_families_properties
+                [usize::try_from(queue_family_index).expect("Unsupported architecture")]
+            .timestamp_valid_bits;
+
+            GpuProfiler::new(
+                &device,
+                graphics_queue.handle,
+                &device_properties,
+                timestamp_valid_bits,
+                &mut command_uploader,
+            )
+            .map_err(|error| log::warn!("GPU profiling disabled: {error}"))
+            .ok()
+        } else {
+            None
+        };
+
+        let pipeline_statistics = if self.pipeline_statistics {
+            PipelineStatistics::new(&device, &enabled_features)
+                .map_err(|error| log::warn!("Pipeline statistics disabled: {error}"))
+                .ok()
+        } else {
+            None
+        };
+
+        let mut renderer = Renderer {
+            clear_config: ClearConfig::default(),
+            flipped_viewport: self.flipped_viewport,
+            render_region: None,
+            active_render_target: None,
+            swapchain_pass_begun: false,
 
             needs_resize: false,
             window_width: self.width,
@@ -985,29 +1768,85 @@ impl<'a> RendererBuilder<'a> {
             framebuffer_width: self.width,
             framebuffer_height: self.height,
             next_image_index: 0,
+            capture_requested: false,
+            pending_capture: None,
 
             debug_messenger,
+            debug_utils_device,
+            auto_naming: self.auto_naming,
+            time_override: None,
+            lights: vec![],
+            stale_lights_buffers: vec![false; self.frames_in_flight],
 
             default_texture_ref,
 
             command_uploader,
+            async_compute,
+            gpu_profiler,
+            pipeline_statistics,
+            entity_ids: None,
             descriptors,
+            dynamic_uniforms,
             descriptor_pool,
-            sync_objects,
+            descriptor_set_cache: DescriptorSetCache::default(),
+            sampler_cache,
+            #[cfg(feature = "bindless")]
+            bindless_textures: None,
+            frames,
+            current_frame: 0,
             primary_command_buffer,
+            frame_barrier: PipelineBarrier::default(),
+            frame_wait_semaphores: vec![],
+            released_image_views: vec![],
+            released_buffers: vec![],
             command_pool,
             swapchain_framebuffers,
             primary_render_pass,
+            depth_format,
+            resume_render_pass,
+            load_color_render_pass,
+            load_depth_render_pass,
+            headless_target: None,
             swapchain,
             graphics_queue,
+            compute_queue,
             allocator: Some(ThreadSafeRef::new(gpu_allocator)),
             device,
             device_properties,
+            capabilities,
             physical_device,
             surface,
             instance,
             entry,
-        })
+        };
+
+        if self.headless {
+            let headless_target =
+                RenderTarget::new(self.width, self.height, color_format, &mut renderer)
+                    .expect("Failed to create the headless render target");
+            renderer.headless_target = Some(headless_target);
+        }
+
+        if self.entity_picking {
+            renderer.entity_ids = EntityIdPass::new(
+                renderer.framebuffer_width,
+                renderer.framebuffer_height,
+                &mut renderer,
+            )
+            .map_err(|error| log::warn!("Entity picking disabled: {error}"))
+            .ok();
+        }
+
+        #[cfg(feature = "bindless")]
+        if bindless::is_supported(&renderer.instance, renderer.physical_device) {
+            let capacity = bindless::texture_capacity(&renderer.instance, renderer.physical_device);
+            renderer.bindless_textures = BindlessTextures::new(capacity, &renderer.device)
+                .map_err(|error| log::warn!("Bindless textures disabled: {error}"))
+                .ok();
+            renderer.capabilities.features.bindless_textures = renderer.bindless_textures.is_some();
+        }
+
+        ThreadSafeRef::new(renderer)
     }
 }
 
@@ -1027,27 +1866,543 @@ impl Renderer {
         (self.window_width, self.window_height)
     }
 
-    pub(crate) fn begin_frame(&mut self) -> bool {
-        if self.window_width == 0 || self.window_height == 0 {
-            return false;
+    /// Names `handle` after `name` if automatic naming is enabled (see
+    /// [`RendererBuilder::with_auto_naming`]).
+    pub(crate) fn auto_name(&self, handle: impl vk::Handle, name: &str) {
+        if self.auto_naming {
+            self.set_debug_name(handle, name);
         }
+    }
 
-        unsafe {
-            self.device
-                .wait_for_fences(&[self.sync_objects.render_fence], true, u64::MAX)
+    /// Names `handle` after `name` in validation messages and captures (e.g. RenderDoc). This
+    /// relies on the `VK_EXT_debug_utils` extension, only enabled in debug builds, and does
+    /// nothing without it. Naming is only a debugging aid, so failures are logged rather than
+    /// returned.
+    pub fn set_debug_name<H: vk::Handle>(&self, handle: H, name: &str) {
+        let Some(debug_utils_device) = &self.debug_utils_device else {
+            return;
+        };
+        let Ok(name) = CString::new(name) else {
+            log::warn!("Could not name object {name:?}, as it contains a nul byte");
+            return;
+        };
+
+        let name_info = vk::DebugUtilsObjectNameInfoEXT::default()
+            .object_handle(handle)
+            .object_name(&name);
+        if let Err(result) = unsafe { debug_utils_device.set_debug_utils_object_name(&name_info) } {
+            log::warn!("Failed to name object {name:?}: {result}");
         }
-        .expect("Failed to wait for the render fence");
+    }
 
-        let next_image_index_maybe = unsafe {
-            self.swapchain.loader.acquire_next_image(
-                self.swapchain.handle,
-                u64::MAX,
-                self.sync_objects.present_semaphore,
-                vk::Fence::null(),
-            )
+    /// Restricts the frames to a region of the swapchain images, in pixels, starting with the next
+    /// frame. The region becomes the render area of the primary render pass, so only it is
+    /// cleared, and the viewports of the scene, debug draws and egui (see
+    /// [`crate::components::viewport::Viewport`]) are fractions of it instead of the whole
+    /// framebuffer. This lets the application compose morrigu's output with other content drawn
+    /// in the same window.
+    ///
+    /// The rest of the images is left undefined, as the swapchain images are not loaded at the
+    /// start of a frame. The region is clamped to the framebuffer when it is resized, but cameras
+    /// keep being resized to the window's dimensions: their aspect ratio should be set from
+    /// [`Renderer::render_region`] by the application.
+    pub fn set_render_region(&mut self, region: vk::Rect2D) {
+        self.render_region = Some(region);
+    }
+
+    /// Renders to the whole framebuffer again (see [`Renderer::set_render_region`]).
+    pub fn reset_render_region(&mut self) {
+        self.render_region = None;
+    }
+
+    /// Region of the framebuffer the frames are rendered to, clamped to the current framebuffer.
+    /// While rendering to a target (see [`Renderer::begin_frame_to`]), this is the whole target.
+    pub fn render_region(&self) -> vk::Rect2D {
+        if let Some(extent) = self.active_render_target {
+            return vk::Rect2D {
+                extent,
+                ..Default::default()
+            };
+        }
+        let Some(region) = self.render_region else {
+            return vk::Rect2D {
+                extent: vk::Extent2D {
+                    width: self.framebuffer_width,
+                    height: self.framebuffer_height,
+                },
+                ..Default::default()
+            };
         };
 
-        match next_image_index_maybe {
+        let x = region.offset.x.clamp(0, self.framebuffer_width as i32);
+        let y = region.offset.y.clamp(0, self.framebuffer_height as i32);
+        vk::Rect2D {
+            offset: vk::Offset2D { x, y },
+            extent: vk::Extent2D {
+                width: region.extent.width.min(self.framebuffer_width - x as u32),
+                height: region.extent.height.min(self.framebuffer_height - y as u32),
+            },
+        }
+    }
+
+    /// Whether the Y axis of the NDC points up (see [`RendererBuilder::with_flipped_viewport`]).
+    pub fn is_viewport_flipped(&self) -> bool {
+        self.flipped_viewport
+    }
+
+    /// Bytes of the dynamic uniform buffer written so far during the current frame, and its
+    /// capacity (see [`RendererBuilder::with_dynamic_uniform_capacity`]).
+    pub fn dynamic_uniform_usage(&self) -> (u64, u64) {
+        (
+            self.dynamic_uniforms.used(),
+            self.dynamic_uniforms.capacity(),
+        )
+    }
+
+    /// Index of the frame being recorded among the frames in flight, between 0 and
+    /// [`Renderer::frames_in_flight`] excluded. Resources written by the CPU every frame can be
+    /// duplicated per frame in flight and indexed with it, so that a frame never overwrites data
+    /// still read by the GPU.
+    #[profiling::skip]
+    pub fn current_frame_index(&self) -> usize {
+        self.current_frame
+    }
+
+    /// See [`RendererBuilder::with_frames_in_flight`].
+    #[profiling::skip]
+    pub fn frames_in_flight(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Sets 0 (time) and 1 (lights) of the frame being recorded, which every material binds
+    /// before its own sets.
+    pub(crate) fn frame_descriptor_sets(&self) -> [vk::DescriptorSet; 2] {
+        [
+            self.descriptors[0].handles[self.current_frame],
+            self.descriptors[1].handles[self.current_frame],
+        ]
+    }
+
+    pub(crate) fn dynamic_uniform_buffer(&self) -> vk::Buffer {
+        self.dynamic_uniforms.handle()
+    }
+
+    /// Writes `data` to the dynamic uniform buffer for the current frame, and returns the dynamic
+    /// offset to bind it with.
+    pub(crate) fn push_dynamic_uniform(&mut self, data: &[u8]) -> Result<u32, DynamicUniformError> {
+        self.dynamic_uniforms.push(data)
+    }
+
+    /// Limits of the selected device, and the optional features that are enabled on it.
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    /// Number of live buffers, images, pipelines, descriptor sets and samplers (see
+    /// [`ResourceCounts`] for what is counted). The counts are shared by all the renderers of the
+    /// process, and include the renderer's own resources.
+    #[profiling::skip]
+    pub fn resource_counts(&self) -> ResourceCounts {
+        resource_registry::counts()
+    }
+
+    /// Present mode of the swapchain, which is the preferred one if the surface supports it (see
+    /// [`RendererBuilder::with_fallback_present_mode`]). `None` for compute only renderers.
+    pub fn active_present_mode(&self) -> Option<vk::PresentModeKHR> {
+        self.swapchain
+            .as_ref()
+            .map(|swapchain| swapchain.present_mode)
+    }
+
+    /// Replaces the preferred present mode (see [`RendererBuilder::with_preferred_present_mode`]),
+    /// e.g. to toggle vsync with `FIFO` against `MAILBOX` or `IMMEDIATE`. The swapchain is
+    /// recreated with it after the current frame is presented. Modes the surface doesn't support
+    /// are replaced by `FIFO`. Does nothing for compute only renderers.
+    pub fn set_present_mode(&mut self, present_mode: vk::PresentModeKHR) {
+        let (Some(swapchain), Some(surface)) = (self.swapchain.as_mut(), self.surface.as_ref())
+        else {
+            return;
+        };
+
+        let supported = unsafe {
+            surface
+                .loader
+                .get_physical_device_surface_present_modes(self.physical_device, surface.handle)
+        }
+        .is_ok_and(|present_modes| present_modes.contains(&present_mode));
+        let present_mode = if supported {
+            present_mode
+        } else {
+            log::warn!(
+                "Present mode {present_mode:?} is not supported by the surface, falling back to {:?}",
+                vk::PresentModeKHR::FIFO
+            );
+            vk::PresentModeKHR::FIFO
+        };
+
+        swapchain.preferred_present_mode = present_mode;
+        if swapchain.present_mode != present_mode {
+            self.needs_resize = true;
+        }
+    }
+
+    /// Format and color space of the swapchain images. Shaders writing to an HDR color space
+    /// must apply the matching transfer function, see [`crate::tone_mapping::ToneMapping`].
+    pub fn surface_format(&self) -> vk::SurfaceFormatKHR {
+        self.surface
+            .as_ref()
+            .map_or(COMPUTE_ONLY_SURFACE_FORMAT, |surface| surface.format)
+    }
+
+    /// Format of the depth attachment of the primary render pass, the first one supported by the
+    /// device among `D32_SFLOAT`, `D32_SFLOAT_S8_UINT` and `D24_UNORM_S8_UINT`. Render passes
+    /// whose pipelines must be compatible with the primary render pass (e.g. the ones of render
+    /// targets) use it as well.
+    #[profiling::skip]
+    pub fn depth_format(&self) -> vk::Format {
+        self.depth_format
+    }
+
+    /// Clears the whole depth buffer of the primary render pass (to the depth of
+    /// [`Renderer::clear_config`], or 1.0 if it keeps the previous depth) in the middle of the
+    /// frame, so that what is drawn next ignores the depth of what was drawn before (e.g. overlays,
+    /// or a first person weapon that must stay on top).
+    ///
+    /// The clear is recorded inside the ongoing render pass, ordered with the draws around it: it
+    /// only affects the draws recorded afterwards. As the render systems record their draws while
+    /// the ECS schedule runs, this is typically called from a system or from
+    /// [`crate::application::ApplicationState::after_systems`], before drawing the overlay.
+    pub fn clear_depth(&mut self) {
+        let clear_attachment = vk::ClearAttachment::default()
+            .aspect_mask(vk::ImageAspectFlags::DEPTH)
+            .clear_value(vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: self.clear_config.depth.unwrap_or(1.0),
+                    stencil: 0,
+                },
+            });
+        let clear_rect = vk::ClearRect::default()
+            .rect(self.render_region())
+            .base_array_layer(0)
+            .layer_count(1);
+
+        unsafe {
+            self.device.cmd_clear_attachments(
+                self.primary_command_buffer,
+                std::slice::from_ref(&clear_attachment),
+                std::slice::from_ref(&clear_rect),
+            )
+        };
+    }
+
+    /// Opens a named GPU zone in the current frame. Zones can be nested, and must be closed with
+    /// [`Renderer::end_gpu_zone`] before the end of the frame. Does nothing if GPU profiling is
+    /// disabled.
+    pub fn begin_gpu_zone(&mut self, name: &'static str) {
+        if let Some(gpu_profiler) = self.gpu_profiler.as_mut() {
+            gpu_profiler.begin_zone(name, &self.device, self.primary_command_buffer);
+        }
+    }
+
+    pub fn end_gpu_zone(&mut self) {
+        if let Some(gpu_profiler) = self.gpu_profiler.as_mut() {
+            gpu_profiler.end_zone(&self.device, self.primary_command_buffer);
+        }
+    }
+
+    /// Opens a named GPU zone (see [`Renderer::begin_gpu_zone`]), closed when the returned guard
+    /// is dropped. The pass measured is recorded through the guard.
+    pub fn gpu_scope(&mut self, name: &'static str) -> GpuScope<'_> {
+        GpuScope::new(name, self)
+    }
+
+    /// GPU timings of the last completed frame, empty if GPU profiling is disabled.
+    pub fn gpu_zones(&self) -> &[GpuZone] {
+        self.gpu_profiler
+            .as_ref()
+            .map(GpuProfiler::last_frame_zones)
+            .unwrap_or_default()
+    }
+
+    /// Milliseconds spent in every GPU zone of the last completed frame, by zone name (see
+    /// [`GpuProfiler::last_frame_timings`]). Empty if GPU profiling is disabled.
+    pub fn gpu_timings(&self) -> HashMap<String, f64> {
+        self.gpu_profiler
+            .as_ref()
+            .map(GpuProfiler::last_frame_timings)
+            .unwrap_or_default()
+    }
+
+    /// Counts of the work done by the GPU during the last completed frame, `None` if pipeline
+    /// statistics are disabled (see [`RendererBuilder::with_pipeline_statistics`]).
+    pub fn pipeline_stats(&self) -> Option<PipelineStats> {
+        self.pipeline_statistics
+            .as_ref()
+            .map(PipelineStatistics::last_frame_stats)
+    }
+
+    /// Pass the mesh renderer queues its draws to when entity picking is enabled (see
+    /// [`RendererBuilder::with_entity_picking`]), `None` while rendering to a target, as its
+    /// pixels don't match the framebuffer's.
+    #[profiling::skip]
+    pub(crate) fn entity_id_pass(&mut self) -> Option<&mut EntityIdPass> {
+        if self.active_render_target.is_some() {
+            return None;
+        }
+        self.entity_ids.as_mut()
+    }
+
+    /// Entity drawn by the mesh renderer at pixel (`x`, `y`) of the framebuffer during the last
+    /// frame, `None` if there is none, if the position is outside of the framebuffer or if entity
+    /// picking is disabled (see [`RendererBuilder::with_entity_picking`]). Like
+    /// [`Renderer::capture_frame`], this must be called outside of a frame, and waits for the
+    /// last frame to be done executing.
+    ///
+    /// The position is in physical pixels from the top left corner of the window (e.g. the cursor
+    /// position given by winit). The entity IDs are rendered with the same viewports as the
+    /// scene, so window positions map to them directly whether the viewport is flipped or not
+    /// (see [`RendererBuilder::with_flipped_viewport`]).
+    pub fn pick_entity_at(&self, x: f32, y: f32) -> Option<Entity> {
+        let entity_ids = self.entity_ids.as_ref()?;
+        let [width, height] = entity_ids.dimensions();
+        if !(0.0..width as f32).contains(&x) || !(0.0..height as f32).contains(&y) {
+            return None;
+        }
+        let frame_index = entity_ids.last_frame_index()?;
+
+        let result = unsafe {
+            self.device.wait_for_fences(
+                &[self.frames[frame_index].sync_objects.render_fence],
+                true,
+                u64::MAX,
+            )
+        }
+        .map_err(EntityPickingError::FenceWaitFailed)
+        .and_then(|()| entity_ids.entity_at(x as u32, y as u32, self));
+
+        result
+            .map_err(|error| log::error!("Failed to pick entity: {error}"))
+            .ok()
+            .flatten()
+    }
+
+    /// Requests `image` to be in `layout` while the current frame's commands execute.
+    ///
+    /// The layout transitions requested during a frame aren't recorded in the render pass (where
+    /// they aren't allowed), but batched in a single pipeline barrier, submitted right before the
+    /// frame's commands. The image's tracked layout ([`AllocatedImage::layout`]) is updated
+    /// immediately, so requesting the same layout again in the frame is free. Previous writes to
+    /// the image (e.g. by a compute shader) are made visible to the frame's shaders.
+    pub fn request_image_layout(&mut self, image: &mut AllocatedImage, layout: vk::ImageLayout) {
+        if image.layout == layout {
+            return;
+        }
+
+        let aspect_mask = image_aspect_mask(image.format);
+        let dst_access_mask = if layout == vk::ImageLayout::GENERAL {
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE
+        } else {
+            vk::AccessFlags::SHADER_READ
+        };
+
+        self.frame_barrier.src_stage_mask |= vk::PipelineStageFlags::ALL_COMMANDS;
+        self.frame_barrier.dst_stage_mask |=
+            vk::PipelineStageFlags::VERTEX_SHADER | vk::PipelineStageFlags::FRAGMENT_SHADER;
+        self.frame_barrier.image_memory_barriers.push(
+            vk::ImageMemoryBarrier::default()
+                .src_access_mask(vk::AccessFlags::MEMORY_WRITE)
+                .dst_access_mask(dst_access_mask)
+                .old_layout(image.layout)
+                .new_layout(layout)
+                .image(image.handle)
+                .subresource_range(
+                    vk::ImageSubresourceRange::default()
+                        .aspect_mask(aspect_mask)
+                        .level_count(vk::REMAINING_MIP_LEVELS)
+                        .layer_count(vk::REMAINING_ARRAY_LAYERS),
+                ),
+        );
+        image.layout = layout;
+    }
+
+    /// Number of distinct descriptor sets currently shared by cached mesh renderings (see
+    /// [`crate::components::mesh_rendering::MeshRendering::new_cached`]).
+    pub fn cached_descriptor_set_count(&self) -> usize {
+        self.descriptor_set_cache.len()
+    }
+
+    /// Writes a texture to the bindless texture array (see [`crate::bindless::BINDLESS_SET`]), and
+    /// returns its index in the array, which shaders receive through their own data (e.g. a push
+    /// constant or a storage buffer) to sample it.
+    ///
+    /// The texture's current view and sampler are written, so it must be registered again if they
+    /// change (see [`Texture::set_resident_mips`]), and unregistered before it is destroyed.
+    #[cfg(feature = "bindless")]
+    pub fn register_bindless_texture(
+        &mut self,
+        texture_ref: &ThreadSafeRef<Texture>,
+    ) -> Result<u32, BindlessTextureError> {
+        let bindless_textures = self
+            .bindless_textures
+            .as_mut()
+            .ok_or(BindlessTextureError::Unsupported)?;
+
+        bindless_textures.register(&texture_ref.lock(), &self.device)
+    }
+
+    /// Frees the slot of a texture registered with [`Renderer::register_bindless_texture`], to be
+    /// reused by the next registered texture. The slot must not be sampled anymore.
+    #[cfg(feature = "bindless")]
+    pub fn unregister_bindless_texture(&mut self, index: u32) {
+        if let Some(bindless_textures) = self.bindless_textures.as_mut() {
+            bindless_textures.unregister(index);
+        }
+    }
+
+    #[cfg(feature = "bindless")]
+    pub(crate) fn bindless_textures(&self) -> Option<&BindlessTextures> {
+        self.bindless_textures.as_ref()
+    }
+
+    /// Number of distinct samplers shared by the textures, one per combination of sampler
+    /// settings and mip level count in use.
+    pub fn cached_sampler_count(&self) -> usize {
+        self.sampler_cache.len()
+    }
+
+    /// Allocation counters of the staging buffers reused by the texture and mesh uploads.
+    pub fn staging_pool_stats(&self) -> StagingPoolStats {
+        self.command_uploader
+            .staging_pool
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .stats()
+    }
+
+    /// Writes the time uniform bound at set 0, binding 0:
+    /// ```glsl
+    /// layout(set = 0, binding = 0) uniform TimeData {
+    ///     vec4 time; // (t / 20, t, t * 2, t * 3), t being the seconds elapsed since startup
+    /// } u_TimeData;
+    /// ```
+    /// Each frame in flight has its own buffer, only the current frame's one is written.
+    fn update_time_buffer(&mut self, total_elapsed: Duration) {
+        let time_data = self
+            .time_override
+            .unwrap_or_else(|| time_uniform_data(total_elapsed));
+
+        if let Some(time_buffer) = self.descriptors[0].buffers.get_mut(self.current_frame) {
+            if let Err(error) = time_buffer.upload_pod(time_data) {
+                log::warn!("Failed to update the time buffer: {error}");
+            }
+        }
+    }
+
+    /// Replaces the lights of the storage buffer bound at
+    /// [`crate::components::light::LIGHTS_SET`] (see [`GpuLight`] for its layout), which
+    /// [`crate::systems::light::gather_lights`] does every frame from the light components. Only
+    /// the first [`MAX_LIGHTS`] lights are kept.
+    ///
+    /// Each frame in flight has its own buffer, written when the frame is submitted if the lights
+    /// changed since the buffer was last written, so this can be called at any time.
+    pub fn upload_lights(&mut self, lights: &[GpuLight]) {
+        let lights = &lights[..lights.len().min(MAX_LIGHTS)];
+        if self.lights != lights {
+            self.lights = lights.to_vec();
+            self.stale_lights_buffers.fill(true);
+        }
+    }
+
+    /// Writes the lights to the current frame's buffer if it misses them. The frame's previous
+    /// submission is done executing, so the buffer isn't read anymore.
+    fn update_lights_buffer(&mut self) {
+        if !self.stale_lights_buffers[self.current_frame] {
+            return;
+        }
+        let Some(lights_buffer) = self.descriptors[1].buffers.get_mut(self.current_frame) else {
+            return;
+        };
+
+        let lights = &self.lights;
+        let light_count: u32 = lights.len().try_into().unwrap();
+        let result = lights_buffer
+            .upload_data_at(
+                mem::size_of::<[u32; 4]>().try_into().unwrap(),
+                bytemuck::cast_slice(lights),
+            )
+            .and_then(|()| {
+                lights_buffer.upload_data_at(0, bytemuck::bytes_of(&[light_count, 0, 0, 0]))
+            });
+        match result {
+            Ok(()) => self.stale_lights_buffers[self.current_frame] = false,
+            Err(error) => log::warn!("Failed to update the lights buffer: {error}"),
+        }
+    }
+
+    /// Makes the following frames write `time` to the time uniform (bound at set 0, binding 0)
+    /// instead of `(t / 20, t, t * 2, t * 3)`, t being the seconds elapsed since startup, e.g. to
+    /// pause or slow down the shaders' animations.
+    pub fn set_time(&mut self, time: Vec4) {
+        self.time_override = Some(time);
+    }
+
+    /// Goes back to writing the time elapsed since startup to the time uniform, after
+    /// [`Renderer::set_time`].
+    pub fn clear_time_override(&mut self) {
+        self.time_override = None;
+    }
+
+    /// Starts recording a frame, and returns whether it was started (e.g. not while the window is
+    /// minimized). [`crate::application::Application`] calls this and [`Renderer::end_frame`]
+    /// around every update, only headless renderers (see [`RendererBuilder::headless`]) need to
+    /// call them directly.
+    pub fn begin_frame(&mut self, total_elapsed: Duration) -> bool {
+        if self.window_width == 0 || self.window_height == 0 {
+            return false;
+        }
+        if self.swapchain.is_none() && self.headless_target.is_none() {
+            return false;
+        }
+
+        // Only the frame that last used this frame's objects needs to be done executing
+        let frame = &mut self.frames[self.current_frame];
+        unsafe {
+            self.device
+                .wait_for_fences(&[frame.sync_objects.render_fence], true, u64::MAX)
+        }
+        .expect("Failed to wait for the render fence");
+        for semaphore in frame.waited_semaphores.drain(..) {
+            unsafe { self.device.destroy_semaphore(semaphore, None) };
+        }
+        for image_view in frame.released_image_views.drain(..) {
+            unsafe { self.device.destroy_image_view(image_view, None) };
+        }
+        let released_buffers = mem::take(&mut frame.released_buffers);
+        self.primary_command_buffer = frame.primary_command_buffer;
+        for mut buffer in released_buffers {
+            buffer.destroy(&self.device, &mut self.allocator());
+        }
+        self.dynamic_uniforms.reset(self.current_frame);
+        self.update_time_buffer(total_elapsed);
+
+        if self.headless_target.is_some() {
+            self.start_recording();
+            self.begin_frame_to(FrameTarget::Swapchain);
+            return true;
+        }
+
+        let frame = &self.frames[self.current_frame];
+        let swapchain = self.swapchain.as_ref().unwrap();
+        let next_image_index_maybe = unsafe {
+            swapchain.loader.acquire_next_image(
+                swapchain.handle,
+                u64::MAX,
+                frame.sync_objects.present_semaphore,
+                vk::Fence::null(),
+            )
+        };
+
+        match next_image_index_maybe {
             Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
                 self.recreate_swapchain();
                 false
@@ -1058,91 +2413,286 @@ impl Renderer {
                     log::debug!("Suboptimal frame image acquired (probably due to resize)");
                 }
 
-                unsafe { self.device.reset_fences(&[self.sync_objects.render_fence]) }
-                    .expect("Failed to reset the render fence");
-
                 self.next_image_index = next_image_index;
-                let next_image_index: usize = next_image_index
-                    .try_into()
-                    .expect("Unsupported architecture");
+                self.start_recording();
+                self.begin_frame_to(FrameTarget::Swapchain);
 
-                unsafe {
-                    self.device.begin_command_buffer(
-                        self.primary_command_buffer,
-                        &vk::CommandBufferBeginInfo {
-                            flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
-                            ..Default::default()
-                        },
-                    )
-                }
-                .expect("Failed to start command buffer");
-
-                let clear_values = [
-                    vk::ClearValue {
-                        color: vk::ClearColorValue {
-                            float32: self.clear_color,
-                        },
-                    },
-                    vk::ClearValue {
-                        depth_stencil: vk::ClearDepthStencilValue {
-                            depth: 1.0_f32,
-                            stencil: 0,
-                        },
-                    },
-                ];
-                let rp_begin_info = vk::RenderPassBeginInfo::default()
-                    .render_pass(self.primary_render_pass)
-                    .framebuffer(self.swapchain_framebuffers[next_image_index])
-                    .render_area(vk::Rect2D {
-                        extent: vk::Extent2D {
-                            width: self.framebuffer_width,
-                            height: self.framebuffer_height,
-                        },
-                        ..Default::default()
-                    })
-                    .clear_values(&clear_values);
+                true
+            }
+        }
+    }
 
-                unsafe {
-                    self.device.cmd_begin_render_pass(
-                        self.primary_command_buffer,
-                        &rp_begin_info,
-                        vk::SubpassContents::INLINE,
-                    )
-                };
+    /// Resets the current frame's fence and begins its primary command buffer.
+    fn start_recording(&mut self) {
+        self.swapchain_pass_begun = false;
+        let frame = &self.frames[self.current_frame];
+        unsafe { self.device.reset_fences(&[frame.sync_objects.render_fence]) }
+            .expect("Failed to reset the render fence");
 
-                true
+        unsafe {
+            self.device.begin_command_buffer(
+                self.primary_command_buffer,
+                &vk::CommandBufferBeginInfo {
+                    flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+                    ..Default::default()
+                },
+            )
+        }
+        .expect("Failed to start command buffer");
+
+        if let Some(gpu_profiler) = self.gpu_profiler.as_mut() {
+            gpu_profiler.begin_frame(&self.device, self.primary_command_buffer);
+            gpu_profiler.begin_zone("GPU frame", &self.device, self.primary_command_buffer);
+        }
+        if let Some(pipeline_statistics) = self.pipeline_statistics.as_mut() {
+            pipeline_statistics.begin_frame(&self.device, self.primary_command_buffer);
+        }
+    }
+
+    /// Variant of the primary render pass matching [`Renderer::clear_config`] for the frame
+    /// rendered to `image_index`, which is flagged as rendered to.
+    fn frame_render_pass(&mut self, image_index: usize) -> vk::RenderPass {
+        let swapchain = self.swapchain.as_mut().unwrap();
+        // The depth image is shared by all the swapchain images
+        let load_depth =
+            self.clear_config.depth.is_none() && swapchain.rendered_images.contains(&true);
+        let load_color =
+            self.clear_config.color.is_none() && swapchain.rendered_images[image_index];
+        swapchain.rendered_images[image_index] = true;
+
+        match (load_color, load_depth) {
+            (false, false) => self.primary_render_pass,
+            (true, false) => self.load_color_render_pass,
+            (false, true) => self.load_depth_render_pass,
+            (true, true) => self.resume_render_pass,
+        }
+    }
+
+    fn begin_swapchain_render_pass(&self, render_pass: vk::RenderPass, image_index: usize) {
+        let clear_values = [
+            vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: self.clear_config.color.unwrap_or_default(),
+                },
+            },
+            vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: self.clear_config.depth.unwrap_or(1.0),
+                    stencil: 0,
+                },
+            },
+        ];
+        let rp_begin_info = vk::RenderPassBeginInfo::default()
+            .render_pass(render_pass)
+            .framebuffer(self.swapchain_framebuffers[image_index])
+            .render_area(self.render_region())
+            .clear_values(&clear_values);
+
+        unsafe {
+            self.device.cmd_begin_render_pass(
+                self.primary_command_buffer,
+                &rp_begin_info,
+                vk::SubpassContents::INLINE,
+            )
+        };
+    }
+
+    /// Redirects the rest of the frame's rendering to `target` (e.g. to show the scene in an egui
+    /// image), until [`Renderer::end_frame_to`] switches back to the swapchain. This must be
+    /// called while a frame is being recorded (e.g. in `on_update`), before the render systems.
+    ///
+    /// The swapchain's render pass is interrupted, and resumed later without clearing what was
+    /// drawn before. While the target is active, [`Renderer::render_region`] covers the whole
+    /// target, and the materials used must be built for its render pass (see
+    /// [`crate::material::MaterialBuilder::for_render_target`]). The target is cleared to the color
+    /// of [`Renderer::clear_config`]. Beginning another target ends the current one first.
+    ///
+    /// [`Renderer::begin_frame`] starts every frame with [`FrameTarget::Swapchain`], which
+    /// switches back to the swapchain when given mid-frame, like [`Renderer::end_frame_to`].
+    pub fn begin_frame_to<'a>(&mut self, target: impl Into<FrameTarget<'a>>) {
+        match target.into() {
+            FrameTarget::Swapchain if !self.swapchain_pass_begun => {
+                self.begin_swapchain_pass(false);
+                self.swapchain_pass_begun = true;
+            }
+            FrameTarget::Swapchain => {
+                if self.active_render_target.is_none() {
+                    log::warn!("The frame is already rendering to the swapchain");
+                    return;
+                }
+                self.end_render_target_pass();
+                self.begin_swapchain_pass(true);
+            }
+            FrameTarget::RenderTarget(target) => {
+                self.interrupt_render_pass();
+                target.begin_render_pass(
+                    self.primary_command_buffer,
+                    self.clear_config.color.unwrap_or_default(),
+                    &self.device,
+                );
+                let [width, height] = target.dimensions();
+                self.active_render_target = Some(vk::Extent2D { width, height });
             }
         }
     }
 
-    pub(crate) fn end_frame(&mut self) {
+    /// Ends the pass started by [`Renderer::begin_frame_to`], and resumes rendering to the
+    /// swapchain (like `begin_frame_to(FrameTarget::Swapchain)`). The target's texture can be
+    /// sampled by the draws recorded after this.
+    pub fn end_frame_to(&mut self) {
+        if self.active_render_target.is_none() {
+            log::warn!("end_frame_to was called without an active render target");
+            return;
+        }
+
+        self.begin_frame_to(FrameTarget::Swapchain);
+    }
+
+    /// Records `record` outside of any render pass, in the middle of the frame (e.g. the passes of
+    /// a [`crate::shadows::ShadowMap`]). The current render pass is interrupted and the swapchain's
+    /// one resumed afterwards, as with [`Renderer::begin_frame_to`] and
+    /// [`Renderer::end_frame_to`].
+    pub(crate) fn record_outside_render_pass(
+        &mut self,
+        record: impl FnOnce(&ash::Device, vk::CommandBuffer),
+    ) {
+        self.interrupt_render_pass();
+        record(&self.device, self.primary_command_buffer);
+        self.begin_swapchain_pass(true);
+    }
+
+    /// Ends the active render target's pass, or the swapchain's one.
+    fn interrupt_render_pass(&mut self) {
+        if self.active_render_target.is_some() {
+            self.end_render_target_pass();
+        } else {
+            unsafe { self.device.cmd_end_render_pass(self.primary_command_buffer) };
+        }
+    }
+
+    /// Begins the swapchain's render pass, initialized as configured by [`Renderer::clear_config`]
+    /// at the start of the frame, or loading what was drawn before when `resume` is set.
+    fn begin_swapchain_pass(&mut self, resume: bool) {
+        if let Some(headless_target_ref) = &self.headless_target {
+            // The headless target's render pass can't load what was drawn before, see
+            // `RendererBuilder::headless`
+            headless_target_ref.lock().begin_render_pass(
+                self.primary_command_buffer,
+                self.clear_config.color.unwrap_or_default(),
+                &self.device,
+            );
+            return;
+        }
+
+        let image_index = self
+            .next_image_index
+            .try_into()
+            .expect("Unsupported architecture");
+        let render_pass = if resume {
+            self.resume_render_pass
+        } else {
+            self.frame_render_pass(image_index)
+        };
+        self.begin_swapchain_render_pass(render_pass, image_index);
+    }
+
+    fn end_render_target_pass(&mut self) {
         unsafe { self.device.cmd_end_render_pass(self.primary_command_buffer) };
+        self.active_render_target = None;
+    }
+
+    /// Ends the frame started by [`Renderer::begin_frame`], submits it and presents it.
+    pub fn end_frame(&mut self) {
+        if self.active_render_target.is_some() {
+            log::warn!("The frame ended while rendering to a target, see Renderer::end_frame_to");
+            self.end_frame_to();
+        }
+        unsafe { self.device.cmd_end_render_pass(self.primary_command_buffer) };
+        if let Some(entity_ids) = self.entity_ids.as_mut() {
+            entity_ids.record(
+                self.current_frame,
+                &self.device,
+                self.primary_command_buffer,
+            );
+        }
+        if let Some(pipeline_statistics) = self.pipeline_statistics.as_mut() {
+            pipeline_statistics.end_frame(&self.device, self.primary_command_buffer);
+        }
+        if let Some(gpu_profiler) = self.gpu_profiler.as_mut() {
+            gpu_profiler.end_zone(&self.device, self.primary_command_buffer);
+        }
+        if mem::take(&mut self.capture_requested) {
+            self.record_capture();
+        }
         unsafe { self.device.end_command_buffer(self.primary_command_buffer) }
             .expect("Failed to record command buffer");
+        self.update_lights_buffer();
 
-        let submit_info = vk::SubmitInfo::default()
-            .wait_semaphores(std::slice::from_ref(&self.sync_objects.present_semaphore))
-            .wait_dst_stage_mask(&[vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT])
-            .command_buffers(std::slice::from_ref(&self.primary_command_buffer))
-            .signal_semaphores(std::slice::from_ref(&self.sync_objects.render_semaphore));
+        let frame = &mut self.frames[self.current_frame];
+        let mut command_buffers = vec![];
+        if !self.frame_barrier.is_empty() {
+            unsafe {
+                self.device.begin_command_buffer(
+                    frame.barrier_command_buffer,
+                    &vk::CommandBufferBeginInfo::default()
+                        .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+                )
+            }
+            .expect("Failed to start barrier command buffer");
+            self.frame_barrier
+                .record(&self.device, frame.barrier_command_buffer);
+            unsafe { self.device.end_command_buffer(frame.barrier_command_buffer) }
+                .expect("Failed to record barrier command buffer");
+
+            command_buffers.push(frame.barrier_command_buffer);
+            self.frame_barrier = PipelineBarrier::default();
+        }
+        command_buffers.push(frame.primary_command_buffer);
+
+        let (mut wait_semaphores, mut wait_stages): (Vec<_>, Vec<_>) =
+            self.frame_wait_semaphores.drain(..).unzip();
+        frame.waited_semaphores.extend_from_slice(&wait_semaphores);
+        frame
+            .released_image_views
+            .append(&mut self.released_image_views);
+        frame.released_buffers.append(&mut self.released_buffers);
+        let mut submit_info = vk::SubmitInfo::default().command_buffers(&command_buffers);
+        // Headless renderers have no swapchain image to wait for nor to present
+        if self.headless_target.is_none() {
+            wait_semaphores.push(frame.sync_objects.present_semaphore);
+            wait_stages.push(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT);
+            submit_info = submit_info
+                .signal_semaphores(std::slice::from_ref(&frame.sync_objects.render_semaphore));
+        }
+        submit_info = submit_info
+            .wait_semaphores(&wait_semaphores)
+            .wait_dst_stage_mask(&wait_stages);
         unsafe {
             self.device.queue_submit(
                 self.graphics_queue.handle,
                 &[submit_info],
-                self.sync_objects.render_fence,
+                frame.sync_objects.render_fence,
             )
         }
         .expect("Failed to submit command buffer to present queue");
 
+        if self.headless_target.is_some() {
+            self.current_frame = (self.current_frame + 1) % self.frames.len();
+            return;
+        }
+
+        // Frames are only started by renderers with a swapchain or a headless target
+        let swapchain = self.swapchain.as_ref().unwrap();
         let present_info = vk::PresentInfoKHR::default()
-            .wait_semaphores(std::slice::from_ref(&self.sync_objects.render_semaphore))
-            .swapchains(std::slice::from_ref(&self.swapchain.handle))
+            .wait_semaphores(std::slice::from_ref(&frame.sync_objects.render_semaphore))
+            .swapchains(std::slice::from_ref(&swapchain.handle))
             .image_indices(std::slice::from_ref(&self.next_image_index));
         let result = unsafe {
-            self.swapchain
+            swapchain
                 .loader
                 .queue_present(self.graphics_queue.handle, &present_info)
         };
+        self.current_frame = (self.current_frame + 1) % self.frames.len();
 
         match result {
             Err(vk::Result::ERROR_OUT_OF_DATE_KHR) | Ok(true) => {
@@ -1165,6 +2715,9 @@ impl Renderer {
     }
 
     fn recreate_swapchain(&mut self) {
+        let Some(mut swapchain) = self.swapchain.take() else {
+            return;
+        };
         unsafe { self.device.device_wait_idle() }.expect("Failed to wait for device");
 
         // 1. Destroy all VK objects that will need to be recreated with the new swapchain.
@@ -1174,46 +2727,424 @@ impl Renderer {
         }
 
         //    - the depth image
-        let mut swapchain_depth_image = mem::take(&mut self.swapchain.depth_image);
-        swapchain_depth_image.destroy(self);
+        swapchain.depth_image.destroy(self);
 
         //    - the swapchain image views
-        for image_view in &self.swapchain.image_views {
+        for image_view in &swapchain.image_views {
             unsafe { self.device.destroy_image_view(*image_view, None) };
         }
 
         //    - and finally the swapchain itself
-        unsafe {
-            self.swapchain
-                .loader
-                .destroy_swapchain(self.swapchain.handle, None)
-        };
+        unsafe { swapchain.loader.destroy_swapchain(swapchain.handle, None) };
 
         // 2. Recreate all necessary VK objects
         //    - the swapchain itself
         //    - the swapchain image views
         //    - the depth image
-        self.swapchain = create_swapchain(
+        let swapchain = create_swapchain(
             self.window_width,
             self.window_height,
-            self.swapchain.preferred_present_mode,
+            swapchain.preferred_present_mode,
+            swapchain.fallback_present_mode,
             &self.instance,
             self.physical_device,
             &self.device,
-            &self.surface,
+            self.surface.as_ref().unwrap(),
+            self.depth_format,
             &mut self.allocator.as_ref().unwrap().lock(),
         );
 
         //    - and finally the framebuffers
-        self.framebuffer_width = std::cmp::min(self.window_width, self.swapchain.extent.width);
-        self.framebuffer_height = std::cmp::min(self.window_height, self.swapchain.extent.height);
+        self.framebuffer_width = std::cmp::min(self.window_width, swapchain.extent.width);
+        self.framebuffer_height = std::cmp::min(self.window_height, swapchain.extent.height);
         self.swapchain_framebuffers = create_framebuffers(
             self.framebuffer_width,
             self.framebuffer_height,
             self.primary_render_pass,
-            &self.swapchain,
+            &swapchain,
+            &self.device,
+        );
+        self.swapchain = Some(swapchain);
+
+        //    - the entity ID attachments, which match the framebuffers
+        if let Some(mut entity_ids) = self.entity_ids.take() {
+            match entity_ids.resize(self.framebuffer_width, self.framebuffer_height, self) {
+                Ok(()) => self.entity_ids = Some(entity_ids),
+                Err(error) => {
+                    log::warn!("Entity picking disabled: {error}");
+                    entity_ids.destroy(self);
+                }
+            }
+        }
+    }
+
+    /// Creates the surface of an additional window (e.g. a detached egui viewport). The surface must
+    /// support presenting from the graphics queue and the main surface's format, as materials are
+    /// built against it, `ERROR_FORMAT_NOT_SUPPORTED` is returned otherwise.
+    #[cfg(feature = "egui")]
+    pub(crate) fn create_window_surface(&self, window: &Window) -> Result<SurfaceInfo, vk::Result> {
+        let handle = unsafe {
+            ash_window::create_surface(
+                &self.entry,
+                &self.instance,
+                window
+                    .display_handle()
+                    .expect("window has no display_handle")
+                    .as_raw(),
+                window
+                    .window_handle()
+                    .expect("window has no window handle")
+                    .as_raw(),
+                None,
+            )
+        }?;
+        let surface = SurfaceInfo {
+            handle,
+            format: self.surface_format(),
+            loader: khr::surface::Instance::new(&self.entry, &self.instance),
+        };
+
+        let is_supported = unsafe {
+            surface.loader.get_physical_device_surface_support(
+                self.physical_device,
+                self.graphics_queue.family_index,
+                handle,
+            )
+        }
+        .and_then(|is_presentable| {
+            let formats = unsafe {
+                surface
+                    .loader
+                    .get_physical_device_surface_formats(self.physical_device, handle)
+            }?;
+
+            Ok(is_presentable
+                && formats.iter().any(|format| {
+                    format.format == surface.format.format
+                        && format.color_space == surface.format.color_space
+                }))
+        });
+        match is_supported {
+            Ok(true) => Ok(surface),
+            Ok(false) => {
+                self.destroy_window_surface(surface);
+                Err(vk::Result::ERROR_FORMAT_NOT_SUPPORTED)
+            }
+            Err(error) => {
+                self.destroy_window_surface(surface);
+                Err(error)
+            }
+        }
+    }
+
+    #[cfg(feature = "egui")]
+    pub(crate) fn destroy_window_surface(&self, surface: SurfaceInfo) {
+        unsafe { surface.loader.destroy_surface(surface.handle, None) };
+    }
+
+    /// Creates the swapchain of a surface from [`Renderer::create_window_surface`], with the
+    /// present modes of the main swapchain, and its framebuffers for the primary render pass.
+    #[cfg(feature = "egui")]
+    pub(crate) fn create_window_swapchain(
+        &mut self,
+        width: u32,
+        height: u32,
+        surface: &SurfaceInfo,
+    ) -> (SwapchainInfo, Vec<vk::Framebuffer>) {
+        let (preferred_present_mode, fallback_present_mode) = self.swapchain.as_ref().map_or(
+            (vk::PresentModeKHR::FIFO, vk::PresentModeKHR::FIFO),
+            |swapchain| {
+                (
+                    swapchain.preferred_present_mode,
+                    swapchain.fallback_present_mode,
+                )
+            },
+        );
+        let swapchain = create_swapchain(
+            width,
+            height,
+            preferred_present_mode,
+            fallback_present_mode,
+            &self.instance,
+            self.physical_device,
+            &self.device,
+            surface,
+            self.depth_format,
+            &mut self.allocator.as_ref().unwrap().lock(),
+        );
+        let framebuffers = create_framebuffers(
+            swapchain.extent.width,
+            swapchain.extent.height,
+            self.primary_render_pass,
+            &swapchain,
             &self.device,
         );
+
+        (swapchain, framebuffers)
+    }
+
+    /// Destroys a swapchain from [`Renderer::create_window_swapchain`], which must not be in use
+    /// anymore.
+    #[cfg(feature = "egui")]
+    pub(crate) fn destroy_window_swapchain(
+        &mut self,
+        mut swapchain: SwapchainInfo,
+        framebuffers: Vec<vk::Framebuffer>,
+    ) {
+        for framebuffer in framebuffers {
+            unsafe { self.device.destroy_framebuffer(framebuffer, None) };
+        }
+        swapchain.depth_image.destroy(self);
+        for image_view in &swapchain.image_views {
+            unsafe { self.device.destroy_image_view(*image_view, None) };
+        }
+        unsafe { swapchain.loader.destroy_swapchain(swapchain.handle, None) };
+    }
+
+    /// Requests a copy of the image rendered by the current frame, or by the next one if called
+    /// outside of a frame, to be read back with [`Renderer::capture_frame`] (e.g. for screenshots
+    /// or golden image tests). The copy is recorded at the end of the frame, before the image is
+    /// presented, so the presentation engine can't alter it.
+    ///
+    /// Headless renderers (see [`RendererBuilder::headless`]) copy their target instead of a
+    /// swapchain image. The swapchain's alpha channel is kept as rendered, even though the window
+    /// is opaque. Only 8 bits per channel RGBA and BGRA images can be captured, other formats
+    /// (e.g. HDR swapchains) return [`CaptureError::UnsupportedFormat`].
+    pub fn request_capture(&mut self) -> Result<(), CaptureError> {
+        if self
+            .swapchain
+            .as_ref()
+            .is_some_and(|swapchain| !swapchain.capturable)
+        {
+            return Err(CaptureError::UnsupportedUsage);
+        }
+        let format = match &self.headless_target {
+            Some(headless_target_ref) => {
+                headless_target_ref
+                    .lock()
+                    .texture_ref
+                    .lock()
+                    .image_ref
+                    .lock()
+                    .format
+            }
+            None => self.surface_format().format,
+        };
+        if !ImageData::is_format_supported(format) {
+            return Err(CaptureError::UnsupportedFormat(format));
+        }
+        self.capture_requested = true;
+
+        Ok(())
+    }
+
+    /// Records the copy requested with [`Renderer::request_capture`] of the image rendered by the
+    /// current frame to a readback buffer. A capture that wasn't read yet is replaced.
+    fn record_capture(&mut self) {
+        let (image, extent, layout, format) = match &self.headless_target {
+            Some(headless_target_ref) => {
+                let headless_target = headless_target_ref.lock();
+                let texture = headless_target.texture_ref.lock();
+                let image = texture.image_ref.lock();
+                let [width, height] = texture.dimensions;
+                (
+                    image.handle,
+                    vk::Extent2D { width, height },
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    image.format,
+                )
+            }
+            None => {
+                // Frames are only started by renderers with a swapchain or a headless target
+                let swapchain = self.swapchain.as_ref().unwrap();
+                (
+                    swapchain.images[self.next_image_index as usize],
+                    swapchain.extent,
+                    vk::ImageLayout::PRESENT_SRC_KHR,
+                    self.surface.as_ref().unwrap().format.format,
+                )
+            }
+        };
+
+        if let Some(previous_capture) = self.pending_capture.take() {
+            self.destroy_capture(previous_capture);
+        }
+
+        let size = u64::from(extent.width) * u64::from(extent.height) * 4;
+        let readback_buffer = match AllocatedBuffer::builder(size)
+            .with_name("Frame capture")
+            .with_usage(vk::BufferUsageFlags::TRANSFER_DST)
+            .with_memory_location(gpu_allocator::MemoryLocation::GpuToCpu)
+            .build_internal(&self.device, &mut self.allocator())
+        {
+            Ok(readback_buffer) => readback_buffer,
+            Err(error) => {
+                log::error!(
+                    "Failed to create the capture buffer, the frame won't be captured: {error}"
+                );
+                return;
+            }
+        };
+        self.record_image_readback(
+            self.primary_command_buffer,
+            image,
+            extent,
+            layout,
+            &readback_buffer,
+        );
+
+        self.pending_capture = Some(PendingCapture {
+            readback_buffer,
+            extent,
+            format,
+            frame_index: self.current_frame,
+        });
+    }
+
+    /// Waits for the frame that recorded `capture` to be done with its buffer, and destroys it.
+    fn destroy_capture(&mut self, mut capture: PendingCapture) {
+        let render_fence = self.frames[capture.frame_index].sync_objects.render_fence;
+        unsafe { self.device.wait_for_fences(&[render_fence], true, u64::MAX) }
+            .expect("Failed to wait for render fence");
+        capture
+            .readback_buffer
+            .destroy(&self.device, &mut self.allocator());
+    }
+
+    /// Reads back the image copied by the last frame that ended after [`Renderer::request_capture`]
+    /// was called, converted to RGBA8. This must be called outside of a frame, and waits for that
+    /// frame to be done executing. Each capture can only be read once.
+    pub fn capture_frame(&mut self) -> Result<ImageData, CaptureError> {
+        let mut capture = self
+            .pending_capture
+            .take()
+            .ok_or(CaptureError::NoCaptureRecorded)?;
+
+        let render_fence = self.frames[capture.frame_index].sync_objects.render_fence;
+        let pixels = unsafe { self.device.wait_for_fences(&[render_fence], true, u64::MAX) }
+            .map_err(CaptureError::FenceWaitFailed)
+            .and_then(|()| {
+                capture
+                    .readback_buffer
+                    .allocation
+                    .as_ref()
+                    .and_then(|allocation| allocation.mapped_slice())
+                    .map(|mapped_slice| {
+                        mapped_slice[..capture.readback_buffer.size() as usize].to_vec()
+                    })
+                    .ok_or(CaptureError::MemoryMappingFailed)
+            });
+        capture
+            .readback_buffer
+            .destroy(&self.device, &mut self.allocator());
+
+        ImageData::from_raw(
+            capture.extent.width,
+            capture.extent.height,
+            capture.format,
+            pixels?,
+        )
+    }
+
+    /// Reads back the last capture (see [`Renderer::capture_frame`]) and saves it as a PNG file.
+    pub fn capture_frame_to_png(&mut self, path: impl AsRef<Path>) -> Result<(), CaptureError> {
+        let image_data = self.capture_frame()?;
+        image::save_buffer_with_format(
+            path,
+            &image_data.pixels,
+            image_data.width,
+            image_data.height,
+            image::ExtendedColorType::Rgba8,
+            image::ImageFormat::Png,
+        )?;
+
+        Ok(())
+    }
+
+    /// Records the copy of an image just rendered to by the frame to `readback_buffer`. The image
+    /// is left in its `layout`.
+    fn record_image_readback(
+        &self,
+        cmd_buffer: vk::CommandBuffer,
+        image: vk::Image,
+        extent: vk::Extent2D,
+        layout: vk::ImageLayout,
+        readback_buffer: &AllocatedBuffer,
+    ) {
+        let subresource_range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+
+        unsafe {
+            let to_transfer_barrier = vk::ImageMemoryBarrier::default()
+                .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+                .old_layout(layout)
+                .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(image)
+                .subresource_range(subresource_range);
+            self.device.cmd_pipeline_barrier(
+                cmd_buffer,
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                std::slice::from_ref(&to_transfer_barrier),
+            );
+
+            let copy_region = vk::BufferImageCopy::default()
+                .image_subresource(vk::ImageSubresourceLayers {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    mip_level: 0,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                })
+                .image_extent(vk::Extent3D {
+                    width: extent.width,
+                    height: extent.height,
+                    depth: 1,
+                });
+            self.device.cmd_copy_image_to_buffer(
+                cmd_buffer,
+                image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                readback_buffer.handle,
+                std::slice::from_ref(&copy_region),
+            );
+
+            let to_present_barrier = vk::ImageMemoryBarrier::default()
+                .src_access_mask(vk::AccessFlags::TRANSFER_READ)
+                .dst_access_mask(vk::AccessFlags::empty())
+                .old_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+                .new_layout(layout)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(image)
+                .subresource_range(subresource_range);
+            let readback_barrier = vk::BufferMemoryBarrier::default()
+                .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                .dst_access_mask(vk::AccessFlags::HOST_READ)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .buffer(readback_buffer.handle)
+                .size(vk::WHOLE_SIZE);
+            self.device.cmd_pipeline_barrier(
+                cmd_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE | vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(),
+                &[],
+                std::slice::from_ref(&readback_barrier),
+                std::slice::from_ref(&to_present_barrier),
+            );
+        }
     }
 
     pub fn immediate_command<F>(&self, function: F) -> Result<(), ImmediateCommandError>
@@ -1223,6 +3154,64 @@ impl Renderer {
         self.command_uploader
             .immediate_command(&self.device, self.graphics_queue.handle, function)
     }
+
+    /// Whether [`Renderer::compute_queue`] belongs to a queue family dedicated to compute, whose
+    /// work can overlap with rendering. It is the graphics queue otherwise.
+    #[profiling::skip]
+    pub fn has_dedicated_compute_queue(&self) -> bool {
+        self.compute_queue.family_index != self.graphics_queue.family_index
+    }
+
+    /// Queue families accessing the buffers and images built with the renderer: the graphics one,
+    /// and the compute one if it is dedicated (see [`Renderer::has_dedicated_compute_queue`]).
+    /// Sharing the resources with it lets async dispatches (see `ComputeShader::run_async`) use
+    /// them without queue family ownership transfers.
+    pub(crate) fn resource_queue_families(&self) -> Vec<u32> {
+        if self.has_dedicated_compute_queue() {
+            vec![
+                self.graphics_queue.family_index,
+                self.compute_queue.family_index,
+            ]
+        } else {
+            vec![self.graphics_queue.family_index]
+        }
+    }
+
+    /// Submits the commands recorded by `function` to the compute queue without waiting for them,
+    /// and returns the semaphore they signal once complete. See `ComputeShader::run_async`.
+    pub fn submit_async_compute<F>(
+        &mut self,
+        function: F,
+    ) -> Result<vk::Semaphore, AsyncCommandError>
+    where
+        F: FnOnce(&vk::CommandBuffer),
+    {
+        self.async_compute
+            .submit(&self.device, self.compute_queue.handle, function)
+    }
+
+    /// Makes the next frame's commands wait for `semaphore` (e.g. returned by
+    /// `ComputeShader::run_async`) before executing `dst_stage_mask`. The renderer takes ownership
+    /// of the semaphore, and destroys it once that frame completed.
+    pub fn wait_semaphore(
+        &mut self,
+        semaphore: vk::Semaphore,
+        dst_stage_mask: vk::PipelineStageFlags,
+    ) {
+        self.frame_wait_semaphores.push((semaphore, dst_stage_mask));
+    }
+
+    /// Destroys `image_view` once the frames that may use it are done executing: the next frame
+    /// to be submitted (the current one if called during a frame) and the ones before it.
+    pub(crate) fn destroy_image_view_deferred(&mut self, image_view: vk::ImageView) {
+        self.released_image_views.push(image_view);
+    }
+
+    /// Destroys `buffer` once the frames that may use it are done executing, as
+    /// [`Renderer::destroy_image_view_deferred`] does for image views.
+    pub(crate) fn destroy_buffer_deferred(&mut self, buffer: AllocatedBuffer) {
+        self.released_buffers.push(buffer);
+    }
 }
 
 impl Drop for Renderer {
@@ -1232,26 +3221,83 @@ impl Drop for Renderer {
                 .device_wait_idle()
                 .expect("Failed to wait for device");
 
+            if let Some(headless_target_ref) = self.headless_target.take() {
+                headless_target_ref.lock().destroy(self);
+            }
+            if let Some(mut capture) = self.pending_capture.take() {
+                capture
+                    .readback_buffer
+                    .destroy(&self.device, &mut self.allocator());
+            }
             self.default_texture_ref
                 .lock()
                 .destroy_internal(&self.device, &mut self.allocator());
 
-            self.device
-                .destroy_descriptor_set_layout(self.descriptors[1].layout, None);
-            if let Some(mut time_buffer) = self.descriptors[0].buffer.take() {
-                time_buffer.destroy(&self.device, &mut self.allocator());
+            if let Some(gpu_profiler) = self.gpu_profiler.as_mut() {
+                gpu_profiler.destroy(&self.device);
+            }
+            if let Some(pipeline_statistics) = self.pipeline_statistics.as_mut() {
+                pipeline_statistics.destroy(&self.device);
+            }
+            if let Some(mut entity_ids) = self.entity_ids.take() {
+                entity_ids.destroy(self);
+            }
+            self.descriptor_set_cache.destroy(&self.device);
+            self.sampler_cache.destroy(&self.device);
+            self.command_uploader
+                .staging_pool
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .destroy(&self.device, &mut self.allocator());
+            #[cfg(feature = "bindless")]
+            if let Some(mut bindless_textures) = self.bindless_textures.take() {
+                bindless_textures.destroy(&self.device);
+            }
+            self.dynamic_uniforms
+                .destroy(&self.device, &mut self.allocator.as_ref().unwrap().lock());
+
+            let mut descriptor_set_count = 0;
+            for descriptor_index in 0..self.descriptors.len() {
+                let buffers = mem::take(&mut self.descriptors[descriptor_index].buffers);
+                for mut buffer in buffers {
+                    buffer.destroy(&self.device, &mut self.allocator());
+                }
+                let descriptor = &self.descriptors[descriptor_index];
+                self.device
+                    .destroy_descriptor_set_layout(descriptor.layout, None);
+                descriptor_set_count += descriptor.handles.len();
             }
-            self.device
-                .destroy_descriptor_set_layout(self.descriptors[0].layout, None);
             self.device
                 .destroy_descriptor_pool(self.descriptor_pool, None);
+            resource_registry::unregister(ResourceKind::DescriptorSet, descriptor_set_count);
 
-            self.device
-                .destroy_semaphore(self.sync_objects.render_semaphore, None);
-            self.device
-                .destroy_semaphore(self.sync_objects.present_semaphore, None);
-            self.device
-                .destroy_fence(self.sync_objects.render_fence, None);
+            for (semaphore, _) in self.frame_wait_semaphores.drain(..) {
+                self.device.destroy_semaphore(semaphore, None);
+            }
+            for image_view in self.released_image_views.drain(..) {
+                self.device.destroy_image_view(image_view, None);
+            }
+            let mut released_buffers = mem::take(&mut self.released_buffers);
+            for frame in &mut self.frames {
+                released_buffers.append(&mut frame.released_buffers);
+            }
+            for mut buffer in released_buffers {
+                buffer.destroy(&self.device, &mut self.allocator());
+            }
+            for frame in &self.frames {
+                for semaphore in &frame.waited_semaphores {
+                    self.device.destroy_semaphore(*semaphore, None);
+                }
+                for image_view in &frame.released_image_views {
+                    self.device.destroy_image_view(*image_view, None);
+                }
+                self.device
+                    .destroy_semaphore(frame.sync_objects.render_semaphore, None);
+                self.device
+                    .destroy_semaphore(frame.sync_objects.present_semaphore, None);
+                self.device
+                    .destroy_fence(frame.sync_objects.render_fence, None);
+            }
 
             self.device.destroy_command_pool(self.command_pool, None);
 
@@ -1261,17 +3307,22 @@ impl Drop for Renderer {
 
             self.device
                 .destroy_render_pass(self.primary_render_pass, None);
+            self.device
+                .destroy_render_pass(self.resume_render_pass, None);
+            self.device
+                .destroy_render_pass(self.load_color_render_pass, None);
+            self.device
+                .destroy_render_pass(self.load_depth_render_pass, None);
 
-            let mut swapchain_depth_image = mem::take(&mut self.swapchain.depth_image);
-            swapchain_depth_image.destroy(self);
+            if let Some(mut swapchain) = self.swapchain.take() {
+                swapchain.depth_image.destroy(self);
 
-            for image_view in &self.swapchain.image_views {
-                self.device.destroy_image_view(*image_view, None);
-            }
+                for image_view in &swapchain.image_views {
+                    self.device.destroy_image_view(*image_view, None);
+                }
 
-            self.swapchain
-                .loader
-                .destroy_swapchain(self.swapchain.handle, None);
+                swapchain.loader.destroy_swapchain(swapchain.handle, None);
+            }
 
             if let Some(allocator) = self.allocator.take() {
                 drop(allocator);
@@ -1279,12 +3330,14 @@ impl Drop for Renderer {
 
             let command_uploader = mem::take(&mut self.command_uploader);
             command_uploader.destroy(&self.device);
+            let async_compute = mem::take(&mut self.async_compute);
+            async_compute.destroy(&self.device);
 
             self.device.destroy_device(None);
 
-            self.surface
-                .loader
-                .destroy_surface(self.surface.handle, None);
+            if let Some(surface) = &self.surface {
+                surface.loader.destroy_surface(surface.handle, None);
+            }
 
             if let Some(debug_messenger) = &self.debug_messenger {
                 debug_messenger
@@ -1296,3 +3349,45 @@ impl Drop for Renderer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_uniform_layout() {
+        assert_eq!(
+            time_uniform_data(Duration::from_secs(10)),
+            Vec4::new(0.5, 10.0, 20.0, 30.0)
+        );
+        assert_eq!(time_uniform_data(Duration::ZERO), Vec4::ZERO);
+    }
+
+    #[test]
+    #[ignore = "requires a Vulkan device"]
+    fn time_buffer_read_back_after_frame() {
+        let total_elapsed = Duration::from_secs(10);
+        let renderer_ref = RendererBuilder::headless().with_dimensions(16, 16).build();
+        let mut renderer = renderer_ref.lock();
+
+        assert!(renderer.begin_frame(total_elapsed));
+        renderer
+            .request_capture()
+            .expect("Failed to request the capture");
+        renderer.end_frame();
+        // Waits for the frame to be done executing
+        renderer
+            .capture_frame()
+            .expect("Failed to read back the frame");
+
+        // Headless renderers only have one frame in flight
+        let time_buffer = &renderer.descriptors[0].buffers[0];
+        let mapped_slice = time_buffer
+            .allocation
+            .as_ref()
+            .and_then(|allocation| allocation.mapped_slice())
+            .expect("The time buffer isn't host visible");
+        let time_data: Vec4 = bytemuck::pod_read_unaligned(&mapped_slice[..mem::size_of::<Vec4>()]);
+        assert_eq!(time_data, time_uniform_data(total_elapsed));
+    }
+}
@@ -0,0 +1,69 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Number of live GPU resources of each category, see [`crate::renderer::Renderer::resource_counts`].
+///
+/// Comparing the counts before and after a state is dropped (together with the allocator's report,
+/// see [`crate::renderer::Renderer::allocator`]) shows which kind of resource its `on_drop` forgot
+/// to destroy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceCounts {
+    /// [`crate::allocated_types::AllocatedBuffer`]s, counted when built and when destroyed.
+    pub buffers: usize,
+    /// [`crate::allocated_types::AllocatedImage`]s (including the ones owned by textures, cubemaps
+    /// and render targets, and the swapchain's depth image), counted when built and when destroyed.
+    pub images: usize,
+    /// Graphics and compute pipelines, counted when built and when their material or compute
+    /// shader is destroyed.
+    pub pipelines: usize,
+    /// Descriptor sets of materials, compute shaders, mesh renderings (shared ones only count once)
+    /// and the renderer's own sets. They are counted when allocated, and when their pool is
+    /// destroyed.
+    pub descriptor_sets: usize,
+    /// Samplers of textures, cubemaps and font atlases, counted when created and destroyed.
+    pub samplers: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum ResourceKind {
+    Buffer,
+    Image,
+    Pipeline,
+    DescriptorSet,
+    Sampler,
+}
+
+// The counters are process wide, as most resources are destroyed with only the device at hand
+static COUNTERS: [AtomicUsize; 5] = [const { AtomicUsize::new(0) }; 5];
+
+impl ResourceKind {
+    fn counter(self) -> &'static AtomicUsize {
+        &COUNTERS[self as usize]
+    }
+}
+
+/// Records the creation of `count` resources of the given kind.
+pub(crate) fn register(kind: ResourceKind, count: usize) {
+    kind.counter().fetch_add(count, Ordering::Relaxed);
+}
+
+/// Records the destruction of `count` resources of the given kind.
+pub(crate) fn unregister(kind: ResourceKind, count: usize) {
+    // Saturating, so that a double destroy doesn't wrap the counter around
+    let _ = kind
+        .counter()
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |value| {
+            Some(value.saturating_sub(count))
+        });
+}
+
+pub(crate) fn counts() -> ResourceCounts {
+    let load = |kind: ResourceKind| kind.counter().load(Ordering::Relaxed);
+
+    ResourceCounts {
+        buffers: load(ResourceKind::Buffer),
+        images: load(ResourceKind::Image),
+        pipelines: load(ResourceKind::Pipeline),
+        descriptor_sets: load(ResourceKind::DescriptorSet),
+        samplers: load(ResourceKind::Sampler),
+    }
+}
@@ -0,0 +1,80 @@
+use ash::{prelude::VkResult, vk};
+
+use crate::{
+    resource_registry::{self, ResourceKind},
+    texture::SamplerSettings,
+};
+
+use std::collections::HashMap;
+
+/// Parameters a sampler is created from, [`SamplerSettings`] being made hashable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct SamplerKey {
+    filter: vk::Filter,
+    mipmap_mode: vk::SamplerMipmapMode,
+    address_mode: vk::SamplerAddressMode,
+    /// Bits of the maximum anisotropy, if enabled.
+    anisotropy: Option<u32>,
+    /// Number of levels sampled, from LOD 0.
+    lod_count: u32,
+}
+
+/// Samplers shared by all the textures with the same settings, so that their count doesn't grow
+/// with the number of textures (implementations may only allow a few thousands, see
+/// `max_sampler_allocation_count`).
+///
+/// Samplers are only destroyed along with the renderer, the handles it hands out must not be
+/// destroyed by their users.
+#[derive(Debug, Default)]
+pub(crate) struct SamplerCache {
+    samplers: HashMap<SamplerKey, vk::Sampler>,
+}
+
+impl SamplerCache {
+    /// Returns the sampler for `settings` covering `lod_count` levels, creating it if needed.
+    pub(crate) fn get(
+        &mut self,
+        settings: &SamplerSettings,
+        lod_count: u32,
+        device: &ash::Device,
+    ) -> VkResult<vk::Sampler> {
+        let key = SamplerKey {
+            filter: settings.filter,
+            mipmap_mode: settings.mipmap_mode,
+            address_mode: settings.address_mode,
+            anisotropy: settings.anisotropy.map(f32::to_bits),
+            lod_count,
+        };
+        if let Some(sampler) = self.samplers.get(&key) {
+            return Ok(*sampler);
+        }
+
+        let sampler_info = vk::SamplerCreateInfo::default()
+            .mag_filter(settings.filter)
+            .min_filter(settings.filter)
+            .mipmap_mode(settings.mipmap_mode)
+            .address_mode_u(settings.address_mode)
+            .address_mode_v(settings.address_mode)
+            .address_mode_w(settings.address_mode)
+            .min_lod(0.0)
+            .max_lod((lod_count - 1) as f32)
+            .anisotropy_enable(settings.anisotropy.is_some())
+            .max_anisotropy(settings.anisotropy.unwrap_or(1.0));
+        let sampler = unsafe { device.create_sampler(&sampler_info, None) }?;
+        resource_registry::register(ResourceKind::Sampler, 1);
+        self.samplers.insert(key, sampler);
+
+        Ok(sampler)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.samplers.len()
+    }
+
+    pub(crate) fn destroy(&mut self, device: &ash::Device) {
+        for (_, sampler) in self.samplers.drain() {
+            unsafe { device.destroy_sampler(sampler, None) };
+            resource_registry::unregister(ResourceKind::Sampler, 1);
+        }
+    }
+}
@@ -0,0 +1,578 @@
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use thiserror::Error;
+
+use std::{collections::HashMap, path::Path};
+
+use crate::{
+    allocated_types::{AllocatedBuffer, BufferBuildWithDataError},
+    descriptor_resources::DescriptorResources,
+    material::{Material, MaterialBuildError, MaterialBuilder},
+    math_types::{Vec2, Vec3, Vec4},
+    mesh::{upload_mesh_data, Aabb, Mesh},
+    renderer::Renderer,
+    shader::{Shader, ShaderBuildError},
+    text::TextMeshBuildError,
+    texture::{Texture, TextureBuildError, TextureColorSpace, TextureFormat},
+    utils::ThreadSafeRef,
+};
+
+pub use crate::vertices::glyph::GlyphVertex;
+
+/// Binding of the atlas texture in the set 2 of [`SdfFontAtlas::build_material`]'s material.
+pub const SDF_TEXT_ATLAS_BINDING: u32 = 0;
+/// Binding of the [`SdfTextStyleData`] uniform in the set 2 of [`SdfFontAtlas::build_material`]'s
+/// material.
+pub const SDF_TEXT_STYLE_BINDING: u32 = 1;
+
+/// Gap left between glyphs in the atlas, so that filtering never reads a neighbouring glyph.
+const GLYPH_GAP: usize = 1;
+/// Initial distance of the texels a distance transform has to find the closest feature of.
+const UNREACHED_DISTANCE: f64 = 1e20;
+
+#[derive(Debug, Clone)]
+pub struct SdfAtlasSettings {
+    /// Size (in pixels per em) glyphs are rasterized at before computing their distance field.
+    /// Text stays sharp at any scale, but very thin details may be lost at small baking sizes.
+    pub font_size: f32,
+    /// Distance (in pixels of the baked glyphs) covered by the distance field on each side of the
+    /// glyphs' edges. This bounds the widest outline and the farthest shadow that can be rendered.
+    pub spread: u32,
+    pub characters: Vec<char>,
+    pub atlas_width: u32,
+}
+
+impl Default for SdfAtlasSettings {
+    fn default() -> Self {
+        Self {
+            font_size: 48.0,
+            spread: 6,
+            characters: (' '..='~').collect(),
+            atlas_width: 1024,
+        }
+    }
+}
+
+/// Placement of a glyph in the atlas, in pixels of the baked font unless stated otherwise.
+#[derive(Debug, Clone, Copy)]
+pub struct SdfGlyph {
+    /// Top left and bottom right texture coordinates of the glyph's quad in the atlas.
+    pub uv_min: Vec2,
+    pub uv_max: Vec2,
+    /// Offset of the quad's bottom left corner from the pen position on the baseline.
+    pub bearing: Vec2,
+    /// Size of the quad, which includes the distance field's spread on each side.
+    pub size: Vec2,
+    pub advance: f32,
+}
+
+/// Appearance of the text drawn by a [`SdfFontAtlas::build_material`] material, in pixels of the
+/// baked font. The outline width plus the shadow's softness and offset should stay below the
+/// atlas' spread, as distances are clamped beyond it.
+#[derive(Debug, Clone, Copy)]
+pub struct SdfTextStyle {
+    pub outline_color: Vec4,
+    pub outline_width: f32,
+    pub shadow_color: Vec4,
+    /// Offset of the shadow, x pointing right and y pointing up.
+    pub shadow_offset: Vec2,
+    pub shadow_softness: f32,
+}
+
+impl Default for SdfTextStyle {
+    fn default() -> Self {
+        Self {
+            outline_color: Vec4::new(0.0, 0.0, 0.0, 1.0),
+            outline_width: 0.0,
+            shadow_color: Vec4::ZERO,
+            shadow_offset: Vec2::new(1.5, -1.5),
+            shadow_softness: 1.5,
+        }
+    }
+}
+
+/// [`SdfTextStyle`] converted to the atlas' distance and texture coordinate units, laid out to be
+/// used directly as the following std140 uniform block:
+/// ```glsl
+/// layout(set = 2, binding = 1) uniform TextStyle {
+///     vec4 outlineColor;
+///     vec4 shadowColor;
+///     vec2 shadowOffset;
+///     float outlineWidth;
+///     float shadowSoftness;
+/// } u_TextStyle;
+/// ```
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SdfTextStyleData {
+    pub outline_color: Vec4,
+    pub shadow_color: Vec4,
+    pub shadow_offset: Vec2,
+    pub outline_width: f32,
+    pub shadow_softness: f32,
+}
+unsafe impl Zeroable for SdfTextStyleData {}
+unsafe impl Pod for SdfTextStyleData {}
+
+#[derive(Error, Debug)]
+pub enum SdfFontAtlasBuildError {
+    #[error("Reading of font file \"{path}\" failed with error: {error}.")]
+    FileReadingFailed { path: String, error: std::io::Error },
+
+    #[error("Parsing of the font failed with error: {0}.")]
+    FontParsingFailed(&'static str),
+
+    #[error("The font does not provide horizontal line metrics.")]
+    MissingLineMetrics,
+
+    #[error("Glyph '{character}' does not fit in an atlas {atlas_width} pixels wide.")]
+    GlyphTooLarge { character: char, atlas_width: u32 },
+
+    #[error("Creation of the atlas texture failed with error: {0}.")]
+    TextureCreationFailed(#[from] TextureBuildError),
+}
+
+#[derive(Error, Debug)]
+pub enum SdfTextMaterialBuildError {
+    #[error("Creation of SDF text shader failed with error: {0}.")]
+    ShaderCreationFailed(#[from] ShaderBuildError),
+
+    #[error("Creation of the text style uniform buffer failed with error: {0}.")]
+    StyleBufferCreationFailed(#[from] BufferBuildWithDataError),
+
+    #[error("Creation of SDF text material failed with error: {0}.")]
+    MaterialCreationFailed(#[from] MaterialBuildError),
+}
+
+/// Signed distance field atlas of a font's glyphs.
+///
+/// Each texel's alpha holds the distance to the closest glyph edge, 0.5 being on the edge and
+/// higher values inside the glyph. As the edge is reconstructed from filtered distances rather than
+/// coverage, text drawn with [`SdfFontAtlas::build_material`] stays sharp at any scale, and
+/// outlines and shadows come for free.
+pub struct SdfFontAtlas {
+    pub texture_ref: ThreadSafeRef<Texture>,
+
+    font: fontdue::Font,
+    glyphs: HashMap<char, SdfGlyph>,
+    font_size: f32,
+    spread: u32,
+    line_height: f32,
+}
+
+#[profiling::all_functions]
+impl SdfFontAtlas {
+    pub fn from_path(
+        path: &Path,
+        settings: &SdfAtlasSettings,
+        renderer: &mut Renderer,
+    ) -> Result<Self, SdfFontAtlasBuildError> {
+        let font_data =
+            std::fs::read(path).map_err(|error| SdfFontAtlasBuildError::FileReadingFailed {
+                path: path.display().to_string(),
+                error,
+            })?;
+
+        Self::from_bytes(&font_data, settings, renderer)
+    }
+
+    /// Bakes the atlas from the content of a TrueType or OpenType font file.
+    pub fn from_bytes(
+        font_data: &[u8],
+        settings: &SdfAtlasSettings,
+        renderer: &mut Renderer,
+    ) -> Result<Self, SdfFontAtlasBuildError> {
+        let font = fontdue::Font::from_bytes(font_data, fontdue::FontSettings::default())
+            .map_err(SdfFontAtlasBuildError::FontParsingFailed)?;
+        let line_height = font
+            .horizontal_line_metrics(settings.font_size)
+            .ok_or(SdfFontAtlasBuildError::MissingLineMetrics)?
+            .new_line_size;
+
+        let spread = usize::try_from(settings.spread).unwrap();
+        let atlas_width = usize::try_from(settings.atlas_width).unwrap();
+
+        let mut baked_glyphs = settings
+            .characters
+            .iter()
+            .map(|character| {
+                let (metrics, coverage) = font.rasterize(*character, settings.font_size);
+                let (distance_field, width, height) =
+                    distance_field(&coverage, metrics.width, metrics.height, spread);
+
+                (*character, metrics, distance_field, width, height)
+            })
+            .collect::<Vec<_>>();
+        // Shelf packing works best with glyphs sorted by height
+        baked_glyphs.sort_by_key(|glyph| std::cmp::Reverse(glyph.4));
+
+        let mut placements = Vec::with_capacity(baked_glyphs.len());
+        let (mut cursor_x, mut cursor_y, mut shelf_height) = (GLYPH_GAP, GLYPH_GAP, 0);
+        for (character, _, _, width, height) in &baked_glyphs {
+            if *width + 2 * GLYPH_GAP > atlas_width {
+                return Err(SdfFontAtlasBuildError::GlyphTooLarge {
+                    character: *character,
+                    atlas_width: settings.atlas_width,
+                });
+            }
+            if cursor_x + width + GLYPH_GAP > atlas_width {
+                cursor_x = GLYPH_GAP;
+                cursor_y += shelf_height + GLYPH_GAP;
+                shelf_height = 0;
+            }
+
+            placements.push((cursor_x, cursor_y));
+            cursor_x += width + GLYPH_GAP;
+            shelf_height = shelf_height.max(*height);
+        }
+        let atlas_height = cursor_y + shelf_height + GLYPH_GAP;
+
+        let mut atlas_data = [255, 255, 255, 0].repeat(atlas_width * atlas_height);
+        let mut glyphs = HashMap::with_capacity(baked_glyphs.len());
+        for ((character, metrics, distance_field, width, height), (x, y)) in
+            baked_glyphs.iter().zip(placements)
+        {
+            for row in 0..*height {
+                for column in 0..*width {
+                    atlas_data[4 * ((y + row) * atlas_width + x + column) + 3] =
+                        distance_field[row * width + column];
+                }
+            }
+
+            glyphs.insert(
+                *character,
+                SdfGlyph {
+                    uv_min: Vec2::new(
+                        x as f32 / atlas_width as f32,
+                        y as f32 / atlas_height as f32,
+                    ),
+                    uv_max: Vec2::new(
+                        (x + width) as f32 / atlas_width as f32,
+                        (y + height) as f32 / atlas_height as f32,
+                    ),
+                    bearing: Vec2::new(
+                        (metrics.xmin - i32::try_from(spread).unwrap()) as f32,
+                        (metrics.ymin - i32::try_from(spread).unwrap()) as f32,
+                    ),
+                    size: Vec2::new(*width as f32, *height as f32),
+                    advance: metrics.advance_width,
+                },
+            );
+        }
+
+        // Distances must be interpolated between texels for the edges to be reconstructed
+        let texture_ref = Texture::builder()
+            .with_format(TextureFormat::RGBA8_UNORM)
+            .with_color_space(TextureColorSpace::Linear)
+            .with_filter(vk::Filter::LINEAR)
+            .with_address_mode(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .build_from_data(
+                &atlas_data,
+                settings.atlas_width,
+                atlas_height.try_into().unwrap(),
+                renderer,
+            )?;
+
+        Ok(Self {
+            texture_ref,
+            font,
+            glyphs,
+            font_size: settings.font_size,
+            spread: settings.spread,
+            line_height,
+        })
+    }
+
+    #[profiling::skip]
+    pub fn font_size(&self) -> f32 {
+        self.font_size
+    }
+
+    #[profiling::skip]
+    pub fn spread(&self) -> u32 {
+        self.spread
+    }
+
+    #[profiling::skip]
+    pub fn glyph(&self, character: char) -> Option<&SdfGlyph> {
+        self.glyphs.get(&character)
+    }
+
+    /// Converts `style` to the units of this atlas, to be uploaded with
+    /// [`Material::update_uniform`] at [`SDF_TEXT_STYLE_BINDING`].
+    pub fn style_data(&self, style: &SdfTextStyle) -> SdfTextStyleData {
+        let [atlas_width, atlas_height] = self.texture_ref.lock().dimensions;
+        // The spread on each side of the edge maps to half of the distance range
+        let distance_scale = 1.0 / (2.0 * self.spread as f32);
+
+        SdfTextStyleData {
+            outline_color: style.outline_color,
+            shadow_color: style.shadow_color,
+            shadow_offset: Vec2::new(
+                style.shadow_offset.x / atlas_width as f32,
+                -style.shadow_offset.y / atlas_height as f32,
+            ),
+            outline_width: style.outline_width * distance_scale,
+            shadow_softness: style.shadow_softness * distance_scale,
+        }
+    }
+
+    /// Builds a material drawing [`GlyphVertex`] quads with this atlas and `style`.
+    ///
+    /// The material is alpha blended and doesn't write depth, so it should be drawn after the
+    /// opaque geometry. The caller owns the returned material, its shader and its style uniform
+    /// buffer (found at [`SDF_TEXT_STYLE_BINDING`] in its descriptor resources), the atlas texture
+    /// is still owned by the atlas.
+    pub fn build_material(
+        &self,
+        style: &SdfTextStyle,
+        renderer: &mut Renderer,
+    ) -> Result<ThreadSafeRef<Material<GlyphVertex>>, SdfTextMaterialBuildError> {
+        let shader = Shader::from_spirv_u8(
+            include_bytes!("shaders/gen/sdf_text.vert"),
+            include_bytes!("shaders/gen/sdf_text.frag"),
+            &renderer.device,
+        )?;
+
+        let style_buffer =
+            AllocatedBuffer::builder(std::mem::size_of::<SdfTextStyleData>().try_into().unwrap())
+                .with_name("SDF text style")
+                .build_with_pod(self.style_data(style), renderer)?;
+
+        Ok(MaterialBuilder::new()
+            .cull_mode(vk::CullModeFlags::NONE)
+            .z_write(false)
+            .build(
+                &shader,
+                DescriptorResources {
+                    uniform_buffers: HashMap::from([(
+                        SDF_TEXT_STYLE_BINDING,
+                        ThreadSafeRef::new(style_buffer),
+                    )]),
+                    sampled_images: HashMap::from([(
+                        SDF_TEXT_ATLAS_BINDING,
+                        self.texture_ref.clone(),
+                    )]),
+                    ..Default::default()
+                },
+                renderer,
+            )?)
+    }
+
+    /// Lays out `text` in the XY plane, starting at the origin on the first line's baseline and
+    /// going right then down. `size` is the height of an em in world units. Characters missing from
+    /// the atlas are skipped, but still advance the pen.
+    pub fn layout_text(&self, text: &str, size: f32, color: Vec4) -> (Vec<GlyphVertex>, Vec<u32>) {
+        let scale = size / self.font_size;
+
+        let mut vertices = vec![];
+        let mut indices = vec![];
+        let mut pen = Vec2::ZERO;
+        let mut previous_character = None;
+        for character in text.chars() {
+            if character == '\n' {
+                pen = Vec2::new(0.0, pen.y - self.line_height * scale);
+                previous_character = None;
+                continue;
+            }
+
+            if let Some(kerning) = previous_character.and_then(|previous| {
+                self.font
+                    .horizontal_kern(previous, character, self.font_size)
+            }) {
+                pen.x += kerning * scale;
+            }
+            previous_character = Some(character);
+
+            let Some(glyph) = self.glyphs.get(&character) else {
+                pen.x += self.font.metrics(character, self.font_size).advance_width * scale;
+                continue;
+            };
+
+            let min = pen + glyph.bearing * scale;
+            let max = min + glyph.size * scale;
+            pen.x += glyph.advance * scale;
+            if glyph
+                .size
+                .cmple(Vec2::splat(2.0 * self.spread as f32))
+                .any()
+            {
+                // Nothing but the spread, e.g. a space
+                continue;
+            }
+
+            let first_index: u32 = vertices.len().try_into().unwrap();
+            vertices.extend(
+                [
+                    (Vec2::new(min.x, max.y), glyph.uv_min),
+                    (max, Vec2::new(glyph.uv_max.x, glyph.uv_min.y)),
+                    (min, Vec2::new(glyph.uv_min.x, glyph.uv_max.y)),
+                    (Vec2::new(max.x, min.y), glyph.uv_max),
+                ]
+                .map(|(position, texture_coords)| GlyphVertex {
+                    position: Vec3::new(position.x, position.y, 0.0),
+                    texture_coords,
+                    color,
+                }),
+            );
+            indices.extend([0, 2, 1, 1, 2, 3].map(|offset| first_index + offset));
+        }
+
+        (vertices, indices)
+    }
+
+    /// Lays out `text` (see [`SdfFontAtlas::layout_text`]) and uploads it as a mesh. Text without
+    /// any visible glyph returns [`TextMeshBuildError::NoVisibleGlyph`].
+    pub fn build_text_mesh(
+        &self,
+        text: &str,
+        size: f32,
+        color: Vec4,
+        renderer: &mut Renderer,
+    ) -> Result<ThreadSafeRef<Mesh<GlyphVertex>>, TextMeshBuildError> {
+        let (vertices, indices) = self.layout_text(text, size, color);
+        if vertices.is_empty() {
+            return Err(TextMeshBuildError::NoVisibleGlyph);
+        }
+        let upload_result = upload_mesh_data(&vertices, &indices, renderer)?;
+
+        Ok(ThreadSafeRef::new(Mesh {
+            aabb: Aabb::from_vertices(&vertices),
+            vertices,
+            indices: Some(indices),
+            vertex_buffer: upload_result.vertex_buffer,
+            index_buffer: Some(upload_result.index_buffer),
+        }))
+    }
+
+    pub fn destroy(&mut self, renderer: &mut Renderer) {
+        self.texture_ref.lock().destroy(renderer);
+    }
+}
+
+/// Computes the distance field of a glyph's coverage bitmap, padded by `spread` on each side.
+/// Returns the distances (encoded as described in [`SdfFontAtlas`]) and their dimensions.
+fn distance_field(
+    coverage: &[u8],
+    width: usize,
+    height: usize,
+    spread: usize,
+) -> (Vec<u8>, usize, usize) {
+    let padded_width = width + 2 * spread;
+    let padded_height = height + 2 * spread;
+
+    let is_inside = |x: usize, y: usize| {
+        (spread..spread + width).contains(&x)
+            && (spread..spread + height).contains(&y)
+            && coverage[(y - spread) * width + x - spread] >= 128
+    };
+    let mut to_inside = vec![0.0; padded_width * padded_height];
+    let mut to_outside = vec![0.0; padded_width * padded_height];
+    for y in 0..padded_height {
+        for x in 0..padded_width {
+            let index = y * padded_width + x;
+            if is_inside(x, y) {
+                to_outside[index] = UNREACHED_DISTANCE;
+            } else {
+                to_inside[index] = UNREACHED_DISTANCE;
+            }
+        }
+    }
+    squared_distance_transform_2d(&mut to_inside, padded_width, padded_height);
+    squared_distance_transform_2d(&mut to_outside, padded_width, padded_height);
+
+    let distances = to_inside
+        .iter()
+        .zip(&to_outside)
+        .map(|(to_inside, to_outside)| {
+            // Distances are measured between texel centers, while the edge lies halfway between them
+            let signed_distance = if *to_inside == 0.0 {
+                to_outside.sqrt() - 0.5
+            } else {
+                0.5 - to_inside.sqrt()
+            };
+            let encoded = 0.5 + signed_distance / (2.0 * spread.max(1) as f64);
+
+            (encoded.clamp(0.0, 1.0) * 255.0).round() as u8
+        })
+        .collect();
+
+    (distances, padded_width, padded_height)
+}
+
+/// Exact squared euclidean distance transform (Felzenszwalb & Huttenlocher), applied in place on
+/// the columns then the rows of `grid`.
+fn squared_distance_transform_2d(grid: &mut [f64], width: usize, height: usize) {
+    let max_dimension = width.max(height);
+    let mut line = vec![0.0; max_dimension];
+    let mut transformed = vec![0.0; max_dimension];
+    let mut parabolas = vec![0; max_dimension];
+    let mut boundaries = vec![0.0; max_dimension + 1];
+
+    for x in 0..width {
+        for y in 0..height {
+            line[y] = grid[y * width + x];
+        }
+        squared_distance_transform_1d(
+            &line[..height],
+            &mut transformed[..height],
+            &mut parabolas,
+            &mut boundaries,
+        );
+        for y in 0..height {
+            grid[y * width + x] = transformed[y];
+        }
+    }
+
+    for y in 0..height {
+        line[..width].copy_from_slice(&grid[y * width..(y + 1) * width]);
+        squared_distance_transform_1d(
+            &line[..width],
+            &mut transformed[..width],
+            &mut parabolas,
+            &mut boundaries,
+        );
+        grid[y * width..(y + 1) * width].copy_from_slice(&transformed[..width]);
+    }
+}
+
+fn squared_distance_transform_1d(
+    input: &[f64],
+    output: &mut [f64],
+    parabolas: &mut [usize],
+    boundaries: &mut [f64],
+) {
+    if input.is_empty() {
+        return;
+    }
+
+    let intersection = |q: usize, p: usize| {
+        let (q_f, p_f) = (q as f64, p as f64);
+        ((input[q] + q_f * q_f) - (input[p] + p_f * p_f)) / (2.0 * q_f - 2.0 * p_f)
+    };
+
+    let mut k = 0;
+    parabolas[0] = 0;
+    boundaries[0] = f64::NEG_INFINITY;
+    boundaries[1] = f64::INFINITY;
+    for q in 1..input.len() {
+        let mut s = intersection(q, parabolas[k]);
+        while s <= boundaries[k] {
+            k -= 1;
+            s = intersection(q, parabolas[k]);
+        }
+        k += 1;
+        parabolas[k] = q;
+        boundaries[k] = s;
+        boundaries[k + 1] = f64::INFINITY;
+    }
+
+    k = 0;
+    for (q, output) in output.iter_mut().enumerate() {
+        while boundaries[k + 1] < q as f64 {
+            k += 1;
+        }
+        let offset = q as f64 - parabolas[k] as f64;
+        *output = offset * offset + input[parabolas[k]];
+    }
+}
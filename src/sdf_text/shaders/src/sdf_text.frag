@@ -0,0 +1,43 @@
+#version 450
+
+layout(location = 0) in vec2 vs_TextureCoords;
+layout(location = 1) in vec4 vs_Color;
+
+// Distances are stored in the alpha channel, 0.5 being the glyph's edge
+layout(set = 2, binding = 0) uniform sampler2D u_Atlas;
+layout(set = 2, binding = 1) uniform TextStyle {
+    vec4 outlineColor;
+    vec4 shadowColor;
+    vec2 shadowOffset;
+    float outlineWidth;
+    float shadowSoftness;
+}
+u_TextStyle;
+
+layout(location = 0) out vec4 f_Color;
+
+void main() {
+    float distance = texture(u_Atlas, vs_TextureCoords).a;
+    // Half a screen pixel, so that edges stay sharp at any scale
+    float smoothing = max(fwidth(distance) * 0.5, 1e-4);
+
+    float fillAlpha = smoothstep(0.5 - smoothing, 0.5 + smoothing, distance);
+    float outlineEdge = 0.5 - u_TextStyle.outlineWidth;
+    float outlineAlpha = smoothstep(outlineEdge - smoothing, outlineEdge + smoothing, distance);
+
+    vec4 glyph = mix(vec4(u_TextStyle.outlineColor.rgb, u_TextStyle.outlineColor.a * outlineAlpha),
+                     vs_Color,
+                     fillAlpha);
+
+    float shadowDistance = texture(u_Atlas, vs_TextureCoords - u_TextStyle.shadowOffset).a;
+    float shadowAlpha = u_TextStyle.shadowColor.a *
+                        smoothstep(outlineEdge - u_TextStyle.shadowSoftness - smoothing,
+                                   outlineEdge + smoothing,
+                                   shadowDistance);
+
+    // Glyph over its shadow
+    float alpha = glyph.a + shadowAlpha * (1 - glyph.a);
+    vec3 color = glyph.rgb * glyph.a + u_TextStyle.shadowColor.rgb * shadowAlpha * (1 - glyph.a);
+
+    f_Color = vec4(color / max(alpha, 1e-4), alpha);
+}
@@ -0,0 +1,24 @@
+#version 450
+
+layout(location = 0) in vec3 v_Position;
+layout(location = 1) in vec2 v_TextureCoords;
+layout(location = 2) in vec4 v_Color;
+
+layout(push_constant) uniform CameraData {
+    mat4 viewProjection;
+    vec4 worldPos;
+}
+pc_CameraData;
+
+layout(set = 3, binding = 0) uniform ModelData { mat4 modelMatrix; }
+u_ModelData;
+
+layout(location = 0) out vec2 fs_TextureCoords;
+layout(location = 1) out vec4 fs_Color;
+
+void main() {
+    fs_TextureCoords = v_TextureCoords;
+    fs_Color = v_Color;
+
+    gl_Position = pc_CameraData.viewProjection * u_ModelData.modelMatrix * vec4(v_Position, 1);
+}
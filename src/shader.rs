@@ -1,12 +1,19 @@
 use crate::{
-    descriptor_resources::{create_dsl, DSLCreationError},
+    descriptor_resources::{
+        create_dsl, DSLCreationError, MATERIAL_SET, MODEL_MATRIX_BINDING, OBJECT_SET,
+    },
     utils::ThreadSafeRef,
 };
 
 use ash::{vk, Device};
-use spirv_reflect::types::{ReflectBlockVariable, ReflectDescriptorType, ReflectDimension};
+use spirv_reflect::types::{
+    ReflectBlockVariable, ReflectDecorationFlags, ReflectDescriptorBinding, ReflectDescriptorType,
+    ReflectDimension, ReflectFormat, ReflectInterfaceVariable,
+};
 use thiserror::Error;
 
+#[cfg(feature = "hot_reload")]
+use std::path::PathBuf;
 use std::{fs, path::Path};
 
 #[derive(Debug, Clone, Copy)]
@@ -16,10 +23,457 @@ pub struct BindingData {
     pub descriptor_type: ReflectDescriptorType,
     pub size: u32,
     pub dim: ReflectDimension,
+    pub count: u32,
+    /// Whether this uniform is read at a dynamic offset into the renderer's dynamic uniform buffer
+    /// (see [`Shader::set_dynamic_uniforms`]). Always the case of the model matrix.
+    pub dynamic: bool,
+}
+
+impl From<&ReflectDescriptorBinding> for BindingData {
+    fn from(binding: &ReflectDescriptorBinding) -> Self {
+        Self {
+            set: binding.set,
+            slot: binding.binding,
+            descriptor_type: binding.descriptor_type,
+            size: binding.block.size,
+            dim: binding.image.dim,
+            count: binding.count,
+            dynamic: is_model_matrix(binding.set, binding.binding, binding.descriptor_type),
+        }
+    }
+}
+
+/// The model matrix is written by the render systems for every object drawn, each frame in flight
+/// reading its own copy from the renderer's dynamic uniform buffer.
+fn is_model_matrix(set: u32, slot: u32, descriptor_type: ReflectDescriptorType) -> bool {
+    set == OBJECT_SET
+        && slot == MODEL_MATRIX_BINDING
+        && descriptor_type == ReflectDescriptorType::UniformBuffer
+}
+
+/// Shader stages an item of a [`ShaderReflection`] is used in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ShaderStages {
+    pub vertex: bool,
+    pub fragment: bool,
+}
+
+impl ShaderStages {
+    pub fn to_vk(self) -> vk::ShaderStageFlags {
+        let mut flags = vk::ShaderStageFlags::empty();
+        if self.vertex {
+            flags |= vk::ShaderStageFlags::VERTEX;
+        }
+        if self.fragment {
+            flags |= vk::ShaderStageFlags::FRAGMENT;
+        }
+
+        flags
+    }
+}
+
+/// Kind of resource a binding expects, matching the [`crate::descriptor_resources::DescriptorResources`]
+/// field it must be provided in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ShaderResourceKind {
+    UniformBuffer,
+    StorageBuffer,
+    StorageImage,
+    SampledImage,
+    SampledImageArray,
+    Cubemap,
+    AccelerationStructure,
+    /// Descriptor types the renderer can't bind.
+    Unsupported,
+}
+
+/// Member of a uniform, storage or push constant block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ShaderBlockMember {
+    pub name: String,
+    /// Offset in bytes from the start of the block.
+    pub offset: u32,
+    pub size: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ShaderBinding {
+    /// Instance name of blocks, or their type name for anonymous instances.
+    pub name: String,
+    pub set: u32,
+    pub slot: u32,
+    pub kind: ShaderResourceKind,
+    /// Size in bytes of the block, 0 for images.
+    pub size: u32,
+    /// Number of array elements, 1 for non array bindings.
+    pub count: u32,
+    pub stages: ShaderStages,
+    /// Members of the block, empty for images.
+    pub members: Vec<ShaderBlockMember>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ShaderPushConstantRange {
+    pub name: String,
+    /// Offset in bytes of the first member of the block.
+    pub offset: u32,
+    /// Size in bytes of the range starting at `offset`.
+    pub size: u32,
+    pub stages: ShaderStages,
+    pub members: Vec<ShaderBlockMember>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ShaderScalarType {
+    Float,
+    Int,
+    Uint,
+    /// Types the reflection can't describe (e.g. matrices or 64 bit types).
+    Unknown,
+}
+
+/// Input of the vertex stage, that a vertex attribute must provide.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ShaderVertexInput {
+    pub name: String,
+    pub location: u32,
+    pub scalar_type: ShaderScalarType,
+    /// Number of components (1 for scalars, 2 to 4 for vectors).
+    pub component_count: u32,
+}
+
+impl ShaderScalarType {
+    /// Type of the components a vertex attribute of `format` provides to the shader: normalized
+    /// and scaled formats are read as floats. Formats that can't be used by vertex attributes are
+    /// `Unknown`.
+    pub fn from_vertex_format(format: vk::Format) -> Self {
+        match format {
+            vk::Format::R8_UNORM
+            | vk::Format::R8_SNORM
+            | vk::Format::R8_USCALED
+            | vk::Format::R8_SSCALED
+            | vk::Format::R8_SRGB
+            | vk::Format::R8G8_UNORM
+            | vk::Format::R8G8_SNORM
+            | vk::Format::R8G8_USCALED
+            | vk::Format::R8G8_SSCALED
+            | vk::Format::R8G8_SRGB
+            | vk::Format::R8G8B8_UNORM
+            | vk::Format::R8G8B8_SNORM
+            | vk::Format::R8G8B8_USCALED
+            | vk::Format::R8G8B8_SSCALED
+            | vk::Format::R8G8B8_SRGB
+            | vk::Format::B8G8R8_UNORM
+            | vk::Format::B8G8R8_SNORM
+            | vk::Format::B8G8R8_USCALED
+            | vk::Format::B8G8R8_SSCALED
+            | vk::Format::B8G8R8_SRGB
+            | vk::Format::R8G8B8A8_UNORM
+            | vk::Format::R8G8B8A8_SNORM
+            | vk::Format::R8G8B8A8_USCALED
+            | vk::Format::R8G8B8A8_SSCALED
+            | vk::Format::R8G8B8A8_SRGB
+            | vk::Format::B8G8R8A8_UNORM
+            | vk::Format::B8G8R8A8_SNORM
+            | vk::Format::B8G8R8A8_USCALED
+            | vk::Format::B8G8R8A8_SSCALED
+            | vk::Format::B8G8R8A8_SRGB
+            | vk::Format::A8B8G8R8_UNORM_PACK32
+            | vk::Format::A8B8G8R8_SNORM_PACK32
+            | vk::Format::A8B8G8R8_USCALED_PACK32
+            | vk::Format::A8B8G8R8_SSCALED_PACK32
+            | vk::Format::A8B8G8R8_SRGB_PACK32
+            | vk::Format::A2R10G10B10_UNORM_PACK32
+            | vk::Format::A2R10G10B10_SNORM_PACK32
+            | vk::Format::A2R10G10B10_USCALED_PACK32
+            | vk::Format::A2R10G10B10_SSCALED_PACK32
+            | vk::Format::A2B10G10R10_UNORM_PACK32
+            | vk::Format::A2B10G10R10_SNORM_PACK32
+            | vk::Format::A2B10G10R10_USCALED_PACK32
+            | vk::Format::A2B10G10R10_SSCALED_PACK32
+            | vk::Format::R16_UNORM
+            | vk::Format::R16_SNORM
+            | vk::Format::R16_USCALED
+            | vk::Format::R16_SSCALED
+            | vk::Format::R16_SFLOAT
+            | vk::Format::R16G16_UNORM
+            | vk::Format::R16G16_SNORM
+            | vk::Format::R16G16_USCALED
+            | vk::Format::R16G16_SSCALED
+            | vk::Format::R16G16_SFLOAT
+            | vk::Format::R16G16B16_UNORM
+            | vk::Format::R16G16B16_SNORM
+            | vk::Format::R16G16B16_USCALED
+            | vk::Format::R16G16B16_SSCALED
+            | vk::Format::R16G16B16_SFLOAT
+            | vk::Format::R16G16B16A16_UNORM
+            | vk::Format::R16G16B16A16_SNORM
+            | vk::Format::R16G16B16A16_USCALED
+            | vk::Format::R16G16B16A16_SSCALED
+            | vk::Format::R16G16B16A16_SFLOAT
+            | vk::Format::R32_SFLOAT
+            | vk::Format::R32G32_SFLOAT
+            | vk::Format::R32G32B32_SFLOAT
+            | vk::Format::R32G32B32A32_SFLOAT
+            | vk::Format::R64_SFLOAT
+            | vk::Format::R64G64_SFLOAT
+            | vk::Format::R64G64B64_SFLOAT
+            | vk::Format::R64G64B64A64_SFLOAT
+            | vk::Format::B10G11R11_UFLOAT_PACK32
+            | vk::Format::E5B9G9R9_UFLOAT_PACK32 => ShaderScalarType::Float,
+            vk::Format::R8_UINT
+            | vk::Format::R8G8_UINT
+            | vk::Format::R8G8B8_UINT
+            | vk::Format::B8G8R8_UINT
+            | vk::Format::R8G8B8A8_UINT
+            | vk::Format::B8G8R8A8_UINT
+            | vk::Format::A8B8G8R8_UINT_PACK32
+            | vk::Format::A2R10G10B10_UINT_PACK32
+            | vk::Format::A2B10G10R10_UINT_PACK32
+            | vk::Format::R16_UINT
+            | vk::Format::R16G16_UINT
+            | vk::Format::R16G16B16_UINT
+            | vk::Format::R16G16B16A16_UINT
+            | vk::Format::R32_UINT
+            | vk::Format::R32G32_UINT
+            | vk::Format::R32G32B32_UINT
+            | vk::Format::R32G32B32A32_UINT
+            | vk::Format::R64_UINT
+            | vk::Format::R64G64_UINT
+            | vk::Format::R64G64B64_UINT
+            | vk::Format::R64G64B64A64_UINT => ShaderScalarType::Uint,
+            vk::Format::R8_SINT
+            | vk::Format::R8G8_SINT
+            | vk::Format::R8G8B8_SINT
+            | vk::Format::B8G8R8_SINT
+            | vk::Format::R8G8B8A8_SINT
+            | vk::Format::B8G8R8A8_SINT
+            | vk::Format::A8B8G8R8_SINT_PACK32
+            | vk::Format::A2R10G10B10_SINT_PACK32
+            | vk::Format::A2B10G10R10_SINT_PACK32
+            | vk::Format::R16_SINT
+            | vk::Format::R16G16_SINT
+            | vk::Format::R16G16B16_SINT
+            | vk::Format::R16G16B16A16_SINT
+            | vk::Format::R32_SINT
+            | vk::Format::R32G32_SINT
+            | vk::Format::R32G32B32_SINT
+            | vk::Format::R32G32B32A32_SINT
+            | vk::Format::R64_SINT
+            | vk::Format::R64G64_SINT
+            | vk::Format::R64G64B64_SINT
+            | vk::Format::R64G64B64A64_SINT => ShaderScalarType::Int,
+            _ => ShaderScalarType::Unknown,
+        }
+    }
+}
+
+impl ShaderBinding {
+    pub fn member(&self, name: &str) -> Option<&ShaderBlockMember> {
+        self.members.iter().find(|member| member.name == name)
+    }
+}
+
+impl ShaderVertexInput {
+    /// Format a vertex attribute feeding this input with 32 bits components would have, `UNDEFINED`
+    /// if the input's type is unknown.
+    pub fn vk_format(&self) -> vk::Format {
+        match (self.scalar_type, self.component_count) {
+            (ShaderScalarType::Float, 1) => vk::Format::R32_SFLOAT,
+            (ShaderScalarType::Float, 2) => vk::Format::R32G32_SFLOAT,
+            (ShaderScalarType::Float, 3) => vk::Format::R32G32B32_SFLOAT,
+            (ShaderScalarType::Float, 4) => vk::Format::R32G32B32A32_SFLOAT,
+            (ShaderScalarType::Int, 1) => vk::Format::R32_SINT,
+            (ShaderScalarType::Int, 2) => vk::Format::R32G32_SINT,
+            (ShaderScalarType::Int, 3) => vk::Format::R32G32B32_SINT,
+            (ShaderScalarType::Int, 4) => vk::Format::R32G32B32A32_SINT,
+            (ShaderScalarType::Uint, 1) => vk::Format::R32_UINT,
+            (ShaderScalarType::Uint, 2) => vk::Format::R32G32_UINT,
+            (ShaderScalarType::Uint, 3) => vk::Format::R32G32B32_UINT,
+            (ShaderScalarType::Uint, 4) => vk::Format::R32G32B32A32_UINT,
+            _ => vk::Format::UNDEFINED,
+        }
+    }
+}
+
+/// Description of a shader's interface, collected from its SPIR-V when it is built.
+///
+/// Bindings used by both stages are only listed once, with both stages set.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ShaderReflection {
+    /// Sorted by set, then by slot.
+    pub bindings: Vec<ShaderBinding>,
+    pub push_constant_ranges: Vec<ShaderPushConstantRange>,
+    /// Sorted by location.
+    pub vertex_inputs: Vec<ShaderVertexInput>,
+}
+
+impl ShaderReflection {
+    pub fn binding(&self, set: u32, slot: u32) -> Option<&ShaderBinding> {
+        self.bindings
+            .iter()
+            .find(|binding| binding.set == set && binding.slot == slot)
+    }
+
+    /// Binding of `set` named `name` (see [`ShaderBinding::name`] for the name of blocks).
+    pub fn binding_by_name(&self, set: u32, name: &str) -> Option<&ShaderBinding> {
+        self.bindings
+            .iter()
+            .find(|binding| binding.set == set && binding.name == name)
+    }
+
+    fn new(
+        stage_bindings: [(&[ReflectDescriptorBinding], ShaderStages); 2],
+        stage_push_constants: [(&[ReflectBlockVariable], ShaderStages); 2],
+        vertex_inputs: &[ReflectInterfaceVariable],
+    ) -> Self {
+        let mut bindings = Vec::<ShaderBinding>::new();
+        for (reflected_bindings, stages) in stage_bindings {
+            for reflected_binding in reflected_bindings {
+                if let Some(binding) = bindings.iter_mut().find(|binding| {
+                    binding.set == reflected_binding.set
+                        && binding.slot == reflected_binding.binding
+                }) {
+                    binding.stages.vertex |= stages.vertex;
+                    binding.stages.fragment |= stages.fragment;
+                    continue;
+                }
+
+                bindings.push(ShaderBinding {
+                    name: binding_name(reflected_binding),
+                    set: reflected_binding.set,
+                    slot: reflected_binding.binding,
+                    kind: resource_kind(reflected_binding),
+                    size: reflected_binding.block.size,
+                    count: reflected_binding.count,
+                    stages,
+                    members: block_members(&reflected_binding.block),
+                });
+            }
+        }
+        bindings.sort_by_key(|binding| (binding.set, binding.slot));
+
+        let push_constant_ranges = stage_push_constants
+            .into_iter()
+            .flat_map(|(blocks, stages)| {
+                blocks.iter().map(move |block| {
+                    let offset = block
+                        .members
+                        .iter()
+                        .map(|member| member.offset)
+                        .min()
+                        .unwrap_or(0);
+
+                    ShaderPushConstantRange {
+                        name: block.name.clone(),
+                        offset,
+                        size: block.size.saturating_sub(offset),
+                        stages,
+                        members: block_members(block),
+                    }
+                })
+            })
+            .collect();
+
+        let mut vertex_inputs = vertex_inputs
+            .iter()
+            .filter(|input| {
+                !input
+                    .decoration_flags
+                    .contains(ReflectDecorationFlags::BUILT_IN)
+            })
+            .map(|input| {
+                let (scalar_type, component_count) = match input.format {
+                    ReflectFormat::R32_UINT => (ShaderScalarType::Uint, 1),
+                    ReflectFormat::R32_SINT => (ShaderScalarType::Int, 1),
+                    ReflectFormat::R32_SFLOAT => (ShaderScalarType::Float, 1),
+                    ReflectFormat::R32G32_UINT => (ShaderScalarType::Uint, 2),
+                    ReflectFormat::R32G32_SINT => (ShaderScalarType::Int, 2),
+                    ReflectFormat::R32G32_SFLOAT => (ShaderScalarType::Float, 2),
+                    ReflectFormat::R32G32B32_UINT => (ShaderScalarType::Uint, 3),
+                    ReflectFormat::R32G32B32_SINT => (ShaderScalarType::Int, 3),
+                    ReflectFormat::R32G32B32_SFLOAT => (ShaderScalarType::Float, 3),
+                    ReflectFormat::R32G32B32A32_UINT => (ShaderScalarType::Uint, 4),
+                    ReflectFormat::R32G32B32A32_SINT => (ShaderScalarType::Int, 4),
+                    ReflectFormat::R32G32B32A32_SFLOAT => (ShaderScalarType::Float, 4),
+                    ReflectFormat::Undefined => (ShaderScalarType::Unknown, 0),
+                };
+
+                ShaderVertexInput {
+                    name: input.name.clone(),
+                    location: input.location,
+                    scalar_type,
+                    component_count,
+                }
+            })
+            .collect::<Vec<_>>();
+        vertex_inputs.sort_by_key(|input| input.location);
+
+        Self {
+            bindings,
+            push_constant_ranges,
+            vertex_inputs,
+        }
+    }
+}
+
+/// Uniform and storage blocks are usually named after their instance, which the reflection exposes
+/// as the binding name, falling back to the block's type name for anonymous instances.
+fn binding_name(binding: &ReflectDescriptorBinding) -> String {
+    if !binding.name.is_empty() {
+        return binding.name.clone();
+    }
+
+    binding
+        .type_description
+        .as_ref()
+        .map(|type_description| type_description.type_name.clone())
+        .unwrap_or_default()
+}
+
+fn resource_kind(binding: &ReflectDescriptorBinding) -> ShaderResourceKind {
+    match binding.descriptor_type {
+        ReflectDescriptorType::UniformBuffer => ShaderResourceKind::UniformBuffer,
+        ReflectDescriptorType::StorageBuffer => ShaderResourceKind::StorageBuffer,
+        ReflectDescriptorType::StorageImage => ShaderResourceKind::StorageImage,
+        ReflectDescriptorType::CombinedImageSampler if binding.count > 1 => {
+            ShaderResourceKind::SampledImageArray
+        }
+        ReflectDescriptorType::CombinedImageSampler => match binding.image.dim {
+            ReflectDimension::Cube => ShaderResourceKind::Cubemap,
+            _ => ShaderResourceKind::SampledImage,
+        },
+        ReflectDescriptorType::AccelerationStructureNV => ShaderResourceKind::AccelerationStructure,
+        _ => ShaderResourceKind::Unsupported,
+    }
+}
+
+fn block_members(block: &ReflectBlockVariable) -> Vec<ShaderBlockMember> {
+    block
+        .members
+        .iter()
+        .map(|member| ShaderBlockMember {
+            name: member.name.clone(),
+            offset: member.offset,
+            size: member.size,
+        })
+        .collect()
 }
 
 #[derive(Debug)]
 pub struct Shader {
+    /// Used to name the Vulkan objects of the materials built from this shader (see
+    /// [`crate::renderer::RendererBuilder::with_auto_naming`]).
+    pub name: Option<String>,
+
     pub(crate) vertex_module: vk::ShaderModule,
     pub(crate) fragment_module: vk::ShaderModule,
 
@@ -30,6 +484,38 @@ pub struct Shader {
     pub vertex_push_constants: Vec<ReflectBlockVariable>,
     pub fragment_bindings: Vec<BindingData>,
     pub fragment_push_constants: Vec<ReflectBlockVariable>,
+
+    reflection: ShaderReflection,
+
+    /// SPIR-V files of the vertex and fragment stages, when loaded with [`Shader::from_path`].
+    #[cfg(feature = "hot_reload")]
+    source_paths: Option<[PathBuf; 2]>,
+    #[cfg(feature = "hot_reload")]
+    watcher: Option<ShaderWatcher>,
+}
+
+#[cfg(feature = "hot_reload")]
+struct ShaderWatcher {
+    // Events stop being sent once the watcher is dropped
+    _watcher: notify::RecommendedWatcher,
+    events: std::sync::mpsc::Receiver<notify::Result<notify::Event>>,
+}
+
+#[cfg(feature = "hot_reload")]
+impl std::fmt::Debug for ShaderWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ShaderWatcher").finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "hot_reload")]
+#[derive(Error, Debug)]
+pub enum ShaderWatchError {
+    #[error("The shader was not loaded with Shader::from_path, there are no files to watch.")]
+    NoSourceFiles,
+
+    #[error("Watching of the shader's files failed with error: {0}.")]
+    WatcherCreationFailed(#[from] notify::Error),
 }
 
 pub(crate) fn create_shader_module(
@@ -69,8 +555,88 @@ pub enum ShaderBuildError {
         error_msg: &'static str,
     },
 
+    #[error("The SPIRV of stage {0:?} does not contain any entry point.")]
+    NoEntryPoint(vk::ShaderStageFlags),
+
     #[error("Descriptor set layout creation failed with error: {0}.")]
     DSLCreationFailed(#[from] DSLCreationError),
+
+    #[error("Slot {0} is not a uniform buffer of set 3, and can't be made dynamic.")]
+    InvalidDynamicUniformSlot(u32),
+
+    #[cfg(feature = "shaderc")]
+    #[error("GLSL compilation of stage {stage:?} failed with log: {log}")]
+    GlslCompilationFailed {
+        stage: vk::ShaderStageFlags,
+        log: String,
+    },
+
+    #[cfg(feature = "hot_reload")]
+    #[error("The reloaded shader's bindings or push constants differ from the current ones, which the materials and mesh renderings built from it can't adapt to.")]
+    IncompatibleReload,
+}
+
+#[cfg(feature = "hot_reload")]
+fn read_spirv_file(path: &Path, stage: vk::ShaderStageFlags) -> Result<Vec<u32>, ShaderBuildError> {
+    let spirv = fs::read(path).map_err(|error| ShaderBuildError::InvalidPath {
+        provided_path: path.display().to_string(),
+        error,
+    })?;
+
+    ash::util::read_spv(&mut std::io::Cursor::new(spirv))
+        .map_err(|error| ShaderBuildError::SPIRVDecodingFailed { stage, error })
+}
+
+/// Whether both binding lists describe the same descriptor set layouts.
+#[cfg(feature = "hot_reload")]
+fn same_bindings(old_bindings: &[BindingData], new_bindings: &[BindingData]) -> bool {
+    old_bindings.len() == new_bindings.len()
+        && old_bindings.iter().all(|old_binding| {
+            new_bindings.iter().any(|new_binding| {
+                new_binding.set == old_binding.set
+                    && new_binding.slot == old_binding.slot
+                    && new_binding.descriptor_type == old_binding.descriptor_type
+                    && new_binding.count == old_binding.count
+            })
+        })
+}
+
+#[cfg(feature = "shaderc")]
+fn compile_glsl(
+    compiler: &shaderc::Compiler,
+    source: &str,
+    stage: vk::ShaderStageFlags,
+) -> Result<Vec<u32>, ShaderBuildError> {
+    let (shader_kind, file_name) = if stage == vk::ShaderStageFlags::VERTEX {
+        (shaderc::ShaderKind::Vertex, "shader.vert")
+    } else {
+        (shaderc::ShaderKind::Fragment, "shader.frag")
+    };
+
+    let mut compile_options =
+        shaderc::CompileOptions::new().ok_or_else(|| ShaderBuildError::GlslCompilationFailed {
+            stage,
+            log: "Failed to create the shaderc compile options".to_owned(),
+        })?;
+    // Same environment as the shaders embedded by the build script
+    compile_options.set_target_env(
+        shaderc::TargetEnv::Vulkan,
+        shaderc::EnvVersion::Vulkan1_1 as u32,
+    );
+
+    compiler
+        .compile_into_spirv(
+            source,
+            shader_kind,
+            file_name,
+            "main",
+            Some(&compile_options),
+        )
+        .map(|artifact| artifact.as_binary().to_vec())
+        .map_err(|error| ShaderBuildError::GlslCompilationFailed {
+            stage,
+            log: error.to_string(),
+        })
 }
 
 #[profiling::all_functions]
@@ -83,22 +649,48 @@ impl Shader {
     ) -> Result<ThreadSafeRef<Self>, ShaderBuildError> {
         let vertex_spirv =
             fs::read(vertex_path).map_err(|error| ShaderBuildError::InvalidPath {
-                provided_path: vertex_path
-                    .to_str()
-                    .map(|str| str.to_owned())
-                    .expect("Failed to parse provided path."),
+                provided_path: vertex_path.display().to_string(),
                 error,
             })?;
         let fragment_spirv =
             fs::read(fragment_path).map_err(|error| ShaderBuildError::InvalidPath {
-                provided_path: fragment_path
-                    .to_str()
-                    .map(|str| str.to_owned())
-                    .expect("Failed to parse provided path."),
+                provided_path: fragment_path.display().to_string(),
                 error,
             })?;
 
-        Self::from_spirv_u8(&vertex_spirv, &fragment_spirv, device)
+        let shader_ref = Self::from_spirv_u8(&vertex_spirv, &fragment_spirv, device)?;
+        {
+            let mut shader = shader_ref.lock();
+            shader.name = vertex_path
+                .file_name()
+                .map(|file_name| file_name.to_string_lossy().into_owned());
+            #[cfg(feature = "hot_reload")]
+            {
+                shader.source_paths = Some([vertex_path.to_owned(), fragment_path.to_owned()]);
+            }
+        }
+
+        Ok(shader_ref)
+    }
+
+    /// Compiles both stages from GLSL source at runtime, e.g. to edit shaders live. The resulting
+    /// SPIR-V goes through the same reflection as [`Shader::from_spirv_u32`]. Compilation errors
+    /// are returned as [`ShaderBuildError::GlslCompilationFailed`], with shaderc's log.
+    #[cfg(feature = "shaderc")]
+    pub fn from_glsl_source(
+        vertex_src: &str,
+        fragment_src: &str,
+        device: &Device,
+    ) -> Result<ThreadSafeRef<Self>, ShaderBuildError> {
+        let compiler =
+            shaderc::Compiler::new().ok_or_else(|| ShaderBuildError::GlslCompilationFailed {
+                stage: vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                log: "Failed to create the shaderc compiler".to_owned(),
+            })?;
+        let vertex_spirv = compile_glsl(&compiler, vertex_src, vk::ShaderStageFlags::VERTEX)?;
+        let fragment_spirv = compile_glsl(&compiler, fragment_src, vk::ShaderStageFlags::FRAGMENT)?;
+
+        Self::from_spirv_u32(device, &vertex_spirv, &fragment_spirv)
     }
 
     /// This function expects **COMPILED SPIR-V**, not higher level languages like GLSL or HSLS source code.
@@ -131,6 +723,14 @@ impl Shader {
         vertex_spirv: &[u32],
         fragment_spirv: &[u32],
     ) -> Result<ThreadSafeRef<Self>, ShaderBuildError> {
+        Self::from_spirv_u32_internal(device, vertex_spirv, fragment_spirv).map(ThreadSafeRef::new)
+    }
+
+    fn from_spirv_u32_internal(
+        device: &Device,
+        vertex_spirv: &[u32],
+        fragment_spirv: &[u32],
+    ) -> Result<Self, ShaderBuildError> {
         let vertex_module = create_shader_module(device, vertex_spirv).map_err(|result| {
             ShaderBuildError::ShaderModuleCreationFailed {
                 stage: vk::ShaderStageFlags::VERTEX,
@@ -149,14 +749,15 @@ impl Shader {
                 stage: vk::ShaderStageFlags::VERTEX,
                 error_msg,
             })?;
-        let vertex_entry_point =
-            vertex_reflection_module
-                .enumerate_entry_points()
-                .map_err(|error_msg| ShaderBuildError::ReflectionLoadingFailed {
-                    stage: vk::ShaderStageFlags::VERTEX,
-                    error_msg,
-                })?[0]
-                .clone();
+        let vertex_entry_point = vertex_reflection_module
+            .enumerate_entry_points()
+            .map_err(|error_msg| ShaderBuildError::ReflectionLoadingFailed {
+                stage: vk::ShaderStageFlags::VERTEX,
+                error_msg,
+            })?
+            .into_iter()
+            .next()
+            .ok_or(ShaderBuildError::NoEntryPoint(vk::ShaderStageFlags::VERTEX))?;
         let vertex_bindings_reflection = vertex_reflection_module
             .enumerate_descriptor_bindings(Some(vertex_entry_point.name.as_str()))
             .map_err(|error_msg| ShaderBuildError::ReflectionLoadingFailed {
@@ -180,8 +781,12 @@ impl Shader {
             .map_err(|error_msg| ShaderBuildError::ReflectionLoadingFailed {
                 stage: vk::ShaderStageFlags::FRAGMENT,
                 error_msg,
-            })?[0]
-            .clone();
+            })?
+            .into_iter()
+            .next()
+            .ok_or(ShaderBuildError::NoEntryPoint(
+                vk::ShaderStageFlags::FRAGMENT,
+            ))?;
         let fragment_bindings_reflection = fragment_reflection_module
             .enumerate_descriptor_bindings(Some(fragment_entry_point.name.as_str()))
             .map_err(|error_msg| ShaderBuildError::ReflectionLoadingFailed {
@@ -195,58 +800,67 @@ impl Shader {
                 error_msg,
             })?;
 
-        let level_2_dsl = create_dsl(
-            device,
-            2,
-            &[
+        let vertex_bindings = vertex_bindings_reflection
+            .iter()
+            .map(BindingData::from)
+            .collect::<Vec<_>>();
+        let fragment_bindings = fragment_bindings_reflection
+            .iter()
+            .map(BindingData::from)
+            .collect::<Vec<_>>();
+
+        let stage_bindings = [
+            (vertex_bindings.as_slice(), vk::ShaderStageFlags::VERTEX),
+            (fragment_bindings.as_slice(), vk::ShaderStageFlags::FRAGMENT),
+        ];
+        let level_2_dsl = create_dsl(device, MATERIAL_SET, &stage_bindings)
+            .map_err(ShaderBuildError::DSLCreationFailed)?;
+        let level_3_dsl = create_dsl(device, OBJECT_SET, &stage_bindings)?;
+
+        let vertex_inputs = vertex_reflection_module
+            .enumerate_input_variables(Some(vertex_entry_point.name.as_str()))
+            .map_err(|error_msg| ShaderBuildError::ReflectionLoadingFailed {
+                stage: vk::ShaderStageFlags::VERTEX,
+                error_msg,
+            })?;
+        let reflection = ShaderReflection::new(
+            [
                 (
-                    vertex_bindings_reflection.clone(),
-                    vk::ShaderStageFlags::VERTEX,
+                    &vertex_bindings_reflection,
+                    ShaderStages {
+                        vertex: true,
+                        fragment: false,
+                    },
                 ),
                 (
-                    fragment_bindings_reflection.clone(),
-                    vk::ShaderStageFlags::FRAGMENT,
+                    &fragment_bindings_reflection,
+                    ShaderStages {
+                        vertex: false,
+                        fragment: true,
+                    },
                 ),
             ],
-        )
-        .map_err(ShaderBuildError::DSLCreationFailed)?;
-        let level_3_dsl = create_dsl(
-            device,
-            3,
-            &[
+            [
                 (
-                    vertex_bindings_reflection.clone(),
-                    vk::ShaderStageFlags::VERTEX,
+                    &vertex_push_constants,
+                    ShaderStages {
+                        vertex: true,
+                        fragment: false,
+                    },
                 ),
                 (
-                    fragment_bindings_reflection.clone(),
-                    vk::ShaderStageFlags::FRAGMENT,
+                    &fragment_push_constants,
+                    ShaderStages {
+                        vertex: false,
+                        fragment: true,
+                    },
                 ),
             ],
-        )?;
+            &vertex_inputs,
+        );
 
-        let vertex_bindings = vertex_bindings_reflection
-            .iter()
-            .map(|binding| BindingData {
-                set: binding.set,
-                slot: binding.binding,
-                descriptor_type: binding.descriptor_type,
-                size: binding.block.size,
-                dim: binding.image.dim,
-            })
-            .collect::<Vec<_>>();
-        let fragment_bindings = fragment_bindings_reflection
-            .iter()
-            .map(|binding| BindingData {
-                set: binding.set,
-                slot: binding.binding,
-                descriptor_type: binding.descriptor_type,
-                size: binding.block.size,
-                dim: binding.image.dim,
-            })
-            .collect::<Vec<_>>();
-
-        Ok(ThreadSafeRef::new(Self {
+        Ok(Self {
+            name: None,
             vertex_module,
             fragment_module,
             level_2_dsl,
@@ -255,7 +869,189 @@ impl Shader {
             vertex_push_constants,
             fragment_bindings,
             fragment_push_constants,
-        }))
+            reflection,
+            #[cfg(feature = "hot_reload")]
+            source_paths: None,
+            #[cfg(feature = "hot_reload")]
+            watcher: None,
+        })
+    }
+
+    /// Starts watching the SPIR-V files the shader was loaded from (see [`Shader::from_path`]),
+    /// so that [`Shader::poll_reload`] rebuilds it when they change, e.g. when they are recompiled
+    /// by `glslc` or a build script. Their folders are watched rather than the files themselves,
+    /// as editors and compilers often replace files instead of writing to them.
+    #[cfg(feature = "hot_reload")]
+    pub fn watch(&mut self) -> Result<(), ShaderWatchError> {
+        use notify::Watcher;
+
+        let source_paths = self
+            .source_paths
+            .as_mut()
+            .ok_or(ShaderWatchError::NoSourceFiles)?;
+        let (sender, events) = std::sync::mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+
+        let mut watched_folders = Vec::with_capacity(2);
+        for path in source_paths.iter_mut() {
+            // Event paths are absolute, so they are compared against canonical ones
+            if let Ok(canonical_path) = fs::canonicalize(&*path) {
+                *path = canonical_path;
+            }
+            let folder = path
+                .parent()
+                .map_or_else(|| PathBuf::from("."), Path::to_owned);
+            if !watched_folders.contains(&folder) {
+                watcher.watch(&folder, notify::RecursiveMode::NonRecursive)?;
+                watched_folders.push(folder);
+            }
+        }
+
+        self.watcher = Some(ShaderWatcher {
+            _watcher: watcher,
+            events,
+        });
+
+        Ok(())
+    }
+
+    /// Rebuilds the shader's modules if its files changed since the last call (see
+    /// [`Shader::watch`]), and returns the result of the rebuild, or `None` if nothing changed.
+    ///
+    /// The descriptor set layouts are kept, so that the existing materials and mesh renderings
+    /// stay valid: a new version changing the shader's bindings or push constants is refused with
+    /// [`ShaderBuildError::IncompatibleReload`]. After a successful reload, the materials using
+    /// this shader must call [`crate::material::Material::rebuild_pipeline`] to use the new code.
+    #[cfg(feature = "hot_reload")]
+    pub fn poll_reload(&mut self, device: &Device) -> Option<Result<(), ShaderBuildError>> {
+        let watcher = self.watcher.as_ref()?;
+        let source_paths = self.source_paths.as_ref()?;
+
+        let mut changed = false;
+        for event in watcher.events.try_iter() {
+            match event {
+                Ok(event) => {
+                    changed |= matches!(
+                        event.kind,
+                        notify::EventKind::Create(_) | notify::EventKind::Modify(_)
+                    ) && event.paths.iter().any(|path| source_paths.contains(path));
+                }
+                Err(error) => log::warn!("Failed to watch shader files: {error}"),
+            }
+        }
+        if !changed {
+            return None;
+        }
+
+        Some(self.reload(device))
+    }
+
+    #[cfg(feature = "hot_reload")]
+    fn reload(&mut self, device: &Device) -> Result<(), ShaderBuildError> {
+        let Some([vertex_path, fragment_path]) = &self.source_paths else {
+            return Ok(());
+        };
+        let vertex_spirv = read_spirv_file(vertex_path, vk::ShaderStageFlags::VERTEX)?;
+        let fragment_spirv = read_spirv_file(fragment_path, vk::ShaderStageFlags::FRAGMENT)?;
+        let mut reloaded = Self::from_spirv_u32_internal(device, &vertex_spirv, &fragment_spirv)?;
+
+        let push_constant_size =
+            |blocks: &[ReflectBlockVariable]| blocks.first().map(|block| block.size);
+        let is_compatible = same_bindings(&self.vertex_bindings, &reloaded.vertex_bindings)
+            && same_bindings(&self.fragment_bindings, &reloaded.fragment_bindings)
+            && push_constant_size(&self.vertex_push_constants)
+                == push_constant_size(&reloaded.vertex_push_constants)
+            && push_constant_size(&self.fragment_push_constants)
+                == push_constant_size(&reloaded.fragment_push_constants);
+        if !is_compatible {
+            reloaded.destroy(device);
+            return Err(ShaderBuildError::IncompatibleReload);
+        }
+
+        // Pipelines don't reference their modules once created, so the old ones can go. The
+        // layouts (and the bindings, with their dynamic flags) are kept, as they are in use
+        unsafe {
+            device.destroy_shader_module(self.vertex_module, None);
+            device.destroy_shader_module(self.fragment_module, None);
+            device.destroy_descriptor_set_layout(reloaded.level_2_dsl, None);
+            device.destroy_descriptor_set_layout(reloaded.level_3_dsl, None);
+        }
+        self.vertex_module = reloaded.vertex_module;
+        self.fragment_module = reloaded.fragment_module;
+        self.vertex_push_constants = reloaded.vertex_push_constants;
+        self.fragment_push_constants = reloaded.fragment_push_constants;
+        self.reflection = reloaded.reflection;
+
+        Ok(())
+    }
+
+    /// Interface of the shader (bindings, push constants and vertex inputs), e.g. to check which
+    /// resources a material needs.
+    #[profiling::skip]
+    pub fn reflection(&self) -> &ShaderReflection {
+        &self.reflection
+    }
+
+    /// Makes the given set 3 uniforms dynamic: instead of reading a buffer owned by each mesh
+    /// rendering, they read a slice of the renderer's dynamic uniform buffer, written every frame
+    /// by the render systems with the data of
+    /// [`crate::components::mesh_rendering::MeshRendering::set_dynamic_uniform_pod`]. This avoids
+    /// allocating a buffer per object for data that changes every frame, such as animation
+    /// parameters. The model matrix ([`crate::descriptor_resources::MODEL_MATRIX_BINDING`]) is
+    /// always dynamic, whether it is part of `slots` or not.
+    ///
+    /// This recreates the set 3 layout, so it must be called before any material or mesh rendering
+    /// is built with this shader.
+    pub fn set_dynamic_uniforms(
+        &mut self,
+        slots: &[u32],
+        device: &Device,
+    ) -> Result<(), ShaderBuildError> {
+        for &slot in slots {
+            let is_object_uniform = |binding: &BindingData| {
+                binding.set == OBJECT_SET
+                    && binding.slot == slot
+                    && binding.descriptor_type == ReflectDescriptorType::UniformBuffer
+            };
+            if !self
+                .vertex_bindings
+                .iter()
+                .chain(&self.fragment_bindings)
+                .any(is_object_uniform)
+            {
+                return Err(ShaderBuildError::InvalidDynamicUniformSlot(slot));
+            }
+        }
+
+        for binding in self
+            .vertex_bindings
+            .iter_mut()
+            .chain(&mut self.fragment_bindings)
+        {
+            binding.dynamic = is_model_matrix(binding.set, binding.slot, binding.descriptor_type)
+                || (binding.set == OBJECT_SET
+                    && binding.descriptor_type == ReflectDescriptorType::UniformBuffer
+                    && slots.contains(&binding.slot));
+        }
+
+        let level_3_dsl = create_dsl(
+            device,
+            OBJECT_SET,
+            &[
+                (
+                    self.vertex_bindings.as_slice(),
+                    vk::ShaderStageFlags::VERTEX,
+                ),
+                (
+                    self.fragment_bindings.as_slice(),
+                    vk::ShaderStageFlags::FRAGMENT,
+                ),
+            ],
+        )?;
+        unsafe { device.destroy_descriptor_set_layout(self.level_3_dsl, None) };
+        self.level_3_dsl = level_3_dsl;
+
+        Ok(())
     }
 
     pub fn destroy(&mut self, device: &Device) {
@@ -0,0 +1,570 @@
+use std::collections::HashMap;
+
+use ash::vk;
+use bevy_ecs::component::Component;
+use bytemuck::{bytes_of, Pod, Zeroable};
+use thiserror::Error;
+
+use crate::{
+    allocated_types::{
+        AllocatedBuffer, AllocatedImage, BufferBuildError, BufferDataUploadError, ImageBuildError,
+    },
+    components::camera::Camera,
+    material::Vertex,
+    math_types::{Mat4, Vec3, Vec4},
+    mesh::{Mesh, PositionLayout, PositionOnlyDraw},
+    pipeline_builder::{PipelineBuildError, PipelineBuilder},
+    render_target::{depth_only_render_pass, transition_to_shader_read, DEPTH_ONLY_FORMAT},
+    renderer::Renderer,
+    shader::{Shader, ShaderBuildError},
+    texture::Texture,
+    utils::{ImmediateCommandError, ThreadSafeRef},
+};
+
+pub const MAX_SHADOW_CASCADES: usize = 8;
+/// Distance covered by the cascades of a camera with an infinite far plane, when
+/// [`ShadowSettings::max_distance`] isn't set.
+pub const DEFAULT_INFINITE_SHADOW_DISTANCE: f32 = 1000.0;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ShadowSettings {
+    /// Number of cascades (and layers of the shadow map array), between 1 and [`MAX_SHADOW_CASCADES`].
+    pub cascade_count: u32,
+    /// Blend between a uniform (0.0) and a logarithmic (1.0) split of the view frustum.
+    pub split_lambda: f32,
+    /// Cascades cover the view frustum up to this distance, or up to the camera's far plane if it
+    /// is closer.
+    pub max_distance: Option<f32>,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            cascade_count: 4,
+            split_lambda: 0.75,
+            max_distance: None,
+        }
+    }
+}
+
+impl ShadowSettings {
+    /// Distance covered by the cascades for a camera whose far plane is `far_plane` (`None` being
+    /// infinite).
+    pub fn shadow_distance(&self, far_plane: Option<f32>) -> f32 {
+        match (far_plane, self.max_distance) {
+            (Some(far_plane), Some(max_distance)) => far_plane.min(max_distance),
+            (Some(distance), None) | (None, Some(distance)) => distance,
+            (None, None) => DEFAULT_INFINITE_SHADOW_DISTANCE,
+        }
+    }
+
+    /// Returns the far distance (in view space) of each cascade.
+    pub fn split_distances(&self, near_plane: f32, far_plane: f32) -> Vec<f32> {
+        let cascade_count = self.cascade_count.clamp(1, MAX_SHADOW_CASCADES as u32);
+
+        (1..=cascade_count)
+            .map(|cascade_index| {
+                let ratio = cascade_index as f32 / cascade_count as f32;
+                let logarithmic_split = near_plane * (far_plane / near_plane).powf(ratio);
+                let uniform_split = near_plane + (far_plane - near_plane) * ratio;
+
+                self.split_lambda * logarithmic_split + (1.0 - self.split_lambda) * uniform_split
+            })
+            .collect()
+    }
+}
+
+/// Per-cascade data, laid out to be used directly as a std140 uniform block:
+/// ```glsl
+/// layout(set = 2, binding = 0) uniform CascadeData {
+///     mat4 lightViewProjections[8];
+///     vec4 splitDistances[2]; // cascade i's split is splitDistances[i / 4][i % 4]
+///     uint cascadeCount;
+/// } u_CascadeData;
+/// ```
+/// Shaders select a cascade by comparing the fragment's view space depth (`-(view * position).z`)
+/// to the split distances: the first cascade whose split is greater than the depth is used, and its
+/// matrix projects the fragment into the matching layer of the shadow map array (see
+/// [`ShadowMap`]).
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct CascadeData {
+    pub light_view_projections: [Mat4; MAX_SHADOW_CASCADES],
+    pub split_distances: [Vec4; MAX_SHADOW_CASCADES / 4],
+    pub cascade_count: u32,
+    _padding: [u32; 3],
+}
+unsafe impl Zeroable for CascadeData {}
+unsafe impl Pod for CascadeData {}
+
+/// Cascaded shadow map matrices of a directional light, kept in a uniform buffer that can be bound
+/// to any material or mesh rendering (see [`CascadeData`] for its layout).
+pub struct ShadowCascades {
+    pub settings: ShadowSettings,
+    pub uniform_buffer: ThreadSafeRef<AllocatedBuffer>,
+
+    cascade_data: CascadeData,
+}
+
+#[profiling::all_functions]
+impl ShadowCascades {
+    pub fn new(
+        settings: ShadowSettings,
+        renderer: &mut Renderer,
+    ) -> Result<Self, BufferBuildError> {
+        let uniform_buffer =
+            AllocatedBuffer::builder(std::mem::size_of::<CascadeData>().try_into().unwrap())
+                .with_name("Shadow cascades")
+                .build(renderer)?;
+
+        Ok(Self {
+            settings,
+            uniform_buffer: ThreadSafeRef::new(uniform_buffer),
+            cascade_data: CascadeData::zeroed(),
+        })
+    }
+
+    #[profiling::skip]
+    pub fn cascade_data(&self) -> &CascadeData {
+        &self.cascade_data
+    }
+
+    /// Fits each cascade to its slice of the camera frustum, and uploads the result.
+    pub fn update(
+        &mut self,
+        camera: &Camera,
+        light_direction: Vec3,
+    ) -> Result<(), BufferDataUploadError> {
+        let near_plane = camera.projection_type().near_plane();
+        let far_plane = self
+            .settings
+            .shadow_distance(camera.projection_type().far_plane());
+        let splits = self.settings.split_distances(near_plane, far_plane);
+
+        // The frustum is cut at the shadow distance, which also keeps its corners finite when the
+        // far plane is at infinity
+        let projection = Camera::compute_projection(
+            &camera.projection_type().with_far_plane(far_plane),
+            *camera.aspect_ratio(),
+        );
+        let inverse_view_projection = (projection * *camera.view()).inverse();
+        let frustum_corners = [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)].map(|(x, y)| {
+            (
+                inverse_view_projection.project_point3(Vec3::new(x, y, 0.0)),
+                inverse_view_projection.project_point3(Vec3::new(x, y, 1.0)),
+            )
+        });
+
+        let light_direction = light_direction.normalize();
+        let up = if light_direction.abs_diff_eq(Vec3::Y, 1e-3)
+            || light_direction.abs_diff_eq(Vec3::NEG_Y, 1e-3)
+        {
+            Vec3::Z
+        } else {
+            Vec3::Y
+        };
+
+        let mut cascade_data = CascadeData::zeroed();
+        let mut previous_split = near_plane;
+        for (cascade_index, split) in splits.iter().enumerate() {
+            let start = (previous_split - near_plane) / (far_plane - near_plane);
+            let end = (split - near_plane) / (far_plane - near_plane);
+            previous_split = *split;
+
+            let slice_corners = frustum_corners
+                .iter()
+                .flat_map(|(near, far)| [near.lerp(*far, start), near.lerp(*far, end)])
+                .collect::<Vec<_>>();
+
+            // Fitting a sphere rather than a box keeps the cascade size stable when the camera rotates
+            let center = slice_corners.iter().sum::<Vec3>() / slice_corners.len() as f32;
+            let radius = slice_corners
+                .iter()
+                .map(|corner| corner.distance(center))
+                .fold(0.0_f32, f32::max);
+            let radius = (radius * 16.0).ceil() / 16.0;
+
+            let light_view = Mat4::look_at_rh(center - light_direction * radius, center, up);
+            let light_projection =
+                Mat4::orthographic_rh(-radius, radius, -radius, radius, 0.0, 2.0 * radius);
+
+            cascade_data.light_view_projections[cascade_index] = light_projection * light_view;
+            cascade_data.split_distances[cascade_index / 4][cascade_index % 4] = *split;
+        }
+        cascade_data.cascade_count = splits.len().try_into().unwrap();
+
+        self.cascade_data = cascade_data;
+        self.uniform_buffer.lock().upload_pod(cascade_data)
+    }
+
+    pub fn destroy(&mut self, renderer: &mut Renderer) {
+        self.uniform_buffer
+            .lock()
+            .destroy(&renderer.device, &mut renderer.allocator());
+    }
+}
+
+/// Slope scaled depth bias applied when rendering the casters, to avoid shadow acne.
+const DEPTH_BIAS_CONSTANT_FACTOR: f32 = 1.25;
+const DEPTH_BIAS_SLOPE_FACTOR: f32 = 1.75;
+
+#[derive(Error, Debug)]
+pub enum ShadowMapCreationError {
+    #[error("Creation of the shadow depth shader failed with error: {0}.")]
+    ShaderCreationFailed(#[from] ShaderBuildError),
+
+    #[error("Creation of the shadow map image failed with error: {0}.")]
+    ImageCreationFailed(#[from] ImageBuildError),
+
+    #[error("Creation of the shadow cascades buffer failed with error: {0}.")]
+    BufferCreationFailed(#[from] BufferBuildError),
+
+    #[error("Initial layout transition of the shadow map failed with error: {0}.")]
+    LayoutTransitionFailed(#[from] ImmediateCommandError),
+
+    #[error("Vulkan creation of the shadow map's sampler failed with result: {0}.")]
+    VulkanSamplerCreationFailed(vk::Result),
+
+    #[error("Vulkan creation of a shadow map layer view failed with result: {0}.")]
+    VulkanImageViewCreationFailed(vk::Result),
+
+    #[error("Vulkan creation of the shadow map render pass failed with result: {0}.")]
+    VulkanRenderPassCreationFailed(vk::Result),
+
+    #[error("Vulkan creation of a shadow map framebuffer failed with result: {0}.")]
+    VulkanFramebufferCreationFailed(vk::Result),
+
+    #[error("Vulkan creation of the shadow map pipeline layout failed with result: {0}.")]
+    VulkanPipelineLayoutCreationFailed(vk::Result),
+}
+
+/// Marks the entities whose mesh rendering is drawn into the [`ShadowMap`], see
+/// [`crate::systems::shadows::queue_shadow_casters`].
+#[derive(Debug, Default, Clone, Copy, Component)]
+pub struct ShadowCaster;
+
+/// Depth array rendered from the [`ShadowCascades`] of a directional light, with one layer per
+/// cascade (see [`crate::systems::shadows::render_shadow_map`]).
+///
+/// Shaders sample [`ShadowMap::texture_ref`] as a `sampler2DArray`: the fragment is projected by
+/// the matrix of its cascade (see [`CascadeData`]), the layer of that cascade is read at
+/// `ndc.xy * 0.5 + 0.5`, and the fragment is in shadow if its depth `ndc.z` is greater than the
+/// stored one. The texture is left in `SHADER_READ_ONLY_OPTIMAL` layout at the end of every pass.
+///
+/// Only the position of the casters' vertices is read, so skinned meshes cast the shadow of their
+/// bind pose.
+pub struct ShadowMap {
+    pub cascades: ShadowCascades,
+    pub texture_ref: ThreadSafeRef<Texture>,
+
+    layer_views: Vec<vk::ImageView>,
+    framebuffers: Vec<vk::Framebuffer>,
+    render_pass: vk::RenderPass,
+
+    shader_ref: ThreadSafeRef<Shader>,
+    pipeline_layout: vk::PipelineLayout,
+    pipelines: HashMap<PositionLayout, vk::Pipeline>,
+
+    /// Model matrix and draw of the casters queued during the frame.
+    queued_draws: Vec<(Mat4, PositionOnlyDraw)>,
+}
+
+#[profiling::all_functions]
+impl ShadowMap {
+    /// Creates a `resolution` x `resolution` shadow map with a layer for each of the
+    /// `settings.cascade_count` cascades. The cascade count can be lowered afterwards through
+    /// [`ShadowCascades::settings`], but the cascades above the initial count are not rendered.
+    pub fn new(
+        settings: ShadowSettings,
+        resolution: u32,
+        renderer: &mut Renderer,
+    ) -> Result<Self, ShadowMapCreationError> {
+        let layer_count = settings
+            .cascade_count
+            .clamp(1, MAX_SHADOW_CASCADES.try_into().unwrap());
+
+        let shader_ref = Shader::from_spirv_u8(
+            include_bytes!("shaders/gen/shadow_depth.vert"),
+            include_bytes!("shaders/gen/shadow_depth.frag"),
+            &renderer.device,
+        )?;
+
+        let mut image_builder = AllocatedImage::builder(vk::Extent3D {
+            width: resolution,
+            height: resolution,
+            depth: 1,
+        });
+        image_builder.image_create_info = image_builder
+            .image_create_info
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(DEPTH_ONLY_FORMAT)
+            .mip_levels(1)
+            .array_layers(layer_count)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+        image_builder.image_view_create_info = image_builder
+            .image_view_create_info
+            .view_type(vk::ImageViewType::TYPE_2D_ARRAY)
+            .format(DEPTH_ONLY_FORMAT)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::DEPTH,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count,
+            });
+        let mut image =
+            image_builder.build_uninitialized(&renderer.device, &mut renderer.allocator())?;
+        transition_to_shader_read(&mut image, vk::ImageAspectFlags::DEPTH, renderer)?;
+
+        let render_pass = depth_only_render_pass(&renderer.device)
+            .map_err(ShadowMapCreationError::VulkanRenderPassCreationFailed)?;
+
+        let mut layer_views = Vec::with_capacity(layer_count as usize);
+        let mut framebuffers = Vec::with_capacity(layer_count as usize);
+        for layer in 0..layer_count {
+            let view_info = vk::ImageViewCreateInfo::default()
+                .image(image.handle)
+                .view_type(vk::ImageViewType::TYPE_2D)
+                .format(DEPTH_ONLY_FORMAT)
+                .subresource_range(
+                    vk::ImageSubresourceRange::default()
+                        .aspect_mask(vk::ImageAspectFlags::DEPTH)
+                        .level_count(1)
+                        .base_array_layer(layer)
+                        .layer_count(1),
+                );
+            let layer_view = unsafe { renderer.device.create_image_view(&view_info, None) }
+                .map_err(ShadowMapCreationError::VulkanImageViewCreationFailed)?;
+            layer_views.push(layer_view);
+
+            let framebuffer_info = vk::FramebufferCreateInfo::default()
+                .render_pass(render_pass)
+                .attachments(std::slice::from_ref(&layer_view))
+                .width(resolution)
+                .height(resolution)
+                .layers(1);
+            let framebuffer =
+                unsafe { renderer.device.create_framebuffer(&framebuffer_info, None) }
+                    .map_err(ShadowMapCreationError::VulkanFramebufferCreationFailed)?;
+            framebuffers.push(framebuffer);
+        }
+
+        let texture = Texture::from_image(image, renderer)
+            .map_err(ShadowMapCreationError::VulkanSamplerCreationFailed)?;
+
+        let push_constant_range = vk::PushConstantRange::default()
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .offset(0)
+            .size(std::mem::size_of::<Mat4>().try_into().unwrap());
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::default()
+            .push_constant_ranges(std::slice::from_ref(&push_constant_range));
+        let pipeline_layout = unsafe {
+            renderer
+                .device
+                .create_pipeline_layout(&pipeline_layout_info, None)
+        }
+        .map_err(ShadowMapCreationError::VulkanPipelineLayoutCreationFailed)?;
+
+        let cascades = ShadowCascades::new(settings, renderer)?;
+
+        Ok(Self {
+            cascades,
+            texture_ref: ThreadSafeRef::new(texture),
+            layer_views,
+            framebuffers,
+            render_pass,
+            shader_ref,
+            pipeline_layout,
+            pipelines: HashMap::new(),
+            queued_draws: vec![],
+        })
+    }
+
+    /// Number of layers of the shadow map, the maximum number of cascades rendered.
+    #[profiling::skip]
+    pub fn layer_count(&self) -> u32 {
+        self.framebuffers.len().try_into().unwrap()
+    }
+
+    /// Queues the draw of `mesh` for the next pass. Meshes whose position (see
+    /// [`Vertex::position_index`]) is not a `vec3` read from the vertex buffer can't be drawn,
+    /// and are ignored.
+    pub(crate) fn queue_draw<VertexType>(&mut self, model: Mat4, mesh: &Mesh<VertexType>)
+    where
+        VertexType: Vertex,
+    {
+        if let Some(draw) = mesh.position_only_draw(None) {
+            self.queued_draws.push((model, draw));
+        }
+    }
+
+    /// Records a pass for each cascade with the draws queued during the frame, outside of any
+    /// render pass. Each layer is cleared, even if no caster was queued.
+    pub(crate) fn record(&mut self, device: &ash::Device, cmd_buffer: vk::CommandBuffer) {
+        let draws = std::mem::take(&mut self.queued_draws);
+        let pipelines = draws
+            .iter()
+            .map(|(_, draw)| match self.pipeline(draw.layout, device) {
+                Ok(pipeline) => Some(pipeline),
+                Err(error) => {
+                    log::warn!("Failed to build a shadow depth pipeline: {error}");
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let [width, height] = self.texture_ref.lock().dimensions;
+        let extent = vk::Extent2D { width, height };
+        let viewport = vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: width as f32,
+            height: height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        let scissor = vk::Rect2D {
+            extent,
+            ..Default::default()
+        };
+        let clear_value = vk::ClearValue {
+            depth_stencil: vk::ClearDepthStencilValue {
+                depth: 1.0,
+                stencil: 0,
+            },
+        };
+
+        let cascade_data = self.cascades.cascade_data();
+        let cascade_count = cascade_data.cascade_count as usize;
+        for (light_view_projection, framebuffer) in cascade_data
+            .light_view_projections
+            .iter()
+            .zip(&self.framebuffers)
+            .take(cascade_count)
+        {
+            let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+                .render_pass(self.render_pass)
+                .framebuffer(*framebuffer)
+                .render_area(scissor)
+                .clear_values(std::slice::from_ref(&clear_value));
+            unsafe {
+                device.cmd_begin_render_pass(
+                    cmd_buffer,
+                    &render_pass_begin_info,
+                    vk::SubpassContents::INLINE,
+                );
+                device.cmd_set_viewport(cmd_buffer, 0, std::slice::from_ref(&viewport));
+                device.cmd_set_scissor(cmd_buffer, 0, std::slice::from_ref(&scissor));
+            }
+
+            for ((model, draw), pipeline) in draws.iter().zip(&pipelines) {
+                let Some(pipeline) = pipeline else {
+                    continue;
+                };
+                let model_light_view_projection = *light_view_projection * *model;
+
+                unsafe {
+                    device.cmd_bind_pipeline(
+                        cmd_buffer,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        *pipeline,
+                    );
+                    device.cmd_push_constants(
+                        cmd_buffer,
+                        self.pipeline_layout,
+                        vk::ShaderStageFlags::VERTEX,
+                        0,
+                        bytes_of(&model_light_view_projection),
+                    );
+                }
+                draw.record(device, cmd_buffer);
+            }
+
+            unsafe { device.cmd_end_render_pass(cmd_buffer) };
+        }
+    }
+
+    fn pipeline(
+        &mut self,
+        vertex_layout: PositionLayout,
+        device: &ash::Device,
+    ) -> Result<vk::Pipeline, PipelineBuildError> {
+        if let Some(pipeline) = self.pipelines.get(&vertex_layout) {
+            return Ok(*pipeline);
+        }
+
+        let binding = vertex_layout.binding_description();
+        let attribute = vertex_layout.attribute_description();
+        let shader_module_entry_point = std::ffi::CString::new("main").unwrap();
+        let shader = self.shader_ref.lock();
+
+        let pipeline = PipelineBuilder {
+            shader_stages: vec![
+                vk::PipelineShaderStageCreateInfo::default()
+                    .stage(vk::ShaderStageFlags::VERTEX)
+                    .module(shader.vertex_module)
+                    .name(&shader_module_entry_point),
+                vk::PipelineShaderStageCreateInfo::default()
+                    .stage(vk::ShaderStageFlags::FRAGMENT)
+                    .module(shader.fragment_module)
+                    .name(&shader_module_entry_point),
+            ],
+            vertex_input_state_info: vk::PipelineVertexInputStateCreateInfo::default()
+                .vertex_binding_descriptions(std::slice::from_ref(&binding))
+                .vertex_attribute_descriptions(std::slice::from_ref(&attribute)),
+            input_assembly_state_info: vk::PipelineInputAssemblyStateCreateInfo::default()
+                .topology(vk::PrimitiveTopology::TRIANGLE_LIST),
+            // Back faces are kept, so that open or single sided meshes still cast a shadow
+            rasterizer_state_info: vk::PipelineRasterizationStateCreateInfo::default()
+                .polygon_mode(vk::PolygonMode::FILL)
+                .cull_mode(vk::CullModeFlags::NONE)
+                .depth_bias_enable(true)
+                .depth_bias_constant_factor(DEPTH_BIAS_CONSTANT_FACTOR)
+                .depth_bias_slope_factor(DEPTH_BIAS_SLOPE_FACTOR)
+                .line_width(1.0),
+            multisampling_state_info: vk::PipelineMultisampleStateCreateInfo::default()
+                .rasterization_samples(vk::SampleCountFlags::TYPE_1)
+                .min_sample_shading(1.0),
+            depth_stencil_state_info: vk::PipelineDepthStencilStateCreateInfo::default()
+                .depth_test_enable(true)
+                .depth_write_enable(true)
+                .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL)
+                .min_depth_bounds(0.0)
+                .max_depth_bounds(1.0),
+            color_blend_attachment_state: vk::PipelineColorBlendAttachmentState::default(),
+            color_attachment_count: 0,
+            subpass: 0,
+            layout: self.pipeline_layout,
+            cache: None,
+        }
+        .build(device, self.render_pass)?;
+        self.pipelines.insert(vertex_layout, pipeline);
+
+        Ok(pipeline)
+    }
+
+    /// Destroys the shadow map, its pipelines and shader, and the cascades' buffer.
+    pub fn destroy(&mut self, renderer: &mut Renderer) {
+        unsafe {
+            for framebuffer in self.framebuffers.drain(..) {
+                renderer.device.destroy_framebuffer(framebuffer, None);
+            }
+            for layer_view in self.layer_views.drain(..) {
+                renderer.device.destroy_image_view(layer_view, None);
+            }
+            for (_, pipeline) in self.pipelines.drain() {
+                renderer.device.destroy_pipeline(pipeline, None);
+            }
+            renderer
+                .device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+            renderer.device.destroy_render_pass(self.render_pass, None);
+        }
+        self.shader_ref.lock().destroy(&renderer.device);
+        self.texture_ref.lock().destroy(renderer);
+        self.cascades.destroy(renderer);
+    }
+}
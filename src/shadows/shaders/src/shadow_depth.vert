@@ -0,0 +1,12 @@
+#version 450
+
+layout(location = 0) in vec3 v_Position;
+
+layout(push_constant) uniform CasterData {
+    mat4 modelLightViewProjection;
+}
+pc_CasterData;
+
+void main() {
+    gl_Position = pc_CasterData.modelLightViewProjection * vec4(v_Position, 1);
+}
@@ -0,0 +1,140 @@
+use gpu_allocator::vulkan::Allocator;
+
+use std::{
+    ops::{Deref, DerefMut},
+    sync::Mutex,
+};
+
+use crate::allocated_types::{AllocatedBuffer, AllocatedBufferBuilder, BufferBuildError};
+
+/// Size of the smallest staging buffer, so that small uploads (e.g. uniform data) share buffers.
+const MIN_STAGING_BUFFER_SIZE: u64 = 64 * 1024;
+/// Total size of the unused staging buffers kept alive, beyond which the largest ones are freed.
+const MAX_POOLED_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Counters of the staging buffers handed out for uploads (see
+/// [`crate::renderer::Renderer::staging_pool_stats`]).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StagingPoolStats {
+    /// Number of staging buffers allocated since the renderer was built.
+    pub allocation_count: u64,
+    /// Number of uploads that went through the pool, which is the number of staging buffers that
+    /// would have been allocated without it.
+    pub lease_count: u64,
+    /// Number of unused staging buffers currently kept alive.
+    pub pooled_buffer_count: usize,
+}
+
+/// Host visible staging buffers reused by the uploads, instead of allocating and freeing one for
+/// each of them. Buffers are sized to powers of two, an upload leasing the smallest unused buffer
+/// large enough for its data.
+#[derive(Debug, Default)]
+pub(crate) struct StagingPool {
+    free_buffers: Vec<AllocatedBuffer>,
+    stats: StagingPoolStats,
+}
+
+/// Staging buffer leased from a [`StagingPool`], returned to it when dropped. The upload using it
+/// must have completed by then, which immediate commands ensure.
+pub(crate) struct StagingLease<'a> {
+    pool: &'a Mutex<StagingPool>,
+    buffer: Option<AllocatedBuffer>,
+}
+
+impl StagingPool {
+    /// Leases a staging buffer of at least `size` bytes, allocating it if none is available.
+    pub(crate) fn lease<'a>(
+        pool: &'a Mutex<Self>,
+        size: u64,
+        device: &ash::Device,
+        allocator: &mut Allocator,
+    ) -> Result<StagingLease<'a>, BufferBuildError> {
+        let mut this = pool.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        this.stats.lease_count += 1;
+
+        let best_fit = this
+            .free_buffers
+            .iter()
+            .enumerate()
+            .filter(|(_, buffer)| buffer.size() >= size)
+            .min_by_key(|(_, buffer)| buffer.size())
+            .map(|(index, _)| index);
+        let buffer = match best_fit {
+            Some(index) => this.free_buffers.swap_remove(index),
+            None => {
+                let buffer_size = size.next_power_of_two().max(MIN_STAGING_BUFFER_SIZE);
+                this.trim(
+                    MAX_POOLED_SIZE.saturating_sub(buffer_size),
+                    device,
+                    allocator,
+                );
+
+                let buffer = AllocatedBufferBuilder::staging_buffer_default(buffer_size)
+                    .with_name("Pooled staging buffer")
+                    .build_internal(device, allocator)?;
+                this.stats.allocation_count += 1;
+                buffer
+            }
+        };
+
+        Ok(StagingLease {
+            pool,
+            buffer: Some(buffer),
+        })
+    }
+
+    pub(crate) fn stats(&self) -> StagingPoolStats {
+        StagingPoolStats {
+            pooled_buffer_count: self.free_buffers.len(),
+            ..self.stats
+        }
+    }
+
+    /// Frees the largest unused buffers until the others total at most `max_size` bytes.
+    fn trim(&mut self, max_size: u64, device: &ash::Device, allocator: &mut Allocator) {
+        self.free_buffers
+            .sort_unstable_by_key(AllocatedBuffer::size);
+        let mut pooled_size = self
+            .free_buffers
+            .iter()
+            .map(AllocatedBuffer::size)
+            .sum::<u64>();
+        while pooled_size > max_size {
+            let Some(mut buffer) = self.free_buffers.pop() else {
+                break;
+            };
+            pooled_size -= buffer.size();
+            buffer.destroy(device, allocator);
+        }
+    }
+
+    pub(crate) fn destroy(&mut self, device: &ash::Device, allocator: &mut Allocator) {
+        self.trim(0, device, allocator);
+    }
+}
+
+impl Deref for StagingLease<'_> {
+    type Target = AllocatedBuffer;
+
+    fn deref(&self) -> &Self::Target {
+        self.buffer.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for StagingLease<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.buffer.as_mut().unwrap()
+    }
+}
+
+impl Drop for StagingLease<'_> {
+    fn drop(&mut self) {
+        if let Some(buffer) = self.buffer.take() {
+            self.pool
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .free_buffers
+                .push(buffer);
+        }
+    }
+}
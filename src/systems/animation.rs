@@ -0,0 +1,72 @@
+use crate::{
+    animation::AnimationTarget,
+    components::{
+        animation_player::AnimationPlayer,
+        skeleton::{JointPose, Skeleton},
+        transform::Transform,
+    },
+    frame_stats::FrameStats,
+};
+
+use bevy_ecs::{
+    change_detection::DetectChanges,
+    system::{Query, Res},
+};
+
+/// Advances the [`AnimationPlayer`]s that are playing by the frame time, and writes the pose of
+/// their clip to their entity's [`Transform`] and [`Skeleton`] joints. Paused players are only
+/// applied when modified (e.g. by [`AnimationPlayer::set_time`]). This should run before
+/// [`crate::systems::skinning::upload_joint_matrices`] and
+/// [`crate::systems::transform::propagate_transforms`].
+#[profiling::function]
+pub fn advance_players(
+    frame_stats: Res<FrameStats>,
+    mut players: Query<(
+        &mut AnimationPlayer,
+        Option<&mut Transform>,
+        Option<&mut Skeleton>,
+    )>,
+) {
+    let delta_time = frame_stats.frame_time.as_secs_f32();
+
+    for (mut player, mut transform, mut skeleton) in &mut players {
+        if player.is_playing() {
+            player.advance(delta_time);
+        }
+        if !player.is_changed() {
+            continue;
+        }
+        let Some(clip_ref) = player.clip() else {
+            continue;
+        };
+
+        let mut root_pose = transform.as_ref().map(|transform| JointPose {
+            translation: *transform.translation(),
+            rotation: *transform.rotation(),
+            scale: *transform.scale(),
+        });
+        for track in clip_ref.lock().tracks() {
+            let pose = match track.target {
+                AnimationTarget::Root => root_pose.as_mut(),
+                AnimationTarget::Joint(index) => skeleton
+                    .as_mut()
+                    .and_then(|skeleton| skeleton.joints.get_mut(index))
+                    .map(|joint| &mut joint.pose),
+            };
+            if let Some(pose) = pose {
+                track.apply(player.time(), pose);
+            }
+        }
+
+        if let (Some(transform), Some(root_pose)) = (transform.as_mut(), root_pose) {
+            if *transform.translation() != root_pose.translation
+                || *transform.rotation() != root_pose.rotation
+                || *transform.scale() != root_pose.scale
+            {
+                transform.set_translation(&root_pose.translation);
+                transform.set_rotation(&root_pose.rotation);
+                transform.set_scale(&root_pose.scale);
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use crate::{
+    components::{
+        debug_visualization::{ShowBounds, ShowNormals},
+        mesh_rendering::MeshRendering,
+        transform::{GlobalTransform, Transform},
+    },
+    debug_draw,
+    material::Vertex,
+    utils::ThreadSafeRef,
+};
+
+use bevy_ecs::prelude::{Or, Query, With};
+
+type DebugVisualizationData<'a, VertexType> = (
+    &'a Transform,
+    Option<&'a GlobalTransform>,
+    &'a ThreadSafeRef<MeshRendering<VertexType>>,
+    Option<&'a ShowBounds>,
+    Option<&'a ShowNormals>,
+);
+type DebugVisualizationFilter = Or<(With<ShowBounds>, With<ShowNormals>)>;
+
+/// Adds the debug lines of the entities with a [`ShowBounds`] or [`ShowNormals`] component to the
+/// frame's [`debug_draw`] primitives. Like `render_meshes`, this system must be registered for
+/// every vertex type used by the entities to visualize. Hidden meshes are skipped.
+///
+/// The bounds and normals are recomputed from the mesh's vertices every frame, so this is only
+/// meant for a handful of entities at a time (e.g. the ones selected in an editor).
+#[profiling::function]
+pub fn draw_debug_visualizations<VertexType>(
+    query: Query<DebugVisualizationData<VertexType>, DebugVisualizationFilter>,
+) where
+    VertexType: Vertex,
+{
+    for (transform, global_transform, mesh_rendering_ref, show_bounds, show_normals) in &query {
+        let mesh_rendering = mesh_rendering_ref.lock();
+        if !mesh_rendering.visible {
+            continue;
+        }
+        let mesh = mesh_rendering.mesh_ref.lock();
+        let model_matrix = GlobalTransform::resolve(transform, global_transform);
+
+        if let Some(show_bounds) = show_bounds {
+            if let Some((min, max)) = mesh.bounds() {
+                debug_draw::transformed_aabb(&model_matrix, min, max, show_bounds.color);
+            }
+        }
+
+        if let Some(show_normals) = show_normals {
+            // Normals are transformed by the inverse transpose to stay orthogonal to the surface
+            // under non uniform scales
+            let normal_matrix = model_matrix.inverse().transpose();
+            for vertex in &mesh.vertices {
+                let Some(normal) = vertex.normal() else {
+                    break;
+                };
+                let start = model_matrix.transform_point3(vertex.position());
+                let direction = normal_matrix.transform_vector3(normal).normalize_or_zero();
+                debug_draw::line(
+                    start,
+                    start + direction * show_normals.length,
+                    show_normals.color,
+                );
+            }
+        }
+    }
+}
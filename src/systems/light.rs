@@ -0,0 +1,51 @@
+use crate::{
+    components::{
+        light::{DirectionalLight, GpuLight, PointLight, SpotLight, MAX_LIGHTS},
+        transform::{GlobalTransform, Transform},
+    },
+    renderer::Renderer,
+    utils::ThreadSafeRef,
+};
+
+use bevy_ecs::system::{Query, Res};
+
+/// Uploads the light components of every entity to the lights storage buffer (see
+/// [`Renderer::upload_lights`]), positioned and oriented by the entity's transform. Directional
+/// lights come first, then point lights and spot lights: only the first
+/// [`MAX_LIGHTS`] are kept, the others are reported.
+#[profiling::function]
+pub fn gather_lights(
+    directional_lights: Query<(&DirectionalLight, &Transform, Option<&GlobalTransform>)>,
+    point_lights: Query<(&PointLight, &Transform, Option<&GlobalTransform>)>,
+    spot_lights: Query<(&SpotLight, &Transform, Option<&GlobalTransform>)>,
+    renderer_ref: Res<ThreadSafeRef<Renderer>>,
+) {
+    let lights = directional_lights
+        .iter()
+        .map(|(light, transform, global_transform)| {
+            GpuLight::directional(light, GlobalTransform::resolve(transform, global_transform))
+        })
+        .chain(
+            point_lights
+                .iter()
+                .map(|(light, transform, global_transform)| {
+                    GpuLight::point(light, GlobalTransform::resolve(transform, global_transform))
+                }),
+        )
+        .chain(
+            spot_lights
+                .iter()
+                .map(|(light, transform, global_transform)| {
+                    GpuLight::spot(light, GlobalTransform::resolve(transform, global_transform))
+                }),
+        )
+        .collect::<Vec<_>>();
+    if lights.len() > MAX_LIGHTS {
+        log::warn!(
+            "{} lights are in the scene, only the first {MAX_LIGHTS} are used",
+            lights.len()
+        );
+    }
+
+    renderer_ref.lock().upload_lights(&lights);
+}
@@ -1,221 +1,584 @@
-use std::time::Instant;
-
 use crate::{
     components::{
-        camera::Camera, mesh_rendering::MeshRendering, resource_wrapper::ResourceWrapper,
-        transform::Transform,
+        camera::Camera,
+        mesh_rendering::MeshRendering,
+        transform::{GlobalTransform, Transform},
+        viewport::{CameraViews, Viewport},
     },
+    descriptor_resources::MODEL_MATRIX_BINDING,
+    gpu_culling::GpuCulling,
+    indirect_batch::IndirectBatch,
     material::{Material, Vertex},
-    math_types::{Mat4, Vec4},
+    math_types::{Mat4, Vec3, Vec4},
     renderer::Renderer,
     utils::ThreadSafeRef,
 };
 
 use ash::vk;
-use bevy_ecs::{prelude::Query, system::Res};
+use bevy_ecs::{
+    entity::Entity,
+    prelude::{Query, Without},
+    system::Res,
+};
 use bytemuck::{bytes_of, Pod, Zeroable};
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
-struct CameraData {
+pub(crate) struct CameraData {
     pub(crate) view_projection: Mat4,
     pub(crate) world_position: Vec4,
 }
 unsafe impl Zeroable for CameraData {}
 unsafe impl Pod for CameraData {}
 
+type MeshRenderingData<'a, VertexType> = (
+    &'a Transform,
+    Option<&'a GlobalTransform>,
+    &'a ThreadSafeRef<MeshRendering<VertexType>>,
+);
+type CulledMeshRenderingData<'a, VertexType> = (
+    &'a Transform,
+    Option<&'a GlobalTransform>,
+    &'a ThreadSafeRef<MeshRendering<VertexType>>,
+    &'a ThreadSafeRef<GpuCulling>,
+);
+type IndirectMeshRenderingData<'a, VertexType> = (
+    &'a Transform,
+    Option<&'a GlobalTransform>,
+    &'a ThreadSafeRef<MeshRendering<VertexType>>,
+    &'a ThreadSafeRef<IndirectBatch>,
+);
+/// Excludes the mesh renderings drawn by `render_culled_meshes` and `render_indirect`.
+type DirectDrawFilter = (
+    Without<ThreadSafeRef<GpuCulling>>,
+    Without<ThreadSafeRef<IndirectBatch>>,
+);
+type MeshRenderingItem<'a, VertexType> = (
+    Mat4,
+    &'a ThreadSafeRef<MeshRendering<VertexType>>,
+    DrawSource<'a>,
+);
+
+/// Where the draws of a mesh rendering come from.
+#[derive(Clone, Copy)]
+enum DrawSource<'a> {
+    /// Drawn by `render_meshes`, the entity is drawn to the entity ID pass (see
+    /// [`crate::renderer::RendererBuilder::with_entity_picking`]).
+    Mesh(Entity),
+    GpuCulling(&'a ThreadSafeRef<GpuCulling>),
+    IndirectBatch(&'a ThreadSafeRef<IndirectBatch>),
+}
+
+/// Pipelines the mesh renderings are drawn with.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum MeshPass {
+    Color,
+    /// Only the materials with a depth pipeline are drawn, see [`depth_prepass`].
+    DepthPrepass,
+}
+
+/// Tests the world space bounds (see [`crate::mesh::Mesh::aabb`]) of every mesh rendering
+/// against the frustum of the camera, or of the [`CameraViews`] if there are any, and flags the
+/// ones that are outside of all of them as culled so that the render systems skip them. This
+/// should run before `render_meshes`, and like it must be registered for every vertex type.
+///
+/// Meshes without bounds (no vertices) are never culled.
+#[profiling::function]
+pub fn cull_meshes<VertexType>(
+    query: Query<MeshRenderingData<VertexType>>,
+    camera: Res<Camera>,
+    camera_views: Option<Res<CameraViews>>,
+) where
+    VertexType: Vertex,
+{
+    let frustums = match camera_views.as_deref() {
+        Some(camera_views) if !camera_views.views.is_empty() => camera_views
+            .views
+            .iter()
+            .map(|(camera, _)| camera.frustum_planes())
+            .collect(),
+        _ => vec![camera.frustum_planes()],
+    };
+
+    for (transform, global_transform, mesh_rendering_ref) in &query {
+        let mut mesh_rendering = mesh_rendering_ref.lock();
+        let Some(aabb) = mesh_rendering.mesh_ref.lock().aabb else {
+            mesh_rendering.culled = false;
+            continue;
+        };
+
+        let world_aabb = aabb.transformed(&GlobalTransform::resolve(transform, global_transform));
+        mesh_rendering.culled = !frustums
+            .iter()
+            .any(|frustum| world_aabb.intersects_frustum(frustum));
+    }
+}
+
 #[profiling::function]
 pub fn render_meshes<VertexType>(
-    query: Query<(&Transform, &ThreadSafeRef<MeshRendering<VertexType>>)>,
-    timer: Res<ResourceWrapper<Instant>>,
+    query: Query<(Entity, MeshRenderingData<VertexType>), DirectDrawFilter>,
     camera: Res<Camera>,
+    camera_views: Option<Res<CameraViews>>,
     renderer_ref: Res<ThreadSafeRef<Renderer>>,
 ) where
     VertexType: Vertex,
 {
-    let timer = timer.data;
-    let mut renderer = renderer_ref.lock();
+    record_mesh_renderings(
+        query
+            .iter()
+            .map(
+                |(entity, (transform, global_transform, mesh_rendering_ref))| {
+                    (
+                        GlobalTransform::resolve(transform, global_transform),
+                        mesh_rendering_ref,
+                        DrawSource::Mesh(entity),
+                    )
+                },
+            )
+            .collect(),
+        &camera,
+        camera_views.as_deref(),
+        &renderer_ref,
+        MeshPass::Color,
+    );
+}
+
+/// Draws the same meshes as `render_meshes`, the opaque ones first, then the transparent ones
+/// (see [`Material::is_transparent`]) from the farthest to the closest to the camera, so that
+/// they are blended over what is behind them. A mesh rendering is transparent if any of its
+/// materials is, and its distance is the one of the center of its world space bounds.
+///
+/// Note that [`crate::material::BlendMode::AlphaBlend`] is the default blend mode: materials of
+/// opaque objects should be built with [`crate::material::BlendMode::Opaque`] to be drawn first.
+#[profiling::function]
+pub fn render_meshes_sorted<VertexType>(
+    query: Query<(Entity, MeshRenderingData<VertexType>), DirectDrawFilter>,
+    camera: Res<Camera>,
+    camera_views: Option<Res<CameraViews>>,
+    renderer_ref: Res<ThreadSafeRef<Renderer>>,
+) where
+    VertexType: Vertex,
+{
+    let mut opaque_mesh_renderings = vec![];
+    let mut transparent_mesh_renderings = vec![];
+    for (entity, (transform, global_transform, mesh_rendering_ref)) in &query {
+        let model = GlobalTransform::resolve(transform, global_transform);
+        let item = (model, mesh_rendering_ref, DrawSource::Mesh(entity));
+
+        let mesh_rendering = mesh_rendering_ref.lock();
+        let is_transparent = mesh_rendering.material_ref.lock().is_transparent()
+            || mesh_rendering
+                .submeshes()
+                .iter()
+                .any(|submesh| submesh.material_ref.lock().is_transparent());
+        if !is_transparent {
+            opaque_mesh_renderings.push(item);
+            continue;
+        }
+
+        let center = mesh_rendering
+            .mesh_ref
+            .lock()
+            .aabb
+            .map_or(Vec3::ZERO, |aabb| aabb.center());
+        let distance = model
+            .transform_point3(center)
+            .distance_squared(*camera.position());
+        transparent_mesh_renderings.push((distance, item));
+    }
+    transparent_mesh_renderings
+        .sort_unstable_by(|(distance, _), (other_distance, _)| other_distance.total_cmp(distance));
 
-    let current_time = timer.elapsed().as_secs_f32();
-    let time_data = Vec4::new(
-        current_time / 20.0,
-        current_time,
-        current_time * 2.0,
-        current_time * 3.0,
+    opaque_mesh_renderings.extend(
+        transparent_mesh_renderings
+            .into_iter()
+            .map(|(_, item)| item),
     );
+    record_mesh_renderings(
+        opaque_mesh_renderings,
+        &camera,
+        camera_views.as_deref(),
+        &renderer_ref,
+        MeshPass::Color,
+    );
+}
 
-    let time_buffer = renderer.descriptors[0].buffer.as_mut().unwrap();
+/// Draws the depth of the meshes drawn by `render_meshes` whose materials were built with
+/// [`crate::material::MaterialBuilder::depth_prepass`], which must run right before
+/// `render_meshes` in the same render pass. Their color pipelines then only shade the visible
+/// fragments.
+///
+/// This is opt-in per state, by registering this system: only opaque materials should be built
+/// with a depth pipeline, as transparent ones must not hide what is drawn behind them.
+#[profiling::function]
+pub fn depth_prepass<VertexType>(
+    query: Query<(Entity, MeshRenderingData<VertexType>), DirectDrawFilter>,
+    camera: Res<Camera>,
+    camera_views: Option<Res<CameraViews>>,
+    renderer_ref: Res<ThreadSafeRef<Renderer>>,
+) where
+    VertexType: Vertex,
+{
+    record_mesh_renderings(
+        query
+            .iter()
+            .map(
+                |(entity, (transform, global_transform, mesh_rendering_ref))| {
+                    (
+                        GlobalTransform::resolve(transform, global_transform),
+                        mesh_rendering_ref,
+                        DrawSource::Mesh(entity),
+                    )
+                },
+            )
+            .collect(),
+        &camera,
+        camera_views.as_deref(),
+        &renderer_ref,
+        MeshPass::DepthPrepass,
+    );
+}
 
-    let raw_time_data = bytes_of(&time_data);
-    time_buffer
-        .allocation
-        .as_mut()
-        .expect("Free after use")
-        .mapped_slice_mut()
-        .expect("Memory should be mappable")[..raw_time_data.len()]
-        .copy_from_slice(raw_time_data);
+/// Draws the meshes whose instances were culled on the GPU, using the draw list produced by the
+/// entity's [`GpuCulling`] (see [`GpuCulling::cull`]). The depth of the materials built with
+/// [`crate::material::MaterialBuilder::depth_prepass`] is drawn first, as `depth_prepass` doesn't
+/// draw these meshes.
+#[profiling::function]
+pub fn render_culled_meshes<VertexType>(
+    query: Query<CulledMeshRenderingData<VertexType>>,
+    camera: Res<Camera>,
+    camera_views: Option<Res<CameraViews>>,
+    renderer_ref: Res<ThreadSafeRef<Renderer>>,
+) where
+    VertexType: Vertex,
+{
+    record_with_depth_prepass(
+        query
+            .iter()
+            .map(
+                |(transform, global_transform, mesh_rendering_ref, culling_ref)| {
+                    (
+                        GlobalTransform::resolve(transform, global_transform),
+                        mesh_rendering_ref,
+                        DrawSource::GpuCulling(culling_ref),
+                    )
+                },
+            )
+            .collect(),
+        &camera,
+        camera_views.as_deref(),
+        &renderer_ref,
+    );
+}
 
-    let mut last_material: Option<ThreadSafeRef<Material<VertexType>>> = None;
-    let mut last_material_pipeline: Option<vk::Pipeline> = None;
-    let device = renderer.device.clone();
-    let cmd_buffer = renderer.primary_command_buffer;
-    for (transform, mesh_rendering_ref) in query.iter() {
-        let mut mesh_rendering = mesh_rendering_ref.lock();
+/// Draws the meshes batched by the entity's [`IndirectBatch`], with a single indirect draw call
+/// per batch (see [`IndirectBatch`]). The mesh rendering's submeshes are ignored, its visibility
+/// and culling apply to the whole batch. Like in `render_culled_meshes`, the depth of the
+/// materials built with a depth prepass is drawn first.
+#[profiling::function]
+pub fn render_indirect<VertexType>(
+    query: Query<IndirectMeshRenderingData<VertexType>, Without<ThreadSafeRef<GpuCulling>>>,
+    camera: Res<Camera>,
+    camera_views: Option<Res<CameraViews>>,
+    renderer_ref: Res<ThreadSafeRef<Renderer>>,
+) where
+    VertexType: Vertex,
+{
+    record_with_depth_prepass(
+        query
+            .iter()
+            .map(
+                |(transform, global_transform, mesh_rendering_ref, batch_ref)| {
+                    (
+                        GlobalTransform::resolve(transform, global_transform),
+                        mesh_rendering_ref,
+                        DrawSource::IndirectBatch(batch_ref),
+                    )
+                },
+            )
+            .collect(),
+        &camera,
+        camera_views.as_deref(),
+        &renderer_ref,
+    );
+}
 
-        if !mesh_rendering.visible {
-            continue;
-        };
+/// Records the depth prepass of the mesh renderings right before their color pass, for the draw
+/// sources `depth_prepass` doesn't cover: the color pipelines of materials built with a depth
+/// pipeline test the depth with `EQUAL`, and would draw nothing without it.
+fn record_with_depth_prepass<'a, VertexType>(
+    mesh_renderings: Vec<MeshRenderingItem<'a, VertexType>>,
+    camera: &Camera,
+    camera_views: Option<&CameraViews>,
+    renderer_ref: &ThreadSafeRef<Renderer>,
+) where
+    VertexType: Vertex,
+{
+    let has_depth_pipeline = mesh_renderings.iter().any(|(_, mesh_rendering_ref, _)| {
+        let mesh_rendering = mesh_rendering_ref.lock();
+        mesh_rendering.material_ref.lock().depth_pipeline.is_some()
+            || mesh_rendering
+                .submeshes()
+                .iter()
+                .any(|submesh| submesh.material_ref.lock().depth_pipeline.is_some())
+    });
+    if has_depth_pipeline {
+        record_mesh_renderings(
+            mesh_renderings.clone(),
+            camera,
+            camera_views,
+            renderer_ref,
+            MeshPass::DepthPrepass,
+        );
+    }
 
-        if mesh_rendering
-            .update_uniform_pod(0, transform.matrix())
-            .is_err()
-        {
-            log::warn!("Failed to upload model data to slot 0");
-        }
+    record_mesh_renderings(
+        mesh_renderings,
+        camera,
+        camera_views,
+        renderer_ref,
+        MeshPass::Color,
+    );
+}
 
-        let material = mesh_rendering.material_ref.lock();
-        let mesh = mesh_rendering.mesh_ref.lock();
-
-        if last_material.is_none() {
-            // first draw, need to bind the descriptor set (common for all materials)
-            unsafe {
-                device.cmd_bind_descriptor_sets(
-                    cmd_buffer,
-                    vk::PipelineBindPoint::GRAPHICS,
-                    material.layout,
-                    0,
-                    &[
-                        renderer.descriptors[0].handle,
-                        renderer.descriptors[1].handle,
-                    ],
-                    &[],
-                )
-            };
+fn record_mesh_renderings<'a, VertexType>(
+    mesh_renderings: Vec<MeshRenderingItem<'a, VertexType>>,
+    camera: &Camera,
+    camera_views: Option<&CameraViews>,
+    renderer_ref: &ThreadSafeRef<Renderer>,
+    pass: MeshPass,
+) where
+    VertexType: Vertex,
+{
+    let mut renderer = renderer_ref.lock();
+    renderer.begin_gpu_zone(match pass {
+        MeshPass::Color => "Mesh rendering",
+        MeshPass::DepthPrepass => "Depth prepass",
+    });
+
+    let views = match camera_views {
+        Some(camera_views) if !camera_views.views.is_empty() => camera_views.views.clone(),
+        _ => vec![(*camera, Viewport::default())],
+    };
+
+    // Layout transitions aren't allowed in the render pass, so they are all requested before
+    // drawing, and batched by the renderer in a single barrier executed before the frame
+    for (_, mesh_rendering_ref, draw_source) in &mesh_renderings {
+        let mesh_rendering = mesh_rendering_ref.lock();
+        if !mesh_rendering.visible || mesh_rendering.culled {
+            continue;
         }
-        if last_material_pipeline != Some(material.pipeline) {
-            material
-                .descriptor_resources
-                .prepare_image_layouts_for_render(&mut renderer)
-                .expect("Failed to prepare images for draw");
-
-            // This one small trick allows us to keep vertex data sane
-            // (Actual engineers hate him)
-            // This is also why we had to bump to requesting 1.1.0 lmao
-            // https://www.saschawillems.de/blog/2019/03/29/flipping-the-vulkan-viewport/
-            let y: f32 = u16::try_from(renderer.framebuffer_height)
-                .expect("Invalid width")
-                .into();
-
-            let viewport = vk::Viewport::default()
-                .x(0.0)
-                .y(y)
-                .width(
-                    u16::try_from(renderer.framebuffer_width)
-                        .expect("Invalid width")
-                        .into(),
-                )
-                .height(-y)
-                .min_depth(0.0)
-                .max_depth(1.0);
-            let scissor = vk::Rect2D::default()
-                .offset(vk::Offset2D::default())
-                .extent(vk::Extent2D {
-                    width: renderer.framebuffer_width,
-                    height: renderer.framebuffer_height,
-                });
-            unsafe {
-                device.cmd_bind_pipeline(
-                    cmd_buffer,
-                    vk::PipelineBindPoint::GRAPHICS,
-                    material.pipeline,
-                );
-                device.cmd_set_viewport(cmd_buffer, 0, std::slice::from_ref(&viewport));
-                device.cmd_set_scissor(cmd_buffer, 0, std::slice::from_ref(&scissor));
-                device.cmd_bind_descriptor_sets(
-                    cmd_buffer,
-                    vk::PipelineBindPoint::GRAPHICS,
-                    material.layout,
-                    2,
-                    std::slice::from_ref(&material.descriptor_set),
-                    &[],
-                );
-            };
 
-            last_material_pipeline = Some(material.pipeline);
-            if let Some(last_material) = last_material {
-                last_material
-                    .lock()
-                    .descriptor_resources
-                    .restore_image_layouts(&mut renderer)
-                    .expect("Failed to restore image layouts");
+        // Host writes, which are visible to the frame's submission
+        if let DrawSource::IndirectBatch(batch_ref) = draw_source {
+            if let Err(error) = batch_ref.lock().prepare(&mut renderer) {
+                log::warn!("Failed to upload indirect draw commands: {error}");
             }
-            last_material = Some(mesh_rendering.material_ref.clone());
         }
 
+        mesh_rendering
+            .descriptor_resources
+            .request_layouts_for_render(&mut renderer);
+        mesh_rendering
+            .material_ref
+            .lock()
+            .descriptor_resources
+            .request_layouts_for_render(&mut renderer);
+        for submesh in mesh_rendering.submeshes() {
+            submesh
+                .material_ref
+                .lock()
+                .descriptor_resources
+                .request_layouts_for_render(&mut renderer);
+        }
+    }
+
+    let mut last_material: Option<ThreadSafeRef<Material<VertexType>>> = None;
+    let mut last_material_pipeline: Option<vk::Pipeline> = None;
+    let device = renderer.device.clone();
+    let cmd_buffer = renderer.primary_command_buffer;
+    let multi_draw_indirect = renderer.capabilities().features.multi_draw_indirect;
+    for (view_index, (camera, viewport)) in views.iter().enumerate() {
+        let (viewport, scissor) =
+            viewport.to_vk(renderer.render_region(), renderer.is_viewport_flipped());
+        let mut viewport_dirty = true;
+
         let camera_data = CameraData {
             view_projection: *camera.view_projection(),
             world_position: (*camera.position(), 1.0).into(),
         };
 
-        unsafe {
-            device.cmd_push_constants(
-                cmd_buffer,
-                material.layout,
-                vk::ShaderStageFlags::VERTEX,
-                0,
-                bytes_of(&camera_data),
-            );
-
-            device.cmd_bind_descriptor_sets(
-                cmd_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                material.layout,
-                3,
-                std::slice::from_ref(&mesh_rendering.descriptor_set),
-                &[],
-            );
-
-            device.cmd_bind_vertex_buffers(
-                cmd_buffer,
-                0,
-                std::slice::from_ref(&mesh.vertex_buffer.handle),
-                &[0],
-            );
-            match mesh.index_buffer.as_ref() {
-                Some(index_buffer) => {
-                    device.cmd_bind_index_buffer(
+        for (model, mesh_rendering_ref, draw_source) in &mesh_renderings {
+            let mut mesh_rendering = mesh_rendering_ref.lock();
+
+            if !mesh_rendering.visible || mesh_rendering.culled {
+                continue;
+            };
+
+            if view_index == 0 {
+                // Shaders declaring the model matrix always read it as a dynamic uniform
+                if mesh_rendering.is_uniform_dynamic(MODEL_MATRIX_BINDING)
+                    && mesh_rendering
+                        .set_dynamic_uniform_pod(MODEL_MATRIX_BINDING, *model)
+                        .is_err()
+                {
+                    log::warn!("Failed to upload model data to slot {MODEL_MATRIX_BINDING}");
+                }
+
+                // Written once per frame, every view reuses the same offsets
+                if let Err(error) = mesh_rendering.write_dynamic_uniforms(&mut renderer) {
+                    log::warn!("Failed to write dynamic uniforms: {error}");
+                }
+            }
+            if !mesh_rendering.has_dynamic_offsets() {
+                continue;
+            }
+
+            let mesh = mesh_rendering.mesh_ref.lock();
+
+            // Without submeshes, the whole mesh is drawn with the mesh rendering's material
+            let draws = if mesh_rendering.submeshes().is_empty()
+                || matches!(draw_source, DrawSource::IndirectBatch(_))
+            {
+                vec![(mesh_rendering.material_ref.clone(), None)]
+            } else {
+                mesh_rendering
+                    .submeshes()
+                    .iter()
+                    .map(|submesh| {
+                        (
+                            submesh.material_ref.clone(),
+                            Some(submesh.index_range.clone()),
+                        )
+                    })
+                    .collect()
+            };
+
+            for (material_ref, index_range) in draws {
+                let material = material_ref.lock();
+                let pipeline = match (pass, material.depth_pipeline) {
+                    (MeshPass::Color, _) => material.pipeline,
+                    (MeshPass::DepthPrepass, Some(depth_pipeline)) => depth_pipeline,
+                    (MeshPass::DepthPrepass, None) => continue,
+                };
+
+                if last_material.is_none() {
+                    // first draw, need to bind the descriptor set (common for all materials)
+                    unsafe {
+                        device.cmd_bind_descriptor_sets(
+                            cmd_buffer,
+                            vk::PipelineBindPoint::GRAPHICS,
+                            material.layout,
+                            0,
+                            &renderer.frame_descriptor_sets(),
+                            &[],
+                        )
+                    };
+                }
+                if last_material_pipeline != Some(pipeline) {
+                    unsafe {
+                        device.cmd_bind_pipeline(
+                            cmd_buffer,
+                            vk::PipelineBindPoint::GRAPHICS,
+                            pipeline,
+                        );
+                        device.cmd_bind_descriptor_sets(
+                            cmd_buffer,
+                            vk::PipelineBindPoint::GRAPHICS,
+                            material.layout,
+                            2,
+                            std::slice::from_ref(&material.descriptor_set),
+                            &[],
+                        );
+                        // Binding a material set with another layout disturbs the sets after it
+                        #[cfg(feature = "bindless")]
+                        if let Some(bindless_textures) = renderer.bindless_textures() {
+                            device.cmd_bind_descriptor_sets(
+                                cmd_buffer,
+                                vk::PipelineBindPoint::GRAPHICS,
+                                material.layout,
+                                crate::bindless::BINDLESS_SET,
+                                std::slice::from_ref(&bindless_textures.set),
+                                &[],
+                            );
+                        }
+                    };
+                    viewport_dirty = true;
+
+                    last_material_pipeline = Some(pipeline);
+                    last_material = Some(material_ref.clone());
+                }
+                if viewport_dirty {
+                    unsafe {
+                        device.cmd_set_viewport(cmd_buffer, 0, std::slice::from_ref(&viewport));
+                        device.cmd_set_scissor(cmd_buffer, 0, std::slice::from_ref(&scissor));
+                    }
+                    viewport_dirty = false;
+                }
+
+                unsafe {
+                    device.cmd_push_constants(
                         cmd_buffer,
-                        index_buffer.handle,
+                        material.layout,
+                        vk::ShaderStageFlags::VERTEX,
                         0,
-                        vk::IndexType::UINT32,
+                        bytes_of(&camera_data),
                     );
-                    device.cmd_draw_indexed(
+
+                    let texture_index_offset: u32 =
+                        std::mem::size_of::<CameraData>().try_into().unwrap();
+                    if material.push_constant_size
+                        >= texture_index_offset + std::mem::size_of::<u32>() as u32
+                    {
+                        device.cmd_push_constants(
+                            cmd_buffer,
+                            material.layout,
+                            material.push_constant_stages,
+                            texture_index_offset,
+                            bytes_of(&mesh_rendering.texture_index),
+                        );
+                    }
+
+                    device.cmd_bind_descriptor_sets(
                         cmd_buffer,
-                        mesh.indices
-                            .as_ref()
-                            .unwrap()
-                            .len()
-                            .try_into()
-                            .expect("Unsupported architecture"),
-                        1,
-                        0,
-                        0,
-                        0,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        material.layout,
+                        3,
+                        std::slice::from_ref(&mesh_rendering.descriptor_set),
+                        &mesh_rendering.dynamic_offsets,
                     );
                 }
-                None => {
-                    device.cmd_draw(
+
+                if let (DrawSource::Mesh(entity), MeshPass::Color) = (draw_source, pass) {
+                    if let Some(entity_ids) = renderer.entity_id_pass() {
+                        entity_ids.queue_draw(
+                            *entity,
+                            camera_data.view_projection * *model,
+                            (viewport, scissor),
+                            &mesh,
+                            index_range.clone(),
+                        );
+                    }
+                }
+
+                match (index_range, draw_source) {
+                    (Some(index_range), _) => {
+                        mesh.record_draw_range(index_range, &device, cmd_buffer)
+                    }
+                    (None, DrawSource::GpuCulling(culling_ref)) => {
+                        culling_ref.lock().record_draw(&mesh, &device, cmd_buffer)
+                    }
+                    (None, DrawSource::IndirectBatch(batch_ref)) => batch_ref.lock().record_draw(
+                        &mesh,
+                        multi_draw_indirect,
+                        &device,
                         cmd_buffer,
-                        mesh.vertices
-                            .len()
-                            .try_into()
-                            .expect("Unsupported architecture"),
-                        1,
-                        0,
-                        0,
-                    );
+                    ),
+                    (None, DrawSource::Mesh(_)) => mesh.record_draw(&device, cmd_buffer),
                 }
             }
         }
     }
+
+    renderer.end_gpu_zone();
 }
@@ -1 +1,8 @@
+pub mod animation;
+pub mod debug_visualization;
+pub mod light;
 pub mod mesh_renderer;
+pub mod shadows;
+pub mod skinning;
+pub mod skybox;
+pub mod transform;
@@ -0,0 +1,65 @@
+use crate::{
+    components::{
+        mesh_rendering::MeshRendering,
+        transform::{GlobalTransform, Transform},
+    },
+    material::Vertex,
+    renderer::Renderer,
+    shadows::{ShadowCaster, ShadowMap},
+    utils::ThreadSafeRef,
+};
+
+use bevy_ecs::{
+    prelude::{Query, With},
+    system::Res,
+};
+
+/// Queues the visible [`ShadowCaster`]s in the [`ShadowMap`], to be drawn by the next
+/// [`render_shadow_map`]. Like `render_meshes`, it must be registered for every vertex type of the
+/// casters.
+#[profiling::function]
+pub fn queue_shadow_casters<VertexType>(
+    query: Query<
+        (
+            &Transform,
+            Option<&GlobalTransform>,
+            &ThreadSafeRef<MeshRendering<VertexType>>,
+        ),
+        With<ShadowCaster>,
+    >,
+    shadow_map_ref: Res<ThreadSafeRef<ShadowMap>>,
+) where
+    VertexType: Vertex,
+{
+    let mut shadow_map = shadow_map_ref.lock();
+    for (transform, global_transform, mesh_rendering_ref) in &query {
+        let mesh_rendering = mesh_rendering_ref.lock();
+        if !mesh_rendering.visible {
+            continue;
+        }
+
+        shadow_map.queue_draw(
+            GlobalTransform::resolve(transform, global_transform),
+            &mesh_rendering.mesh_ref.lock(),
+        );
+    }
+}
+
+/// Renders the queued casters into every cascade of the [`ShadowMap`], from the matrices of its
+/// last [`crate::shadows::ShadowCascades::update`]. This must run after the `queue_shadow_casters`
+/// systems, and before the rendering systems sampling the shadow map.
+///
+/// The swapchain's render pass is interrupted during the shadow passes, see
+/// [`Renderer::begin_frame_to`].
+#[profiling::function]
+pub fn render_shadow_map(
+    shadow_map_ref: Res<ThreadSafeRef<ShadowMap>>,
+    renderer_ref: Res<ThreadSafeRef<Renderer>>,
+) {
+    let mut shadow_map = shadow_map_ref.lock();
+    let mut renderer = renderer_ref.lock();
+
+    renderer.begin_gpu_zone("Shadows");
+    renderer.record_outside_render_pass(|device, cmd_buffer| shadow_map.record(device, cmd_buffer));
+    renderer.end_gpu_zone();
+}
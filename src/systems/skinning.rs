@@ -0,0 +1,33 @@
+use crate::{
+    components::{mesh_rendering::MeshRendering, skeleton::Skeleton},
+    material::Vertex,
+    utils::ThreadSafeRef,
+};
+
+use bevy_ecs::{
+    query::{Changed, Or},
+    system::Query,
+};
+
+/// Writes the joint matrices of the skeletons whose pose changed (or that were just added) to the
+/// mesh renderings of their entity (see [`Skeleton::write_joint_matrices`]). This should run after
+/// the systems animating the skeletons, and before the rendering systems. Like `render_meshes`, it
+/// must be registered for every vertex type of skinned meshes.
+#[profiling::function]
+pub fn upload_joint_matrices<VertexType>(
+    skeletons: Query<
+        (&Skeleton, &ThreadSafeRef<MeshRendering<VertexType>>),
+        Or<(
+            Changed<Skeleton>,
+            Changed<ThreadSafeRef<MeshRendering<VertexType>>>,
+        )>,
+    >,
+) where
+    VertexType: Vertex,
+{
+    for (skeleton, mesh_rendering_ref) in &skeletons {
+        if let Err(error) = skeleton.write_joint_matrices(&mut mesh_rendering_ref.lock()) {
+            log::error!("Failed to write the joint matrices of a skeleton: {error}");
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use crate::{
+    components::{
+        camera::Camera,
+        skybox::Skybox,
+        viewport::{CameraViews, Viewport},
+    },
+    renderer::Renderer,
+    systems::mesh_renderer::CameraData,
+    utils::ThreadSafeRef,
+};
+
+use ash::vk;
+use bevy_ecs::{prelude::Query, system::Res};
+use bytemuck::bytes_of;
+
+/// Draws the [`Skybox`]es around the camera, or around each of the [`CameraViews`] if there are
+/// any. The cube is moved to the position of the camera in the vertex shader, so the skybox
+/// entities don't need a transform.
+#[profiling::function]
+pub fn render_skybox(
+    query: Query<&Skybox>,
+    camera: Res<Camera>,
+    camera_views: Option<Res<CameraViews>>,
+    renderer_ref: Res<ThreadSafeRef<Renderer>>,
+) {
+    if query.is_empty() {
+        return;
+    }
+
+    let mut renderer = renderer_ref.lock();
+    renderer.begin_gpu_zone("Skybox");
+
+    for skybox in &query {
+        skybox
+            .material_ref
+            .lock()
+            .descriptor_resources
+            .request_layouts_for_render(&mut renderer);
+    }
+
+    let views = match camera_views.as_deref() {
+        Some(camera_views) if !camera_views.views.is_empty() => camera_views.views.clone(),
+        _ => vec![(*camera, Viewport::default())],
+    };
+
+    let device = renderer.device.clone();
+    let cmd_buffer = renderer.primary_command_buffer;
+    for skybox in &query {
+        let material = skybox.material_ref.lock();
+        let mesh = skybox.mesh_ref.lock();
+
+        unsafe {
+            device.cmd_bind_pipeline(
+                cmd_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                material.pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                cmd_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                material.layout,
+                2,
+                std::slice::from_ref(&material.descriptor_set),
+                &[],
+            );
+        }
+
+        for (camera, viewport) in &views {
+            let (viewport, scissor) =
+                viewport.to_vk(renderer.render_region(), renderer.is_viewport_flipped());
+            let camera_data = CameraData {
+                view_projection: *camera.view_projection(),
+                world_position: (*camera.position(), 1.0).into(),
+            };
+
+            unsafe {
+                device.cmd_set_viewport(cmd_buffer, 0, std::slice::from_ref(&viewport));
+                device.cmd_set_scissor(cmd_buffer, 0, std::slice::from_ref(&scissor));
+                device.cmd_push_constants(
+                    cmd_buffer,
+                    material.layout,
+                    vk::ShaderStageFlags::VERTEX,
+                    0,
+                    bytes_of(&camera_data),
+                );
+            }
+            mesh.record_draw(&device, cmd_buffer);
+        }
+    }
+
+    renderer.end_gpu_zone();
+}
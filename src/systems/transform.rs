@@ -0,0 +1,86 @@
+use crate::{
+    components::transform::{GlobalTransform, Parent, Transform},
+    math_types::Mat4,
+};
+
+use bevy_ecs::{
+    entity::Entity,
+    system::{Commands, Query},
+};
+
+use std::collections::HashMap;
+
+/// Computes the [`GlobalTransform`] of every entity with a [`Transform`], by combining it with the
+/// transforms of its [`Parent`] chain. Entities without a [`GlobalTransform`] get one inserted
+/// (at the end of the schedule, so rendering uses their local transform until the next frame).
+/// This should run before the rendering systems, e.g. with
+/// `propagate_transforms.before(render_meshes::<Vertex>)`.
+///
+/// A parent without a [`Transform`] is treated as the root of the hierarchy. Entities that are
+/// part of (or descend from) a cycle in the hierarchy are reported and their global transform is
+/// left as is.
+#[profiling::function]
+pub fn propagate_transforms(
+    transforms: Query<(Entity, &Transform, Option<&Parent>)>,
+    mut global_transforms: Query<&mut GlobalTransform>,
+    mut commands: Commands,
+) {
+    let hierarchy = transforms
+        .iter()
+        .map(|(entity, transform, parent)| {
+            (entity, (transform.matrix(), parent.map(|parent| parent.0)))
+        })
+        .collect::<HashMap<_, _>>();
+
+    // `None` marks the entities in or below a cycle
+    let mut world_matrices = HashMap::<Entity, Option<Mat4>>::with_capacity(hierarchy.len());
+    for &entity in hierarchy.keys() {
+        // Walks up the hierarchy until the root, or an ancestor whose matrix is already known
+        let mut chain = vec![];
+        let mut current = entity;
+        let mut parent_matrix = Some(Mat4::IDENTITY);
+        loop {
+            if let Some(world_matrix) = world_matrices.get(&current) {
+                parent_matrix = *world_matrix;
+                break;
+            }
+            if chain.contains(&current) {
+                log::error!(
+                    "The transform hierarchy of entity {current:?} contains a cycle, its global transform will not be updated"
+                );
+                parent_matrix = None;
+                break;
+            }
+            let Some((_, parent)) = hierarchy.get(&current) else {
+                break;
+            };
+
+            chain.push(current);
+            match parent {
+                Some(parent) => current = *parent,
+                None => break,
+            }
+        }
+
+        for ancestor in chain.into_iter().rev() {
+            let local_matrix = hierarchy[&ancestor].0;
+            parent_matrix = parent_matrix.map(|parent_matrix| parent_matrix * local_matrix);
+            world_matrices.insert(ancestor, parent_matrix);
+        }
+    }
+
+    for (entity, world_matrix) in world_matrices {
+        let Some(world_matrix) = world_matrix else {
+            continue;
+        };
+
+        match global_transforms.get_mut(entity) {
+            Ok(mut global_transform) => global_transform.0 = world_matrix,
+            Err(_) => {
+                commands
+                    .entity(entity)
+                    .insert(GlobalTransform(world_matrix));
+            }
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use ash::vk;
+use thiserror::Error;
+
+use std::path::Path;
+
+use crate::{
+    math_types::{Vec2, Vec3},
+    mesh::{upload_mesh_data, Aabb, Mesh, MeshDataUploadError},
+    renderer::Renderer,
+    texture::{Texture, TextureBuildError},
+    utils::ThreadSafeRef,
+    vertices::textured::TexturedVertex,
+};
+
+#[derive(Error, Debug)]
+pub enum BitmapFontBuildError {
+    #[error("Creation of the atlas texture failed with error: {0}.")]
+    TextureCreationFailed(#[from] TextureBuildError),
+
+    #[error("A {width}x{height} atlas can't be split in a grid of {columns}x{rows} glyphs.")]
+    InvalidGrid {
+        width: u32,
+        height: u32,
+        columns: u32,
+        rows: u32,
+    },
+}
+
+#[derive(Error, Debug)]
+pub enum TextMeshBuildError {
+    #[error("The text has no visible glyph to build a mesh from.")]
+    NoVisibleGlyph,
+
+    #[error("Upload of the text mesh failed with error: {0}.")]
+    UploadFailed(#[from] MeshDataUploadError),
+}
+
+/// Monospaced font whose glyphs are drawn in a grid of equally sized cells of an atlas texture, in
+/// character code order from left to right then top to bottom (e.g. the usual 16x16 grids of
+/// ASCII glyphs, starting at `'\0'`, or 16x6 ones starting at `' '`).
+///
+/// Unlike `crate::sdf_text::SdfFontAtlas`, no font file is rasterized: the text is made of
+/// [`TexturedVertex`] quads, drawn by any material sampling the atlas (with alpha blending, or
+/// discarding transparent texels), which makes it a cheap way of labelling entities in the world.
+pub struct BitmapFont {
+    pub texture_ref: ThreadSafeRef<Texture>,
+    columns: u32,
+    rows: u32,
+    first_character: char,
+    /// Width of a glyph divided by its height.
+    aspect_ratio: f32,
+}
+
+#[profiling::all_functions]
+impl BitmapFont {
+    /// Loads the atlas image file at `path` (see [`Texture::builder`]), sampled without filtering
+    /// to keep the glyphs' pixels sharp.
+    pub fn from_path(
+        path: &Path,
+        columns: u32,
+        rows: u32,
+        first_character: char,
+        renderer: &mut Renderer,
+    ) -> Result<Self, BitmapFontBuildError> {
+        let texture_ref = Texture::builder()
+            .with_filter(vk::Filter::NEAREST)
+            .with_address_mode(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .build_from_path(path, renderer)?;
+
+        Self::new(texture_ref.clone(), columns, rows, first_character).inspect_err(|_| {
+            texture_ref.lock().destroy(renderer);
+        })
+    }
+
+    /// Uses an already loaded atlas, which must have been decoded from an image file (see
+    /// [`crate::texture::TextureBuilder::build_from_path`] and
+    /// [`crate::texture::TextureBuilder::build_from_encoded`]), as the glyphs' texture coordinates
+    /// account for the horizontal flip applied to decoded images.
+    pub fn new(
+        texture_ref: ThreadSafeRef<Texture>,
+        columns: u32,
+        rows: u32,
+        first_character: char,
+    ) -> Result<Self, BitmapFontBuildError> {
+        let [width, height] = texture_ref.lock().dimensions;
+        if columns == 0 || rows == 0 || width % columns != 0 || height % rows != 0 {
+            return Err(BitmapFontBuildError::InvalidGrid {
+                width,
+                height,
+                columns,
+                rows,
+            });
+        }
+
+        Ok(Self {
+            texture_ref,
+            columns,
+            rows,
+            first_character,
+            aspect_ratio: (width / columns) as f32 / (height / rows) as f32,
+        })
+    }
+
+    /// Width of a glyph for a line height of 1.
+    #[profiling::skip]
+    pub fn aspect_ratio(&self) -> f32 {
+        self.aspect_ratio
+    }
+
+    /// Texture coordinates of the top left and bottom right corners of a glyph, if the atlas has
+    /// one for `character`.
+    pub fn glyph_uv(&self, character: char) -> Option<(Vec2, Vec2)> {
+        let index = u32::from(character).checked_sub(u32::from(self.first_character))?;
+        let (column, row) = (index % self.columns, index / self.columns);
+        if row >= self.rows {
+            return None;
+        }
+
+        let cell_size = Vec2::new(1.0 / self.columns as f32, 1.0 / self.rows as f32);
+        // Decoded images are flipped horizontally, so the columns go from right to left
+        let top_left = Vec2::new(1.0 - column as f32 * cell_size.x, row as f32 * cell_size.y);
+
+        Some((top_left, top_left + Vec2::new(-cell_size.x, cell_size.y)))
+    }
+
+    /// Lays out `text` in the XY plane, facing +Z, starting at the origin on the bottom of the
+    /// first line and going right then down. `size` is the height of a line in world units.
+    /// Whitespaces and characters missing from the atlas are skipped, but still advance the pen.
+    pub fn layout_text(&self, text: &str, size: f32) -> (Vec<TexturedVertex>, Vec<u32>) {
+        let glyph_size = Vec2::new(size * self.aspect_ratio, size);
+
+        let mut vertices = vec![];
+        let mut indices = vec![];
+        let mut pen = Vec2::ZERO;
+        for character in text.chars() {
+            if character == '\n' {
+                pen = Vec2::new(0.0, pen.y - size);
+                continue;
+            }
+
+            let min = pen;
+            let max = min + glyph_size;
+            pen.x += glyph_size.x;
+            let Some((uv_min, uv_max)) = self.glyph_uv(character) else {
+                continue;
+            };
+            if character.is_whitespace() {
+                continue;
+            }
+
+            let first_index: u32 = vertices.len().try_into().unwrap();
+            vertices.extend(
+                [
+                    (Vec2::new(min.x, max.y), uv_min),
+                    (max, Vec2::new(uv_max.x, uv_min.y)),
+                    (min, Vec2::new(uv_min.x, uv_max.y)),
+                    (Vec2::new(max.x, min.y), uv_max),
+                ]
+                .map(|(position, texture_coords)| TexturedVertex {
+                    position: Vec3::new(position.x, position.y, 0.0),
+                    normal: Vec3::Z,
+                    texture_coords,
+                }),
+            );
+            indices.extend([0, 2, 1, 1, 2, 3].map(|offset| first_index + offset));
+        }
+
+        (vertices, indices)
+    }
+
+    /// Lays out `text` (see [`BitmapFont::layout_text`]) and uploads it as a mesh, to be drawn by a
+    /// [`crate::components::mesh_rendering::MeshRendering`] like any other. Text without any
+    /// visible glyph (e.g. empty or only made of whitespace) has no mesh, and returns
+    /// [`TextMeshBuildError::NoVisibleGlyph`].
+    pub fn build_text_mesh(
+        &self,
+        text: &str,
+        size: f32,
+        renderer: &mut Renderer,
+    ) -> Result<ThreadSafeRef<Mesh<TexturedVertex>>, TextMeshBuildError> {
+        let (vertices, indices) = self.layout_text(text, size);
+        if vertices.is_empty() {
+            return Err(TextMeshBuildError::NoVisibleGlyph);
+        }
+        let upload_result = upload_mesh_data(&vertices, &indices, renderer)?;
+
+        Ok(ThreadSafeRef::new(Mesh {
+            aabb: Aabb::from_vertices(&vertices),
+            vertices,
+            indices: Some(indices),
+            vertex_buffer: upload_result.vertex_buffer,
+            index_buffer: Some(upload_result.index_buffer),
+        }))
+    }
+
+    pub fn destroy(&mut self, renderer: &mut Renderer) {
+        self.texture_ref.lock().destroy(renderer);
+    }
+}
@@ -1,13 +1,18 @@
 use crate::{
     allocated_types::{AllocatedImage, ImageBuildError, ImageDataUploadError},
+    capabilities::Capabilities,
+    ktx2::{self, Ktx2LoadingError},
     renderer::Renderer,
+    sampler_cache::SamplerCache,
     utils::{CommandUploader, ImmediateCommandError, ThreadSafeRef},
 };
 
-use ash::vk;
-use image::{self, EncodableLayout};
+use ash::{prelude::VkResult, vk};
+use image::{self, error::ImageError, EncodableLayout};
 use thiserror::Error;
 
+use std::ops::Range;
+
 #[non_exhaustive]
 #[allow(non_camel_case_types)]
 pub enum TextureFormat {
@@ -24,16 +29,168 @@ impl From<TextureFormat> for vk::Format {
     }
 }
 
+/// How the texels of a texture are interpreted when sampled, independently of their byte layout.
+///
+/// Color maps (albedo/base color, emissive, UI images, ...) are authored gamma encoded and should
+/// use [`TextureColorSpace::Srgb`], so that the sampler returns linear values to the shaders.
+/// Everything that isn't a color (normal, metallic, roughness, ambient occlusion, height and flow
+/// maps, lookup tables, ...) must use [`TextureColorSpace::Linear`], sampling a normal map as sRGB
+/// skews its vectors and produces wrong lighting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureColorSpace {
+    Srgb,
+    Linear,
+}
+
+impl TextureColorSpace {
+    /// Returns the variant of `format` matching this color space. Formats without an sRGB
+    /// counterpart (e.g. floating point ones) are returned unchanged.
+    pub fn apply_to(self, format: vk::Format) -> vk::Format {
+        const FORMAT_PAIRS: [(vk::Format, vk::Format); 10] = [
+            (vk::Format::R8G8B8A8_UNORM, vk::Format::R8G8B8A8_SRGB),
+            (vk::Format::B8G8R8A8_UNORM, vk::Format::B8G8R8A8_SRGB),
+            (vk::Format::R8G8B8_UNORM, vk::Format::R8G8B8_SRGB),
+            (vk::Format::B8G8R8_UNORM, vk::Format::B8G8R8_SRGB),
+            (
+                vk::Format::A8B8G8R8_UNORM_PACK32,
+                vk::Format::A8B8G8R8_SRGB_PACK32,
+            ),
+            (
+                vk::Format::BC1_RGB_UNORM_BLOCK,
+                vk::Format::BC1_RGB_SRGB_BLOCK,
+            ),
+            (
+                vk::Format::BC1_RGBA_UNORM_BLOCK,
+                vk::Format::BC1_RGBA_SRGB_BLOCK,
+            ),
+            (vk::Format::BC2_UNORM_BLOCK, vk::Format::BC2_SRGB_BLOCK),
+            (vk::Format::BC3_UNORM_BLOCK, vk::Format::BC3_SRGB_BLOCK),
+            (vk::Format::BC7_UNORM_BLOCK, vk::Format::BC7_SRGB_BLOCK),
+        ];
+
+        FORMAT_PAIRS
+            .iter()
+            .find(|(linear, srgb)| *linear == format || *srgb == format)
+            .map(|(linear, srgb)| match self {
+                TextureColorSpace::Srgb => *srgb,
+                TextureColorSpace::Linear => *linear,
+            })
+            .unwrap_or(format)
+    }
+
+    pub fn of(format: vk::Format) -> Self {
+        if TextureColorSpace::Linear.apply_to(format) != format {
+            TextureColorSpace::Srgb
+        } else {
+            TextureColorSpace::Linear
+        }
+    }
+}
+
 pub struct TextureBuilder {
     pub format: vk::Format,
+    /// When set, overrides the sRGB-ness of `format` (see [`TextureColorSpace`]).
+    pub color_space: Option<TextureColorSpace>,
     pub layout: vk::ImageLayout,
     pub usage: vk::ImageUsageFlags,
+    /// See [`TextureBuilder::with_mipmaps`].
+    pub mipmaps: bool,
+    pub sampler_settings: SamplerSettings,
+}
+
+/// Filtering and addressing of the sampler of a texture.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplerSettings {
+    /// See [`TextureBuilder::with_filter`].
+    pub filter: vk::Filter,
+    /// See [`TextureBuilder::with_mipmap_mode`].
+    pub mipmap_mode: vk::SamplerMipmapMode,
+    /// See [`TextureBuilder::with_address_mode`].
+    pub address_mode: vk::SamplerAddressMode,
+    /// See [`TextureBuilder::with_anisotropy`].
+    pub anisotropy: Option<f32>,
+}
+
+impl Default for SamplerSettings {
+    fn default() -> Self {
+        Self {
+            filter: vk::Filter::NEAREST,
+            mipmap_mode: vk::SamplerMipmapMode::NEAREST,
+            address_mode: vk::SamplerAddressMode::REPEAT,
+            anisotropy: None,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ImageFileLoadingError {
+    #[error("No image file was found at path \"{0}\".")]
+    FileNotFound(String),
+
+    #[error("Reading of image file \"{path}\" failed with error: {error}.")]
+    FileReadingFailed { path: String, error: std::io::Error },
+
+    #[error("Format of image file \"{path}\" is not supported: {error}.")]
+    UnsupportedFormat { path: String, error: ImageError },
+
+    #[error("Decoding of image file \"{path}\" failed with error: {error}.")]
+    DecodingFailed { path: String, error: ImageError },
+}
+
+/// Reads and decodes the image file at `path` to RGBA8, flipped to match the renderer's UV
+/// convention. Shared by the texture and cubemap loaders.
+pub(crate) fn load_image_file(
+    path: &std::path::Path,
+) -> Result<image::RgbaImage, ImageFileLoadingError> {
+    Ok(open_image_file(path)?.fliph().into_rgba8())
+}
+
+/// Reads and decodes the image file at `path` (e.g. a Radiance `.hdr` or OpenEXR panorama) to
+/// RGBA32F, without flipping it.
+pub(crate) fn load_hdr_image_file(
+    path: &std::path::Path,
+) -> Result<image::Rgba32FImage, ImageFileLoadingError> {
+    Ok(open_image_file(path)?.into_rgba32f())
+}
+
+fn open_image_file(path: &std::path::Path) -> Result<image::DynamicImage, ImageFileLoadingError> {
+    let path_str = path.display().to_string();
+
+    image::open(path).map_err(|error| match error {
+        ImageError::IoError(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            ImageFileLoadingError::FileNotFound(path_str.clone())
+        }
+        ImageError::IoError(error) => ImageFileLoadingError::FileReadingFailed {
+            path: path_str.clone(),
+            error,
+        },
+        error @ ImageError::Unsupported(_) => ImageFileLoadingError::UnsupportedFormat {
+            path: path_str.clone(),
+            error,
+        },
+        error => ImageFileLoadingError::DecodingFailed {
+            path: path_str.clone(),
+            error,
+        },
+    })
 }
 
 #[derive(Error, Debug)]
 pub enum TextureBuildError {
     #[error("Base image loading failed with error: {0}.")]
-    ImageLoadError(#[from] image::error::ImageError),
+    ImageLoadError(#[from] ImageFileLoadingError),
+
+    #[error("Decoding of the encoded image failed with error: {0}.")]
+    DecodeFailed(ImageError),
+
+    #[error("KTX2 container loading failed with error: {0}.")]
+    Ktx2LoadError(#[from] Ktx2LoadingError),
+
+    #[error("Compressed format {0:?} is not supported (BC formats require the texture_compression_bc feature).")]
+    UnsupportedCompressedFormat(vk::Format),
+
+    #[error("Upload of the texture's data failed with error: {0}.")]
+    DataUploadFailed(#[from] ImageDataUploadError),
 
     #[error("Creation of texture's underlying image failed with error: {0}.")]
     ImageCreationFailed(#[from] ImageBuildError),
@@ -41,21 +198,19 @@ pub enum TextureBuildError {
     #[error("Vulkan creation of texture sampler failed with result: {0}.")]
     VulkanSamplerCreationFailed(vk::Result),
 
-    #[cfg(debug_assertions)]
-    #[error("Could not convert texture path \"{0}\" to an FFI string")]
-    InvalidPathConversion(String),
-
-    #[cfg(debug_assertions)]
-    #[error("Failed to set texture handle name to handle with result: {0}")]
-    VulkanObjectNameAssignationFailed(vk::Result),
+    #[error("Generation of the texture's mipmaps failed with error: {0}.")]
+    MipmapGenerationFailed(ImmediateCommandError),
 }
 
 impl TextureBuilder {
     pub fn new() -> Self {
         Self {
             format: vk::Format::R8G8B8A8_SRGB,
+            color_space: None,
             layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
             usage: vk::ImageUsageFlags::empty(),
+            mipmaps: false,
+            sampler_settings: SamplerSettings::default(),
         }
     }
 
@@ -65,6 +220,14 @@ impl TextureBuilder {
         self
     }
 
+    /// Selects the sRGB or linear variant of the texture's format, regardless of the order in
+    /// which [`TextureBuilder::with_format`] and this method are called.
+    pub fn with_color_space(mut self, color_space: TextureColorSpace) -> Self {
+        self.color_space = Some(color_space);
+
+        self
+    }
+
     pub fn with_layout(mut self, layout: vk::ImageLayout) -> Self {
         self.layout = layout;
 
@@ -77,9 +240,66 @@ impl TextureBuilder {
         self
     }
 
+    /// Generates the full mip chain of the texture (`floor(log2(max(width, height))) + 1` levels,
+    /// see [`Texture::mip_levels`]) by successively downsampling the uploaded data on the GPU,
+    /// which avoids the shimmering of detailed textures sampled from afar.
+    pub fn with_mipmaps(mut self, mipmaps: bool) -> Self {
+        self.mipmaps = mipmaps;
+
+        self
+    }
+
+    /// Enables anisotropic filtering with up to `max_anisotropy` samples, which keeps surfaces
+    /// seen at grazing angles (e.g. floors) sharp. The value is clamped to the device's
+    /// `max_sampler_anisotropy` limit, and ignored if the `sampler_anisotropy` feature isn't
+    /// enabled (see [`crate::capabilities::CapabilitiesFeatures::sampler_anisotropy`]).
+    pub fn with_anisotropy(mut self, max_anisotropy: f32) -> Self {
+        self.sampler_settings.anisotropy = Some(max_anisotropy);
+
+        self
+    }
+
+    /// Filter used to magnify and minify the texture, `NEAREST` by default. `LINEAR` smooths
+    /// textures sampled at a different resolution than theirs (e.g. photos or gradients), while
+    /// `NEAREST` keeps the texels sharp (e.g. pixel art).
+    pub fn with_filter(mut self, filter: vk::Filter) -> Self {
+        self.sampler_settings.filter = filter;
+
+        self
+    }
+
+    /// How samples between two mip levels are filtered, `NEAREST` by default (see
+    /// [`TextureBuilder::with_mipmaps`]). `LINEAR` blends the two levels (trilinear filtering).
+    pub fn with_mipmap_mode(mut self, mipmap_mode: vk::SamplerMipmapMode) -> Self {
+        self.sampler_settings.mipmap_mode = mipmap_mode;
+
+        self
+    }
+
+    /// How coordinates outside of `[0, 1]` are handled on all the axes, `REPEAT` by default for
+    /// tiling textures. `CLAMP_TO_EDGE` avoids the opposite edge bleeding in when filtering
+    /// textures that must not wrap around (e.g. UI atlases, flow maps or gradients).
+    pub fn with_address_mode(mut self, address_mode: vk::SamplerAddressMode) -> Self {
+        self.sampler_settings.address_mode = address_mode;
+
+        self
+    }
+
+    /// Clamps the requested anisotropy to what the device supports.
+    fn resolve_anisotropy(&mut self, capabilities: &Capabilities) {
+        self.sampler_settings.anisotropy = self.sampler_settings.anisotropy.and_then(|anisotropy| {
+            if !capabilities.features.sampler_anisotropy {
+                log::warn!("Anisotropic filtering was requested for a texture, but the sampler_anisotropy feature is not enabled, ignoring");
+                return None;
+            }
+
+            Some(anisotropy.clamp(1.0, capabilities.limits.max_sampler_anisotropy))
+        });
+    }
+
     #[profiling::function]
     pub fn build(
-        self,
+        mut self,
         dimensions: [u32; 2],
         renderer: &mut Renderer,
     ) -> Result<ThreadSafeRef<Texture>, TextureBuildError> {
@@ -91,6 +311,8 @@ impl TextureBuilder {
             .copied()
             .collect::<Vec<_>>();
 
+        self.resolve_anisotropy(&renderer.capabilities());
+        let queue_family_indices = renderer.resource_queue_families();
         self.build_from_data_internal(
             &data,
             dimensions[0],
@@ -99,6 +321,8 @@ impl TextureBuilder {
             renderer.graphics_queue.handle,
             &mut renderer.allocator.as_mut().unwrap().lock(),
             &mut renderer.command_uploader,
+            &mut renderer.sampler_cache,
+            queue_family_indices,
         )
     }
 
@@ -108,57 +332,156 @@ impl TextureBuilder {
         path: &std::path::Path,
         renderer: &mut Renderer,
     ) -> Result<ThreadSafeRef<Texture>, TextureBuildError> {
-        let image = image::open(path)?.fliph().into_rgba8();
+        let image = load_image_file(path)?;
         let dimensions = image.dimensions();
 
         let new_texture =
             self.build_from_data(image.as_bytes(), dimensions.0, dimensions.1, renderer)?;
-        let path_str = path.to_str().unwrap_or("invalid path").to_owned();
-        new_texture.lock().path = Some(path_str.clone());
+        Self::set_path(&new_texture, path, renderer);
 
-        #[cfg(debug_assertions)]
-        {
-            let ffi_string = std::ffi::CString::new(path_str.clone())
-                .map_err(|_| TextureBuildError::InvalidPathConversion(path_str))?;
-            let temp_new_texture = new_texture.lock();
-            let new_image = temp_new_texture.image_ref.lock();
-            let name_info = vk::DebugUtilsObjectNameInfoEXT::default()
-                .object_handle(new_image.handle)
-                .object_name(ffi_string.as_c_str());
+        Ok(new_texture)
+    }
 
-            unsafe {
-                crate::utils::debug_name_vk_object(renderer, &name_info)
-                    .map_err(TextureBuildError::VulkanObjectNameAssignationFailed)?
-            };
+    /// Loads a texture stored in a KTX2 container with its mip levels, whose data is uploaded as
+    /// is. This is mostly useful for block compressed textures, which use a fraction of the
+    /// memory of RGBA8 ones: `BC1`, `BC3`, `BC5` and `BC7` are supported, and require the
+    /// `texture_compression_bc` feature (see
+    /// [`crate::capabilities::CapabilitiesFeatures::texture_compression_bc`]).
+    ///
+    /// The texture's format is the container's, [`TextureBuilder::with_color_space`] still
+    /// applies to it. Mip levels can't be generated for compressed formats, so
+    /// [`TextureBuilder::with_mipmaps`] is ignored: only the levels stored in the container are
+    /// used. Containers with supercompression (e.g. Basis Universal), array layers or cube faces
+    /// aren't supported. Unlike decoded images, the data is not flipped.
+    #[profiling::function]
+    pub fn build_from_ktx2(
+        mut self,
+        path: &std::path::Path,
+        renderer: &mut Renderer,
+    ) -> Result<ThreadSafeRef<Texture>, TextureBuildError> {
+        let bytes = std::fs::read(path).map_err(|error| Ktx2LoadingError::FileReadingFailed {
+            path: path.display().to_string(),
+            error,
+        })?;
+        let ktx2_image = ktx2::parse(&bytes)?;
 
-            let name_info = name_info
-                .object_handle(new_image.view);
+        const BC_FORMATS: [vk::Format; 10] = [
+            vk::Format::BC1_RGB_UNORM_BLOCK,
+            vk::Format::BC1_RGB_SRGB_BLOCK,
+            vk::Format::BC1_RGBA_UNORM_BLOCK,
+            vk::Format::BC1_RGBA_SRGB_BLOCK,
+            vk::Format::BC3_UNORM_BLOCK,
+            vk::Format::BC3_SRGB_BLOCK,
+            vk::Format::BC5_UNORM_BLOCK,
+            vk::Format::BC5_SNORM_BLOCK,
+            vk::Format::BC7_UNORM_BLOCK,
+            vk::Format::BC7_SRGB_BLOCK,
+        ];
+        let capabilities = renderer.capabilities();
+        if !BC_FORMATS.contains(&ktx2_image.format) || !capabilities.features.texture_compression_bc
+        {
+            return Err(TextureBuildError::UnsupportedCompressedFormat(
+                ktx2_image.format,
+            ));
+        }
+        self.resolve_anisotropy(&capabilities);
 
-            unsafe {
-                crate::utils::debug_name_vk_object(renderer, &name_info)
-                    .map_err(TextureBuildError::VulkanObjectNameAssignationFailed)?
-            };
+        let format = self.color_space.map_or(ktx2_image.format, |color_space| {
+            color_space.apply_to(ktx2_image.format)
+        });
+        let mip_levels: u32 = ktx2_image
+            .levels
+            .len()
+            .try_into()
+            .expect("Unsupported architecture");
+        let [width, height] = ktx2_image.dimensions;
 
-            let name_info = name_info
-                .object_handle(temp_new_texture.sampler);
+        let mut image_builder = AllocatedImage::builder(vk::Extent3D {
+            width,
+            height,
+            depth: 1,
+        })
+        .with_usage(self.usage | vk::ImageUsageFlags::TRANSFER_DST)
+        .texture_default(format);
+        image_builder.image_create_info = image_builder.image_create_info.mip_levels(mip_levels);
+        image_builder
+            .image_view_create_info
+            .subresource_range
+            .level_count = mip_levels;
 
-            unsafe {
-                crate::utils::debug_name_vk_object(renderer, &name_info)
-                    .map_err(TextureBuildError::VulkanObjectNameAssignationFailed)?
-            };
+        let mut image =
+            image_builder.build_uninitialized(&renderer.device, &mut renderer.allocator())?;
+        for (level, data) in (0..mip_levels).zip(&ktx2_image.levels) {
+            image.upload_mip_data(
+                level,
+                data,
+                Some(self.layout),
+                &renderer.device,
+                renderer.graphics_queue.handle,
+                &mut renderer.allocator(),
+                &renderer.command_uploader,
+            )?;
         }
 
+        let sampler = renderer
+            .sampler_cache
+            .get(&self.sampler_settings, mip_levels, &renderer.device)
+            .map_err(TextureBuildError::VulkanSamplerCreationFailed)?;
+
+        let new_texture = ThreadSafeRef::new(Texture {
+            image_ref: ThreadSafeRef::new(image),
+            sampler,
+            path: None,
+            dimensions: ktx2_image.dimensions,
+            format,
+            mip_levels,
+            resident_mips: 0..mip_levels,
+            sampler_settings: self.sampler_settings,
+        });
+        Self::set_path(&new_texture, path, renderer);
+
         Ok(new_texture)
     }
 
+    /// Records the file a texture was loaded from, and names its Vulkan objects after it.
+    fn set_path(texture_ref: &ThreadSafeRef<Texture>, path: &std::path::Path, renderer: &Renderer) {
+        let path_str = path.to_str().unwrap_or("invalid path").to_owned();
+        let mut texture = texture_ref.lock();
+        let image = texture.image_ref.lock();
+        renderer.auto_name(image.handle, &path_str);
+        renderer.auto_name(image.view, &format!("{path_str} (view)"));
+        drop(image);
+        texture.path = Some(path_str);
+    }
+
+    /// Decodes an image file already in memory (e.g. read from an archive, received from the
+    /// network or embedded in a `.glb` binary blob), whose format (PNG, JPEG...) is guessed from
+    /// its content, and builds a texture from it like [`TextureBuilder::build_from_path`] does.
     #[profiling::function]
-    pub fn build_from_data(
+    pub fn build_from_encoded(
         self,
+        bytes: &[u8],
+        renderer: &mut Renderer,
+    ) -> Result<ThreadSafeRef<Texture>, TextureBuildError> {
+        let image = image::load_from_memory(bytes)
+            .map_err(TextureBuildError::DecodeFailed)?
+            .fliph()
+            .into_rgba8();
+        let dimensions = image.dimensions();
+
+        self.build_from_data(image.as_bytes(), dimensions.0, dimensions.1, renderer)
+    }
+
+    #[profiling::function]
+    pub fn build_from_data(
+        mut self,
         data: &[u8],
         width: u32,
         height: u32,
         renderer: &mut Renderer,
     ) -> Result<ThreadSafeRef<Texture>, TextureBuildError> {
+        self.resolve_anisotropy(&renderer.capabilities());
+        let queue_family_indices = renderer.resource_queue_families();
         self.build_from_data_internal(
             data,
             width,
@@ -167,8 +490,73 @@ impl TextureBuilder {
             renderer.graphics_queue.handle,
             &mut renderer.allocator.as_mut().unwrap().lock(),
             &mut renderer.command_uploader,
+            &mut renderer.sampler_cache,
+            queue_family_indices,
         )
     }
+
+    /// Builds a texture with `mip_levels` levels of which only the last (smallest) one is
+    /// resident, initialized with `lowest_mip_data`. The other levels are meant to be uploaded
+    /// later with [`Texture::upload_mip_data`], and made resident with
+    /// [`Texture::set_resident_mips`] (see the `texture_streaming` module).
+    #[profiling::function]
+    pub fn build_streamed(
+        mut self,
+        dimensions: [u32; 2],
+        mip_levels: u32,
+        lowest_mip_data: &[u8],
+        renderer: &mut Renderer,
+    ) -> Result<ThreadSafeRef<Texture>, TextureBuildError> {
+        self.resolve_anisotropy(&renderer.capabilities());
+        let format = self
+            .color_space
+            .map_or(self.format, |color_space| color_space.apply_to(self.format));
+        let mip_levels = mip_levels.clamp(1, dimensions[0].max(dimensions[1]).max(1).ilog2() + 1);
+        let lowest_mip = mip_levels - 1;
+
+        let mut image_builder = AllocatedImage::builder(vk::Extent3D {
+            width: dimensions[0],
+            height: dimensions[1],
+            depth: 1,
+        })
+        .with_usage(self.usage | vk::ImageUsageFlags::TRANSFER_DST)
+        .texture_default(format);
+        image_builder.image_create_info = image_builder.image_create_info.mip_levels(mip_levels);
+        image_builder
+            .image_view_create_info
+            .subresource_range
+            .base_mip_level = lowest_mip;
+
+        let mut image =
+            image_builder.build_uninitialized(&renderer.device, &mut renderer.allocator())?;
+        image
+            .upload_mip_data(
+                lowest_mip,
+                lowest_mip_data,
+                Some(self.layout),
+                &renderer.device,
+                renderer.graphics_queue.handle,
+                &mut renderer.allocator(),
+                &renderer.command_uploader,
+            )
+            .map_err(ImageBuildError::DataUploadFailed)?;
+
+        let sampler = renderer
+            .sampler_cache
+            .get(&self.sampler_settings, 1, &renderer.device)
+            .map_err(TextureBuildError::VulkanSamplerCreationFailed)?;
+
+        Ok(ThreadSafeRef::new(Texture {
+            image_ref: ThreadSafeRef::new(image),
+            sampler,
+            path: None,
+            dimensions,
+            format,
+            mip_levels,
+            resident_mips: lowest_mip..mip_levels,
+            sampler_settings: self.sampler_settings,
+        }))
+    }
 }
 
 impl TextureBuilder {
@@ -179,6 +567,7 @@ impl TextureBuilder {
         graphics_queue: vk::Queue,
         allocator: &mut gpu_allocator::vulkan::Allocator,
         command_uploader: &mut CommandUploader,
+        sampler_cache: &mut SamplerCache,
     ) -> Result<ThreadSafeRef<Texture>, TextureBuildError> {
         self.build_from_data_internal(
             &[
@@ -190,6 +579,8 @@ impl TextureBuilder {
             graphics_queue,
             allocator,
             command_uploader,
+            sampler_cache,
+            vec![],
         )
     }
 
@@ -204,25 +595,49 @@ impl TextureBuilder {
         graphics_queue: vk::Queue,
         allocator: &mut gpu_allocator::vulkan::Allocator,
         command_uploader: &mut CommandUploader,
+        sampler_cache: &mut SamplerCache,
+        queue_family_indices: Vec<u32>,
     ) -> Result<ThreadSafeRef<Texture>, TextureBuildError> {
-        let image = AllocatedImage::builder(vk::Extent3D {
+        let format = self
+            .color_space
+            .map_or(self.format, |color_space| color_space.apply_to(self.format));
+
+        let mip_levels = if self.mipmaps {
+            width.max(height).max(1).ilog2() + 1
+        } else {
+            1
+        };
+        let mut usage = self.usage;
+        if mip_levels > 1 {
+            // Every level is blitted from the previous one
+            usage |= vk::ImageUsageFlags::TRANSFER_SRC | vk::ImageUsageFlags::TRANSFER_DST;
+        }
+
+        let mut image_builder = AllocatedImage::builder(vk::Extent3D {
             width,
             height,
             depth: 1,
         })
-        .texture_default(self.format)
+        .texture_default(format)
         .with_layout(self.layout)
-        .with_usage(self.usage)
-        .with_data(data.to_vec())
-        .build_internal(device, graphics_queue, allocator, command_uploader)?;
-
-        let sampler_info = vk::SamplerCreateInfo::default()
-            .mag_filter(vk::Filter::NEAREST)
-            .min_filter(vk::Filter::NEAREST)
-            .address_mode_u(vk::SamplerAddressMode::REPEAT)
-            .address_mode_v(vk::SamplerAddressMode::REPEAT)
-            .address_mode_w(vk::SamplerAddressMode::REPEAT);
-        let sampler = unsafe { device.create_sampler(&sampler_info, None) }
+        .with_usage(usage)
+        .with_data(data.to_vec());
+        image_builder.queue_family_indices = queue_family_indices;
+        image_builder.image_create_info = image_builder.image_create_info.mip_levels(mip_levels);
+        image_builder
+            .image_view_create_info
+            .subresource_range
+            .level_count = mip_levels;
+        let image =
+            image_builder.build_internal(device, graphics_queue, allocator, command_uploader)?;
+
+        if mip_levels > 1 {
+            generate_mipmaps(&image, mip_levels, device, graphics_queue, command_uploader)
+                .map_err(TextureBuildError::MipmapGenerationFailed)?;
+        }
+
+        let sampler = sampler_cache
+            .get(&self.sampler_settings, mip_levels, device)
             .map_err(TextureBuildError::VulkanSamplerCreationFailed)?;
 
         Ok(ThreadSafeRef::new(Texture {
@@ -230,7 +645,10 @@ impl TextureBuilder {
             sampler,
             path: None,
             dimensions: [width, height],
-            format: self.format,
+            format,
+            mip_levels,
+            resident_mips: 0..mip_levels,
+            sampler_settings: self.sampler_settings,
         }))
     }
 }
@@ -244,11 +662,130 @@ impl Default for TextureBuilder {
 #[derive(Debug)]
 pub struct Texture {
     pub image_ref: ThreadSafeRef<AllocatedImage>,
+    /// Shared with the other textures using the same sampler settings, and owned by the
+    /// renderer's sampler cache: it must not be destroyed.
     pub sampler: vk::Sampler,
 
     pub path: Option<String>,
     pub dimensions: [u32; 2],
     format: vk::Format,
+    mip_levels: u32,
+    resident_mips: Range<u32>,
+    /// The anisotropy is already clamped to the device's limit, see
+    /// [`TextureBuilder::with_anisotropy`].
+    sampler_settings: SamplerSettings,
+}
+
+/// Fills the levels after the first one of `image` by blitting every level to the next one, with
+/// linear filtering (which all the [`TextureFormat`]s support). The first level must be in the
+/// image's layout, which all the levels are in afterwards.
+fn generate_mipmaps(
+    image: &AllocatedImage,
+    mip_levels: u32,
+    device: &ash::Device,
+    graphics_queue: vk::Queue,
+    command_uploader: &CommandUploader,
+) -> Result<(), ImmediateCommandError> {
+    let level_barrier = |level: u32| {
+        vk::ImageMemoryBarrier::default()
+            .image(image.handle)
+            .subresource_range(
+                vk::ImageSubresourceRange::default()
+                    .aspect_mask(vk::ImageAspectFlags::COLOR)
+                    .base_mip_level(level)
+                    .level_count(1)
+                    .base_array_layer(0)
+                    .layer_count(image.layer_count),
+            )
+    };
+    let level_size = |level: u32| vk::Offset3D {
+        x: (image.extent.width >> level).max(1) as i32,
+        y: (image.extent.height >> level).max(1) as i32,
+        z: 1,
+    };
+
+    command_uploader.immediate_command(device, graphics_queue, |cmd_buffer| {
+        for level in 1..mip_levels {
+            // The previous level was either uploaded (level 0) or blitted to
+            let source_barrier = level_barrier(level - 1)
+                .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+                .old_layout(if level == 1 {
+                    image.layout
+                } else {
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL
+                })
+                .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL);
+            let destination_barrier = level_barrier(level)
+                .src_access_mask(vk::AccessFlags::NONE)
+                .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                .old_layout(vk::ImageLayout::UNDEFINED)
+                .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL);
+            unsafe {
+                device.cmd_pipeline_barrier(
+                    *cmd_buffer,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[],
+                    &[source_barrier, destination_barrier],
+                )
+            };
+
+            let subresource = |level: u32| vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: level,
+                base_array_layer: 0,
+                layer_count: image.layer_count,
+            };
+            let blit = vk::ImageBlit::default()
+                .src_subresource(subresource(level - 1))
+                .src_offsets([vk::Offset3D::default(), level_size(level - 1)])
+                .dst_subresource(subresource(level))
+                .dst_offsets([vk::Offset3D::default(), level_size(level)]);
+            unsafe {
+                device.cmd_blit_image(
+                    *cmd_buffer,
+                    image.handle,
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    image.handle,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    std::slice::from_ref(&blit),
+                    vk::Filter::LINEAR,
+                )
+            };
+        }
+
+        // All levels but the last one were blitted from, the last one was only written to
+        let mut final_barriers = (0..mip_levels - 1)
+            .map(|level| {
+                level_barrier(level)
+                    .src_access_mask(vk::AccessFlags::TRANSFER_READ)
+                    .dst_access_mask(vk::AccessFlags::SHADER_READ)
+                    .old_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+                    .new_layout(image.layout)
+            })
+            .collect::<Vec<_>>();
+        final_barriers.push(
+            level_barrier(mip_levels - 1)
+                .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                .dst_access_mask(vk::AccessFlags::SHADER_READ)
+                .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                .new_layout(image.layout),
+        );
+        unsafe {
+            device.cmd_pipeline_barrier(
+                *cmd_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &final_barriers,
+            )
+        };
+    })
 }
 
 #[derive(Error, Debug)]
@@ -263,6 +800,22 @@ pub enum TextureCloneError {
     VulkanSamplerCreationFailed(vk::Result),
 }
 
+#[derive(Error, Debug)]
+pub enum TextureMipRangeError {
+    #[error("Invalid resident mip range {start}..{end}, the texture has {mip_levels} mip levels.")]
+    InvalidRange {
+        start: u32,
+        end: u32,
+        mip_levels: u32,
+    },
+
+    #[error("Vulkan creation of the texture's new view failed with result: {0}.")]
+    VulkanViewCreationFailed(vk::Result),
+
+    #[error("Vulkan creation of the texture's new sampler failed with result: {0}.")]
+    VulkanSamplerCreationFailed(vk::Result),
+}
+
 #[profiling::all_functions]
 impl Texture {
     #[profiling::skip]
@@ -270,6 +823,46 @@ impl Texture {
         TextureBuilder::default()
     }
 
+    /// Wraps an already initialized 2D image (e.g. the color attachment of a render target).
+    pub(crate) fn from_image(image: AllocatedImage, renderer: &mut Renderer) -> VkResult<Self> {
+        Self::from_image_ref(
+            ThreadSafeRef::new(image),
+            SamplerSettings::default(),
+            renderer,
+        )
+    }
+
+    /// Wraps a single level image, sampled with `sampler_settings`.
+    pub(crate) fn from_image_ref(
+        image_ref: ThreadSafeRef<AllocatedImage>,
+        sampler_settings: SamplerSettings,
+        renderer: &mut Renderer,
+    ) -> VkResult<Self> {
+        let sampler = renderer
+            .sampler_cache
+            .get(&sampler_settings, 1, &renderer.device)?;
+        let (dimensions, format) = {
+            let image = image_ref.lock();
+            ([image.extent.width, image.extent.height], image.format)
+        };
+
+        Ok(Self {
+            sampler,
+            path: None,
+            dimensions,
+            format,
+            mip_levels: 1,
+            resident_mips: 0..1,
+            sampler_settings,
+            image_ref,
+        })
+    }
+
+    #[profiling::skip]
+    pub fn color_space(&self) -> TextureColorSpace {
+        TextureColorSpace::of(self.format)
+    }
+
     pub fn clone(&self, renderer: &mut Renderer) -> Result<Self, TextureCloneError> {
         let new_image = AllocatedImage::builder(vk::Extent3D {
             width: self.dimensions[0],
@@ -366,13 +959,9 @@ impl Texture {
             };
         })?;
 
-        let sampler_info = vk::SamplerCreateInfo::default()
-            .mag_filter(vk::Filter::NEAREST)
-            .min_filter(vk::Filter::NEAREST)
-            .address_mode_u(vk::SamplerAddressMode::REPEAT)
-            .address_mode_v(vk::SamplerAddressMode::REPEAT)
-            .address_mode_w(vk::SamplerAddressMode::REPEAT);
-        let sampler = unsafe { renderer.device.create_sampler(&sampler_info, None) }
+        let sampler = renderer
+            .sampler_cache
+            .get(&self.sampler_settings, 1, &renderer.device)
             .map_err(TextureCloneError::VulkanSamplerCreationFailed)?;
 
         Ok(Self {
@@ -381,9 +970,83 @@ impl Texture {
             path: self.path.clone(),
             dimensions: self.dimensions,
             format: self.format,
+            mip_levels: 1,
+            resident_mips: 0..1,
+            sampler_settings: self.sampler_settings,
         })
     }
 
+    #[profiling::skip]
+    pub fn mip_levels(&self) -> u32 {
+        self.mip_levels
+    }
+
+    #[profiling::skip]
+    pub fn resident_mips(&self) -> Range<u32> {
+        self.resident_mips.clone()
+    }
+
+    /// Restricts sampling to the given range of mip levels (0 being the most detailed one),
+    /// without reallocating the image. This allows streaming a texture progressively: start with
+    /// only its smallest mips resident, and extend the range as more detailed ones are uploaded.
+    ///
+    /// The texture's view is recreated with the same type (e.g. cubemap or array) to only cover the
+    /// resident levels (so that the other ones can be in any layout, e.g. while they are being
+    /// written to), and its sampler's LOD range is clamped to match. As descriptor sets reference
+    /// the view and sampler, the texture must be bound again (see `Material::bind_texture` and
+    /// `MeshRendering::bind_texture`) after this call, before it is used in the current frame. The previous view is only destroyed once the
+    /// frames in flight that may sample it are done executing.
+    pub fn set_resident_mips(
+        &mut self,
+        mips: Range<u32>,
+        renderer: &mut Renderer,
+    ) -> Result<(), TextureMipRangeError> {
+        if mips.is_empty() || mips.end > self.mip_levels {
+            return Err(TextureMipRangeError::InvalidRange {
+                start: mips.start,
+                end: mips.end,
+                mip_levels: self.mip_levels,
+            });
+        }
+        if mips == self.resident_mips {
+            return Ok(());
+        }
+
+        let mut image = self.image_ref.lock();
+        let view_info = vk::ImageViewCreateInfo::default()
+            .image(image.handle)
+            .view_type(image.view_type)
+            .format(image.format)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: mips.start,
+                level_count: mips.len().try_into().unwrap(),
+                base_array_layer: 0,
+                layer_count: image.layer_count,
+            });
+        let view = unsafe { renderer.device.create_image_view(&view_info, None) }
+            .map_err(TextureMipRangeError::VulkanViewCreationFailed)?;
+        let sampler = match renderer.sampler_cache.get(
+            &self.sampler_settings,
+            mips.len().try_into().unwrap(),
+            &renderer.device,
+        ) {
+            Ok(sampler) => sampler,
+            Err(result) => {
+                unsafe { renderer.device.destroy_image_view(view, None) };
+                return Err(TextureMipRangeError::VulkanSamplerCreationFailed(result));
+            }
+        };
+
+        // Frames in flight may still sample the old view
+        renderer.destroy_image_view_deferred(image.view);
+        image.view = view;
+        self.sampler = sampler;
+        self.resident_mips = mips;
+
+        Ok(())
+    }
+
     pub fn upload_data(
         &mut self,
         data: &[u8],
@@ -399,6 +1062,25 @@ impl Texture {
         )
     }
 
+    /// Uploads the content of a single mip level. Uploading a level doesn't make it resident, see
+    /// [`Texture::set_resident_mips`].
+    pub fn upload_mip_data(
+        &mut self,
+        mip_level: u32,
+        data: &[u8],
+        renderer: &mut Renderer,
+    ) -> Result<(), ImageDataUploadError> {
+        self.image_ref.lock().upload_mip_data(
+            mip_level,
+            data,
+            None,
+            &renderer.device,
+            renderer.graphics_queue.handle,
+            &mut renderer.allocator(),
+            &renderer.command_uploader,
+        )
+    }
+
     pub fn destroy(&mut self, renderer: &mut Renderer) {
         self.destroy_internal(&renderer.device, &mut renderer.allocator())
     }
@@ -409,8 +1091,6 @@ impl Texture {
         device: &ash::Device,
         allocator: &mut gpu_allocator::vulkan::Allocator,
     ) {
-        unsafe { device.destroy_sampler(self.sampler, None) };
-
         self.image_ref.lock().destroy_internal(device, allocator);
     }
 }
@@ -0,0 +1,378 @@
+use crate::{
+    allocated_types::ImageDataUploadError,
+    async_load::AsyncLoad,
+    renderer::Renderer,
+    texture::{Texture, TextureMipRangeError},
+    utils::ThreadSafeRef,
+};
+
+use thiserror::Error;
+
+use std::{collections::BTreeMap, sync::Arc, task::Poll};
+
+/// Loads the data of a mip level of a streamed texture, in the texture's format. Loaders are called
+/// on worker threads, so they should do the heavy lifting themselves (reading and decoding files).
+pub type MipLoader = Arc<dyn Fn(u32) -> Vec<u8> + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamingSettings {
+    /// Maximum size in bytes of the resident mips of all streamed textures, mips being loaded
+    /// included. This limits the detail streamed in, not the memory used: the images of streamed
+    /// textures keep their whole mip chain allocated.
+    pub max_resident_bytes: u64,
+    /// Maximum number of mip loads started by a single [`TextureStreamer::update`].
+    pub max_loads_per_update: usize,
+    /// Distance under which [`TextureStreamer::request_at_distance`] asks for the most detailed
+    /// mip. Each doubling of the distance past this one drops a mip level.
+    pub full_detail_distance: f32,
+}
+
+impl Default for StreamingSettings {
+    fn default() -> Self {
+        Self {
+            max_resident_bytes: 512 * 1024 * 1024,
+            max_loads_per_update: 4,
+            full_detail_distance: 10.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StreamedTextureId(u64);
+
+/// Change of the resident mips of a streamed texture made by [`TextureStreamer::update`]. As the
+/// texture's view and sampler are recreated, it must be bound again wherever it is used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamingAction {
+    Loaded {
+        texture: StreamedTextureId,
+        mip: u32,
+    },
+    Evicted {
+        texture: StreamedTextureId,
+        mip: u32,
+    },
+}
+
+#[derive(Error, Debug)]
+pub enum TextureStreamingError {
+    #[error("Upload of a streamed mip failed with error: {0}.")]
+    MipUploadFailed(#[from] ImageDataUploadError),
+
+    #[error("Update of a streamed texture's resident mips failed with error: {0}.")]
+    ResidentMipsUpdateFailed(#[from] TextureMipRangeError),
+}
+
+struct StreamedTexture {
+    texture_ref: ThreadSafeRef<Texture>,
+    loader: MipLoader,
+    dimensions: [u32; 2],
+    mip_levels: u32,
+
+    /// Most detailed mip requested for the texture.
+    desired_mip: u32,
+    /// Value of [`TextureStreamer::frame`] when the texture was last requested.
+    last_used: u64,
+    pending_load: Option<(u32, AsyncLoad<Vec<u8>>)>,
+}
+
+impl StreamedTexture {
+    /// Estimated size of a mip, for 4 bytes per texel formats.
+    fn mip_size(&self, mip: u32) -> u64 {
+        let width = u64::from((self.dimensions[0] >> mip).max(1));
+        let height = u64::from((self.dimensions[1] >> mip).max(1));
+
+        width * height * 4
+    }
+
+    fn used_bytes(&self) -> u64 {
+        let resident_mips = self.texture_ref.lock().resident_mips();
+        let pending_mip = self.pending_load.as_ref().map(|(mip, _)| *mip);
+
+        resident_mips
+            .chain(pending_mip)
+            .map(|mip| self.mip_size(mip))
+            .sum()
+    }
+}
+
+/// Streams the mips of textures in and out to keep their resident mips under a size limit.
+///
+/// Streamed textures are built with [`crate::texture::TextureBuilder::build_streamed`], so that
+/// only their smallest mip is resident at first, and registered along with a [`MipLoader`].
+/// Every frame, the application requests the mips it needs (from the distance of the objects using
+/// a texture, or from their visibility), then calls [`TextureStreamer::update`], which:
+/// - makes the mips that finished loading resident,
+/// - evicts the most detailed mip of the least recently requested textures while over the limit,
+/// - starts loading the next mip of the most recently requested textures that need it, on worker
+///   threads, evicting less recently requested textures to make room if needed.
+///
+/// Mips are always made resident one at a time from the smallest one, so a texture can be sampled
+/// at any point of its streaming. Evicted mips stop being sampled, but the image keeps its whole
+/// mip chain allocated: [`StreamingSettings::max_resident_bytes`] limits the detail (and upload
+/// bandwidth) spent on the scene rather than the size of the allocations.
+///
+/// The streamer is not part of the renderer, and holds no Vulkan objects: streaming is off unless
+/// the application creates one. It can be shared between threads with a [`ThreadSafeRef`] to
+/// request mips from systems running in parallel.
+pub struct TextureStreamer {
+    pub settings: StreamingSettings,
+
+    textures: BTreeMap<StreamedTextureId, StreamedTexture>,
+    next_id: u64,
+    frame: u64,
+}
+
+#[profiling::all_functions]
+impl TextureStreamer {
+    pub fn new(settings: StreamingSettings) -> Self {
+        Self {
+            settings,
+            textures: BTreeMap::new(),
+            next_id: 0,
+            frame: 0,
+        }
+    }
+
+    /// Starts streaming the given texture. The mips of the texture that are already resident stay
+    /// so until they are evicted.
+    pub fn register(
+        &mut self,
+        texture_ref: ThreadSafeRef<Texture>,
+        loader: MipLoader,
+    ) -> StreamedTextureId {
+        let id = StreamedTextureId(self.next_id);
+        self.next_id += 1;
+
+        let (dimensions, mip_levels, resident_mips) = {
+            let texture = texture_ref.lock();
+            (
+                texture.dimensions,
+                texture.mip_levels(),
+                texture.resident_mips(),
+            )
+        };
+        self.textures.insert(
+            id,
+            StreamedTexture {
+                texture_ref,
+                loader,
+                dimensions,
+                mip_levels,
+                desired_mip: resident_mips.start,
+                last_used: self.frame,
+                pending_load: None,
+            },
+        );
+
+        id
+    }
+
+    /// Stops streaming the texture and returns it, with the mips it had resident.
+    pub fn unregister(&mut self, id: StreamedTextureId) -> Option<ThreadSafeRef<Texture>> {
+        self.textures
+            .remove(&id)
+            .map(|streamed_texture| streamed_texture.texture_ref)
+    }
+
+    #[profiling::skip]
+    pub fn texture(&self, id: StreamedTextureId) -> Option<&ThreadSafeRef<Texture>> {
+        self.textures
+            .get(&id)
+            .map(|streamed_texture| &streamed_texture.texture_ref)
+    }
+
+    /// Size of the resident and loading mips of all streamed textures, to compare with
+    /// [`StreamingSettings::max_resident_bytes`].
+    pub fn used_bytes(&self) -> u64 {
+        self.textures
+            .values()
+            .map(StreamedTexture::used_bytes)
+            .sum()
+    }
+
+    /// Requests the mips of the texture up to `mip` (0 being the most detailed one) for the
+    /// current frame. Requests made during the same frame keep the most detailed mip.
+    pub fn request_mip(&mut self, id: StreamedTextureId, mip: u32) {
+        let frame = self.frame;
+        let Some(streamed_texture) = self.textures.get_mut(&id) else {
+            log::warn!("Requested a mip of unknown streamed texture {id:?}");
+            return;
+        };
+
+        let mip = mip.min(streamed_texture.mip_levels - 1);
+        if streamed_texture.last_used != frame {
+            streamed_texture.last_used = frame;
+            streamed_texture.desired_mip = mip;
+        } else {
+            streamed_texture.desired_mip = streamed_texture.desired_mip.min(mip);
+        }
+    }
+
+    /// Requests the mip matching an object using the texture at `distance` from the camera (see
+    /// [`StreamingSettings::full_detail_distance`]).
+    pub fn request_at_distance(&mut self, id: StreamedTextureId, distance: f32) {
+        let ratio = distance / self.settings.full_detail_distance;
+        let mip = if ratio > 1.0 {
+            ratio.log2().floor() as u32
+        } else {
+            0
+        };
+
+        self.request_mip(id, mip);
+    }
+
+    /// Applies the streaming decisions for the frame, and returns the changes made to the resident
+    /// mips of the textures so that they can be bound again. Must be called once per frame, after
+    /// the frame's requests.
+    pub fn update(
+        &mut self,
+        renderer: &mut Renderer,
+    ) -> Result<Vec<StreamingAction>, TextureStreamingError> {
+        let mut actions = vec![];
+
+        self.collect_loads(renderer, &mut actions)?;
+
+        let mut used_bytes = self.used_bytes();
+        while used_bytes > self.settings.max_resident_bytes {
+            match self.evict_least_recently_used(u64::MAX, renderer, &mut actions)? {
+                Some(freed_bytes) => used_bytes -= freed_bytes,
+                None => break,
+            }
+        }
+
+        self.start_loads(&mut used_bytes, renderer, &mut actions)?;
+
+        self.frame += 1;
+
+        Ok(actions)
+    }
+
+    fn collect_loads(
+        &mut self,
+        renderer: &mut Renderer,
+        actions: &mut Vec<StreamingAction>,
+    ) -> Result<(), TextureStreamingError> {
+        for (id, streamed_texture) in &mut self.textures {
+            let Some((mip, load)) = &mut streamed_texture.pending_load else {
+                continue;
+            };
+            let mip = *mip;
+            let Poll::Ready(result) = load.poll() else {
+                continue;
+            };
+            streamed_texture.pending_load = None;
+
+            let data = match result {
+                Ok(data) => data,
+                Err(error) => {
+                    log::warn!("Failed to load mip {mip} of streamed texture {id:?}: {error}");
+                    continue;
+                }
+            };
+
+            let mut texture = streamed_texture.texture_ref.lock();
+            let resident_mips = texture.resident_mips();
+            // Mips are loaded from the smallest one, so this only fails if the texture's resident
+            // mips were changed outside of the streamer
+            if resident_mips.start != mip + 1 {
+                continue;
+            }
+            texture.upload_mip_data(mip, &data, renderer)?;
+            texture.set_resident_mips(mip..resident_mips.end, renderer)?;
+
+            actions.push(StreamingAction::Loaded { texture: *id, mip });
+        }
+
+        Ok(())
+    }
+
+    /// Evicts the most detailed mip of the least recently requested texture among the ones last
+    /// requested before `used_before`, and returns the size of the evicted mip.
+    fn evict_least_recently_used(
+        &mut self,
+        used_before: u64,
+        renderer: &mut Renderer,
+        actions: &mut Vec<StreamingAction>,
+    ) -> Result<Option<u64>, TextureStreamingError> {
+        let candidate = self
+            .textures
+            .iter()
+            .filter(|(_, streamed_texture)| {
+                streamed_texture.last_used < used_before
+                    && streamed_texture.pending_load.is_none()
+                    && streamed_texture.texture_ref.lock().resident_mips().len() > 1
+            })
+            .min_by_key(|(_, streamed_texture)| streamed_texture.last_used)
+            .map(|(id, _)| *id);
+        let Some(id) = candidate else {
+            return Ok(None);
+        };
+
+        let streamed_texture = &self.textures[&id];
+        let mut texture = streamed_texture.texture_ref.lock();
+        let resident_mips = texture.resident_mips();
+        let mip = resident_mips.start;
+        texture.set_resident_mips(mip + 1..resident_mips.end, renderer)?;
+
+        actions.push(StreamingAction::Evicted { texture: id, mip });
+
+        Ok(Some(streamed_texture.mip_size(mip)))
+    }
+
+    fn start_loads(
+        &mut self,
+        used_bytes: &mut u64,
+        renderer: &mut Renderer,
+        actions: &mut Vec<StreamingAction>,
+    ) -> Result<(), TextureStreamingError> {
+        // Most recently requested first, then the ones missing the most detail
+        let mut candidates = self
+            .textures
+            .iter()
+            .filter_map(|(id, streamed_texture)| {
+                let resident_start = streamed_texture.texture_ref.lock().resident_mips().start;
+                (streamed_texture.pending_load.is_none()
+                    && streamed_texture.desired_mip < resident_start)
+                    .then_some((
+                        *id,
+                        streamed_texture.last_used,
+                        resident_start - streamed_texture.desired_mip,
+                    ))
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by(|lhs, rhs| rhs.1.cmp(&lhs.1).then(rhs.2.cmp(&lhs.2)));
+
+        let mut started_loads = 0;
+        for (id, last_used, _) in candidates {
+            if started_loads >= self.settings.max_loads_per_update {
+                break;
+            }
+
+            // Evictions made for previous candidates may have changed this texture's mips
+            let resident_start = self.textures[&id].texture_ref.lock().resident_mips().start;
+            if resident_start <= self.textures[&id].desired_mip {
+                continue;
+            }
+            let mip = resident_start - 1;
+            let mip_size = self.textures[&id].mip_size(mip);
+            while *used_bytes + mip_size > self.settings.max_resident_bytes {
+                match self.evict_least_recently_used(last_used, renderer, actions)? {
+                    Some(freed_bytes) => *used_bytes -= freed_bytes,
+                    None => break,
+                }
+            }
+            if *used_bytes + mip_size > self.settings.max_resident_bytes {
+                continue;
+            }
+
+            let streamed_texture = self.textures.get_mut(&id).unwrap();
+            let loader = streamed_texture.loader.clone();
+            streamed_texture.pending_load = Some((mip, AsyncLoad::spawn(move |_| loader(mip))));
+            *used_bytes += mip_size;
+            started_loads += 1;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,250 @@
+//! Tone mapping of an HDR scene to the swapchain, encoded for its color space (see
+//! [`crate::renderer::RendererBuilder::with_color_space`]).
+
+use ash::vk;
+use bytemuck::{bytes_of, Pod, Zeroable};
+use thiserror::Error;
+
+use crate::{
+    descriptor_resources::DescriptorResources,
+    material::{BlendMode, Material, MaterialBuildError, MaterialBuilder},
+    math_types::Vec3,
+    mesh::{Mesh, UploadError},
+    renderer::Renderer,
+    shader::{Shader, ShaderBuildError},
+    texture::Texture,
+    utils::ThreadSafeRef,
+    vertices::simple::SimpleVertex,
+};
+
+/// Binding of the scene texture in the set 2 of the tone mapping material.
+pub const SCENE_COLOR_BINDING: u32 = 0;
+
+/// Single triangle covering the whole viewport.
+const FULLSCREEN_TRIANGLE: [Vec3; 3] = [
+    Vec3::new(-1.0, -1.0, 0.0),
+    Vec3::new(3.0, -1.0, 0.0),
+    Vec3::new(-1.0, 3.0, 0.0),
+];
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct ToneMappingData {
+    exposure: f32,
+    paper_white_nits: f32,
+    peak_nits: f32,
+    output_transfer: u32,
+}
+unsafe impl Zeroable for ToneMappingData {}
+unsafe impl Pod for ToneMappingData {}
+
+#[derive(Error, Debug)]
+pub enum ToneMappingBuildError {
+    #[error("Creation of tone mapping shader failed with error: {0}.")]
+    ShaderCreationFailed(#[from] ShaderBuildError),
+
+    #[error("Creation of tone mapping material failed with error: {0}.")]
+    MaterialCreationFailed(#[from] MaterialBuildError),
+
+    #[error("Upload of the fullscreen triangle failed with error: {0}.")]
+    MeshUploadFailed(#[from] UploadError),
+}
+
+/// How the tone mapped colors are encoded, which depends on the swapchain's format and color space
+/// (see [`Renderer::surface_format`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputTransfer {
+    /// SDR output to an `_SRGB` format, which encodes the colors when they are written.
+    SrgbFormat = 0,
+    /// SDR output to a `_UNORM` format, the shader applies the sRGB transfer function.
+    Srgb = 1,
+    /// `EXTENDED_SRGB_LINEAR_EXT` (scRGB): linear colors, 1.0 being 80 nits.
+    ExtendedLinear = 2,
+    /// `HDR10_ST2084_EXT`: Rec. 2020 colors encoded with the PQ transfer function.
+    Pq = 3,
+}
+
+impl OutputTransfer {
+    pub fn of(surface_format: vk::SurfaceFormatKHR) -> Self {
+        match (surface_format.color_space, surface_format.format) {
+            (vk::ColorSpaceKHR::HDR10_ST2084_EXT, _) => Self::Pq,
+            (vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT, _) => Self::ExtendedLinear,
+            (
+                _,
+                vk::Format::B8G8R8A8_SRGB
+                | vk::Format::R8G8B8A8_SRGB
+                | vk::Format::A8B8G8R8_SRGB_PACK32,
+            ) => Self::SrgbFormat,
+            _ => Self::Srgb,
+        }
+    }
+
+    pub fn is_hdr(&self) -> bool {
+        matches!(self, Self::ExtendedLinear | Self::Pq)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ToneMappingSettings {
+    /// Factor the scene colors are multiplied by before tone mapping, e.g.
+    /// [`crate::components::camera::Camera::exposure_multiplier`].
+    pub exposure: f32,
+    /// Luminance (in nits) of a scene color of 1.0 on HDR displays, the white of SDR content.
+    pub paper_white_nits: f32,
+    /// Peak luminance (in nits) of HDR displays, which brighter colors are compressed towards.
+    pub peak_nits: f32,
+}
+
+impl Default for ToneMappingSettings {
+    fn default() -> Self {
+        Self {
+            exposure: 1.0,
+            paper_white_nits: 200.0,
+            peak_nits: 1000.0,
+        }
+    }
+}
+
+/// Draws an HDR scene texture (e.g. the texture of a `R16G16B16A16_SFLOAT`
+/// [`crate::render_target::RenderTarget`]) to the swapchain, compressing its colors to the range of
+/// the display.
+///
+/// SDR outputs use the ACES filmic curve, which maps the scene to `[0, 1]`. HDR outputs keep the
+/// colors up to the paper white mostly linear, and compress the brighter ones towards the peak
+/// luminance of the display, before encoding them as described in [`OutputTransfer`].
+pub struct ToneMapping {
+    pub settings: ToneMappingSettings,
+    pub material_ref: ThreadSafeRef<Material<SimpleVertex>>,
+    pub mesh_ref: ThreadSafeRef<Mesh<SimpleVertex>>,
+    output_transfer: OutputTransfer,
+}
+
+#[profiling::all_functions]
+impl ToneMapping {
+    /// The scene texture isn't owned by the tone mapping, and must outlive it.
+    pub fn new(
+        scene_texture_ref: ThreadSafeRef<Texture>,
+        settings: ToneMappingSettings,
+        renderer: &mut Renderer,
+    ) -> Result<Self, ToneMappingBuildError> {
+        let shader_ref = Shader::from_spirv_u8(
+            include_bytes!("shaders/gen/tone_mapping.vert"),
+            include_bytes!("shaders/gen/tone_mapping.frag"),
+            &renderer.device,
+        )?;
+
+        let material_ref = match MaterialBuilder::new()
+            .z_test(false)
+            .z_write(false)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .blend_mode(BlendMode::Opaque)
+            .build(
+                &shader_ref,
+                DescriptorResources {
+                    sampled_images: [(SCENE_COLOR_BINDING, scene_texture_ref)].into(),
+                    ..Default::default()
+                },
+                renderer,
+            ) {
+            Ok(material_ref) => material_ref,
+            Err(error) => {
+                shader_ref.lock().destroy(&renderer.device);
+                return Err(error.into());
+            }
+        };
+
+        let vertices = FULLSCREEN_TRIANGLE
+            .iter()
+            .map(|position| SimpleVertex {
+                position: *position,
+            })
+            .collect();
+        let mesh_ref = match Mesh::from_vertices(vertices, renderer) {
+            Ok(mesh_ref) => mesh_ref,
+            Err(error) => {
+                material_ref.lock().destroy(renderer);
+                shader_ref.lock().destroy(&renderer.device);
+                return Err(error.into());
+            }
+        };
+
+        Ok(Self {
+            settings,
+            material_ref,
+            mesh_ref,
+            output_transfer: OutputTransfer::of(renderer.surface_format()),
+        })
+    }
+
+    #[profiling::skip]
+    pub fn output_transfer(&self) -> OutputTransfer {
+        self.output_transfer
+    }
+
+    /// Records the draw of the tone mapped scene over the whole render region. This must be called
+    /// while rendering to the swapchain, once the scene texture is rendered (e.g. after
+    /// [`Renderer::end_frame_to`]).
+    pub fn record(&self, renderer: &mut Renderer) {
+        let material = self.material_ref.lock();
+        material
+            .descriptor_resources
+            .request_layouts_for_render(renderer);
+
+        renderer.begin_gpu_zone("Tone mapping");
+
+        // The viewport is never flipped, the scene texture already is if the renderer's viewport is
+        let render_region = renderer.render_region();
+        let viewport = vk::Viewport {
+            x: render_region.offset.x as f32,
+            y: render_region.offset.y as f32,
+            width: render_region.extent.width as f32,
+            height: render_region.extent.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        let tone_mapping_data = ToneMappingData {
+            exposure: self.settings.exposure,
+            paper_white_nits: self.settings.paper_white_nits,
+            peak_nits: self.settings.peak_nits,
+            output_transfer: self.output_transfer as u32,
+        };
+
+        let device = renderer.device.clone();
+        let cmd_buffer = renderer.primary_command_buffer;
+        unsafe {
+            device.cmd_bind_pipeline(
+                cmd_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                material.pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                cmd_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                material.layout,
+                2,
+                std::slice::from_ref(&material.descriptor_set),
+                &[],
+            );
+            device.cmd_set_viewport(cmd_buffer, 0, std::slice::from_ref(&viewport));
+            device.cmd_set_scissor(cmd_buffer, 0, std::slice::from_ref(&render_region));
+            device.cmd_push_constants(
+                cmd_buffer,
+                material.layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                bytes_of(&tone_mapping_data),
+            );
+        }
+        self.mesh_ref.lock().record_draw(&device, cmd_buffer);
+
+        renderer.end_gpu_zone();
+    }
+
+    /// Destroys the triangle, the material and its shader, but not the scene texture.
+    pub fn destroy(&mut self, renderer: &mut Renderer) {
+        self.mesh_ref.lock().destroy(renderer);
+        let mut material = self.material_ref.lock();
+        material.destroy(renderer);
+        material.shader_ref.lock().destroy(&renderer.device);
+    }
+}
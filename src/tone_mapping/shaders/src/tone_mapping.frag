@@ -0,0 +1,73 @@
+#version 450
+
+// See `OutputTransfer`
+#define OUTPUT_SRGB_FORMAT 0
+#define OUTPUT_SRGB 1
+#define OUTPUT_EXTENDED_LINEAR 2
+#define OUTPUT_PQ 3
+
+layout(location = 0) in vec2 fs_TexCoords;
+
+layout(set = 2, binding = 0) uniform sampler2D u_SceneColor;
+
+layout(push_constant) uniform ToneMappingData {
+    float exposure;
+    float paperWhiteNits;
+    float peakNits;
+    uint outputTransfer;
+}
+pc_ToneMappingData;
+
+layout(location = 0) out vec4 f_Color;
+
+// Rec. 709 primaries (the ones of sRGB and scRGB) to Rec. 2020 ones, for linear colors
+const mat3 REC709_TO_REC2020 = mat3(
+    0.6274, 0.0691, 0.0164,
+    0.3293, 0.9195, 0.0880,
+    0.0433, 0.0114, 0.8956
+);
+
+// Narkowicz's fit of the ACES filmic curve, maps [0, inf) to [0, 1]
+vec3 acesFilmic(vec3 color) {
+    return clamp((color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14), 0.0, 1.0);
+}
+
+vec3 srgbEncode(vec3 color) {
+    return mix(12.92 * color, 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, color));
+}
+
+// Inverse EOTF of SMPTE ST 2084, `nits` being absolute luminances
+vec3 pqEncode(vec3 nits) {
+    const float m1 = 0.1593017578125;
+    const float m2 = 78.84375;
+    const float c1 = 0.8359375;
+    const float c2 = 18.8515625;
+    const float c3 = 18.6875;
+
+    vec3 y = pow(clamp(nits / 10000.0, 0.0, 1.0), vec3(m1));
+    return pow((c1 + c2 * y) / (1.0 + c3 * y), vec3(m2));
+}
+
+void main() {
+    vec3 color = max(texture(u_SceneColor, fs_TexCoords).rgb * pc_ToneMappingData.exposure, 0.0);
+
+    vec3 outputColor;
+    if (pc_ToneMappingData.outputTransfer == OUTPUT_SRGB_FORMAT) {
+        outputColor = acesFilmic(color);
+    } else if (pc_ToneMappingData.outputTransfer == OUTPUT_SRGB) {
+        outputColor = srgbEncode(acesFilmic(color));
+    } else {
+        // Compressed towards the peak of the display, 1.0 being the paper white
+        float maxValue = pc_ToneMappingData.peakNits / pc_ToneMappingData.paperWhiteNits;
+        vec3 nits = color / (1.0 + color / maxValue) * pc_ToneMappingData.paperWhiteNits;
+
+        if (pc_ToneMappingData.outputTransfer == OUTPUT_EXTENDED_LINEAR) {
+            // scRGB's 1.0 is 80 nits
+            outputColor = nits / 80.0;
+        } else {
+            outputColor = pqEncode(REC709_TO_REC2020 * nits);
+        }
+    }
+
+    f_Color = vec4(outputColor, 1);
+}
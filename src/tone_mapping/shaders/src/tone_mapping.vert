@@ -0,0 +1,11 @@
+#version 450
+
+layout(location = 0) in vec3 v_Position;
+
+layout(location = 0) out vec2 fs_TexCoords;
+
+void main() {
+    // The triangle covers the whole viewport, where the texture coordinates span [0, 1]
+    fs_TexCoords = v_Position.xy * 0.5 + 0.5;
+    gl_Position = vec4(v_Position.xy, 0, 1);
+}
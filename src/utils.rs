@@ -5,6 +5,8 @@ use bevy_ecs::{prelude::Component, system::Resource};
 use bytemuck::Zeroable;
 use thiserror::Error;
 
+use crate::staging_pool::StagingPool;
+
 #[derive(Debug, Copy, Clone)]
 #[repr(C)]
 pub struct PodWrapper<T: Copy + 'static>(pub T);
@@ -47,6 +49,8 @@ pub struct CommandUploader {
     command_pool: vk::CommandPool,
     command_buffer: vk::CommandBuffer,
     fence: vk::Fence,
+    /// Staging buffers of the uploads, which are done when their immediate command returns.
+    pub(crate) staging_pool: Mutex<StagingPool>,
 }
 
 #[derive(Error, Debug)]
@@ -113,6 +117,7 @@ impl CommandUploader {
             command_pool,
             command_buffer,
             fence,
+            staging_pool: Mutex::default(),
         })
     }
 
@@ -159,6 +164,153 @@ impl CommandUploader {
     }
 }
 
+/// Submits commands without waiting for them to complete (see `ComputeShader::run_async`). Every
+/// submission signals a new semaphore, and its command buffer is reused by later submissions once
+/// it is done executing.
+#[derive(Default)]
+pub struct AsyncCommandSubmitter {
+    command_pool: vk::CommandPool,
+    /// Command buffers with the fence signaled by their last submission.
+    submissions: Vec<(vk::CommandBuffer, vk::Fence)>,
+}
+
+#[derive(Error, Debug)]
+pub enum AsyncCommandError {
+    #[error("Vulkan command buffer allocation failed with result: {0}")]
+    VulkanCommandBufferAllocationFailed(vk::Result),
+
+    #[error("Vulkan fence creation failed with result: {0}")]
+    VulkanFenceCreationFailed(vk::Result),
+
+    #[error("Vulkan semaphore creation failed with result: {0}")]
+    VulkanSemaphoreCreationFailed(vk::Result),
+
+    #[error("Vulkan command buffer begin call failed with result: {0}")]
+    VulkanCommandBufferBeginFailed(vk::Result),
+
+    #[error("Vulkan command buffer end call failed with result: {0}")]
+    VulkanCommandBufferEndFailed(vk::Result),
+
+    #[error("Vulkan command buffer submission failed with result: {0}")]
+    VulkanCommandBufferSubmissionFailed(vk::Result),
+
+    #[error("Vulkan command buffer fence reset failed with result: {0}")]
+    VulkanCommandBufferFenceResetFailed(vk::Result),
+
+    #[error("Vulkan command buffer reset failed with result: {0}")]
+    VulkanCommandBufferResetFailed(vk::Result),
+}
+
+impl AsyncCommandSubmitter {
+    pub(crate) fn new(
+        device: &ash::Device,
+        queue_index: u32,
+    ) -> Result<Self, CommandUploaderCreationError> {
+        let command_pool_info = vk::CommandPoolCreateInfo::default()
+            .queue_family_index(queue_index)
+            .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER);
+        let command_pool = unsafe { device.create_command_pool(&command_pool_info, None) }
+            .map_err(CommandUploaderCreationError::VulkanCommandPoolCreationFailed)?;
+
+        Ok(Self {
+            command_pool,
+            submissions: vec![],
+        })
+    }
+
+    /// The device must be idle.
+    pub(crate) fn destroy(self, device: &ash::Device) {
+        unsafe {
+            for (_, fence) in self.submissions {
+                device.destroy_fence(fence, None);
+            }
+            device.destroy_command_pool(self.command_pool, None);
+        };
+    }
+
+    /// Records the commands of `function` and submits them to `queue`, returning the semaphore
+    /// signaled once they complete. The caller owns the semaphore, and must destroy it once it
+    /// has been waited on.
+    pub fn submit<F>(
+        &mut self,
+        device: &ash::Device,
+        queue: vk::Queue,
+        function: F,
+    ) -> Result<vk::Semaphore, AsyncCommandError>
+    where
+        F: FnOnce(&vk::CommandBuffer),
+    {
+        let (command_buffer, fence) = self.acquire_command_buffer(device)?;
+
+        let begin_info = vk::CommandBufferBeginInfo::default()
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        unsafe { device.begin_command_buffer(command_buffer, &begin_info) }
+            .map_err(AsyncCommandError::VulkanCommandBufferBeginFailed)?;
+        function(&command_buffer);
+        unsafe { device.end_command_buffer(command_buffer) }
+            .map_err(AsyncCommandError::VulkanCommandBufferEndFailed)?;
+
+        let semaphore =
+            unsafe { device.create_semaphore(&vk::SemaphoreCreateInfo::default(), None) }
+                .map_err(AsyncCommandError::VulkanSemaphoreCreationFailed)?;
+        // Only reset right before the submission, so that the command buffer can still be reused
+        // if anything above failed
+        let submission = unsafe { device.reset_fences(std::slice::from_ref(&fence)) }
+            .map_err(AsyncCommandError::VulkanCommandBufferFenceResetFailed)
+            .and_then(|()| {
+                let submit_info = vk::SubmitInfo::default()
+                    .command_buffers(std::slice::from_ref(&command_buffer))
+                    .signal_semaphores(std::slice::from_ref(&semaphore));
+                unsafe { device.queue_submit(queue, &[submit_info], fence) }
+                    .map_err(AsyncCommandError::VulkanCommandBufferSubmissionFailed)
+            });
+        if let Err(error) = submission {
+            unsafe { device.destroy_semaphore(semaphore, None) };
+            return Err(error);
+        }
+
+        Ok(semaphore)
+    }
+
+    /// Returns a command buffer whose last submission completed, and its (signaled) fence.
+    fn acquire_command_buffer(
+        &mut self,
+        device: &ash::Device,
+    ) -> Result<(vk::CommandBuffer, vk::Fence), AsyncCommandError> {
+        let completed_submission = self
+            .submissions
+            .iter()
+            .find(|(_, fence)| unsafe { device.get_fence_status(*fence) } == Ok(true));
+        if let Some(&(command_buffer, fence)) = completed_submission {
+            unsafe {
+                device.reset_command_buffer(command_buffer, CommandBufferResetFlags::default())
+            }
+            .map_err(AsyncCommandError::VulkanCommandBufferResetFailed)?;
+
+            return Ok((command_buffer, fence));
+        }
+
+        let cmd_buffer_info = vk::CommandBufferAllocateInfo::default()
+            .command_pool(self.command_pool)
+            .level(vk::CommandBufferLevel::PRIMARY)
+            .command_buffer_count(1);
+        let command_buffer = unsafe { device.allocate_command_buffers(&cmd_buffer_info) }
+            .map_err(AsyncCommandError::VulkanCommandBufferAllocationFailed)?
+            .swap_remove(0);
+        let fence_info = vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED);
+        let fence = match unsafe { device.create_fence(&fence_info, None) } {
+            Ok(fence) => fence,
+            Err(result) => {
+                unsafe { device.free_command_buffers(self.command_pool, &[command_buffer]) };
+                return Err(AsyncCommandError::VulkanFenceCreationFailed(result));
+            }
+        };
+        self.submissions.push((command_buffer, fence));
+
+        Ok((command_buffer, fence))
+    }
+}
+
 /// Attempts to name a vulkan object using the `VK_EXT_debug_utils` extension.
 ///
 /// # Panics
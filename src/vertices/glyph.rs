@@ -0,0 +1,73 @@
+use std::mem::offset_of;
+
+use ash::vk;
+
+use crate::{
+    material::{Vertex, VertexInputDescription},
+    math_types::{Vec2, Vec3, Vec4},
+};
+
+/// Corner of a text glyph quad, sampling a signed distance field atlas.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct GlyphVertex {
+    pub position: Vec3,
+    pub texture_coords: Vec2,
+    pub color: Vec4,
+}
+
+impl Default for GlyphVertex {
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            texture_coords: Vec2::ZERO,
+            color: Vec4::ONE,
+        }
+    }
+}
+
+impl Vertex for GlyphVertex {
+    fn vertex_input_description() -> VertexInputDescription {
+        let main_binding = vk::VertexInputBindingDescription::default()
+            .binding(0)
+            .stride(
+                std::mem::size_of::<GlyphVertex>()
+                    .try_into()
+                    .expect("Unsupported architecture"),
+            )
+            .input_rate(vk::VertexInputRate::VERTEX);
+
+        let position = vk::VertexInputAttributeDescription::default()
+            .location(0)
+            .binding(0)
+            .format(vk::Format::R32G32B32_SFLOAT)
+            .offset(
+                offset_of!(GlyphVertex, position)
+                    .try_into()
+                    .expect("Unsupported architecture"),
+            );
+        let texture_coords = vk::VertexInputAttributeDescription::default()
+            .location(1)
+            .binding(0)
+            .format(vk::Format::R32G32_SFLOAT)
+            .offset(
+                offset_of!(GlyphVertex, texture_coords)
+                    .try_into()
+                    .expect("Unsupported architecture"),
+            );
+        let color = vk::VertexInputAttributeDescription::default()
+            .location(2)
+            .binding(0)
+            .format(vk::Format::R32G32B32A32_SFLOAT)
+            .offset(
+                offset_of!(GlyphVertex, color)
+                    .try_into()
+                    .expect("Unsupported architecture"),
+            );
+
+        VertexInputDescription {
+            bindings: vec![main_binding],
+            attributes: vec![position, texture_coords, color],
+        }
+    }
+}
@@ -1,18 +1,49 @@
 use ply_rs::ply;
 use thiserror::Error;
 
-use crate::mesh::{MeshDataUploadError, UploadError};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
 
+#[cfg(feature = "gltf")]
+use crate::math_types::{Vec2, Vec4};
+use crate::{
+    material::Vertex,
+    math_types::Vec3,
+    mesh::{upload_mesh_data, Aabb, LoadedModel, Mesh, MeshDataUploadError, UploadError},
+    renderer::Renderer,
+    utils::ThreadSafeRef,
+};
+
+pub mod glyph;
+pub mod line;
+pub mod point;
 pub mod simple;
+pub mod skinned;
 pub mod textured;
 
 // used by all (for now ?) vertex types for deserialization
 
 #[derive(Error, Debug)]
 pub enum VertexModelLoadingError {
+    #[error("No model file was found at path \"{0}\".")]
+    FileNotFound(String),
+
     #[error("Loading of the OBJ file failed with error: {0}.")]
     OBJLoadError(#[from] tobj::LoadError),
 
+    #[error("The model file does not contain any mesh.")]
+    EmptyModel,
+
+    #[error("The model is missing its {0} attribute, which is required by this vertex type.")]
+    MissingAttribute(&'static str),
+
+    #[error("The model references vertex {index}, but only has {vertex_count} vertices.")]
+    IndexOutOfBounds { index: u32, vertex_count: usize },
+
     #[error("Uploading of the mesh data failed with error: {0}.")]
     MeshDataUploadFailed(#[from] MeshDataUploadError),
 
@@ -21,6 +52,533 @@ pub enum VertexModelLoadingError {
 
     #[error("Uploading of the mesh data failed with error: {0}.")]
     BufferUploadFailed(#[from] UploadError),
+
+    #[cfg(feature = "gltf")]
+    #[error("Loading of the glTF file failed with error: {0}.")]
+    GltfLoadError(#[from] gltf::Error),
+
+    #[cfg(feature = "gltf")]
+    #[error("The {attribute} accessor of the glTF model uses an unsupported component type ({data_type:?}).")]
+    UnsupportedGltfAccessor {
+        attribute: &'static str,
+        data_type: gltf::accessor::DataType,
+    },
+
+    #[cfg(feature = "gltf")]
+    #[error("Creation of the skeleton of a glTF skin failed with error: {0}.")]
+    SkeletonBuildFailed(#[from] crate::components::skeleton::SkeletonBuildError),
+
+    #[cfg(feature = "gltf")]
+    #[error("The vertex type {0} cannot be loaded from glTF models, as it doesn't implement Vertex::from_gltf_attributes.")]
+    UnsupportedGltfVertexType(&'static str),
+}
+
+/// Attributes of a glTF vertex, given to [`crate::material::Vertex::from_gltf_attributes`].
+/// Optional attributes are `None` if the primitive doesn't have them.
+#[cfg(feature = "gltf")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GltfVertexAttributes {
+    pub position: Vec3,
+    pub normal: Option<Vec3>,
+    /// First set of texture coordinates (`TEXCOORD_0`), normalized ones converted to floats.
+    pub texture_coords: Option<Vec2>,
+    /// First set of vertex colors (`COLOR_0`), as RGBA (alpha is 1 for RGB colors).
+    pub color: Option<Vec4>,
+    /// First set of joint indices (`JOINTS_0`), in the skin of the mesh.
+    pub joints: Option<[u16; 4]>,
+    /// First set of joint weights (`WEIGHTS_0`), normalized ones converted to floats.
+    pub weights: Option<Vec4>,
+}
+
+/// Default tolerance used to consider two vertex attributes identical during deduplication.
+pub const DEFAULT_DEDUPLICATION_EPSILON: f32 = 1e-6;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ObjLoadOptions {
+    /// Merges the vertices whose attributes (the ones used by the vertex type) are identical,
+    /// remapping the index buffer to the shared vertices. Disabled by default, so that the loaded
+    /// vertices and indices are the ones of the file.
+    pub deduplicate_vertices: bool,
+    /// Attributes are compared after being rounded to a multiple of this value, so vertices closer
+    /// than that are merged (though two vertices straddling a rounding boundary won't be). A value
+    /// of 0 only merges bitwise identical attributes.
+    pub deduplication_epsilon: f32,
+}
+
+impl Default for ObjLoadOptions {
+    fn default() -> Self {
+        Self {
+            deduplicate_vertices: false,
+            deduplication_epsilon: DEFAULT_DEDUPLICATION_EPSILON,
+        }
+    }
+}
+
+/// Material of an OBJ submesh, read from the `.mtl` file(s) referenced by the model's `mtllib`
+/// statements (see [`crate::mesh::LoadedModel`]). Only the geometry is loaded by the renderer, the
+/// textures are left for the caller to load.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MtlMaterial {
+    /// Empty for the faces that don't use any material (or an unknown one).
+    pub name: String,
+    /// Diffuse color (`Kd`), 1 when unspecified.
+    pub diffuse: Vec3,
+    /// Specular color (`Ks`), 0 when unspecified.
+    pub specular: Vec3,
+    /// Specular exponent (`Ns`), 0 when unspecified.
+    pub shininess: f32,
+    /// Opacity (`d`, or `1 - Tr`), 1 when unspecified.
+    pub dissolve: f32,
+    /// Texture paths, resolved relatively to the OBJ file's folder.
+    pub diffuse_texture: Option<PathBuf>,
+    pub specular_texture: Option<PathBuf>,
+    pub normal_texture: Option<PathBuf>,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        Self {
+            name: String::default(),
+            diffuse: Vec3::ONE,
+            specular: Vec3::ZERO,
+            shininess: 0.0,
+            dissolve: 1.0,
+            diffuse_texture: None,
+            specular_texture: None,
+            normal_texture: None,
+        }
+    }
+}
+
+impl MtlMaterial {
+    fn from_tobj(material: tobj::Material, folder: &Path) -> Self {
+        let default = Self::default();
+        let texture_path = |texture: Option<String>| texture.map(|texture| folder.join(texture));
+
+        Self {
+            name: material.name,
+            diffuse: material.diffuse.map_or(default.diffuse, Vec3::from),
+            specular: material.specular.map_or(default.specular, Vec3::from),
+            shininess: material.shininess.unwrap_or(default.shininess),
+            dissolve: material.dissolve.unwrap_or(default.dissolve),
+            diffuse_texture: texture_path(material.diffuse_texture),
+            specular_texture: texture_path(material.specular_texture),
+            normal_texture: texture_path(material.normal_texture),
+        }
+    }
+}
+
+/// Attributes of an OBJ mesh a vertex type reads, which are the ones compared when deduplicating.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ObjVertexAttributes {
+    pub(crate) normals: bool,
+    pub(crate) texture_coords: bool,
+}
+
+/// Loads every object of an OBJ model as a single mesh.
+pub(crate) fn load_obj_mesh(
+    path: &Path,
+    options: &ObjLoadOptions,
+    attributes: ObjVertexAttributes,
+) -> Result<tobj::Mesh, VertexModelLoadingError> {
+    let (models, _) = read_obj_file(path)?;
+
+    let mut mesh = merge_obj_meshes(models.into_iter().map(|model| model.mesh));
+    if mesh.indices.is_empty() {
+        return Err(VertexModelLoadingError::EmptyModel);
+    }
+
+    if options.deduplicate_vertices {
+        deduplicate_obj_vertices(&mut mesh, options.deduplication_epsilon, attributes);
+    }
+
+    Ok(mesh)
+}
+
+/// Loads an OBJ model as one mesh per material, in the order of their first use. The faces
+/// without a material are grouped in a last mesh using [`MtlMaterial::default`].
+pub(crate) fn load_obj_submeshes(
+    path: &Path,
+    options: &ObjLoadOptions,
+    attributes: ObjVertexAttributes,
+) -> Result<Vec<(tobj::Mesh, MtlMaterial)>, VertexModelLoadingError> {
+    let (models, materials) = read_obj_file(path)?;
+
+    // `tobj` splits objects on every material change, so the models sharing a material are
+    // merged back together
+    let mut material_ids = Vec::<Option<usize>>::new();
+    let mut groups = Vec::<Vec<tobj::Mesh>>::new();
+    for model in models {
+        if model.mesh.indices.is_empty() {
+            continue;
+        }
+        let material_id = model.mesh.material_id;
+        match material_ids.iter().position(|id| *id == material_id) {
+            Some(group) => groups[group].push(model.mesh),
+            None => {
+                material_ids.push(material_id);
+                groups.push(vec![model.mesh]);
+            }
+        }
+    }
+    if groups.is_empty() {
+        return Err(VertexModelLoadingError::EmptyModel);
+    }
+
+    let folder = path.parent().unwrap_or(Path::new(""));
+    let mut submeshes = material_ids
+        .into_iter()
+        .zip(groups)
+        .map(|(material_id, meshes)| {
+            let mut mesh = merge_obj_meshes(meshes.into_iter());
+            if options.deduplicate_vertices {
+                deduplicate_obj_vertices(&mut mesh, options.deduplication_epsilon, attributes);
+            }
+            let material = material_id
+                .and_then(|id| materials.get(id).cloned())
+                .map_or_else(MtlMaterial::default, |material| {
+                    MtlMaterial::from_tobj(material, folder)
+                });
+
+            (mesh, material)
+        })
+        .collect::<Vec<_>>();
+    submeshes.sort_by_key(|(mesh, _)| mesh.material_id.is_none());
+
+    Ok(submeshes)
+}
+
+/// Triangulated models of the file, along with the materials of its `mtllib`s (empty if they
+/// can't be loaded, which is only logged).
+fn read_obj_file(
+    path: &Path,
+) -> Result<(Vec<tobj::Model>, Vec<tobj::Material>), VertexModelLoadingError> {
+    if !path.exists() {
+        return Err(VertexModelLoadingError::FileNotFound(
+            path.display().to_string(),
+        ));
+    }
+
+    let (models, materials) = tobj::load_obj(
+        path,
+        &tobj::LoadOptions {
+            triangulate: true,
+            single_index: true,
+            ..Default::default()
+        },
+    )?;
+    let materials = materials.unwrap_or_else(|error| {
+        log::warn!(
+            "Failed to load the materials of OBJ model \"{}\": {error}",
+            path.display()
+        );
+        vec![]
+    });
+
+    Ok((models, materials))
+}
+
+/// Concatenates the vertices of `meshes` and offsets their indices accordingly. The material of
+/// the first mesh is kept, and an attribute missing from any mesh is dropped from the result so
+/// that the remaining ones stay aligned with the positions.
+fn merge_obj_meshes(mut meshes: impl Iterator<Item = tobj::Mesh>) -> tobj::Mesh {
+    fn merge_attribute(
+        merged: &mut Vec<f32>,
+        merged_vertex_count: usize,
+        attribute: Vec<f32>,
+        vertex_count: usize,
+        component_count: usize,
+    ) {
+        if merged.len() == merged_vertex_count * component_count
+            && attribute.len() == vertex_count * component_count
+        {
+            merged.extend(attribute);
+        } else {
+            merged.clear();
+        }
+    }
+
+    let Some(mut merged) = meshes.next() else {
+        return tobj::Mesh::default();
+    };
+
+    for mesh in meshes {
+        let merged_vertex_count = merged.positions.len() / 3;
+        let vertex_count = mesh.positions.len() / 3;
+        let index_offset =
+            u32::try_from(merged_vertex_count).expect("Too many vertices in OBJ model");
+
+        merged.positions.extend(mesh.positions);
+        merge_attribute(
+            &mut merged.normals,
+            merged_vertex_count,
+            mesh.normals,
+            vertex_count,
+            3,
+        );
+        merge_attribute(
+            &mut merged.texcoords,
+            merged_vertex_count,
+            mesh.texcoords,
+            vertex_count,
+            2,
+        );
+        merge_attribute(
+            &mut merged.vertex_color,
+            merged_vertex_count,
+            mesh.vertex_color,
+            vertex_count,
+            3,
+        );
+        // Out of bounds indices must stay out of bounds for `validate_indices` to report them
+        merged.indices.extend(
+            mesh.indices
+                .into_iter()
+                .map(|index| index.saturating_add(index_offset)),
+        );
+    }
+
+    merged
+}
+
+/// Uploads an indexed mesh loaded from `path`, after checking its indices.
+pub(crate) fn upload_model_mesh<VertexType: Vertex>(
+    path: &Path,
+    vertices: Vec<VertexType>,
+    indices: Vec<u32>,
+    renderer: &mut Renderer,
+) -> Result<ThreadSafeRef<Mesh<VertexType>>, VertexModelLoadingError> {
+    validate_indices(&indices, vertices.len())?;
+
+    let mesh = upload_indexed_mesh(vertices, indices, renderer)?;
+    mesh.auto_name(&path.display().to_string(), renderer);
+
+    Ok(ThreadSafeRef::new(mesh))
+}
+
+/// Uploads the submeshes of an OBJ model loaded from `path`, after checking all their indices so
+/// that an invalid one doesn't leave the previous ones' buffers behind.
+pub(crate) fn upload_obj_submeshes<VertexType: Vertex>(
+    path: &Path,
+    submeshes: Vec<(Vec<VertexType>, Vec<u32>, MtlMaterial)>,
+    renderer: &mut Renderer,
+) -> Result<LoadedModel<VertexType>, VertexModelLoadingError> {
+    for (vertices, indices, _) in &submeshes {
+        validate_indices(indices, vertices.len())?;
+    }
+
+    let mut model = LoadedModel {
+        submeshes: Vec::with_capacity(submeshes.len()),
+    };
+    for (vertices, indices, material) in submeshes {
+        let mesh = match upload_indexed_mesh(vertices, indices, renderer) {
+            Ok(mesh) => mesh,
+            Err(error) => {
+                for (mesh, _) in &model.submeshes {
+                    mesh.lock().destroy(renderer);
+                }
+                return Err(error);
+            }
+        };
+        let material_name = if material.name.is_empty() {
+            "no material"
+        } else {
+            material.name.as_str()
+        };
+        mesh.auto_name(&format!("{} ({material_name})", path.display()), renderer);
+
+        model.submeshes.push((ThreadSafeRef::new(mesh), material));
+    }
+
+    Ok(model)
+}
+
+fn upload_indexed_mesh<VertexType: Vertex>(
+    vertices: Vec<VertexType>,
+    indices: Vec<u32>,
+    renderer: &mut Renderer,
+) -> Result<Mesh<VertexType>, VertexModelLoadingError> {
+    let upload_result = upload_mesh_data(&vertices, &indices, renderer)?;
+
+    Ok(Mesh {
+        aabb: Aabb::from_vertices(&vertices),
+        vertices,
+        indices: Some(indices),
+        vertex_buffer: upload_result.vertex_buffer,
+        index_buffer: Some(upload_result.index_buffer),
+    })
+}
+
+fn quantize_attribute(value: f32, epsilon: f32) -> u32 {
+    let value = if epsilon > 0.0 {
+        (value / epsilon).round() * epsilon
+    } else {
+        value
+    };
+
+    // Adding 0 turns -0 into +0, so that they are considered identical
+    (value + 0.0).to_bits()
+}
+
+/// `tobj`'s `single_index` only merges vertices sharing the same position, normal and texture
+/// coordinates indices, this also merges the ones whose attribute values are identical.
+fn deduplicate_obj_vertices(mesh: &mut tobj::Mesh, epsilon: f32, attributes: ObjVertexAttributes) {
+    let vertex_count = mesh.positions.len() / 3;
+    let has_normals = attributes.normals && mesh.normals.len() >= vertex_count * 3;
+    let has_texture_coords = attributes.texture_coords && mesh.texcoords.len() >= vertex_count * 2;
+
+    let mut unique_vertices = HashMap::<Vec<u32>, u32>::with_capacity(vertex_count);
+    let mut remapped_indices = Vec::with_capacity(vertex_count);
+    let mut positions = Vec::with_capacity(mesh.positions.len());
+    let mut normals = Vec::with_capacity(mesh.normals.len());
+    let mut texcoords = Vec::with_capacity(mesh.texcoords.len());
+    for vertex in 0..vertex_count {
+        let mut key = Vec::with_capacity(8);
+        let position = &mesh.positions[vertex * 3..vertex * 3 + 3];
+        key.extend(
+            position
+                .iter()
+                .map(|value| quantize_attribute(*value, epsilon)),
+        );
+        if has_normals {
+            let normal = &mesh.normals[vertex * 3..vertex * 3 + 3];
+            key.extend(
+                normal
+                    .iter()
+                    .map(|value| quantize_attribute(*value, epsilon)),
+            );
+        }
+        if has_texture_coords {
+            let texture_coords = &mesh.texcoords[vertex * 2..vertex * 2 + 2];
+            key.extend(
+                texture_coords
+                    .iter()
+                    .map(|value| quantize_attribute(*value, epsilon)),
+            );
+        }
+
+        let new_index = *unique_vertices.entry(key).or_insert_with(|| {
+            positions.extend_from_slice(position);
+            if let Some(normal) = mesh.normals.get(vertex * 3..vertex * 3 + 3) {
+                normals.extend_from_slice(normal);
+            }
+            if let Some(texture_coords) = mesh.texcoords.get(vertex * 2..vertex * 2 + 2) {
+                texcoords.extend_from_slice(texture_coords);
+            }
+
+            u32::try_from(positions.len() / 3 - 1).expect("Too many vertices in OBJ mesh")
+        });
+        remapped_indices.push(new_index);
+    }
+
+    log::debug!(
+        "Deduplicated OBJ mesh from {} to {} vertices",
+        vertex_count,
+        positions.len() / 3
+    );
+
+    // Out of bounds indices are kept as is for `validate_indices` to report them
+    for index in &mut mesh.indices {
+        if let Some(new_index) = usize::try_from(*index)
+            .ok()
+            .and_then(|index| remapped_indices.get(index))
+        {
+            *index = *new_index;
+        }
+    }
+    mesh.positions = positions;
+    mesh.normals = normals;
+    mesh.texcoords = texcoords;
+}
+
+pub(crate) fn open_ply_file(path: &Path) -> Result<BufReader<File>, VertexModelLoadingError> {
+    let file = File::open(path).map_err(|error| match error.kind() {
+        std::io::ErrorKind::NotFound => {
+            VertexModelLoadingError::FileNotFound(path.display().to_string())
+        }
+        _ => VertexModelLoadingError::FileReadingError(error),
+    })?;
+
+    Ok(BufReader::new(file))
+}
+
+/// Malformed files can reference vertices that don't exist, which would make the GPU read out of
+/// the vertex buffer's bounds.
+pub(crate) fn validate_indices(
+    indices: &[u32],
+    vertex_count: usize,
+) -> Result<(), VertexModelLoadingError> {
+    match indices
+        .iter()
+        .find(|index| usize::try_from(**index).map_or(true, |index| index >= vertex_count))
+    {
+        Some(index) => Err(VertexModelLoadingError::IndexOutOfBounds {
+            index: *index,
+            vertex_count,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// The `gltf` crate's readers trust the accessors' component types, so the ones the loader reads
+/// are checked against the types the specification allows for them first.
+#[cfg(feature = "gltf")]
+pub(crate) fn validate_gltf_accessors(
+    primitive: &gltf::Primitive,
+) -> Result<(), VertexModelLoadingError> {
+    use gltf::{accessor::DataType, Semantic};
+
+    fn check(
+        attribute: &'static str,
+        accessor: Option<gltf::Accessor>,
+        supported: &[DataType],
+    ) -> Result<(), VertexModelLoadingError> {
+        match accessor {
+            Some(accessor) if !supported.contains(&accessor.data_type()) => {
+                Err(VertexModelLoadingError::UnsupportedGltfAccessor {
+                    attribute,
+                    data_type: accessor.data_type(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    check(
+        "POSITION",
+        primitive.get(&Semantic::Positions),
+        &[DataType::F32],
+    )?;
+    check(
+        "NORMAL",
+        primitive.get(&Semantic::Normals),
+        &[DataType::F32],
+    )?;
+    check(
+        "TEXCOORD_0",
+        primitive.get(&Semantic::TexCoords(0)),
+        &[DataType::F32, DataType::U8, DataType::U16],
+    )?;
+    check(
+        "COLOR_0",
+        primitive.get(&Semantic::Colors(0)),
+        &[DataType::F32, DataType::U8, DataType::U16],
+    )?;
+    check(
+        "JOINTS_0",
+        primitive.get(&Semantic::Joints(0)),
+        &[DataType::U8, DataType::U16],
+    )?;
+    check(
+        "WEIGHTS_0",
+        primitive.get(&Semantic::Weights(0)),
+        &[DataType::F32, DataType::U8, DataType::U16],
+    )?;
+    check(
+        "indices",
+        primitive.indices(),
+        &[DataType::U8, DataType::U16, DataType::U32],
+    )
 }
 
 pub(crate) struct Face {
@@ -43,3 +601,79 @@ impl ply::PropertyAccess for Face {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cube whose faces have their own 4 vertices, with the face's normal, as exported by most
+    /// modeling tools.
+    fn split_faces_cube() -> tobj::Mesh {
+        let mut mesh = tobj::Mesh::default();
+        for axis in 0..3 {
+            for sign in [-1.0, 1.0] {
+                let first_vertex = u32::try_from(mesh.positions.len() / 3).unwrap();
+                for corner in [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]] {
+                    let mut position = [0.0; 3];
+                    let mut normal = [0.0; 3];
+                    position[axis] = sign;
+                    normal[axis] = sign;
+                    position[(axis + 1) % 3] = corner[0];
+                    position[(axis + 2) % 3] = corner[1];
+                    mesh.positions.extend_from_slice(&position);
+                    mesh.normals.extend_from_slice(&normal);
+                }
+                mesh.indices
+                    .extend([0, 1, 2, 0, 2, 3].iter().map(|index| first_vertex + index));
+            }
+        }
+
+        mesh
+    }
+
+    fn position(mesh: &tobj::Mesh, index: u32) -> &[f32] {
+        let index = usize::try_from(index).unwrap();
+        &mesh.positions[index * 3..index * 3 + 3]
+    }
+
+    #[test]
+    fn cube_positions_deduplicate_to_corners() {
+        let original = split_faces_cube();
+        let mut mesh = original.clone();
+        assert_eq!(mesh.positions.len() / 3, 24);
+
+        deduplicate_obj_vertices(
+            &mut mesh,
+            1e-5,
+            ObjVertexAttributes {
+                normals: false,
+                texture_coords: false,
+            },
+        );
+
+        assert_eq!(mesh.positions.len() / 3, 8);
+        assert_eq!(mesh.indices.len(), original.indices.len());
+        for (index, original_index) in mesh.indices.iter().zip(&original.indices) {
+            assert_eq!(
+                position(&mesh, *index),
+                position(&original, *original_index)
+            );
+        }
+    }
+
+    #[test]
+    fn cube_keeps_vertices_with_distinct_normals() {
+        let mut mesh = split_faces_cube();
+
+        deduplicate_obj_vertices(
+            &mut mesh,
+            1e-5,
+            ObjVertexAttributes {
+                normals: true,
+                texture_coords: false,
+            },
+        );
+
+        assert_eq!(mesh.positions.len() / 3, 24);
+    }
+}
@@ -0,0 +1,73 @@
+use std::mem::offset_of;
+
+use ash::vk;
+
+use crate::{
+    material::{Vertex, VertexInputDescription},
+    math_types::{Vec3, Vec4},
+};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PointVertex {
+    pub position: Vec3,
+    pub color: Vec4,
+    /// Size of the point in pixels, clamped to the device's `point_size_range`.
+    pub size: f32,
+}
+
+impl Default for PointVertex {
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            color: Vec4::ONE,
+            size: 1.0,
+        }
+    }
+}
+
+impl Vertex for PointVertex {
+    fn vertex_input_description() -> VertexInputDescription {
+        let main_binding = vk::VertexInputBindingDescription::default()
+            .binding(0)
+            .stride(
+                std::mem::size_of::<PointVertex>()
+                    .try_into()
+                    .expect("Unsupported architecture"),
+            )
+            .input_rate(vk::VertexInputRate::VERTEX);
+
+        let position = vk::VertexInputAttributeDescription::default()
+            .location(0)
+            .binding(0)
+            .format(vk::Format::R32G32B32_SFLOAT)
+            .offset(
+                offset_of!(PointVertex, position)
+                    .try_into()
+                    .expect("Unsupported architecture"),
+            );
+        let color = vk::VertexInputAttributeDescription::default()
+            .location(1)
+            .binding(0)
+            .format(vk::Format::R32G32B32A32_SFLOAT)
+            .offset(
+                offset_of!(PointVertex, color)
+                    .try_into()
+                    .expect("Unsupported architecture"),
+            );
+        let size = vk::VertexInputAttributeDescription::default()
+            .location(2)
+            .binding(0)
+            .format(vk::Format::R32_SFLOAT)
+            .offset(
+                offset_of!(PointVertex, size)
+                    .try_into()
+                    .expect("Unsupported architecture"),
+            );
+
+        VertexInputDescription {
+            bindings: vec![main_binding],
+            attributes: vec![position, color, size],
+        }
+    }
+}
@@ -6,12 +6,15 @@ use ply_rs::{parser, ply};
 use crate::{
     material::{Vertex, VertexInputDescription},
     math_types::Vec3,
-    mesh::{upload_index_buffer, upload_mesh_data, upload_vertex_buffer, Mesh},
+    mesh::{upload_index_buffer, upload_vertex_buffer, Aabb, LoadedModel, Mesh},
     renderer::Renderer,
     utils::ThreadSafeRef,
 };
 
-use super::{Face, VertexModelLoadingError};
+use super::{
+    load_obj_mesh, load_obj_submeshes, open_ply_file, upload_model_mesh, upload_obj_submeshes,
+    validate_indices, Face, ObjLoadOptions, ObjVertexAttributes, VertexModelLoadingError,
+};
 
 #[repr(C)]
 #[derive(Debug, Default)]
@@ -45,6 +48,15 @@ impl Vertex for SimpleVertex {
             attributes: vec![position],
         }
     }
+
+    #[cfg(feature = "gltf")]
+    fn from_gltf_attributes(
+        attributes: &super::GltfVertexAttributes,
+    ) -> Result<Self, VertexModelLoadingError> {
+        Ok(Self {
+            position: attributes.position,
+        })
+    }
 }
 
 impl ply::PropertyAccess for SimpleVertex {
@@ -67,50 +79,63 @@ impl ply::PropertyAccess for SimpleVertex {
 
 #[profiling::all_functions]
 impl SimpleVertex {
+    /// Loads an OBJ model with the default [`ObjLoadOptions`], which don't deduplicate its vertices.
     pub fn load_model_from_path_obj(
         path: &std::path::Path,
         renderer: &mut Renderer,
     ) -> Result<ThreadSafeRef<Mesh<Self>>, VertexModelLoadingError> {
-        let (load_result, _) = tobj::load_obj(
-            path,
-            &tobj::LoadOptions {
-                triangulate: true,
-                single_index: true,
-                ..Default::default()
-            },
-        )?;
+        Self::load_model_from_path_obj_with_options(path, &ObjLoadOptions::default(), renderer)
+    }
 
-        let mesh = &load_result[0].mesh;
+    pub fn load_model_from_path_obj_with_options(
+        path: &std::path::Path,
+        options: &ObjLoadOptions,
+        renderer: &mut Renderer,
+    ) -> Result<ThreadSafeRef<Mesh<Self>>, VertexModelLoadingError> {
+        let mesh = load_obj_mesh(path, options, Self::OBJ_ATTRIBUTES)?;
 
-        let positions = mesh
-            .positions
-            .chunks_exact(3)
-            .map(|slice| Vec3::new(slice[0], slice[1], slice[2]))
-            .collect::<Vec<Vec3>>();
+        upload_model_mesh(
+            path,
+            Self::vertices_from_obj_mesh(&mesh),
+            mesh.indices,
+            renderer,
+        )
+    }
 
-        let mut vertices = Vec::with_capacity(positions.len());
-        for position in positions {
-            vertices.push(SimpleVertex { position });
-        }
+    /// Loads an OBJ model as one mesh per material of its `.mtl` files, instead of a single mesh
+    /// like [`SimpleVertex::load_model_from_path_obj_with_options`] does.
+    pub fn load_model_from_path_obj_with_materials(
+        path: &std::path::Path,
+        options: &ObjLoadOptions,
+        renderer: &mut Renderer,
+    ) -> Result<LoadedModel<Self>, VertexModelLoadingError> {
+        let submeshes = load_obj_submeshes(path, options, Self::OBJ_ATTRIBUTES)?
+            .into_iter()
+            .map(|(mesh, material)| (Self::vertices_from_obj_mesh(&mesh), mesh.indices, material))
+            .collect();
 
-        let indices = mesh.indices.clone();
+        upload_obj_submeshes(path, submeshes, renderer)
+    }
 
-        let upload_result = upload_mesh_data(&vertices, &indices, renderer)?;
+    const OBJ_ATTRIBUTES: ObjVertexAttributes = ObjVertexAttributes {
+        normals: false,
+        texture_coords: false,
+    };
 
-        Ok(ThreadSafeRef::new(Mesh::<Self> {
-            vertices,
-            indices: Some(indices),
-            vertex_buffer: upload_result.vertex_buffer,
-            index_buffer: Some(upload_result.index_buffer),
-        }))
+    fn vertices_from_obj_mesh(mesh: &tobj::Mesh) -> Vec<Self> {
+        mesh.positions
+            .chunks_exact(3)
+            .map(|slice| SimpleVertex {
+                position: Vec3::new(slice[0], slice[1], slice[2]),
+            })
+            .collect()
     }
 
     pub fn load_model_from_path_ply(
         path: &std::path::Path,
         renderer: &mut Renderer,
     ) -> Result<ThreadSafeRef<Mesh<Self>>, VertexModelLoadingError> {
-        let file = std::fs::File::open(path)?;
-        let mut file = std::io::BufReader::new(file);
+        let mut file = open_ply_file(path)?;
 
         let vertex_parser = parser::Parser::<Self>::new();
         let face_parser = parser::Parser::<Face>::new();
@@ -133,19 +158,24 @@ impl SimpleVertex {
             }
         }
 
-        let vertex_buffer = upload_vertex_buffer(&vertices, renderer)?;
-
         let mut indices = Vec::with_capacity(faces.len() * 3);
         for face in faces {
             indices.extend(face.indices.iter());
         }
+        validate_indices(&indices, vertices.len())?;
+
+        let vertex_buffer = upload_vertex_buffer(&vertices, renderer)?;
         let index_buffer = upload_index_buffer(&indices, renderer)?;
 
-        Ok(ThreadSafeRef::new(Mesh::<Self> {
+        let mesh = Mesh::<Self> {
+            aabb: Aabb::from_vertices(&vertices),
             vertices,
             indices: Some(indices),
             vertex_buffer,
             index_buffer: Some(index_buffer),
-        }))
+        };
+        mesh.auto_name(&path.display().to_string(), renderer);
+
+        Ok(ThreadSafeRef::new(mesh))
     }
 }
@@ -0,0 +1,118 @@
+use std::mem::offset_of;
+
+use ash::vk;
+
+use crate::{
+    material::{Vertex, VertexInputDescription},
+    math_types::{Vec2, Vec3, Vec4},
+};
+
+#[cfg(feature = "gltf")]
+use super::VertexModelLoadingError;
+
+/// Vertex deformed by up to four joints of a [`crate::components::skeleton::Skeleton`]. Shaders
+/// read the joints as a `uvec4` at location 3 and their weights as a `vec4` at location 4, and
+/// blend the skeleton's joint matrices with them (see
+/// [`crate::components::skeleton::Skeleton::write_joint_matrices`]):
+/// ```glsl
+/// mat4 skin_matrix = weights.x * joints[joint_indices.x] + weights.y * joints[joint_indices.y]
+///     + weights.z * joints[joint_indices.z] + weights.w * joints[joint_indices.w];
+/// ```
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SkinnedVertex {
+    pub position: Vec3,
+    pub normal: Vec3,
+    pub texture_coords: Vec2,
+    /// Weights of the joints, which should add up to 1.
+    pub weights: Vec4,
+    /// Indices of the joints in the skeleton.
+    pub joints: [u16; 4],
+}
+
+impl Vertex for SkinnedVertex {
+    fn vertex_input_description() -> VertexInputDescription {
+        let main_binding = vk::VertexInputBindingDescription::default()
+            .binding(0)
+            .stride(
+                std::mem::size_of::<SkinnedVertex>()
+                    .try_into()
+                    .expect("Unsupported architecture"),
+            )
+            .input_rate(vk::VertexInputRate::VERTEX);
+
+        let position = vk::VertexInputAttributeDescription::default()
+            .location(0)
+            .binding(0)
+            .format(vk::Format::R32G32B32_SFLOAT)
+            .offset(
+                offset_of!(SkinnedVertex, position)
+                    .try_into()
+                    .expect("Unsupported architecture"),
+            );
+        let normal = vk::VertexInputAttributeDescription::default()
+            .location(1)
+            .binding(0)
+            .format(vk::Format::R32G32B32_SFLOAT)
+            .offset(
+                offset_of!(SkinnedVertex, normal)
+                    .try_into()
+                    .expect("Unsupported architecture"),
+            );
+        let texture_coords = vk::VertexInputAttributeDescription::default()
+            .location(2)
+            .binding(0)
+            .format(vk::Format::R32G32_SFLOAT)
+            .offset(
+                offset_of!(SkinnedVertex, texture_coords)
+                    .try_into()
+                    .expect("Unsupported architecture"),
+            );
+        let joints = vk::VertexInputAttributeDescription::default()
+            .location(3)
+            .binding(0)
+            .format(vk::Format::R16G16B16A16_UINT)
+            .offset(
+                offset_of!(SkinnedVertex, joints)
+                    .try_into()
+                    .expect("Unsupported architecture"),
+            );
+        let weights = vk::VertexInputAttributeDescription::default()
+            .location(4)
+            .binding(0)
+            .format(vk::Format::R32G32B32A32_SFLOAT)
+            .offset(
+                offset_of!(SkinnedVertex, weights)
+                    .try_into()
+                    .expect("Unsupported architecture"),
+            );
+
+        VertexInputDescription {
+            bindings: vec![main_binding],
+            attributes: vec![position, normal, texture_coords, joints, weights],
+        }
+    }
+
+    fn normal(&self) -> Option<Vec3> {
+        Some(self.normal)
+    }
+
+    #[cfg(feature = "gltf")]
+    fn from_gltf_attributes(
+        attributes: &super::GltfVertexAttributes,
+    ) -> Result<Self, VertexModelLoadingError> {
+        Ok(Self {
+            position: attributes.position,
+            normal: attributes
+                .normal
+                .ok_or(VertexModelLoadingError::MissingAttribute("normal"))?,
+            texture_coords: attributes.texture_coords.unwrap_or_default(),
+            weights: attributes
+                .weights
+                .ok_or(VertexModelLoadingError::MissingAttribute("joint weights"))?,
+            joints: attributes
+                .joints
+                .ok_or(VertexModelLoadingError::MissingAttribute("joints"))?,
+        })
+    }
+}
@@ -5,14 +5,17 @@ use ash::vk;
 use crate::{
     material::{Vertex, VertexInputDescription},
     math_types::{Vec2, Vec3},
-    mesh::{upload_index_buffer, upload_mesh_data, upload_vertex_buffer, Mesh},
+    mesh::{upload_index_buffer, upload_vertex_buffer, Aabb, LoadedModel, Mesh},
     renderer::Renderer,
     utils::ThreadSafeRef,
 };
 
 use ply_rs::{parser, ply};
 
-use super::{Face, VertexModelLoadingError};
+use super::{
+    load_obj_mesh, load_obj_submeshes, open_ply_file, upload_model_mesh, upload_obj_submeshes,
+    validate_indices, Face, ObjLoadOptions, ObjVertexAttributes, VertexModelLoadingError,
+};
 
 #[repr(C)]
 #[derive(Debug, Default)]
@@ -68,6 +71,25 @@ impl Vertex for TexturedVertex {
             attributes: vec![position, normal, texture_coords],
         }
     }
+
+    fn normal(&self) -> Option<Vec3> {
+        Some(self.normal)
+    }
+
+    #[cfg(feature = "gltf")]
+    fn from_gltf_attributes(
+        attributes: &super::GltfVertexAttributes,
+    ) -> Result<Self, VertexModelLoadingError> {
+        Ok(Self {
+            position: attributes.position,
+            normal: attributes
+                .normal
+                .ok_or(VertexModelLoadingError::MissingAttribute("normal"))?,
+            texture_coords: attributes.texture_coords.ok_or(
+                VertexModelLoadingError::MissingAttribute("texture coordinates"),
+            )?,
+        })
+    }
 }
 
 impl ply::PropertyAccess for TexturedVertex {
@@ -97,21 +119,52 @@ impl ply::PropertyAccess for TexturedVertex {
 
 #[profiling::all_functions]
 impl TexturedVertex {
+    /// Loads an OBJ model with the default [`ObjLoadOptions`], which don't deduplicate its vertices.
     pub fn load_model_from_path_obj(
         path: &std::path::Path,
         renderer: &mut Renderer,
     ) -> Result<ThreadSafeRef<Mesh<Self>>, VertexModelLoadingError> {
-        let (load_result, _) = tobj::load_obj(
+        Self::load_model_from_path_obj_with_options(path, &ObjLoadOptions::default(), renderer)
+    }
+
+    pub fn load_model_from_path_obj_with_options(
+        path: &std::path::Path,
+        options: &ObjLoadOptions,
+        renderer: &mut Renderer,
+    ) -> Result<ThreadSafeRef<Mesh<Self>>, VertexModelLoadingError> {
+        let mesh = load_obj_mesh(path, options, Self::OBJ_ATTRIBUTES)?;
+
+        upload_model_mesh(
             path,
-            &tobj::LoadOptions {
-                triangulate: true,
-                single_index: true,
-                ..Default::default()
-            },
-        )?;
+            Self::vertices_from_obj_mesh(&mesh)?,
+            mesh.indices,
+            renderer,
+        )
+    }
+
+    /// Loads an OBJ model as one mesh per material of its `.mtl` files, instead of a single mesh
+    /// like [`TexturedVertex::load_model_from_path_obj_with_options`] does.
+    pub fn load_model_from_path_obj_with_materials(
+        path: &std::path::Path,
+        options: &ObjLoadOptions,
+        renderer: &mut Renderer,
+    ) -> Result<LoadedModel<Self>, VertexModelLoadingError> {
+        let submeshes = load_obj_submeshes(path, options, Self::OBJ_ATTRIBUTES)?
+            .into_iter()
+            .map(|(mesh, material)| {
+                Ok((Self::vertices_from_obj_mesh(&mesh)?, mesh.indices, material))
+            })
+            .collect::<Result<Vec<_>, VertexModelLoadingError>>()?;
+
+        upload_obj_submeshes(path, submeshes, renderer)
+    }
 
-        let mesh = &load_result[0].mesh;
+    const OBJ_ATTRIBUTES: ObjVertexAttributes = ObjVertexAttributes {
+        normals: true,
+        texture_coords: true,
+    };
 
+    fn vertices_from_obj_mesh(mesh: &tobj::Mesh) -> Result<Vec<Self>, VertexModelLoadingError> {
         let positions = mesh
             .positions
             .chunks_exact(3)
@@ -128,6 +181,15 @@ impl TexturedVertex {
             .map(|slice| Vec2::new(slice[0], slice[1]))
             .collect::<Vec<Vec2>>();
 
+        if normals.len() < positions.len() {
+            return Err(VertexModelLoadingError::MissingAttribute("normal"));
+        }
+        if texture_coordinates.len() < positions.len() {
+            return Err(VertexModelLoadingError::MissingAttribute(
+                "texture coordinates",
+            ));
+        }
+
         let mut vertices = Vec::with_capacity(positions.len());
         for index in 0..positions.len() {
             vertices.push(TexturedVertex {
@@ -137,24 +199,14 @@ impl TexturedVertex {
             });
         }
 
-        let indices = mesh.indices.clone();
-
-        let upload_result = upload_mesh_data(&vertices, &indices, renderer)?;
-
-        Ok(ThreadSafeRef::new(Mesh::<Self> {
-            vertices,
-            indices: Some(indices),
-            vertex_buffer: upload_result.vertex_buffer,
-            index_buffer: Some(upload_result.index_buffer),
-        }))
+        Ok(vertices)
     }
 
     pub fn load_model_from_path_ply(
         path: &std::path::Path,
         renderer: &mut Renderer,
     ) -> Result<ThreadSafeRef<Mesh<Self>>, VertexModelLoadingError> {
-        let file = std::fs::File::open(path)?;
-        let mut file = std::io::BufReader::new(file);
+        let mut file = open_ply_file(path)?;
 
         let vertex_parser = parser::Parser::<Self>::new();
         let face_parser = parser::Parser::<Face>::new();
@@ -177,19 +229,24 @@ impl TexturedVertex {
             }
         }
 
-        let vertex_buffer = upload_vertex_buffer(&vertices, renderer)?;
-
         let mut indices = Vec::with_capacity(faces.len() * 3);
         for face in faces {
             indices.extend(face.indices.iter());
         }
+        validate_indices(&indices, vertices.len())?;
+
+        let vertex_buffer = upload_vertex_buffer(&vertices, renderer)?;
         let index_buffer = upload_index_buffer(&indices, renderer)?;
 
-        Ok(ThreadSafeRef::new(Mesh::<Self> {
+        let mesh = Mesh::<Self> {
+            aabb: Aabb::from_vertices(&vertices),
             vertices,
             indices: Some(indices),
             vertex_buffer,
             index_buffer: Some(index_buffer),
-        }))
+        };
+        mesh.auto_name(&path.display().to_string(), renderer);
+
+        Ok(ThreadSafeRef::new(mesh))
     }
 }